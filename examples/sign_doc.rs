@@ -1,7 +1,7 @@
-use cryptographic_message_syntax::SignerBuilder;
-use pdf_signing::{PDFSigningDocument, UserSignatureInfo};
+use pdf_signing::{DigestAlgorithm, PDFSigningDocument, SigningOptions, UserSignatureInfo};
+use std::sync::Arc;
 use std::{fs::File, io::Write};
-use x509_certificate::{CapturedX509Certificate, InMemorySigningKeyPair};
+use x509_certificate::{CapturedX509Certificate, InMemorySigningKeyPair, KeyInfoSigner};
 
 fn main() {
     // let pdf_file_name = "test-small-1sig.pdf";
@@ -13,62 +13,72 @@ fn main() {
     let cert = std::fs::read_to_string("./examples/assets/pdf_cert.crt").unwrap();
     let x509_cert = CapturedX509Certificate::from_pem(cert).unwrap();
     let private_key_data = std::fs::read_to_string("./examples/assets/pkcs8.pem").unwrap();
-    let private_key = InMemorySigningKeyPair::from_pkcs8_pem(&private_key_data).unwrap();
-    let signer = SignerBuilder::new(&private_key, x509_cert);
-    // Try using a time server. If it fails we continue without it.
-    // Alternative time servers:
+    let private_key: Arc<dyn KeyInfoSigner + Send + Sync> =
+        Arc::new(InMemorySigningKeyPair::from_pkcs8_pem(&private_key_data).unwrap());
+    // A time server can be added with `.time_stamp_url(...)`, for example:
     // 1: https://freetsa.org/tsr
     // 2: http://timestamp.digicert.com
-    // let signer_fallback = signer.clone();
-    // let signer = signer
-    //     .time_stamp_url("http://timestamp.digicert.com")
-    //     .or::<reqwest::Error>(Ok(signer_fallback))
-    //     .expect("Can not happen because of fallback.");
+    let signing_options = SigningOptions::builder()
+        .digest_algorithm(DigestAlgorithm::Sha256)
+        .build();
+
+    // The images are shared between the users, they are not copied.
+    let sig1: Arc<[u8]> = std::fs::read("./examples/assets/sig1.png").unwrap().into();
+    let sig2: Arc<[u8]> = std::fs::read("./examples/assets/sig2.png").unwrap().into();
+    let sig3: Arc<[u8]> = std::fs::read("./examples/assets/sig3.png").unwrap().into();
 
     let users_signature_info = vec![
         UserSignatureInfo {
             user_id: "9".to_owned(),
             user_name: "Alice".to_owned(),
             user_email: "alice@test.com".to_owned(),
-            user_signature: std::fs::read("./examples/assets/sig1.png").unwrap(),
-            user_signing_keys: signer.clone(),
+            user_signature: sig1.clone(),
+            user_signing_keys: private_key.clone(),
+            user_certificate: x509_cert.clone(),
         },
         UserSignatureInfo {
             user_id: "256".to_owned(),
             user_name: "Bob".to_owned(),
             user_email: "bob@test.com".to_owned(),
-            user_signature: std::fs::read("./examples/assets/sig2.png").unwrap(),
-            user_signing_keys: signer.clone(),
+            user_signature: sig2.clone(),
+            user_signing_keys: private_key.clone(),
+            user_certificate: x509_cert.clone(),
         },
         UserSignatureInfo {
             user_id: "272".to_owned(),
             user_name: "Charlie".to_owned(),
             user_email: "charlie@test.com".to_owned(),
-            user_signature: std::fs::read("./examples/assets/sig1.png").unwrap(),
-            user_signing_keys: signer.clone(),
+            user_signature: sig1.clone(),
+            user_signing_keys: private_key.clone(),
+            user_certificate: x509_cert.clone(),
         },
         UserSignatureInfo {
             user_id: "292".to_owned(),
             user_name: "Dave".to_owned(),
             user_email: "dave@test.com".to_owned(),
-            user_signature: std::fs::read("./examples/assets/sig3.png").unwrap(),
-            user_signing_keys: signer.clone(),
+            user_signature: sig3.clone(),
+            user_signing_keys: private_key.clone(),
+            user_certificate: x509_cert.clone(),
         },
         UserSignatureInfo {
             user_id: "274".to_owned(),
             user_name: "Ester".to_owned(),
             user_email: "ester@test.com".to_owned(),
-            user_signature: std::fs::read("./examples/assets/sig2.png").unwrap(),
-            user_signing_keys: signer.clone(),
+            user_signature: sig2.clone(),
+            user_signing_keys: private_key.clone(),
+            user_certificate: x509_cert.clone(),
         },
     ];
 
     let mut pdf_signing_document =
         PDFSigningDocument::read_from(&*pdf_data, pdf_file_name.to_owned()).unwrap();
-    let pdf_file_data = pdf_signing_document
-        .sign_document(users_signature_info)
+    let signing_result = pdf_signing_document
+        .sign(&users_signature_info, &signing_options)
         .unwrap();
+    for field in signing_result.fields {
+        println!("{:?}: {:?}", field.field_name, field.action);
+    }
 
     let mut pdf_file = File::create("./examples/result.pdf").unwrap();
-    pdf_file.write_all(&pdf_file_data).unwrap();
+    pdf_file.write_all(&signing_result.document).unwrap();
 }
@@ -11,6 +11,8 @@ use lopdf::{Document, Object, ObjectId};
 pub(crate) struct AcroForm {
     object_id: Option<ObjectId>,
     partial_field_name: Option<String>,
+    /// The partial field names of all parents and this field, separated by `.`.
+    fully_qualified_field_name: Option<String>,
     alternate_field_name: Option<String>,
 
     form_component: FormComponent,
@@ -84,10 +86,15 @@ struct InheritableFields {
     /// - `Ch` (Choice)
     /// - `Sig` (Signature) (PDF 1.3)
     ft: Option<String>,
+    /// The fully qualified field name of the parent.
+    parent_name: Option<String>,
+    /// The number of parents of the fields in the list.
+    depth: usize,
 }
 
 impl AcroForm {
-    pub(crate) fn load_all_forms(raw_doc: &Document) -> Result<Vec<Self>, Error> {
+    /// Load the fields of the form, `max_depth` limits the depth of the field hierarchy.
+    pub(crate) fn load_all_forms(raw_doc: &Document, max_depth: usize) -> Result<Vec<Self>, Error> {
         // Help with pdf structure:
         // https://pdfux.com/inspect-pdf/
         // Structure of pdf:
@@ -105,7 +112,7 @@ impl AcroForm {
 
         // Check if document has forms.
         if !root.has(b"AcroForm") {
-            log::info!("Document does not contain any forms.");
+            tracing::info!("Document does not contain any forms.");
             return Ok(vec![]);
         }
         // Get `AcroForm` node
@@ -115,7 +122,7 @@ impl AcroForm {
 
         // Fields can be a hierarchy, so need to be parsed this way.
         let empty_inherit_root = InheritableFields::default();
-        Self::load_field_list(raw_doc, fields_list, empty_inherit_root)
+        Self::load_field_list(raw_doc, fields_list, empty_inherit_root, max_depth)
     }
 
     /// For an AcroForm find a reference to the page it is on.
@@ -153,10 +160,33 @@ impl AcroForm {
         }
     }
 
+    /// Get the widget annotations of this field.
+    /// When the field has no `Kids` the field and widget are merged into one dictionary.
+    pub(crate) fn get_widget_ids(&self, raw_doc: &Document) -> Result<Vec<ObjectId>, Error> {
+        let self_object_id = self
+            .object_id
+            .ok_or_else(|| Error::from("AcroForm object is not a indirect reference."))?;
+
+        match self.get_kids(raw_doc)? {
+            Some(kids) => {
+                let mut widget_ids = vec![];
+                for kid in kids {
+                    widget_ids.push(kid.as_reference()?);
+                }
+                Ok(widget_ids)
+            }
+            None => Ok(vec![self_object_id]),
+        }
+    }
+
     pub(crate) fn get_partial_field_name(&self) -> Option<&str> {
         self.partial_field_name.as_deref()
     }
 
+    pub(crate) fn get_fully_qualified_field_name(&self) -> Option<&str> {
+        self.fully_qualified_field_name.as_deref()
+    }
+
     pub(crate) fn get_alternate_field_name(&self) -> Option<&str> {
         self.alternate_field_name.as_deref()
     }
@@ -180,17 +210,51 @@ impl AcroForm {
         raw_doc: &Document,
         list: &[Object],
         inherit: InheritableFields,
+        max_depth: usize,
     ) -> Result<Vec<Self>, Error> {
+        if inherit.depth > max_depth {
+            return Err(Error::NestingTooDeep { maximum: max_depth });
+        }
         // Create list for results
         let mut form_fields = vec![];
 
         for field in list {
             let field_object_id = field.get_object_id();
             let field_dict = field.deref(raw_doc)?.as_dict()?;
+            let partial_field_name = lopdf_utils::as_option_text_string(field_dict.get(b"T").ok())?;
+            let fully_qualified_field_name = match (&inherit.parent_name, &partial_field_name) {
+                (Some(parent_name), Some(name)) => Some(format!("{}.{}", parent_name, name)),
+                (parent_name, name) => name.clone().or_else(|| parent_name.clone()),
+            };
+            let ft = match field_dict.get(b"FT") {
+                Ok(ft) => Some(lopdf_utils::as_name(Some(ft))?),
+                Err(_) => inherit.ft.clone(),
+            };
 
-            // Check if it has the `FT` field.
-            if field_dict.has(b"FT") {
-                let component = match field_dict.get(b"FT")?.as_name()? {
+            // Kids with a name are fields, kids without a name are the widgets of the field.
+            let kids = match field_dict.get(b"Kids") {
+                Ok(kids) => kids.deref(raw_doc)?.as_array()?.as_slice(),
+                Err(_) => &[],
+            };
+            let has_child_fields = kids.iter().any(|kid| {
+                kid.deref(raw_doc)
+                    .ok()
+                    .and_then(|kid| kid.as_dict().ok())
+                    .is_some_and(|kid| kid.has(b"T"))
+            });
+            if has_child_fields {
+                let inherit = InheritableFields {
+                    ft,
+                    parent_name: fully_qualified_field_name,
+                    depth: inherit.depth + 1,
+                };
+                form_fields.extend(Self::load_field_list(raw_doc, kids, inherit, max_depth)?);
+                continue;
+            }
+
+            // Only fields with a type (`FT`, or inherited from a parent) are loaded.
+            if let Some(ft) = ft {
+                let component = match ft.as_bytes() {
                     b"Btn" => {
                         // Not implemented, ignored
                         FormComponent::Button
@@ -209,7 +273,7 @@ impl AcroForm {
 
                         // Check if `SV` (seed value) is set
                         if field_dict.has(b"SV") {
-                            log::warn!("`SV` is not supported for signatures.");
+                            tracing::warn!("`SV` is not supported for signatures.");
                         }
 
                         // Check if already signed
@@ -259,7 +323,7 @@ impl AcroForm {
                         }
                     }
                     unknown_type => {
-                        log::warn!(
+                        tracing::warn!(
                             "Found an unknown `FT`: {}",
                             String::from_utf8_lossy(unknown_type)
                         );
@@ -268,9 +332,8 @@ impl AcroForm {
                 };
                 form_fields.push(AcroForm {
                     object_id: field_object_id,
-                    partial_field_name: lopdf_utils::as_option_text_string(
-                        field_dict.get(b"T").ok(),
-                    )?,
+                    fully_qualified_field_name,
+                    partial_field_name,
                     alternate_field_name: lopdf_utils::as_option_text_string(
                         field_dict.get(b"TU").ok(),
                     )?,
@@ -0,0 +1,102 @@
+//! The PAdES baseline level of signatures (ETSI EN 319 142-1), see
+//! `SignatureVerification::ades_level`.
+
+use crate::revision_diff::get_revision_ends;
+use crate::PDFSigningDocument;
+use lopdf::{Document, Object};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The PAdES baseline level of a signature, based on the data in the document. The levels
+/// are ordered, every level has the data of the levels before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AdesLevel {
+    /// A PAdES signature (`ETSI.CAdES.detached`).
+    #[cfg_attr(feature = "serde", serde(rename = "B-B"))]
+    BaselineB,
+    /// A time stamp of the signature, or a document time stamp after it.
+    #[cfg_attr(feature = "serde", serde(rename = "B-T"))]
+    BaselineT,
+    /// Validation data (certificates, OCSP responses or CRLs) is added to the Document
+    /// Security Store (`DSS`) in the revision of the signature or after it.
+    #[cfg_attr(feature = "serde", serde(rename = "B-LT"))]
+    BaselineLt,
+    /// A document time stamp covers the validation data.
+    #[cfg_attr(feature = "serde", serde(rename = "B-LTA"))]
+    BaselineLta,
+}
+
+/// Get the level of a signature that ends at `signature_end`. The time stamps and
+/// validation data are not checked, only their position in the file is used.
+pub(crate) fn get_ades_level(
+    signature_end: u64,
+    has_signature_time_stamp: bool,
+    document_time_stamp_ends: &[u64],
+    validation_data_ends: &[u64],
+) -> AdesLevel {
+    let has_time_stamp_after = |end: u64| {
+        document_time_stamp_ends
+            .iter()
+            .any(|time_stamp_end| *time_stamp_end > signature_end && *time_stamp_end >= end)
+    };
+    if !has_signature_time_stamp && !has_time_stamp_after(signature_end) {
+        return AdesLevel::BaselineB;
+    }
+    let Some(validation_data_end) = validation_data_ends
+        .iter()
+        .find(|end| **end >= signature_end)
+    else {
+        return AdesLevel::BaselineT;
+    };
+    if has_time_stamp_after(*validation_data_end) {
+        AdesLevel::BaselineLta
+    } else {
+        AdesLevel::BaselineLt
+    }
+}
+
+impl PDFSigningDocument {
+    /// Get the end of the revisions that add validation data to the `DSS`.
+    /// Every revision is read, so only use it when there are PAdES signatures.
+    pub(crate) fn get_validation_data_ends(&self) -> Vec<u64> {
+        let mut ends = vec![];
+        let mut previous_count = 0;
+        for end in get_revision_ends(self.get_prev_documents_bytes()) {
+            let count = match self.load_revision(end) {
+                Ok(doc) => count_validation_data(&doc),
+                Err(error) => {
+                    tracing::warn!("Revision at {}: {}", end, error);
+                    continue;
+                }
+            };
+            if count > previous_count {
+                ends.push(end as u64);
+            }
+            previous_count = count;
+        }
+        ends
+    }
+}
+
+/// Count the certificates, OCSP responses and CRLs in the `DSS` of the document.
+fn count_validation_data(doc: &Document) -> usize {
+    let dss = doc
+        .catalog()
+        .ok()
+        .and_then(|catalog| catalog.get(b"DSS").ok())
+        .and_then(|dss| doc.dereference(dss).ok())
+        .and_then(|(_, dss)| dss.as_dict().ok());
+    let Some(dss) = dss else {
+        return 0;
+    };
+    [&b"Certs"[..], b"OCSPs", b"CRLs"]
+        .iter()
+        .filter_map(|key| dss.get(key).ok())
+        .filter_map(|array| doc.dereference(array).ok())
+        .filter_map(|(_, array)| match array {
+            Object::Array(array) => Some(array.len()),
+            _ => None,
+        })
+        .sum()
+}
@@ -0,0 +1,527 @@
+//! Add annotations (like rubber stamps, notes, free text and links) to pages.
+
+use crate::image_options::ImageOptions;
+use crate::journal::JournalChange;
+use crate::lopdf_utils::{date_string, text_string};
+use crate::rectangle::Rectangle;
+use crate::rich_text::{rich_text_font_resources, RichText};
+use crate::{Error, InsertImage, InsertImageToPage, PDFSigningDocument};
+use chrono::{DateTime, Utc};
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Object, ObjectId, Stream, StringFormat};
+
+/// Size of the icon of a note annotation, in points.
+const NOTE_ICON_SIZE: f64 = 20.0;
+
+/// Settings of a rubber stamp annotation.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StampOptions {
+    /// Name of the stamp icon (`Name`), for example `Approved` or `Draft`.
+    /// Viewers only use the icon when they can not show the appearance.
+    pub icon_name: Option<String>,
+    /// Comment shown for the stamp (`Contents`).
+    pub contents: Option<String>,
+    /// Author of the stamp (`T`).
+    pub author: Option<String>,
+    /// How the image is fitted into the rectangle and how it is encoded.
+    pub image_options: ImageOptions,
+}
+
+/// Settings of a note (sticky note) annotation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoteOptions {
+    /// Author of the note (`T`).
+    pub author: Option<String>,
+    /// Name of the icon (`Name`), for example `Comment`, `Note` or `Help`.
+    /// Viewers only use the icon when they can not show the appearance.
+    pub icon_name: Option<String>,
+    /// Color of the icon and the popup window (RGB, from 0.0 to 1.0).
+    pub color: (f64, f64, f64),
+    /// Date and time the note was last changed (`M`), the current time when `None`
+    /// (see `set_fixed_time`).
+    pub modified: Option<DateTime<Utc>>,
+    /// Show the popup window with the contents when the document is opened.
+    pub open: bool,
+}
+
+impl Default for NoteOptions {
+    fn default() -> Self {
+        NoteOptions {
+            author: None,
+            icon_name: Some("Comment".to_owned()),
+            color: (1.0, 0.85, 0.2),
+            modified: None,
+            open: false,
+        }
+    }
+}
+
+/// Settings of a free text annotation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FreeTextOptions {
+    /// Font size in points. The font is Helvetica, or the font set with `set_embedded_font`.
+    pub font_size: f64,
+    /// Color of the text that has no color in the rich contents (RGB, from 0.0 to 1.0).
+    pub text_color: (f64, f64, f64),
+    /// Width of the border in points, no border when `0.0`.
+    pub border_width: f64,
+    pub border_color: (f64, f64, f64),
+    /// Color of the background, transparent when `None`.
+    pub background_color: Option<(f64, f64, f64)>,
+    /// Author of the annotation (`T`).
+    pub author: Option<String>,
+}
+
+impl Default for FreeTextOptions {
+    fn default() -> Self {
+        FreeTextOptions {
+            font_size: 12.0,
+            text_color: (0.0, 0.0, 0.0),
+            border_width: 1.0,
+            border_color: (0.0, 0.0, 0.0),
+            background_color: None,
+            author: None,
+        }
+    }
+}
+
+/// Where a link annotation goes to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkTarget {
+    /// Open a URI (like a website). The URI should only contain ASCII characters,
+    /// other characters must be percent-encoded.
+    Uri(String),
+    /// Go to the page with this index (starting at 0) in the document.
+    Page(usize),
+}
+
+/// Style of the border of a link annotation (`BS` -> `S`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BorderStyle {
+    #[default]
+    Solid,
+    Dashed,
+    /// Only a line at the bottom of the rectangle.
+    Underline,
+}
+
+impl BorderStyle {
+    fn name(self) -> &'static str {
+        match self {
+            BorderStyle::Solid => "S",
+            BorderStyle::Dashed => "D",
+            BorderStyle::Underline => "U",
+        }
+    }
+}
+
+/// How a link annotation looks when it is clicked (`H`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LinkHighlight {
+    None,
+    /// Invert the colors of the rectangle.
+    #[default]
+    Invert,
+    /// Invert the border of the rectangle.
+    Outline,
+    /// Show the rectangle as if it is pushed below the page.
+    Push,
+}
+
+impl LinkHighlight {
+    fn name(self) -> &'static str {
+        match self {
+            LinkHighlight::None => "N",
+            LinkHighlight::Invert => "I",
+            LinkHighlight::Outline => "O",
+            LinkHighlight::Push => "P",
+        }
+    }
+}
+
+/// Settings of a link annotation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkOptions {
+    /// Width of the border in points, no border when `0.0`.
+    pub border_width: f64,
+    pub border_color: (f64, f64, f64),
+    pub border_style: BorderStyle,
+    pub highlight: LinkHighlight,
+}
+
+impl Default for LinkOptions {
+    fn default() -> Self {
+        LinkOptions {
+            border_width: 0.0,
+            border_color: (0.0, 0.0, 1.0),
+            border_style: BorderStyle::default(),
+            highlight: LinkHighlight::default(),
+        }
+    }
+}
+
+impl PDFSigningDocument {
+    /// Add a rubber stamp annotation (`Stamp`) to the page with index `page_index`.
+    /// The image (PNG, JPEG, TIFF or WebP, SVG with `ImageOptions::allow_svg`) is used as
+    /// appearance of the stamp.
+    /// Unlike `add_image`, the stamp is not part of the page content,
+    /// so it can still be moved or deleted in a viewer.
+    /// Return the ObjectId of the annotation.
+    pub fn add_stamp_annotation(
+        &mut self,
+        page_index: usize,
+        image_bytes: &[u8],
+        rect: Rectangle,
+        options: &StampOptions,
+    ) -> Result<ObjectId, Error> {
+        let page_id = self.get_page_id(page_index)?;
+        let appearance_id = self.add_image_as_form_xobject(
+            image_bytes,
+            "Stamp",
+            rect.clone(),
+            &options.image_options,
+        )?;
+
+        let mut annotation = dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Stamp",
+            "Rect" => vec![rect.x1.into(), rect.y1.into(), rect.x2.into(), rect.y2.into()],
+            "P" => Object::Reference(page_id),
+            // `F` = 4: Print the annotation
+            "F" => 4,
+            "AP" => dictionary! {
+                "N" => Object::Reference(appearance_id),
+            },
+        };
+        if let Some(icon_name) = &options.icon_name {
+            annotation.set("Name", Object::Name(icon_name.as_bytes().to_vec()));
+        }
+        if let Some(contents) = &options.contents {
+            annotation.set("Contents", text_string(contents));
+        }
+        if let Some(author) = &options.author {
+            annotation.set("T", text_string(author));
+        }
+        if let Some(modified) = self.get_current_time() {
+            annotation.set("M", date_string(modified));
+        }
+        let annotation_id = self.add_object(annotation);
+        self.add_annotation_to_page(page_id, annotation_id)?;
+        Ok(annotation_id)
+    }
+
+    /// Add a note annotation (`Text`, a sticky note) with the comment `contents`
+    /// to the page with index `page_index`.
+    /// The bottom left corner of the icon is at (`x`, `y`) in default user space units.
+    /// Return the ObjectId of the annotation.
+    pub fn add_note_annotation(
+        &mut self,
+        page_index: usize,
+        x: f64,
+        y: f64,
+        contents: &str,
+        options: &NoteOptions,
+    ) -> Result<ObjectId, Error> {
+        let page_id = self.get_page_id(page_index)?;
+        let (red, green, blue) = options.color;
+        // Speech bubble with 3 lines of text.
+        let icon = format!(
+            "{} {} {} rg 0.3 0.3 0.3 RG 1 w \
+            0.5 5.5 m 0.5 19.5 l 19.5 19.5 l 19.5 5.5 l 9 5.5 l 4 0.5 l 5 5.5 l h B \
+            4 15 m 16 15 l 4 12 m 16 12 l 4 9 m 12 9 l S",
+            red, green, blue
+        );
+        let appearance_id = self.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => vec![0.into(), 0.into(), NOTE_ICON_SIZE.into(), NOTE_ICON_SIZE.into()],
+            },
+            icon.into_bytes(),
+        ));
+
+        let mut annotation = dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Text",
+            "Rect" => vec![x.into(), y.into(), (x + NOTE_ICON_SIZE).into(), (y + NOTE_ICON_SIZE).into()],
+            "P" => Object::Reference(page_id),
+            "Contents" => text_string(contents),
+            // `F` = 28: Print the annotation, do not zoom or rotate the icon
+            "F" => 28,
+            "C" => vec![red.into(), green.into(), blue.into()],
+            "Open" => options.open,
+            "AP" => dictionary! {
+                "N" => Object::Reference(appearance_id),
+            },
+        };
+        if let Some(icon_name) = &options.icon_name {
+            annotation.set("Name", Object::Name(icon_name.as_bytes().to_vec()));
+        }
+        if let Some(author) = &options.author {
+            annotation.set("T", text_string(author));
+        }
+        if let Some(modified) = options.modified.or_else(|| self.get_current_time()) {
+            annotation.set("M", date_string(modified));
+        }
+        let annotation_id = self.add_object(annotation);
+        self.add_annotation_to_page(page_id, annotation_id)?;
+        Ok(annotation_id)
+    }
+
+    /// Add a free text annotation with the text `contents` in `rect` to the page with
+    /// index `page_index`. The text is visible on the page, like a label.
+    /// Lines are only broken at line breaks (`\n`) in `contents`.
+    /// Return the ObjectId of the annotation.
+    pub fn add_free_text_annotation(
+        &mut self,
+        page_index: usize,
+        rect: Rectangle,
+        contents: &str,
+        options: &FreeTextOptions,
+    ) -> Result<ObjectId, Error> {
+        let rich_text = RichText::from_plain_text(contents);
+        self.add_free_text(page_index, rect, &rich_text, None, options)
+    }
+
+    /// Add a free text annotation with rich contents (`RC`) in `rect` to the page with
+    /// index `page_index`. The rich contents support the same XHTML subset as
+    /// `fill_rich_text_field`: bold, italic, colors and line breaks.
+    /// Return the ObjectId of the annotation.
+    pub fn add_rich_free_text_annotation(
+        &mut self,
+        page_index: usize,
+        rect: Rectangle,
+        xhtml: &str,
+        options: &FreeTextOptions,
+    ) -> Result<ObjectId, Error> {
+        let rich_text = RichText::parse(xhtml)?;
+        self.add_free_text(page_index, rect, &rich_text, Some(xhtml), options)
+    }
+
+    fn add_free_text(
+        &mut self,
+        page_index: usize,
+        rect: Rectangle,
+        rich_text: &RichText,
+        xhtml: Option<&str>,
+        options: &FreeTextOptions,
+    ) -> Result<ObjectId, Error> {
+        let page_id = self.get_page_id(page_index)?;
+        let text_font = self.get_text_font()?;
+        let (width, height) = (rect.width(), rect.height());
+        let border_width = options.border_width.max(0.0);
+
+        let mut operations = vec![Operation::new("q", vec![])];
+        if let Some((red, green, blue)) = options.background_color {
+            operations.push(Operation::new(
+                "rg",
+                vec![red.into(), green.into(), blue.into()],
+            ));
+            operations.push(Operation::new(
+                "re",
+                vec![0.into(), 0.into(), width.into(), height.into()],
+            ));
+            operations.push(Operation::new("f", vec![]));
+        }
+        if border_width > 0.0 {
+            let (red, green, blue) = options.border_color;
+            operations.push(Operation::new(
+                "RG",
+                vec![red.into(), green.into(), blue.into()],
+            ));
+            operations.push(Operation::new("w", vec![border_width.into()]));
+            // The border is drawn inside the rectangle.
+            operations.push(Operation::new(
+                "re",
+                vec![
+                    (border_width / 2.0).into(),
+                    (border_width / 2.0).into(),
+                    (width - border_width).into(),
+                    (height - border_width).into(),
+                ],
+            ));
+            operations.push(Operation::new("S", vec![]));
+        }
+        let padding = border_width + 2.0;
+        operations.append(&mut rich_text.to_text_operations(
+            options.font_size,
+            (padding, height - padding),
+            options.text_color,
+            text_font.as_ref(),
+        ));
+        operations.push(Operation::new("Q", vec![]));
+
+        let font_resources =
+            rich_text_font_resources(text_font.as_ref(), |object| self.add_object(object));
+        let mut appearance = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+                "Resources" => dictionary! {
+                    "Font" => font_resources,
+                },
+            },
+            Content { operations }.encode()?,
+        );
+        let _ = appearance.compress();
+        let appearance_id = self.add_object(appearance);
+
+        let (red, green, blue) = options.text_color;
+        let mut annotation = dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "FreeText",
+            "Rect" => vec![rect.x1.into(), rect.y1.into(), rect.x2.into(), rect.y2.into()],
+            "P" => Object::Reference(page_id),
+            "Contents" => text_string(&rich_text.to_plain_text()),
+            // `F` = 4: Print the annotation
+            "F" => 4,
+            "DA" => Object::string_literal(format!(
+                "/Helv {} Tf {} {} {} rg",
+                options.font_size, red, green, blue
+            )),
+            "BS" => dictionary! {
+                "W" => border_width,
+                "S" => "S",
+            },
+            "AP" => dictionary! {
+                "N" => Object::Reference(appearance_id),
+            },
+        };
+        if let Some((red, green, blue)) = options.background_color {
+            annotation.set("C", vec![red.into(), green.into(), blue.into()]);
+        }
+        if let Some(xhtml) = xhtml {
+            annotation.set(
+                "RC",
+                Object::String(
+                    RichText::to_rich_value(xhtml).into_bytes(),
+                    StringFormat::Literal,
+                ),
+            );
+        }
+        if let Some(author) = &options.author {
+            annotation.set("T", text_string(author));
+        }
+        if let Some(modified) = self.get_current_time() {
+            annotation.set("M", date_string(modified));
+        }
+        let annotation_id = self.add_object(annotation);
+        self.add_annotation_to_page(page_id, annotation_id)?;
+        Ok(annotation_id)
+    }
+
+    /// Add a link annotation over `rect` on the page with index `page_index`,
+    /// for example to make a printed URL or QR code clickable.
+    /// The link has no appearance, the viewer draws the border (if any).
+    /// Return the ObjectId of the annotation.
+    pub fn add_link_annotation(
+        &mut self,
+        page_index: usize,
+        rect: Rectangle,
+        target: &LinkTarget,
+        options: &LinkOptions,
+    ) -> Result<ObjectId, Error> {
+        let page_id = self.get_page_id(page_index)?;
+        let (red, green, blue) = options.border_color;
+        let mut annotation = dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Link",
+            "Rect" => vec![rect.x1.into(), rect.y1.into(), rect.x2.into(), rect.y2.into()],
+            "P" => Object::Reference(page_id),
+            // `F` = 4: Print the annotation
+            "F" => 4,
+            "H" => Object::Name(options.highlight.name().as_bytes().to_vec()),
+            "BS" => dictionary! {
+                "W" => options.border_width.max(0.0),
+                "S" => Object::Name(options.border_style.name().as_bytes().to_vec()),
+            },
+            "C" => vec![red.into(), green.into(), blue.into()],
+        };
+        match target {
+            LinkTarget::Uri(uri) => annotation.set(
+                "A",
+                dictionary! {
+                    "S" => "URI",
+                    "URI" => Object::string_literal(uri.as_bytes()),
+                },
+            ),
+            LinkTarget::Page(target_index) => {
+                let target_id = self.get_page_id(*target_index)?;
+                annotation.set(
+                    "Dest",
+                    vec![Object::Reference(target_id), Object::Name(b"Fit".to_vec())],
+                );
+            }
+        }
+        let annotation_id = self.add_object(annotation);
+        self.add_annotation_to_page(page_id, annotation_id)?;
+        Ok(annotation_id)
+    }
+
+    /// Add the annotation to the `Annots` of the page,
+    /// and a structure element for it in tagged documents (see `set_tag_content`).
+    pub(crate) fn add_annotation_to_page(
+        &mut self,
+        page_id: ObjectId,
+        annotation_id: ObjectId,
+    ) -> Result<(), Error> {
+        self.opt_clone_object_to_new_document(page_id)?;
+        // `Annots` can be an array or a reference to an array.
+        let annots_id = match self
+            .raw_document
+            .new_document
+            .get_object(page_id)?
+            .as_dict()?
+            .get(b"Annots")
+        {
+            Ok(Object::Reference(annots_id)) => Some(*annots_id),
+            _ => None,
+        };
+        if let Some(annots_id) = annots_id {
+            self.opt_clone_object_to_new_document(annots_id)?;
+            self.raw_document
+                .new_document
+                .get_object_mut(annots_id)?
+                .as_array_mut()?
+                .push(Object::Reference(annotation_id));
+            self.tag_annotation(page_id, annotation_id)?;
+            return self.record_annotation(page_id, annotation_id);
+        }
+
+        let page = self
+            .raw_document
+            .new_document
+            .get_object_mut(page_id)?
+            .as_dict_mut()?;
+        match page.get_mut(b"Annots").and_then(Object::as_array_mut) {
+            Ok(annots) => annots.push(Object::Reference(annotation_id)),
+            Err(_) => page.set("Annots", vec![Object::Reference(annotation_id)]),
+        }
+        self.tag_annotation(page_id, annotation_id)?;
+        self.record_annotation(page_id, annotation_id)
+    }
+
+    /// Add the annotation that is added to the page to the journal.
+    fn record_annotation(
+        &mut self,
+        page_id: ObjectId,
+        annotation_id: ObjectId,
+    ) -> Result<(), Error> {
+        let page_index = self
+            .get_page_index(page_id)
+            .ok_or_else(|| Error::from("Annotation: Page not found."))?;
+        let annotation = self
+            .get_latest_object(annotation_id)
+            .ok_or_else(|| Error::from("Annotation not found."))?
+            .as_dict()?;
+        let subtype = annotation.get(b"Subtype")?.as_name_str()?.to_owned();
+        let rect = Rectangle::from_array(annotation.get(b"Rect")?.as_array()?)?;
+        self.record_change(JournalChange::AnnotationAdded {
+            page_index,
+            subtype,
+            rect,
+        });
+        Ok(())
+    }
+}
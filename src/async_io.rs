@@ -0,0 +1,106 @@
+//! Read, write and sign documents in an async (tokio) application, feature `tokio`.
+
+use crate::{Error, PDFSigningDocument, ParsingLimits};
+#[cfg(feature = "signing")]
+use crate::{MatchBy, SigningOptions, SigningResult, UserSignatureInfo};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+impl PDFSigningDocument {
+    /// Read a document like `read_from`, from an async reader.
+    pub async fn read_from_async<R: AsyncRead + Unpin>(
+        reader: R,
+        file_name: String,
+    ) -> Result<Self, Error> {
+        Self::read_from_async_with_password(reader, file_name, "").await
+    }
+
+    /// Read a document like `read_from_with_password`, from an async reader.
+    pub async fn read_from_async_with_password<R: AsyncRead + Unpin>(
+        mut reader: R,
+        file_name: String,
+        password: &str,
+    ) -> Result<Self, Error> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data).await?;
+        // Parsing is CPU-heavy, it is done on the blocking threads of the runtime.
+        let password = password.to_owned();
+        tokio::task::spawn_blocking(move || {
+            Self::load(data, file_name, &password, &ParsingLimits::default())
+        })
+        .await
+        .map_err(|error| Error::Other(format!("Reading the document failed: {}", error)))?
+    }
+
+    /// Write the document like `write_document`, to an async writer.
+    /// The document is first written to memory (like `sign_async`, without blocking the
+    /// other tasks), it is not flushed or shut down.
+    pub async fn write_document_async<W: AsyncWrite + Unpin>(
+        &self,
+        target: &mut W,
+    ) -> Result<(), Error> {
+        target
+            .write_all(&run_blocking(|| self.save_to_bytes())?)
+            .await?;
+        Ok(())
+    }
+
+    /// Sign the document like `sign`.
+    /// Signing and its (blocking) network requests, like time stamps and OCSP responses,
+    /// are run with `tokio::task::block_in_place` in the multi-threaded runtime, so the
+    /// other tasks are moved to other threads. The current-thread runtime has no other
+    /// threads, signing blocks its tasks until it is done.
+    #[cfg(feature = "signing")]
+    pub async fn sign_async(
+        &mut self,
+        users_signature_info: &[UserSignatureInfo],
+        options: &SigningOptions,
+    ) -> Result<SigningResult, Error> {
+        run_blocking(|| self.sign(users_signature_info, options))
+    }
+
+    /// Sign the document like `sign_document`, see `sign_async`.
+    #[cfg(feature = "signing")]
+    #[deprecated(note = "use `sign_async` with `MatchBy::UserId`")]
+    pub async fn sign_document_async(
+        &mut self,
+        users_signature_info: &[UserSignatureInfo],
+        options: &SigningOptions,
+    ) -> Result<SigningResult, Error> {
+        let options = SigningOptions {
+            match_by: MatchBy::UserId,
+            ..options.clone()
+        };
+        self.sign_async(users_signature_info, &options).await
+    }
+
+    /// Sign the document like `sign_document_2`, see `sign_async`.
+    #[cfg(feature = "signing")]
+    #[deprecated(note = "use `sign_async` with `MatchBy::BoxId`")]
+    pub async fn sign_document_2_async(
+        &mut self,
+        users_signature_info: &[UserSignatureInfo],
+        options: &SigningOptions,
+    ) -> Result<Vec<u8>, Error> {
+        let options = SigningOptions {
+            match_by: MatchBy::BoxId,
+            ..options.clone()
+        };
+        Ok(self
+            .sign_async(users_signature_info, &options)
+            .await?
+            .document)
+    }
+}
+
+/// Run CPU-heavy or blocking work, like signing, without blocking the other tasks of the
+/// runtime: in the multi-threaded runtime the tasks of this thread are moved to other
+/// threads. The work borrows the document, so it can not be moved to a blocking thread.
+/// `block_in_place` panics in the current-thread runtime, the work is run in place there.
+fn run_blocking<T>(work: impl FnOnce() -> T) -> T {
+    use tokio::runtime::{Handle, RuntimeFlavor};
+
+    match Handle::try_current().map(|handle| handle.runtime_flavor()) {
+        Ok(RuntimeFlavor::MultiThread) => tokio::task::block_in_place(work),
+        _ => work(),
+    }
+}
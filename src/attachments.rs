@@ -0,0 +1,389 @@
+//! Embed files in the document (`EmbeddedFiles`) and extract them again,
+//! for example the source data of a signed document.
+
+use crate::journal::JournalChange;
+use crate::lopdf_utils::{as_byte_range, date_string, decode_text_string, text_string};
+use crate::{Error, InsertImage, InsertImageToPage, PDFSigningDocument};
+use lopdf::xref::XrefEntry;
+use lopdf::{dictionary, Dictionary, Object, ObjectId, Stream, StringFormat};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// How an attachment is related to the document (`AFRelationship`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum AttachmentRelationship {
+    /// The original source of the document, like the data it was generated from.
+    Source,
+    /// Data used to create the document, like a table of a chart.
+    Data,
+    /// Another representation of the document, like a machine-readable invoice.
+    Alternative,
+    /// Extra information that is not part of the document.
+    Supplement,
+    #[default]
+    Unspecified,
+}
+
+impl AttachmentRelationship {
+    fn name(self) -> &'static str {
+        match self {
+            AttachmentRelationship::Source => "Source",
+            AttachmentRelationship::Data => "Data",
+            AttachmentRelationship::Alternative => "Alternative",
+            AttachmentRelationship::Supplement => "Supplement",
+            AttachmentRelationship::Unspecified => "Unspecified",
+        }
+    }
+
+    fn from_name(name: &str) -> Self {
+        match name {
+            "Source" => AttachmentRelationship::Source,
+            "Data" => AttachmentRelationship::Data,
+            "Alternative" => AttachmentRelationship::Alternative,
+            "Supplement" => AttachmentRelationship::Supplement,
+            _ => AttachmentRelationship::Unspecified,
+        }
+    }
+}
+
+/// A file embedded in the document.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Attachment {
+    /// The file name, also used as name in the `EmbeddedFiles` name tree.
+    pub file_name: String,
+    pub data: Vec<u8>,
+    /// The MIME type, like `application/json`.
+    pub mime_type: Option<String>,
+    pub description: Option<String>,
+    pub relationship: AttachmentRelationship,
+}
+
+impl PDFSigningDocument {
+    /// Embed `attachment` in the document with an incremental update, so existing signatures
+    /// stay valid. An attachment with the same file name is replaced.
+    /// The attachment is also added to the associated files (`AF`) of the document.
+    /// Add attachments before signing, attachments added to a signed document are not
+    /// covered by the existing signatures (see `get_unsigned_attachments`).
+    pub fn add_attachment(&mut self, attachment: &Attachment) -> Result<(), Error> {
+        if self.pdfa_mode {
+            tracing::warn!(
+                "PDF/A: Attachments are only allowed in PDF/A-3 or when they are PDF/A documents."
+            );
+        }
+        self.add_embedded_file(attachment)
+    }
+
+    pub(crate) fn add_embedded_file(&mut self, attachment: &Attachment) -> Result<(), Error> {
+        if self.get_last_signed_byte_range().is_some() {
+            tracing::warn!(
+                "Attachment `{}` is added after the last signature, it is not covered by it.",
+                attachment.file_name
+            );
+        }
+        let mut params = dictionary! {
+            "Size" => attachment.data.len() as i64,
+            "CheckSum" => Object::String(
+                md5::compute(&attachment.data).to_vec(),
+                StringFormat::Hexadecimal,
+            ),
+        };
+        if attachment.data.is_empty() {
+            params.remove(b"CheckSum");
+        }
+        if let Some(modified) = self.get_current_time() {
+            params.set("ModDate", date_string(modified));
+        }
+        let mut embedded_file = Stream::new(
+            dictionary! {
+                "Type" => "EmbeddedFile",
+                "Params" => params,
+            },
+            attachment.data.clone(),
+        );
+        if let Some(mime_type) = &attachment.mime_type {
+            embedded_file
+                .dict
+                .set("Subtype", Object::Name(mime_type.as_bytes().to_vec()));
+        }
+        let _ = embedded_file.compress();
+        let embedded_file_id = self.add_object(embedded_file);
+
+        let mut file_spec = dictionary! {
+            "Type" => "Filespec",
+            "F" => text_string(&attachment.file_name),
+            "UF" => text_string(&attachment.file_name),
+            "EF" => dictionary! {
+                "F" => Object::Reference(embedded_file_id),
+                "UF" => Object::Reference(embedded_file_id),
+            },
+            "AFRelationship" => attachment.relationship.name(),
+        };
+        if let Some(description) = &attachment.description {
+            file_spec.set("Desc", text_string(description));
+        }
+        let file_spec_id = self.add_object(file_spec);
+
+        // Replace the name tree with a single node, that is valid for any number of files.
+        let mut entries = self.get_embedded_files();
+        let name = text_string(&attachment.file_name);
+        let name_bytes = name.as_str()?.to_vec();
+        let replaced_file_spec = entries
+            .iter()
+            .position(|(entry_name, _)| *entry_name == name_bytes)
+            .and_then(|index| entries.remove(index).1.as_reference().ok());
+        entries.push((name_bytes, Object::Reference(file_spec_id)));
+        entries.sort_by(|(name_a, _), (name_b, _)| name_a.cmp(name_b));
+        let names: Vec<Object> = entries
+            .into_iter()
+            .flat_map(|(name, file_spec)| [Object::String(name, StringFormat::Literal), file_spec])
+            .collect();
+        let embedded_files_id = self.add_object(dictionary! { "Names" => names });
+
+        let catalog_id = self
+            .raw_document
+            .new_document
+            .trailer
+            .get(b"Root")?
+            .as_reference()?;
+        self.opt_clone_object_to_new_document(catalog_id)?;
+        let catalog = self
+            .raw_document
+            .new_document
+            .get_object(catalog_id)?
+            .as_dict()?;
+        let names_id = catalog.get(b"Names").and_then(Object::as_reference).ok();
+        let mut associated_files = catalog
+            .get(b"AF")
+            .ok()
+            .and_then(|associated_files| self.resolve_latest_object(associated_files))
+            .and_then(|associated_files| associated_files.as_array().ok())
+            .cloned()
+            .unwrap_or_default();
+        if let Some(replaced_file_spec) = replaced_file_spec {
+            associated_files.retain(|associated_file| {
+                associated_file.as_reference().ok() != Some(replaced_file_spec)
+            });
+        }
+        associated_files.push(Object::Reference(file_spec_id));
+
+        let names = match names_id {
+            Some(names_id) => {
+                self.opt_clone_object_to_new_document(names_id)?;
+                self.raw_document
+                    .new_document
+                    .get_object_mut(names_id)?
+                    .as_dict_mut()?
+            }
+            None => {
+                let catalog = self
+                    .raw_document
+                    .new_document
+                    .get_object_mut(catalog_id)?
+                    .as_dict_mut()?;
+                if !catalog.has(b"Names") {
+                    catalog.set("Names", Dictionary::new());
+                }
+                catalog.get_mut(b"Names")?.as_dict_mut()?
+            }
+        };
+        names.set("EmbeddedFiles", Object::Reference(embedded_files_id));
+        self.raw_document
+            .new_document
+            .get_object_mut(catalog_id)?
+            .as_dict_mut()?
+            .set("AF", associated_files);
+        self.record_change(JournalChange::AttachmentAdded {
+            file_name: attachment.file_name.clone(),
+        });
+        Ok(())
+    }
+
+    /// Get all the files embedded in the document (`EmbeddedFiles`).
+    pub fn get_attachments(&self) -> Vec<Attachment> {
+        self.get_embedded_files()
+            .into_iter()
+            .filter_map(|(name, file_spec)| self.get_attachment_from_file_spec(&name, &file_spec))
+            .collect()
+    }
+
+    /// Get the embedded file with `file_name`.
+    pub fn get_attachment(&self, file_name: &str) -> Option<Attachment> {
+        self.get_attachments()
+            .into_iter()
+            .find(|attachment| attachment.file_name == file_name)
+    }
+
+    /// Get the file names of the attachments that are not covered by the last signature
+    /// or document timestamp, because they were added or changed after it (or are not saved yet).
+    /// These attachments can be changed without invalidating the signatures.
+    /// The list is empty when the document is not signed.
+    pub fn get_unsigned_attachments(&self) -> Vec<String> {
+        let byte_range = match self.get_last_signed_byte_range() {
+            Some(byte_range) => byte_range,
+            None => return vec![],
+        };
+        let is_signed = |object_id: ObjectId| {
+            self.get_object_offset(object_id).is_some_and(|offset| {
+                byte_range
+                    .iter()
+                    .any(|(start, length)| (*start..start + length).contains(&offset))
+            })
+        };
+        self.get_embedded_files()
+            .into_iter()
+            .filter_map(|(name, file_spec)| {
+                let attachment = self.get_attachment_from_file_spec(&name, &file_spec)?;
+                // The file specification can be a direct object in the (signed) name tree.
+                let file_spec_signed = file_spec.as_reference().map_or(true, is_signed);
+                let embedded_file_signed =
+                    self.get_embedded_file_id(&file_spec).is_some_and(is_signed);
+                match file_spec_signed && embedded_file_signed {
+                    true => None,
+                    false => Some(attachment.file_name),
+                }
+            })
+            .collect()
+    }
+
+    /// Get the `ByteRange` of the signature (or document timestamp) that ends last in the file.
+    pub(crate) fn get_last_signed_byte_range(&self) -> Option<Vec<(u64, u64)>> {
+        self.raw_document
+            .get_prev_documents()
+            .objects
+            .values()
+            .filter_map(|object| object.as_dict().ok())
+            .filter(|dict| dict.has(b"Contents"))
+            .filter_map(|dict| as_byte_range(dict.get(b"ByteRange").ok()).ok())
+            .max_by_key(|byte_range| {
+                byte_range
+                    .iter()
+                    .map(|(start, length)| start + length)
+                    .max()
+                    .unwrap_or(0)
+            })
+    }
+
+    /// Get the offset of the object in the saved file, `None` when it is not saved yet.
+    /// Objects in an object stream get the offset of the object stream.
+    fn get_object_offset(&self, object_id: ObjectId) -> Option<u64> {
+        if self
+            .raw_document
+            .new_document
+            .objects
+            .contains_key(&object_id)
+        {
+            return None;
+        }
+        let reference_table = &self.raw_document.get_prev_documents().reference_table;
+        match reference_table.get(object_id.0)? {
+            XrefEntry::Normal { offset, .. } => Some(u64::from(*offset)),
+            XrefEntry::Compressed { container, .. } => match reference_table.get(*container)? {
+                XrefEntry::Normal { offset, .. } => Some(u64::from(*offset)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Get the entries of the `EmbeddedFiles` name tree: the name and the file specification.
+    fn get_embedded_files(&self) -> Vec<(Vec<u8>, Object)> {
+        let embedded_files = self
+            .raw_document
+            .new_document
+            .trailer
+            .get(b"Root")
+            .ok()
+            .and_then(|root| self.resolve_latest_object(root))
+            .and_then(|catalog| catalog.as_dict().ok())
+            .and_then(|catalog| catalog.get(b"Names").ok())
+            .and_then(|names| self.resolve_latest_object(names))
+            .and_then(|names| names.as_dict().ok())
+            .and_then(|names| names.get(b"EmbeddedFiles").ok());
+        let mut entries = vec![];
+        if let Some(embedded_files) = embedded_files {
+            self.collect_name_tree_entries(embedded_files, 0, &mut entries);
+        }
+        entries
+    }
+
+    /// Collect the entries (name and value) of the name tree `node` and its kids.
+    pub(crate) fn collect_name_tree_entries(
+        &self,
+        node: &Object,
+        depth: usize,
+        entries: &mut Vec<(Vec<u8>, Object)>,
+    ) {
+        // Limit the depth, so a loop in the name tree does not hang.
+        let node = match self.resolve_latest_object(node) {
+            Some(Object::Dictionary(node)) if depth < self.limits.max_depth => node,
+            _ => return,
+        };
+        let array = |key: &[u8]| {
+            node.get(key)
+                .ok()
+                .and_then(|array| self.resolve_latest_object(array))
+                .and_then(|array| array.as_array().ok())
+        };
+        if let Some(names) = array(b"Names") {
+            for pair in names.chunks_exact(2) {
+                if let Ok(name) = pair[0].as_str() {
+                    entries.push((name.to_vec(), pair[1].clone()));
+                }
+            }
+        }
+        for kid in array(b"Kids").into_iter().flatten() {
+            self.collect_name_tree_entries(kid, depth + 1, entries);
+        }
+    }
+
+    fn get_attachment_from_file_spec(&self, name: &[u8], file_spec: &Object) -> Option<Attachment> {
+        let embedded_file_id = self.get_embedded_file_id(file_spec)?;
+        let file_spec = self.resolve_latest_object(file_spec)?.as_dict().ok()?;
+        let embedded_file = self.get_latest_object(embedded_file_id)?.as_stream().ok()?;
+        let data = match embedded_file.dict.has(b"Filter") {
+            true => embedded_file.decompressed_content().ok()?,
+            false => embedded_file.content.clone(),
+        };
+
+        let text = |key: &[u8]| {
+            file_spec
+                .get(key)
+                .ok()
+                .and_then(|text| self.resolve_latest_object(text))
+                .and_then(|text| text.as_str().ok())
+                .map(decode_text_string)
+        };
+        Some(Attachment {
+            file_name: text(b"UF")
+                .or_else(|| text(b"F"))
+                .unwrap_or_else(|| decode_text_string(name)),
+            data,
+            mime_type: embedded_file
+                .dict
+                .get(b"Subtype")
+                .and_then(Object::as_name_str)
+                .ok()
+                .map(str::to_owned),
+            description: text(b"Desc"),
+            relationship: file_spec
+                .get(b"AFRelationship")
+                .and_then(Object::as_name_str)
+                .map(AttachmentRelationship::from_name)
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Get the embedded file stream (`EF`) of the file specification.
+    fn get_embedded_file_id(&self, file_spec: &Object) -> Option<ObjectId> {
+        let file_spec = self.resolve_latest_object(file_spec)?.as_dict().ok()?;
+        let embedded_files = file_spec.get(b"EF").ok()?;
+        let embedded_files = self.resolve_latest_object(embedded_files)?.as_dict().ok()?;
+        embedded_files
+            .get(b"UF")
+            .or_else(|_| embedded_files.get(b"F"))
+            .ok()?
+            .as_reference()
+            .ok()
+    }
+}
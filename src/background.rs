@@ -0,0 +1,269 @@
+//! Place an image or a page of another PDF (like a letterhead) underneath the page content.
+
+use crate::image_options::ImageOptions;
+use crate::journal::JournalChange;
+use crate::rectangle::Rectangle;
+use crate::tagged_content::ContentTag;
+use crate::{Error, InsertImage, InsertImageToPage, PDFSigningDocument};
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Document, Object, ObjectId, Stream,
+};
+use std::collections::HashMap;
+
+/// Content placed underneath the existing content of a page.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Background {
+    /// Image data (PNG, JPEG, TIFF or WebP, SVG with `ImageOptions::allow_svg`).
+    Image(Vec<u8>),
+    /// The page with index `page_index` (starting at 0) of another PDF document.
+    PdfPage {
+        pdf_data: Vec<u8>,
+        page_index: usize,
+    },
+}
+
+impl PDFSigningDocument {
+    /// Place `background` underneath the existing content of the pages with `page_indexes`.
+    /// `options.placement` defines how the background is fitted into the page,
+    /// `options.compression` how images are encoded.
+    /// The background is added once as a Form XObject and reused by all pages.
+    pub fn add_background(
+        &mut self,
+        page_indexes: &[usize],
+        background: &Background,
+        options: &ImageOptions,
+    ) -> Result<(), Error> {
+        let page_ids = page_indexes
+            .iter()
+            .map(|page_index| self.get_page_id(*page_index))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (xobject_id, bbox) = match background {
+            Background::Image(image_data) => {
+                let (xobject_id, size) =
+                    self.add_image_as_unscaled_form_xobject(image_data, "Image", options)?;
+                let bbox = Rectangle {
+                    x1: 0.0,
+                    y1: 0.0,
+                    x2: size.0,
+                    y2: size.1,
+                };
+                (xobject_id, bbox)
+            }
+            Background::PdfPage {
+                pdf_data,
+                page_index,
+            } => self.import_page_as_form_xobject(pdf_data, *page_index)?,
+        };
+
+        for page_id in page_ids {
+            let page_rect = self.get_page_rect(page_id)?;
+            let xobject_name = self.get_unused_xobject_name(page_id, "Background");
+            self.add_xobject(page_id, xobject_name.as_str(), xobject_id)?;
+
+            let placement = options.placement;
+            let image_rect = placement.get_image_rect(bbox.width(), bbox.height(), &page_rect);
+            let scale_x = image_rect.width() / bbox.width();
+            let scale_y = image_rect.height() / bbox.height();
+            // The background is drawn first and isolated with `q` and `Q`,
+            // so it does not change the graphics state of the existing content.
+            let mut operations = vec![Operation::new("q", vec![])];
+            operations.extend(placement.clip_operations(&page_rect));
+            operations.extend(placement.rotation_operations(&image_rect));
+            operations.push(Operation::new(
+                "cm",
+                vec![
+                    scale_x.into(),
+                    0.into(),
+                    0.into(),
+                    scale_y.into(),
+                    (image_rect.x1 - bbox.x1 * scale_x).into(),
+                    (image_rect.y1 - bbox.y1 * scale_y).into(),
+                ],
+            ));
+            operations.push(Operation::new(
+                "Do",
+                vec![Object::Name(xobject_name.into_bytes())],
+            ));
+            operations.push(Operation::new("Q", vec![]));
+            let tag = ContentTag::Artifact(dictionary! {
+                "Type" => "Background",
+                "BBox" => vec![
+                    page_rect.x1.into(),
+                    page_rect.y1.into(),
+                    page_rect.x2.into(),
+                    page_rect.y2.into(),
+                ],
+            });
+            let operations = self.tag_page_content(page_id, operations, tag)?;
+            self.prepend_to_page_content(page_id, Content { operations })?;
+        }
+        self.record_change(JournalChange::BackgroundAdded {
+            page_indexes: page_indexes.to_vec(),
+        });
+        Ok(())
+    }
+
+    /// Add `content` before the existing content of the page.
+    pub(crate) fn prepend_to_page_content(
+        &mut self,
+        page_id: ObjectId,
+        content: Content<Vec<Operation>>,
+    ) -> Result<(), Error> {
+        let mut stream = Stream::new(dictionary! {}, content.encode()?);
+        let _ = stream.compress();
+        let content_id = self.add_object(stream);
+
+        self.opt_clone_object_to_new_document(page_id)?;
+        // `Contents` can be a stream, an array of streams or a reference to an array.
+        let contents = self
+            .raw_document
+            .new_document
+            .get_object(page_id)?
+            .as_dict()?
+            .get(b"Contents")
+            .ok()
+            .cloned();
+        let contents = match contents {
+            Some(Object::Reference(contents_id)) => {
+                self.opt_clone_object_to_new_document(contents_id)?;
+                let contents = self.raw_document.new_document.get_object_mut(contents_id)?;
+                if let Object::Array(contents) = contents {
+                    contents.insert(0, Object::Reference(content_id));
+                    return Ok(());
+                }
+                vec![
+                    Object::Reference(content_id),
+                    Object::Reference(contents_id),
+                ]
+            }
+            Some(Object::Array(mut contents)) => {
+                contents.insert(0, Object::Reference(content_id));
+                contents
+            }
+            _ => vec![Object::Reference(content_id)],
+        };
+        self.raw_document
+            .new_document
+            .get_object_mut(page_id)?
+            .as_dict_mut()?
+            .set("Contents", contents);
+        Ok(())
+    }
+
+    /// Add the page with index `page_index` of `pdf_data` as a Form XObject.
+    /// Return the ObjectId and the bounding box of the Form XObject.
+    fn import_page_as_form_xobject(
+        &mut self,
+        pdf_data: &[u8],
+        page_index: usize,
+    ) -> Result<(ObjectId, Rectangle), Error> {
+        let source = Document::load_mem(pdf_data)?;
+        let page_id = source
+            .get_pages()
+            .into_values()
+            .nth(page_index)
+            .ok_or_else(|| Error::Other(format!("Page with index `{}` not found.", page_index)))?;
+        let page_box = get_inherited_attribute(&source, page_id, b"CropBox")
+            .or_else(|| get_inherited_attribute(&source, page_id, b"MediaBox"))
+            .ok_or_else(|| Error::from("Page: MediaBox not found."))?;
+        let bbox = Rectangle::from_array(page_box.as_array()?)?;
+        let resources = get_inherited_attribute(&source, page_id, b"Resources")
+            .cloned()
+            .unwrap_or_else(|| Object::Dictionary(dictionary! {}));
+
+        let mut imported_ids = HashMap::new();
+        let resources = self.import_object(&source, &resources, &mut imported_ids);
+        let mut stream = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => vec![bbox.x1.into(), bbox.y1.into(), bbox.x2.into(), bbox.y2.into()],
+                "Resources" => resources,
+            },
+            source.get_page_content(page_id)?,
+        );
+        let _ = stream.compress();
+        Ok((self.add_object(stream), bbox))
+    }
+
+    /// Copy `object` of the `source` document to the new document,
+    /// including the objects it references. `imported_ids` links the old and new ObjectIds.
+    fn import_object(
+        &mut self,
+        source: &Document,
+        object: &Object,
+        imported_ids: &mut HashMap<ObjectId, ObjectId>,
+    ) -> Object {
+        match object {
+            Object::Reference(object_id) => {
+                if let Some(imported_id) = imported_ids.get(object_id) {
+                    return Object::Reference(*imported_id);
+                }
+                let imported_id = self.raw_document.new_document.new_object_id();
+                imported_ids.insert(*object_id, imported_id);
+                let imported_object = match source.get_object(*object_id) {
+                    // Do not copy the page tree, it is not needed to draw the page.
+                    Ok(Object::Dictionary(dict))
+                        if dict.get(b"Type").and_then(Object::as_name_str).ok() == Some("Page") =>
+                    {
+                        Object::Null
+                    }
+                    Ok(object) => self.import_object(source, object, imported_ids),
+                    Err(_) => Object::Null,
+                };
+                self.raw_document
+                    .new_document
+                    .set_object(imported_id, imported_object);
+                Object::Reference(imported_id)
+            }
+            Object::Array(array) => Object::Array(
+                array
+                    .iter()
+                    .map(|item| self.import_object(source, item, imported_ids))
+                    .collect(),
+            ),
+            Object::Dictionary(dict) => {
+                Object::Dictionary(self.import_dictionary(source, dict, imported_ids))
+            }
+            Object::Stream(stream) => {
+                let mut stream = stream.clone();
+                stream.dict = self.import_dictionary(source, &stream.dict, imported_ids);
+                Object::Stream(stream)
+            }
+            _ => object.clone(),
+        }
+    }
+
+    fn import_dictionary(
+        &mut self,
+        source: &Document,
+        dict: &lopdf::Dictionary,
+        imported_ids: &mut HashMap<ObjectId, ObjectId>,
+    ) -> lopdf::Dictionary {
+        dict.iter()
+            .map(|(key, value)| (key.clone(), self.import_object(source, value, imported_ids)))
+            .collect()
+    }
+}
+
+/// Get an attribute of the page, also when it is inherited from a `Pages` node.
+fn get_inherited_attribute<'a>(
+    doc: &'a Document,
+    page_id: ObjectId,
+    key: &[u8],
+) -> Option<&'a Object> {
+    let mut node_id = page_id;
+    // Limit the depth, so a loop in the page tree does not hang.
+    for _ in 0..32 {
+        let node = doc.get_dictionary(node_id).ok()?;
+        if let Ok(value) = node.get(key) {
+            return match value {
+                Object::Reference(value_id) => doc.get_object(*value_id).ok(),
+                _ => Some(value),
+            };
+        }
+        node_id = node.get(b"Parent").ok()?.as_reference().ok()?;
+    }
+    None
+}
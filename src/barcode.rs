@@ -0,0 +1,292 @@
+//! Render barcodes as native PDF content (filled rectangles).
+
+use crate::journal::JournalChange;
+use crate::rectangle::Rectangle;
+use crate::tagged_content::ContentTag;
+use crate::{lopdf_utils, Error, InsertImageToPage, PDFSigningDocument};
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Object, ObjectId, Stream,
+};
+
+/// Code 128 patterns: widths of bar, space, bar, space, bar, space.
+const CODE_128_PATTERNS: [&str; 106] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212",
+    "221213", "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221",
+    "223211", "221132", "221231", "213212", "223112", "312131", "311222", "321122", "321221",
+    "312212", "322112", "322211", "212123", "212321", "232121", "111323", "131123", "131321",
+    "112313", "132113", "132311", "211313", "231113", "231311", "112133", "112331", "132131",
+    "113123", "113321", "133121", "313121", "211331", "231131", "213113", "213311", "213131",
+    "311123", "311321", "331121", "312113", "312311", "332111", "314111", "221411", "431111",
+    "111224", "111422", "121124", "121421", "141122", "141221", "112214", "112412", "122114",
+    "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111", "111242",
+    "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311",
+    "113141", "114131", "311141", "411131", "211412", "211214", "211232",
+];
+const CODE_128_STOP: &str = "2331112";
+const CODE_128_START_B: usize = 104;
+const CODE_128_START_C: usize = 105;
+
+/// The type of barcode to render.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BarcodeType {
+    /// Linear barcode, supports ASCII characters.
+    Code128,
+    /// 2D QR Code.
+    QrCode,
+    /// 2D Data Matrix (ECC 200).
+    DataMatrix,
+}
+
+/// The modules (dots or bars) of a barcode.
+#[derive(Debug, Clone)]
+struct BarcodeModules {
+    width: usize,
+    /// Is `1` for linear barcodes.
+    height: usize,
+    /// Row by row, `true` is a dark module.
+    modules: Vec<bool>,
+    /// Amount of empty modules needed around the barcode.
+    quiet_zone: usize,
+}
+
+impl BarcodeModules {
+    fn encode(barcode_type: BarcodeType, value: &str) -> Result<Self, Error> {
+        match barcode_type {
+            BarcodeType::Code128 => Self::encode_code_128(value),
+            #[cfg(feature = "barcodes")]
+            BarcodeType::QrCode => {
+                let code = qrcode::QrCode::new(value.as_bytes())
+                    .map_err(|err| Error::Other(format!("QR Code: {}", err)))?;
+                Ok(BarcodeModules {
+                    width: code.width(),
+                    height: code.width(),
+                    modules: code
+                        .to_colors()
+                        .into_iter()
+                        .map(|color| color == qrcode::Color::Dark)
+                        .collect(),
+                    quiet_zone: 4,
+                })
+            }
+            #[cfg(feature = "barcodes")]
+            BarcodeType::DataMatrix => {
+                let code = datamatrix::DataMatrix::encode(
+                    value.as_bytes(),
+                    datamatrix::SymbolList::default(),
+                )
+                .map_err(|err| Error::Other(format!("Data Matrix: {:?}", err)))?;
+                let bitmap = code.bitmap();
+                let (width, height) = (bitmap.width(), bitmap.height());
+                let mut modules = vec![false; width * height];
+                for (x, y) in bitmap.pixels() {
+                    modules[y * width + x] = true;
+                }
+                Ok(BarcodeModules {
+                    width,
+                    height,
+                    modules,
+                    quiet_zone: 1,
+                })
+            }
+            #[cfg(not(feature = "barcodes"))]
+            BarcodeType::QrCode | BarcodeType::DataMatrix => Err(Error::from(
+                "QR Code and Data Matrix are not supported, enable the `barcodes` feature.",
+            )),
+        }
+    }
+
+    /// Encode using code set B, or code set C when the value only contains
+    /// an even amount of digits.
+    fn encode_code_128(value: &str) -> Result<Self, Error> {
+        let mut symbols = vec![];
+        if !value.is_empty() && value.len() % 2 == 0 && value.bytes().all(|b| b.is_ascii_digit()) {
+            symbols.push(CODE_128_START_C);
+            for pair in value.as_bytes().chunks(2) {
+                symbols.push(((pair[0] - b'0') * 10 + (pair[1] - b'0')) as usize);
+            }
+        } else {
+            symbols.push(CODE_128_START_B);
+            for byte in value.bytes() {
+                if !(32..=127).contains(&byte) {
+                    return Err(Error::Other(format!(
+                        "Code 128: Character `{}` can not be encoded.",
+                        byte as char
+                    )));
+                }
+                symbols.push((byte - 32) as usize);
+            }
+        }
+        let checksum = symbols
+            .iter()
+            .enumerate()
+            .map(|(index, symbol)| index.max(1) * symbol)
+            .sum::<usize>()
+            % 103;
+        symbols.push(checksum);
+
+        let mut modules = vec![];
+        let patterns = symbols
+            .iter()
+            .map(|symbol| CODE_128_PATTERNS[*symbol])
+            .chain(std::iter::once(CODE_128_STOP));
+        for pattern in patterns {
+            for (index, width) in pattern.bytes().enumerate() {
+                let dark = index % 2 == 0;
+                modules.extend(std::iter::repeat(dark).take((width - b'0') as usize));
+            }
+        }
+        Ok(BarcodeModules {
+            width: modules.len(),
+            height: 1,
+            modules,
+            quiet_zone: 10,
+        })
+    }
+
+    /// Create the content that draws the barcode inside `rect`.
+    /// 2D barcodes keep square modules and are centered, linear barcodes fill the `rect`.
+    fn to_content(&self, rect: &Rectangle) -> Content<Vec<Operation>> {
+        let total_width = (self.width + 2 * self.quiet_zone) as f64;
+        let (module_width, module_height, x_offset, y_offset) = if self.height == 1 {
+            let module_width = rect.width() / total_width;
+            (module_width, rect.height(), rect.x1, rect.y1)
+        } else {
+            let total_height = (self.height + 2 * self.quiet_zone) as f64;
+            let size = (rect.width() / total_width).min(rect.height() / total_height);
+            (
+                size,
+                size,
+                rect.x1 + (rect.width() - size * total_width) / 2.0,
+                rect.y1 + (rect.height() - size * total_height) / 2.0,
+            )
+        };
+
+        let mut operations = vec![
+            Operation::new("q", vec![]),
+            // Black fill color
+            Operation::new("g", vec![0.into()]),
+        ];
+        for row in 0..self.height {
+            // PDF coordinates start at the bottom, the modules at the top.
+            let y = if self.height == 1 {
+                y_offset
+            } else {
+                y_offset + (self.quiet_zone + self.height - 1 - row) as f64 * module_height
+            };
+            let mut column = 0;
+            while column < self.width {
+                if !self.modules[row * self.width + column] {
+                    column += 1;
+                    continue;
+                }
+                // Merge dark modules next to each other into 1 rectangle.
+                let start = column;
+                while column < self.width && self.modules[row * self.width + column] {
+                    column += 1;
+                }
+                let x = x_offset + (start + self.quiet_zone) as f64 * module_width;
+                operations.push(Operation::new(
+                    "re",
+                    vec![
+                        x.into(),
+                        y.into(),
+                        ((column - start) as f64 * module_width).into(),
+                        module_height.into(),
+                    ],
+                ));
+            }
+        }
+        operations.push(Operation::new("f", vec![]));
+        operations.push(Operation::new("Q", vec![]));
+        Content { operations }
+    }
+}
+
+impl PDFSigningDocument {
+    /// Render a barcode on a page inside `rect`.
+    /// In tagged documents the barcode is a `Figure` with the value as alternate description.
+    pub fn add_barcode(
+        &mut self,
+        page_id: ObjectId,
+        rect: Rectangle,
+        barcode_type: BarcodeType,
+        value: &str,
+    ) -> Result<(), Error> {
+        let content = BarcodeModules::encode(barcode_type, value)?.to_content(&rect);
+        let tag = ContentTag::Element {
+            structure_type: "Figure",
+            alternate_text: value,
+            bbox: rect.clone(),
+        };
+        let operations = self.tag_page_content(page_id, content.operations, tag)?;
+        self.opt_clone_object_to_new_document(page_id)?;
+        self.add_to_page_content(page_id, Content { operations })?;
+        self.record_change(JournalChange::BarcodeAdded {
+            page_index: self.get_page_index(page_id),
+            rect,
+            value: value.to_owned(),
+        });
+        Ok(())
+    }
+
+    /// Render a barcode as the appearance of a field and set its value (`V`).
+    pub fn fill_barcode_field(
+        &mut self,
+        field_name: &str,
+        barcode_type: BarcodeType,
+        value: &str,
+    ) -> Result<(), Error> {
+        self.load_acro_form()?;
+        let form_field = self.get_form_field(field_name)?;
+        let field_id = form_field
+            .get_object_id()
+            .ok_or_else(|| Error::from("AcroForm object is not a indirect reference."))?;
+        let modules = BarcodeModules::encode(barcode_type, value)?;
+
+        let mut doc = self.raw_document.get_prev_documents().clone();
+        for widget_id in form_field.get_widget_ids(&doc)? {
+            let rect = Rectangle::from_array(
+                doc.get_object(widget_id)?
+                    .as_dict()?
+                    .get(b"Rect")?
+                    .as_array()?,
+            )?;
+            let (width, height) = (rect.width(), rect.height());
+            let bbox = Rectangle {
+                x1: 0.0,
+                y1: 0.0,
+                x2: width,
+                y2: height,
+            };
+            let stream = Stream::new(
+                dictionary! {
+                    "Type" => "XObject",
+                    "Subtype" => "Form",
+                    "BBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+                },
+                modules.to_content(&bbox).encode()?,
+            );
+            let appearance_id = doc.add_object(stream);
+
+            let widget = doc.get_object_mut(widget_id)?.as_dict_mut()?;
+            widget.set(
+                "AP",
+                dictionary! {
+                    "N" => Object::Reference(appearance_id),
+                },
+            );
+        }
+
+        let field = doc.get_object_mut(field_id)?.as_dict_mut()?;
+        field.set("V", lopdf_utils::text_string(value));
+
+        self.reload_document(doc)?;
+        self.record_change(JournalChange::FieldFilled {
+            field_name: field_name.to_owned(),
+            value: value.to_owned(),
+        });
+        Ok(())
+    }
+}
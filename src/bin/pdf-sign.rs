@@ -0,0 +1,248 @@
+//! Command line tool to fill, sign, verify and flatten documents with `pdf_rs`.
+//! Build it with `cargo build --features cli`, see `pdf-sign --help`.
+
+use clap::{Parser, Subcommand, ValueEnum};
+use pdf_rs::{
+    ByteRangeIssue, DigestAlgorithm, Error, MatchBy, PDFSigningDocument, SigningOptions,
+    UserSignatureInfo, VerificationOptions, VerificationStatus,
+};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use x509_certificate::{CapturedX509Certificate, InMemorySigningKeyPair};
+
+#[derive(Parser)]
+#[command(
+    name = "pdf-sign",
+    version,
+    about = "Fill, sign, verify and flatten PDF documents."
+)]
+struct Cli {
+    /// The user or owner password of an encrypted document.
+    #[arg(long, global = true, default_value = "")]
+    password: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the form fields: name, type, page (starting at 1) and if they are signed.
+    Fields { input: PathBuf },
+    /// Fill the form fields with the values of a JSON object (field name to string).
+    Fill {
+        input: PathBuf,
+        data: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Sign the empty signature fields of a user.
+    Sign {
+        input: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+        /// The certificate of the signer (PEM).
+        #[arg(long)]
+        cert: PathBuf,
+        /// The private key of the signer (PKCS #8, PEM).
+        #[arg(long)]
+        key: PathBuf,
+        /// The signature image (PNG, JPEG, TIFF or WebP, SVG when the file has the
+        /// extension `.svg`).
+        #[arg(long)]
+        image: PathBuf,
+        #[arg(long)]
+        user_id: String,
+        #[arg(long, default_value = "")]
+        user_name: String,
+        #[arg(long, default_value = "")]
+        user_email: String,
+        /// Sign the field with this name, instead of the fields with the encoded user id.
+        #[arg(long)]
+        box_id: Option<String>,
+        #[arg(long, value_enum, default_value_t = Digest::Sha256)]
+        digest: Digest,
+        /// Add a time stamp of the time stamp authority at this URL. Repeat it to try the
+        /// next authority when one fails.
+        #[arg(long)]
+        time_stamp_url: Vec<String>,
+        /// Fail instead of using the network.
+        #[arg(long)]
+        offline: bool,
+    },
+    /// Check the signatures, the exit code is 1 when a signature is not valid.
+    Verify { input: PathBuf },
+    /// Draw the form fields in the pages and remove the form.
+    Flatten {
+        input: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Digest {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl From<Digest> for DigestAlgorithm {
+    fn from(digest: Digest) -> Self {
+        match digest {
+            Digest::Sha256 => DigestAlgorithm::Sha256,
+            Digest::Sha384 => DigestAlgorithm::Sha384,
+            Digest::Sha512 => DigestAlgorithm::Sha512,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli) {
+        Ok(exit_code) => exit_code,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<ExitCode, Error> {
+    match cli.command {
+        Command::Fields { input } => {
+            let mut document = read_document(&input, &cli.password)?;
+            for field in document.get_form_fields()? {
+                println!(
+                    "{}\t{:?}\t{}\t{}",
+                    field.fully_qualified_name().unwrap_or_default(),
+                    field.field_type(),
+                    field
+                        .page_index()
+                        .map_or_else(|| "-".to_owned(), |index| (index + 1).to_string()),
+                    if field.is_signed() { "signed" } else { "" },
+                );
+            }
+        }
+        Command::Fill {
+            input,
+            data,
+            output,
+        } => {
+            let mut document = read_document(&input, &cli.password)?;
+            let data: Map<String, Value> = serde_json::from_slice(&std::fs::read(data)?)
+                .map_err(|error| Error::Other(format!("Data: {}", error)))?;
+            document.load_all()?;
+            document.fill_form(data)?;
+            document.save_document(output)?;
+        }
+        Command::Sign {
+            input,
+            output,
+            cert,
+            key,
+            image,
+            user_id,
+            user_name,
+            user_email,
+            box_id,
+            digest,
+            time_stamp_url,
+            offline,
+        } => {
+            let cert = CapturedX509Certificate::from_pem(std::fs::read(cert)?)
+                .map_err(|error| Error::Other(format!("Certificate: {}", error)))?;
+            let key = InMemorySigningKeyPair::from_pkcs8_pem(std::fs::read(key)?)
+                .map_err(|error| Error::Other(format!("Private key: {}", error)))?;
+            let match_by = match box_id {
+                Some(_) => MatchBy::BoxId,
+                None => MatchBy::UserId,
+            };
+            let mut options = SigningOptions::builder()
+                .digest_algorithm(digest.into())
+                .match_by(match_by)
+                .offline(offline);
+            for url in &time_stamp_url {
+                options = options.time_stamp_url(url);
+            }
+            let options = options.build();
+            let users_signature_info = [UserSignatureInfo {
+                box_id: box_id.unwrap_or_default(),
+                user_id,
+                user_name,
+                user_email,
+                user_signature: std::fs::read(&image)?.into(),
+                user_signing_keys: Arc::new(key),
+                user_certificate: cert,
+            }];
+
+            let mut document = read_document(&input, &cli.password)?;
+            document.set_image_allow_svg(
+                image
+                    .extension()
+                    .is_some_and(|extension| extension == "svg"),
+            );
+            let result = document.sign(&users_signature_info, &options)?;
+            std::fs::write(output, result.document)?;
+        }
+        Command::Verify { input } => {
+            let mut document = read_document(&input, &cli.password)?;
+            let verifications = document.verify_signatures(&VerificationOptions::default())?;
+            if verifications.is_empty() {
+                println!("The document has no signatures.");
+            }
+            let mut all_valid = true;
+            for verification in verifications {
+                all_valid &= verification.is_valid();
+                let signature = match verification.signature_valid {
+                    Some(true) => "signature valid",
+                    Some(false) => "signature INVALID",
+                    None => "signature not checked",
+                };
+                // Without trust stores the certificate chain is not trusted.
+                let status = match verification.status {
+                    VerificationStatus::Valid => "valid",
+                    VerificationStatus::Invalid => "INVALID",
+                    VerificationStatus::Indeterminate => "indeterminate",
+                };
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    verification.field_name,
+                    verification.signer_name.as_deref().unwrap_or_default(),
+                    match verification.byte_range_issue {
+                        None => "byte range valid",
+                        Some(ByteRangeIssue::PartialCoverage) => "byte range PARTIAL COVERAGE",
+                        Some(_) => "byte range INVALID",
+                    },
+                    signature,
+                    match verification.covers_whole_document {
+                        true => "covers whole document",
+                        false => "document changed after signing",
+                    },
+                    status,
+                );
+            }
+            if !all_valid {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Flatten { input, output } => {
+            let mut document = read_document(&input, &cli.password)?;
+            let widget_count = document.flatten_form()?;
+            document.save_document(output)?;
+            println!("Flattened {} form fields.", widget_count);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn read_document(path: &Path, password: &str) -> Result<PDFSigningDocument, Error> {
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    PDFSigningDocument::builder()
+        .password(password)
+        .read(path, file_name)
+}
@@ -0,0 +1,85 @@
+use crate::form_field::FormFieldType;
+use crate::image_options::ImageOptions;
+use crate::image_placement::{ImagePlacement, ImageScaling};
+use crate::journal::JournalChange;
+use crate::rectangle::Rectangle;
+use crate::{Error, InsertImage, PDFSigningDocument};
+use lopdf::{dictionary, Document, Object, ObjectId};
+use std::io::Read;
+
+/// Used to add images to a document that is changed in place (like `fill_form` does).
+impl InsertImage for Document {
+    fn add_object<T: Into<Object>>(&mut self, object: T) -> ObjectId {
+        Document::add_object(self, object)
+    }
+}
+
+impl PDFSigningDocument {
+    /// Set the icon of a pushbutton field to the given (PNG) image.
+    ///
+    /// Pushbuttons are often used as image placeholders (photo, company stamp).
+    /// The image is scaled to the rectangle of every widget of the field.
+    pub fn fill_button_image<R: Read>(
+        &mut self,
+        field_name: &str,
+        mut image_reader: R,
+    ) -> Result<(), Error> {
+        self.load_acro_form()?;
+        let form_field = self.get_form_field(field_name)?;
+        // Check boxes and radio buttons are also buttons (`Btn`), their appearance has a
+        // state for on and off, so only pushbuttons (`Ff` bit 17) are filled.
+        if self.get_form_field_info(&form_field).field_type() != FormFieldType::PushButton {
+            return Err(Error::UnsupportedFieldType {
+                name: field_name.to_owned(),
+                expected: "pushbutton".to_owned(),
+            });
+        }
+
+        // Read the image once, it is used for every widget.
+        let mut image_data = vec![];
+        image_reader.read_to_end(&mut image_data)?;
+        let image_name = format!("ButtonImage{}", field_name.replace(' ', ""));
+
+        let image_options = ImageOptions {
+            placement: ImagePlacement::new(ImageScaling::Contain, 0.0),
+            ..ImageOptions::default()
+        };
+
+        let mut doc = self.raw_document.get_prev_documents().clone();
+        for widget_id in form_field.get_widget_ids(&doc)? {
+            let rect = Rectangle::from_array(
+                doc.get_object(widget_id)?
+                    .as_dict()?
+                    .get(b"Rect")?
+                    .as_array()?,
+            )?;
+            let appearance_id =
+                doc.add_image_as_form_xobject(&*image_data, &image_name, rect, &image_options)?;
+
+            let widget = doc.get_object_mut(widget_id)?.as_dict_mut()?;
+            widget.set(
+                "AP",
+                dictionary! {
+                    "N" => Object::Reference(appearance_id),
+                },
+            );
+            // Set the normal icon in the appearance characteristics.
+            let mut appearance_characteristics = widget
+                .get(b"MK")
+                .and_then(Object::as_dict)
+                .cloned()
+                .unwrap_or_default();
+            appearance_characteristics.set("I", Object::Reference(appearance_id));
+            // `TP` = 1: No caption, icon only
+            appearance_characteristics.set("TP", 1);
+            widget.set("MK", appearance_characteristics);
+        }
+
+        self.reload_document(doc)?;
+        self.record_change(JournalChange::FieldFilled {
+            field_name: field_name.to_owned(),
+            value: String::new(),
+        });
+        Ok(())
+    }
+}
@@ -27,12 +27,15 @@ impl ByteRange {
         }
     }
 
-    pub fn get_range(&self, range_pair_index: usize) -> RangeInclusive<usize> {
+    pub fn get_range(&self, range_pair_index: usize) -> Result<RangeInclusive<usize>, Error> {
         let index = range_pair_index * 2;
-        if index > self.0.len() {
-            panic!("Index range out of bounds: TODO");
+        match (self.0.get(index), self.0.get(index + 1)) {
+            (Some(&start), Some(&length)) if length > 0 => Ok(start..=start + length - 1),
+            _ => Err(Error::from(format!(
+                "ByteRange: Range with index `{}` not found.",
+                range_pair_index
+            ))),
         }
-        self.0[index]..=self.0[index] + self.0[index + 1] - 1
     }
 
     pub fn get_capacity_inclusive(&self) -> usize {
@@ -0,0 +1,344 @@
+//! The details of the certificate of a signer, see `SignatureVerification::signer_certificate`.
+//! The certificate is read without an X.509 crate, so it is available without the `signing`
+//! feature with `CertificateDetails::from_der`.
+
+use crate::der::read_der;
+use crate::Error;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The object identifiers (DER encoded content) of the attributes and extensions that are read.
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+const OID_EMAIL_ADDRESS: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01];
+const OID_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x0f];
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+
+/// The details of an X.509 certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct CertificateDetails {
+    /// The subject, like `C=BE, O=Example, CN=Alice` (in the order of the certificate).
+    pub subject: String,
+    /// The common name (`CN`) of the subject.
+    pub common_name: Option<String>,
+    /// The issuer, in the format of `subject`.
+    pub issuer: String,
+    /// The serial number in hexadecimal (upper case).
+    pub serial_number: String,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+    /// The allowed usages of the key, empty when the certificate has no key usage extension.
+    pub key_usage: Vec<KeyUsage>,
+    /// The email addresses in the subject alternative name extension.
+    pub emails: Vec<String>,
+}
+
+/// A usage of the key of a certificate (key usage extension).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub enum KeyUsage {
+    DigitalSignature,
+    /// Also called content commitment, used for signatures that can not be denied.
+    NonRepudiation,
+    KeyEncipherment,
+    DataEncipherment,
+    KeyAgreement,
+    KeyCertSign,
+    CrlSign,
+    EncipherOnly,
+    DecipherOnly,
+}
+
+impl CertificateDetails {
+    /// Read the details of a DER encoded certificate.
+    pub fn from_der(data: &[u8]) -> Result<Self, Error> {
+        parse_certificate(data).ok_or_else(|| Error::from("Certificate: Invalid certificate."))
+    }
+}
+
+/// The fields of the `TBSCertificate` of a certificate that are used.
+pub(crate) struct TbsCertificate<'a> {
+    /// The content of the serial number `INTEGER`.
+    pub(crate) serial_number: &'a [u8],
+    /// The DER encoded issuer name.
+    pub(crate) issuer: &'a [u8],
+    validity: &'a [u8],
+    /// The DER encoded subject name.
+    pub(crate) subject: &'a [u8],
+    /// The bits of the public key (`subjectPublicKey`), without the number of unused bits.
+    pub(crate) public_key: &'a [u8],
+    /// The content of the `AlgorithmIdentifier` of the public key: the object identifier
+    /// and the parameters (like the curve).
+    pub(crate) public_key_algorithm: &'a [u8],
+    /// The object identifier and the value of every extension.
+    pub(crate) extensions: Vec<(&'a [u8], &'a [u8])>,
+}
+
+/// Read the `TBSCertificate` of a DER encoded certificate: the version, serial number,
+/// signature algorithm, issuer, validity, subject, public key and the optional fields.
+pub(crate) fn read_tbs_certificate(data: &[u8]) -> Option<TbsCertificate<'_>> {
+    let (0x30, certificate, _) = read_der(data)? else {
+        return None;
+    };
+    let (0x30, tbs_certificate, _) = read_der(certificate)? else {
+        return None;
+    };
+    let (mut tag, mut serial_number, mut rest) = read_der(tbs_certificate)?;
+    // The version is optional.
+    if tag == 0xa0 {
+        (tag, serial_number, rest) = read_der(rest)?;
+    }
+    if tag != 0x02 {
+        return None;
+    }
+    let (0x30, _signature, rest) = read_der(rest)? else {
+        return None;
+    };
+    let (issuer, rest) = read_der_value(rest, 0x30)?;
+    let (0x30, validity, rest) = read_der(rest)? else {
+        return None;
+    };
+    let (subject, rest) = read_der_value(rest, 0x30)?;
+    let (0x30, public_key_info, mut rest) = read_der(rest)? else {
+        return None;
+    };
+    let (0x30, public_key_algorithm, public_key) = read_der(public_key_info)? else {
+        return None;
+    };
+    let (0x03, public_key, _) = read_der(public_key)? else {
+        return None;
+    };
+
+    let mut extensions = vec![];
+    while let Some((tag, content, next)) = read_der(rest) {
+        rest = next;
+        if tag != 0xa3 {
+            continue;
+        }
+        let (0x30, mut extensions_data, _) = read_der(content)? else {
+            return None;
+        };
+        while let Some((0x30, extension, next)) = read_der(extensions_data) {
+            extensions_data = next;
+            let Some((0x06, oid, value)) = read_der(extension) else {
+                continue;
+            };
+            // Skip `critical`.
+            let value = match read_der(value) {
+                Some((0x01, _, value)) => value,
+                _ => value,
+            };
+            if let Some((0x04, value, _)) = read_der(value) {
+                extensions.push((oid, value));
+            }
+        }
+    }
+
+    Some(TbsCertificate {
+        serial_number,
+        issuer,
+        validity,
+        subject,
+        public_key: public_key.get(1..)?,
+        public_key_algorithm,
+        extensions,
+    })
+}
+
+/// Read the value with `tag` at the start of `data`, including the tag and length.
+/// Returns the value and the data after it.
+fn read_der_value(data: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    match read_der(data)? {
+        (value_tag, _, rest) if value_tag == tag => Some((&data[..data.len() - rest.len()], rest)),
+        _ => None,
+    }
+}
+
+/// Read the details from the fields of the certificate.
+fn parse_certificate(data: &[u8]) -> Option<CertificateDetails> {
+    let tbs_certificate = read_tbs_certificate(data)?;
+    let (not_before_tag, not_before, validity) = read_der(tbs_certificate.validity)?;
+    let (not_after_tag, not_after, _) = read_der(validity)?;
+    let (_, subject, _) = read_der(tbs_certificate.subject)?;
+    let (_, issuer, _) = read_der(tbs_certificate.issuer)?;
+    let (subject, common_name) = parse_name(subject)?;
+    let (issuer, _) = parse_name(issuer)?;
+    let mut serial_number = tbs_certificate.serial_number;
+    if serial_number.len() > 1 && serial_number[0] == 0 {
+        serial_number = &serial_number[1..];
+    }
+
+    let mut key_usage = vec![];
+    let mut emails = vec![];
+    for (oid, value) in tbs_certificate.extensions {
+        match oid {
+            OID_KEY_USAGE => key_usage = parse_key_usage(value),
+            OID_SUBJECT_ALT_NAME => emails = parse_emails(value),
+            _ => {}
+        }
+    }
+
+    Some(CertificateDetails {
+        subject,
+        common_name,
+        issuer,
+        serial_number: serial_number
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect(),
+        not_before: parse_time(not_before_tag, not_before)?,
+        not_after: parse_time(not_after_tag, not_after)?,
+        key_usage,
+        emails,
+    })
+}
+
+/// Format the attributes of a name like `C=BE, O=Example, CN=Alice`, and get the first
+/// common name.
+fn parse_name(mut name: &[u8]) -> Option<(String, Option<String>)> {
+    let mut attributes = vec![];
+    let mut common_name = None;
+    while !name.is_empty() {
+        let (0x31, mut set, next) = read_der(name)? else {
+            return None;
+        };
+        name = next;
+        while !set.is_empty() {
+            let (0x30, attribute, next) = read_der(set)? else {
+                return None;
+            };
+            set = next;
+            let (0x06, oid, value) = read_der(attribute)? else {
+                return None;
+            };
+            let (tag, value, _) = read_der(value)?;
+            let value = decode_string(tag, value);
+            if oid == OID_COMMON_NAME && common_name.is_none() {
+                common_name = Some(value.clone());
+            }
+            attributes.push(format!("{}={}", get_attribute_name(oid), value));
+        }
+    }
+    Some((attributes.join(", "), common_name))
+}
+
+fn get_attribute_name(oid: &[u8]) -> String {
+    let name = match oid {
+        OID_COMMON_NAME => "CN",
+        [0x55, 0x04, 0x04] => "SN",
+        [0x55, 0x04, 0x05] => "serialNumber",
+        [0x55, 0x04, 0x06] => "C",
+        [0x55, 0x04, 0x07] => "L",
+        [0x55, 0x04, 0x08] => "ST",
+        [0x55, 0x04, 0x0a] => "O",
+        [0x55, 0x04, 0x0b] => "OU",
+        [0x55, 0x04, 0x2a] => "GN",
+        [0x55, 0x04, 0x61] => "organizationIdentifier",
+        OID_EMAIL_ADDRESS => "emailAddress",
+        _ => return format_oid(oid),
+    };
+    name.to_owned()
+}
+
+/// Format an object identifier like `2.5.4.3`.
+fn format_oid(oid: &[u8]) -> String {
+    let mut values = vec![];
+    let mut value: u64 = 0;
+    for byte in oid {
+        value = (value << 7) | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            values.push(value);
+            value = 0;
+        }
+    }
+    // The first value has the first 2 parts.
+    let first = values.first().copied().unwrap_or_default();
+    let (first, second) = match first {
+        0..=79 => (first / 40, first % 40),
+        _ => (2, first - 80),
+    };
+    [first, second]
+        .into_iter()
+        .chain(values.into_iter().skip(1))
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Decode the string types used in names, `BMPString` is UTF-16 and `TeletexString` is
+/// read as Latin-1.
+fn decode_string(tag: u8, value: &[u8]) -> String {
+    match tag {
+        0x1e => String::from_utf16_lossy(
+            &value
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect::<Vec<_>>(),
+        ),
+        0x14 => value.iter().map(|byte| char::from(*byte)).collect(),
+        _ => String::from_utf8_lossy(value).into_owned(),
+    }
+}
+
+/// Read a `UTCTime` (`YYMMDDHHMMSSZ`) or `GeneralizedTime` (`YYYYMMDDHHMMSSZ`).
+pub(crate) fn parse_time(tag: u8, value: &[u8]) -> Option<DateTime<Utc>> {
+    let value = std::str::from_utf8(value).ok()?;
+    let time = match tag {
+        0x17 => {
+            let year: u32 = value.get(..2)?.parse().ok()?;
+            let century = if year >= 50 { "19" } else { "20" };
+            let time = format!("{}{}", century, value.get(..12)?);
+            NaiveDateTime::parse_from_str(&time, "%Y%m%d%H%M%S").ok()?
+        }
+        0x18 => NaiveDateTime::parse_from_str(value.get(..14)?, "%Y%m%d%H%M%S").ok()?,
+        _ => return None,
+    };
+    Some(Utc.from_utc_datetime(&time))
+}
+
+/// Read the `BIT STRING` of the key usage extension.
+fn parse_key_usage(value: &[u8]) -> Vec<KeyUsage> {
+    let Some((0x03, bits, _)) = read_der(value) else {
+        return vec![];
+    };
+    // The first byte is the number of unused bits.
+    let bits = bits.get(1..).unwrap_or_default();
+    let is_set = |index: usize| {
+        bits.get(index / 8)
+            .is_some_and(|byte| byte & (0x80 >> (index % 8)) != 0)
+    };
+    [
+        KeyUsage::DigitalSignature,
+        KeyUsage::NonRepudiation,
+        KeyUsage::KeyEncipherment,
+        KeyUsage::DataEncipherment,
+        KeyUsage::KeyAgreement,
+        KeyUsage::KeyCertSign,
+        KeyUsage::CrlSign,
+        KeyUsage::EncipherOnly,
+        KeyUsage::DecipherOnly,
+    ]
+    .into_iter()
+    .enumerate()
+    .filter(|(index, _)| is_set(*index))
+    .map(|(_, key_usage)| key_usage)
+    .collect()
+}
+
+/// Read the `rfc822Name` values of the subject alternative name extension.
+fn parse_emails(value: &[u8]) -> Vec<String> {
+    let Some((0x30, mut names, _)) = read_der(value) else {
+        return vec![];
+    };
+    let mut emails = vec![];
+    while let Some((tag, name, next)) = read_der(names) {
+        names = next;
+        if tag == 0x81 {
+            emails.push(String::from_utf8_lossy(name).into_owned());
+        }
+    }
+    emails
+}
@@ -0,0 +1,563 @@
+//! Sign with a remote signing service with the API of the Cloud Signature Consortium
+//! (CSC), like the services of qualified trust service providers (feature `network`),
+//! see `CscSigner::builder`.
+
+use crate::external_signer::{ExternalSigner, KeyAlgorithm};
+use crate::{DigestAlgorithm, Error, HttpClient};
+use serde_json::{json, Value};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The version of the CSC API of the service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CscVersion {
+    /// Version 1.0.4.
+    #[default]
+    V1,
+    /// Version 2.
+    V2,
+}
+
+/// How the application logs in at the service, the service returns an OAuth 2.0 access
+/// token for the other requests.
+#[derive(Clone, PartialEq, Eq)]
+pub enum CscAuthentication {
+    /// An access token that the application got itself, like with the OAuth 2.0
+    /// authorization code flow of the user.
+    AccessToken(String),
+    /// OAuth 2.0 client credentials (`oauth2/token`).
+    ClientCredentials {
+        client_id: String,
+        client_secret: String,
+    },
+    /// The username and password of the user (`auth/login`).
+    Basic { username: String, password: String },
+}
+
+/// The secrets are not shown.
+impl fmt::Debug for CscAuthentication {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CscAuthentication::AccessToken(_) => f.write_str("AccessToken"),
+            CscAuthentication::ClientCredentials { client_id, .. } => f
+                .debug_struct("ClientCredentials")
+                .field("client_id", client_id)
+                .finish_non_exhaustive(),
+            CscAuthentication::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// The question for the authorization of a signature, see `CscAuthorizationCallback`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CscAuthorizationRequest {
+    pub credential_id: String,
+    /// The PIN of the credential is needed.
+    pub pin_needed: bool,
+    /// A one-time password (OTP) is needed, the service sends it to the user (like with a
+    /// text message) or the user has a device for it.
+    pub otp_needed: bool,
+    /// The description of the PIN or OTP of the service, to show to the user.
+    pub description: Option<String>,
+}
+
+/// The PIN and one-time password that authorize a signature.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct CscAuthorization {
+    pub pin: Option<String>,
+    pub otp: Option<String>,
+}
+
+/// The PIN and OTP are not shown.
+impl fmt::Debug for CscAuthorization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CscAuthorization").finish_non_exhaustive()
+    }
+}
+
+/// Asks the user for the PIN and the one-time password of a credential before every
+/// signature, when the service needs them. Return `None` to stop signing
+/// (`Error::PinCancelled`).
+pub type CscAuthorizationCallback =
+    dyn Fn(&CscAuthorizationRequest) -> Option<CscAuthorization> + Send + Sync;
+
+/// The authorization of the signature of one digest that the user gave, see
+/// `CscSigner::sign_authorized_digest`.
+#[derive(Clone, PartialEq, Eq)]
+pub enum CscDigestAuthorization {
+    /// The Signature Activation Data (SAD) of `credentials/authorize`, like of
+    /// `CscSigner::authorize_digest`.
+    Sad(String),
+    /// An access token with the scope `credential` for the digest, of the OAuth 2.0
+    /// authorization code flow of the user (see `CscSigner::is_authorized_with_oauth2`).
+    CredentialToken(String),
+}
+
+/// The SAD and token are not shown.
+impl fmt::Debug for CscDigestAuthorization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CscDigestAuthorization::Sad(_) => f.write_str("Sad"),
+            CscDigestAuthorization::CredentialToken(_) => f.write_str("CredentialToken"),
+        }
+    }
+}
+
+/// The requests to a service, with the access token.
+#[derive(Clone)]
+struct Session {
+    service_url: String,
+    client: HttpClient,
+    timeout: Duration,
+    authorization: String,
+}
+
+impl Session {
+    /// Call the method `method` (like `credentials/list`) of the API with `body`.
+    fn call(&self, method: &str, body: Value) -> Result<Value, Error> {
+        let url = format!("{}/{}", self.service_url, method);
+        self.client
+            .post_json(&url, Some(&self.authorization), &body, self.timeout)
+            .map_err(|error| Error::Other(format!("CSC: {}", error)))
+    }
+}
+
+/// Signs with a credential (a key and its certificate) of a remote signing service with the
+/// CSC API: the digest is signed with `signatures/signHash`, after it is authorized with
+/// `credentials/authorize`. Use it with `sign_field_with_signer`, or with `begin_signature`
+/// when the user authorizes the digest in another step.
+pub struct CscSigner {
+    session: Session,
+    version: CscVersion,
+    credential_ids: Vec<String>,
+    credential_id: String,
+    /// The certificate of the credential, followed by the certificates of the issuers.
+    certificates: Vec<Vec<u8>>,
+    key_algorithm: KeyAlgorithm,
+    pin_needed: bool,
+    otp_needed: bool,
+    /// The service sends the OTP when it is asked (`credentials/sendOTP`).
+    online_otp: bool,
+    description: Option<String>,
+    oauth2_authorization: bool,
+    authorization_callback: Option<Arc<CscAuthorizationCallback>>,
+}
+
+impl CscSigner {
+    /// Sign with the service at `service_url`, the URL before the methods of the API
+    /// (like `https://example.com/csc/v1`).
+    pub fn builder(service_url: &str, authentication: CscAuthentication) -> CscSignerBuilder {
+        CscSignerBuilder {
+            service_url: service_url.trim_end_matches('/').to_owned(),
+            authentication,
+            version: CscVersion::default(),
+            credential_id: None,
+            client: HttpClient::default(),
+            timeout: Duration::from_secs(30),
+            authorization_callback: None,
+        }
+    }
+
+    /// Get the identifiers of the credentials of the user.
+    pub fn get_credential_ids(&self) -> &[String] {
+        &self.credential_ids
+    }
+
+    /// Get the identifier of the credential that signs.
+    pub fn get_credential_id(&self) -> &str {
+        &self.credential_id
+    }
+
+    /// Get the DER encoded certificate of the credential.
+    pub fn get_certificate(&self) -> &[u8] {
+        &self.certificates[0]
+    }
+
+    /// The credential is authorized with the OAuth 2.0 authorization code flow of the user
+    /// (authorization mode `oauth2code`), for every digest. Then the signer can not be used
+    /// with `sign_field_with_signer`: authorize the digest of a `PendingSignature` and sign
+    /// it with `sign_authorized_digest`.
+    pub fn is_authorized_with_oauth2(&self) -> bool {
+        self.oauth2_authorization
+    }
+
+    /// Authorize the signature of `digest` with `credentials/authorize`, the PIN and OTP are
+    /// asked with the `CscAuthorizationCallback` when they are needed. Returns the Signature
+    /// Activation Data (SAD), to sign the digest with `sign_authorized_digest`, like after
+    /// the `PendingSignature` was stored.
+    pub fn authorize_digest(
+        &self,
+        digest: &[u8],
+        digest_algorithm: DigestAlgorithm,
+    ) -> Result<String, Error> {
+        if self.oauth2_authorization {
+            return Err(Error::Other(format!(
+                "CSC: Credential `{}` is authorized with OAuth 2.0, use `sign_authorized_digest`.",
+                self.credential_id
+            )));
+        }
+        self.authorize(&self.session, digest, digest_algorithm, true)
+    }
+
+    /// Sign `digest` with the `authorization` that the user gave for it, like the digest of a
+    /// `PendingSignature`. Returns the signature for `complete_signature`.
+    pub fn sign_authorized_digest(
+        &self,
+        digest: &[u8],
+        digest_algorithm: DigestAlgorithm,
+        authorization: &CscDigestAuthorization,
+    ) -> Result<Vec<u8>, Error> {
+        match authorization {
+            CscDigestAuthorization::Sad(sad) => {
+                self.sign_hash(&self.session, digest, digest_algorithm, Some(sad.clone()))
+            }
+            CscDigestAuthorization::CredentialToken(access_token) => {
+                let session = Session {
+                    authorization: format!("Bearer {}", access_token),
+                    ..self.session.clone()
+                };
+                match self.version {
+                    // Version 1 gets the SAD with the token, version 2 signs with the token.
+                    CscVersion::V1 => {
+                        let sad = self.authorize(&session, digest, digest_algorithm, false)?;
+                        self.sign_hash(&self.session, digest, digest_algorithm, Some(sad))
+                    }
+                    CscVersion::V2 => self.sign_hash(&session, digest, digest_algorithm, None),
+                }
+            }
+        }
+    }
+
+    /// Authorize the signature of `digest` with `session`, asks the PIN and OTP when they
+    /// are needed and `ask_user` is `true`. Returns the SAD.
+    fn authorize(
+        &self,
+        session: &Session,
+        digest: &[u8],
+        digest_algorithm: DigestAlgorithm,
+        ask_user: bool,
+    ) -> Result<String, Error> {
+        let hash = base64::encode(digest);
+        let mut body = match self.version {
+            CscVersion::V1 => json!({
+                "credentialID": self.credential_id,
+                "numSignatures": 1,
+                "hash": [hash],
+            }),
+            CscVersion::V2 => json!({
+                "credentialID": self.credential_id,
+                "numSignatures": 1,
+                "hashes": [hash],
+                "hashAlgorithmOID": get_hash_algorithm_oid(digest_algorithm),
+            }),
+        };
+        if ask_user && (self.pin_needed || self.otp_needed) {
+            if self.online_otp {
+                session.call(
+                    "credentials/sendOTP",
+                    json!({ "credentialID": self.credential_id }),
+                )?;
+            }
+            let authorization_callback = self
+                .authorization_callback
+                .as_ref()
+                .ok_or(Error::PinCancelled)?;
+            let authorization = authorization_callback(&CscAuthorizationRequest {
+                credential_id: self.credential_id.clone(),
+                pin_needed: self.pin_needed,
+                otp_needed: self.otp_needed,
+                description: self.description.clone(),
+            })
+            .ok_or(Error::PinCancelled)?;
+            if let Some(pin) = authorization.pin {
+                body["PIN"] = pin.into();
+            }
+            if let Some(otp) = authorization.otp {
+                body["OTP"] = otp.into();
+            }
+        }
+        let response = session.call("credentials/authorize", body)?;
+        Ok(get_string(&response, "SAD")?.to_owned())
+    }
+
+    /// Sign `digest` with `signatures/signHash` of `session`.
+    fn sign_hash(
+        &self,
+        session: &Session,
+        digest: &[u8],
+        digest_algorithm: DigestAlgorithm,
+        sad: Option<String>,
+    ) -> Result<Vec<u8>, Error> {
+        let hash = base64::encode(digest);
+        let hash_algorithm = get_hash_algorithm_oid(digest_algorithm);
+        let sign_algorithm = match self.key_algorithm {
+            // `rsaEncryption`, the hash algorithm is a separate parameter.
+            KeyAlgorithm::Rsa { .. } => "1.2.840.113549.1.1.1",
+            KeyAlgorithm::Ecdsa { .. } => match digest_algorithm {
+                DigestAlgorithm::Sha256 => "1.2.840.10045.4.3.2",
+                DigestAlgorithm::Sha384 => "1.2.840.10045.4.3.3",
+                DigestAlgorithm::Sha512 => "1.2.840.10045.4.3.4",
+            },
+        };
+        let mut body = match self.version {
+            CscVersion::V1 => json!({
+                "credentialID": self.credential_id,
+                "hash": [hash],
+                "hashAlgo": hash_algorithm,
+                "signAlgo": sign_algorithm,
+            }),
+            CscVersion::V2 => json!({
+                "credentialID": self.credential_id,
+                "hashes": [hash],
+                "hashAlgorithmOID": hash_algorithm,
+                "signAlgo": sign_algorithm,
+            }),
+        };
+        if let Some(sad) = sad {
+            body["SAD"] = sad.into();
+        }
+        let response = session.call("signatures/signHash", body)?;
+        response
+            .get("signatures")
+            .and_then(|signatures| signatures.get(0))
+            .and_then(Value::as_str)
+            .and_then(|signature| base64::decode(signature).ok())
+            .ok_or_else(|| Error::from("CSC: The response of `signHash` has no signature."))
+    }
+}
+
+impl ExternalSigner for CscSigner {
+    fn get_certificates(&self) -> Result<Vec<Vec<u8>>, Error> {
+        Ok(self.certificates.clone())
+    }
+
+    fn sign_digest(
+        &self,
+        digest: &[u8],
+        digest_algorithm: DigestAlgorithm,
+    ) -> Result<Vec<u8>, Error> {
+        let sad = self.authorize_digest(digest, digest_algorithm)?;
+        self.sign_hash(&self.session, digest, digest_algorithm, Some(sad))
+    }
+    fn needs_network(&self) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for CscSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CscSigner")
+            .field("service_url", &self.session.service_url)
+            .field("version", &self.version)
+            .field("credential_id", &self.credential_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Builder for `CscSigner`.
+#[derive(Clone)]
+pub struct CscSignerBuilder {
+    service_url: String,
+    authentication: CscAuthentication,
+    version: CscVersion,
+    credential_id: Option<String>,
+    client: HttpClient,
+    timeout: Duration,
+    authorization_callback: Option<Arc<CscAuthorizationCallback>>,
+}
+
+impl CscSignerBuilder {
+    /// The version of the API of the service, by default `CscVersion::V1`.
+    pub fn version(mut self, version: CscVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sign with the credential `credential_id`, by default the first credential of the
+    /// user is used.
+    pub fn credential_id(mut self, credential_id: &str) -> Self {
+        self.credential_id = Some(credential_id.to_owned());
+        self
+    }
+
+    /// Send the requests with `client`, like with a proxy.
+    pub fn http_client(mut self, client: HttpClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// The maximum time of a request, by default 30 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Ask the PIN and OTP with `authorization_callback`. Without it signing fails with
+    /// `Error::PinCancelled` when the service needs them.
+    pub fn authorization_callback(
+        mut self,
+        authorization_callback: Arc<CscAuthorizationCallback>,
+    ) -> Self {
+        self.authorization_callback = Some(authorization_callback);
+        self
+    }
+
+    /// Log in at the service, and read the certificates and the authorization of the
+    /// credential.
+    pub fn build(self) -> Result<CscSigner, Error> {
+        let mut session = Session {
+            service_url: self.service_url,
+            client: self.client,
+            timeout: self.timeout,
+            authorization: String::new(),
+        };
+        let access_token = match &self.authentication {
+            CscAuthentication::AccessToken(access_token) => access_token.clone(),
+            CscAuthentication::ClientCredentials {
+                client_id,
+                client_secret,
+            } => {
+                let url = format!("{}/oauth2/token", session.service_url);
+                let form = [
+                    ("grant_type", "client_credentials"),
+                    ("client_id", client_id.as_str()),
+                    ("client_secret", client_secret.as_str()),
+                ];
+                let response = session
+                    .client
+                    .post_form(&url, &form, session.timeout)
+                    .map_err(|error| Error::Other(format!("CSC: {}", error)))?;
+                get_string(&response, "access_token")?.to_owned()
+            }
+            CscAuthentication::Basic { username, password } => {
+                session.authorization = format!(
+                    "Basic {}",
+                    base64::encode(format!("{}:{}", username, password))
+                );
+                let response = session.call("auth/login", json!({}))?;
+                get_string(&response, "access_token")?.to_owned()
+            }
+        };
+        session.authorization = format!("Bearer {}", access_token);
+
+        let response = session.call("credentials/list", json!({ "maxResults": 100 }))?;
+        let credential_ids: Vec<String> = response
+            .get("credentialIDs")
+            .and_then(Value::as_array)
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|id| id.as_str().map(str::to_owned))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let credential_id = match self.credential_id {
+            Some(credential_id) => credential_id,
+            None => credential_ids
+                .first()
+                .cloned()
+                .ok_or_else(|| Error::from("CSC: The user has no credentials."))?,
+        };
+
+        let info = session.call(
+            "credentials/info",
+            json!({
+                "credentialID": credential_id,
+                "certificates": "chain",
+                "certInfo": false,
+                "authInfo": true,
+            }),
+        )?;
+        let certificates: Vec<Vec<u8>> = info
+            .pointer("/cert/certificates")
+            .and_then(Value::as_array)
+            .map(|certificates| {
+                certificates
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .filter_map(|certificate| base64::decode(certificate).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let key_algorithm = certificates
+            .first()
+            .and_then(|certificate| KeyAlgorithm::from_certificate(certificate))
+            .ok_or_else(|| {
+                Error::Other(format!(
+                    "CSC: Credential `{}` has no certificate with an RSA or ECDSA key.",
+                    credential_id
+                ))
+            })?;
+
+        // Version 1 has the `authMode` with the `PIN` and `OTP`, version 2 can have the
+        // `auth` with the `mode` and the objects that are needed instead.
+        let auth_mode = info
+            .get("authMode")
+            .or_else(|| info.pointer("/auth/mode"))
+            .and_then(Value::as_str)
+            .unwrap_or("explicit");
+        let has_auth_object = |id: &str| {
+            info.pointer("/auth/objects")
+                .and_then(Value::as_array)
+                .is_some_and(|objects| objects.iter().any(|object| object["id"] == id))
+        };
+        let pin_needed = auth_mode == "explicit"
+            && (info.pointer("/PIN/presence").and_then(Value::as_str) == Some("true")
+                || has_auth_object("PIN"));
+        let otp_needed = auth_mode == "explicit"
+            && (info.pointer("/OTP/presence").and_then(Value::as_str) == Some("true")
+                || has_auth_object("OTP"));
+        let online_otp =
+            otp_needed && info.pointer("/OTP/type").and_then(Value::as_str) == Some("online");
+        let description = ["/PIN/label", "/PIN/description", "/OTP/description"]
+            .iter()
+            .find_map(|pointer| info.pointer(pointer).and_then(Value::as_str))
+            .map(str::to_owned);
+
+        Ok(CscSigner {
+            session,
+            version: self.version,
+            credential_ids,
+            credential_id,
+            certificates,
+            key_algorithm,
+            pin_needed,
+            otp_needed,
+            online_otp,
+            description,
+            oauth2_authorization: auth_mode == "oauth2code",
+            authorization_callback: self.authorization_callback,
+        })
+    }
+}
+
+impl fmt::Debug for CscSignerBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CscSignerBuilder")
+            .field("service_url", &self.service_url)
+            .field("authentication", &self.authentication)
+            .field("version", &self.version)
+            .field("credential_id", &self.credential_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The dotted object identifier of the digest algorithm, for the API.
+fn get_hash_algorithm_oid(digest_algorithm: DigestAlgorithm) -> &'static str {
+    match digest_algorithm {
+        DigestAlgorithm::Sha256 => "2.16.840.1.101.3.4.2.1",
+        DigestAlgorithm::Sha384 => "2.16.840.1.101.3.4.2.2",
+        DigestAlgorithm::Sha512 => "2.16.840.1.101.3.4.2.3",
+    }
+}
+
+/// Get the string `key` of a JSON `response`.
+fn get_string<'a>(response: &'a Value, key: &str) -> Result<&'a str, Error> {
+    response
+        .get(key)
+        .and_then(Value::as_str)
+        .ok_or_else(|| Error::Other(format!("CSC: The response has no `{}`.", key)))
+}
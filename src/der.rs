@@ -0,0 +1,53 @@
+//! Reading and writing DER encoded values (ASN.1), for the parts of signatures and
+//! certificates that are read or created without the CMS crate.
+
+use bcder::Mode;
+
+/// Read the DER encoded value at the start of `data`.
+/// Returns the tag, the content and the data after the value.
+/// The value is checked by `bcder` in DER mode, with its nested values: lengths must be
+/// definite and as short as possible. Tags with a number larger than 30 are not used in
+/// the values that are read, so they are rejected.
+pub(crate) fn read_der(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *data.first()?;
+    if tag & 0x1f == 0x1f || tag == 0 {
+        return None;
+    }
+    let length_byte = *data.get(1)?;
+    let (header_length, length) = if length_byte < 0x80 {
+        (2, usize::from(length_byte))
+    } else {
+        let length_size = usize::from(length_byte & 0x7f);
+        if length_size == 0 || length_size > 4 {
+            return None;
+        }
+        let length = data
+            .get(2..2 + length_size)?
+            .iter()
+            .fold(0usize, |length, byte| (length << 8) | usize::from(*byte));
+        (2 + length_size, length)
+    };
+    let content = data.get(header_length..header_length.checked_add(length)?)?;
+    let value = &data[..header_length + length];
+    Mode::Der.decode(value, |cons| cons.skip_all()).ok()?;
+    Some((tag, content, &data[header_length + length..]))
+}
+
+/// Encode a DER value with the `tag` and `content`.
+pub(crate) fn write_der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut data = vec![tag];
+    if content.len() < 0x80 {
+        data.push(content.len() as u8);
+    } else {
+        let length: Vec<u8> = content
+            .len()
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|byte| *byte == 0)
+            .collect();
+        data.push(0x80 | length.len() as u8);
+        data.extend_from_slice(&length);
+    }
+    data.extend_from_slice(content);
+    data
+}
@@ -1,16 +1,22 @@
 use crate::error::Error;
-use crate::{ByteRange, PDFSigningDocument, UserSignatureInfo};
-use cryptographic_message_syntax::{Bytes, Oid, SignedDataBuilder};
+#[cfg(feature = "signing")]
+use crate::signing_options::SigningOptions;
+#[cfg(feature = "signing")]
+use crate::UserSignatureInfo;
+use crate::{ByteRange, PDFSigningDocument};
+#[cfg(feature = "signing")]
+use cryptographic_message_syntax::{Bytes, Oid, SignedDataBuilder, SignerBuilder};
 use lopdf::ObjectId;
-use std::io::Write;
 
 impl PDFSigningDocument {
     /// Digitally signs the document using a cryptographically secure algorithm.
     /// Note that using this function will prevent you from changing anything else about the document.
     /// Changing the document in any other way will invalidate the cryptographic check.
+    #[cfg(feature = "signing")]
     pub(crate) fn digitally_sign_document(
         &self,
         user_info: &UserSignatureInfo,
+        options: &SigningOptions,
     ) -> Result<Vec<u8>, Error> {
         // TODO: Code should be enabled in the future, do not remove.
         // Decompose `pdf_document` into it parts.
@@ -39,17 +45,85 @@ impl PDFSigningDocument {
         let mut pdf_file_data: Vec<u8> = Vec::new();
         self.write_document(&mut pdf_file_data)?;
 
-        let (byte_range, pdf_file_data) = Self::set_next_byte_range(pdf_file_data);
+        let (byte_range, pdf_file_data) =
+            Self::set_next_byte_range(pdf_file_data, options.placeholder_size)?;
 
-        let first_part = &pdf_file_data[byte_range.get_range(0)];
-        let second_part = &pdf_file_data[byte_range.get_range(1)];
+        let vec = Self::get_signed_bytes(&pdf_file_data, &byte_range)?;
+
+        // Calculate file hash and sign it using the users key
+        let build_signature = |time_stamp_url: Option<&str>| {
+            SignedDataBuilder::default()
+                .content_external(vec.clone())
+                .content_type(Oid(Bytes::copy_from_slice(
+                    cryptographic_message_syntax::asn1::rfc5652::OID_ID_DATA.as_ref(),
+                )))
+                .signer(options.configure_signer(
+                    SignerBuilder::new(
+                        user_info.user_signing_keys.as_ref(),
+                        user_info.user_certificate.clone(),
+                    ),
+                    time_stamp_url,
+                )?)
+                .build_der()
+                .map_err(|error| Error::Other(format!("Signature: {}", error)))
+        };
+        // The time stamp is requested by this crate, with a timeout and the nonce checked.
+        #[cfg(feature = "network")]
+        let signature = match options.time_stamp_urls.is_empty() {
+            true => build_signature(None)?,
+            false => crate::time_stamp::add_time_stamp(
+                &build_signature(None)?,
+                &options.time_stamp_urls,
+                options.time_stamp_timeout,
+                &options.http_client,
+            )?,
+        };
+        // The time stamp is requested by the CMS crate, the next TSA is tried when it fails.
+        #[cfg(not(feature = "network"))]
+        let signature = {
+            let mut result = build_signature(None);
+            for (index, url) in options.time_stamp_urls.iter().enumerate() {
+                result = build_signature(Some(url));
+                match &result {
+                    Ok(_) => break,
+                    Err(error) if index + 1 < options.time_stamp_urls.len() => {
+                        tracing::warn!("Time stamp `{}`: {}", url, error)
+                    }
+                    Err(_) => {}
+                }
+            }
+            result?
+        };
+
+        #[cfg(feature = "debug")]
+        {
+            use std::io::Write;
+            let mut file = std::fs::File::create("./signature.der")?;
+            file.write_all(&signature)?;
+        }
+
+        // Write signature to file
+        Self::set_content(pdf_file_data, signature, options.placeholder_size)
+    }
+
+    /// Get the bytes covered by the `byte_range`, the document without the `Contents`.
+    pub(crate) fn get_signed_bytes(
+        pdf_file_data: &[u8],
+        byte_range: &ByteRange,
+    ) -> Result<Vec<u8>, Error> {
+        let first_part = pdf_file_data
+            .get(byte_range.get_range(0)?)
+            .ok_or_else(|| Error::from("ByteRange: First range is outside the document."))?;
+        let second_part = pdf_file_data
+            .get(byte_range.get_range(1)?)
+            .ok_or_else(|| Error::from("ByteRange: Second range is outside the document."))?;
 
         // Used for debugging
-        // log::trace!(
+        // tracing::trace!(
         //     "End of first part: {}",
         //     String::from_utf8_lossy(&first_part[(byte_range.0[1] - 15)..])
         // );
-        // log::trace!(
+        // tracing::trace!(
         //     "Start of second part: {}...{}",
         //     String::from_utf8_lossy(&second_part[0..10]),
         //     String::from_utf8_lossy(&second_part[(second_part.len() - 5)..])
@@ -59,31 +133,11 @@ impl PDFSigningDocument {
         let mut vec = Vec::with_capacity(byte_range.get_capacity_inclusive());
         vec.extend_from_slice(first_part);
         vec.extend_from_slice(second_part);
-
-        // Calculate file hash and sign it using the users key
-        let signature = SignedDataBuilder::default()
-            .content_external(vec)
-            .content_type(Oid(Bytes::copy_from_slice(
-                cryptographic_message_syntax::asn1::rfc5652::OID_ID_DATA.as_ref(),
-            )))
-            .signer(user_info.user_signing_keys.clone())
-            .build_der()
-            .unwrap();
-
-        #[cfg(feature = "debug")]
-        {
-            let mut file = std::fs::File::create("./signature.der").unwrap();
-            file.write_all(&signature).unwrap();
-        }
-
-        // Write signature to file
-        let pdf_file_data = Self::set_content(pdf_file_data, signature);
-
-        Ok(pdf_file_data)
+        Ok(vec)
     }
 
-    // TODO: Not used, see start of `digitally_sign_document()`
-    #[allow(dead_code)]
+    /// Add the certification signature `first_signature_id` to the permissions (`Perms`)
+    /// of the catalog, see `add_doc_mdp`.
     pub(crate) fn add_digital_signature_data(
         &mut self,
         first_signature_id: ObjectId,
@@ -105,12 +159,12 @@ impl PDFSigningDocument {
             .new_document
             .get_object_mut(root_obj_id)?
             .as_dict_mut()?;
-        log::debug!("Root: {:?}", root);
+        tracing::debug!("Root: {:?}", root);
 
         if root.has(b"Perms") {
-            log::info!("Document already has `Perms` field.");
+            tracing::info!("Document already has `Perms` field.");
             let perms = root.get_mut(b"Perms")?.as_dict_mut()?;
-            log::debug!("Perms: {:?}", perms);
+            tracing::debug!("Perms: {:?}", perms);
             // Add `DocMDP` reference to existing dict
             perms.set("DocMDP", Reference(first_signature_id));
         } else {
@@ -124,22 +178,46 @@ impl PDFSigningDocument {
         Ok(())
     }
 
+    /// Check that `needed` bytes fit in the placeholder of the signature.
+    /// `placeholder_size` can come from a deserialized `PendingSignature`, so it is
+    /// checked against the searched part of the placeholder and the document as well.
+    pub(crate) fn check_placeholder_size(
+        placeholder_size: usize,
+        needed: usize,
+        document_length: usize,
+    ) -> Result<(), Error> {
+        // The written `ByteRange` takes up to 4 bytes of the placeholder, and the first
+        // 51 zeros (26 bytes) are searched for.
+        let available = placeholder_size.saturating_sub(4);
+        if placeholder_size < 26 || needed > available {
+            return Err(Error::PlaceholderTooSmall {
+                available,
+                needed: needed.max(22),
+            });
+        }
+        if placeholder_size.saturating_mul(2) > document_length {
+            return Err(Error::from(
+                "The placeholder of the signature is larger than the document.",
+            ));
+        }
+        Ok(())
+    }
+
     // Find and set the `Content` field in the signature
-    fn set_content(mut pdf_file_data: Vec<u8>, content: Vec<u8>) -> Vec<u8> {
+    pub(crate) fn set_content(
+        mut pdf_file_data: Vec<u8>,
+        content: Vec<u8>,
+        placeholder_size: usize,
+    ) -> Result<Vec<u8>, Error> {
         // Determine the byte ranged
         // Find the `Content` part of the file
         let pattern_prefix = b"/Contents<";
-        let pattern_content = vec![48u8; 18000]; // 48 = 0x30 = `0`
+        // The start of the placeholder that is searched for, 48 = 0x30 = `0`.
+        let pattern_content = [48u8; 51];
 
-        if content.len() > pattern_content.len() {
-            panic!(
-                "Length of content is to long. Available: {}, Needed: {}",
-                pattern_content.len(),
-                content.len()
-            );
-        }
+        Self::check_placeholder_size(placeholder_size, content.len(), pdf_file_data.len())?;
         let mut pattern = pattern_prefix.to_vec();
-        pattern.extend_from_slice(&pattern_content[..=50]); // Just add the first part, rest will be okay
+        pattern.extend_from_slice(&pattern_content);
 
         // Find the pattern in the PDF file binary
         let found_at = Self::find_binary_pattern(&pdf_file_data, &pattern);
@@ -163,40 +241,46 @@ impl PDFSigningDocument {
                     new_contents_vec,
                 );
 
-                pdf_file_data
+                Ok(pdf_file_data)
             }
             None => {
                 // Pattern was not found, add debug info
-                #[cfg(debug_assertions)]
+                #[cfg(all(debug_assertions, feature = "fs"))]
                 {
+                    use std::io::Write;
                     let crashed_file = "./pdf_missing_pattern.pdf";
-                    let mut file = std::fs::File::create(crashed_file).unwrap();
-                    file.write_all(&pdf_file_data).unwrap();
-                    log::error!(
+                    if let Ok(mut file) = std::fs::File::create(crashed_file) {
+                        let _ = file.write_all(&pdf_file_data);
+                    }
+                    tracing::error!(
                         "Pattern not found `{}`. Saved file to: `{}`.",
                         String::from_utf8_lossy(&pattern),
                         crashed_file
                     );
                 }
-                panic!(
+                Err(Error::Other(format!(
                     "Pattern not found `{}`. PDF Signing bug in the code.",
                     String::from_utf8_lossy(&pattern),
-                );
+                )))
             }
         }
     }
 
     /// Set the next found byte `ByteRange` that still has the default values.
-    fn set_next_byte_range(mut pdf_file_data: Vec<u8>) -> (ByteRange, Vec<u8>) {
+    pub(crate) fn set_next_byte_range(
+        mut pdf_file_data: Vec<u8>,
+        placeholder_size: usize,
+    ) -> Result<(ByteRange, Vec<u8>), Error> {
         // Determine the byte ranged
         // Find the `Content` part of the file
         let pattern_prefix = b"/ByteRange[0 10000 20000 10000]/Contents<";
-        let pattern_content = vec![48u8; 18000]; // 48 = 0x30 = `0`
+        let pattern_content = vec![48u8; placeholder_size * 2]; // 48 = 0x30 = `0`
         let mut pattern = pattern_prefix.to_vec();
         pattern.extend_from_slice(&pattern_content[..=50]); // Just add the first part, rest will be okay
 
         // Search for `ByteRange` tag with default values
-        let found_at = Self::find_binary_pattern(&pdf_file_data, &pattern).unwrap();
+        let found_at = Self::find_binary_pattern(&pdf_file_data, &pattern)
+            .ok_or_else(|| Error::from("ByteRange: Signature placeholder not found."))?;
 
         // Calculate `ByteRange`
         let fixed_byte_range_width = 25;
@@ -229,16 +313,16 @@ impl PDFSigningDocument {
         // Have at least "0 10000 20000 10000".len() + "{}".len() `0`s. (and even number)
         let mut new_byte_range_string = format!(
             "/ByteRange[{}]/Contents<0000000000000000000000",
-            byte_range.to_list(fixed_byte_range_width).unwrap()
+            byte_range.to_list(fixed_byte_range_width)?
         );
 
         // The `Contents<...>` always need to be an even number of chars
         if pattern_prefix.len() % 2 != new_byte_range_string.len() % 2 {
-            log::trace!("Added space to `ByteRange`");
+            tracing::trace!("Added space to `ByteRange`");
             // Add space to make equal
             new_byte_range_string = format!(
                 "/ByteRange[{} ]/Contents<0000000000000000000000",
-                byte_range.to_list(fixed_byte_range_width).unwrap()
+                byte_range.to_list(fixed_byte_range_width)?
             );
         }
         let new_byte_range_string = new_byte_range_string.as_bytes().to_vec();
@@ -248,7 +332,7 @@ impl PDFSigningDocument {
             new_byte_range_string,
         );
 
-        (byte_range, pdf_file_data)
+        Ok((byte_range, pdf_file_data))
     }
 
     /// Finds the first instance matching the pattern.
@@ -0,0 +1,132 @@
+//! Configure how a document is read, see `PDFSigningDocument::builder`.
+
+use crate::{Error, PDFSigningDocument, ParsingLimits};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// When the form fields (`AcroForm`) are loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AcroFormLoading {
+    /// Load the form fields when they are needed (like when filling or signing),
+    /// or with `load_all`.
+    #[default]
+    Lazy,
+    /// Load the form fields when the document is read,
+    /// so errors in the form are returned immediately.
+    Eager,
+}
+
+/// Builder to read a document with the chosen settings. The settings that are not set
+/// work like `PDFSigningDocument::read_from`.
+#[derive(Debug, Clone, Default)]
+pub struct PDFSigningDocumentBuilder {
+    password: String,
+    recovery: bool,
+    acro_form_loading: AcroFormLoading,
+    limits: ParsingLimits,
+}
+
+impl PDFSigningDocument {
+    pub fn builder() -> PDFSigningDocumentBuilder {
+        PDFSigningDocumentBuilder::default()
+    }
+}
+
+impl PDFSigningDocumentBuilder {
+    /// The user or owner password of an encrypted document, see `read_from_with_password`.
+    pub fn password(mut self, password: &str) -> Self {
+        self.password = password.to_owned();
+        self
+    }
+
+    /// Rebuild the cross-reference table when it is damaged, see `read_from_lenient`.
+    pub fn recovery(mut self, recovery: bool) -> Self {
+        self.recovery = recovery;
+        self
+    }
+
+    /// When the form fields are loaded, default `AcroFormLoading::Lazy`.
+    pub fn acro_form_loading(mut self, acro_form_loading: AcroFormLoading) -> Self {
+        self.acro_form_loading = acro_form_loading;
+        self
+    }
+
+    /// Reject documents with more than `max_objects` objects, see `ParsingLimits::max_objects`.
+    pub fn max_objects(mut self, max_objects: usize) -> Self {
+        self.limits.max_objects = Some(max_objects);
+        self
+    }
+
+    /// The limits for untrusted documents, default `ParsingLimits::default()`.
+    /// They are checked before and after the document is parsed, see `ParsingLimits`.
+    pub fn limits(mut self, limits: ParsingLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn read_from<R: std::io::Read>(
+        self,
+        mut reader: R,
+        file_name: String,
+    ) -> Result<PDFSigningDocument, Error> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        self.load(data, file_name)
+    }
+
+    /// Read the document from the bytes of the file, without copying them.
+    pub fn read_from_bytes(
+        self,
+        data: Vec<u8>,
+        file_name: String,
+    ) -> Result<PDFSigningDocument, Error> {
+        self.load(data, file_name)
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn read<P: AsRef<Path>>(
+        self,
+        path: P,
+        file_name: String,
+    ) -> Result<PDFSigningDocument, Error> {
+        self.load(std::fs::read(path)?, file_name)
+    }
+
+    /// Read the document from a memory-mapped file, see `PDFSigningDocument::read_mmap`.
+    /// Recovery of damaged documents is not supported for memory-mapped files.
+    ///
+    /// # Safety
+    ///
+    /// See `PDFSigningDocument::read_mmap`.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn read_mmap<P: AsRef<Path>>(
+        self,
+        path: P,
+        file_name: String,
+    ) -> Result<PDFSigningDocument, Error> {
+        if self.recovery {
+            return Err(Error::from(
+                "Recovery: Memory-mapped files can not be recovered.",
+            ));
+        }
+        let mut document =
+            PDFSigningDocument::load_mmap(path, file_name, &self.password, &self.limits)?;
+        if self.acro_form_loading == AcroFormLoading::Eager {
+            document.load_all()?;
+        }
+        Ok(document)
+    }
+
+    fn load(self, data: Vec<u8>, file_name: String) -> Result<PDFSigningDocument, Error> {
+        let mut document = match self.recovery {
+            true => {
+                PDFSigningDocument::load_lenient(data, file_name, &self.password, &self.limits)?
+            }
+            false => PDFSigningDocument::load(data, file_name, &self.password, &self.limits)?,
+        };
+        if self.acro_form_loading == AcroFormLoading::Eager {
+            document.load_all()?;
+        }
+        Ok(document)
+    }
+}
@@ -0,0 +1,143 @@
+//! Read and change the document information dictionary (`Info`), like the title and author.
+
+use crate::journal::JournalChange;
+use crate::lopdf_utils::{date_string, decode_text_string, text_string};
+use crate::{Error, InsertImage, InsertImageToPage, PDFSigningDocument};
+use chrono::{DateTime, Utc};
+use lopdf::{dictionary, Dictionary, Object};
+
+impl PDFSigningDocument {
+    /// Get the title of the document (`Title`).
+    pub fn get_title(&self) -> Option<String> {
+        self.get_info_text(b"Title")
+    }
+
+    /// Set the title of the document (`Title`).
+    pub fn set_title(&mut self, title: &str) -> Result<(), Error> {
+        self.set_info_text("Title", title)
+    }
+
+    /// Get the author of the document (`Author`).
+    pub fn get_author(&self) -> Option<String> {
+        self.get_info_text(b"Author")
+    }
+
+    /// Set the author of the document (`Author`).
+    pub fn set_author(&mut self, author: &str) -> Result<(), Error> {
+        self.set_info_text("Author", author)
+    }
+
+    /// Get the subject of the document (`Subject`).
+    pub fn get_subject(&self) -> Option<String> {
+        self.get_info_text(b"Subject")
+    }
+
+    /// Set the subject of the document (`Subject`).
+    pub fn set_subject(&mut self, subject: &str) -> Result<(), Error> {
+        self.set_info_text("Subject", subject)
+    }
+
+    /// Get the keywords of the document (`Keywords`), as written in the document.
+    pub fn get_keywords(&self) -> Option<String> {
+        self.get_info_text(b"Keywords")
+    }
+
+    /// Set the keywords of the document (`Keywords`), like `contract, HR`.
+    pub fn set_keywords(&mut self, keywords: &str) -> Result<(), Error> {
+        self.set_info_text("Keywords", keywords)
+    }
+
+    /// Get the date the document was created (`CreationDate`).
+    pub fn get_creation_date(&self) -> Option<DateTime<Utc>> {
+        self.get_info_date(b"CreationDate")
+    }
+
+    /// Set the date the document was created (`CreationDate`).
+    pub fn set_creation_date(&mut self, date: DateTime<Utc>) -> Result<(), Error> {
+        self.set_info_date("CreationDate", date)
+    }
+
+    /// Get the date the document was last changed (`ModDate`).
+    pub fn get_modification_date(&self) -> Option<DateTime<Utc>> {
+        self.get_info_date(b"ModDate")
+    }
+
+    /// Set the date the document was last changed (`ModDate`).
+    pub fn set_modification_date(&mut self, date: DateTime<Utc>) -> Result<(), Error> {
+        self.set_info_date("ModDate", date)
+    }
+
+    /// Get the latest version of the `Info` dictionary.
+    fn get_info(&self) -> Option<&Dictionary> {
+        let info = self.raw_document.new_document.trailer.get(b"Info").ok()?;
+        self.resolve_latest_object(info)?.as_dict().ok()
+    }
+
+    fn get_info_text(&self, key: &[u8]) -> Option<String> {
+        let value = self.get_info()?.get(key).ok()?;
+        let text = self.resolve_latest_object(value)?.as_str().ok()?;
+        Some(decode_text_string(text))
+    }
+
+    fn get_info_date(&self, key: &[u8]) -> Option<DateTime<Utc>> {
+        let value = self.get_info()?.get(key).ok()?;
+        let date = self.resolve_latest_object(value)?.as_datetime()?;
+        Some(date.with_timezone(&Utc))
+    }
+
+    fn set_info_text(&mut self, key: &str, text: &str) -> Result<(), Error> {
+        self.set_info_value(key, text_string(text))?;
+        if self.pdfa_mode {
+            self.update_xmp_metadata(key, text)?;
+        }
+        self.record_change(JournalChange::DocumentInfoChanged {
+            key: key.to_owned(),
+            value: text.to_owned(),
+        });
+        Ok(())
+    }
+
+    fn set_info_date(&mut self, key: &str, date: DateTime<Utc>) -> Result<(), Error> {
+        self.set_info_value(key, date_string(date))?;
+        if self.pdfa_mode {
+            self.update_xmp_metadata(key, &date.format("%Y-%m-%dT%H:%M:%S+00:00").to_string())?;
+        }
+        self.record_change(JournalChange::DocumentInfoChanged {
+            key: key.to_owned(),
+            value: date.to_rfc3339(),
+        });
+        Ok(())
+    }
+
+    /// Set a value in the `Info` dictionary with an incremental update,
+    /// so existing signatures stay valid.
+    fn set_info_value(&mut self, key: &str, value: Object) -> Result<(), Error> {
+        let info = self.raw_document.new_document.trailer.get(b"Info").ok();
+        let info_id = match info {
+            Some(Object::Reference(info_id)) => {
+                let info_id = *info_id;
+                self.opt_clone_object_to_new_document(info_id)?;
+                info_id
+            }
+            // A direct (or missing) dictionary is moved to a new object.
+            _ => {
+                let info = match info {
+                    Some(Object::Dictionary(info)) => info.clone(),
+                    _ => dictionary! {},
+                };
+                let info_id = self.add_object(info);
+                self.raw_document
+                    .new_document
+                    .trailer
+                    .set("Info", Object::Reference(info_id));
+                info_id
+            }
+        };
+        self.raw_document
+            .new_document
+            .get_object_mut(info_id)?
+            .as_dict_mut()?
+            .set(key, value);
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+//! Add validation data to the Document Security Store (`DSS`) of documents that are
+//! already signed, also by other tools, see `add_dss`.
+
+use crate::acro_form::FormComponent;
+use crate::der::read_der;
+use crate::{Error, PDFSigningDocument, ValidationData};
+use lopdf::{Dictionary, Object, ObjectId};
+use sha1::{Digest, Sha1};
+
+/// Validation data to add to the `DSS` with `add_dss`, create it with `DssUpdate::builder`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DssUpdate {
+    validation_data: ValidationData,
+    /// The validation data of signature fields, by fully qualified field name.
+    signatures: Vec<(String, ValidationData)>,
+}
+
+impl DssUpdate {
+    pub fn builder() -> DssUpdateBuilder {
+        DssUpdateBuilder {
+            update: DssUpdate::default(),
+        }
+    }
+}
+
+/// Builder for `DssUpdate`.
+#[derive(Debug, Clone)]
+pub struct DssUpdateBuilder {
+    update: DssUpdate,
+}
+
+impl DssUpdateBuilder {
+    /// Add validation data to the `DSS` that does not belong to one signature.
+    pub fn validation_data(mut self, validation_data: ValidationData) -> Self {
+        let data = &mut self.update.validation_data;
+        data.certificates.extend(validation_data.certificates);
+        data.ocsp_responses.extend(validation_data.ocsp_responses);
+        data.crls.extend(validation_data.crls);
+        self
+    }
+
+    /// Add the validation data of the signed signature field `field_name` (fully qualified
+    /// name) to the `DSS`, and to the Validation Related Information (`VRI`) of the
+    /// signature. Call it for every signature.
+    pub fn signature(mut self, field_name: &str, validation_data: ValidationData) -> Self {
+        self.update
+            .signatures
+            .push((field_name.to_owned(), validation_data));
+        self
+    }
+
+    pub fn build(self) -> DssUpdate {
+        self.update
+    }
+}
+
+impl PDFSigningDocument {
+    /// Add the validation data of `update` to the `DSS` in a new revision, so signatures
+    /// that are created without it (like by other tools) get the PAdES B-LT level.
+    /// The data that is already in the `DSS` is reused. Returns `Error::FieldNotFound` when
+    /// a signature field of the update does not exist, or is not signed.
+    /// Returns the document, this document is reloaded with the changes.
+    pub fn add_dss(&mut self, update: &DssUpdate) -> Result<Vec<u8>, Error> {
+        self.load_all()?;
+        let forms = self.acro_form.clone().unwrap_or_default();
+        let mut dss = self.get_latest_dss()?;
+        self.add_dss_validation_data(&mut dss, &update.validation_data);
+
+        let mut vri = dss
+            .get(b"VRI")
+            .ok()
+            .and_then(|vri| self.resolve_latest_object(vri))
+            .and_then(|vri| vri.as_dict().ok())
+            .cloned()
+            .unwrap_or_default();
+        for (field_name, validation_data) in &update.signatures {
+            let signature = forms.iter().find_map(|form| {
+                if form.get_fully_qualified_field_name() != Some(field_name.as_str()) {
+                    return None;
+                }
+                match form.get_form_component() {
+                    FormComponent::SignedSignature {
+                        contents,
+                        sub_filter,
+                        ..
+                    } => Some((contents, sub_filter)),
+                    _ => None,
+                }
+            });
+            let Some((contents, sub_filter)) = signature else {
+                return Err(Error::FieldNotFound {
+                    name: field_name.clone(),
+                });
+            };
+            let [certificates, ocsp_responses, crls] =
+                self.add_dss_validation_data(&mut dss, validation_data);
+            let key = get_vri_key(contents, sub_filter.as_deref());
+            let mut entry = vri
+                .get(key.as_bytes())
+                .ok()
+                .and_then(|entry| self.resolve_latest_object(entry))
+                .and_then(|entry| entry.as_dict().ok())
+                .cloned()
+                .unwrap_or_default();
+            for (key, references) in [
+                ("Cert", certificates),
+                ("OCSP", ocsp_responses),
+                ("CRL", crls),
+            ] {
+                add_references(&mut entry, key, references);
+            }
+            vri.set(key, entry);
+        }
+        if !vri.is_empty() {
+            dss.set("VRI", vri);
+        }
+
+        self.set_dss(dss)?;
+        let data = self.save_to_bytes()?;
+        self.copy_from(self.reload_from(data.clone(), self.file_name.clone())?);
+        self.load_all()?;
+        Ok(data)
+    }
+}
+
+/// The key of a signature in the `VRI`: the uppercase hex encoded SHA-1 hash of the
+/// `Contents`. The padding after a time stamp token is not hashed, like other tools do.
+fn get_vri_key(contents: &[u8], sub_filter: Option<&str>) -> String {
+    let mut contents = contents;
+    if sub_filter == Some("ETSI.RFC3161") {
+        if let Some((_, _, rest)) = read_der(contents) {
+            contents = &contents[..contents.len() - rest.len()];
+        }
+    }
+    Sha1::digest(contents)
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect()
+}
+
+/// Add the `references` that are not in the array `key` of the VRI `entry` yet.
+fn add_references(entry: &mut Dictionary, key: &str, references: Vec<ObjectId>) {
+    if references.is_empty() {
+        return;
+    }
+    let mut array = entry
+        .get(key.as_bytes())
+        .and_then(Object::as_array)
+        .cloned()
+        .unwrap_or_default();
+    for reference in references {
+        if !array
+            .iter()
+            .any(|item| item.as_reference().ok() == Some(reference))
+        {
+            array.push(Object::Reference(reference));
+        }
+    }
+    entry.set(key, array);
+}
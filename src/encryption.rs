@@ -0,0 +1,769 @@
+//! Standard security handler: decrypt password protected PDFs (RC4, AES-128 and AES-256)
+//! and encrypt documents with AES-256.
+
+use crate::Error;
+use aes::cipher::block_padding::{NoPadding, Pkcs7};
+use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use lopdf::{
+    dictionary, Dictionary, Document, IncrementalDocument, Object, ObjectId, ObjectStream, Reader,
+    StringFormat,
+};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+/// Padding added to passwords of revision 2 to 4 (Algorithm 2 of the PDF specification).
+const PASSWORD_PADDING: [u8; 32] = [
+    0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08,
+    0x2E, 0x2E, 0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+/// `Type` of object streams while they are still encrypted, so they are not parsed when loading.
+const ENCRYPTED_OBJECT_STREAM: &[u8] = b"EncryptedObjStm";
+
+/// How strings and streams are encrypted (`CFM` of the crypt filter).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CryptMethod {
+    /// `Identity`: the data is not encrypted.
+    None,
+    Rc4,
+    /// AES-128 (`AESV2`).
+    Aes128,
+    /// AES-256 (`AESV3`).
+    Aes256,
+}
+
+/// Settings to encrypt the document with AES-256.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EncryptionOptions {
+    /// Password needed to open the document, can be empty.
+    pub user_password: String,
+    /// Password that gives all permissions. The user password is used when it is empty.
+    pub owner_password: String,
+    /// What is allowed when the document is opened with the user password.
+    pub permissions: Permissions,
+}
+
+/// What is allowed when the document is opened with the user password (`P`).
+/// Viewers are expected to follow these, the owner password allows everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct Permissions {
+    /// Print the document, in a low quality when `print_high_quality` is `false`.
+    pub print: bool,
+    /// Print the document in the best quality.
+    pub print_high_quality: bool,
+    /// Change the content of the document.
+    pub modify: bool,
+    /// Copy or extract text and images.
+    pub copy: bool,
+    /// Add or change annotations and fill in form fields.
+    pub annotate: bool,
+    /// Fill in form fields (including signature fields), also when `annotate` is `false`.
+    pub fill_forms: bool,
+    /// Extract text and images for accessibility, like screen readers.
+    pub extract_for_accessibility: bool,
+    /// Insert, rotate or delete pages and create bookmarks.
+    pub assemble: bool,
+}
+
+impl Default for Permissions {
+    /// Everything is allowed.
+    fn default() -> Self {
+        Permissions {
+            print: true,
+            print_high_quality: true,
+            modify: true,
+            copy: true,
+            annotate: true,
+            fill_forms: true,
+            extract_for_accessibility: true,
+            assemble: true,
+        }
+    }
+}
+
+impl Permissions {
+    /// Get the value of `P` in the `Encrypt` dictionary, bit 1 is the lowest bit.
+    fn to_value(self) -> i32 {
+        let flags = [
+            (3, self.print),
+            (4, self.modify),
+            (5, self.copy),
+            (6, self.annotate),
+            (9, self.fill_forms),
+            (10, self.extract_for_accessibility),
+            (11, self.assemble),
+            (12, self.print_high_quality),
+        ];
+        // Bit 7, 8 and 13 to 32 must be 1, bit 1 and 2 must be 0.
+        let mut value: u32 = 0xFFFF_F0C0;
+        for (bit, allowed) in flags {
+            if allowed {
+                value |= 1 << (bit - 1);
+            }
+        }
+        value as i32
+    }
+
+    /// Read the permissions from the value of `P` in the `Encrypt` dictionary.
+    pub(crate) fn from_value(value: i64) -> Self {
+        let is_allowed = |bit: u32| value & (1 << (bit - 1)) != 0;
+        Permissions {
+            print: is_allowed(3),
+            print_high_quality: is_allowed(12),
+            modify: is_allowed(4),
+            copy: is_allowed(5),
+            annotate: is_allowed(6),
+            fill_forms: is_allowed(9),
+            extract_for_accessibility: is_allowed(10),
+            assemble: is_allowed(11),
+        }
+    }
+}
+
+/// How the data is changed by `SecurityHandler::crypt_object`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CryptDirection {
+    Encrypt,
+    Decrypt,
+}
+
+/// The file key and crypt methods of an encrypted document.
+#[derive(Debug, Clone)]
+pub(crate) struct SecurityHandler {
+    key: Vec<u8>,
+    string_method: CryptMethod,
+    stream_method: CryptMethod,
+    encrypt_metadata: bool,
+}
+
+/// Parse the document `data` and decrypt it with the user or owner `password` when it is
+/// encrypted. `encrypted` is the guess of a scan of the trailers (`recovery::has_encrypt`),
+/// so the file is only parsed a second time when the guess was wrong.
+/// Only the loaded objects are decrypted, `data` itself is kept,
+/// so incremental updates (like signatures) do not change the previous revisions.
+pub(crate) fn parse_document(
+    data: &[u8],
+    password: &str,
+    encrypted: bool,
+) -> Result<(Document, Option<SecurityHandler>), Error> {
+    if encrypted {
+        let mut doc = read_encrypted_document(data)?;
+        if doc.trailer.has(b"Encrypt") {
+            let handler = SecurityHandler::from_document(&doc, password)?;
+            handler.decrypt_document(&mut doc)?;
+            return Ok((doc, Some(handler)));
+        }
+    }
+    let doc = Document::load_mem(data)?;
+    if !encrypted && doc.trailer.has(b"Encrypt") {
+        return parse_document(data, password, true);
+    }
+    Ok((doc, None))
+}
+
+/// Load the encrypted document `data` with the security handler of an earlier load.
+pub(crate) fn reload_encrypted_document(
+    data: Vec<u8>,
+    handler: &SecurityHandler,
+) -> Result<IncrementalDocument, Error> {
+    let mut doc = read_encrypted_document(&data)?;
+    handler.decrypt_document(&mut doc)?;
+    Ok(IncrementalDocument::create_from(data, doc))
+}
+
+/// Add AES-256 encryption (revision 6) to the trailer of `doc`, replacing any previous encryption.
+/// The objects themselves are encrypted when saving, with `SecurityHandler::encrypt_document`.
+pub(crate) fn add_encryption(
+    doc: &mut Document,
+    options: &EncryptionOptions,
+) -> Result<SecurityHandler, Error> {
+    if let Some(encrypt_id) = get_encrypt_id(doc) {
+        doc.objects.remove(&encrypt_id);
+    }
+    let key: [u8; 32] = rand::random();
+    let user_password = truncate_password(&options.user_password);
+    let owner_password = match options.owner_password.is_empty() {
+        true => user_password,
+        false => truncate_password(&options.owner_password),
+    };
+    let permissions = options.permissions.to_value();
+
+    // Algorithm 8 and 9: Compute the user and owner hash and the encrypted keys.
+    let (user_salt, user_key_salt): ([u8; 8], [u8; 8]) = (rand::random(), rand::random());
+    let mut user_hash = hash_aes_256(6, user_password, &user_salt, &[]);
+    user_hash.extend_from_slice(&user_salt);
+    user_hash.extend_from_slice(&user_key_salt);
+    let user_encrypted_key =
+        aes_256_encrypt_block(&hash_aes_256(6, user_password, &user_key_salt, &[]), &key);
+    let (owner_salt, owner_key_salt): ([u8; 8], [u8; 8]) = (rand::random(), rand::random());
+    let mut owner_hash = hash_aes_256(6, owner_password, &owner_salt, &user_hash);
+    owner_hash.extend_from_slice(&owner_salt);
+    owner_hash.extend_from_slice(&owner_key_salt);
+    let owner_encrypted_key = aes_256_encrypt_block(
+        &hash_aes_256(6, owner_password, &owner_key_salt, &user_hash),
+        &key,
+    );
+    // Algorithm 10: Encrypt the permissions, so they can be checked.
+    let mut permissions_block = permissions.to_le_bytes().to_vec();
+    permissions_block.extend_from_slice(&[0xFF; 4]);
+    permissions_block.extend_from_slice(b"Tadb");
+    permissions_block.extend_from_slice(&rand::random::<[u8; 4]>());
+    let encrypted_permissions = aes_256_encrypt_block(&key, &permissions_block);
+
+    let hex_string = |data: Vec<u8>| Object::String(data, StringFormat::Hexadecimal);
+    let encrypt_id = doc.add_object(dictionary! {
+        "Filter" => "Standard",
+        "V" => 5,
+        "R" => 6,
+        "Length" => 256,
+        "CF" => dictionary! {
+            "StdCF" => dictionary! {
+                "CFM" => "AESV3",
+                "AuthEvent" => "DocOpen",
+                "Length" => 32,
+            },
+        },
+        "StmF" => "StdCF",
+        "StrF" => "StdCF",
+        "P" => permissions as i64,
+        "O" => hex_string(owner_hash),
+        "U" => hex_string(user_hash),
+        "OE" => hex_string(owner_encrypted_key),
+        "UE" => hex_string(user_encrypted_key),
+        "Perms" => hex_string(encrypted_permissions),
+        "EncryptMetadata" => true,
+    });
+    doc.trailer.set("Encrypt", Object::Reference(encrypt_id));
+    if !doc.trailer.has(b"ID") {
+        let file_id = rand::random::<[u8; 16]>().to_vec();
+        doc.trailer
+            .set("ID", vec![hex_string(file_id.clone()), hex_string(file_id)]);
+    }
+    Ok(SecurityHandler {
+        key: key.to_vec(),
+        string_method: CryptMethod::Aes256,
+        stream_method: CryptMethod::Aes256,
+        encrypt_metadata: true,
+    })
+}
+
+/// Read the document without parsing the object streams, they can only be parsed after decryption.
+fn read_encrypted_document(data: &[u8]) -> Result<Document, Error> {
+    Ok(Reader {
+        buffer: data,
+        document: Document::new(),
+    }
+    .read(Some(keep_encrypted_object_streams))?)
+}
+
+/// Filter used when loading, it renames object streams so they are not parsed before decryption.
+fn keep_encrypted_object_streams(
+    object_id: ObjectId,
+    object: &mut Object,
+) -> Option<(ObjectId, Object)> {
+    if let Object::Stream(stream) = object {
+        if stream.dict.type_is(b"ObjStm") {
+            stream
+                .dict
+                .set("Type", Object::Name(ENCRYPTED_OBJECT_STREAM.to_vec()));
+        }
+    }
+    Some((object_id, object.clone()))
+}
+
+fn get_encrypt_id(doc: &Document) -> Option<ObjectId> {
+    doc.trailer
+        .get(b"Encrypt")
+        .and_then(Object::as_reference)
+        .ok()
+}
+
+impl SecurityHandler {
+    fn from_document(doc: &Document, password: &str) -> Result<Self, Error> {
+        let encrypt_dict = match doc.trailer.get(b"Encrypt")? {
+            Object::Reference(encrypt_id) => doc.get_dictionary(*encrypt_id)?,
+            Object::Dictionary(encrypt_dict) => encrypt_dict,
+            _ => return Err(Error::from("Encryption: Encrypt dictionary not found.")),
+        };
+        Self::new(doc, encrypt_dict, password)
+    }
+
+    fn new(doc: &Document, encrypt_dict: &Dictionary, password: &str) -> Result<Self, Error> {
+        let filter = encrypt_dict.get(b"Filter").and_then(Object::as_name_str)?;
+        if filter != "Standard" {
+            return Err(Error::Other(format!(
+                "Encryption: Security handler `{}` is not supported.",
+                filter
+            )));
+        }
+        let version = encrypt_dict.get(b"V").and_then(Object::as_i64).unwrap_or(0);
+        let revision = encrypt_dict.get(b"R").and_then(Object::as_i64)?;
+        let encrypt_metadata = encrypt_dict
+            .get(b"EncryptMetadata")
+            .and_then(Object::as_bool)
+            .unwrap_or(true);
+
+        let (string_method, stream_method) = match version {
+            1 | 2 => (CryptMethod::Rc4, CryptMethod::Rc4),
+            4 | 5 => (
+                get_crypt_method(encrypt_dict, b"StrF")?,
+                get_crypt_method(encrypt_dict, b"StmF")?,
+            ),
+            _ => {
+                return Err(Error::Other(format!(
+                    "Encryption: Version `{}` is not supported.",
+                    version
+                )))
+            }
+        };
+
+        let key = match revision {
+            2..=4 => get_rc4_file_key(doc, encrypt_dict, revision, encrypt_metadata, password)?,
+            5 | 6 => get_aes_256_file_key(encrypt_dict, revision, password)?,
+            _ => {
+                return Err(Error::Other(format!(
+                    "Encryption: Revision `{}` is not supported.",
+                    revision
+                )))
+            }
+        };
+        Ok(SecurityHandler {
+            key,
+            string_method,
+            stream_method,
+            encrypt_metadata,
+        })
+    }
+
+    /// Decrypt all objects of `doc`, also the objects inside object streams.
+    pub(crate) fn decrypt_document(&self, doc: &mut Document) -> Result<(), Error> {
+        let encrypt_id = get_encrypt_id(doc);
+        for (&object_id, object) in doc.objects.iter_mut() {
+            if Some(object_id) != encrypt_id {
+                self.crypt_object(object_id, object, CryptDirection::Decrypt)?;
+            }
+        }
+
+        // Objects inside object streams are not encrypted separately, so parse them now.
+        let object_stream_ids: Vec<ObjectId> = doc
+            .objects
+            .iter()
+            .filter(|(_, object)| {
+                object
+                    .as_stream()
+                    .is_ok_and(|stream| stream.dict.type_is(ENCRYPTED_OBJECT_STREAM))
+            })
+            .map(|(object_id, _)| *object_id)
+            .collect();
+        for object_stream_id in object_stream_ids {
+            if let Some(Object::Stream(mut stream)) = doc.objects.remove(&object_stream_id) {
+                stream.dict.set("Type", Object::Name(b"ObjStm".to_vec()));
+                for (object_id, object) in ObjectStream::new(&mut stream)?.objects {
+                    // Objects outside the object streams are newer, so never replace them.
+                    doc.objects.entry(object_id).or_insert(object);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Encrypt all objects of `doc`, except the `Encrypt` dictionary.
+    /// Streams should be compressed before, encrypted data can not be compressed.
+    pub(crate) fn encrypt_document(&self, doc: &mut Document) -> Result<(), Error> {
+        let encrypt_id = get_encrypt_id(doc);
+        for (&object_id, object) in doc.objects.iter_mut() {
+            if Some(object_id) != encrypt_id {
+                self.crypt_object(object_id, object, CryptDirection::Encrypt)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encrypt or decrypt the strings and stream data of the object.
+    fn crypt_object(
+        &self,
+        object_id: ObjectId,
+        object: &mut Object,
+        direction: CryptDirection,
+    ) -> Result<(), Error> {
+        match object {
+            Object::String(data, _) => {
+                *data = self.crypt(object_id, data, self.string_method, direction)?;
+            }
+            Object::Array(array) => {
+                for item in array {
+                    self.crypt_object(object_id, item, direction)?;
+                }
+            }
+            Object::Dictionary(dict) => self.crypt_dictionary(object_id, dict, direction)?,
+            Object::Stream(stream) => {
+                // Cross-reference streams are never encrypted, metadata can be plain text.
+                if stream.dict.type_is(b"XRef")
+                    || (stream.dict.type_is(b"Metadata") && !self.encrypt_metadata)
+                {
+                    return Ok(());
+                }
+                self.crypt_dictionary(object_id, &mut stream.dict, direction)?;
+                let content =
+                    self.crypt(object_id, &stream.content, self.stream_method, direction)?;
+                stream.set_content(content);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn crypt_dictionary(
+        &self,
+        object_id: ObjectId,
+        dict: &mut Dictionary,
+        direction: CryptDirection,
+    ) -> Result<(), Error> {
+        // The `Contents` of signatures are not encrypted.
+        let is_signature = dict.type_is(b"Sig") || dict.type_is(b"DocTimeStamp");
+        for (key, value) in dict.iter_mut() {
+            if is_signature && key == b"Contents" {
+                continue;
+            }
+            self.crypt_object(object_id, value, direction)?;
+        }
+        Ok(())
+    }
+
+    fn crypt(
+        &self,
+        object_id: ObjectId,
+        data: &[u8],
+        method: CryptMethod,
+        direction: CryptDirection,
+    ) -> Result<Vec<u8>, Error> {
+        let key = self.get_object_key(object_id, method);
+        match (method, direction) {
+            (CryptMethod::None, _) => Ok(data.to_vec()),
+            // RC4 encryption and decryption are the same.
+            (CryptMethod::Rc4, _) => Ok(rc4(&key, data)),
+            (_, CryptDirection::Encrypt) => aes_cbc_encrypt(&key, data),
+            (_, CryptDirection::Decrypt) => aes_cbc_decrypt(&key, data),
+        }
+    }
+
+    /// Get the key of one object (Algorithm 1), AES-256 uses the file key for all objects.
+    fn get_object_key(&self, object_id: ObjectId, method: CryptMethod) -> Vec<u8> {
+        if method == CryptMethod::Aes256 {
+            return self.key.clone();
+        }
+        let mut data = self.key.clone();
+        data.extend_from_slice(&object_id.0.to_le_bytes()[..3]);
+        data.extend_from_slice(&object_id.1.to_le_bytes());
+        if method == CryptMethod::Aes128 {
+            data.extend_from_slice(b"sAlT");
+        }
+        let key_length = (self.key.len() + 5).min(16);
+        md5::compute(&data)[..key_length].to_vec()
+    }
+}
+
+/// Get the method of the crypt filter named in `key` (`StrF` or `StmF`).
+fn get_crypt_method(encrypt_dict: &Dictionary, key: &[u8]) -> Result<CryptMethod, Error> {
+    let filter_name = match encrypt_dict.get(key) {
+        Ok(filter_name) => filter_name.as_name()?,
+        Err(_) => return Ok(CryptMethod::None),
+    };
+    if filter_name == b"Identity" {
+        return Ok(CryptMethod::None);
+    }
+    let crypt_filter = encrypt_dict
+        .get(b"CF")
+        .and_then(Object::as_dict)
+        .and_then(|crypt_filters| crypt_filters.get(filter_name))
+        .and_then(Object::as_dict)?;
+    match crypt_filter.get(b"CFM").and_then(Object::as_name) {
+        Ok(b"V2") => Ok(CryptMethod::Rc4),
+        Ok(b"AESV2") => Ok(CryptMethod::Aes128),
+        Ok(b"AESV3") => Ok(CryptMethod::Aes256),
+        Ok(b"None") | Err(_) => Ok(CryptMethod::None),
+        Ok(method) => Err(Error::Other(format!(
+            "Encryption: Crypt filter method `{}` is not supported.",
+            String::from_utf8_lossy(method)
+        ))),
+    }
+}
+
+/// Get the file key of revision 2 to 4 for the user or the owner password.
+fn get_rc4_file_key(
+    doc: &Document,
+    encrypt_dict: &Dictionary,
+    revision: i64,
+    encrypt_metadata: bool,
+    password: &str,
+) -> Result<Vec<u8>, Error> {
+    let owner_hash = encrypt_dict.get(b"O").and_then(Object::as_str)?;
+    let user_hash = encrypt_dict.get(b"U").and_then(Object::as_str)?;
+    let permissions = encrypt_dict.get(b"P").and_then(Object::as_i64)? as u32;
+    let key_length = match revision {
+        2 => 5,
+        _ => encrypt_dict
+            .get(b"Length")
+            .and_then(Object::as_i64)
+            .map(|length| (length / 8).clamp(5, 16) as usize)
+            .unwrap_or(5),
+    };
+    let file_id = doc
+        .trailer
+        .get(b"ID")
+        .and_then(Object::as_array)
+        .ok()
+        .and_then(|ids| ids.first())
+        .and_then(|id| id.as_str().ok())
+        .unwrap_or_default();
+    let password = pad_password(password);
+
+    // Algorithm 2: Compute the file key from the user password.
+    let compute_file_key = |user_password: &[u8]| {
+        let mut data = user_password.to_vec();
+        data.extend_from_slice(owner_hash);
+        data.extend_from_slice(&permissions.to_le_bytes());
+        data.extend_from_slice(file_id);
+        if revision >= 4 && !encrypt_metadata {
+            data.extend_from_slice(&[0xFF; 4]);
+        }
+        let mut key = md5::compute(&data)[..key_length].to_vec();
+        if revision >= 3 {
+            for _ in 0..50 {
+                key = md5::compute(&key)[..key_length].to_vec();
+            }
+        }
+        key
+    };
+    // Algorithm 4 and 5: Compute the user hash (`U`) to check the file key.
+    let is_user_key = |key: &[u8]| {
+        if revision == 2 {
+            return rc4(key, &PASSWORD_PADDING) == user_hash;
+        }
+        let mut data = PASSWORD_PADDING.to_vec();
+        data.extend_from_slice(file_id);
+        let mut hash = rc4(key, &md5::compute(&data).0);
+        for round in 1..=19u8 {
+            let round_key: Vec<u8> = key.iter().map(|value| value ^ round).collect();
+            hash = rc4(&round_key, &hash);
+        }
+        user_hash.get(..16) == Some(&hash[..])
+    };
+
+    let key = compute_file_key(&password);
+    if is_user_key(&key) {
+        return Ok(key);
+    }
+
+    // Algorithm 7: Get the user password from the owner hash (`O`) with the owner password.
+    let mut owner_key = md5::compute(password).0.to_vec();
+    if revision >= 3 {
+        for _ in 0..50 {
+            owner_key = md5::compute(&owner_key).0.to_vec();
+        }
+    }
+    owner_key.truncate(key_length);
+    let user_password = if revision == 2 {
+        rc4(&owner_key, owner_hash)
+    } else {
+        let mut user_password = owner_hash.to_vec();
+        for round in (0..=19u8).rev() {
+            let round_key: Vec<u8> = owner_key.iter().map(|value| value ^ round).collect();
+            user_password = rc4(&round_key, &user_password);
+        }
+        user_password
+    };
+    let key = compute_file_key(&user_password);
+    if is_user_key(&key) {
+        return Ok(key);
+    }
+    Err(Error::EncryptionRequired)
+}
+
+/// Get the file key of revision 5 and 6 (AES-256) for the user or the owner password.
+fn get_aes_256_file_key(
+    encrypt_dict: &Dictionary,
+    revision: i64,
+    password: &str,
+) -> Result<Vec<u8>, Error> {
+    let get_bytes = |key: &[u8], length: usize| -> Result<&[u8], Error> {
+        encrypt_dict
+            .get(key)
+            .and_then(Object::as_str)?
+            .get(..length)
+            .ok_or_else(|| {
+                Error::Other(format!(
+                    "Encryption: `{}` is too short.",
+                    String::from_utf8_lossy(key)
+                ))
+            })
+    };
+    let owner_hash = get_bytes(b"O", 48)?;
+    let user_hash = get_bytes(b"U", 48)?;
+    let owner_encrypted_key = get_bytes(b"OE", 32)?;
+    let user_encrypted_key = get_bytes(b"UE", 32)?;
+    let password = truncate_password(password);
+
+    // Algorithm 2.A: Check the owner password first, then the user password.
+    let (intermediate_key, encrypted_key) =
+        if hash_aes_256(revision, password, &owner_hash[32..40], user_hash) == owner_hash[..32] {
+            (
+                hash_aes_256(revision, password, &owner_hash[40..48], user_hash),
+                owner_encrypted_key,
+            )
+        } else if hash_aes_256(revision, password, &user_hash[32..40], &[]) == user_hash[..32] {
+            (
+                hash_aes_256(revision, password, &user_hash[40..48], &[]),
+                user_encrypted_key,
+            )
+        } else {
+            return Err(Error::EncryptionRequired);
+        };
+
+    let mut key = encrypted_key.to_vec();
+    cbc::Decryptor::<aes::Aes256>::new_from_slices(&intermediate_key, &[0; 16])
+        .map_err(|_| Error::from("Encryption: Invalid key length."))?
+        .decrypt_padded_mut::<NoPadding>(&mut key)
+        .map_err(|_| Error::from("Encryption: Invalid encrypted key."))?;
+    Ok(key)
+}
+
+/// Compute the password hash of revision 5 (SHA-256) or 6 (Algorithm 2.B).
+fn hash_aes_256(revision: i64, password: &[u8], salt: &[u8], user_hash: &[u8]) -> Vec<u8> {
+    let mut hash = Sha256::new()
+        .chain(password)
+        .chain(salt)
+        .chain(user_hash)
+        .finalize()
+        .to_vec();
+    if revision == 5 {
+        return hash;
+    }
+
+    let mut round = 0;
+    loop {
+        let mut data = Vec::with_capacity(64 * (password.len() + hash.len() + user_hash.len()));
+        for _ in 0..64 {
+            data.extend_from_slice(password);
+            data.extend_from_slice(&hash);
+            data.extend_from_slice(user_hash);
+        }
+        let data_length = data.len();
+        // The key and IV are 16 bytes, so the lengths are always valid.
+        let encrypted = cbc::Encryptor::<aes::Aes128>::new_from_slices(&hash[..16], &hash[16..32])
+            .expect("AES-128 key and IV of 16 bytes")
+            .encrypt_padded_mut::<NoPadding>(&mut data, data_length)
+            .expect("Data is a multiple of 16 bytes")
+            .to_vec();
+        let remainder = encrypted[..16]
+            .iter()
+            .map(|value| *value as u32)
+            .sum::<u32>()
+            % 3;
+        hash = match remainder {
+            0 => Sha256::digest(&encrypted).to_vec(),
+            1 => Sha384::digest(&encrypted).to_vec(),
+            _ => Sha512::digest(&encrypted).to_vec(),
+        };
+        round += 1;
+        if round >= 64 && *encrypted.last().unwrap_or(&0) as u32 <= round - 32 {
+            break;
+        }
+    }
+    hash.truncate(32);
+    hash
+}
+
+/// Pad (or truncate) a password to 32 bytes. Characters are encoded as Latin-1.
+fn pad_password(password: &str) -> Vec<u8> {
+    let mut padded: Vec<u8> = password
+        .chars()
+        .map(|character| u8::try_from(character as u32).unwrap_or(b'?'))
+        .take(32)
+        .collect();
+    padded.extend_from_slice(&PASSWORD_PADDING[..32 - padded.len()]);
+    padded
+}
+
+/// Passwords of revision 5 and 6 are UTF-8, limited to 127 bytes.
+fn truncate_password(password: &str) -> &[u8] {
+    &password.as_bytes()[..password.len().min(127)]
+}
+
+/// Encrypt AES-CBC data with a random initialization vector, which is added before the data.
+fn aes_cbc_encrypt(key: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+    let iv: [u8; 16] = rand::random();
+    // Room for the padding, which is at least 1 byte.
+    let mut buffer = data.to_vec();
+    buffer.extend_from_slice(&[0; 16]);
+    // The key length comes from the `Length` of the document, so it can be invalid.
+    let encrypted = match key.len() {
+        16 => cbc::Encryptor::<aes::Aes128>::new_from_slices(key, &iv)
+            .map_err(|_| Error::from("Encryption: Invalid key length."))?
+            .encrypt_padded_mut::<Pkcs7>(&mut buffer, data.len())
+            .expect("Buffer has room for the padding")
+            .to_vec(),
+        _ => cbc::Encryptor::<aes::Aes256>::new_from_slices(key, &iv)
+            .map_err(|_| Error::from("Encryption: Invalid key length."))?
+            .encrypt_padded_mut::<Pkcs7>(&mut buffer, data.len())
+            .expect("Buffer has room for the padding")
+            .to_vec(),
+    };
+    let mut result = iv.to_vec();
+    result.extend(encrypted);
+    Ok(result)
+}
+
+/// Encrypt blocks of 16 bytes with AES-256 without initialization vector and padding.
+fn aes_256_encrypt_block(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut buffer = data.to_vec();
+    cbc::Encryptor::<aes::Aes256>::new_from_slices(key, &[0; 16])
+        .expect("AES-256 key of 32 bytes")
+        .encrypt_padded_mut::<NoPadding>(&mut buffer, data.len())
+        .expect("Data is a multiple of 16 bytes");
+    buffer
+}
+
+/// Decrypt AES-CBC data, the first 16 bytes are the initialization vector.
+fn aes_cbc_decrypt(key: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+    if data.len() < 16 {
+        return Ok(vec![]);
+    }
+    let (iv, data) = data.split_at(16);
+    let mut buffer = data.to_vec();
+    let length = match key.len() {
+        16 => cbc::Decryptor::<aes::Aes128>::new_from_slices(key, iv)
+            .map_err(|_| Error::from("Encryption: Invalid key length."))?
+            .decrypt_padded_mut::<Pkcs7>(&mut buffer)
+            .map(|decrypted| decrypted.len()),
+        _ => cbc::Decryptor::<aes::Aes256>::new_from_slices(key, iv)
+            .map_err(|_| Error::from("Encryption: Invalid key length."))?
+            .decrypt_padded_mut::<Pkcs7>(&mut buffer)
+            .map(|decrypted| decrypted.len()),
+    }
+    .map_err(|_| Error::from("Encryption: Invalid AES padding."))?;
+    buffer.truncate(length);
+    Ok(buffer)
+}
+
+/// RC4 encryption and decryption (they are the same).
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut state: Vec<u8> = (0..=255).collect();
+    let mut j: u8 = 0;
+    for i in 0..256 {
+        j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+        state.swap(i, j as usize);
+    }
+    let (mut i, mut j) = (0u8, 0u8);
+    data.iter()
+        .map(|value| {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(state[i as usize]);
+            state.swap(i as usize, j as usize);
+            let index = state[i as usize].wrapping_add(state[j as usize]);
+            value ^ state[index as usize]
+        })
+        .collect()
+}
@@ -4,9 +4,137 @@ use std::str::Utf8Error;
 pub enum Error {
     LoPdfError(lopdf::Error),
     TryFromIntError(std::num::TryFromIntError),
+    /// There is no form field with the (fully qualified or partial) name.
+    FieldNotFound {
+        name: String,
+    },
+    /// There is no signature field for the `box_id` of a user.
+    MissingSignatureBox {
+        box_id: String,
+    },
+    /// A signature field belongs to a user that is not in the signature info.
+    SignerMismatch {
+        user_id: String,
+        box_id: String,
+    },
+    /// The signature does not fit in the space reserved for it,
+    /// see `SigningOptionsBuilder::placeholder_size`.
+    PlaceholderTooSmall {
+        available: usize,
+        needed: usize,
+    },
+    /// The form field does not have the type needed, like a pushbutton or choice field.
+    UnsupportedFieldType {
+        name: String,
+        expected: String,
+    },
+    /// The document is encrypted and the password is missing or incorrect.
+    EncryptionRequired,
+    /// The document has more objects than allowed, see `ParsingLimits::max_objects`.
+    TooManyObjects {
+        count: usize,
+        maximum: usize,
+    },
+    /// A stream is larger than allowed (before or after decompression),
+    /// see `ParsingLimits::max_stream_size`.
+    StreamTooLarge {
+        object_id: (u32, u16),
+        maximum: usize,
+    },
+    /// The form field hierarchy is deeper than allowed (or has a loop),
+    /// see `ParsingLimits::max_depth`.
+    NestingTooDeep {
+        maximum: usize,
+    },
+    /// `sign` visited more form fields than allowed,
+    /// see `SigningOptionsBuilder::max_iterations`.
+    TooManyIterations {
+        maximum: usize,
+        /// The number of fields signed before the maximum was reached.
+        signed_fields: usize,
+    },
+    /// Signing needs the network, but the options do not allow it,
+    /// see `SigningOptionsBuilder::offline`.
+    NetworkDisabled {
+        /// What needs the network, like `time stamp`.
+        operation: String,
+    },
+    /// The PIN of a smartcard or remote signing service was not entered, the PIN callback
+    /// returned `None`.
+    PinCancelled,
+    /// The PIN of a smartcard is blocked after too many wrong tries.
+    PinBlocked,
+    /// The `Contents` of the signature field is not a DER encoded signature, like when its
+    /// length is longer than the `Contents`.
+    InvalidSignature {
+        name: String,
+    },
     Other(String),
 }
 
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::LoPdfError(err) => write!(f, "{}", err),
+            Error::TryFromIntError(err) => write!(f, "{}", err),
+            Error::FieldNotFound { name } => write!(f, "Form field `{}` not found.", name),
+            Error::MissingSignatureBox { box_id } => {
+                write!(f, "Signature field for box `{}` not found.", box_id)
+            }
+            Error::SignerMismatch { user_id, box_id } => write!(
+                f,
+                "Signature info for user `{}` (box `{}`) not found.",
+                user_id, box_id
+            ),
+            Error::PlaceholderTooSmall { available, needed } => write!(
+                f,
+                "Signature is too long for the placeholder. Available: {}, Needed: {}",
+                available, needed
+            ),
+            Error::UnsupportedFieldType { name, expected } => {
+                write!(f, "Form field `{}` is not a {}.", name, expected)
+            }
+            Error::EncryptionRequired => write!(f, "Encryption: Incorrect password."),
+            Error::TooManyObjects { count, maximum } => write!(
+                f,
+                "Document has {} objects, the maximum is {}.",
+                count, maximum
+            ),
+            Error::StreamTooLarge { object_id, maximum } => write!(
+                f,
+                "Stream {} {} R is larger than the maximum of {} bytes.",
+                object_id.0, object_id.1, maximum
+            ),
+            Error::NestingTooDeep { maximum } => write!(
+                f,
+                "Form field hierarchy is deeper than the maximum of {}.",
+                maximum
+            ),
+            Error::TooManyIterations {
+                maximum,
+                signed_fields,
+            } => write!(
+                f,
+                "Signing stopped after visiting {} form fields, {} fields were signed.",
+                maximum, signed_fields
+            ),
+            Error::NetworkDisabled { operation } => write!(
+                f,
+                "The {} needs the network, but signing is offline.",
+                operation
+            ),
+            Error::PinCancelled => write!(f, "The PIN was not entered."),
+            Error::PinBlocked => write!(f, "Smartcard: The PIN is blocked."),
+            Error::InvalidSignature { name } => {
+                write!(f, "Signature `{}` is not DER encoded.", name)
+            }
+            Error::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 impl From<lopdf::Error> for Error {
     fn from(err: lopdf::Error) -> Self {
         Self::LoPdfError(err)
@@ -40,4 +168,4 @@ impl From<Utf8Error> for Error {
     fn from(_err: Utf8Error) -> Self {
         Self::Other("Utf8Error".to_string())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,251 @@
+//! Signatures created with a key that this crate can not read, like the key on a smartcard
+//! or at a remote signing service, see `ExternalSigner`.
+
+use crate::certificate_details::{read_tbs_certificate, TbsCertificate};
+use crate::der::{read_der, write_der};
+use crate::signing_options::{DigestAlgorithm, SigningOptions};
+use crate::{Error, PDFSigningDocument};
+use sha2::{Digest, Sha256};
+
+/// The DER encoded object identifiers used in CMS signatures and certificates.
+const OID_ID_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x01];
+const OID_SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+const OID_CONTENT_TYPE: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x03];
+const OID_MESSAGE_DIGEST: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x04];
+const OID_SIGNING_CERTIFICATE_V2: &[u8] = &[
+    0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x10, 0x02, 0x2f,
+];
+const OID_RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_P384: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+const OID_P521: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x23];
+
+/// Creates the signature value of a signature with a key that is not in memory, like the
+/// key on a smartcard. This crate creates the CMS signature around it, see
+/// `sign_field_with_signer`. Signers are `Send` and `Sync`, so they can be shared between
+/// the tasks of a server.
+pub trait ExternalSigner: Send + Sync {
+    /// The DER encoded certificate of the key, followed by the certificates of its issuers
+    /// that are added to the signature.
+    fn get_certificates(&self) -> Result<Vec<Vec<u8>>, Error>;
+
+    /// Sign the `digest` (calculated with `digest_algorithm`) with the key. Returns the
+    /// PKCS #1 v1.5 signature for RSA keys and the DER encoded `Ecdsa-Sig-Value` for
+    /// ECDSA keys.
+    fn sign_digest(
+        &self,
+        digest: &[u8],
+        digest_algorithm: DigestAlgorithm,
+    ) -> Result<Vec<u8>, Error>;
+
+    /// The signer uses the network to sign, like a remote signing service. Offline
+    /// options (`SigningOptionsBuilder::offline`) reject these signers. Default `false`.
+    fn needs_network(&self) -> bool {
+        false
+    }
+}
+
+/// The algorithm of the public key of a certificate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum KeyAlgorithm {
+    /// RSA, with the length of the modulus in bytes.
+    Rsa { modulus_length: usize },
+    /// ECDSA, with the length of the private key of the curve in bytes.
+    Ecdsa { key_length: usize },
+}
+
+impl KeyAlgorithm {
+    /// Read the algorithm of the public key of the DER encoded `certificate`.
+    /// `None` for other algorithms and curves.
+    pub(crate) fn from_certificate(certificate: &[u8]) -> Option<Self> {
+        Self::from_tbs_certificate(&read_tbs_certificate(certificate)?)
+    }
+
+    fn from_tbs_certificate(certificate: &TbsCertificate) -> Option<Self> {
+        match read_der(certificate.public_key_algorithm)? {
+            (0x06, OID_RSA_ENCRYPTION, _) => {
+                // `RSAPublicKey`: the modulus and the public exponent.
+                let (0x30, public_key, _) = read_der(certificate.public_key)? else {
+                    return None;
+                };
+                let (0x02, modulus, _) = read_der(public_key)? else {
+                    return None;
+                };
+                let modulus_length = modulus.iter().skip_while(|byte| **byte == 0).count();
+                Some(KeyAlgorithm::Rsa { modulus_length })
+            }
+            (0x06, OID_EC_PUBLIC_KEY, parameters) => {
+                let key_length = match read_der(parameters)? {
+                    (0x06, OID_P256, _) => 32,
+                    (0x06, OID_P384, _) => 48,
+                    (0x06, OID_P521, _) => 66,
+                    _ => return None,
+                };
+                Some(KeyAlgorithm::Ecdsa { key_length })
+            }
+            _ => None,
+        }
+    }
+
+    /// The DER encoded `AlgorithmIdentifier` of signatures with this key and the
+    /// `digest_algorithm`.
+    fn get_signature_algorithm(self, digest_algorithm: DigestAlgorithm) -> Vec<u8> {
+        let (oid, parameters): (&[u8], &[u8]) = match (self, digest_algorithm) {
+            // `sha256WithRSAEncryption`, `sha384WithRSAEncryption` and
+            // `sha512WithRSAEncryption`, with `NULL` parameters.
+            (KeyAlgorithm::Rsa { .. }, DigestAlgorithm::Sha256) => (
+                &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b],
+                &[0x05, 0x00],
+            ),
+            (KeyAlgorithm::Rsa { .. }, DigestAlgorithm::Sha384) => (
+                &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c],
+                &[0x05, 0x00],
+            ),
+            (KeyAlgorithm::Rsa { .. }, DigestAlgorithm::Sha512) => (
+                &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d],
+                &[0x05, 0x00],
+            ),
+            // `ecdsa-with-SHA256`, `ecdsa-with-SHA384` and `ecdsa-with-SHA512`.
+            (KeyAlgorithm::Ecdsa { .. }, DigestAlgorithm::Sha256) => {
+                (&[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02], &[])
+            }
+            (KeyAlgorithm::Ecdsa { .. }, DigestAlgorithm::Sha384) => {
+                (&[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03], &[])
+            }
+            (KeyAlgorithm::Ecdsa { .. }, DigestAlgorithm::Sha512) => {
+                (&[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x04], &[])
+            }
+        };
+        write_der(0x30, &[&write_der(0x06, oid)[..], parameters].concat())
+    }
+}
+
+/// Create the signed attributes of a CMS signature of the content with `content_digest`:
+/// the content type, the message digest and the signing certificate
+/// (`signing-certificate-v2`, needed for PAdES) of the DER encoded `certificate`.
+/// Returns the DER encoded `SET` of the attributes, the bytes that are signed.
+pub(crate) fn create_signed_attributes(certificate: &[u8], content_digest: &[u8]) -> Vec<u8> {
+    // `SigningCertificateV2` with 1 `ESSCertIDv2`, with the default hash algorithm SHA-256.
+    let mut signing_certificate = write_der(0x04, &Sha256::digest(certificate));
+    for _ in 0..3 {
+        signing_certificate = write_der(0x30, &signing_certificate);
+    }
+    let mut attributes = [
+        create_attribute(OID_CONTENT_TYPE, &write_der(0x06, OID_ID_DATA)),
+        create_attribute(OID_MESSAGE_DIGEST, &write_der(0x04, content_digest)),
+        create_attribute(OID_SIGNING_CERTIFICATE_V2, &signing_certificate),
+    ];
+    // The values of a DER encoded `SET OF` are sorted.
+    attributes.sort();
+    write_der(0x31, &attributes.concat())
+}
+
+/// Create an `Attribute` with the object identifier `oid` and 1 DER encoded `value`.
+fn create_attribute(oid: &[u8], value: &[u8]) -> Vec<u8> {
+    write_der(
+        0x30,
+        &[write_der(0x06, oid), write_der(0x31, value)].concat(),
+    )
+}
+
+/// Create a detached CMS signature (`ContentInfo` with `SignedData`) with the
+/// `signed_attributes` of `create_signed_attributes` and the `signature` of the key of
+/// the first of the DER encoded `certificates`. Returns the DER encoded signature.
+pub(crate) fn create_signed_data(
+    certificates: &[Vec<u8>],
+    digest_algorithm: DigestAlgorithm,
+    signed_attributes: &[u8],
+    signature: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let invalid_certificate = || Error::from("Signature: Invalid signer certificate.");
+    let certificate = certificates
+        .first()
+        .and_then(|certificate| read_tbs_certificate(certificate))
+        .ok_or_else(invalid_certificate)?;
+    let key_algorithm =
+        KeyAlgorithm::from_tbs_certificate(&certificate).ok_or_else(invalid_certificate)?;
+    let Some((0x31, signed_attributes, _)) = read_der(signed_attributes) else {
+        return Err(Error::from("Signature: Invalid signed attributes."));
+    };
+
+    let digest_algorithm_id = write_der(0x30, &write_der(0x06, digest_algorithm.oid()));
+    let issuer_and_serial_number = write_der(
+        0x30,
+        &[
+            certificate.issuer,
+            &write_der(0x02, certificate.serial_number),
+        ]
+        .concat(),
+    );
+    // The signed attributes are implicitly tagged in the `SignerInfo`.
+    let signer_info = write_der(
+        0x30,
+        &[
+            vec![0x02, 0x01, 0x01],
+            issuer_and_serial_number,
+            digest_algorithm_id.clone(),
+            write_der(0xa0, signed_attributes),
+            key_algorithm.get_signature_algorithm(digest_algorithm),
+            write_der(0x04, signature),
+        ]
+        .concat(),
+    );
+    let mut sorted_certificates = certificates.to_vec();
+    sorted_certificates.sort();
+    // `SignedData`: the version, the digest algorithms, the content type without the
+    // content, the certificates and the signer infos.
+    let signed_data = write_der(
+        0x30,
+        &[
+            vec![0x02, 0x01, 0x01],
+            write_der(0x31, &digest_algorithm_id),
+            write_der(0x30, &write_der(0x06, OID_ID_DATA)),
+            write_der(0xa0, &sorted_certificates.concat()),
+            write_der(0x31, &signer_info),
+        ]
+        .concat(),
+    );
+    Ok(write_der(
+        0x30,
+        &[
+            write_der(0x06, OID_SIGNED_DATA),
+            write_der(0xa0, &signed_data),
+        ]
+        .concat(),
+    ))
+}
+
+impl PDFSigningDocument {
+    /// Sign the empty signature field `field_name` with an `ExternalSigner`, like a
+    /// smartcard, see `prepare_signature` for the other arguments. The CMS signature is
+    /// created by this crate, time stamps (`SigningOptionsBuilder::time_stamp_url`) need
+    /// the feature `network`.
+    /// Return the signed document, this document is reloaded with the signature.
+    pub fn sign_field_with_signer(
+        &mut self,
+        field_name: &str,
+        signer_name: &str,
+        image: Option<&[u8]>,
+        options: &SigningOptions,
+        signer: &dyn ExternalSigner,
+    ) -> Result<Vec<u8>, Error> {
+        if options.offline && signer.needs_network() {
+            return Err(Error::NetworkDisabled {
+                operation: "external signer".to_owned(),
+            });
+        }
+        let pending_signature = self.begin_signature(
+            field_name,
+            signer_name,
+            image,
+            options,
+            &signer.get_certificates()?,
+        )?;
+        let signature = signer.sign_digest(
+            &pending_signature.get_digest(),
+            pending_signature.get_digest_algorithm(),
+        )?;
+        self.complete_signature(pending_signature, &signature, options)
+    }
+}
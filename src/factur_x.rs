@@ -0,0 +1,141 @@
+//! Embed a Factur-X / ZUGFeRD invoice XML, so the invoice can be read by machines.
+//! The XMP metadata gets the Factur-X extension schema and properties.
+
+use crate::attachments::{Attachment, AttachmentRelationship};
+use crate::pdfa::{set_xmp_property, XmpKind};
+use crate::{Error, PDFSigningDocument};
+
+const NAMESPACE_FACTUR_X: &str = "urn:factur-x:pdfa:CrossIndustryDocument:invoice:1p0#";
+/// PDF/A extension schema that describes the Factur-X properties.
+const FACTUR_X_EXTENSION_SCHEMA: &str = "<rdf:li rdf:parseType=\"Resource\" \
+xmlns:pdfaSchema=\"http://www.aiim.org/pdfa/ns/schema#\" \
+xmlns:pdfaProperty=\"http://www.aiim.org/pdfa/ns/property#\">\
+<pdfaSchema:schema>Factur-X PDFA Extension Schema</pdfaSchema:schema>\
+<pdfaSchema:namespaceURI>urn:factur-x:pdfa:CrossIndustryDocument:invoice:1p0#</pdfaSchema:namespaceURI>\
+<pdfaSchema:prefix>fx</pdfaSchema:prefix>\
+<pdfaSchema:property><rdf:Seq>\
+<rdf:li rdf:parseType=\"Resource\"><pdfaProperty:name>DocumentFileName</pdfaProperty:name>\
+<pdfaProperty:valueType>Text</pdfaProperty:valueType><pdfaProperty:category>external</pdfaProperty:category>\
+<pdfaProperty:description>The name of the embedded XML document</pdfaProperty:description></rdf:li>\
+<rdf:li rdf:parseType=\"Resource\"><pdfaProperty:name>DocumentType</pdfaProperty:name>\
+<pdfaProperty:valueType>Text</pdfaProperty:valueType><pdfaProperty:category>external</pdfaProperty:category>\
+<pdfaProperty:description>The type of the hybrid document in capital letters, e.g. INVOICE or ORDER</pdfaProperty:description></rdf:li>\
+<rdf:li rdf:parseType=\"Resource\"><pdfaProperty:name>Version</pdfaProperty:name>\
+<pdfaProperty:valueType>Text</pdfaProperty:valueType><pdfaProperty:category>external</pdfaProperty:category>\
+<pdfaProperty:description>The actual version of the standard applying to the embedded XML document</pdfaProperty:description></rdf:li>\
+<rdf:li rdf:parseType=\"Resource\"><pdfaProperty:name>ConformanceLevel</pdfaProperty:name>\
+<pdfaProperty:valueType>Text</pdfaProperty:valueType><pdfaProperty:category>external</pdfaProperty:category>\
+<pdfaProperty:description>The conformance level of the embedded XML document</pdfaProperty:description></rdf:li>\
+</rdf:Seq></pdfaSchema:property></rdf:li>";
+
+/// The Factur-X (ZUGFeRD 2) profile of the invoice XML.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FacturXProfile {
+    Minimum,
+    BasicWl,
+    Basic,
+    En16931,
+    Extended,
+    XRechnung,
+}
+
+impl FacturXProfile {
+    /// The conformance level in the XMP metadata (`fx:ConformanceLevel`).
+    fn conformance_level(self) -> &'static str {
+        match self {
+            FacturXProfile::Minimum => "MINIMUM",
+            FacturXProfile::BasicWl => "BASIC WL",
+            FacturXProfile::Basic => "BASIC",
+            FacturXProfile::En16931 => "EN 16931",
+            FacturXProfile::Extended => "EXTENDED",
+            FacturXProfile::XRechnung => "XRECHNUNG",
+        }
+    }
+
+    fn file_name(self) -> &'static str {
+        match self {
+            FacturXProfile::XRechnung => "xrechnung.xml",
+            _ => "factur-x.xml",
+        }
+    }
+
+    /// The XML of the `MINIMUM` and `BASIC WL` profiles is not a full invoice,
+    /// so it is only data for the invoice in the PDF.
+    fn relationship(self) -> AttachmentRelationship {
+        match self {
+            FacturXProfile::Minimum | FacturXProfile::BasicWl => AttachmentRelationship::Data,
+            _ => AttachmentRelationship::Alternative,
+        }
+    }
+}
+
+impl PDFSigningDocument {
+    /// Embed the invoice XML (`invoice_xml`) as Factur-X / ZUGFeRD invoice, with an
+    /// incremental update. The XMP metadata gets the Factur-X extension schema and properties.
+    ///
+    /// Factur-X requires a PDF/A-3 document: a PDF/A-2 document is marked as PDF/A-3
+    /// (which allows attachments), other documents are not converted.
+    /// Embed the invoice before signing, so the signature covers it.
+    pub fn add_factur_x_invoice(
+        &mut self,
+        invoice_xml: &[u8],
+        profile: FacturXProfile,
+    ) -> Result<(), Error> {
+        self.add_embedded_file(&Attachment {
+            file_name: profile.file_name().to_owned(),
+            data: invoice_xml.to_vec(),
+            mime_type: Some("text/xml".to_owned()),
+            description: Some("Factur-X/ZUGFeRD Invoice".to_owned()),
+            relationship: profile.relationship(),
+        })?;
+
+        self.edit_xmp_metadata(|xmp| {
+            let mut xmp = add_factur_x_extension_schema(xmp);
+            for (name, value) in [
+                ("fx:DocumentType", "INVOICE"),
+                ("fx:DocumentFileName", profile.file_name()),
+                ("fx:Version", "1.0"),
+                ("fx:ConformanceLevel", profile.conformance_level()),
+            ] {
+                xmp = set_xmp_property(&xmp, name, NAMESPACE_FACTUR_X, XmpKind::Text, value);
+            }
+            // PDF/A-3 is PDF/A-2 with attachments.
+            xmp.replace("pdfaid:part=\"2\"", "pdfaid:part=\"3\"")
+                .replace("pdfaid:part='2'", "pdfaid:part='3'")
+                .replace(
+                    "<pdfaid:part>2</pdfaid:part>",
+                    "<pdfaid:part>3</pdfaid:part>",
+                )
+        })
+    }
+}
+
+/// Add the Factur-X schema to the PDF/A extension schemas, when it is not there yet.
+fn add_factur_x_extension_schema(xmp: &str) -> String {
+    let mut xmp = xmp.to_owned();
+    if xmp.contains(&format!(">{}<", NAMESPACE_FACTUR_X)) {
+        return xmp;
+    }
+    // Only 1 `pdfaExtension:schemas` property is allowed, so add to the existing one.
+    let existing_schemas = xmp.find("<pdfaExtension:schemas").and_then(|start| {
+        let bag = start + xmp[start..].find("<rdf:Bag>")?;
+        Some(bag + "<rdf:Bag>".len())
+    });
+    match existing_schemas {
+        Some(position) => xmp.insert_str(position, FACTUR_X_EXTENSION_SCHEMA),
+        None => match xmp.find("</rdf:RDF>") {
+            Some(position) => xmp.insert_str(
+                position,
+                &format!(
+                    "<rdf:Description rdf:about=\"\" \
+                    xmlns:pdfaExtension=\"http://www.aiim.org/pdfa/ns/extension/\">\
+                    <pdfaExtension:schemas><rdf:Bag>{}</rdf:Bag></pdfaExtension:schemas>\
+                    </rdf:Description>",
+                    FACTUR_X_EXTENSION_SCHEMA
+                ),
+            ),
+            None => tracing::warn!("Factur-X: XMP metadata has no `rdf:RDF` element."),
+        },
+    }
+    xmp
+}
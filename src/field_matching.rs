@@ -0,0 +1,66 @@
+use crate::acro_form::AcroForm;
+#[cfg(feature = "serde")]
+use serde_json::{Map, Value};
+use std::fmt;
+use std::sync::Arc;
+
+/// Maps a data key to the fully qualified name of the field it should fill.
+pub type FieldMatchingFn = dyn Fn(&str) -> Option<String> + Send + Sync;
+
+/// How the keys of the data given to `fill_form` are matched with the form fields.
+#[derive(Clone, Default)]
+pub enum FieldMatching {
+    /// The partial field name (`T`) is converted to lowercase and should match the key exactly.
+    /// So keys are expected to be lowercase.
+    #[default]
+    LowerCasePartialName,
+    /// The partial field name (`T`) should match the key exactly.
+    Exact,
+    /// The partial field name (`T`) should match the key, ignoring the case of both.
+    CaseInsensitive,
+    /// The fully qualified field name (`parent.child`) should match the key exactly.
+    FullyQualified,
+    /// A custom function that maps a data key to a fully qualified field name.
+    Custom(Arc<FieldMatchingFn>),
+}
+
+impl FieldMatching {
+    /// Find the value in `data` for the form field.
+    #[cfg(feature = "serde")]
+    pub(crate) fn get_value<'a>(
+        &self,
+        data: &'a Map<String, Value>,
+        field: &AcroForm,
+    ) -> Option<&'a Value> {
+        let partial_field_name = field.get_partial_field_name()?;
+        match self {
+            FieldMatching::LowerCasePartialName => data.get(&partial_field_name.to_lowercase()),
+            FieldMatching::Exact => data.get(partial_field_name),
+            FieldMatching::CaseInsensitive => data
+                .iter()
+                .find(|(key, _)| key.to_lowercase() == partial_field_name.to_lowercase())
+                .map(|(_, value)| value),
+            FieldMatching::FullyQualified => data.get(field.get_fully_qualified_field_name()?),
+            FieldMatching::Custom(matching_fn) => {
+                let fully_qualified_field_name = field.get_fully_qualified_field_name()?;
+                data.iter()
+                    .find(|(key, _)| {
+                        matching_fn(key).as_deref() == Some(fully_qualified_field_name)
+                    })
+                    .map(|(_, value)| value)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for FieldMatching {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldMatching::LowerCasePartialName => write!(f, "LowerCasePartialName"),
+            FieldMatching::Exact => write!(f, "Exact"),
+            FieldMatching::CaseInsensitive => write!(f, "CaseInsensitive"),
+            FieldMatching::FullyQualified => write!(f, "FullyQualified"),
+            FieldMatching::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Read the info of the user that signs a signature field (the routing data) from the
+//! field, see `SigningOptionsBuilder::field_metadata_parser`.
+
+use crate::merge::get_original_field_name;
+use crate::{FormField, UserFormSignatureInfo};
+use lopdf::Dictionary;
+use std::fmt;
+#[cfg(not(feature = "serde"))]
+use std::iter::Peekable;
+#[cfg(not(feature = "serde"))]
+use std::str::Chars;
+use std::sync::Arc;
+
+/// Reads the info of the user that signs a signature field, used to match the fields to
+/// the users with `MatchBy::UserId` and `MatchBy::EncodedBoxId`.
+/// Functions with the same arguments implement it, so templates with the info in custom
+/// keys of the field dictionary can be supported without a new type.
+pub trait FieldMetadataParser: Send + Sync {
+    /// Get the info of the user from the field, `None` when the field has no info.
+    /// `field_dict` is the dictionary of the field, with the keys that are not in `field`.
+    fn parse(&self, field: &FormField, field_dict: &Dictionary) -> Option<UserFormSignatureInfo>;
+}
+
+impl<F> FieldMetadataParser for F
+where
+    F: Fn(&FormField, &Dictionary) -> Option<UserFormSignatureInfo> + Send + Sync,
+{
+    fn parse(&self, field: &FormField, field_dict: &Dictionary) -> Option<UserFormSignatureInfo> {
+        self(field, field_dict)
+    }
+}
+
+/// The partial field name (`T`) is the base64 encoded JSON of `UserFormSignatureInfo`.
+/// This is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EncodedFieldName;
+
+impl FieldMetadataParser for EncodedFieldName {
+    fn parse(&self, field: &FormField, _field_dict: &Dictionary) -> Option<UserFormSignatureInfo> {
+        decode_user_form_signature_info(field.name()?)
+    }
+}
+
+/// The alternate field name (`TU`, the tooltip) is the base64 encoded JSON of
+/// `UserFormSignatureInfo`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EncodedAlternateName;
+
+impl FieldMetadataParser for EncodedAlternateName {
+    fn parse(&self, field: &FormField, _field_dict: &Dictionary) -> Option<UserFormSignatureInfo> {
+        decode_user_form_signature_info(field.alternate_name()?)
+    }
+}
+
+/// The partial field name (`T`) is the `user_id` and `box_id` as plain text.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlainFieldName;
+
+impl FieldMetadataParser for PlainFieldName {
+    fn parse(&self, field: &FormField, _field_dict: &Dictionary) -> Option<UserFormSignatureInfo> {
+        let name = get_original_field_name(field.name()?);
+        Some(UserFormSignatureInfo::new(name.to_owned(), name.to_owned()))
+    }
+}
+
+/// The parser in `SigningOptions`, so the options can still be compared and printed.
+#[derive(Clone)]
+pub(crate) struct MetadataParser(pub(crate) Arc<dyn FieldMetadataParser>);
+
+impl Default for MetadataParser {
+    fn default() -> Self {
+        MetadataParser(Arc::new(EncodedFieldName))
+    }
+}
+
+impl fmt::Debug for MetadataParser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FieldMetadataParser")
+    }
+}
+
+impl PartialEq for MetadataParser {
+    /// Parsers are only equal when they are the same `Arc`.
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Decode base64 encoded JSON of `UserFormSignatureInfo`.
+/// The suffix that `merge` adds to field names is removed first.
+fn decode_user_form_signature_info(encoded_data: &str) -> Option<UserFormSignatureInfo> {
+    // Decode data (from base64 to Vec<u8>)
+    let decoded_data = match base64::decode(get_original_field_name(encoded_data)) {
+        Ok(decoded_data) => decoded_data,
+        Err(err) => {
+            tracing::warn!("Signature field info is not base64 encoded. Err: {}", err);
+            return None;
+        }
+    };
+    // Decode to JSON
+    #[cfg(feature = "serde")]
+    match serde_json::from_slice(&decoded_data) {
+        Ok(json_data) => Some(json_data),
+        Err(err) => {
+            tracing::warn!(
+                "Signature field info does not contain json data. Err: {}",
+                err
+            );
+            None
+        }
+    }
+    #[cfg(not(feature = "serde"))]
+    {
+        let info = std::str::from_utf8(&decoded_data)
+            .ok()
+            .and_then(read_json_object)
+            .and_then(|entries| {
+                let get = |key: &str| {
+                    entries
+                        .iter()
+                        .find(|(entry_key, _)| entry_key == key)
+                        .map(|(_, value)| value.clone())
+                };
+                Some(UserFormSignatureInfo::new(get("userId")?, get("boxId")?))
+            });
+        if info.is_none() {
+            tracing::warn!("Signature field info does not contain json data.");
+        }
+        info
+    }
+}
+
+/// Read a JSON object with string values, like `{"userId":"1","boxId":"2"}`.
+/// Without the `serde` feature there is no JSON parser, and the info only has strings.
+#[cfg(not(feature = "serde"))]
+fn read_json_object(json: &str) -> Option<Vec<(String, String)>> {
+    let mut chars = json.trim().chars().peekable();
+    let mut entries = vec![];
+    if chars.next()? != '{' {
+        return None;
+    }
+    skip_whitespace(&mut chars);
+    if chars.next_if_eq(&'}').is_some() {
+        return chars.next().is_none().then_some(entries);
+    }
+    loop {
+        skip_whitespace(&mut chars);
+        let key = read_json_string(&mut chars)?;
+        skip_whitespace(&mut chars);
+        if chars.next()? != ':' {
+            return None;
+        }
+        skip_whitespace(&mut chars);
+        let value = read_json_string(&mut chars)?;
+        entries.push((key, value));
+        skip_whitespace(&mut chars);
+        match chars.next()? {
+            ',' => continue,
+            '}' => break,
+            _ => return None,
+        }
+    }
+    chars.next().is_none().then_some(entries)
+}
+
+#[cfg(not(feature = "serde"))]
+fn skip_whitespace(chars: &mut Peekable<Chars<'_>>) {
+    while chars
+        .peek()
+        .is_some_and(|character| character.is_whitespace())
+    {
+        chars.next();
+    }
+}
+
+/// Read a JSON string, the characters of surrogate pairs (`\ud83d\ude00`) are not supported.
+#[cfg(not(feature = "serde"))]
+fn read_json_string(chars: &mut Peekable<Chars<'_>>) -> Option<String> {
+    if chars.next()? != '"' {
+        return None;
+    }
+    let mut value = String::new();
+    loop {
+        let character = match chars.next()? {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                '"' => '"',
+                '\\' => '\\',
+                '/' => '/',
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'u' => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    char::from_u32(u32::from_str_radix(&code, 16).ok()?)?
+                }
+                _ => return None,
+            },
+            character => character,
+        };
+        value.push(character);
+    }
+}
@@ -0,0 +1,262 @@
+//! Validate form field values before they are written to `V`.
+//!
+//! Acrobat stores the format of a field as JavaScript in the additional actions (`AA`)
+//! of the field. Only the `AFNumber_*` and `AFDate_*` functions are recognized.
+
+use crate::{Error, PdfObjectDeref};
+use chrono::format::{parse, ParseResult, Parsed, StrftimeItems};
+use lopdf::{Dictionary, Document, Object};
+
+/// Predefined formats used by `AFDate_Format(index)`.
+const AF_DATE_FORMATS: [&str; 14] = [
+    "m/d",
+    "m/d/yy",
+    "mm/dd/yy",
+    "mm/yy",
+    "d-mmm",
+    "d-mmm-yy",
+    "dd-mmm-yy",
+    "yy-mm-dd",
+    "mmm-yy",
+    "mmmm-yy",
+    "mmm d, yyyy",
+    "mmmm d, yyyy",
+    "m/d/yy h:MM tt",
+    "m/d/yy HH:MM",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum FieldFormat {
+    /// `AFNumber_Format(nDec, sepStyle, ...)`
+    Number { separator_style: u8 },
+    /// `AFDate_FormatEx(cFormat)` or `AFDate_Format(index)`
+    Date { format: String },
+}
+
+impl FieldFormat {
+    /// Get the format from the `F` (format) or `K` (keystroke) action of the field.
+    pub(crate) fn from_field(
+        doc: &Document,
+        field_dict: &Dictionary,
+    ) -> Result<Option<Self>, Error> {
+        let additional_actions = match field_dict.get(b"AA") {
+            Ok(additional_actions) => additional_actions.deref(doc)?.as_dict()?,
+            Err(_) => return Ok(None),
+        };
+
+        for key in [b"F".as_ref(), b"K".as_ref()] {
+            let action = match additional_actions.get(key) {
+                Ok(action) => action.deref(doc)?.as_dict()?,
+                Err(_) => continue,
+            };
+            let javascript = match action.get(b"JS") {
+                Ok(javascript) => match javascript.deref(doc)? {
+                    Object::String(bytes, _) => String::from_utf8_lossy(bytes).to_string(),
+                    Object::Stream(stream) => {
+                        let content = stream
+                            .decompressed_content()
+                            .unwrap_or_else(|_| stream.content.clone());
+                        String::from_utf8_lossy(&content).to_string()
+                    }
+                    _ => continue,
+                },
+                Err(_) => continue,
+            };
+            if let Some(format) = Self::from_javascript(&javascript) {
+                return Ok(Some(format));
+            }
+        }
+        Ok(None)
+    }
+
+    fn from_javascript(javascript: &str) -> Option<Self> {
+        if let Some(arguments) = Self::get_arguments(javascript, "AFNumber_") {
+            let separator_style = arguments
+                .get(1)
+                .and_then(|style| style.parse::<u8>().ok())
+                .unwrap_or(0);
+            return Some(FieldFormat::Number { separator_style });
+        }
+        if let Some(arguments) = Self::get_arguments(javascript, "AFDate_FormatEx") {
+            return arguments.first().map(|format| FieldFormat::Date {
+                format: format.to_owned(),
+            });
+        }
+        if let Some(arguments) = Self::get_arguments(javascript, "AFDate_Format") {
+            let format = arguments
+                .first()
+                .and_then(|index| index.parse::<usize>().ok())
+                .and_then(|index| AF_DATE_FORMATS.get(index))?;
+            return Some(FieldFormat::Date {
+                format: format.to_string(),
+            });
+        }
+        None
+    }
+
+    /// Get the arguments of the first function call starting with `function_prefix`.
+    fn get_arguments(javascript: &str, function_prefix: &str) -> Option<Vec<String>> {
+        let start = javascript.find(function_prefix)?;
+        let open = start + javascript[start..].find('(')?;
+        let close = open + javascript[open..].find(')')?;
+        Some(
+            javascript[open + 1..close]
+                .split(',')
+                .map(|argument| {
+                    argument
+                        .trim()
+                        .trim_matches('"')
+                        .trim_matches('\'')
+                        .to_owned()
+                })
+                .collect(),
+        )
+    }
+
+    pub(crate) fn validate(&self, field_name: &str, value: &str) -> Result<(), Error> {
+        match self {
+            FieldFormat::Number { separator_style } => {
+                let (thousands, decimal) = match separator_style {
+                    1 => ("", "."),
+                    2 => (".", ","),
+                    3 => ("", ","),
+                    4 => ("'", "."),
+                    _ => (",", "."),
+                };
+                let mut number = value.trim().to_owned();
+                if !thousands.is_empty() {
+                    number = number.replace(thousands, "");
+                }
+                let number = number.replace(decimal, ".");
+                number.parse::<f64>().map(|_| ()).map_err(|_| {
+                    Error::Other(format!(
+                        "Value `{}` for field `{}` is not a valid number.",
+                        value, field_name
+                    ))
+                })
+            }
+            FieldFormat::Date { format } => {
+                Self::parse_date(value.trim(), &Self::to_strftime(format)).map_err(|err| {
+                    Error::Other(format!(
+                        "Value `{}` for field `{}` does not match date format `{}`: {}.",
+                        value, field_name, format, err
+                    ))
+                })
+            }
+        }
+    }
+
+    /// Parse `value` with the `strftime` format. If the format has a month, the value must
+    /// also be an existing date (so `02/31/2024` is rejected).
+    fn parse_date(value: &str, strftime: &str) -> ParseResult<()> {
+        let mut parsed = Parsed::new();
+        parse(&mut parsed, value, StrftimeItems::new(strftime))?;
+        if !["%m", "%b", "%B"]
+            .iter()
+            .any(|month| strftime.contains(month))
+        {
+            return Ok(());
+        }
+        // Formats without a year (`m/d`) are checked in a leap year,
+        // formats without a day (`mm/yy`) on the first day of the month.
+        if !strftime.contains("%Y") && !strftime.contains("%y") {
+            parsed.set_year(2000)?;
+        }
+        if !strftime.contains("%d") {
+            parsed.set_day(1)?;
+        }
+        parsed.to_naive_date().map(|_| ())
+    }
+
+    /// Convert an Acrobat date format (`dd/mm/yyyy`) to a `strftime` format (`%d/%m/%Y`).
+    fn to_strftime(format: &str) -> String {
+        // Longest tokens first, so `mmmm` is not matched as `mm` twice.
+        let tokens = [
+            ("yyyy", "%Y"),
+            ("mmmm", "%B"),
+            ("dddd", "%A"),
+            ("mmm", "%b"),
+            ("ddd", "%a"),
+            ("yy", "%y"),
+            ("mm", "%m"),
+            ("dd", "%d"),
+            ("HH", "%H"),
+            ("hh", "%I"),
+            ("MM", "%M"),
+            ("ss", "%S"),
+            ("tt", "%p"),
+            ("m", "%m"),
+            ("d", "%d"),
+            ("H", "%H"),
+            ("h", "%I"),
+            ("M", "%M"),
+            ("s", "%S"),
+        ];
+        let mut result = String::new();
+        let mut rest = format;
+        'outer: while let Some(next_char) = rest.chars().next() {
+            for (token, replacement) in tokens {
+                if let Some(after) = rest.strip_prefix(token) {
+                    result.push_str(replacement);
+                    rest = after;
+                    continue 'outer;
+                }
+            }
+            if next_char == '%' {
+                result.push_str("%%");
+            } else {
+                result.push(next_char);
+            }
+            rest = &rest[next_char.len_utf8()..];
+        }
+        result
+    }
+}
+
+/// Get `key` of the field, or else of one of its parents (`Parent`), for inheritable
+/// attributes like `MaxLen`. At most `max_depth` parents are checked.
+fn get_inherited<'a>(
+    doc: &'a Document,
+    field_dict: &'a Dictionary,
+    key: &[u8],
+    max_depth: usize,
+) -> Option<&'a Object> {
+    let mut node = field_dict;
+    for _ in 0..max_depth {
+        if let Ok(value) = node.get(key) {
+            return value.deref(doc).ok();
+        }
+        node = node.get(b"Parent").ok()?.deref(doc).ok()?.as_dict().ok()?;
+    }
+    None
+}
+
+/// Validate `value` against the `MaxLen` of the field (or inherited from its parents)
+/// and (optionally) the format of the field.
+pub(crate) fn validate_field_value(
+    doc: &Document,
+    field_dict: &Dictionary,
+    field_name: &str,
+    value: &str,
+    check_format: bool,
+    max_depth: usize,
+) -> Result<(), Error> {
+    let max_len = get_inherited(doc, field_dict, b"MaxLen", max_depth)
+        .and_then(|max_len| max_len.as_i64().ok());
+    if let Some(max_len) = max_len {
+        let length = value.chars().count();
+        if length as i64 > max_len {
+            return Err(Error::Other(format!(
+                "Value for field `{}` is too long. Maximum length: `{}`, Length: `{}`.",
+                field_name, max_len, length
+            )));
+        }
+    }
+
+    if check_format {
+        if let Some(format) = FieldFormat::from_field(doc, field_dict)? {
+            format.validate(field_name, value)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,206 @@
+//! Flatten the form: the appearances of the fields are drawn in the page content and
+//! the fields are removed, see `flatten_form`.
+
+use crate::image_replace::{multiply, transform_unit_square};
+use crate::journal::JournalChange;
+use crate::pdf_object::PdfObjectDeref;
+use crate::rectangle::Rectangle;
+use crate::text::IDENTITY;
+use crate::{Error, PDFSigningDocument};
+use lopdf::content::{Content, Operation};
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+/// Annotation flags (`F`) of annotations that are not shown.
+const FLAG_HIDDEN: i64 = 1 << 1;
+const FLAG_NO_VIEW: i64 = 1 << 5;
+
+impl PDFSigningDocument {
+    /// Draw the appearance of every form field (widget) in the content of its page and
+    /// remove the form, so the values can not be changed anymore. Hidden fields and
+    /// fields without appearance are removed without drawing them.
+    /// Return the number of widgets that are removed.
+    ///
+    /// The document is rewritten without its earlier versions (including the changes that
+    /// are not saved yet), so existing signatures become invalid.
+    /// The document is not changed when it has no form.
+    pub fn flatten_form(&mut self) -> Result<usize, Error> {
+        let mut doc = self.get_latest_document();
+        let catalog_id = doc.trailer.get(b"Root")?.as_reference()?;
+        let has_form = doc.get_object(catalog_id)?.as_dict()?.has(b"AcroForm");
+
+        let page_ids: Vec<ObjectId> = doc.get_pages().into_values().collect();
+        let mut widget_count = 0;
+        let mut page_indexes = vec![];
+        for (page_index, page_id) in page_ids.into_iter().enumerate() {
+            let used_names = self.get_xobject_names(page_id);
+            let count = flatten_page(&mut doc, page_id, used_names)?;
+            if count > 0 {
+                widget_count += count;
+                page_indexes.push(page_index);
+            }
+        }
+        if !has_form && widget_count == 0 {
+            return Ok(0);
+        }
+        doc.get_object_mut(catalog_id)?
+            .as_dict_mut()?
+            .remove(b"AcroForm");
+        // Remove the fields and the appearances that are not used anymore.
+        doc.prune_objects();
+        self.reload_document(doc)?;
+        self.record_change(JournalChange::FormFlattened { page_indexes });
+        Ok(widget_count)
+    }
+}
+
+/// Draw the widgets of the page in its content and remove them from the annotations.
+/// `used_names` are the names of the XObjects of the page.
+/// Return the number of widgets that are removed.
+fn flatten_page(
+    doc: &mut Document,
+    page_id: ObjectId,
+    mut used_names: Vec<Vec<u8>>,
+) -> Result<usize, Error> {
+    let annots = match doc.get_object(page_id)?.as_dict()?.get(b"Annots") {
+        Ok(annots) => annots.deref(doc)?.as_array()?.clone(),
+        Err(_) => return Ok(0),
+    };
+    let mut kept_annots = vec![];
+    let mut widget_count = 0;
+    let mut xobjects = vec![];
+    let mut operations = vec![];
+    for annot in annots {
+        let widget = match annot.deref(doc).ok().and_then(|annot| annot.as_dict().ok()) {
+            Some(widget) if is_widget(widget) => widget,
+            _ => {
+                kept_annots.push(annot);
+                continue;
+            }
+        };
+        if let Some((appearance_id, matrix)) = get_appearance_placement(doc, widget) {
+            let name = (1..)
+                .map(|number| format!("Flattened{}", number).into_bytes())
+                .find(|name| !used_names.contains(name))
+                .unwrap_or_default();
+            used_names.push(name.clone());
+            operations.extend([
+                Operation::new("q", vec![]),
+                Operation::new("cm", matrix.iter().map(|&value| value.into()).collect()),
+                Operation::new("Do", vec![Object::Name(name.clone())]),
+                Operation::new("Q", vec![]),
+            ]);
+            xobjects.push((name, appearance_id));
+        }
+        widget_count += 1;
+    }
+    if widget_count == 0 {
+        return Ok(0);
+    }
+
+    for (name, appearance_id) in xobjects {
+        doc.add_xobject(page_id, name, appearance_id)?;
+    }
+    if !operations.is_empty() {
+        doc.add_to_page_content(page_id, Content { operations })?;
+    }
+    let page = doc.get_object_mut(page_id)?.as_dict_mut()?;
+    if kept_annots.is_empty() {
+        page.remove(b"Annots");
+    } else {
+        page.set("Annots", kept_annots);
+    }
+    Ok(widget_count)
+}
+
+fn is_widget(annot: &Dictionary) -> bool {
+    annot
+        .get(b"Subtype")
+        .and_then(|subtype| subtype.as_name())
+        .is_ok_and(|subtype| subtype == b"Widget")
+}
+
+/// Get the normal appearance of the widget (for its state `AS` when it has more than 1)
+/// and the matrix that places it in the `Rect` of the widget.
+/// `None` when the widget is hidden or has no appearance.
+fn get_appearance_placement(doc: &Document, widget: &Dictionary) -> Option<(ObjectId, [f64; 6])> {
+    let flags = widget
+        .get(b"F")
+        .and_then(|flags| flags.as_i64())
+        .unwrap_or(0);
+    if flags & (FLAG_HIDDEN | FLAG_NO_VIEW) != 0 {
+        return None;
+    }
+    let normal = widget
+        .get(b"AP")
+        .ok()?
+        .deref(doc)
+        .ok()?
+        .as_dict()
+        .ok()?
+        .get(b"N")
+        .ok()?;
+    let appearance_id = match normal.deref(doc).ok()? {
+        Object::Dictionary(states) => {
+            let state = widget.get(b"AS").ok()?.as_name().ok()?;
+            states.get(state).ok()?.as_reference().ok()?
+        }
+        _ => normal.as_reference().ok()?,
+    };
+    let appearance = doc.get_object(appearance_id).ok()?.as_stream().ok()?;
+
+    let rect =
+        Rectangle::from_array(widget.get(b"Rect").ok()?.deref(doc).ok()?.as_array().ok()?).ok()?;
+    let bbox = appearance
+        .dict
+        .get(b"BBox")
+        .ok()?
+        .deref(doc)
+        .ok()?
+        .as_array()
+        .ok()?;
+    let bbox = Rectangle::from_array(bbox).ok()?;
+    let matrix = match appearance
+        .dict
+        .get(b"Matrix")
+        .and_then(|matrix| matrix.as_array())
+    {
+        Ok(matrix) if matrix.len() == 6 => {
+            let mut values = IDENTITY;
+            for (value, object) in values.iter_mut().zip(matrix) {
+                *value = object
+                    .as_f64()
+                    .or_else(|_| object.as_i64().map(|value| value as f64))
+                    .ok()?;
+            }
+            values
+        }
+        _ => IDENTITY,
+    };
+
+    // Map the bounding box (after the matrix of the appearance) to the `Rect` of the widget.
+    let bbox_to_page = [
+        bbox.width(),
+        0.0,
+        0.0,
+        bbox.height(),
+        bbox.x1.min(bbox.x2),
+        bbox.y1.min(bbox.y2),
+    ];
+    let transformed = transform_unit_square(&multiply(&bbox_to_page, &matrix));
+    if transformed.width() <= 0.0 || transformed.height() <= 0.0 {
+        return None;
+    }
+    let scale_x = rect.width() / transformed.width();
+    let scale_y = rect.height() / transformed.height();
+    Some((
+        appearance_id,
+        [
+            scale_x,
+            0.0,
+            0.0,
+            scale_y,
+            rect.x1.min(rect.x2) - transformed.x1 * scale_x,
+            rect.y1.min(rect.y2) - transformed.y1 * scale_y,
+        ],
+    ))
+}
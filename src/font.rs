@@ -0,0 +1,340 @@
+//! TrueType fonts embedded in the document, needed for PDF/A where standard fonts are not allowed.
+//!
+//! Only simple fonts with `WinAnsiEncoding` (characters 32 to 255) are created.
+
+use crate::Error;
+use lopdf::{dictionary, Dictionary, Object, ObjectId, Stream};
+
+/// Characters 0x80 to 0x9F of `WinAnsiEncoding`, the other characters are the same as Latin-1.
+const WIN_ANSI_80_TO_9F: [Option<char>; 32] = [
+    Some('€'),
+    None,
+    Some('‚'),
+    Some('ƒ'),
+    Some('„'),
+    Some('…'),
+    Some('†'),
+    Some('‡'),
+    Some('ˆ'),
+    Some('‰'),
+    Some('Š'),
+    Some('‹'),
+    Some('Œ'),
+    None,
+    Some('Ž'),
+    None,
+    None,
+    Some('‘'),
+    Some('’'),
+    Some('“'),
+    Some('”'),
+    Some('•'),
+    Some('–'),
+    Some('—'),
+    Some('˜'),
+    Some('™'),
+    Some('š'),
+    Some('›'),
+    Some('œ'),
+    None,
+    Some('ž'),
+    Some('Ÿ'),
+];
+const FIRST_CHAR: u8 = 32;
+const LAST_CHAR: u8 = 255;
+
+/// A TrueType font program and the metrics needed to embed it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TrueTypeFont {
+    data: Vec<u8>,
+    /// PostScript name of the font.
+    name: String,
+    /// Widths of the characters `FIRST_CHAR` to `LAST_CHAR` (in 1/1000 of the font size),
+    /// `None` when the font has no glyph for the character.
+    widths: Vec<Option<f64>>,
+    /// Width of the `.notdef` glyph.
+    missing_width: f64,
+    bbox: [f64; 4],
+    ascent: f64,
+    descent: f64,
+    cap_height: f64,
+}
+
+impl TrueTypeFont {
+    /// Read the metrics of a TrueType font (`.ttf`).
+    /// OpenType fonts with CFF outlines (`.otf`) and font collections are not supported.
+    pub(crate) fn parse(data: Vec<u8>) -> Result<Self, Error> {
+        match data.get(..4) {
+            Some([0, 1, 0, 0]) | Some(b"true") => {}
+            Some(b"OTTO") => return Err(Error::from(
+                "Font: OpenType fonts with CFF outlines are not supported, use a TrueType font.",
+            )),
+            _ => return Err(Error::from("Font: Data is not a TrueType font.")),
+        }
+        let head = find_table(&data, b"head")?;
+        let hhea = find_table(&data, b"hhea")?;
+        let hmtx = find_table(&data, b"hmtx")?;
+        let cmap = get_unicode_cmap(find_table(&data, b"cmap")?)?;
+
+        let units_per_em = read_u16(head, 18)?.max(1) as f64;
+        let scale = |value: f64| (value * 1000.0 / units_per_em).round();
+        let metric_count = read_u16(hhea, 34)?.max(1);
+        let get_advance_width = |glyph_index: u16| {
+            read_u16(hmtx, 4 * glyph_index.min(metric_count - 1) as usize)
+                .map(|width| scale(width as f64))
+        };
+
+        let mut widths = vec![];
+        for code in FIRST_CHAR..=LAST_CHAR {
+            let glyph_index = match decode_win_ansi(code) {
+                Some(character) => get_glyph_index(cmap, character)?,
+                None => 0,
+            };
+            widths.push(match glyph_index {
+                0 => None,
+                _ => Some(get_advance_width(glyph_index)?),
+            });
+        }
+        let ascent = scale(read_i16(hhea, 4)? as f64);
+        let cap_height = match find_table(&data, b"OS/2") {
+            // `sCapHeight` was added in version 2.
+            Ok(os2) if read_u16(os2, 0)? >= 2 => scale(read_i16(os2, 88)? as f64),
+            _ => ascent,
+        };
+        Ok(TrueTypeFont {
+            name: get_postscript_name(&data).unwrap_or_else(|| "EmbeddedFont".to_owned()),
+            widths,
+            missing_width: get_advance_width(0)?,
+            bbox: [
+                scale(read_i16(head, 36)? as f64),
+                scale(read_i16(head, 38)? as f64),
+                scale(read_i16(head, 40)? as f64),
+                scale(read_i16(head, 42)? as f64),
+            ],
+            ascent,
+            descent: scale(read_i16(hhea, 6)? as f64),
+            cap_height,
+            data,
+        })
+    }
+
+    /// Encode `text` in `WinAnsiEncoding`.
+    /// Characters that are not in the encoding or the font are replaced by `?`.
+    pub(crate) fn encode(&self, text: &str) -> Vec<u8> {
+        encode_win_ansi(text)
+            .into_iter()
+            .map(|code| match self.get_width(code) {
+                Some(_) => code,
+                None => b'?',
+            })
+            .collect()
+    }
+
+    /// Get the width of the encoded text, in 1/1000 of the font size.
+    pub(crate) fn get_text_width(&self, encoded_text: &[u8]) -> f64 {
+        encoded_text
+            .iter()
+            .map(|code| self.get_width(*code).unwrap_or(self.missing_width))
+            .sum()
+    }
+
+    /// Height above the baseline, in 1/1000 of the font size.
+    pub(crate) fn ascent(&self) -> f64 {
+        self.ascent
+    }
+
+    /// Depth below the baseline (a negative number), in 1/1000 of the font size.
+    pub(crate) fn descent(&self) -> f64 {
+        self.descent
+    }
+
+    fn get_width(&self, code: u8) -> Option<f64> {
+        let index = code.checked_sub(FIRST_CHAR)?;
+        *self.widths.get(index as usize)?
+    }
+
+    /// Create the font dictionary, the font descriptor and font program are added with `add_object`.
+    pub(crate) fn to_font_dictionary<F: FnMut(Object) -> ObjectId>(
+        &self,
+        mut add_object: F,
+    ) -> Dictionary {
+        let mut font_file = Stream::new(
+            dictionary! {
+                "Length1" => self.data.len() as i64,
+            },
+            self.data.clone(),
+        );
+        let _ = font_file.compress();
+        let font_file_id = add_object(Object::Stream(font_file));
+
+        let font_name = Object::Name(self.name.as_bytes().to_vec());
+        let font_descriptor_id = add_object(Object::Dictionary(dictionary! {
+            "Type" => "FontDescriptor",
+            "FontName" => font_name.clone(),
+            // Nonsymbolic: the characters are in the standard Latin character set.
+            "Flags" => 32,
+            "FontBBox" => self.bbox.iter().map(|value| Object::from(*value)).collect::<Vec<_>>(),
+            "ItalicAngle" => 0,
+            "Ascent" => self.ascent,
+            "Descent" => self.descent,
+            "CapHeight" => self.cap_height,
+            "StemV" => 80,
+            "MissingWidth" => self.missing_width,
+            "FontFile2" => Object::Reference(font_file_id),
+        }));
+
+        dictionary! {
+            "Type" => "Font",
+            "Subtype" => "TrueType",
+            "BaseFont" => font_name,
+            "FirstChar" => FIRST_CHAR as i64,
+            "LastChar" => LAST_CHAR as i64,
+            "Widths" => self
+                .widths
+                .iter()
+                .map(|width| Object::from(width.unwrap_or(self.missing_width)))
+                .collect::<Vec<_>>(),
+            "Encoding" => "WinAnsiEncoding",
+            "FontDescriptor" => Object::Reference(font_descriptor_id),
+        }
+    }
+}
+
+/// Encode `text` in `WinAnsiEncoding`, characters that are not in the encoding become `?`.
+pub(crate) fn encode_win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|character| {
+            (FIRST_CHAR..=LAST_CHAR)
+                .find(|code| decode_win_ansi(*code) == Some(character))
+                .unwrap_or(b'?')
+        })
+        .collect()
+}
+
+pub(crate) fn decode_win_ansi(code: u8) -> Option<char> {
+    match code {
+        0x80..=0x9F => WIN_ANSI_80_TO_9F[(code - 0x80) as usize],
+        0x20..=0x7E | 0xA0..=0xFF => Some(code as char),
+        _ => None,
+    }
+}
+
+fn find_table<'a>(data: &'a [u8], tag: &[u8; 4]) -> Result<&'a [u8], Error> {
+    let table_count = read_u16(data, 4)?;
+    for index in 0..table_count as usize {
+        let record = 12 + 16 * index;
+        if data.get(record..record + 4) == Some(tag) {
+            let offset = read_u32(data, record + 8)? as usize;
+            let length = read_u32(data, record + 12)? as usize;
+            return data
+                .get(offset..offset + length)
+                .ok_or_else(|| Error::from("Font: Unexpected end of the font data."));
+        }
+    }
+    Err(Error::Other(format!(
+        "Font: Table `{}` not found.",
+        String::from_utf8_lossy(tag)
+    )))
+}
+
+/// Get the Unicode (platform 3, encoding 1) character map, it has to use format 4.
+fn get_unicode_cmap(cmap: &[u8]) -> Result<&[u8], Error> {
+    let subtable_count = read_u16(cmap, 2)?;
+    for index in 0..subtable_count as usize {
+        let record = 4 + 8 * index;
+        if read_u16(cmap, record)? == 3 && read_u16(cmap, record + 2)? == 1 {
+            let subtable = cmap
+                .get(read_u32(cmap, record + 4)? as usize..)
+                .ok_or_else(|| Error::from("Font: Unexpected end of the font data."))?;
+            if read_u16(subtable, 0)? == 4 {
+                return Ok(subtable);
+            }
+        }
+    }
+    Err(Error::from(
+        "Font: The font has no Unicode character map (`cmap` format 4).",
+    ))
+}
+
+/// Get the glyph of `character` from a format 4 character map, 0 when it is missing.
+fn get_glyph_index(cmap: &[u8], character: char) -> Result<u16, Error> {
+    let code = match u16::try_from(character as u32) {
+        Ok(code) => code,
+        Err(_) => return Ok(0),
+    };
+    let segment_count = (read_u16(cmap, 6)? / 2) as usize;
+    let end_codes = 14;
+    let start_codes = end_codes + 2 * segment_count + 2;
+    let deltas = start_codes + 2 * segment_count;
+    let range_offsets = deltas + 2 * segment_count;
+    for segment in 0..segment_count {
+        if code > read_u16(cmap, end_codes + 2 * segment)? {
+            continue;
+        }
+        let start_code = read_u16(cmap, start_codes + 2 * segment)?;
+        if code < start_code {
+            return Ok(0);
+        }
+        let delta = read_u16(cmap, deltas + 2 * segment)?;
+        let range_offset_position = range_offsets + 2 * segment;
+        let range_offset = read_u16(cmap, range_offset_position)? as usize;
+        if range_offset == 0 {
+            return Ok(code.wrapping_add(delta));
+        }
+        let glyph_position =
+            range_offset_position + range_offset + 2 * (code - start_code) as usize;
+        return Ok(match read_u16(cmap, glyph_position)? {
+            0 => 0,
+            glyph_index => glyph_index.wrapping_add(delta),
+        });
+    }
+    Ok(0)
+}
+
+/// Get the PostScript name (name ID 6) of the font, without characters that are not allowed.
+fn get_postscript_name(data: &[u8]) -> Option<String> {
+    let names = find_table(data, b"name").ok()?;
+    let record_count = read_u16(names, 2).ok()?;
+    let strings = read_u16(names, 4).ok()? as usize;
+    for index in 0..record_count as usize {
+        let record = 6 + 12 * index;
+        if read_u16(names, record + 6).ok()? != 6 {
+            continue;
+        }
+        let length = read_u16(names, record + 8).ok()? as usize;
+        let offset = strings + read_u16(names, record + 10).ok()? as usize;
+        let bytes = names.get(offset..offset + length)?;
+        let name: String = match read_u16(names, record).ok()? {
+            // Windows names are UTF-16BE, the ASCII characters are enough.
+            3 => bytes
+                .chunks_exact(2)
+                .map(|unit| char::from(unit[1]))
+                .collect(),
+            _ => bytes.iter().map(|byte| char::from(*byte)).collect(),
+        };
+        let name: String = name
+            .chars()
+            .filter(|character| character.is_ascii_alphanumeric() || *character == '-')
+            .collect();
+        if !name.is_empty() {
+            return Some(name);
+        }
+    }
+    None
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, Error> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| Error::from("Font: Unexpected end of the font data."))
+}
+
+fn read_i16(data: &[u8], offset: usize) -> Result<i16, Error> {
+    read_u16(data, offset).map(|value| value as i16)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, Error> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| Error::from("Font: Unexpected end of the font data."))
+}
@@ -0,0 +1,209 @@
+//! Information about the form fields of a document, see `get_form_fields`.
+
+use crate::acro_form::{AcroForm, FormComponent};
+use crate::rectangle::Rectangle;
+use crate::{Error, PDFSigningDocument};
+use lopdf::{Dictionary, Object, ObjectId};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// Field flag (`Ff`) of radio buttons.
+const FLAG_RADIO: u32 = 1 << 15;
+/// Field flag (`Ff`) of push buttons.
+const FLAG_PUSHBUTTON: u32 = 1 << 16;
+/// Field flag (`Ff`) of combo boxes, choice fields without it are list boxes.
+const FLAG_COMBO: u32 = 1 << 17;
+
+/// The type of a form field, from the field type (`FT`) and the field flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum FormFieldType {
+    PushButton,
+    CheckBox,
+    RadioButton,
+    Text,
+    ListBox,
+    ComboBox,
+    Signature,
+    Unknown,
+}
+
+/// A form field of the document, see `PDFSigningDocument::get_form_fields`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct FormField {
+    #[cfg_attr(feature = "serde", serde(skip))]
+    object_id: Option<ObjectId>,
+    name: Option<String>,
+    fully_qualified_name: Option<String>,
+    alternate_name: Option<String>,
+    field_type: FormFieldType,
+    flags: u32,
+    rect: Option<Rectangle>,
+    page_index: Option<usize>,
+    is_signed: bool,
+}
+
+impl FormField {
+    /// The ObjectId of the field dictionary.
+    pub fn object_id(&self) -> Option<ObjectId> {
+        self.object_id
+    }
+
+    /// The partial field name (`T`), used by functions like `set_field_tooltip`.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The partial field names of the parents and this field, separated by `.`.
+    pub fn fully_qualified_name(&self) -> Option<&str> {
+        self.fully_qualified_name.as_deref()
+    }
+
+    /// The alternate field name (`TU`), shown as tooltip by viewers.
+    pub fn alternate_name(&self) -> Option<&str> {
+        self.alternate_name.as_deref()
+    }
+
+    pub fn field_type(&self) -> FormFieldType {
+        self.field_type
+    }
+
+    /// The field flags (`Ff`), also when they are inherited from a parent.
+    /// For example bit 1 (`1`) is read only and bit 2 (`2`) is required.
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// The rectangle of the first widget, in the coordinates of the page.
+    pub fn rect(&self) -> Option<&Rectangle> {
+        self.rect.as_ref()
+    }
+
+    /// The index of the page of the first widget, the first page has index 0.
+    pub fn page_index(&self) -> Option<usize> {
+        self.page_index
+    }
+
+    /// Check if this is a signature field that is already signed.
+    pub fn is_signed(&self) -> bool {
+        self.is_signed
+    }
+}
+
+impl PDFSigningDocument {
+    /// Get the form fields of the document, the properties include the changes not saved yet.
+    /// Fields that are added in the changes not saved yet are not included.
+    pub fn get_form_fields(&mut self) -> Result<Vec<FormField>, Error> {
+        self.load_all()?;
+        let forms = self.acro_form.clone().unwrap_or_default();
+        Ok(forms
+            .iter()
+            .map(|form| self.get_form_field_info(form))
+            .collect())
+    }
+
+    pub(crate) fn get_form_field_info(&self, form: &AcroForm) -> FormField {
+        let field = form
+            .get_object_id()
+            .and_then(|object_id| self.get_latest_object(object_id))
+            .and_then(|field| field.as_dict().ok());
+        let flags = field
+            .and_then(|field| self.get_inherited_field_attribute(field, b"Ff"))
+            .and_then(|flags| flags.as_i64().ok())
+            .unwrap_or(0) as u32;
+        let field_type = match form.get_form_component() {
+            FormComponent::Button if flags & FLAG_PUSHBUTTON != 0 => FormFieldType::PushButton,
+            FormComponent::Button if flags & FLAG_RADIO != 0 => FormFieldType::RadioButton,
+            FormComponent::Button | FormComponent::CheckBox => FormFieldType::CheckBox,
+            FormComponent::Radio => FormFieldType::RadioButton,
+            FormComponent::Text => FormFieldType::Text,
+            FormComponent::ComboBox | FormComponent::ListBox if flags & FLAG_COMBO != 0 => {
+                FormFieldType::ComboBox
+            }
+            FormComponent::ComboBox | FormComponent::ListBox => FormFieldType::ListBox,
+            FormComponent::EmptySignature | FormComponent::SignedSignature { .. } => {
+                FormFieldType::Signature
+            }
+            FormComponent::Unknown => FormFieldType::Unknown,
+        };
+
+        let widget_id = form
+            .get_widget_ids(self.raw_document.get_prev_documents())
+            .ok()
+            .and_then(|widget_ids| widget_ids.first().copied());
+        let widget = widget_id
+            .and_then(|widget_id| self.get_latest_object(widget_id))
+            .and_then(|widget| widget.as_dict().ok());
+        let rect = widget
+            .and_then(|widget| widget.get(b"Rect").ok())
+            .and_then(|rect| self.resolve_latest_object(rect))
+            .and_then(|rect| rect.as_array().ok())
+            .and_then(|rect| Rectangle::from_array(rect).ok());
+        let page_index = widget_id.and_then(|widget_id| self.get_widget_page_index(widget_id));
+
+        FormField {
+            object_id: form.get_object_id(),
+            name: form.get_partial_field_name().map(str::to_owned),
+            fully_qualified_name: form.get_fully_qualified_field_name().map(str::to_owned),
+            alternate_name: form.get_alternate_field_name().map(str::to_owned),
+            field_type,
+            flags,
+            rect,
+            page_index,
+            is_signed: matches!(
+                form.get_form_component(),
+                FormComponent::SignedSignature { .. }
+            ),
+        }
+    }
+
+    /// Get an attribute of the field, also when it is inherited from a parent field.
+    fn get_inherited_field_attribute<'a>(
+        &'a self,
+        field: &'a Dictionary,
+        key: &[u8],
+    ) -> Option<&'a Object> {
+        let mut node = field;
+        // Limit the depth, so a loop of parents does not hang.
+        for _ in 0..self.limits.max_depth {
+            if let Ok(value) = node.get(key) {
+                return self.resolve_latest_object(value);
+            }
+            node = node
+                .get(b"Parent")
+                .ok()
+                .and_then(|parent| self.resolve_latest_object(parent))?
+                .as_dict()
+                .ok()?;
+        }
+        None
+    }
+
+    /// Get the index of the page with the widget, from the page of the widget (`P`) or
+    /// else the page that has the widget in its annotations.
+    fn get_widget_page_index(&self, widget_id: ObjectId) -> Option<usize> {
+        let page_id = self
+            .get_latest_object(widget_id)
+            .and_then(|widget| widget.as_dict().ok())
+            .and_then(|widget| widget.get(b"P").ok())
+            .and_then(|page| page.as_reference().ok());
+        if let Some(page_index) = page_id.and_then(|page_id| self.get_page_index(page_id)) {
+            return Some(page_index);
+        }
+        self.get_page_ids().iter().position(|page_id| {
+            self.get_latest_object(*page_id)
+                .and_then(|page| page.as_dict().ok())
+                .and_then(|page| page.get(b"Annots").ok())
+                .and_then(|annots| self.resolve_latest_object(annots))
+                .and_then(|annots| annots.as_array().ok())
+                .map(|annots| {
+                    annots
+                        .iter()
+                        .any(|annot| annot.as_reference().ok() == Some(widget_id))
+                })
+                .unwrap_or(false)
+        })
+    }
+}
@@ -0,0 +1,236 @@
+use crate::acro_form::FormComponent;
+use crate::font::encode_win_ansi;
+use crate::rectangle::Rectangle;
+use crate::utils::parse_font;
+use crate::{lopdf_utils, Error, PDFSigningDocument};
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Object, Stream, StringFormat};
+use std::collections::HashMap;
+use std::str::from_utf8;
+
+/// An option of a choice field (combo box or list box).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChoiceOption {
+    /// The value stored in `V` when this option is selected.
+    pub export_value: String,
+    /// The text shown to the user.
+    pub display_value: String,
+}
+
+impl ChoiceOption {
+    pub fn new(export_value: String, display_value: String) -> Self {
+        ChoiceOption {
+            export_value,
+            display_value,
+        }
+    }
+
+    fn to_object(&self) -> Object {
+        if self.export_value == self.display_value {
+            lopdf_utils::text_string(&self.display_value)
+        } else {
+            Object::Array(vec![
+                lopdf_utils::text_string(&self.export_value),
+                lopdf_utils::text_string(&self.display_value),
+            ])
+        }
+    }
+}
+
+impl PDFSigningDocument {
+    /// Set or update the alternate field name (`TU`) of a field.
+    /// This is used by screen readers and shown as tooltip.
+    pub fn set_field_tooltip(&mut self, field_name: &str, tooltip: &str) -> Result<(), Error> {
+        self.set_field_tooltips(HashMap::from([(field_name.to_owned(), tooltip.to_owned())]))
+    }
+
+    /// Set or update the alternate field names (`TU`) of multiple fields.
+    /// The key of `tooltips` is the partial field name (`T`).
+    pub fn set_field_tooltips(&mut self, tooltips: HashMap<String, String>) -> Result<(), Error> {
+        self.load_acro_form()?;
+        let mut doc = self.raw_document.get_prev_documents().clone();
+
+        for (field_name, tooltip) in tooltips {
+            let field_id = self
+                .get_form_field(&field_name)?
+                .get_object_id()
+                .ok_or_else(|| Error::from("AcroForm object is not a indirect reference."))?;
+
+            let field = doc.get_object_mut(field_id)?.as_dict_mut()?;
+            field.set("TU", lopdf_utils::text_string(&tooltip));
+        }
+
+        self.reload_document(doc)
+    }
+
+    /// Set or replace the options (`Opt`) of a choice field (combo box or list box).
+    ///
+    /// The selected indices (`I`) are removed because they might no longer be valid.
+    /// Selected values (`V`) that are not one of the new options are removed, and the
+    /// appearance of the widgets is created again with the new options.
+    pub fn set_field_options(
+        &mut self,
+        field_name: &str,
+        options: &[ChoiceOption],
+    ) -> Result<(), Error> {
+        self.load_acro_form()?;
+        let form_field = self.get_form_field(field_name)?;
+        if !matches!(
+            form_field.get_form_component(),
+            FormComponent::ComboBox | FormComponent::ListBox
+        ) {
+            return Err(Error::UnsupportedFieldType {
+                name: field_name.to_owned(),
+                expected: "choice field".to_owned(),
+            });
+        }
+        let field_id = form_field
+            .get_object_id()
+            .ok_or_else(|| Error::from("AcroForm object is not a indirect reference."))?;
+
+        let is_combo_box = matches!(form_field.get_form_component(), FormComponent::ComboBox);
+
+        let mut doc = self.raw_document.get_prev_documents().clone();
+        let widget_ids = form_field.get_widget_ids(&doc)?;
+        let field = doc.get_object_mut(field_id)?.as_dict_mut()?;
+        field.set(
+            "Opt",
+            Object::Array(options.iter().map(ChoiceOption::to_object).collect()),
+        );
+        field.remove(b"I");
+
+        // `V` is 1 value, or an array of values for list boxes with multiple selections.
+        let values = match field.get(b"V") {
+            Ok(Object::String(bytes, _)) => vec![lopdf_utils::decode_text_string(bytes)],
+            Ok(Object::Array(values)) => values
+                .iter()
+                .filter_map(|value| match value {
+                    Object::String(bytes, _) => Some(lopdf_utils::decode_text_string(bytes)),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        };
+        let selected: Vec<&ChoiceOption> = options
+            .iter()
+            .filter(|option| values.contains(&option.export_value))
+            .collect();
+        match selected.as_slice() {
+            [] => {
+                field.remove(b"V");
+            }
+            [option] => field.set("V", lopdf_utils::text_string(&option.export_value)),
+            _ => field.set(
+                "V",
+                Object::Array(
+                    selected
+                        .iter()
+                        .map(|option| lopdf_utils::text_string(&option.export_value))
+                        .collect(),
+                ),
+            ),
+        }
+
+        let da = field.get(b"DA").ok().cloned();
+        let font = parse_font(match da {
+            Some(Object::String(ref bytes, _)) => Some(from_utf8(bytes)?),
+            _ => None,
+        });
+        let font_size = f64::from(if (font.0).1 > 0 { (font.0).1 } else { 12 });
+        let font_id = doc.add_object(Self::standard_font("Helvetica"));
+
+        for widget_id in widget_ids {
+            let widget = doc.get_object(widget_id)?.as_dict()?;
+            let rect = Rectangle::from_array(widget.get(b"Rect")?.as_array()?)?;
+            let (width, height) = (rect.width(), rect.height());
+            let content = if is_combo_box {
+                let text = selected
+                    .first()
+                    .map(|option| option.display_value.as_str())
+                    .unwrap_or_default();
+                choice_content(
+                    &[(text, false)],
+                    font_size,
+                    height / 2.0 - font_size * 0.35,
+                    width,
+                )
+            } else {
+                let lines: Vec<(&str, bool)> = options
+                    .iter()
+                    .map(|option| {
+                        let is_selected = selected.iter().any(|selected| *selected == option);
+                        (option.display_value.as_str(), is_selected)
+                    })
+                    .collect();
+                choice_content(&lines, font_size, height - 2.0 - font_size, width)
+            };
+            let mut stream = Stream::new(
+                dictionary! {
+                    "Type" => "XObject",
+                    "Subtype" => "Form",
+                    "BBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+                    "Resources" => dictionary! {
+                        "Font" => dictionary! { "Helv" => Object::Reference(font_id) },
+                    },
+                },
+                Content {
+                    operations: content,
+                }
+                .encode()?,
+            );
+            let _ = stream.compress();
+            let appearance_id = doc.add_object(stream);
+
+            let widget = doc.get_object_mut(widget_id)?.as_dict_mut()?;
+            widget.set(
+                "AP",
+                dictionary! {
+                    "N" => Object::Reference(appearance_id),
+                },
+            );
+        }
+
+        self.reload_document(doc)
+    }
+}
+
+/// The operations that draw `lines` (the text, and whether it is selected) of a choice
+/// field that is `width` wide, from the baseline `top` down. Selected lines get a
+/// highlight like viewers use.
+fn choice_content(lines: &[(&str, bool)], font_size: f64, top: f64, width: f64) -> Vec<Operation> {
+    let mut operations = vec![
+        Operation::new("BMC", vec!["Tx".into()]),
+        Operation::new("q", vec![]),
+    ];
+    for (index, (text, is_selected)) in lines.iter().enumerate() {
+        let baseline = top - font_size * index as f64;
+        if *is_selected {
+            operations.extend([
+                Operation::new("rg", vec![0.6.into(), 0.75.into(), 0.85.into()]),
+                Operation::new(
+                    "re",
+                    vec![
+                        1.into(),
+                        (baseline - font_size * 0.25).into(),
+                        (width - 2.0).into(),
+                        font_size.into(),
+                    ],
+                ),
+                Operation::new("f", vec![]),
+            ]);
+        }
+        operations.extend([
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec!["Helv".into(), font_size.into()]),
+            Operation::new("g", vec![0.into()]),
+            Operation::new("Td", vec![2.into(), baseline.into()]),
+            Operation::new(
+                "Tj",
+                vec![Object::String(encode_win_ansi(text), StringFormat::Literal)],
+            ),
+            Operation::new("ET", vec![]),
+        ]);
+    }
+    operations.extend([Operation::new("Q", vec![]), Operation::new("EMC", vec![])]);
+    operations
+}
@@ -0,0 +1,263 @@
+//! (Re-)encode raster images to keep the size of the PDF small.
+
+use crate::image_placement::POINTS_PER_INCH;
+use crate::image_xobject::{ColorSpace, ImageFilter, ImageXObject};
+use crate::Error;
+use std::io::Write;
+
+/// Quality used when a JPEG image is downscaled and no quality is set.
+const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+/// Settings used when raster images are (re-)encoded.
+/// The default keeps the images as they are.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ImageCompression {
+    /// Encode color and grayscale images as JPEG with this quality (1 - 100).
+    /// `None` keeps the images lossless. Soft masks always stay lossless.
+    pub jpeg_quality: Option<u8>,
+    /// Compression level (0 - 9) for lossless images.
+    /// `None` uses the default level when the document is saved.
+    pub flate_level: Option<u32>,
+    /// Downscale images that are displayed with a higher resolution (dots per inch).
+    pub max_dpi: Option<f64>,
+}
+
+impl ImageCompression {
+    /// The same settings, without JPEG encoding.
+    pub(crate) fn lossless(&self) -> Self {
+        ImageCompression {
+            jpeg_quality: None,
+            ..*self
+        }
+    }
+}
+
+impl ImageXObject {
+    /// Apply the `compression` settings to the image.
+    /// `display_size` is the size (in points) of the image on the page.
+    /// Black and white (CCITT) images are not changed.
+    pub(crate) fn compress(
+        self,
+        compression: &ImageCompression,
+        display_size: (f64, f64),
+    ) -> Result<Self, Error> {
+        if self.filter == ImageFilter::CcittFax {
+            return Ok(self);
+        }
+        let target_size = compression
+            .max_dpi
+            .and_then(|max_dpi| self.get_downscaled_size(max_dpi, display_size));
+
+        let mut original_jpeg = None;
+        let mut image = self;
+        if image.filter == ImageFilter::Dct {
+            if target_size.is_none() && compression.jpeg_quality.is_none() {
+                return Ok(image);
+            }
+            match image.decode_jpeg() {
+                Ok(decoded_image) => {
+                    original_jpeg = Some(image);
+                    image = decoded_image;
+                }
+                Err(err) => {
+                    tracing::warn!("Image is not re-encoded, decoding failed: {:?}", err);
+                    return Ok(image);
+                }
+            }
+        }
+
+        if let Some((width, height)) = target_size {
+            image = image.resize(width, height);
+        }
+        // A decoded JPEG is encoded as JPEG again, storing the pixels would make it larger.
+        let jpeg_quality = compression
+            .jpeg_quality
+            .or_else(|| original_jpeg.as_ref().map(|_| DEFAULT_JPEG_QUALITY));
+        let image = match jpeg_quality {
+            Some(quality) => image.encode_jpeg(quality)?,
+            None => image.encode_flate(compression.flate_level)?,
+        };
+
+        // Only changing the quality of a JPEG can make it larger.
+        match original_jpeg {
+            Some(original_jpeg)
+                if target_size.is_none()
+                    && original_jpeg.image_data.len() <= image.image_data.len() =>
+            {
+                Ok(original_jpeg)
+            }
+            _ => Ok(image),
+        }
+    }
+
+    /// Get the size (in pixels) needed for `max_dpi`, `None` if the image is small enough.
+    fn get_downscaled_size(&self, max_dpi: f64, display_size: (f64, f64)) -> Option<(u32, u32)> {
+        if max_dpi <= 0.0 || display_size.0 <= 0.0 || display_size.1 <= 0.0 {
+            return None;
+        }
+        let max_width = (display_size.0 / POINTS_PER_INCH * max_dpi).ceil().max(1.0) as u32;
+        let max_height = (display_size.1 / POINTS_PER_INCH * max_dpi).ceil().max(1.0) as u32;
+        if self.width <= max_width && self.height <= max_height {
+            return None;
+        }
+        Some((self.width.min(max_width), self.height.min(max_height)))
+    }
+
+    fn get_components(&self) -> usize {
+        match self.color_space {
+            ColorSpace::DeviceGray => 1,
+            ColorSpace::DeviceRGB => 3,
+            ColorSpace::DeviceCMYK => 4,
+        }
+    }
+
+    /// Decode JPEG (`DCTDecode`) data to 8 bit pixels.
+    #[cfg(feature = "images")]
+    fn decode_jpeg(&self) -> Result<Self, Error> {
+        use jpeg_decoder::PixelFormat;
+
+        let mut decoder = jpeg_decoder::Decoder::new(&*self.image_data);
+        let mut image_data = decoder
+            .decode()
+            .map_err(|err| Error::Other(format!("JPEG: {}", err)))?;
+        let info = decoder
+            .info()
+            .ok_or_else(|| Error::from("JPEG: Image info not found."))?;
+        let color_space = match info.pixel_format {
+            PixelFormat::L8 => ColorSpace::DeviceGray,
+            PixelFormat::RGB24 => ColorSpace::DeviceRGB,
+            PixelFormat::CMYK32 => {
+                // The decoder expects inverted (Adobe) CMYK data and inverts every value.
+                // Undo this for CMYK data that is not inverted, otherwise the colors change.
+                if !self.invert_colors {
+                    image_data
+                        .iter_mut()
+                        .for_each(|value| *value = 255 - *value);
+                }
+                ColorSpace::DeviceCMYK
+            }
+            PixelFormat::L16 => return Err(Error::from("JPEG: 16 bit images are not supported.")),
+        };
+        Ok(Self {
+            width: info.width as u32,
+            height: info.height as u32,
+            color_space,
+            bits_per_component: 8,
+            image_data,
+            filter: ImageFilter::None,
+            invert_colors: false,
+            interpolate: self.interpolate,
+            s_mask: self.s_mask,
+            icc_profile: self.icc_profile.clone(),
+            icc_profile_id: self.icc_profile_id,
+        })
+    }
+
+    #[cfg(not(feature = "images"))]
+    fn decode_jpeg(&self) -> Result<Self, Error> {
+        Err(Error::from(
+            "JPEG decoding is not supported, enable the `images` feature.",
+        ))
+    }
+
+    /// Resize the image by averaging the pixels (box filter).
+    /// Only uncompressed images with 8 bits per component are resized.
+    fn resize(self, width: u32, height: u32) -> Self {
+        if self.filter != ImageFilter::None || self.bits_per_component != 8 {
+            return self;
+        }
+        let components = self.get_components();
+        let (source_width, source_height) = (self.width as usize, self.height as usize);
+        let (width, height) = (width as usize, height as usize);
+        let size = source_width
+            .checked_mul(source_height)
+            .and_then(|size| size.checked_mul(components));
+        if !matches!(size, Some(size) if size <= self.image_data.len()) {
+            return self;
+        }
+
+        let mut image_data = Vec::with_capacity(width * height * components);
+        for y in 0..height {
+            let y_start = y * source_height / height;
+            let y_end = ((y + 1) * source_height / height).max(y_start + 1);
+            for x in 0..width {
+                let x_start = x * source_width / width;
+                let x_end = ((x + 1) * source_width / width).max(x_start + 1);
+                let count = ((y_end - y_start) * (x_end - x_start)) as u64;
+                for component in 0..components {
+                    let mut sum = 0u64;
+                    for source_y in y_start..y_end {
+                        for source_x in x_start..x_end {
+                            let index = (source_y * source_width + source_x) * components;
+                            sum += self.image_data[index + component] as u64;
+                        }
+                    }
+                    image_data.push((sum / count) as u8);
+                }
+            }
+        }
+
+        Self {
+            width: width as u32,
+            height: height as u32,
+            image_data,
+            ..self
+        }
+    }
+
+    /// Encode the pixels as JPEG (`DCTDecode`).
+    /// Only uncompressed images with 8 bits per component are encoded.
+    #[cfg(feature = "images")]
+    fn encode_jpeg(self, quality: u8) -> Result<Self, Error> {
+        if self.filter != ImageFilter::None || self.bits_per_component != 8 {
+            return Ok(self);
+        }
+        let (width, height) = match (u16::try_from(self.width), u16::try_from(self.height)) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => return Ok(self),
+        };
+        let color_type = match self.color_space {
+            ColorSpace::DeviceGray => jpeg_encoder::ColorType::Luma,
+            ColorSpace::DeviceRGB => jpeg_encoder::ColorType::Rgb,
+            ColorSpace::DeviceCMYK => jpeg_encoder::ColorType::Cmyk,
+        };
+
+        let mut jpeg_data = vec![];
+        jpeg_encoder::Encoder::new(&mut jpeg_data, quality.clamp(1, 100))
+            .encode(&self.image_data, width, height, color_type)
+            .map_err(|err| Error::Other(format!("JPEG: {}", err)))?;
+        // Parse the result, so inverted (Adobe) CMYK data is handled the same as other JPEGs.
+        let image = Self::from_jpeg(jpeg_data)?;
+        Ok(Self {
+            interpolate: self.interpolate,
+            s_mask: self.s_mask,
+            icc_profile: self.icc_profile,
+            icc_profile_id: self.icc_profile_id,
+            ..image
+        })
+    }
+
+    /// Without the `images` feature the image is compressed lossless.
+    #[cfg(not(feature = "images"))]
+    fn encode_jpeg(self, _quality: u8) -> Result<Self, Error> {
+        tracing::warn!("Image is not encoded as JPEG, enable the `images` feature.");
+        Ok(self)
+    }
+
+    /// Compress the data (`FlateDecode`) with `level`.
+    /// Without a level the data is compressed when the document is saved.
+    fn encode_flate(self, level: Option<u32>) -> Result<Self, Error> {
+        let level = match level {
+            Some(level) if self.filter == ImageFilter::None => level.min(9),
+            _ => return Ok(self),
+        };
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::new(level));
+        encoder.write_all(&self.image_data)?;
+        Ok(Self {
+            image_data: encoder.finish()?,
+            filter: ImageFilter::Flate,
+            ..self
+        })
+    }
+}
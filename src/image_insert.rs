@@ -1,51 +1,116 @@
-use crate::{image_xobject::ImageXObject, rectangle::Rectangle, Error};
-use lopdf::{
-    content::{Content, Operation},
-    Object, ObjectId,
+use crate::{
+    image_options::{with_graphics_state, ImageOptions},
+    image_xobject::ImageXObject,
+    rectangle::Rectangle,
+    svg::SvgImage,
+    Error,
 };
+use lopdf::{content::Content, dictionary, Object, ObjectId};
 use std::io::Read;
 
 pub trait InsertImage {
     fn add_object<T: Into<Object>>(&mut self, object: T) -> ObjectId;
 
+    /// Add an image, its soft mask and ICC profile to the object list.
+    /// The images are compressed for `display_size` (in points).
+    /// Return the ObjectId of the image.
+    fn add_image_xobject(
+        &mut self,
+        image_xobject: ImageXObject,
+        mask_xobject: Option<ImageXObject>,
+        options: &ImageOptions,
+        display_size: (f64, f64),
+    ) -> Result<ObjectId, Error> {
+        let image_xobject =
+            self.prepare_image_xobject(image_xobject, mask_xobject, options, display_size)?;
+        Ok(self.add_object(image_xobject))
+    }
+
+    /// Compress the image and add its soft mask and ICC profile to the object list.
+    /// The image itself is not added, this is done by `add_image_xobject`.
+    fn prepare_image_xobject(
+        &mut self,
+        image_xobject: ImageXObject,
+        mask_xobject: Option<ImageXObject>,
+        options: &ImageOptions,
+        display_size: (f64, f64),
+    ) -> Result<ImageXObject, Error> {
+        let mut image_xobject = image_xobject.compress(&options.compression, display_size)?;
+        if image_xobject.icc_profile.is_none() {
+            image_xobject.icc_profile = options.icc_profile.clone();
+        }
+        if let Some(icc_profile_stream) = image_xobject.take_icc_profile_stream() {
+            let icc_profile_id = self.add_object(icc_profile_stream);
+            image_xobject.icc_profile_id = Some(icc_profile_id);
+        }
+        if let Some(mask_xobject) = mask_xobject {
+            let mask_xobject =
+                mask_xobject.compress(&options.compression.lossless(), display_size)?;
+            let mask_xobject_id = self.add_object(mask_xobject);
+            image_xobject.s_mask = Some(mask_xobject_id);
+        }
+        Ok(image_xobject)
+    }
+
     /// Add image to pdf as XObject.
     /// The image will not be visible.
+    /// SVG images are converted to vector content instead, when `options.allow_svg` is set.
+    /// `options` define how the image is fitted into `rect` and how it is encoded.
     /// Return the ObjectId of the image.
     fn add_image_as_form_xobject<R: Read>(
         &mut self,
-        image_reader: R,
+        mut image_reader: R,
         image_name: &str,
         rect: Rectangle,
+        options: &ImageOptions,
     ) -> Result<ObjectId, Error> {
         use lopdf::{Object::*, Stream};
-        // Load image
-        let image_decoder = png::Decoder::new(image_reader);
-        let (mut image_xobject, mask_xobject) = ImageXObject::try_from(image_decoder)?;
-        // Add object to object list
-        if let Some(mask_xobject) = mask_xobject {
-            let mask_xobject_id = self.add_object(mask_xobject);
-            image_xobject.s_mask = Some(mask_xobject_id);
+        let mut image_data = vec![];
+        image_reader.read_to_end(&mut image_data)?;
+        if options.is_svg(&image_data) {
+            return self.add_svg_as_form_xobject(&image_data, rect, options);
         }
-        let image_xobject_id = self.add_object(image_xobject);
+        // Load image
+        let (image_xobject, mask_xobject) = ImageXObject::from_reader(&*image_data)?;
+        let image_size = (image_xobject.width as f64, image_xobject.height as f64);
 
-        let position = (0, 0);
         let size = (rect.x2 - rect.x1, rect.y2 - rect.y1);
+        let bbox = Rectangle {
+            x1: 0.0,
+            y1: 0.0,
+            x2: size.0,
+            y2: size.1,
+        };
+        let image_rect = options
+            .placement
+            .get_image_rect(image_size.0, image_size.1, &bbox);
+        let display_size = (image_rect.width(), image_rect.height());
+        // Add object to object list
+        let image_xobject_id =
+            self.add_image_xobject(image_xobject, mask_xobject, options, display_size)?;
+
+        let mut resources = lopdf::Dictionary::from_iter(vec![(
+            "XObject",
+            Dictionary(lopdf::Dictionary::from_iter(vec![(
+                image_name,
+                Reference(image_xobject_id),
+            )])),
+        )]);
+        let mut operations =
+            options
+                .placement
+                .image_operations(image_name, image_size.0, image_size.1, &bbox);
+        if let Some(graphics_state) = options.get_graphics_state() {
+            resources.set("ExtGState", dictionary! { "GS0" => graphics_state });
+            operations = with_graphics_state("GS0", operations);
+        }
 
         // Dictionary
         let form_xobject = lopdf::Dictionary::from_iter(vec![
             ("Type", Name("XObject".as_bytes().to_vec())),
             ("Subtype", Name("Form".as_bytes().to_vec())),
             // ("FormType", Integer(1)),
-            (
-                "Resources",
-                Dictionary(lopdf::Dictionary::from_iter(vec![(
-                    "XObject",
-                    Dictionary(lopdf::Dictionary::from_iter(vec![(
-                        image_name,
-                        Reference(image_xobject_id),
-                    )])),
-                )])),
-            ),
+            ("Resources", Dictionary(resources)),
             (
                 "BBox",
                 Array(vec![0i32.into(), 0i32.into(), size.0.into(), size.1.into()]),
@@ -53,35 +118,45 @@ pub trait InsertImage {
         ]);
 
         // Stream
-        let mut content = Content {
-            operations: Vec::<Operation>::new(),
-        };
-        // The following lines use commands: see p643 (Table A.1) for more info
-        // `q` = Save graphics state
-        content.operations.push(Operation::new("q", vec![]));
-        // `cm` = Concatenate matrix to current transformation matrix
-        content.operations.push(Operation::new(
-            "cm",
-            vec![
-                size.0.into(),
-                0i32.into(),
-                0i32.into(),
-                size.1.into(),
-                position.0.into(),
-                position.1.into(),
-            ],
-        ));
-        // `Do` = Invoke named XObject
-        content.operations.push(Operation::new(
-            "Do",
-            vec![Name(image_name.as_bytes().to_vec())],
-        ));
-        // `Q` = Restore graphics state
-        content.operations.push(Operation::new("Q", vec![]));
+        let content = Content { operations };
 
         let content_data = Content::encode(&content)?;
 
         // Return the form xobject
         Ok(self.add_object(Stream::new(form_xobject, content_data)))
     }
+
+    /// Add a SVG image to pdf as a Form XObject, the shapes are drawn as vector content.
+    /// The image will not be visible.
+    /// Return the ObjectId of the Form XObject.
+    fn add_svg_as_form_xobject(
+        &mut self,
+        svg_data: &[u8],
+        rect: Rectangle,
+        options: &ImageOptions,
+    ) -> Result<ObjectId, Error> {
+        let svg = std::str::from_utf8(svg_data)
+            .map_err(|_| Error::from("SVG: Image is not valid UTF-8."))?;
+        let size = (rect.x2 - rect.x1, rect.y2 - rect.y1);
+        let bbox = Rectangle {
+            x1: 0.0,
+            y1: 0.0,
+            x2: size.0,
+            y2: size.1,
+        };
+        let mut content = SvgImage::parse(svg)?.to_content(&bbox, options.placement);
+        let mut resources = dictionary! {};
+        if let Some(graphics_state) = options.get_graphics_state() {
+            resources.set("ExtGState", dictionary! { "GS0" => graphics_state });
+            content.operations = with_graphics_state("GS0", content.operations);
+        }
+
+        let form_xobject = dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "Resources" => resources,
+            "BBox" => vec![0.into(), 0.into(), size.0.into(), size.1.into()],
+        };
+        Ok(self.add_object(lopdf::Stream::new(form_xobject, content.encode()?)))
+    }
 }
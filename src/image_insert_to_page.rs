@@ -1,4 +1,10 @@
-use crate::{image_xobject::ImageXObject, rectangle::Rectangle, Error, InsertImage};
+use crate::{
+    image_options::{with_graphics_state, ImageOptions},
+    image_placement::ImagePlacement,
+    image_xobject::ImageXObject,
+    rectangle::Rectangle,
+    Error, InsertImage,
+};
 use lopdf::{
     content::{Content, Operation},
     ObjectId,
@@ -13,6 +19,13 @@ pub trait InsertImageToPage: InsertImage {
         xobject_id: ObjectId,
     ) -> Result<(), Error>;
 
+    fn add_graphics_state<N: Into<Vec<u8>>>(
+        &mut self,
+        page_id: ObjectId,
+        graphics_state_name: N,
+        graphics_state_id: ObjectId,
+    ) -> Result<(), Error>;
+
     fn opt_clone_object_to_new_document(&mut self, object_id: ObjectId) -> Result<(), Error>;
 
     fn add_to_page_content(
@@ -21,7 +34,17 @@ pub trait InsertImageToPage: InsertImage {
         content: Content<Vec<Operation>>,
     ) -> Result<(), Error>;
 
+    /// Tag the operations of an image that is added to the page, in tagged documents.
+    fn tag_image_operations(
+        &mut self,
+        page_id: ObjectId,
+        operations: Vec<Operation>,
+        image_rect: &Rectangle,
+        alternate_text: Option<&str>,
+    ) -> Result<Vec<Operation>, Error>;
+
     /// Add image to a page.
+    /// `options` define how the image is fitted into `rect` and how it is encoded.
     /// Return the ObjectId of the image.
     fn add_image<R: Read>(
         &mut self,
@@ -29,27 +52,50 @@ pub trait InsertImageToPage: InsertImage {
         image_name: &str,
         page_id: ObjectId,
         rect: Rectangle,
+        options: &ImageOptions,
     ) -> Result<ObjectId, Error> {
         // Load image
-        let image_decoder = png::Decoder::new(image_reader);
-        let (mut image_xobject, mask_xobject) = ImageXObject::try_from(image_decoder)?;
+        let (image_xobject, mask_xobject) = ImageXObject::from_reader(image_reader)?;
+        // The placement uses the original size, so the aspect ratio does not change.
+        let image_size = (image_xobject.width, image_xobject.height);
+        let image_rect =
+            options
+                .placement
+                .get_image_rect(image_size.0 as f64, image_size.1 as f64, &rect);
+        let display_size = (image_rect.width(), image_rect.height());
         // Add object to object list
-        if let Some(mask_xobject) = mask_xobject {
-            let mask_xobject_id = self.add_object(mask_xobject);
-            image_xobject.s_mask = Some(mask_xobject_id);
-        }
-        let image_xobject_id = self.add_object(image_xobject);
+        let image_xobject_id =
+            self.add_image_xobject(image_xobject, mask_xobject, options, display_size)?;
 
         // Add object to xobject list on page (with new IR)
         // Because of the unique name this item will not be inserted more then once.
         self.add_xobject(page_id, image_name, image_xobject_id)?;
+        // The graphics state gets a name based on the unique image name.
+        let graphics_state_name = match options.get_graphics_state() {
+            Some(graphics_state) => {
+                let graphics_state_name = format!("{}GS", image_name);
+                let graphics_state_id = self.add_object(graphics_state);
+                self.add_graphics_state(page_id, graphics_state_name.as_str(), graphics_state_id)?;
+                Some(graphics_state_name)
+            }
+            None => None,
+        };
         // Add xobject to layer (make visible)
-        self.add_image_to_page_stream(image_name, page_id, rect)?;
+        self.add_image_to_page_stream(
+            image_name,
+            page_id,
+            rect,
+            image_size,
+            options.placement,
+            graphics_state_name.as_deref(),
+            options.alternate_text.as_deref(),
+        )?;
 
         Ok(image_xobject_id)
     }
 
     /// Add an already existing image to a page.
+    /// `image_size` is the size of the image in pixels (`Width` and `Height`).
     /// Return the ObjectId of the image.
     fn add_image_to_page_only(
         &mut self,
@@ -57,12 +103,16 @@ pub trait InsertImageToPage: InsertImage {
         image_name: &str,
         page_id: ObjectId,
         rect: Rectangle,
+        image_size: (u32, u32),
+        placement: ImagePlacement,
     ) -> Result<ObjectId, Error> {
         // Add object to xobject list on page (with new IR)
         // Because of the unique name this item will not be inserted more then once.
         self.add_xobject(page_id, image_name, image_xobject_id)?;
         // Add xobject to layer (make visible)
-        self.add_image_to_page_stream(image_name, page_id, rect)?;
+        self.add_image_to_page_stream(
+            image_name, page_id, rect, image_size, placement, None, None,
+        )?;
 
         Ok(image_xobject_id)
     }
@@ -70,40 +120,34 @@ pub trait InsertImageToPage: InsertImage {
     /// Add image to page stream.
     /// The image must already be added to the object list of the page!
     /// Please use `add_image` or `add_image_to_page_only` instead.
+    /// The graphics state `graphics_state_name` (like the opacity) must also be added already.
+    /// `alternate_text` describes the image in tagged documents.
+    #[allow(clippy::too_many_arguments)]
     fn add_image_to_page_stream(
         &mut self,
         xobject_name: &str,
         page_id: ObjectId,
         rect: Rectangle,
+        image_size: (u32, u32),
+        placement: ImagePlacement,
+        graphics_state_name: Option<&str>,
+        alternate_text: Option<&str>,
     ) -> Result<(), Error> {
-        use lopdf::Object::*;
-        let mut content = Content {
-            operations: Vec::<Operation>::new(),
-        };
-        let position = (rect.x1, rect.y1);
-        let size = (rect.x2 - rect.x1, rect.y2 - rect.y1);
-        // The following lines use commands: see p643 (Table A.1) for more info
-        // `q` = Save graphics state
-        content.operations.push(Operation::new("q", vec![]));
-        // `cm` = Concatenate matrix to current transformation matrix
-        content.operations.push(Operation::new(
-            "cm",
-            vec![
-                size.0.into(),
-                0i32.into(),
-                0i32.into(),
-                size.1.into(),
-                position.0.into(),
-                position.1.into(),
-            ],
-        ));
-        // `Do` = Invoke named XObject
-        content.operations.push(Operation::new(
-            "Do",
-            vec![Name(xobject_name.as_bytes().to_vec())],
-        ));
-        // `Q` = Restore graphics state
-        content.operations.push(Operation::new("Q", vec![]));
+        // The transformation matrix (`cm`) places the image inside `rect`,
+        // using the aspect ratio of the image for `Contain` and `Cover`.
+        let mut operations = placement.image_operations(
+            xobject_name,
+            image_size.0 as f64,
+            image_size.1 as f64,
+            &rect,
+        );
+        if let Some(graphics_state_name) = graphics_state_name {
+            operations = with_graphics_state(graphics_state_name, operations);
+        }
+        let image_rect = placement.get_image_rect(image_size.0 as f64, image_size.1 as f64, &rect);
+        let operations =
+            self.tag_image_operations(page_id, operations, &image_rect, alternate_text)?;
+        let content = Content { operations };
 
         self.opt_clone_object_to_new_document(page_id)?;
         self.add_to_page_content(page_id, content)?;
@@ -0,0 +1,63 @@
+use crate::image_compression::ImageCompression;
+use crate::image_placement::ImagePlacement;
+use crate::svg::SvgImage;
+use lopdf::content::Operation;
+use lopdf::{dictionary, Dictionary, Object};
+
+/// Options used when an image is inserted.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ImageOptions {
+    /// How the image is fitted into the rectangle.
+    pub placement: ImagePlacement,
+    /// How raster images are (re-)encoded.
+    pub compression: ImageCompression,
+    /// ICC profile used for images without an embedded profile.
+    /// The color space of the profile should match the image (Gray, RGB or CMYK).
+    pub icc_profile: Option<Vec<u8>>,
+    /// Opacity from 0.0 (invisible) to 1.0 (opaque). `None` draws the image opaque.
+    pub opacity: Option<f64>,
+    /// Alternate description of images added to pages of tagged documents, see
+    /// `set_tag_content`. Images without a description are marked as decorative (`Artifact`).
+    pub alternate_text: Option<String>,
+    /// Read image data that starts with an `<svg>` element as SVG image, its shapes are
+    /// drawn as vector content. Default `false`: only raster images are read.
+    pub allow_svg: bool,
+}
+
+impl ImageOptions {
+    /// Check if `data` is read as SVG image, only when SVG images are allowed.
+    pub(crate) fn is_svg(&self, data: &[u8]) -> bool {
+        self.allow_svg && SvgImage::is_svg(data)
+    }
+
+    /// Get the graphics state (`ExtGState`) that sets the opacity, `None` when it is not set.
+    pub(crate) fn get_graphics_state(&self) -> Option<Dictionary> {
+        self.opacity.map(|opacity| {
+            let opacity = opacity.clamp(0.0, 1.0);
+            dictionary! {
+                "Type" => "ExtGState",
+                "CA" => opacity,
+                "ca" => opacity,
+            }
+        })
+    }
+}
+
+/// Draw `operations` with the graphics state `graphics_state_name`.
+/// `q` and `Q` make sure the graphics state is only used for these operations.
+pub(crate) fn with_graphics_state(
+    graphics_state_name: &str,
+    operations: Vec<Operation>,
+) -> Vec<Operation> {
+    let mut result = vec![
+        Operation::new("q", vec![]),
+        // `gs` = Set parameters from graphics state parameter dictionary
+        Operation::new(
+            "gs",
+            vec![Object::Name(graphics_state_name.as_bytes().to_vec())],
+        ),
+    ];
+    result.extend(operations);
+    result.push(Operation::new("Q", vec![]));
+    result
+}
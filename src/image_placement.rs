@@ -0,0 +1,235 @@
+use crate::rectangle::Rectangle;
+use lopdf::content::Operation;
+use lopdf::Object::Name;
+
+/// How an image is scaled to fit a rectangle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImageScaling {
+    /// Fill the whole rectangle, the image is distorted when the aspect ratios differ.
+    #[default]
+    Stretch,
+    /// Make the image as large as possible while it stays inside the rectangle.
+    /// The aspect ratio is kept and the image is centered.
+    Contain,
+    /// Cover the whole rectangle, parts of the image outside the rectangle are clipped.
+    /// The aspect ratio is kept and the image is centered.
+    Cover,
+}
+
+impl ImageScaling {
+    /// Get the rectangle the image is drawn in. For `Cover` this can be larger than `rect`.
+    pub(crate) fn get_image_rect(
+        &self,
+        image_width: f64,
+        image_height: f64,
+        rect: &Rectangle,
+    ) -> Rectangle {
+        if image_width <= 0.0 || image_height <= 0.0 {
+            return rect.clone();
+        }
+        let scale_x = rect.width() / image_width;
+        let scale_y = rect.height() / image_height;
+        let scale = match self {
+            ImageScaling::Stretch => return rect.clone(),
+            ImageScaling::Contain => scale_x.min(scale_y),
+            ImageScaling::Cover => scale_x.max(scale_y),
+        };
+        let (width, height) = (image_width * scale, image_height * scale);
+        let x1 = rect.x1 + (rect.width() - width) / 2.0;
+        let y1 = rect.y1 + (rect.height() - height) / 2.0;
+        Rectangle {
+            x1,
+            y1,
+            x2: x1 + width,
+            y2: y1 + height,
+        }
+    }
+
+    /// Operations that clip the drawing to `rect`, only needed for `Cover`.
+    pub(crate) fn clip_operations(&self, rect: &Rectangle) -> Vec<Operation> {
+        match self {
+            ImageScaling::Cover => vec![
+                Operation::new(
+                    "re",
+                    vec![
+                        rect.x1.into(),
+                        rect.y1.into(),
+                        rect.width().into(),
+                        rect.height().into(),
+                    ],
+                ),
+                // `W` = Clip, `n` = End path without filling or stroking
+                Operation::new("W", vec![]),
+                Operation::new("n", vec![]),
+            ],
+            _ => vec![],
+        }
+    }
+}
+
+/// Points per inch, the unit of PDF user space.
+pub(crate) const POINTS_PER_INCH: f64 = 72.0;
+const MILLIMETERS_PER_INCH: f64 = 25.4;
+
+/// The size of an image on the page.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ImageSize {
+    /// Fit the image inside the rectangle, using the `ImageScaling`.
+    #[default]
+    Fit,
+    /// Exact physical size, independent of the amount of pixels of the image.
+    Millimeters { width: f64, height: f64 },
+    /// Size derived from the amount of pixels of the image and a resolution (dots per inch).
+    Dpi(f64),
+}
+
+/// How an image is placed inside a rectangle.
+/// An image with a fixed `size` is centered inside the rectangle and can be larger than it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ImagePlacement {
+    pub scaling: ImageScaling,
+    /// Empty space (in points) between the border of the rectangle and the image.
+    pub padding: f64,
+    pub size: ImageSize,
+    /// Rotation (in degrees, counterclockwise) of the image around its center.
+    /// With `ImageScaling::Contain` the rotated image stays inside the rectangle.
+    pub rotation: f64,
+}
+
+impl ImagePlacement {
+    pub fn new(scaling: ImageScaling, padding: f64) -> Self {
+        ImagePlacement {
+            scaling,
+            padding,
+            size: ImageSize::Fit,
+            rotation: 0.0,
+        }
+    }
+
+    /// Get the rectangle the image is drawn in, before it is rotated.
+    pub(crate) fn get_image_rect(
+        &self,
+        image_width: f64,
+        image_height: f64,
+        rect: &Rectangle,
+    ) -> Rectangle {
+        let content_rect = self.get_content_rect(rect);
+        let (width, height) = match self.size {
+            ImageSize::Fit if self.scaling == ImageScaling::Contain && self.rotation != 0.0 => {
+                // Fit the bounding box of the rotated image, then get the image inside it.
+                let (sin, cos) = self.rotation.to_radians().sin_cos();
+                let bounding_width = image_width * cos.abs() + image_height * sin.abs();
+                let bounding_height = image_width * sin.abs() + image_height * cos.abs();
+                let bounding_rect =
+                    self.scaling
+                        .get_image_rect(bounding_width, bounding_height, &content_rect);
+                if bounding_width <= 0.0 {
+                    return bounding_rect;
+                }
+                let scale = bounding_rect.width() / bounding_width;
+                (image_width * scale, image_height * scale)
+            }
+            ImageSize::Fit => {
+                return self
+                    .scaling
+                    .get_image_rect(image_width, image_height, &content_rect)
+            }
+            ImageSize::Millimeters { width, height } => (
+                width / MILLIMETERS_PER_INCH * POINTS_PER_INCH,
+                height / MILLIMETERS_PER_INCH * POINTS_PER_INCH,
+            ),
+            ImageSize::Dpi(dpi) if dpi > 0.0 => (
+                image_width / dpi * POINTS_PER_INCH,
+                image_height / dpi * POINTS_PER_INCH,
+            ),
+            ImageSize::Dpi(_) => return content_rect,
+        };
+        let x1 = content_rect.x1 + (content_rect.width() - width) / 2.0;
+        let y1 = content_rect.y1 + (content_rect.height() - height) / 2.0;
+        Rectangle {
+            x1,
+            y1,
+            x2: x1 + width,
+            y2: y1 + height,
+        }
+    }
+
+    /// Get `rect` without the padding. The padding is limited so the size never gets negative.
+    pub(crate) fn get_content_rect(&self, rect: &Rectangle) -> Rectangle {
+        let padding_x = self.padding.max(0.0).min(rect.width() / 2.0);
+        let padding_y = self.padding.max(0.0).min(rect.height() / 2.0);
+        Rectangle {
+            x1: rect.x1 + padding_x,
+            y1: rect.y1 + padding_y,
+            x2: rect.x2 - padding_x,
+            y2: rect.y2 - padding_y,
+        }
+    }
+
+    /// Operations that clip the drawing to the content of `rect`, only needed for `Cover`.
+    pub(crate) fn clip_operations(&self, rect: &Rectangle) -> Vec<Operation> {
+        match self.size {
+            ImageSize::Fit => self.scaling.clip_operations(&self.get_content_rect(rect)),
+            _ => vec![],
+        }
+    }
+
+    /// Operations that rotate the drawing around the center of `image_rect`.
+    pub(crate) fn rotation_operations(&self, image_rect: &Rectangle) -> Vec<Operation> {
+        if self.rotation == 0.0 {
+            return vec![];
+        }
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let center_x = (image_rect.x1 + image_rect.x2) / 2.0;
+        let center_y = (image_rect.y1 + image_rect.y2) / 2.0;
+        // `cm` = Concatenate matrix to current transformation matrix
+        vec![Operation::new(
+            "cm",
+            vec![
+                cos.into(),
+                sin.into(),
+                (-sin).into(),
+                cos.into(),
+                (center_x - cos * center_x + sin * center_y).into(),
+                (center_y - sin * center_x - cos * center_y).into(),
+            ],
+        )]
+    }
+
+    /// Operations that draw the image XObject `xobject_name` inside `rect`.
+    pub(crate) fn image_operations(
+        &self,
+        xobject_name: &str,
+        image_width: f64,
+        image_height: f64,
+        rect: &Rectangle,
+    ) -> Vec<Operation> {
+        let image_rect = self.get_image_rect(image_width, image_height, rect);
+        // The following lines use commands: see p643 (Table A.1) for more info
+        // `q` = Save graphics state
+        let mut operations = vec![Operation::new("q", vec![])];
+        operations.extend(self.clip_operations(rect));
+        operations.extend(self.rotation_operations(&image_rect));
+        // `cm` = Concatenate matrix to current transformation matrix
+        // Images are 1 by 1 unit, so scale them to the size of `image_rect`.
+        operations.push(Operation::new(
+            "cm",
+            vec![
+                image_rect.width().into(),
+                0i32.into(),
+                0i32.into(),
+                image_rect.height().into(),
+                image_rect.x1.into(),
+                image_rect.y1.into(),
+            ],
+        ));
+        // `Do` = Invoke named XObject
+        operations.push(Operation::new(
+            "Do",
+            vec![Name(xobject_name.as_bytes().to_vec())],
+        ));
+        // `Q` = Restore graphics state
+        operations.push(Operation::new("Q", vec![]));
+        operations
+    }
+}
@@ -0,0 +1,222 @@
+//! Replace images that are already in the document, like placeholder logos in templates.
+
+use crate::image_options::ImageOptions;
+use crate::image_xobject::ImageXObject;
+use crate::rectangle::Rectangle;
+use crate::{Error, InsertImage, PDFSigningDocument};
+use lopdf::content::{Content, Operation};
+use lopdf::{Object, ObjectId, Stream};
+
+/// How the image XObject on a page is found.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageSelector {
+    /// The name of the XObject in the resources of the page, like `Im1`.
+    Name(String),
+    /// The size of the image in pixels (`Width` and `Height`).
+    Size { width: u32, height: u32 },
+    /// A point (in the coordinates of the page) inside the area where the image is drawn.
+    Position { x: f64, y: f64 },
+}
+
+/// An image XObject drawn on a page.
+struct PageImage {
+    name: Vec<u8>,
+    object_id: ObjectId,
+    size: (u32, u32),
+    /// Area where the image is drawn, `None` when it is not drawn by the page content.
+    rect: Option<Rectangle>,
+}
+
+impl PDFSigningDocument {
+    /// Replace the image selected by `selector` on the page with index `page_index`.
+    /// The new image (PNG, JPEG, TIFF or WebP) is drawn at the same position and size,
+    /// so it should have the same aspect ratio. Other pages using the image also change.
+    /// Only `options.compression` and `options.icc_profile` are used.
+    /// Return the ObjectId of the replaced image.
+    pub fn replace_image(
+        &mut self,
+        page_index: usize,
+        selector: &ImageSelector,
+        image_bytes: &[u8],
+        options: &ImageOptions,
+    ) -> Result<ObjectId, Error> {
+        let page_id = self.get_page_id(page_index)?;
+        let page_image = self
+            .get_page_images(page_id)?
+            .into_iter()
+            .find(|page_image| page_image.matches(selector))
+            .ok_or_else(|| Error::from("Image to replace not found."))?;
+
+        let (image_xobject, mask_xobject) = ImageXObject::from_reader(image_bytes)?;
+        let display_size = match &page_image.rect {
+            Some(rect) => (rect.width(), rect.height()),
+            None => (page_image.size.0 as f64, page_image.size.1 as f64),
+        };
+        let image_xobject =
+            self.prepare_image_xobject(image_xobject, mask_xobject, options, display_size)?;
+        self.raw_document
+            .new_document
+            .set_object(page_image.object_id, Stream::from(image_xobject));
+        tracing::info!(
+            "Replaced image `{}` objId: `({},{})`.",
+            String::from_utf8_lossy(&page_image.name),
+            page_image.object_id.0,
+            page_image.object_id.1,
+        );
+        Ok(page_image.object_id)
+    }
+
+    /// Get the image XObjects in the resources of the page and where they are drawn.
+    fn get_page_images(&self, page_id: ObjectId) -> Result<Vec<PageImage>, Error> {
+        let xobjects = self
+            .get_inherited_page_attribute(page_id, b"Resources")
+            .and_then(|resources| resources.as_dict().ok())
+            .and_then(|resources| resources.get(b"XObject").ok())
+            .and_then(|xobjects| self.resolve_latest_object(xobjects))
+            .and_then(|xobjects| xobjects.as_dict().ok());
+        let xobjects = match xobjects {
+            Some(xobjects) => xobjects,
+            None => return Ok(vec![]),
+        };
+
+        let mut page_images = vec![];
+        for (name, xobject) in xobjects.iter() {
+            let object_id = match xobject {
+                Object::Reference(object_id) => *object_id,
+                _ => continue,
+            };
+            let image = match self.get_latest_object(object_id) {
+                Some(Object::Stream(stream)) => &stream.dict,
+                _ => continue,
+            };
+            if image.get(b"Subtype").and_then(Object::as_name_str).ok() != Some("Image") {
+                continue;
+            }
+            let size = |key: &[u8]| {
+                image
+                    .get(key)
+                    .and_then(Object::as_i64)
+                    .map(|value| value as u32)
+                    .unwrap_or(0)
+            };
+            page_images.push(PageImage {
+                name: name.clone(),
+                object_id,
+                size: (size(b"Width"), size(b"Height")),
+                rect: None,
+            });
+        }
+
+        // Follow the transformation matrix of the page content to find where images are drawn.
+        let mut matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+        let mut saved_matrices = vec![];
+        for operation in self.get_page_operations(page_id)? {
+            match operation.operator.as_str() {
+                "q" => saved_matrices.push(matrix),
+                "Q" => matrix = saved_matrices.pop().unwrap_or(matrix),
+                "cm" => {
+                    let values: Vec<f64> =
+                        operation.operands.iter().filter_map(as_number).collect();
+                    if let [a, b, c, d, e, f] = values[..] {
+                        matrix = multiply(&[a, b, c, d, e, f], &matrix);
+                    }
+                }
+                "Do" => {
+                    let name = operation
+                        .operands
+                        .first()
+                        .and_then(|name| name.as_name().ok());
+                    if let Some(page_image) = page_images
+                        .iter_mut()
+                        .find(|page_image| Some(page_image.name.as_slice()) == name)
+                    {
+                        page_image
+                            .rect
+                            .get_or_insert_with(|| transform_unit_square(&matrix));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(page_images)
+    }
+
+    /// Get the operations of all content streams of the page.
+    pub(crate) fn get_page_operations(&self, page_id: ObjectId) -> Result<Vec<Operation>, Error> {
+        let contents = self
+            .get_latest_object(page_id)
+            .and_then(|page| page.as_dict().ok())
+            .and_then(|page| page.get(b"Contents").ok());
+        // `Contents` can be a stream, an array of streams or a reference to an array.
+        let content_refs = match contents {
+            Some(Object::Reference(contents_id)) => match self.get_latest_object(*contents_id) {
+                Some(Object::Array(content_refs)) => content_refs.clone(),
+                _ => vec![Object::Reference(*contents_id)],
+            },
+            Some(Object::Array(content_refs)) => content_refs.clone(),
+            _ => vec![],
+        };
+
+        let mut content_data = vec![];
+        for content_ref in content_refs {
+            if let Some(Object::Stream(stream)) = self.resolve_latest_object(&content_ref) {
+                let data = stream
+                    .decompressed_content()
+                    .unwrap_or_else(|_| stream.content.clone());
+                content_data.extend(data);
+                // Operators can not continue in the next stream.
+                content_data.push(b'\n');
+            }
+        }
+        Ok(Content::decode(&content_data)?.operations)
+    }
+}
+
+impl PageImage {
+    fn matches(&self, selector: &ImageSelector) -> bool {
+        match selector {
+            ImageSelector::Name(name) => self.name == name.as_bytes(),
+            ImageSelector::Size { width, height } => self.size == (*width, *height),
+            ImageSelector::Position { x, y } => self.rect.as_ref().is_some_and(|rect| {
+                (rect.x1..=rect.x2).contains(x) && (rect.y1..=rect.y2).contains(y)
+            }),
+        }
+    }
+}
+
+pub(crate) fn as_number(object: &Object) -> Option<f64> {
+    object
+        .as_f64()
+        .or_else(|_| object.as_i64().map(|value| value as f64))
+        .ok()
+}
+
+/// Multiply 2 transformation matrices: `first` is applied before `second`.
+pub(crate) fn multiply(first: &[f64; 6], second: &[f64; 6]) -> [f64; 6] {
+    [
+        first[0] * second[0] + first[1] * second[2],
+        first[0] * second[1] + first[1] * second[3],
+        first[2] * second[0] + first[3] * second[2],
+        first[2] * second[1] + first[3] * second[3],
+        first[4] * second[0] + first[5] * second[2] + second[4],
+        first[4] * second[1] + first[5] * second[3] + second[5],
+    ]
+}
+
+/// Get the bounding box of the unit square (the space of an image) after `matrix`.
+pub(crate) fn transform_unit_square(matrix: &[f64; 6]) -> Rectangle {
+    let points = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)].map(|(x, y)| {
+        (
+            matrix[0] * x + matrix[2] * y + matrix[4],
+            matrix[1] * x + matrix[3] * y + matrix[5],
+        )
+    });
+    let xs = points.map(|point| point.0);
+    let ys = points.map(|point| point.1);
+    Rectangle {
+        x1: xs.iter().cloned().fold(f64::INFINITY, f64::min),
+        y1: ys.iter().cloned().fold(f64::INFINITY, f64::min),
+        x2: xs.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+        y2: ys.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+    }
+}
@@ -1,10 +1,44 @@
 // This code is inspired by https://github.com/fschutt/printpdf/blob/2bebdc65d06dafbe926ed4b43fedd10f966c59d3/src/xobject.rs
 
 use crate::Error;
-use lopdf::ObjectId;
-use png::{BitDepth, ColorType};
+use lopdf::{dictionary, ObjectId};
 use std::io::Read;
 
+/// TIFF tag containing an ICC profile.
+#[cfg(feature = "images")]
+const TIFF_ICC_PROFILE_TAG: u16 = 34675;
+
+/// Color space of the image data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    DeviceGray,
+    DeviceRGB,
+    DeviceCMYK,
+}
+
+impl ColorSpace {
+    pub fn as_name(&self) -> &'static str {
+        match self {
+            ColorSpace::DeviceGray => "DeviceGray",
+            ColorSpace::DeviceRGB => "DeviceRGB",
+            ColorSpace::DeviceCMYK => "DeviceCMYK",
+        }
+    }
+}
+
+/// Filter used to encode the image data in the PDF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFilter {
+    /// The image data is stored as is (compressed later on save).
+    None,
+    /// The image data is a JPEG file (`DCTDecode`).
+    Dct,
+    /// The image data is bilevel and encoded using CCITT Group 4 (`CCITTFaxDecode`).
+    CcittFax,
+    /// The image data is compressed already (`FlateDecode`).
+    Flate,
+}
+
 #[derive(Debug, Clone)]
 pub struct ImageXObject {
     /// Width of the image (original width, not scaled width)
@@ -12,31 +46,430 @@ pub struct ImageXObject {
     /// Height of the image (original height, not scaled height)
     pub height: u32,
     /// Color space (Greyscale, RGB, CMYK)
-    pub color_space: ColorType,
+    pub color_space: ColorSpace,
     /// Bits per color component (1, 2, 4, 8, 16) - 1 for black/white, 8 Greyscale / RGB, etc.
     /// If using a JPXDecode filter (for JPEG images), this can be inferred from the image data
-    pub bits_per_component: BitDepth,
+    pub bits_per_component: u8,
     /// Should the image be interpolated when scaled?
     pub interpolate: bool,
     /// The actual data from the image
     pub image_data: Vec<u8>,
+    /// Filter needed to decode `image_data`.
+    pub filter: ImageFilter,
+    /// Invert the color components, used by Adobe CMYK JPEG files.
+    pub invert_colors: bool,
     /// Image used as a soft mask. (transparency)
     pub s_mask: Option<ObjectId>,
+    /// ICC profile embedded in the image file.
+    pub icc_profile: Option<Vec<u8>>,
+    /// Stream of the ICC profile (`ICCBased` color space).
+    pub icc_profile_id: Option<ObjectId>,
 }
 
 impl ImageXObject {
-    // TODO: remove `unwrap`s
+    /// Load a PNG, JPEG or TIFF image, the format is detected from the data.
+    /// Returns 1 or 2 images. The first is the color images.
+    /// The second is (if present) the mask/alpha channel of the image.
+    pub fn from_reader<R: Read>(mut image_reader: R) -> Result<(Self, Option<Self>), Error> {
+        let mut image_data = vec![];
+        image_reader.read_to_end(&mut image_data)?;
+        let (image, mask) = if image_data.starts_with(&[0xFF, 0xD8]) {
+            (Self::from_jpeg(image_data)?, None)
+        } else if image_data.starts_with(b"\x89PNG") {
+            Self::from_png(&image_data)?
+        } else if image_data.starts_with(b"II*\0") || image_data.starts_with(b"MM\0*") {
+            Self::from_tiff(&image_data)?
+        } else if image_data.starts_with(b"RIFF") && image_data.get(8..12) == Some(b"WEBP") {
+            Self::from_webp(&image_data)?
+        } else {
+            return Err(Error::from(
+                "Image format not supported. Use PNG, JPEG or TIFF.",
+            ));
+        };
+        Ok((image.into_bilevel(), mask.map(Self::into_bilevel)))
+    }
+
+    /// Encode black and white images using CCITT Group 4, this is a lot smaller than
+    /// compressing the (expanded) pixels. Other images are returned unchanged.
+    /// Without the `images` feature every image is returned unchanged.
+    #[cfg(feature = "images")]
+    pub fn into_bilevel(self) -> Self {
+        let width = match u16::try_from(self.width) {
+            Ok(width) if self.filter == ImageFilter::None && !self.invert_colors => width,
+            _ => return self,
+        };
+        let white_pixels = match self.bilevel_pixels() {
+            Some(white_pixels) => white_pixels,
+            None => return self,
+        };
+
+        let mut encoder = fax::encoder::Encoder::new(fax::VecWriter::new());
+        for row in white_pixels.chunks(width as usize) {
+            let pixels = row.iter().map(|white| match white {
+                true => fax::Color::White,
+                false => fax::Color::Black,
+            });
+            // `VecWriter` can not fail.
+            let _ = encoder.encode_line(pixels, width);
+        }
+        let image_data = match encoder.finish() {
+            Ok(writer) => writer.finish(),
+            Err(_) => return self,
+        };
+
+        Self {
+            color_space: ColorSpace::DeviceGray,
+            bits_per_component: 1,
+            image_data,
+            filter: ImageFilter::CcittFax,
+            // A color profile does not apply to black and white.
+            icc_profile: None,
+            ..self
+        }
+    }
+
+    #[cfg(not(feature = "images"))]
+    pub fn into_bilevel(self) -> Self {
+        self
+    }
+
+    /// Get the pixels (`true` is white) if every pixel is pure black or white.
+    #[cfg(feature = "images")]
+    fn bilevel_pixels(&self) -> Option<Vec<bool>> {
+        let pixel_count = self.width as usize * self.height as usize;
+        if pixel_count == 0 {
+            return None;
+        }
+        let pixels: Vec<bool> = match (self.color_space, self.bits_per_component) {
+            (ColorSpace::DeviceGray, 1) => {
+                // Every row starts at a new byte.
+                let row_size = (self.width as usize).div_ceil(8);
+                self.image_data
+                    .chunks(row_size)
+                    .flat_map(|row| {
+                        (0..self.width as usize).map(move |x| row[x / 8] & (0x80 >> (x % 8)) != 0)
+                    })
+                    .collect()
+            }
+            (ColorSpace::DeviceGray, 8) => self
+                .image_data
+                .iter()
+                .map(|value| match value {
+                    0 => Some(false),
+                    255 => Some(true),
+                    _ => None,
+                })
+                .collect::<Option<_>>()?,
+            (ColorSpace::DeviceRGB, 8) => self
+                .image_data
+                .chunks(3)
+                .map(|rgb| match rgb {
+                    [0, 0, 0] => Some(false),
+                    [255, 255, 255] => Some(true),
+                    _ => None,
+                })
+                .collect::<Option<_>>()?,
+            _ => return None,
+        };
+        if pixels.len() != pixel_count {
+            return None;
+        }
+        Some(pixels)
+    }
+
+    /// Decode a WebP image (lossy or lossless).
+    /// Only the first frame of animated images is used.
+    #[cfg(feature = "webp")]
+    pub fn from_webp(webp_data: &[u8]) -> Result<(Self, Option<Self>), Error> {
+        let webp_error = |err: image_webp::DecodingError| Error::Other(format!("WebP: {}", err));
+        let mut decoder =
+            image_webp::WebPDecoder::new(std::io::Cursor::new(webp_data)).map_err(webp_error)?;
+        let (width, height) = decoder.dimensions();
+        let has_alpha = decoder.has_alpha();
+        let icc_profile = decoder.icc_profile().map_err(webp_error)?;
+        let buffer_size = decoder
+            .output_buffer_size()
+            .ok_or_else(|| Error::from("WebP: Image is too large."))?;
+        let mut image_data = vec![0; buffer_size];
+        decoder.read_image(&mut image_data).map_err(webp_error)?;
+
+        let (image_data, alpha_data) = if has_alpha {
+            (
+                Self::rgba_to_rgb(&image_data),
+                Some(Self::rgba_to_a(&image_data)),
+            )
+        } else {
+            (image_data, None)
+        };
+
+        Ok((
+            Self {
+                width,
+                height,
+                color_space: ColorSpace::DeviceRGB,
+                bits_per_component: 8,
+                interpolate: false,
+                image_data,
+                filter: ImageFilter::None,
+                invert_colors: false,
+                s_mask: None,
+                icc_profile,
+                icc_profile_id: None,
+            },
+            alpha_data.map(|alpha_data| Self {
+                width,
+                height,
+                color_space: ColorSpace::DeviceGray,
+                bits_per_component: 8,
+                interpolate: false,
+                image_data: alpha_data,
+                filter: ImageFilter::None,
+                invert_colors: false,
+                s_mask: None,
+                icc_profile: None,
+                icc_profile_id: None,
+            }),
+        ))
+    }
+
+    #[cfg(not(feature = "webp"))]
+    pub fn from_webp(_webp_data: &[u8]) -> Result<(Self, Option<Self>), Error> {
+        Err(Error::from(
+            "WebP images are not supported, enable the `webp` feature.",
+        ))
+    }
+
+    /// Decode a TIFF image (any compression supported by the `tiff` crate, like LZW).
+    /// Only the first image (page) of the file is used.
+    #[cfg(feature = "images")]
+    pub fn from_tiff(tiff_data: &[u8]) -> Result<(Self, Option<Self>), Error> {
+        use tiff::decoder::{Decoder, DecodingResult};
+        use tiff::ColorType as TiffColorType;
+
+        let tiff_error = |err: tiff::TiffError| Error::Other(format!("TIFF: {}", err));
+        let mut decoder = Decoder::new(std::io::Cursor::new(tiff_data)).map_err(tiff_error)?;
+        let (width, height) = decoder.dimensions().map_err(tiff_error)?;
+        let color_type = decoder.colortype().map_err(tiff_error)?;
+        let icc_profile = decoder
+            .get_tag_u8_vec(tiff::tags::Tag::Unknown(TIFF_ICC_PROFILE_TAG))
+            .ok();
+        // The decoder inverts the samples of `WhiteIsZero` images (since `tiff` 0.8.1), so
+        // they are `DeviceGray` like `BlackIsZero` images, without a `Decode` array.
+        let (image_data, bits_per_component) = match decoder.read_image().map_err(tiff_error)? {
+            DecodingResult::U8(data) => (data, 8),
+            // Only keep the most significant byte.
+            DecodingResult::U16(data) => (data.iter().map(|value| (value >> 8) as u8).collect(), 8),
+            _ => return Err(Error::from("TIFF: Sample format is not supported.")),
+        };
+
+        let (color_space, image_data, alpha_data, bits_per_component) = match color_type {
+            TiffColorType::Gray(bits) if bits < 8 => {
+                (ColorSpace::DeviceGray, image_data, None, bits)
+            }
+            TiffColorType::Gray(_) => {
+                (ColorSpace::DeviceGray, image_data, None, bits_per_component)
+            }
+            TiffColorType::RGB(_) => (ColorSpace::DeviceRGB, image_data, None, bits_per_component),
+            TiffColorType::CMYK(_) => {
+                (ColorSpace::DeviceCMYK, image_data, None, bits_per_component)
+            }
+            TiffColorType::GrayA(_) => (
+                ColorSpace::DeviceGray,
+                Self::grayscale_alpha_to_grayscale(&image_data),
+                Some(Self::grayscale_alpha_to_a(&image_data)),
+                bits_per_component,
+            ),
+            TiffColorType::RGBA(_) => (
+                ColorSpace::DeviceRGB,
+                Self::rgba_to_rgb(&image_data),
+                Some(Self::rgba_to_a(&image_data)),
+                bits_per_component,
+            ),
+            TiffColorType::Palette(_) => {
+                return Err(Error::from("TIFF: Palette images are not supported."))
+            }
+        };
+
+        Ok((
+            Self {
+                width,
+                height,
+                color_space,
+                bits_per_component,
+                interpolate: false,
+                image_data,
+                filter: ImageFilter::None,
+                invert_colors: false,
+                s_mask: None,
+                icc_profile,
+                icc_profile_id: None,
+            },
+            alpha_data.map(|alpha_data| Self {
+                width,
+                height,
+                color_space: ColorSpace::DeviceGray,
+                bits_per_component,
+                interpolate: false,
+                image_data: alpha_data,
+                filter: ImageFilter::None,
+                invert_colors: false,
+                s_mask: None,
+                icc_profile: None,
+                icc_profile_id: None,
+            }),
+        ))
+    }
+
+    #[cfg(not(feature = "images"))]
+    pub fn from_tiff(_tiff_data: &[u8]) -> Result<(Self, Option<Self>), Error> {
+        Err(Error::from(
+            "TIFF images are not supported, enable the `images` feature.",
+        ))
+    }
+
+    /// Embed the JPEG data directly (`DCTDecode`), without decoding the image.
+    pub fn from_jpeg(jpeg_data: Vec<u8>) -> Result<Self, Error> {
+        let mut width = None;
+        let mut components = 0;
+        let mut is_adobe = false;
+        // ICC profile chunks with their sequence number.
+        let mut icc_profile_chunks = vec![];
+
+        // Walk over the markers until the start of frame (`SOFn`) is found.
+        let mut index = 2;
+        while index + 4 <= jpeg_data.len() {
+            if jpeg_data[index] != 0xFF {
+                return Err(Error::from("JPEG: Invalid marker."));
+            }
+            let marker = jpeg_data[index + 1];
+            // Fill bytes
+            if marker == 0xFF {
+                index += 1;
+                continue;
+            }
+            let length = u16::from_be_bytes([jpeg_data[index + 2], jpeg_data[index + 3]]) as usize;
+            let segment = jpeg_data
+                .get(index + 4..index + 2 + length)
+                .ok_or_else(|| Error::from("JPEG: Segment is out of bounds."))?;
+            match marker {
+                // `APP14` Adobe segment, CMYK data is stored inverted.
+                0xEE if segment.starts_with(b"Adobe") => is_adobe = true,
+                // `APP2` ICC profile, large profiles are split over multiple segments.
+                0xE2 if segment.starts_with(b"ICC_PROFILE\0") && segment.len() > 14 => {
+                    icc_profile_chunks.push((segment[12], &segment[14..]));
+                }
+                // `SOF0` - `SOF15`, except `DHT`, `JPG` and `DAC`
+                0xC0..=0xCF if ![0xC4, 0xC8, 0xCC].contains(&marker) => {
+                    if segment.len() < 6 {
+                        return Err(Error::from("JPEG: Start of frame is too short."));
+                    }
+                    // `DCTDecode` only reads 8 bit images with Huffman coding: not the
+                    // lossless (`SOF3`, `SOF7`) or arithmetic coding (`SOF9` - `SOF15`).
+                    if matches!(marker, 0xC3 | 0xC7) || marker >= 0xC9 {
+                        return Err(Error::Other(format!(
+                            "JPEG: Unsupported coding process: SOF{}",
+                            marker - 0xC0
+                        )));
+                    }
+                    if segment[0] != 8 {
+                        return Err(Error::Other(format!(
+                            "JPEG: Unsupported precision: {} bits",
+                            segment[0]
+                        )));
+                    }
+                    let height = u16::from_be_bytes([segment[1], segment[2]]) as u32;
+                    width = Some((u16::from_be_bytes([segment[3], segment[4]]) as u32, height));
+                    components = segment[5];
+                    break;
+                }
+                // Start of scan, frame should be found before this.
+                0xDA => break,
+                _ => {}
+            }
+            index += 2 + length;
+        }
+
+        let (width, height) =
+            width.ok_or_else(|| Error::from("JPEG: Start of frame not found."))?;
+        icc_profile_chunks.sort_by_key(|(sequence_number, _)| *sequence_number);
+        let icc_profile = match icc_profile_chunks.is_empty() {
+            true => None,
+            false => Some(
+                icc_profile_chunks
+                    .iter()
+                    .flat_map(|(_, chunk)| chunk.iter().copied())
+                    .collect(),
+            ),
+        };
+        // The amount of components defines the color space, `DCTDecode` converts YCbCr and
+        // YCCK data itself. CMYK images (like scans) are never treated as RGB.
+        let color_space = match components {
+            1 => ColorSpace::DeviceGray,
+            3 => ColorSpace::DeviceRGB,
+            4 => ColorSpace::DeviceCMYK,
+            _ => {
+                return Err(Error::Other(format!(
+                    "JPEG: Unsupported amount of color components: {}",
+                    components
+                )))
+            }
+        };
+
+        Ok(Self {
+            width,
+            height,
+            color_space,
+            bits_per_component: 8,
+            interpolate: false,
+            image_data: jpeg_data,
+            filter: ImageFilter::Dct,
+            invert_colors: is_adobe && color_space == ColorSpace::DeviceCMYK,
+            s_mask: None,
+            icc_profile,
+            icc_profile_id: None,
+        })
+    }
+
+    /// Decode a PNG image, see `try_from`.
+    #[cfg(feature = "images")]
+    pub fn from_png(png_data: &[u8]) -> Result<(Self, Option<Self>), Error> {
+        Self::try_from(png::Decoder::new(png_data))
+    }
+
+    #[cfg(not(feature = "images"))]
+    pub fn from_png(_png_data: &[u8]) -> Result<(Self, Option<Self>), Error> {
+        Err(Error::from(
+            "PNG images are not supported, enable the `images` feature.",
+        ))
+    }
+
     /// Returns 1 or 2 images. The first is the color images.
     /// The second is (if present) the mask/alpha channel of the image.
+    #[cfg(feature = "images")]
     pub fn try_from<R: Read>(
-        image_decoder: png::Decoder<R>,
+        mut image_decoder: png::Decoder<R>,
     ) -> Result<(Self, Option<Self>), Error> {
+        use png::ColorType;
+
+        // Expand palette images, transparency (`tRNS`) and low bit depths to 8 bits per
+        // channel. This way the alpha channel can always be split of as a soft mask.
+        image_decoder
+            .set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
         // Load image
-        let mut image_reader = image_decoder.read_info().unwrap();
+        let mut image_reader = image_decoder
+            .read_info()
+            .map_err(|err| Error::Other(format!("PNG: {}", err)))?;
+        let icc_profile = image_reader
+            .info()
+            .icc_profile
+            .as_ref()
+            .map(|icc_profile| icc_profile.to_vec());
         // Allocate the output buffer.
         let mut buf = vec![0; image_reader.output_buffer_size()];
         // Read the next frame. An APNG might contain multiple frames.
-        let info = image_reader.next_frame(&mut buf).unwrap();
+        let info = image_reader
+            .next_frame(&mut buf)
+            .map_err(|err| Error::Other(format!("PNG: {}", err)))?;
         // Grab the bytes of the image.
         let image_data = Vec::from(&buf[..info.buffer_size()]);
 
@@ -50,39 +483,89 @@ impl ImageXObject {
                 )
             }
             ColorType::GrayscaleAlpha => {
-                // TODO split alpha channel
                 color_type = ColorType::Grayscale;
                 (
                     Self::grayscale_alpha_to_grayscale(&image_data),
-                    Some(Self::grayscale_alpha_to_grayscale(&image_data)),
+                    Some(Self::grayscale_alpha_to_a(&image_data)),
                 )
             }
             _ => (image_data, None),
         };
 
+        let color_space = match color_type {
+            ColorType::Grayscale => ColorSpace::DeviceGray,
+            ColorType::Rgb => ColorSpace::DeviceRGB,
+            _ => {
+                return Err(Error::Other(format!(
+                    "PNG: Color type `{:?}` is not supported.",
+                    color_type
+                )))
+            }
+        };
+
         Ok((
             Self {
                 width: info.width,
                 height: info.height,
-                color_space: color_type,
-                bits_per_component: info.bit_depth,
+                color_space,
+                bits_per_component: info.bit_depth as u8,
                 image_data: image_color_data,
                 interpolate: false,
+                filter: ImageFilter::None,
+                invert_colors: false,
                 s_mask: None, // This should be filled in later
+                icc_profile,
+                icc_profile_id: None,
             },
             alpha_data.map(|alpha_data| Self {
                 width: info.width,
                 height: info.height,
-                color_space: ColorType::Grayscale,
-                bits_per_component: info.bit_depth,
+                color_space: ColorSpace::DeviceGray,
+                bits_per_component: info.bit_depth as u8,
                 image_data: alpha_data,
                 interpolate: false,
+                filter: ImageFilter::None,
+                invert_colors: false,
                 s_mask: None,
+                icc_profile: None,
+                icc_profile_id: None,
             }),
         ))
     }
 
+    /// Take the ICC profile and create the stream for the `ICCBased` color space.
+    /// Returns `None` when there is no profile or the profile does not match the color space.
+    pub(crate) fn take_icc_profile_stream(&mut self) -> Option<lopdf::Stream> {
+        let icc_profile = self.icc_profile.take()?;
+        // The color space of the profile is stored in the header at byte 16.
+        let (profile_color_space, components) = match icc_profile.get(16..20)? {
+            b"GRAY" => (ColorSpace::DeviceGray, 1),
+            b"RGB " => (ColorSpace::DeviceRGB, 3),
+            b"CMYK" => (ColorSpace::DeviceCMYK, 4),
+            _ => {
+                tracing::warn!("ICC profile ignored, color space is not supported.");
+                return None;
+            }
+        };
+        if profile_color_space != self.color_space {
+            tracing::warn!(
+                "ICC profile ignored, it is for `{}` but the image is `{}`.",
+                profile_color_space.as_name(),
+                self.color_space.as_name()
+            );
+            return None;
+        }
+        Some(lopdf::Stream::new(
+            dictionary! {
+                "N" => components,
+                "Alternate" => self.color_space.as_name(),
+            },
+            icc_profile,
+        ))
+    }
+
     // NOTE: This function only works for a bit depth of 8.
+    #[cfg(feature = "images")]
     fn rgba_to_rgb(data: &[u8]) -> Vec<u8> {
         let mut temp_counter = 0;
         let mut temp = [0u8; 3];
@@ -109,6 +592,7 @@ impl ImageXObject {
     }
 
     // NOTE: This function only works for a bit depth of 8.
+    #[cfg(feature = "images")]
     fn grayscale_alpha_to_grayscale(data: &[u8]) -> Vec<u8> {
         let mut temp_counter = 0;
         let mut temp = 0u8;
@@ -134,6 +618,14 @@ impl ImageXObject {
     }
 
     // NOTE: This function only works for a bit depth of 8.
+    #[cfg(feature = "images")]
+    fn grayscale_alpha_to_a(data: &[u8]) -> Vec<u8> {
+        // Every pixel consumes 2 bytes (g, a), keep the alpha.
+        data.iter().skip(1).step_by(2).copied().collect()
+    }
+
+    // NOTE: This function only works for a bit depth of 8.
+    #[cfg(feature = "images")]
     fn rgba_to_a(data: &[u8]) -> Vec<u8> {
         let mut temp_counter = 0;
         let mut output = Vec::with_capacity(data.len() / 4);
@@ -162,12 +654,7 @@ impl From<ImageXObject> for lopdf::Stream {
     fn from(image: ImageXObject) -> Self {
         use lopdf::Object::*;
 
-        let cs: &'static str = match image.color_space {
-            ColorType::Rgb => "DeviceRGB",
-            ColorType::Grayscale => "DeviceGray",
-            ColorType::Indexed => "Indexed",
-            ColorType::Rgba | ColorType::GrayscaleAlpha => "DeviceN",
-        };
+        let cs = image.color_space.as_name();
 
         // cf: https://stackoverflow.com/questions/72935931/pdf-signature-expected-a-dict-object
         // let identity_matrix: Vec<f64> = vec![1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
@@ -180,15 +667,55 @@ impl From<ImageXObject> for lopdf::Stream {
             ("Height", Integer(image.height as i64)),
             ("Interpolate", image.interpolate.into()),
             ("BitsPerComponent", Integer(image.bits_per_component as i64)),
-            ("ColorSpace", Name(cs.as_bytes().to_vec())),
+            (
+                "ColorSpace",
+                match image.icc_profile_id {
+                    Some(icc_profile_id) => Array(vec![
+                        Name("ICCBased".as_bytes().to_vec()),
+                        Reference(icc_profile_id),
+                    ]),
+                    None => Name(cs.as_bytes().to_vec()),
+                },
+            ),
             // ("BBox", bbox),
-            ("Resources", Dictionary(lopdf::Dictionary::new()))
+            ("Resources", Dictionary(lopdf::Dictionary::new())),
         ]);
         if let Some(s_mask) = image.s_mask {
             dict.set("SMask", Reference(s_mask));
         }
+        match image.filter {
+            ImageFilter::None => {}
+            ImageFilter::Dct => dict.set("Filter", Name("DCTDecode".as_bytes().to_vec())),
+            ImageFilter::Flate => dict.set("Filter", Name("FlateDecode".as_bytes().to_vec())),
+            ImageFilter::CcittFax => {
+                dict.set("Filter", Name("CCITTFaxDecode".as_bytes().to_vec()));
+                dict.set(
+                    "DecodeParms",
+                    dictionary! {
+                        // Pure two-dimensional encoding (Group 4)
+                        "K" => -1,
+                        "Columns" => image.width as i64,
+                        "Rows" => image.height as i64,
+                    },
+                );
+            }
+        }
+        if image.invert_colors {
+            dict.set(
+                "Decode",
+                Array(
+                    [1, 0, 1, 0, 1, 0, 1, 0]
+                        .iter()
+                        .map(|v| Integer(*v))
+                        .collect(),
+                ),
+            );
+        }
 
-        lopdf::Stream::new(dict, image.image_data)
+        let mut stream = lopdf::Stream::new(dict, image.image_data);
+        // Data with a filter is already compressed, compressing it again is useless.
+        stream.allows_compression = image.filter == ImageFilter::None;
+        stream
     }
 }
 
@@ -0,0 +1,120 @@
+//! Journal of the changes made to the document by this crate, see `get_journal`.
+//! With the `serde` feature the journal can be serialized (for example to JSON) as audit trail.
+
+use crate::rectangle::Rectangle;
+use crate::PDFSigningDocument;
+use chrono::{DateTime, Utc};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
+
+/// A change made to the document. Pages have index 0 for the first page.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "change", rename_all = "camelCase"))]
+pub enum JournalChange {
+    /// A form field is filled with `value`: the text of the barcode for barcode fields,
+    /// the XHTML for rich text fields and empty for button images.
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    FieldFilled { field_name: String, value: String },
+    /// An image is added to the page content in `rect`.
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    ImageInserted { page_index: usize, rect: Rectangle },
+    /// A signature field is signed (digitally and with the signature image).
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    SignatureAdded {
+        field_name: String,
+        user_id: String,
+        user_name: String,
+        user_email: String,
+    },
+    /// An annotation, like `Stamp`, `Text`, `FreeText` or `Link`, is added.
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    AnnotationAdded {
+        page_index: usize,
+        subtype: String,
+        rect: Rectangle,
+    },
+    /// A barcode is added to the page content in `rect`.
+    /// `page_index` is `None` when the page is not found in the page tree.
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    BarcodeAdded {
+        page_index: Option<usize>,
+        rect: Rectangle,
+        value: String,
+    },
+    /// A watermark is added to the pages.
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    WatermarkAdded { page_indexes: Vec<usize> },
+    /// A background is added to the pages.
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    BackgroundAdded { page_indexes: Vec<usize> },
+    /// A file is attached to the document.
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    AttachmentAdded { file_name: String },
+    /// An entry of the document information (like `Title`) is set.
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    DocumentInfoChanged { key: String, value: String },
+    /// A blank page is inserted.
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    PageInserted { page_index: usize },
+    /// Pages are deleted, the indexes are from before the deletion.
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    PagesDeleted { page_indexes: Vec<usize> },
+    /// The rotation of pages is changed, `rotation` is `None` when it is normalized
+    /// (the content is rotated instead).
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    PagesRotated {
+        page_indexes: Vec<usize>,
+        rotation: Option<i64>,
+    },
+    /// Content in `rect` is removed from the page.
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    Redacted { page_index: usize, rect: Rectangle },
+    /// The document is encrypted.
+    Encrypted,
+    /// The form is flattened, the pages had widgets that are drawn in the content.
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    FormFlattened { page_indexes: Vec<usize> },
+    /// Validation data and the document time stamp `field_name` are added, see
+    /// `refresh_ltv`.
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    LtvRefreshed { field_name: String },
+}
+
+/// A change in the journal, with the time it was made.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct JournalEntry {
+    /// Serialized as RFC 3339 (like `2021-05-04T12:30:00Z`).
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_time"))]
+    pub time: DateTime<Utc>,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub change: JournalChange,
+}
+
+impl PDFSigningDocument {
+    /// Get the changes made to the document by this crate, in the order they were made.
+    /// The journal is kept when the document is reloaded (like after signing),
+    /// it is not stored in the document itself.
+    pub fn get_journal(&self) -> &[JournalEntry] {
+        &self.journal
+    }
+
+    /// Remove all the entries from the journal.
+    pub fn clear_journal(&mut self) {
+        self.journal.clear();
+    }
+
+    /// Add `change` to the journal, with the current time.
+    pub(crate) fn record_change(&mut self, change: JournalChange) {
+        self.journal.push(JournalEntry {
+            time: Utc::now(),
+            change,
+        });
+    }
+}
+
+#[cfg(feature = "serde")]
+fn serialize_time<S: Serializer>(time: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+}
@@ -1,49 +1,266 @@
 mod acro_form;
+mod ades_level;
+mod annotation;
+#[cfg(feature = "tokio")]
+mod async_io;
+mod attachments;
+mod background;
+mod barcode;
+mod button_image;
 mod byte_range;
+mod certificate_details;
+#[cfg(feature = "network")]
+mod csc;
+mod der;
 mod digitally_sign;
+mod document_builder;
+mod document_info;
+mod dss;
+mod encryption;
 mod error;
+mod external_signer;
+mod factur_x;
+mod field_matching;
+#[cfg(feature = "signing")]
+mod field_metadata;
+mod field_validation;
+mod flatten;
+mod font;
+mod form_field;
+mod form_properties;
+#[cfg(feature = "network")]
+mod http;
+mod image_compression;
 mod image_insert;
 mod image_insert_to_page;
+mod image_options;
+mod image_placement;
+mod image_replace;
 mod image_xobject;
+mod journal;
+mod limits;
+mod linearize;
 mod lopdf_utils;
+#[cfg(feature = "network")]
+mod ltv_refresh;
+mod merge;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod object_streams;
+#[cfg(feature = "network")]
+mod ocsp;
+mod optimize;
+mod outline;
+mod page_image;
+mod page_rotation;
+mod page_tree;
 mod pdf_object;
+mod pdfa;
+mod pdfa_preflight;
+mod pending_signature;
+#[cfg(feature = "pcsc")]
+mod piv;
+mod prepared_signature;
+mod recovery;
 mod rectangle;
+mod redaction;
+mod revision_diff;
+mod rich_text;
+mod sanitize;
+#[cfg(feature = "signing")]
 mod signature_image;
 mod signature_info;
+mod signing_options;
+#[cfg(feature = "signing")]
+mod signing_plan;
+mod split;
+mod summary;
+mod svg;
+mod tab_order;
+mod tagged_content;
+mod text;
+#[cfg(feature = "network")]
+mod time_stamp;
+mod time_stamp_verification;
+mod trust_store;
+mod trusted_list;
 mod user_signature_info;
 mod utils;
+mod verification;
+mod verification_options;
+mod watermark;
+mod xref_format;
+#[cfg(feature = "pcsc")]
+mod yubikey;
 
 use acro_form::AcroForm;
+#[cfg(feature = "signing")]
+use acro_form::FormComponent;
 use bitflags::_core::str::from_utf8;
 use byte_range::ByteRange;
+use chrono::{DateTime, Utc};
+use encryption::SecurityHandler;
+use field_validation::validate_field_value;
+use font::TrueTypeFont;
 use image_insert::InsertImage;
 use image_insert_to_page::InsertImageToPage;
 use lopdf::{
     content::{Content, Operation},
-    dictionary, Document, IncrementalDocument, Object, ObjectId, Stream,
+    dictionary,
+    xref::XrefType,
+    Document, IncrementalDocument, Object, ObjectId, Stream,
 };
 use pdf_object::PdfObjectDeref;
+#[cfg(feature = "serde")]
 use serde_json::{Map, Value};
 use std::collections::HashMap;
-use std::{fs::File, path::Path};
+use std::sync::Arc;
+#[cfg(feature = "fs")]
+use std::{fs::File, io::BufWriter, path::Path};
+use tagged_content::ContentTag;
 use utils::parse_font;
 
+pub use ades_level::AdesLevel;
+pub use annotation::{
+    BorderStyle, FreeTextOptions, LinkHighlight, LinkOptions, LinkTarget, NoteOptions, StampOptions,
+};
+pub use attachments::{Attachment, AttachmentRelationship};
+pub use background::Background;
+pub use barcode::BarcodeType;
+pub use certificate_details::{CertificateDetails, KeyUsage};
+#[cfg(feature = "network")]
+pub use csc::{
+    CscAuthentication, CscAuthorization, CscAuthorizationCallback, CscAuthorizationRequest,
+    CscDigestAuthorization, CscSigner, CscSignerBuilder, CscVersion,
+};
+pub use document_builder::{AcroFormLoading, PDFSigningDocumentBuilder};
+pub use dss::{DssUpdate, DssUpdateBuilder};
+pub use encryption::{EncryptionOptions, Permissions};
 pub use error::Error;
+pub use external_signer::ExternalSigner;
+pub use factur_x::FacturXProfile;
+pub use field_matching::{FieldMatching, FieldMatchingFn};
+#[cfg(feature = "signing")]
+pub use field_metadata::{
+    EncodedAlternateName, EncodedFieldName, FieldMetadataParser, PlainFieldName,
+};
+pub use form_field::{FormField, FormFieldType};
+pub use form_properties::ChoiceOption;
+#[cfg(feature = "network")]
+pub use http::{HttpClient, HttpClientBuilder};
+pub use image_compression::ImageCompression;
+pub use image_options::ImageOptions;
+pub use image_placement::{ImagePlacement, ImageScaling, ImageSize};
+pub use image_replace::ImageSelector;
+pub use journal::{JournalChange, JournalEntry};
+pub use limits::ParsingLimits;
 pub use lopdf;
-pub use user_signature_info::{UserFormSignatureInfo, UserSignatureInfo};
+pub use object_streams::ObjectStreamOptions;
+#[cfg(feature = "network")]
+pub use ocsp::OcspCache;
+pub use outline::Bookmark;
+pub use pdfa_preflight::{PdfaIssue, PdfaRule};
+pub use pending_signature::PendingSignature;
+#[cfg(feature = "pcsc")]
+pub use piv::{PinCallback, PinRequest, PivSigner, PivSignerBuilder, PivSlot};
+pub use prepared_signature::PreparedSignature;
+pub use rectangle::Rectangle;
+pub use revision_diff::{ChangeKind, FieldValueChange, ObjectChange, RevisionDiff};
+pub use sanitize::{SanitizeKind, SanitizedItem};
+pub use signing_options::{
+    DigestAlgorithm, DocMdpPermissions, LtvPolicy, MatchBy, SignerMatchingFn, SigningOptions,
+    SigningOptionsBuilder, SubFilter, ValidationData,
+};
+#[cfg(feature = "signing")]
+pub use signing_plan::{SigningAction, SigningField, SigningPlan, SigningResult};
+pub use summary::{AttachmentSummary, DocumentSummary, SignatureSummary};
+pub use tab_order::TabOrder;
+pub use text::TextGlyph;
+pub use time_stamp_verification::TimeStampVerification;
+pub use trust_store::TrustStore;
+pub use trusted_list::{TrustService, TrustedList, TrustedListStatus};
+#[cfg(feature = "network")]
+pub use ureq;
+pub use user_signature_info::UserFormSignatureInfo;
+#[cfg(feature = "signing")]
+pub use user_signature_info::UserSignatureInfo;
+pub use verification::{
+    ByteRangeIssue, SignatureVerification, VerificationReport, VerificationStatus,
+};
+pub use verification_options::{VerificationOptions, VerificationOptionsBuilder};
+pub use watermark::{Watermark, WatermarkOptions};
+pub use xref_format::XrefFormat;
+#[cfg(feature = "pcsc")]
+pub use yubikey::{TouchCallback, TouchPolicy, YubiKeySigner, YubiKeySignerBuilder, YubiKeySlot};
 
 /// The whole PDF document. This struct only loads part of the document on demand.
+/// The document is `Send` and `Sync`: it can be held across `.await` and shared with
+/// `Arc<Mutex<_>>`.
 #[derive(Debug, Clone)]
 pub struct PDFSigningDocument {
     raw_document: IncrementalDocument,
     file_name: String,
-    /// Link between the image name saved and the objectId of the image.
+    /// Link between the hash of the image (and field size) and the objectId of the image.
     /// This is used to reduce the amount of copies of the images in the pdf file.
+    #[cfg_attr(not(feature = "signing"), allow(dead_code))]
     image_signature_object_id: HashMap<String, ObjectId>,
 
-    acro_form: Option<Vec<AcroForm>>,
+    /// The form fields, shared with clones of the document so they are not copied.
+    acro_form: Option<Arc<Vec<AcroForm>>>,
+    /// Validate values against the format (`AFNumber`, `AFDate`) of the field when filling forms.
+    validate_field_format: bool,
+    /// How the keys of the data are matched with the form fields when filling forms.
+    field_matching: FieldMatching,
+    /// How signature images are placed inside the signature field and encoded.
+    image_options: ImageOptions,
+    /// Encryption of the document, new objects are encrypted with the same key when saving.
+    security_handler: Option<SecurityHandler>,
+    /// Keep PDF/A documents conformant, see `set_pdfa_mode`.
+    pdfa_mode: bool,
+    /// Font used for text written by this crate, instead of the standard fonts.
+    embedded_font: Option<TrueTypeFont>,
+    /// The cross-reference table was damaged and rebuilt when reading the document.
+    recovered: bool,
+    /// Write linearized (fast web view) documents, see `set_linearize`.
+    linearize: bool,
+    /// Format of the cross-reference section of new revisions, see `set_xref_format`.
+    xref_format: XrefFormat,
+    /// Pack new objects into object streams, see `set_object_streams`.
+    object_streams: Option<ObjectStreamOptions>,
+    /// Compress the streams when the document is written, see `set_compress`.
+    compress: bool,
+    /// Tag added content in tagged documents, see `set_tag_content`.
+    tag_content: bool,
+    /// The file of a document read with `read_mmap`, the `raw_document` has no bytes then.
+    #[cfg(feature = "mmap")]
+    mapped_file: Option<Arc<memmap2::Mmap>>,
+    /// Write reproducible documents, see `set_deterministic`.
+    deterministic: bool,
+    /// Time written in the document instead of the current time, see `set_fixed_time`.
+    fixed_time: Option<DateTime<Utc>>,
+    /// Limits for reading the document, see `PDFSigningDocumentBuilder::limits`.
+    limits: ParsingLimits,
+    /// Changes made to the document, see `get_journal`.
+    journal: Vec<JournalEntry>,
 }
 
+// Fails to compile when a field makes the document (or the types used with it in a
+// server) no longer `Send` and `Sync`.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<PDFSigningDocument>();
+    assert_send_sync::<PDFSigningDocumentBuilder>();
+    assert_send_sync::<SigningOptions>();
+    assert_send_sync::<PreparedSignature>();
+    assert_send_sync::<SignatureVerification>();
+    assert_send_sync::<VerificationReport>();
+    assert_send_sync::<VerificationOptions>();
+    assert_send_sync::<DocumentSummary>();
+    assert_send_sync::<Error>();
+    #[cfg(feature = "signing")]
+    assert_send_sync::<UserSignatureInfo>();
+};
+
 impl PDFSigningDocument {
     fn new(raw_document: IncrementalDocument, file_name: String) -> Self {
         PDFSigningDocument {
@@ -51,6 +268,24 @@ impl PDFSigningDocument {
             file_name,
             image_signature_object_id: HashMap::new(),
             acro_form: None,
+            validate_field_format: false,
+            field_matching: FieldMatching::default(),
+            image_options: ImageOptions::default(),
+            security_handler: None,
+            pdfa_mode: false,
+            embedded_font: None,
+            recovered: false,
+            linearize: false,
+            xref_format: XrefFormat::default(),
+            object_streams: None,
+            compress: true,
+            tag_content: false,
+            #[cfg(feature = "mmap")]
+            mapped_file: None,
+            deterministic: false,
+            fixed_time: None,
+            limits: ParsingLimits::default(),
+            journal: vec![],
         }
     }
 
@@ -60,16 +295,332 @@ impl PDFSigningDocument {
         // Do not replace `image_signature_object_id`
         // We want to keep this so we can do optimization.
         self.acro_form = other.acro_form;
+        self.security_handler = other.security_handler;
+        #[cfg(feature = "mmap")]
+        {
+            self.mapped_file = other.mapped_file;
+        }
+    }
+
+    /// Enable or disable validation of values against the number and date format of fields.
+    /// The `MaxLen` of a field is always validated.
+    pub fn set_validate_field_format(&mut self, validate: bool) {
+        self.validate_field_format = validate;
+    }
+
+    /// Set how the keys of the data are matched with the form fields in `fill_form`.
+    pub fn set_field_matching(&mut self, field_matching: FieldMatching) {
+        self.field_matching = field_matching;
+    }
+
+    /// Set how signature images are scaled to fit the signature field.
+    /// The default is `ImageScaling::Stretch`.
+    pub fn set_image_scaling(&mut self, image_scaling: ImageScaling) {
+        self.image_options.placement.scaling = image_scaling;
+    }
+
+    /// Set the empty space (in points) between the border of the signature field
+    /// and the signature image. The default is `0.0`.
+    pub fn set_image_padding(&mut self, padding: f64) {
+        self.image_options.placement.padding = padding;
+    }
+
+    /// Set the physical size of signature images, like `ImageSize::Millimeters`.
+    /// The default is `ImageSize::Fit`, which uses the image scaling.
+    pub fn set_image_size(&mut self, image_size: ImageSize) {
+        self.image_options.placement.size = image_size;
+    }
+
+    /// Set the rotation (in degrees, counterclockwise) of signature images.
+    /// The default is `0.0`.
+    pub fn set_image_rotation(&mut self, rotation: f64) {
+        self.image_options.placement.rotation = rotation;
+    }
+
+    /// Set the opacity (0.0 - 1.0) of signature images.
+    /// The default is `None`, which draws the images opaque.
+    pub fn set_image_opacity(&mut self, opacity: Option<f64>) {
+        self.image_options.opacity = opacity;
+    }
+
+    /// Set how signature images are (re-)encoded, like the JPEG quality.
+    /// By default images are not re-encoded.
+    pub fn set_image_compression(&mut self, image_compression: ImageCompression) {
+        self.image_options.compression = image_compression;
+    }
+
+    /// Set the ICC profile used for signature images without an embedded profile.
+    pub fn set_image_icc_profile(&mut self, icc_profile: Option<Vec<u8>>) {
+        self.image_options.icc_profile = icc_profile;
+    }
+
+    /// Allow SVG signature images, they are drawn as vector content.
+    /// The default is `false`, see `ImageOptions::allow_svg`.
+    pub fn set_image_allow_svg(&mut self, allow_svg: bool) {
+        self.image_options.allow_svg = allow_svg;
+    }
+
+    /// Enable or disable the PDF/A mode, which keeps PDF/A-2b documents conformant
+    /// when they are filled and signed. In this mode text needs an embedded font
+    /// (`set_embedded_font`), encryption is not allowed, the XMP metadata is updated
+    /// with the document information and signature fields are always printed.
+    /// The output intent and other document level entries are not changed.
+    pub fn set_pdfa_mode(&mut self, pdfa_mode: bool) {
+        self.pdfa_mode = pdfa_mode;
+    }
+
+    /// Keep tagged (accessible) documents valid when content is added, so they still pass
+    /// PDF/UA checks. Images with an alternate description (`ImageOptions::alternate_text`)
+    /// and barcodes get a `Figure` structure element, annotations a `Link` or `Annot` element.
+    /// Watermarks, backgrounds and images without a description are marked as `Artifact`.
+    /// Documents without a structure tree are not changed.
+    pub fn set_tag_content(&mut self, tag_content: bool) {
+        self.tag_content = tag_content;
+    }
+
+    /// Set the TrueType font (`.ttf` file) that is embedded and used for text written
+    /// in the document, like rich text fields and text watermarks.
+    /// Only characters of the Windows-1252 character set are supported.
+    pub fn set_embedded_font(&mut self, font_data: Vec<u8>) -> Result<(), Error> {
+        self.embedded_font = Some(TrueTypeFont::parse(font_data)?);
+        Ok(())
+    }
+
+    /// Write linearized (fast web view) documents with `save_document` and `write_document`,
+    /// so a viewer can show the first page before the whole file is downloaded.
+    /// The document is rewritten as 1 revision instead of an incremental update.
+    /// Signed and encrypted documents are always written as incremental update,
+    /// because rewriting would invalidate the signatures or needs the encryption key.
+    pub fn set_linearize(&mut self, linearize: bool) {
+        self.linearize = linearize;
+    }
+
+    /// Write the cross-reference section of new revisions (incremental updates and rewritten
+    /// documents) as classic table or as cross-reference stream (PDF 1.5).
+    /// The default (`XrefFormat::Auto`) uses the same format as the last revision.
+    pub fn set_xref_format(&mut self, xref_format: XrefFormat) {
+        self.xref_format = xref_format;
+    }
+
+    /// Pack the objects of new revisions into compressed object streams (PDF 1.5),
+    /// `None` (the default) writes them as normal objects.
+    /// Streams, signature dictionaries and the encryption dictionary are never packed.
+    /// Object streams need a cross-reference stream, so they are not used with `XrefFormat::Table`.
+    pub fn set_object_streams(&mut self, options: Option<ObjectStreamOptions>) {
+        self.object_streams = options;
+    }
+
+    /// Compress the streams of new revisions when the document is written (the default).
+    /// Disable it to save or sign faster, for example for intermediate versions of a
+    /// document. Revisions written without compression stay uncompressed, also when the
+    /// document is saved again.
+    pub fn set_compress(&mut self, compress: bool) {
+        self.compress = compress;
+    }
+
+    /// Write the same bytes for the same input document and changes, for example for
+    /// content-addressed storage or snapshot tests. Dates that are not given (like the
+    /// signing time `M` and the modification date of annotations and attachments) are
+    /// left out, unless a time is set with `set_fixed_time`, and the platform is not
+    /// written in the signature.
+    /// Encryption keys stay random, so encrypted documents are not reproducible.
+    /// The CMS signature is created by the signer, it can contain the signing time or
+    /// random values (like ECDSA signatures).
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    /// Set the time written in the document (like the signing time `M`) instead of the
+    /// current time, `None` (the default) uses the current time.
+    pub fn set_fixed_time(&mut self, time: Option<DateTime<Utc>>) {
+        self.fixed_time = time;
+    }
+
+    /// Get the time to write in the document, `None` when it should be left out.
+    pub(crate) fn get_current_time(&self) -> Option<DateTime<Utc>> {
+        match self.fixed_time {
+            Some(time) => Some(time),
+            None if self.deterministic => None,
+            None => Some(Utc::now()),
+        }
     }
 
     pub fn read_from<R: std::io::Read>(reader: R, file_name: String) -> Result<Self, Error> {
-        let raw_doc = IncrementalDocument::load_from(reader)?;
-        Ok(Self::new(raw_doc, file_name))
+        Self::read_from_with_password(reader, file_name, "")
     }
 
+    /// Read the document from the bytes of the file, without copying them.
+    /// Use `builder` for a password or other settings.
+    pub fn read_from_bytes(data: Vec<u8>, file_name: String) -> Result<Self, Error> {
+        Self::load(data, file_name, "", &ParsingLimits::default())
+    }
+
+    #[cfg(feature = "fs")]
     pub fn read<P: AsRef<Path>>(path: P, file_name: String) -> Result<Self, Error> {
-        let raw_doc = IncrementalDocument::load(path)?;
-        Ok(Self::new(raw_doc, file_name))
+        Self::read_with_password(path, file_name, "")
+    }
+
+    /// Read a document that can be protected with a password (RC4, AES-128 or AES-256).
+    /// `password` can be the user or the owner password.
+    /// Changes are saved with the same encryption, so existing signatures stay valid.
+    pub fn read_from_with_password<R: std::io::Read>(
+        mut reader: R,
+        file_name: String,
+        password: &str,
+    ) -> Result<Self, Error> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        Self::load(data, file_name, password, &ParsingLimits::default())
+    }
+
+    /// Read a document like `read_from`, but when the cross-reference table is damaged
+    /// it is rebuilt by scanning the file for objects, like most viewers do.
+    /// Use `is_recovered` to check if the document was damaged.
+    pub fn read_from_lenient<R: std::io::Read>(
+        reader: R,
+        file_name: String,
+    ) -> Result<Self, Error> {
+        Self::read_from_lenient_with_password(reader, file_name, "")
+    }
+
+    /// Read a document like `read_from_with_password`, but when the cross-reference table
+    /// is damaged it is rebuilt by scanning the file for objects.
+    /// The rebuilt table is appended to the file, so the existing bytes are not changed.
+    pub fn read_from_lenient_with_password<R: std::io::Read>(
+        mut reader: R,
+        file_name: String,
+        password: &str,
+    ) -> Result<Self, Error> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        Self::load_lenient(data, file_name, password, &ParsingLimits::default())
+    }
+
+    /// Check if the cross-reference table was damaged and rebuilt when reading the document
+    /// with `read_from_lenient`.
+    pub fn is_recovered(&self) -> bool {
+        self.recovered
+    }
+
+    /// Read a document that can be protected with a password (RC4, AES-128 or AES-256).
+    /// `password` can be the user or the owner password.
+    /// Changes are saved with the same encryption, so existing signatures stay valid.
+    #[cfg(feature = "fs")]
+    pub fn read_with_password<P: AsRef<Path>>(
+        path: P,
+        file_name: String,
+        password: &str,
+    ) -> Result<Self, Error> {
+        Self::load(
+            std::fs::read(path)?,
+            file_name,
+            password,
+            &ParsingLimits::default(),
+        )
+    }
+
+    /// Load the document like `load`, but rebuild the cross-reference table when it is damaged.
+    /// Only a damaged document is parsed a second time, after the table is rebuilt.
+    fn load_lenient(
+        data: Vec<u8>,
+        file_name: String,
+        password: &str,
+        limits: &ParsingLimits,
+    ) -> Result<Self, Error> {
+        let _span = tracing::info_span!("load_lenient", file_name = %file_name).entered();
+        let objects = recovery::find_objects(&data);
+        limits.check_file(&data, &objects)?;
+        let encrypted = recovery::has_encrypt(&data, &objects);
+        match encryption::parse_document(&data, password, encrypted) {
+            Ok((doc, _)) if recovery::is_damaged(&doc) => {}
+            Ok((doc, security_handler)) => {
+                let raw_doc = IncrementalDocument::create_from(data, doc);
+                return Self::from_parsed(raw_doc, security_handler, file_name, limits);
+            }
+            Err(Error::EncryptionRequired) => return Err(Error::EncryptionRequired),
+            Err(err) => tracing::warn!("Recovery: Loading the document failed: {:?}", err),
+        }
+        let data = recovery::rebuild_xref(&data, limits)?;
+        let mut document = Self::load(data, file_name, password, limits)?;
+        document.recovered = true;
+        Ok(document)
+    }
+
+    /// Load the document and decrypt it when it is encrypted.
+    /// The file is checked against the `limits` before and after it is parsed.
+    fn load(
+        data: Vec<u8>,
+        file_name: String,
+        password: &str,
+        limits: &ParsingLimits,
+    ) -> Result<Self, Error> {
+        let _span = tracing::info_span!("load", file_name = %file_name).entered();
+        let objects = recovery::find_objects(&data);
+        limits.check_file(&data, &objects)?;
+        let encrypted = recovery::has_encrypt(&data, &objects);
+        let (doc, security_handler) = encryption::parse_document(&data, password, encrypted)?;
+        let raw_doc = IncrementalDocument::create_from(data, doc);
+        Self::from_parsed(raw_doc, security_handler, file_name, limits)
+    }
+
+    /// Create the document from a parsed (and decrypted) file and check it against the `limits`.
+    fn from_parsed(
+        raw_doc: IncrementalDocument,
+        security_handler: Option<SecurityHandler>,
+        file_name: String,
+        limits: &ParsingLimits,
+    ) -> Result<Self, Error> {
+        let mut document = Self::new(raw_doc, file_name);
+        document.security_handler = security_handler;
+        document.limits = *limits;
+        document.check_limits()?;
+        Ok(document)
+    }
+
+    /// Load a new version of this document, like after signing.
+    /// An encrypted document is decrypted with the key of this document, and the limits
+    /// of this document are used.
+    fn reload_from(&self, data: Vec<u8>, file_name: String) -> Result<Self, Error> {
+        match &self.security_handler {
+            Some(security_handler) => {
+                let raw_doc = encryption::reload_encrypted_document(data, security_handler)?;
+                Self::from_parsed(
+                    raw_doc,
+                    Some(security_handler.clone()),
+                    file_name,
+                    &self.limits,
+                )
+            }
+            None => Self::load(data, file_name, "", &self.limits),
+        }
+    }
+
+    /// Encrypt the document with AES-256, `options.user_password` is needed to open it
+    /// and `options.permissions` restrict what can be done with the document.
+    /// The document is rewritten (including the changes that are not saved yet),
+    /// so existing signatures become invalid: encrypt before signing.
+    /// Signatures added afterwards are encrypted with the same key.
+    pub fn encrypt_document(&mut self, options: &EncryptionOptions) -> Result<(), Error> {
+        if self.pdfa_mode {
+            return Err(Error::from("PDF/A: Encryption is not allowed."));
+        }
+        let mut doc = self.get_latest_document();
+        self.security_handler = Some(encryption::add_encryption(&mut doc, options)?);
+        self.reload_document(doc)?;
+        self.record_change(JournalChange::Encrypted);
+        Ok(())
+    }
+
+    /// Get the permissions of an encrypted document, `None` when it is not encrypted.
+    pub fn get_permissions(&self) -> Option<Permissions> {
+        let doc = self.raw_document.get_prev_documents();
+        let encrypt_dict = match doc.trailer.get(b"Encrypt").ok()? {
+            Object::Reference(encrypt_id) => doc.get_dictionary(*encrypt_id).ok()?,
+            Object::Dictionary(encrypt_dict) => encrypt_dict,
+            _ => return None,
+        };
+        let value = encrypt_dict.get(b"P").and_then(Object::as_i64).ok()?;
+        Some(Permissions::from_value(value))
     }
 
     pub fn load_all(&mut self) -> Result<(), Error> {
@@ -78,32 +629,166 @@ impl PDFSigningDocument {
 
     pub fn load_acro_form(&mut self) -> Result<(), Error> {
         if self.acro_form.is_none() {
-            self.acro_form = Some(AcroForm::load_all_forms(
+            self.acro_form = Some(Arc::new(AcroForm::load_all_forms(
                 self.raw_document.get_prev_documents(),
-            )?);
+                self.limits.max_depth,
+            )?));
         } else {
-            log::info!("Already Loaded Acro Form.");
+            tracing::info!("Already Loaded Acro Form.");
         }
         Ok(())
     }
 
     /// Save document to file
+    #[cfg(feature = "fs")]
     pub fn save_document<P: AsRef<Path>>(&self, path: P) -> Result<File, Error> {
-        // Create clone so we can compress the clone, not the original.
-        let mut raw_document = self.raw_document.clone();
-        raw_document.new_document.compress();
-        Ok(raw_document.save(path)?)
+        let mut file = BufWriter::new(File::create(path)?);
+        self.write_document(&mut file)?;
+        Ok(file.into_inner().map_err(|error| error.into_error())?)
+    }
+
+    /// Get the bytes of the document, with the changes as incremental update.
+    pub fn save_to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        self.write_document(&mut data)?;
+        Ok(data)
     }
 
     /// Write document to Writer or buffer
     pub fn write_document<W: std::io::Write>(&self, target: &mut W) -> Result<(), Error> {
-        // Create clone so we can compress the clone, not the original.
-        let mut raw_document = self.raw_document.clone();
-        raw_document.new_document.compress();
-        raw_document.save_to(target)?;
+        if let Some(data) = self.get_linearized_document()? {
+            target.write_all(&data)?;
+            return Ok(());
+        }
+        let (new_document, packed_objects, xref_type) = self.get_revision_to_save()?;
+        object_streams::write_revision(
+            self.get_prev_documents_bytes(),
+            &new_document,
+            &packed_objects,
+            xref_type,
+            target,
+        )
+    }
+
+    /// Get the linearized document, `None` when it should be written as incremental update.
+    fn get_linearized_document(&self) -> Result<Option<Vec<u8>>, Error> {
+        if !self.linearize {
+            return Ok(None);
+        }
+        if self.security_handler.is_some() || self.get_last_signed_byte_range().is_some() {
+            tracing::warn!("Linearize: Signed and encrypted documents are not linearized.");
+            return Ok(None);
+        }
+        let mut doc = self.get_latest_document();
+        self.prepare_pdfa_document(&mut doc);
+        if self.compress {
+            doc.compress();
+        }
+        Ok(Some(linearize::write_linearized(doc)?))
+    }
+
+    /// Clone the new revision, so the clone can be compressed and encrypted, not the original.
+    /// The previous revisions are not cloned, they are written as they are.
+    /// Also return the objects that are packed in object streams (see `set_object_streams`)
+    /// and the type of cross-reference section to write (see `set_xref_format`).
+    fn get_revision_to_save(
+        &self,
+    ) -> Result<(Document, object_streams::PackedObjects, XrefType), Error> {
+        let mut new_document = self.raw_document.new_document.clone();
+        let xref_type = self.xref_format.to_xref_type(
+            self.raw_document
+                .get_prev_documents()
+                .reference_table
+                .cross_reference_type,
+        );
+        self.prepare_pdfa_update(&mut new_document);
+        let packed_objects = self
+            .pack_object_streams(&mut new_document)
+            .unwrap_or_default();
+        if self.compress {
+            new_document.compress();
+        }
+        if let Some(security_handler) = &self.security_handler {
+            security_handler.encrypt_document(&mut new_document)?;
+        }
+        Ok((new_document, packed_objects, xref_type))
+    }
+
+    /// Get the document with the changes that are not saved yet, as 1 document.
+    pub(crate) fn get_latest_document(&self) -> Document {
+        let mut doc = self.raw_document.get_prev_documents().clone();
+        let new_document = &self.raw_document.new_document;
+        for (object_id, object) in new_document.objects.iter() {
+            doc.objects.insert(*object_id, object.clone());
+        }
+        doc.max_id = doc.max_id.max(new_document.max_id);
+        doc.trailer = new_document.trailer.clone();
+        doc.trailer.remove(b"Prev");
+        doc
+    }
+
+    /// Find a form field by its partial field name (`T`).
+    /// The forms should be loaded before calling this function.
+    fn get_form_field(&self, field_name: &str) -> Result<AcroForm, Error> {
+        self.acro_form
+            .as_ref()
+            .and_then(|forms| {
+                forms
+                    .iter()
+                    .find(|form| form.get_partial_field_name() == Some(field_name))
+            })
+            .cloned()
+            .ok_or_else(|| Error::FieldNotFound {
+                name: field_name.to_owned(),
+            })
+    }
+
+    /// Replace the document with `doc` and reload all the forms.
+    /// Used when the document is changed in place instead of with an incremental update.
+    fn reload_document(&mut self, mut doc: Document) -> Result<(), Error> {
+        // Regenerate the pdf file
+        let mut new_binary_pdf: Vec<u8> = Vec::new();
+        self.prepare_pdfa_document(&mut doc);
+        let packed_objects = self.pack_object_streams(&mut doc);
+        if self.compress {
+            doc.compress();
+        }
+        if let Some(security_handler) = &self.security_handler {
+            security_handler.encrypt_document(&mut doc)?;
+        }
+        let reference_table = &mut doc.reference_table;
+        reference_table.cross_reference_type = self
+            .xref_format
+            .to_xref_type(reference_table.cross_reference_type);
+        if self.xref_format == XrefFormat::Table {
+            doc.trailer.remove(b"Type");
+        }
+        match packed_objects {
+            Some(packed_objects) => object_streams::write_revision(
+                &[],
+                &doc,
+                &packed_objects,
+                XrefType::CrossReferenceStream,
+                &mut new_binary_pdf,
+            )?,
+            None => doc.save_to(&mut new_binary_pdf)?,
+        }
+
+        self.copy_from(self.reload_from(new_binary_pdf, self.file_name.clone())?);
+        self.load_all()?;
         Ok(())
     }
 
+    /// Get the bytes of the document as it was read (all revisions, without the changes
+    /// that are not saved yet).
+    pub fn get_prev_documents_bytes(&self) -> &[u8] {
+        #[cfg(feature = "mmap")]
+        if let Some(mapped_file) = &self.mapped_file {
+            return mapped_file;
+        }
+        self.raw_document.get_prev_documents_bytes()
+    }
+
     pub fn get_incr_document_ref(&self) -> &IncrementalDocument {
         &self.raw_document
     }
@@ -116,171 +801,169 @@ impl PDFSigningDocument {
         &self.raw_document.new_document
     }
 
-    pub fn sign_document_2(
+    /// Sign the empty signature fields of the users, `options` define how the fields are
+    /// matched to the users (see `SigningOptionsBuilder::match_by`) and how the
+    /// signatures are created. Returns the document with what happened to every signature
+    /// field, like `plan_signing`.
+    /// With `MatchBy::BoxId` it returns `Error::MissingSignatureBox` (before anything is
+    /// signed) when there is no signature field for the `box_id` of a user.
+    /// It returns `Error::NetworkDisabled` when the options are offline but need the network.
+    #[cfg(feature = "signing")]
+    pub fn sign(
         &mut self,
-        users_signature_info: Vec<UserSignatureInfo>,
-    ) -> Result<Vec<u8>, Error> {
-        self.load_all()?;
-        // Set PDF version, version 1.5 is the minimum version required.
-        self.raw_document.new_document.version = "1.5".to_owned();
-
-        // loop over AcroForm elements
-        let acro_forms_opts = self.acro_form.clone();
-        let mut last_binary_pdf = None;
-
-        // Covert `Vec<UserSignatureInfo>` to `HashMap<String, UserSignatureInfo>`
-        let users_signature_info_map: HashMap<String, UserSignatureInfo> = users_signature_info
-            .iter()
-            .map(|info| (info.box_id.clone(), info.clone()))
-            .collect();
-
-        if acro_forms_opts.is_some() {
-            let acro_forms = acro_forms_opts.unwrap();
-
-            for form_field in acro_forms.into_iter() {
-                // Check if it is a signature and it is already signed.
-                if !form_field.is_empty_signature() {
-                    // Go back to start of while loop
-                    continue;
-                }
-
-                // Update pdf (when nothing else is incorrect)
-                // Insert signature images into pdf itself.
-                let pdf_document_user_info_opt =
-                    self.add_signature_images_2(form_field, &users_signature_info_map)?;
-
-                // PDF has been updated, now we need to digitally sign it.
-                if let Some((pdf_document_image, user_form_info)) = pdf_document_user_info_opt {
-                    // Digitally sign the document using a cert.
-                    let user_info = users_signature_info_map
-                        .get(&user_form_info.box_id)
-                        .ok_or_else(|| Error::Other("User was not found".to_owned()))?;
-
-                    let new_binary_pdf = pdf_document_image.digitally_sign_document(user_info)?;
-                    // Reload file
-                    self.copy_from(Self::read_from(
-                        &*new_binary_pdf,
-                        pdf_document_image.file_name,
-                    )?);
-                    self.load_all()?;
-                    self.raw_document.new_document.version = "1.5".to_owned();
-
-                    // acro_forms = self.acro_form.clone();
-                    // Set as return value
-                    last_binary_pdf = Some(new_binary_pdf);
-                    // Reset form field index
-                    // form_field_index = 0;
+        users_signature_info: &[UserSignatureInfo],
+        options: &SigningOptions,
+    ) -> Result<SigningResult, Error> {
+        options.check_offline()?;
+        let plan = self.plan_signing(users_signature_info, options)?;
+        self.set_signing_version(options)?;
+
+        // Check that every user has a signature field.
+        if options.match_by == MatchBy::BoxId {
+            let forms = self.acro_form.clone().unwrap_or_default();
+            for user_info in users_signature_info {
+                let has_box = forms.iter().any(|form_field| {
+                    matches!(
+                        form_field.get_form_component(),
+                        FormComponent::EmptySignature | FormComponent::SignedSignature { .. }
+                    ) && form_field.get_partial_field_name() == Some(user_info.box_id.as_str())
+                });
+                if !has_box {
+                    return Err(Error::MissingSignatureBox {
+                        box_id: user_info.box_id.clone(),
+                    });
                 }
             }
         }
 
-        match last_binary_pdf {
-            Some(last_binary_pdf) => Ok(last_binary_pdf),
-            None => {
-                // No signing done, so just return initial document.
-                Ok(self.raw_document.get_prev_documents_bytes().to_vec())
-            }
-        }
-    }
-
-    pub fn sign_document(
-        &mut self,
-        users_signature_info: Vec<UserSignatureInfo>,
-    ) -> Result<Vec<u8>, Error> {
-        self.load_all()?;
-        // Set PDF version, version 1.5 is the minimum version required.
-        self.raw_document.new_document.version = "1.5".to_owned();
-
         // loop over AcroForm elements
-        let mut acro_forms = self.acro_form.clone();
+        let mut acro_forms = self.acro_form.clone().unwrap_or_default();
         let mut last_binary_pdf = None;
 
-        // Take the first form field (if there is any)
-        let mut form_field_current = acro_forms.as_ref().and_then(|list| list.first().cloned());
+        // Start with the first form field (if there is any)
         let mut form_field_index = 0;
 
-        // Covert `Vec<UserSignatureInfo>` to `HashMap<String, UserSignatureInfo>`
-        let users_signature_info_map: HashMap<String, UserSignatureInfo> = users_signature_info
-            .iter()
-            .map(|info| (info.user_id.clone(), info.clone()))
-            .collect();
-
         // Make sure we never end up in an infinite loop, should not happen.
         // But better safe then sorry.
-        let mut loop_counter: u16 = 0;
+        let mut loop_counter: usize = 0;
+        let mut signed_fields = 0;
         // Loop over all the form fields and sign them one by one.
-        while let Some(form_field) = form_field_current {
+        while let Some(form_field) = acro_forms.get(form_field_index) {
             loop_counter += 1;
-            if loop_counter >= 10000 {
-                log::error!(
-                    "Infinite loop detected and prevented. Please check file: `{}`.",
-                    self.file_name
-                );
-                break;
+            if loop_counter > options.max_iterations {
+                return Err(Error::TooManyIterations {
+                    maximum: options.max_iterations,
+                    signed_fields,
+                });
             }
             // Check if it is a signature and it is already signed.
             if !form_field.is_empty_signature() {
                 // Go to next form field if pdf did not change
                 form_field_index += 1;
-                form_field_current = acro_forms
-                    .as_ref()
-                    .and_then(|list| list.get(form_field_index).cloned());
                 // Go back to start of while loop
                 continue;
             }
-
-            // TODO: Debug code, can be removed
-            // if form_field_index == 1 {
-            //     form_field_index += 1;
-            //     form_field_current = acro_forms
-            //         .as_ref()
-            //         .and_then(|list| list.get(form_field_index).cloned());
-            //     continue;
-            // }
-
+            let user_info =
+                match self.match_signature_field(form_field, users_signature_info, options) {
+                    Ok(user_info) => user_info,
+                    Err(action) => {
+                        tracing::warn!("Signature field is skipped: {:?}", action);
+                        // Go to next form field because pdf did not change
+                        form_field_index += 1;
+                        continue;
+                    }
+                };
+
+            let field_name = form_field
+                .get_fully_qualified_field_name()
+                .map(str::to_owned);
+            let _span = tracing::info_span!(
+                "sign_field",
+                file_name = %self.file_name,
+                field_name = field_name.as_deref().unwrap_or_default(),
+            )
+            .entered();
             // Update pdf (when nothing else is incorrect)
             // Insert signature images into pdf itself.
-            let pdf_document_user_info_opt =
-                self.add_signature_images(form_field, &users_signature_info_map)?;
+            let pdf_document_image = self.add_signature_image(form_field, user_info, options)?;
 
             // PDF has been updated, now we need to digitally sign it.
-            if let Some((pdf_document_image, user_form_info)) = pdf_document_user_info_opt {
-                // Digitally sign the document using a cert.
-                let user_info = users_signature_info_map
-                    .get(&user_form_info.user_id)
-                    .ok_or_else(|| Error::Other("User was not found".to_owned()))?;
-
-                let new_binary_pdf = pdf_document_image.digitally_sign_document(user_info)?;
-                // Reload file
-                self.copy_from(Self::read_from(
-                    &*new_binary_pdf,
-                    pdf_document_image.file_name,
-                )?);
-                self.load_all()?;
-                self.raw_document.new_document.version = "1.5".to_owned();
-                acro_forms = self.acro_form.clone();
-                // Set as return value
-                last_binary_pdf = Some(new_binary_pdf);
-                // Reset form field index
-                form_field_index = 0;
-            } else {
-                // Go to next form field because pdf did not change
-                form_field_index += 1;
-            }
-
-            // Load next form field (or set to `0` depending on index.)
-            form_field_current = acro_forms
-                .as_ref()
-                .and_then(|list| list.get(form_field_index).cloned());
+            let new_binary_pdf = pdf_document_image.digitally_sign_document(user_info, options)?;
+            // Reload file
+            self.copy_from(self.reload_from(new_binary_pdf.clone(), pdf_document_image.file_name)?);
+            self.load_all()?;
+            self.set_signing_version(options)?;
+            self.record_signature(field_name, user_info);
+            signed_fields += 1;
+            acro_forms = self.acro_form.clone().unwrap_or_default();
+            // Set as return value
+            last_binary_pdf = Some(new_binary_pdf);
+            // Reset form field index
+            form_field_index = 0;
         }
 
-        match last_binary_pdf {
-            Some(last_binary_pdf) => Ok(last_binary_pdf),
-            None => {
-                // No signing done, so just return initial document.
-                Ok(self.raw_document.get_prev_documents_bytes().to_vec())
-            }
+        #[cfg(feature = "network")]
+        if let (LtvPolicy::FetchOcsp(cache), Some(_)) = (&options.ltv_policy, &last_binary_pdf) {
+            let field_names: Vec<&str> = plan
+                .signed_fields()
+                .filter_map(|field| field.field_name.as_deref())
+                .collect();
+            last_binary_pdf =
+                Some(self.add_ocsp_responses(&field_names, cache, &options.http_client)?);
         }
+
+        let document = match last_binary_pdf {
+            Some(last_binary_pdf) => last_binary_pdf,
+            // No signing done, so just return initial document.
+            None => self.get_prev_documents_bytes().to_vec(),
+        };
+        Ok(SigningResult {
+            document,
+            fields: plan.fields,
+        })
+    }
+
+    /// Sign the empty signature fields that have the `box_id` of a user as name,
+    /// like `sign` with `MatchBy::BoxId`.
+    #[cfg(feature = "signing")]
+    #[deprecated(note = "use `sign` with `MatchBy::BoxId`")]
+    pub fn sign_document_2(
+        &mut self,
+        users_signature_info: &[UserSignatureInfo],
+        options: &SigningOptions,
+    ) -> Result<Vec<u8>, Error> {
+        let options = SigningOptions {
+            match_by: MatchBy::BoxId,
+            ..options.clone()
+        };
+        Ok(self.sign(users_signature_info, &options)?.document)
+    }
+
+    /// Sign the empty signature fields with the encoded info of a user (see
+    /// `UserFormSignatureInfo`) as name, like `sign` with `MatchBy::UserId`.
+    #[cfg(feature = "signing")]
+    #[deprecated(note = "use `sign` with `MatchBy::UserId`")]
+    pub fn sign_document(
+        &mut self,
+        users_signature_info: &[UserSignatureInfo],
+        options: &SigningOptions,
+    ) -> Result<SigningResult, Error> {
+        let options = SigningOptions {
+            match_by: MatchBy::UserId,
+            ..options.clone()
+        };
+        self.sign(users_signature_info, &options)
+    }
+
+    /// Add the signature of the field with `field_name` by the user to the journal.
+    #[cfg(feature = "signing")]
+    fn record_signature(&mut self, field_name: Option<String>, user_info: &UserSignatureInfo) {
+        self.record_change(JournalChange::SignatureAdded {
+            field_name: field_name.unwrap_or_default(),
+            user_id: user_info.user_id.clone(),
+            user_name: user_info.user_name.clone(),
+            user_email: user_info.user_email.clone(),
+        });
     }
 
     // pub fn add_signature_to_form<R: Read>(
@@ -305,30 +988,58 @@ impl PDFSigningDocument {
     //     })
     // }
 
+    /// Fill the text fields with the values of `data` (feature `serde`), the keys are
+    /// matched with the fields as set with `set_field_matching`.
+    #[cfg(feature = "serde")]
     pub fn fill_form(&mut self, data: Map<String, Value>) -> Result<(), Error> {
+        let _span = tracing::info_span!("fill_form", file_name = %self.file_name).entered();
+        self.load_all()?;
         let mut doc = self.raw_document.get_prev_documents().clone();
 
         // inspired by https://github.com/Emulator000/pdf_form/blob/master/src/lib.rs
 
         let acro_forms = self.acro_form.clone();
-        let form_fields_opts = acro_forms.as_ref();
-        if form_fields_opts.is_some() {
-            let form_fields = form_fields_opts.unwrap();
+        let mut filled_fields = vec![];
+        if let Some(form_fields) = acro_forms.as_ref() {
             for field in form_fields.iter() {
                 let object_id_opts = field.get_object_id();
                 let partial_field_name = field.get_partial_field_name().unwrap_or("");
-                let partial_field_name_lower_case = partial_field_name.to_lowercase();
 
-                let data_value_opts = data.get(&partial_field_name_lower_case);
-                if data_value_opts.is_some() && object_id_opts.is_some() {
-                    let object_id = object_id_opts.unwrap();
-                    let data_value = data_value_opts.unwrap().as_str().unwrap().to_string();
+                let data_value_opts = self.field_matching.get_value(&data, field);
+                if let (Some(data_value), Some(object_id)) = (data_value_opts, object_id_opts) {
+                    let _span = tracing::info_span!(
+                        "fill_field",
+                        file_name = %self.file_name,
+                        field_name = partial_field_name,
+                    )
+                    .entered();
+                    let data_value = data_value
+                        .as_str()
+                        .ok_or_else(|| {
+                            Error::Other(format!(
+                                "Value for field `{}` is not a string.",
+                                partial_field_name
+                            ))
+                        })?
+                        .to_string();
+
+                    validate_field_value(
+                        &doc,
+                        doc.get_object(object_id)?.as_dict()?,
+                        partial_field_name,
+                        &data_value,
+                        self.validate_field_format,
+                        self.limits.max_depth,
+                    )?;
+                    filled_fields.push(JournalChange::FieldFilled {
+                        field_name: field
+                            .get_fully_qualified_field_name()
+                            .unwrap_or(partial_field_name)
+                            .to_owned(),
+                        value: data_value.clone(),
+                    });
 
-                    let field = doc
-                        .get_object_mut(object_id)
-                        .unwrap()
-                        .as_dict_mut()
-                        .unwrap();
+                    let field = doc.get_object_mut(object_id)?.as_dict_mut()?;
 
                     field.set("V", Object::string_literal(data_value.into_bytes()));
 
@@ -353,6 +1064,12 @@ impl PDFSigningDocument {
                                 as f32
                         })
                         .collect::<Vec<_>>();
+                    if rect.len() < 4 {
+                        return Err(Error::Other(format!(
+                            "Form field `{}` has an invalid `Rect`.",
+                            partial_field_name
+                        )));
+                    }
 
                     // Gets the object stream
                     let object_id = if field.has(b"AP") {
@@ -364,11 +1081,7 @@ impl PDFSigningDocument {
                             "stream".as_bytes().to_vec(),
                         )));
 
-                        let field = doc
-                            .get_object_mut(object_id)
-                            .unwrap()
-                            .as_dict_mut()
-                            .unwrap();
+                        let field = doc.get_object_mut(object_id)?.as_dict_mut()?;
 
                         field.set(
                             "AP",
@@ -479,14 +1192,10 @@ impl PDFSigningDocument {
             }
         }
 
-        // Regenerate the pdf file
-        let mut new_binary_pdf: Vec<u8> = Vec::new();
-        doc.compress();
-        doc.save_to(&mut new_binary_pdf)?;
-
-        self.copy_from(Self::read_from(&*new_binary_pdf, self.file_name.clone())?);
-        self.load_all()?;
-
+        self.reload_document(doc)?;
+        for change in filled_fields {
+            self.record_change(change);
+        }
         Ok(())
     }
 }
@@ -509,6 +1218,17 @@ impl InsertImageToPage for PDFSigningDocument {
             .add_xobject(page_id, xobject_name, xobject_id)?)
     }
 
+    fn add_graphics_state<N: Into<Vec<u8>>>(
+        &mut self,
+        page_id: ObjectId,
+        graphics_state_name: N,
+        graphics_state_id: ObjectId,
+    ) -> Result<(), Error> {
+        Ok(self
+            .raw_document
+            .add_graphics_state(page_id, graphics_state_name, graphics_state_id)?)
+    }
+
     fn opt_clone_object_to_new_document(&mut self, object_id: ObjectId) -> Result<(), Error> {
         Ok(self
             .raw_document
@@ -525,4 +1245,15 @@ impl InsertImageToPage for PDFSigningDocument {
             .new_document
             .add_to_page_content(page_id, content)?)
     }
+
+    fn tag_image_operations(
+        &mut self,
+        page_id: ObjectId,
+        operations: Vec<Operation>,
+        image_rect: &Rectangle,
+        alternate_text: Option<&str>,
+    ) -> Result<Vec<Operation>, Error> {
+        let tag = ContentTag::image(alternate_text, image_rect);
+        self.tag_page_content(page_id, operations, tag)
+    }
 }
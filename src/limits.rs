@@ -0,0 +1,278 @@
+//! Limits for untrusted documents, see `PDFSigningDocumentBuilder::limits`.
+
+use crate::{recovery, Error, PDFSigningDocument};
+use lopdf::{Object, Stream};
+use std::collections::BTreeMap;
+use std::io::Read;
+
+/// Limits for untrusted documents, so reading them and later work on them (like changing
+/// a stream that decompresses to gigabytes) does not use a lot of memory or time.
+/// The file is scanned for objects and streams before it is parsed, so object streams
+/// over the limits are not unpacked, and the parsed document is checked again (objects
+/// that the scan did not find, like in streams with unusual filters).
+/// The parser itself is not limited, limit the size of the file before it is read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParsingLimits {
+    /// The maximum number of objects in the document.
+    pub max_objects: Option<usize>,
+    /// The maximum size (in bytes) of every stream, before and after decoding.
+    /// The filters are decoded for this (up to the first image filter like `DCTDecode`),
+    /// with at most `max_stream_size` bytes in memory.
+    pub max_stream_size: Option<usize>,
+    /// The maximum depth of the trees that are walked: the form field hierarchy
+    /// (`Kids`), the page tree and the name and number trees. The default is 32.
+    pub max_depth: usize,
+}
+
+impl Default for ParsingLimits {
+    /// No limit on the objects and streams.
+    fn default() -> Self {
+        ParsingLimits {
+            max_objects: None,
+            max_stream_size: None,
+            max_depth: 32,
+        }
+    }
+}
+
+impl PDFSigningDocument {
+    /// Check the objects and streams of the document against the limits.
+    pub(crate) fn check_limits(&self) -> Result<(), Error> {
+        let limits = &self.limits;
+        let doc = self.raw_document.get_prev_documents();
+        if let Some(maximum) = limits.max_objects {
+            if doc.objects.len() > maximum {
+                return Err(Error::TooManyObjects {
+                    count: doc.objects.len(),
+                    maximum,
+                });
+            }
+        }
+        if let Some(maximum) = limits.max_stream_size {
+            for (object_id, object) in &doc.objects {
+                if let Object::Stream(stream) = object {
+                    if stream.content.len() > maximum
+                        || decompressed_size(stream, maximum) > maximum
+                    {
+                        return Err(Error::StreamTooLarge {
+                            object_id: *object_id,
+                            maximum,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ParsingLimits {
+    /// Check the `objects` found in the file (see `recovery::find_objects`) before it is
+    /// parsed: the objects and the objects in object streams are counted, and the streams
+    /// with a direct `Filter` are decoded to check their size.
+    pub(crate) fn check_file(
+        &self,
+        data: &[u8],
+        objects: &BTreeMap<u32, (usize, u16)>,
+    ) -> Result<(), Error> {
+        if self.max_objects.is_none() && self.max_stream_size.is_none() {
+            return Ok(());
+        }
+        let mut count = objects.len();
+        for (id, (offset, generation)) in objects {
+            let dict = recovery::get_dict(data, *offset);
+            let Some(content) = recovery::get_stream_content(data, *offset, dict) else {
+                continue;
+            };
+            if let Some(maximum) = self.max_stream_size {
+                if content.len() > maximum
+                    || decode_bounded(content, &recovery::get_filters(dict), maximum).len()
+                        > maximum
+                {
+                    return Err(Error::StreamTooLarge {
+                        object_id: (*id, *generation),
+                        maximum,
+                    });
+                }
+            }
+            if recovery::is_object_stream(dict) {
+                count = count.saturating_add(recovery::get_direct_number(dict, b"/N").unwrap_or(0));
+            }
+        }
+        if let Some(maximum) = self.max_objects {
+            if count > maximum {
+                return Err(Error::TooManyObjects { count, maximum });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Get the size of the decoded content of a stream, but stop decoding after `limit`.
+fn decompressed_size(stream: &Stream, limit: usize) -> usize {
+    let filters: Vec<&[u8]> = match stream.dict.get(b"Filter") {
+        Ok(Object::Name(filter)) => vec![filter],
+        Ok(Object::Array(filters)) => filters
+            .iter()
+            .filter_map(|filter| filter.as_name().ok())
+            .collect(),
+        _ => vec![],
+    };
+    decode_bounded(&stream.content, &filters, limit).len()
+}
+
+/// Decode `content` with the `filters`, every step stops after a bit more than `limit`
+/// bytes. Decoding stops at an image filter (like `DCTDecode`) or an unknown filter,
+/// and at invalid data: the data decoded up to there is returned.
+pub(crate) fn decode_bounded(content: &[u8], filters: &[&[u8]], limit: usize) -> Vec<u8> {
+    let limit = limit.saturating_add(1);
+    let mut data = content.to_vec();
+    for filter in filters {
+        data = match *filter {
+            b"FlateDecode" | b"Fl" => {
+                let mut decoded = vec![];
+                // The data decoded before an error is kept, like a truncated stream.
+                let _ = flate2::read::ZlibDecoder::new(&*data)
+                    .take(limit as u64)
+                    .read_to_end(&mut decoded);
+                decoded
+            }
+            b"ASCIIHexDecode" | b"AHx" => decode_ascii_hex(&data),
+            b"ASCII85Decode" | b"A85" => decode_ascii_85(&data, limit),
+            b"LZWDecode" | b"LZW" => decode_lzw(&data, limit),
+            b"RunLengthDecode" | b"RL" => decode_run_length(&data, limit),
+            _ => break,
+        };
+        if data.len() >= limit {
+            break;
+        }
+    }
+    data
+}
+
+fn decode_ascii_hex(data: &[u8]) -> Vec<u8> {
+    let digits: Vec<u8> = data
+        .iter()
+        .take_while(|byte| **byte != b'>')
+        .filter_map(|byte| (*byte as char).to_digit(16))
+        .map(|digit| digit as u8)
+        .collect();
+    // A missing last digit is 0.
+    digits
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0))
+        .collect()
+}
+
+fn decode_ascii_85(data: &[u8], limit: usize) -> Vec<u8> {
+    let mut decoded = vec![];
+    let mut group = vec![];
+    for byte in data {
+        if decoded.len() >= limit {
+            break;
+        }
+        match byte {
+            b'~' => break,
+            b'z' if group.is_empty() => decoded.extend_from_slice(&[0; 4]),
+            b'!'..=b'u' => {
+                group.push(byte - b'!');
+                if group.len() == 5 {
+                    decoded.extend_from_slice(&ascii_85_group(&group).to_be_bytes());
+                    group.clear();
+                }
+            }
+            _ => {}
+        }
+    }
+    // A last group of n characters is n - 1 bytes, padded with `u`.
+    if group.len() > 1 {
+        let length = group.len() - 1;
+        group.resize(5, b'u' - b'!');
+        decoded.extend_from_slice(&ascii_85_group(&group).to_be_bytes()[..length]);
+    }
+    decoded
+}
+
+fn ascii_85_group(group: &[u8]) -> u32 {
+    group.iter().fold(0u32, |value, digit| {
+        value.wrapping_mul(85).wrapping_add(u32::from(*digit))
+    })
+}
+
+/// Decode LZW data with the default `EarlyChange` of 1.
+fn decode_lzw(data: &[u8], limit: usize) -> Vec<u8> {
+    const CLEAR_TABLE: usize = 256;
+    const END_OF_DATA: usize = 257;
+    let initial_table = || (0..=255u8).map(|byte| vec![byte]).chain([vec![], vec![]]);
+    let mut table: Vec<Vec<u8>> = initial_table().collect();
+    let mut decoded = vec![];
+    let mut previous: Option<Vec<u8>> = None;
+    let mut code_length = 9;
+    let (mut buffer, mut buffer_length) = (0u32, 0);
+    let mut bytes = data.iter();
+    while decoded.len() < limit {
+        while buffer_length < code_length {
+            let Some(byte) = bytes.next() else {
+                return decoded;
+            };
+            buffer = buffer << 8 | u32::from(*byte);
+            buffer_length += 8;
+        }
+        let code = (buffer >> (buffer_length - code_length)) as usize & ((1 << code_length) - 1);
+        buffer_length -= code_length;
+        match code {
+            CLEAR_TABLE => {
+                table = initial_table().collect();
+                code_length = 9;
+                previous = None;
+                continue;
+            }
+            END_OF_DATA => break,
+            _ => {}
+        }
+        let entry = match (table.get(code), &previous) {
+            (Some(entry), _) => entry.clone(),
+            (None, Some(previous)) if code == table.len() => {
+                let mut entry = previous.clone();
+                entry.push(previous[0]);
+                entry
+            }
+            _ => break,
+        };
+        decoded.extend_from_slice(&entry);
+        if let Some(mut previous) = previous.take() {
+            if table.len() < 4096 {
+                previous.push(entry[0]);
+                table.push(previous);
+            }
+        }
+        if table.len() + 1 >= 1 << code_length && code_length < 12 {
+            code_length += 1;
+        }
+        previous = Some(entry);
+    }
+    decoded
+}
+
+fn decode_run_length(data: &[u8], limit: usize) -> Vec<u8> {
+    let mut decoded = vec![];
+    let mut position = 0;
+    while decoded.len() < limit {
+        match data.get(position) {
+            None | Some(128) => break,
+            Some(&length) if length < 128 => {
+                let end = (position + 2 + usize::from(length)).min(data.len());
+                decoded.extend_from_slice(&data[position + 1..end]);
+                position = end;
+            }
+            Some(&length) => {
+                let Some(byte) = data.get(position + 1) else {
+                    break;
+                };
+                decoded.resize(decoded.len() + 257 - usize::from(length), *byte);
+                position += 2;
+            }
+        }
+    }
+    decoded
+}
@@ -0,0 +1,636 @@
+//! Write linearized (fast web view) documents, so a viewer can show the first page
+//! before the whole file is downloaded.
+//!
+//! Layout of the file (see Annex F of the PDF specification):
+//! header, linearization dictionary, first page cross-reference table, catalog, hint stream,
+//! first page objects, objects of the other pages, shared objects, other objects and the
+//! main cross-reference table.
+//! The hint tables use fixed bit widths, so their size does not depend on the offsets.
+
+use crate::merge::flatten_pages;
+use crate::Error;
+use lopdf::{Dictionary, Document, Object, ObjectId, StringFormat};
+use std::collections::{HashMap, HashSet};
+
+/// Bit width of all the numbers in the hint tables.
+const HINT_BITS: u32 = 32;
+
+/// Rewrite `doc` as a linearized file.
+pub(crate) fn write_linearized(mut doc: Document) -> Result<Vec<u8>, Error> {
+    doc.prune_objects();
+    let page_ids = flatten_pages(&mut doc)?;
+    let catalog_id = doc.trailer.get(b"Root")?.as_reference()?;
+    if page_ids.is_empty() {
+        return Err(Error::from("Linearize: The document has no pages."));
+    }
+
+    // Sort the objects into the parts of the file.
+    let page_objects: Vec<Vec<ObjectId>> = page_ids
+        .iter()
+        .map(|page_id| collect_page_objects(&doc, *page_id))
+        .collect();
+    let first_page: Vec<ObjectId> = page_objects[0].clone();
+    let first_page_set: HashSet<ObjectId> = first_page.iter().copied().collect();
+    let mut usage: HashMap<ObjectId, usize> = HashMap::new();
+    for object_id in page_objects[1..].iter().flatten() {
+        *usage.entry(*object_id).or_default() += 1;
+    }
+    let other_pages: Vec<Vec<ObjectId>> = page_objects[1..]
+        .iter()
+        .map(|objects| {
+            objects
+                .iter()
+                .filter(|id| !first_page_set.contains(id) && usage.get(id) == Some(&1))
+                .copied()
+                .collect()
+        })
+        .collect();
+    let mut shared = vec![];
+    let mut shared_set = HashSet::new();
+    for object_id in page_objects[1..].iter().flatten() {
+        if !first_page_set.contains(object_id)
+            && usage.get(object_id) > Some(&1)
+            && shared_set.insert(*object_id)
+        {
+            shared.push(*object_id);
+        }
+    }
+    let mut assigned: HashSet<ObjectId> = first_page_set.clone();
+    assigned.extend(other_pages.iter().flatten());
+    assigned.extend(&shared);
+    assigned.insert(catalog_id);
+    let other: Vec<ObjectId> = doc
+        .objects
+        .keys()
+        .filter(|id| !assigned.contains(id))
+        .copied()
+        .collect();
+
+    // The objects after the first page get the low numbers (main cross-reference table),
+    // the objects of the first part the high numbers (first page cross-reference table).
+    let mut new_ids: HashMap<ObjectId, ObjectId> = HashMap::new();
+    for object_id in other_pages.iter().flatten().chain(&shared).chain(&other) {
+        new_ids.insert(*object_id, (new_ids.len() as u32 + 1, 0));
+    }
+    let main_count = new_ids.len() as u32;
+    let linearization_id = main_count + 1;
+    new_ids.insert(catalog_id, (main_count + 2, 0));
+    let hint_id = main_count + 3;
+    for (index, object_id) in first_page.iter().enumerate() {
+        new_ids.insert(*object_id, (main_count + 4 + index as u32, 0));
+    }
+    let size = main_count + 4 + first_page.len() as u32;
+    renumber(&mut doc, &new_ids);
+    let new_id = |object_id: &ObjectId| new_ids[object_id].0;
+
+    // Write the file with placeholders for the parts that depend on the offsets.
+    let mut writer = LinearizedWriter {
+        data: format!("%PDF-{}\n", doc.version).into_bytes(),
+        ranges: HashMap::new(),
+    };
+    // Binary characters, so the file is treated as binary.
+    writer.data.extend_from_slice(b"%\xE2\xE3\xCF\xD3\n");
+    let linearization_position = writer.data.len();
+    let linearization_length =
+        linearization_dictionary(linearization_id, &LinearizationValues::default()).len();
+    writer
+        .data
+        .resize(linearization_position + linearization_length, b' ');
+    let first_xref_position = writer.data.len();
+    let first_trailer = |prev: usize| -> Result<Vec<u8>, Error> {
+        let mut trailer =
+            format!("xref\n{} {}\n", linearization_id, size - linearization_id).into_bytes();
+        trailer.resize(
+            trailer.len() + 20 * (size - linearization_id) as usize,
+            b' ',
+        );
+        trailer.extend_from_slice(format!("trailer\n<</Size {}", size).as_bytes());
+        for key in [&b"Root"[..], b"Info", b"ID"] {
+            if let Ok(value) = doc.trailer.get(key) {
+                write_name(&mut trailer, key);
+                trailer.push(b' ');
+                write_object(&mut trailer, value)?;
+            }
+        }
+        trailer
+            .extend_from_slice(format!("/Prev {:010}>>\nstartxref\n0\n%%EOF\n", prev).as_bytes());
+        Ok(trailer)
+    };
+    writer.data.extend_from_slice(&first_trailer(0)?);
+    writer.write_object(&doc, (main_count + 2, 0))?;
+
+    let hint_position = writer.data.len();
+    let page_refs = get_shared_references(&page_objects, &first_page, &shared);
+    let hint_length = hint_stream(
+        hint_id,
+        &HintValues::placeholder(&page_refs, &first_page, &shared),
+    )
+    .len();
+    writer.data.resize(hint_position + hint_length, b' ');
+
+    let first_page_start = writer.data.len();
+    for object_id in &first_page {
+        writer.write_object(&doc, new_ids[object_id])?;
+    }
+    let first_page_end = writer.data.len();
+    let mut page_ranges = vec![(first_page_start, first_page_end)];
+    for objects in &other_pages {
+        let start = writer.data.len();
+        for object_id in objects {
+            writer.write_object(&doc, new_ids[object_id])?;
+        }
+        page_ranges.push((start, writer.data.len()));
+    }
+    for object_id in shared.iter().chain(&other) {
+        writer.write_object(&doc, new_ids[object_id])?;
+    }
+
+    // Main cross-reference table with the objects after the first page.
+    let main_xref_position = writer.data.len();
+    let main_xref_header = format!("xref\n0 {}", main_count + 1);
+    writer.data.extend_from_slice(main_xref_header.as_bytes());
+    writer.data.extend_from_slice(b"\n0000000000 65535 f \n");
+    for id in 1..=main_count {
+        let offset = writer.offset(id);
+        writer
+            .data
+            .extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    writer.data.extend_from_slice(
+        format!(
+            "trailer\n<</Size {}>>\nstartxref\n{}\n%%EOF\n",
+            main_count + 1,
+            first_xref_position
+        )
+        .as_bytes(),
+    );
+
+    // Fill in the placeholders.
+    let object_length = |object_id: &ObjectId| -> usize {
+        let (start, end) = writer.ranges[&new_id(object_id)];
+        end - start
+    };
+    let hint_values = HintValues {
+        page_object_counts: page_objects_counts(&first_page, &other_pages),
+        page_lengths: page_ranges.iter().map(|(start, end)| end - start).collect(),
+        first_page_offset: first_page_start,
+        page_refs,
+        first_page_lengths: first_page.iter().map(object_length).collect(),
+        shared_lengths: shared.iter().map(object_length).collect(),
+        first_shared: shared
+            .first()
+            .map(|object_id| (new_id(object_id), writer.offset(new_id(object_id))))
+            .unwrap_or((0, 0)),
+    };
+    let hint = hint_stream(hint_id, &hint_values);
+    if hint.len() != hint_length {
+        return Err(Error::from(
+            "Linearize: The size of the hint stream changed.",
+        ));
+    }
+    writer.data[hint_position..hint_position + hint_length].copy_from_slice(&hint);
+    writer
+        .ranges
+        .insert(hint_id, (hint_position, hint_position + hint_length));
+    writer.ranges.insert(
+        linearization_id,
+        (
+            linearization_position,
+            linearization_position + linearization_length,
+        ),
+    );
+
+    let mut first_xref = first_trailer(main_xref_position)?;
+    let mut entry_position =
+        format!("xref\n{} {}\n", linearization_id, size - linearization_id).len();
+    for id in linearization_id..size {
+        let offset = writer.offset(id);
+        first_xref[entry_position..entry_position + 20]
+            .copy_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        entry_position += 20;
+    }
+    writer.data[first_xref_position..first_xref_position + first_xref.len()]
+        .copy_from_slice(&first_xref);
+
+    let values = LinearizationValues {
+        file_length: writer.data.len(),
+        hint: (hint_position, hint_length),
+        first_page_id: new_id(&first_page[0]),
+        first_page_end,
+        page_count: page_ids.len(),
+        main_xref_first_entry: main_xref_position + main_xref_header.len(),
+    };
+    let linearization = linearization_dictionary(linearization_id, &values);
+    writer.data[linearization_position..linearization_position + linearization_length]
+        .copy_from_slice(&linearization);
+    Ok(writer.data)
+}
+
+/// Get the objects used by a page, the page itself first. Other pages, the page tree
+/// and the catalog are not followed (`Parent`, `P`, link destinations).
+fn collect_page_objects(doc: &Document, page_id: ObjectId) -> Vec<ObjectId> {
+    let mut object_ids = vec![page_id];
+    let mut visited = HashSet::from([page_id]);
+    let mut index = 0;
+    while index < object_ids.len() {
+        let mut references = vec![];
+        if let Some(object) = doc.objects.get(&object_ids[index]) {
+            collect_references(object, &mut references);
+        }
+        for object_id in references {
+            let is_page_tree = doc
+                .get_dictionary(object_id)
+                .map(|dict| {
+                    dict.type_is(b"Page") || dict.type_is(b"Pages") || dict.type_is(b"Catalog")
+                })
+                .unwrap_or(false);
+            if doc.objects.contains_key(&object_id) && !is_page_tree && visited.insert(object_id) {
+                object_ids.push(object_id);
+            }
+        }
+        index += 1;
+    }
+    object_ids
+}
+
+fn collect_references(object: &Object, references: &mut Vec<ObjectId>) {
+    let collect_dictionary = |dict: &Dictionary, references: &mut Vec<ObjectId>| {
+        for (key, value) in dict.iter() {
+            if key != b"Parent" && key != b"P" {
+                collect_references(value, references);
+            }
+        }
+    };
+    match object {
+        Object::Reference(object_id) => references.push(*object_id),
+        Object::Array(array) => {
+            for item in array {
+                collect_references(item, references);
+            }
+        }
+        Object::Dictionary(dict) => collect_dictionary(dict, references),
+        Object::Stream(stream) => collect_dictionary(&stream.dict, references),
+        _ => {}
+    }
+}
+
+/// Give all objects the new id and update the references.
+fn renumber(doc: &mut Document, new_ids: &HashMap<ObjectId, ObjectId>) {
+    fn renumber_references(object: &mut Object, new_ids: &HashMap<ObjectId, ObjectId>) {
+        match object {
+            Object::Reference(object_id) => {
+                // References to missing objects are `null`.
+                *object = match new_ids.get(object_id) {
+                    Some(new_id) => Object::Reference(*new_id),
+                    None => Object::Null,
+                };
+            }
+            Object::Array(array) => {
+                for item in array.iter_mut() {
+                    renumber_references(item, new_ids);
+                }
+            }
+            Object::Dictionary(dict) => {
+                for (_, value) in dict.iter_mut() {
+                    renumber_references(value, new_ids);
+                }
+            }
+            Object::Stream(stream) => {
+                for (_, value) in stream.dict.iter_mut() {
+                    renumber_references(value, new_ids);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let objects = std::mem::take(&mut doc.objects);
+    doc.objects = objects
+        .into_iter()
+        .filter_map(|(object_id, mut object)| {
+            renumber_references(&mut object, new_ids);
+            Some((*new_ids.get(&object_id)?, object))
+        })
+        .collect();
+    for (_, value) in doc.trailer.iter_mut() {
+        renumber_references(value, new_ids);
+    }
+}
+
+/// For every page, the index of the objects in the shared object hint table that the page
+/// uses: first the objects of the first page, then the shared objects.
+fn get_shared_references(
+    page_objects: &[Vec<ObjectId>],
+    first_page: &[ObjectId],
+    shared: &[ObjectId],
+) -> Vec<Vec<u32>> {
+    let index: HashMap<ObjectId, u32> = first_page
+        .iter()
+        .chain(shared)
+        .enumerate()
+        .map(|(index, object_id)| (*object_id, index as u32))
+        .collect();
+    let mut page_refs = vec![vec![]];
+    for objects in &page_objects[1..] {
+        page_refs.push(
+            objects
+                .iter()
+                .filter_map(|object_id| index.get(object_id).copied())
+                .collect(),
+        );
+    }
+    page_refs
+}
+
+fn page_objects_counts(first_page: &[ObjectId], other_pages: &[Vec<ObjectId>]) -> Vec<usize> {
+    std::iter::once(first_page.len())
+        .chain(other_pages.iter().map(Vec::len))
+        .collect()
+}
+
+struct LinearizedWriter {
+    data: Vec<u8>,
+    /// Start and end of the objects in `data`, by (new) id.
+    ranges: HashMap<u32, (usize, usize)>,
+}
+
+impl LinearizedWriter {
+    fn write_object(&mut self, doc: &Document, object_id: ObjectId) -> Result<(), Error> {
+        let start = self.data.len();
+        self.data
+            .extend_from_slice(format!("{} 0 obj\n", object_id.0).as_bytes());
+        write_object(&mut self.data, doc.get_object(object_id)?)?;
+        self.data.extend_from_slice(b"\nendobj\n");
+        self.ranges.insert(object_id.0, (start, self.data.len()));
+        Ok(())
+    }
+
+    fn offset(&self, id: u32) -> usize {
+        self.ranges.get(&id).map(|(start, _)| *start).unwrap_or(0)
+    }
+}
+
+#[derive(Default)]
+struct LinearizationValues {
+    file_length: usize,
+    /// Offset and length of the hint stream.
+    hint: (usize, usize),
+    first_page_id: u32,
+    first_page_end: usize,
+    page_count: usize,
+    /// Offset of the white-space before the first entry of the main cross-reference table.
+    main_xref_first_entry: usize,
+}
+
+/// The numbers are padded, so the dictionary has the same size for all values.
+fn linearization_dictionary(id: u32, values: &LinearizationValues) -> Vec<u8> {
+    format!(
+        "{} 0 obj\n<</Linearized 1/L {:010}/H [{:010} {:010}]/O {:010}/E {:010}/N {:010}/T {:010}>>\nendobj\n",
+        id,
+        values.file_length,
+        values.hint.0,
+        values.hint.1,
+        values.first_page_id,
+        values.first_page_end,
+        values.page_count,
+        values.main_xref_first_entry
+    )
+    .into_bytes()
+}
+
+struct HintValues {
+    page_object_counts: Vec<usize>,
+    page_lengths: Vec<usize>,
+    first_page_offset: usize,
+    page_refs: Vec<Vec<u32>>,
+    first_page_lengths: Vec<usize>,
+    shared_lengths: Vec<usize>,
+    /// Number and offset of the first object of the shared objects part.
+    first_shared: (u32, usize),
+}
+
+impl HintValues {
+    /// Values with the same number of items, so the hint stream has the final size.
+    fn placeholder(page_refs: &[Vec<u32>], first_page: &[ObjectId], shared: &[ObjectId]) -> Self {
+        HintValues {
+            page_object_counts: vec![0; page_refs.len()],
+            page_lengths: vec![0; page_refs.len()],
+            first_page_offset: 0,
+            page_refs: page_refs.to_vec(),
+            first_page_lengths: vec![0; first_page.len()],
+            shared_lengths: vec![0; shared.len()],
+            first_shared: (0, 0),
+        }
+    }
+}
+
+/// Create the hint stream with the page offset and shared object hint tables.
+fn hint_stream(id: u32, values: &HintValues) -> Vec<u8> {
+    let min = |items: &[usize]| items.iter().copied().min().unwrap_or(0);
+    let mut bits = BitWriter::default();
+
+    // Page offset hint table header.
+    let min_objects = min(&values.page_object_counts);
+    let min_length = min(&values.page_lengths);
+    bits.write(min_objects as u64, 32);
+    bits.write(values.first_page_offset as u64, 32);
+    bits.write(HINT_BITS as u64, 16);
+    bits.write(min_length as u64, 32);
+    bits.write(HINT_BITS as u64, 16);
+    // The content stream offset is not used, the content stream length is the page length.
+    bits.write(0, 32);
+    bits.write(0, 16);
+    bits.write(min_length as u64, 32);
+    bits.write(HINT_BITS as u64, 16);
+    bits.write(HINT_BITS as u64, 16);
+    bits.write(HINT_BITS as u64, 16);
+    // No fractional positions of shared objects.
+    bits.write(0, 16);
+    bits.write(1, 16);
+    // Page offset hint table entries, grouped by item.
+    for count in &values.page_object_counts {
+        bits.write((count - min_objects) as u64, HINT_BITS);
+    }
+    bits.flush();
+    for length in &values.page_lengths {
+        bits.write((length - min_length) as u64, HINT_BITS);
+    }
+    bits.flush();
+    for refs in &values.page_refs {
+        bits.write(refs.len() as u64, HINT_BITS);
+    }
+    bits.flush();
+    for index in values.page_refs.iter().flatten() {
+        bits.write(*index as u64, HINT_BITS);
+    }
+    bits.flush();
+    for length in &values.page_lengths {
+        bits.write((length - min_length) as u64, HINT_BITS);
+    }
+    bits.flush();
+    let shared_table_offset = bits.data.len();
+
+    // Shared object hint table header.
+    let group_lengths: Vec<usize> = values
+        .first_page_lengths
+        .iter()
+        .chain(&values.shared_lengths)
+        .copied()
+        .collect();
+    let min_group_length = min(&group_lengths);
+    bits.write(values.first_shared.0 as u64, 32);
+    bits.write(values.first_shared.1 as u64, 32);
+    bits.write(values.first_page_lengths.len() as u64, 32);
+    bits.write(group_lengths.len() as u64, 32);
+    // Every group is 1 object.
+    bits.write(0, 16);
+    bits.write(min_group_length as u64, 32);
+    bits.write(HINT_BITS as u64, 16);
+    // Shared object hint table entries, grouped by item.
+    for length in &group_lengths {
+        bits.write((length - min_group_length) as u64, HINT_BITS);
+    }
+    bits.flush();
+    // No signatures (MD5) of the groups.
+    for _ in &group_lengths {
+        bits.write(0, 1);
+    }
+    bits.flush();
+
+    let mut stream = format!(
+        "{} 0 obj\n<</S {:010}/Length {:010}>>\nstream\n",
+        id,
+        shared_table_offset,
+        bits.data.len()
+    )
+    .into_bytes();
+    stream.extend_from_slice(&bits.data);
+    stream.extend_from_slice(b"\nendstream\nendobj\n");
+    stream
+}
+
+#[derive(Default)]
+struct BitWriter {
+    data: Vec<u8>,
+    /// Number of bits used in the last byte.
+    used_bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, bits: u32) {
+        for bit in (0..bits).rev() {
+            if self.used_bits == 0 {
+                self.data.push(0);
+            }
+            if (value >> bit) & 1 == 1 {
+                if let Some(last) = self.data.last_mut() {
+                    *last |= 0x80 >> self.used_bits;
+                }
+            }
+            self.used_bits = (self.used_bits + 1) % 8;
+        }
+    }
+
+    /// Start the next item at a byte boundary.
+    fn flush(&mut self) {
+        self.used_bits = 0;
+    }
+}
+
+/// Write an object in PDF syntax, with the same separators as the writer of lopdf
+/// (the signature placeholder is found by its written form).
+/// Returns an error for reals that are not finite (`NaN` and infinity), they have no syntax.
+pub(crate) fn write_object(data: &mut Vec<u8>, object: &Object) -> Result<(), Error> {
+    match object {
+        Object::Null => data.extend_from_slice(b"null"),
+        Object::Boolean(value) => data.extend_from_slice(value.to_string().as_bytes()),
+        Object::Integer(value) => data.extend_from_slice(value.to_string().as_bytes()),
+        Object::Real(value) if !value.is_finite() => {
+            return Err(Error::Other(format!(
+                "Writing: The real number `{}` can not be written in a PDF file.",
+                value
+            )))
+        }
+        Object::Real(value) => data.extend_from_slice(value.to_string().as_bytes()),
+        Object::Name(name) => write_name(data, name),
+        Object::String(text, StringFormat::Literal) => {
+            data.push(b'(');
+            for byte in text {
+                match byte {
+                    b'(' | b')' | b'\\' => data.extend_from_slice(&[b'\\', *byte]),
+                    // A line break in a literal string is read as `\n`.
+                    b'\r' => data.extend_from_slice(b"\\r"),
+                    _ => data.push(*byte),
+                }
+            }
+            data.push(b')');
+        }
+        Object::String(text, StringFormat::Hexadecimal) => {
+            data.push(b'<');
+            for byte in text {
+                data.extend_from_slice(format!("{:02X}", byte).as_bytes());
+            }
+            data.push(b'>');
+        }
+        Object::Array(array) => {
+            data.push(b'[');
+            for (index, item) in array.iter().enumerate() {
+                if index > 0 && need_separator(item) {
+                    data.push(b' ');
+                }
+                write_object(data, item)?;
+            }
+            data.push(b']');
+        }
+        Object::Dictionary(dict) => write_dictionary(data, dict)?,
+        Object::Stream(stream) => {
+            let mut dict = stream.dict.clone();
+            dict.set("Length", stream.content.len() as i64);
+            write_dictionary(data, &dict)?;
+            data.extend_from_slice(b"\nstream\n");
+            data.extend_from_slice(&stream.content);
+            data.extend_from_slice(b"\nendstream");
+        }
+        Object::Reference((id, generation)) => {
+            data.extend_from_slice(format!("{} {} R", id, generation).as_bytes())
+        }
+    }
+    Ok(())
+}
+
+fn write_dictionary(data: &mut Vec<u8>, dict: &Dictionary) -> Result<(), Error> {
+    data.extend_from_slice(b"<<");
+    for (key, value) in dict.iter() {
+        write_name(data, key);
+        if need_separator(value) {
+            data.push(b' ');
+        }
+        write_object(data, value)?;
+    }
+    data.extend_from_slice(b">>");
+    Ok(())
+}
+
+/// Check if a space is needed before the object, other objects start with a delimiter.
+fn need_separator(object: &Object) -> bool {
+    matches!(
+        object,
+        Object::Null
+            | Object::Boolean(_)
+            | Object::Integer(_)
+            | Object::Real(_)
+            | Object::Reference(_)
+    )
+}
+
+fn write_name(data: &mut Vec<u8>, name: &[u8]) {
+    data.push(b'/');
+    for byte in name {
+        // White-space, delimiters and bytes outside `!` to `~` are written as `#xx`.
+        if b"()<>[]{}/%#".contains(byte) || !(33..=126).contains(byte) {
+            data.extend_from_slice(format!("#{:02X}", byte).as_bytes());
+        } else {
+            data.push(*byte);
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::Error;
+use chrono::{DateTime, Utc};
 use lopdf::Object;
 
 pub(crate) fn as_option_name(obj: Option<&Object>) -> Result<Option<String>, Error> {
@@ -44,6 +45,41 @@ pub(crate) fn as_option_text_string(obj: Option<&Object>) -> Result<Option<Strin
     Ok(text_string)
 }
 
+/// Decode a text string, encoded as UTF-16BE or UTF-8 with a byte order mark or else as
+/// PDFDocEncoding (the printable characters of PDFDocEncoding are mostly the same as Latin-1).
+pub(crate) fn decode_text_string(bytes: &[u8]) -> String {
+    if let Some(utf16_bytes) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = utf16_bytes
+            .chunks_exact(2)
+            .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else if let Some(utf8_bytes) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        String::from_utf8_lossy(utf8_bytes).into_owned()
+    } else {
+        bytes.iter().map(|byte| *byte as char).collect()
+    }
+}
+
+/// Create a date string object, like `D:20230102150405+00'00'`.
+pub(crate) fn date_string(date: DateTime<Utc>) -> Object {
+    Object::string_literal(date.format("D:%Y%m%d%H%M%S+00'00'").to_string())
+}
+
+/// Create a text string object.
+/// Text that is not ASCII is encoded as UTF-16BE with a byte order mark.
+pub(crate) fn text_string(text: &str) -> Object {
+    if text.is_ascii() {
+        Object::string_literal(text)
+    } else {
+        let mut bytes = vec![0xFE, 0xFF];
+        for unit in text.encode_utf16() {
+            bytes.extend_from_slice(&unit.to_be_bytes());
+        }
+        Object::String(bytes, lopdf::StringFormat::Hexadecimal)
+    }
+}
+
 pub(crate) fn as_array_or_byte_string(obj: Option<&Object>) -> Result<Vec<Vec<u8>>, Error> {
     let obj = obj.ok_or(Error::LoPdfError(lopdf::Error::DictKey))?;
     match obj {
@@ -77,7 +113,7 @@ pub(crate) fn as_byte_range(obj: Option<&Object>) -> Result<Vec<(u64, u64)>, Err
         }
     }
     if temp_item.is_some() {
-        log::warn!("Expected pairs, got an uneven length.");
+        tracing::warn!("Expected pairs, got an uneven length.");
     }
     Ok(result)
 }
@@ -0,0 +1,155 @@
+//! Renew the long term validation of signed documents with fresh validation data and
+//! a document time stamp (feature `network`), see `refresh_ltv`.
+
+use crate::acro_form::FormComponent;
+use crate::journal::JournalChange;
+use crate::time_stamp::request_time_stamp_token;
+use crate::{
+    Error, InsertImage, InsertImageToPage, LtvPolicy, OcspCache, PDFSigningDocument, SigningOptions,
+};
+use lopdf::{dictionary, Object, StringFormat};
+use std::sync::Arc;
+use std::time::Duration;
+
+impl PDFSigningDocument {
+    /// Renew the long term validation of a signed document, before the certificate of the
+    /// last time stamp expires. The OCSP responses of the certificate chains of all the
+    /// signatures and document time stamps are fetched into the `DSS`, and a document time
+    /// stamp that covers them is added (PAdES B-LTA), each in a new revision.
+    /// The time stamp URLs (at least 1 is needed), HTTP client, placeholder size and OCSP
+    /// cache (`LtvPolicy::FetchOcsp`) of the `options` are used.
+    /// Returns the document, this document is reloaded with the changes.
+    pub fn refresh_ltv(&mut self, options: &SigningOptions) -> Result<Vec<u8>, Error> {
+        options.check_offline()?;
+        if options.time_stamp_urls.is_empty() {
+            return Err(Error::from(
+                "LTV refresh: A time stamp URL is needed, see `time_stamp_url`.",
+            ));
+        }
+        self.load_all()?;
+        let forms = self.acro_form.clone().unwrap_or_default();
+        let signed_field_names: Vec<&str> = forms
+            .iter()
+            .filter(|form| {
+                matches!(
+                    form.get_form_component(),
+                    FormComponent::SignedSignature { .. }
+                )
+            })
+            .filter_map(|form| form.get_fully_qualified_field_name())
+            .collect();
+        if signed_field_names.is_empty() {
+            return Err(Error::from("LTV refresh: The document is not signed."));
+        }
+
+        let cache = match &options.ltv_policy {
+            LtvPolicy::FetchOcsp(cache) => cache.clone(),
+            _ => Arc::new(OcspCache::new(Duration::ZERO)),
+        };
+        self.add_ocsp_responses(&signed_field_names, &cache, &options.http_client)?;
+
+        // The first name that is not used, `DocTimeStamp1`, `DocTimeStamp2`, ...
+        let field_name = (1..)
+            .map(|index| format!("DocTimeStamp{}", index))
+            .find(|name| {
+                !forms
+                    .iter()
+                    .any(|form| form.get_fully_qualified_field_name() == Some(name.as_str()))
+            })
+            .unwrap_or_default();
+        self.add_document_time_stamp_field(&field_name, options.placeholder_size)?;
+
+        let mut data = Vec::new();
+        self.write_document(&mut data)?;
+        let (byte_range, data) = Self::set_next_byte_range(data, options.placeholder_size)?;
+        let token = request_time_stamp_token(
+            &options.time_stamp_urls,
+            options.time_stamp_timeout,
+            &options.http_client,
+            &Self::get_signed_bytes(&data, &byte_range)?,
+        )?;
+        let data = Self::set_content(data, token, options.placeholder_size)?;
+
+        self.copy_from(self.reload_from(data.clone(), self.file_name.clone())?);
+        self.load_all()?;
+        self.record_change(JournalChange::LtvRefreshed { field_name });
+        Ok(data)
+    }
+
+    /// Add an invisible signature field `field_name` on the first page, with a document
+    /// time stamp placeholder of `placeholder_size` bytes.
+    fn add_document_time_stamp_field(
+        &mut self,
+        field_name: &str,
+        placeholder_size: usize,
+    ) -> Result<(), Error> {
+        let page_id = *self
+            .get_page_ids()
+            .first()
+            .ok_or_else(|| Error::from("LTV refresh: The document has no pages."))?;
+        // The order of `ByteRange` and `Contents` is important, see `set_next_byte_range`.
+        let time_stamp_id = self.add_object(dictionary! {
+            "Type" => "DocTimeStamp",
+            "Filter" => "Adobe.PPKLite",
+            "SubFilter" => "ETSI.RFC3161",
+            "ByteRange" => vec![0.into(), 10000.into(), 20000.into(), 10000.into()],
+            "Contents" => Object::String(vec![0u8; placeholder_size], StringFormat::Hexadecimal),
+        });
+        let widget_id = self.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "FT" => "Sig",
+            "T" => Object::string_literal(field_name.as_bytes()),
+            "Rect" => vec![0.into(), 0.into(), 0.into(), 0.into()],
+            // Print and locked.
+            "F" => 132,
+            "P" => Object::Reference(page_id),
+            "V" => Object::Reference(time_stamp_id),
+        });
+        self.add_annotation_to_page(page_id, widget_id)?;
+
+        let catalog_id = self
+            .raw_document
+            .new_document
+            .trailer
+            .get(b"Root")?
+            .as_reference()?;
+        let acro_form = self
+            .get_latest_object(catalog_id)
+            .and_then(|catalog| catalog.as_dict().ok())
+            .and_then(|catalog| catalog.get(b"AcroForm").ok())
+            .cloned()
+            .ok_or_else(|| Error::from("LTV refresh: The document has no form."))?;
+        let mut fields = self
+            .resolve_latest_object(&acro_form)
+            .and_then(|acro_form| acro_form.as_dict().ok())
+            .and_then(|acro_form| acro_form.get(b"Fields").ok())
+            .and_then(|fields| self.resolve_latest_object(fields))
+            .and_then(|fields| fields.as_array().ok())
+            .cloned()
+            .unwrap_or_default();
+        fields.push(Object::Reference(widget_id));
+        let acro_form = match acro_form {
+            Object::Reference(acro_form_id) => {
+                self.opt_clone_object_to_new_document(acro_form_id)?;
+                self.raw_document
+                    .new_document
+                    .get_object_mut(acro_form_id)?
+                    .as_dict_mut()?
+            }
+            _ => {
+                self.opt_clone_object_to_new_document(catalog_id)?;
+                self.raw_document
+                    .new_document
+                    .get_object_mut(catalog_id)?
+                    .as_dict_mut()?
+                    .get_mut(b"AcroForm")?
+                    .as_dict_mut()?
+            }
+        };
+        acro_form.set("Fields", fields);
+        // Signatures exist and the document is changed by appending only.
+        acro_form.set("SigFlags", 3);
+        Ok(())
+    }
+}
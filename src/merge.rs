@@ -0,0 +1,289 @@
+//! Merge multiple documents into 1, for example to assemble a contract packet before signing.
+
+use crate::lopdf_utils::{decode_text_string, text_string};
+use crate::outline::{append_outline, get_outline_root_id};
+use crate::{Error, PDFSigningDocument};
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use std::collections::HashSet;
+
+/// Page attributes that can be inherited from the parent `Pages` nodes.
+const INHERITABLE_PAGE_KEYS: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+impl PDFSigningDocument {
+    /// Merge `documents` into a new document with the pages of all documents, in order.
+    ///
+    /// The form fields of all documents are kept. When a field name is already used by
+    /// a previous document, the field is renamed by adding `_2`, `_3`, ... to its name.
+    /// The bookmarks of all documents are kept, in order. The other catalog entries
+    /// (like named destinations) of the first document are kept, the ones of the other
+    /// documents are dropped.
+    /// The new document is not encrypted and existing signatures become invalid,
+    /// so merge before signing.
+    pub fn merge(documents: &[PDFSigningDocument], file_name: String) -> Result<Self, Error> {
+        let mut documents = documents
+            .iter()
+            .map(|document| document.get_latest_document());
+        let mut merged = documents
+            .next()
+            .ok_or_else(|| Error::from("Merge: No documents to merge."))?;
+        merged.trailer.remove(b"Encrypt");
+        let pages_id = merged.catalog()?.get(b"Pages")?.as_reference()?;
+        let mut page_ids = flatten_pages(&mut merged)?;
+
+        let mut fields = get_acro_form(&merged)
+            .and_then(|acro_form| get_fields(&merged, &acro_form))
+            .unwrap_or_default();
+        let mut field_names: HashSet<String> = fields
+            .iter()
+            .filter_map(|field| get_field_name(&merged, field))
+            .collect();
+        let mut acro_form = get_acro_form(&merged).unwrap_or_default();
+
+        for mut document in documents {
+            offset_object_ids(&mut document, merged.max_id);
+            page_ids.append(&mut flatten_pages(&mut document)?);
+            let other_outline_id = get_outline_root_id(&document);
+            let other_acro_form = get_acro_form(&document);
+            let other_fields = other_acro_form
+                .as_ref()
+                .and_then(|other_acro_form| get_fields(&document, other_acro_form))
+                .unwrap_or_default();
+            merged.objects.append(&mut document.objects);
+            merged.max_id = document.max_id;
+            if let Some(other_outline_id) = other_outline_id {
+                append_outline(&mut merged, other_outline_id)?;
+            }
+
+            for mut field in other_fields {
+                if let Some(name) = get_field_name(&merged, &field) {
+                    let mut new_name = name.clone();
+                    let mut index = 2;
+                    while field_names.contains(&new_name) {
+                        new_name = format!("{}_{}", name, index);
+                        index += 1;
+                    }
+                    if new_name != name {
+                        tracing::info!("Merge: Renamed field `{}` to `{}`.", name, new_name);
+                        let field_dict = match field {
+                            Object::Reference(field_id) => {
+                                merged.get_object_mut(field_id)?.as_dict_mut()?
+                            }
+                            _ => field.as_dict_mut()?,
+                        };
+                        field_dict.set("T", text_string(&new_name));
+                    }
+                    field_names.insert(new_name);
+                }
+                fields.push(field);
+            }
+            if let Some(other_acro_form) = other_acro_form {
+                merge_acro_form(&merged, &mut acro_form, &other_acro_form);
+            }
+        }
+
+        // Link all the pages directly to the `Pages` root of the first document.
+        for page_id in &page_ids {
+            merged
+                .get_object_mut(*page_id)?
+                .as_dict_mut()?
+                .set("Parent", Object::Reference(pages_id));
+        }
+        let pages = merged.get_object_mut(pages_id)?.as_dict_mut()?;
+        pages.set("Count", page_ids.len() as i64);
+        pages.set(
+            "Kids",
+            page_ids
+                .into_iter()
+                .map(Object::Reference)
+                .collect::<Vec<_>>(),
+        );
+
+        if !fields.is_empty() {
+            acro_form.set("Fields", fields);
+            let acro_form_id = merged.add_object(acro_form);
+            merged
+                .catalog_mut()?
+                .set("AcroForm", Object::Reference(acro_form_id));
+        }
+
+        merged.prune_objects();
+        merged.compress();
+        let mut merged_binary_pdf = Vec::new();
+        merged.save_to(&mut merged_binary_pdf)?;
+        Self::read_from(merged_binary_pdf.as_slice(), file_name)
+    }
+}
+
+/// Get the field name before it was renamed by `merge`, so encoded signature field names
+/// can still be decoded. The suffix (`_2`) is not part of the base64 alphabet.
+#[cfg(feature = "signing")]
+pub(crate) fn get_original_field_name(name: &str) -> &str {
+    match name.rsplit_once('_') {
+        Some((original_name, index))
+            if !index.is_empty() && index.bytes().all(|byte| byte.is_ascii_digit()) =>
+        {
+            original_name
+        }
+        _ => name,
+    }
+}
+
+/// Get the pages in order, with the inherited attributes copied to the pages themselves,
+/// so the pages can be moved to another `Pages` node.
+pub(crate) fn flatten_pages(doc: &mut Document) -> Result<Vec<ObjectId>, Error> {
+    let page_ids: Vec<ObjectId> = doc.get_pages().into_values().collect();
+    for page_id in &page_ids {
+        for key in INHERITABLE_PAGE_KEYS {
+            if doc.get_dictionary(*page_id)?.has(key) {
+                continue;
+            }
+            if let Some(value) = get_inherited_value(doc, *page_id, key) {
+                doc.get_object_mut(*page_id)?.as_dict_mut()?.set(key, value);
+            }
+        }
+    }
+    Ok(page_ids)
+}
+
+/// Get an attribute of the page from the closest `Pages` node that has it.
+fn get_inherited_value(doc: &Document, page_id: ObjectId, key: &[u8]) -> Option<Object> {
+    let mut node = doc.get_dictionary(page_id).ok()?;
+    // Limit the depth, so a loop in the `Parent` entries can not hang.
+    for _ in 0..doc.objects.len() {
+        if let Ok(value) = node.get(key) {
+            return Some(value.clone());
+        }
+        let parent_id = node.get(b"Parent").ok()?.as_reference().ok()?;
+        node = doc.get_dictionary(parent_id).ok()?;
+    }
+    None
+}
+
+/// Add `offset` to all the object ids, so the objects do not collide with another document.
+fn offset_object_ids(doc: &mut Document, offset: u32) {
+    let objects = std::mem::take(&mut doc.objects);
+    doc.objects = objects
+        .into_iter()
+        .map(|((id, generation), mut object)| {
+            offset_references(&mut object, offset);
+            ((id + offset, generation), object)
+        })
+        .collect();
+    for (_, value) in doc.trailer.iter_mut() {
+        offset_references(value, offset);
+    }
+    doc.max_id += offset;
+}
+
+fn offset_references(object: &mut Object, offset: u32) {
+    match object {
+        Object::Reference((id, _)) => *id += offset,
+        Object::Array(array) => {
+            for item in array.iter_mut() {
+                offset_references(item, offset);
+            }
+        }
+        Object::Dictionary(dict) => {
+            for (_, value) in dict.iter_mut() {
+                offset_references(value, offset);
+            }
+        }
+        Object::Stream(stream) => {
+            for (_, value) in stream.dict.iter_mut() {
+                offset_references(value, offset);
+            }
+        }
+        _ => {}
+    }
+}
+
+pub(crate) fn get_acro_form(doc: &Document) -> Option<Dictionary> {
+    let acro_form = doc.catalog().ok()?.get(b"AcroForm").ok()?;
+    let acro_form = match acro_form {
+        Object::Reference(acro_form_id) => doc.get_object(*acro_form_id).ok()?,
+        _ => acro_form,
+    };
+    acro_form.as_dict().ok().cloned()
+}
+
+/// Get the top level fields (`Fields`) of the AcroForm.
+pub(crate) fn get_fields(doc: &Document, acro_form: &Dictionary) -> Option<Vec<Object>> {
+    let fields = acro_form.get(b"Fields").ok()?;
+    let fields = match fields {
+        Object::Reference(fields_id) => doc.get_object(*fields_id).ok()?,
+        _ => fields,
+    };
+    fields.as_array().ok().cloned()
+}
+
+fn get_field_name(doc: &Document, field: &Object) -> Option<String> {
+    let field = match field {
+        Object::Reference(field_id) => doc.get_object(*field_id).ok()?,
+        _ => field,
+    };
+    let name = field.as_dict().ok()?.get(b"T").ok()?.as_str().ok()?;
+    Some(decode_text_string(name))
+}
+
+/// Merge the default resources (`DR`) and flags of `other` into `acro_form`.
+/// Resources that are already in `acro_form` are kept.
+fn merge_acro_form(doc: &Document, acro_form: &mut Dictionary, other: &Dictionary) {
+    let resolve = |object: &Object| -> Option<Dictionary> {
+        let object = match object {
+            Object::Reference(object_id) => doc.get_object(*object_id).ok()?,
+            _ => object,
+        };
+        object.as_dict().ok().cloned()
+    };
+
+    if let Some(other_resources) = other.get(b"DR").ok().and_then(resolve) {
+        let mut resources = acro_form
+            .get(b"DR")
+            .ok()
+            .and_then(resolve)
+            .unwrap_or_default();
+        for (category, other_entries) in other_resources.iter() {
+            let other_entries = match resolve(other_entries) {
+                Some(other_entries) => other_entries,
+                None => continue,
+            };
+            let mut entries = resources
+                .get(category)
+                .ok()
+                .and_then(resolve)
+                .unwrap_or_default();
+            for (name, value) in other_entries.iter() {
+                if !entries.has(name) {
+                    entries.set(name.clone(), value.clone());
+                }
+            }
+            resources.set(category.clone(), entries);
+        }
+        acro_form.set("DR", resources);
+    }
+
+    if !acro_form.has(b"DA") {
+        if let Ok(default_appearance) = other.get(b"DA") {
+            acro_form.set("DA", default_appearance.clone());
+        }
+    }
+    let sig_flags = |acro_form: &Dictionary| {
+        acro_form
+            .get(b"SigFlags")
+            .and_then(Object::as_i64)
+            .unwrap_or(0)
+    };
+    let merged_sig_flags = sig_flags(acro_form) | sig_flags(other);
+    if merged_sig_flags != 0 {
+        acro_form.set("SigFlags", merged_sig_flags);
+    }
+    let need_appearances = |acro_form: &Dictionary| {
+        acro_form
+            .get(b"NeedAppearances")
+            .and_then(Object::as_bool)
+            .unwrap_or(false)
+    };
+    if need_appearances(other) && !need_appearances(acro_form) {
+        acro_form.set("NeedAppearances", true);
+    }
+}
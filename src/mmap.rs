@@ -0,0 +1,66 @@
+//! Read large documents from a memory-mapped file (feature `mmap`), see `read_mmap`.
+
+use crate::{encryption, recovery, Error, PDFSigningDocument, ParsingLimits};
+use lopdf::IncrementalDocument;
+use memmap2::Mmap;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+impl PDFSigningDocument {
+    /// Read a document like `read`, but map the file in memory instead of copying it to the
+    /// heap. The parsed objects are still in memory, the bytes of the file are only read
+    /// when they are needed (like when the document is written or signatures are checked).
+    /// Use `get_prev_documents_bytes` of this document to get the bytes, the
+    /// `IncrementalDocument` of `get_incr_document_ref` has no bytes.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be changed or truncated while the document (or a clone of it)
+    /// is used, other processes included. See `memmap2::Mmap::map`.
+    pub unsafe fn read_mmap<P: AsRef<Path>>(path: P, file_name: String) -> Result<Self, Error> {
+        Self::read_mmap_with_password(path, file_name, "")
+    }
+
+    /// Read a document like `read_with_password`, see `read_mmap`.
+    /// Encrypted documents are copied to the heap, they are decrypted when they are read.
+    ///
+    /// # Safety
+    ///
+    /// See `read_mmap`.
+    pub unsafe fn read_mmap_with_password<P: AsRef<Path>>(
+        path: P,
+        file_name: String,
+        password: &str,
+    ) -> Result<Self, Error> {
+        Self::load_mmap(path, file_name, password, &ParsingLimits::default())
+    }
+
+    /// Map the file and load it like `load`, see `read_mmap`.
+    ///
+    /// # Safety
+    ///
+    /// See `read_mmap`.
+    pub(crate) unsafe fn load_mmap<P: AsRef<Path>>(
+        path: P,
+        file_name: String,
+        password: &str,
+        limits: &ParsingLimits,
+    ) -> Result<Self, Error> {
+        let _span = tracing::info_span!("read_mmap", file_name = %file_name).entered();
+        let mapped_file = Arc::new(Mmap::map(&File::open(path)?)?);
+        let objects = recovery::find_objects(&mapped_file);
+        limits.check_file(&mapped_file, &objects)?;
+        let encrypted = recovery::has_encrypt(&mapped_file, &objects);
+        let (doc, security_handler) =
+            encryption::parse_document(&mapped_file, password, encrypted)?;
+        if security_handler.is_some() {
+            let raw_doc = IncrementalDocument::create_from(mapped_file.to_vec(), doc);
+            return Self::from_parsed(raw_doc, security_handler, file_name, limits);
+        }
+        let raw_doc = IncrementalDocument::create_from(vec![], doc);
+        let mut document = Self::from_parsed(raw_doc, None, file_name, limits)?;
+        document.mapped_file = Some(mapped_file);
+        Ok(document)
+    }
+}
@@ -0,0 +1,306 @@
+//! Pack objects into compressed object streams (PDF 1.5), so documents with many small
+//! objects (like forms and annotations) are smaller.
+//!
+//! The writer of lopdf can not write object streams and needs its own copy of the
+//! previous revisions, so new revisions are written here.
+
+use crate::linearize::write_object;
+use crate::xref_format::XrefFormat;
+use crate::{Error, PDFSigningDocument};
+use lopdf::xref::XrefType;
+use lopdf::{dictionary, Document, Object, ObjectId, Stream};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The object stream and the index in it, by the id of the packed object.
+pub(crate) type PackedObjects = BTreeMap<u32, (u32, u16)>;
+
+/// The cross-reference entries by object id: the type, field 2 and field 3 (as in a
+/// cross-reference stream). Field 2 is the byte offset for objects that are not packed.
+type XrefEntries = BTreeMap<u32, (u8, u64, u16)>;
+
+/// Settings for packing objects into object streams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObjectStreamOptions {
+    /// Maximum number of objects in 1 object stream.
+    pub max_objects: usize,
+    /// Maximum size (in bytes) of the objects in 1 object stream, before compression.
+    /// Objects that are larger are written as normal objects.
+    pub max_length: usize,
+}
+
+impl Default for ObjectStreamOptions {
+    fn default() -> Self {
+        ObjectStreamOptions {
+            max_objects: 100,
+            max_length: 64 * 1024,
+        }
+    }
+}
+
+impl PDFSigningDocument {
+    /// Pack the objects of `doc` into object streams when this is enabled,
+    /// see `set_object_streams`.
+    pub(crate) fn pack_object_streams(&self, doc: &mut Document) -> Option<PackedObjects> {
+        let options = self.object_streams?;
+        if self.xref_format == XrefFormat::Table {
+            tracing::warn!("Object streams: Not used, they need a cross-reference stream.");
+            return None;
+        }
+        Some(pack_objects(doc, &options))
+    }
+}
+
+/// Replace the objects that can be packed by object streams.
+fn pack_objects(doc: &mut Document, options: &ObjectStreamOptions) -> PackedObjects {
+    // The object streams and cross-reference streams that were read are replaced,
+    // their objects are already loaded. Like lopdf, do not keep the linearization.
+    doc.objects.retain(|_, object| {
+        object
+            .type_name()
+            .map(|name| !["ObjStm", "XRef", "Linearized"].contains(&name))
+            .unwrap_or(true)
+    });
+
+    let encrypt_id = doc
+        .trailer
+        .get(b"Encrypt")
+        .and_then(Object::as_reference)
+        .ok();
+    let mut groups: Vec<Vec<(u32, Vec<u8>)>> = vec![];
+    let mut group: Vec<(u32, Vec<u8>)> = vec![];
+    let mut group_length = 0;
+    for (object_id, object) in doc.objects.iter() {
+        if !can_pack(*object_id, object, encrypt_id) {
+            continue;
+        }
+        let mut data = vec![];
+        // Objects that can not be written are left out, `write_revision` returns the error.
+        if write_object(&mut data, object).is_err() || data.len() > options.max_length {
+            continue;
+        }
+        if !group.is_empty()
+            && (group.len() >= options.max_objects
+                || group_length + data.len() > options.max_length)
+        {
+            groups.push(std::mem::take(&mut group));
+            group_length = 0;
+        }
+        group_length += data.len();
+        group.push((object_id.0, data));
+    }
+    if !group.is_empty() {
+        groups.push(group);
+    }
+
+    let mut packed_objects = PackedObjects::new();
+    for group in groups {
+        for (id, _) in group.iter() {
+            doc.objects.remove(&(*id, 0));
+        }
+        let stream_id = doc.add_object(object_stream(&group));
+        for (index, (id, _)) in group.iter().enumerate() {
+            packed_objects.insert(*id, (stream_id.0, index as u16));
+        }
+    }
+    tracing::info!("Object streams: Packed {} objects.", packed_objects.len());
+    packed_objects
+}
+
+/// Check if the object is allowed in an object stream.
+fn can_pack(object_id: ObjectId, object: &Object, encrypt_id: Option<ObjectId>) -> bool {
+    match object {
+        Object::Stream(_) => false,
+        // The signature value is written in the file after saving, at its byte range.
+        Object::Dictionary(dict) if dict.has(b"ByteRange") => false,
+        _ => object_id.1 == 0 && Some(object_id) != encrypt_id,
+    }
+}
+
+/// Create an object stream with the written objects.
+fn object_stream(objects: &[(u32, Vec<u8>)]) -> Stream {
+    let mut header = String::new();
+    let mut content = vec![];
+    for (id, data) in objects {
+        header.push_str(&format!("{} {} ", id, content.len()));
+        content.extend_from_slice(data);
+        content.push(b'\n');
+    }
+    let dict = dictionary! {
+        "Type" => "ObjStm",
+        "N" => objects.len() as i64,
+        "First" => header.len() as i64,
+    };
+    let mut data = header.into_bytes();
+    data.append(&mut content);
+    Stream::new(dict, data)
+}
+
+/// Write `doc` as new revision after `prev_bytes`, with a cross-reference table or stream
+/// (`xref_type`). The `packed_objects` need a cross-reference stream, it is always used
+/// when there are packed objects. Without `prev_bytes` the document is written in full.
+pub(crate) fn write_revision<W: std::io::Write>(
+    prev_bytes: &[u8],
+    doc: &Document,
+    packed_objects: &PackedObjects,
+    xref_type: XrefType,
+    target: &mut W,
+) -> Result<(), Error> {
+    // The previous revisions are written as they are, only the new revision is buffered.
+    target.write_all(prev_bytes)?;
+    let offset = prev_bytes.len();
+    let mut data = vec![];
+    match prev_bytes.last() {
+        None => data.extend_from_slice(format!("%PDF-{}\n", doc.version).as_bytes()),
+        Some(b'\n') => {}
+        Some(_) => data.push(b'\n'),
+    }
+
+    let mut entries = XrefEntries::new();
+    if prev_bytes.is_empty() {
+        entries.insert(0, (0, 0, 65535));
+    }
+    let stream_ids: BTreeSet<u32> = packed_objects
+        .values()
+        .map(|(stream_id, _)| *stream_id)
+        .collect();
+    for (&(id, generation), object) in doc.objects.iter() {
+        // Like lopdf, the object streams and cross-reference streams that were read are
+        // not written, their objects are already loaded. The new object streams are.
+        let is_skipped = !stream_ids.contains(&id)
+            && object
+                .type_name()
+                .map(|name| ["ObjStm", "XRef", "Linearized"].contains(&name))
+                .unwrap_or(false);
+        if is_skipped {
+            continue;
+        }
+        entries.insert(id, (1, (offset + data.len()) as u64, generation));
+        data.extend_from_slice(format!("{} {} obj\n", id, generation).as_bytes());
+        write_object(&mut data, object)?;
+        data.extend_from_slice(b"\nendobj\n");
+    }
+    for (id, (stream_id, index)) in packed_objects.iter() {
+        entries.insert(*id, (2, u64::from(*stream_id), *index));
+    }
+
+    let last_id = entries.keys().last().copied().unwrap_or(0);
+    let size = doc.max_id.max(last_id) + 1;
+    let xref_start = offset + data.len();
+    match xref_type {
+        XrefType::CrossReferenceTable if packed_objects.is_empty() => {
+            write_xref_table(&mut data, doc, &entries, size)?
+        }
+        _ => {
+            entries.insert(size, (1, xref_start as u64, 0));
+            write_xref_stream(&mut data, doc, &entries, size)?
+        }
+    }
+    data.extend_from_slice(format!("startxref\n{}\n%%EOF\n", xref_start).as_bytes());
+
+    target.write_all(&data)?;
+    Ok(())
+}
+
+/// Get the subsections of consecutive object ids, as first id and number of entries.
+fn get_subsections(entries: &XrefEntries) -> Vec<(u32, u32)> {
+    let mut subsections: Vec<(u32, u32)> = vec![];
+    for id in entries.keys() {
+        match subsections.last_mut() {
+            Some((start, count)) if *start + *count == *id => *count += 1,
+            _ => subsections.push((*id, 1)),
+        }
+    }
+    subsections
+}
+
+/// Write a cross-reference table (`xref`) and the trailer, `size` is the highest id + 1.
+/// Fails when an offset does not fit in the 10 digits of an entry.
+fn write_xref_table(
+    data: &mut Vec<u8>,
+    doc: &Document,
+    entries: &XrefEntries,
+    size: u32,
+) -> Result<(), Error> {
+    if entries
+        .values()
+        .any(|(_, field2, _)| *field2 > 9_999_999_999)
+    {
+        return Err(Error::from(
+            "Cross-reference table: The document is too large, use a cross-reference stream.",
+        ));
+    }
+    data.extend_from_slice(b"xref\n");
+    for (start, count) in get_subsections(entries) {
+        data.extend_from_slice(format!("{} {}\n", start, count).as_bytes());
+        for (kind, field2, field3) in entries.range(start..start + count).map(|(_, entry)| entry) {
+            let kind = if *kind == 0 { 'f' } else { 'n' };
+            // Every entry is 20 bytes, including the end of line.
+            data.extend_from_slice(format!("{:010} {:05} {} \n", field2, field3, kind).as_bytes());
+        }
+    }
+
+    // The trailer of a cross-reference stream is the stream dictionary.
+    let mut dict = doc.trailer.clone();
+    for key in [
+        &b"Type"[..],
+        b"W",
+        b"Index",
+        b"Length",
+        b"Filter",
+        b"DecodeParms",
+        b"XRefStm",
+    ] {
+        dict.remove(key);
+    }
+    dict.set("Size", size as i64);
+    data.extend_from_slice(b"trailer\n");
+    write_object(data, &Object::Dictionary(dict))?;
+    data.push(b'\n');
+    Ok(())
+}
+
+/// Write a cross-reference stream with id `xref_id`, it is in the `entries`.
+fn write_xref_stream(
+    data: &mut Vec<u8>,
+    doc: &Document,
+    entries: &XrefEntries,
+    xref_id: u32,
+) -> Result<(), Error> {
+    let mut index = vec![];
+    for (start, count) in get_subsections(entries) {
+        index.extend([Object::from(start as i64), Object::from(count as i64)]);
+    }
+    // Field 2 is as wide as the largest offset needs.
+    let largest_field2 = entries.values().map(|(_, field2, _)| *field2).max();
+    let field2_width = (u64::BITS - largest_field2.unwrap_or(0).leading_zeros())
+        .div_ceil(8)
+        .max(1) as usize;
+    let mut content = vec![];
+    for (kind, field2, field3) in entries.values() {
+        content.push(*kind);
+        content.extend_from_slice(&field2.to_be_bytes()[8 - field2_width..]);
+        content.extend_from_slice(&field3.to_be_bytes());
+    }
+
+    let mut dict = doc.trailer.clone();
+    for key in [&b"Filter"[..], b"DecodeParms", b"XRefStm"] {
+        dict.remove(key);
+    }
+    dict.set("Type", "XRef");
+    dict.set("Size", xref_id as i64 + 1);
+    dict.set(
+        "W",
+        vec![
+            Object::from(1),
+            Object::from(field2_width as i64),
+            Object::from(2),
+        ],
+    );
+    dict.set("Index", index);
+    let mut xref_stream = Stream::new(dict, content);
+    xref_stream.compress()?;
+    data.extend_from_slice(format!("{} 0 obj\n", xref_id).as_bytes());
+    write_object(data, &Object::Stream(xref_stream))?;
+    data.extend_from_slice(b"\nendobj\n");
+    Ok(())
+}
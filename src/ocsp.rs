@@ -0,0 +1,365 @@
+//! Fetch OCSP responses after signing for long term validation (feature `network`),
+//! see `LtvPolicy::FetchOcsp`.
+
+use crate::acro_form::FormComponent;
+use crate::certificate_details::{parse_time, read_tbs_certificate, TbsCertificate};
+use crate::der::{read_der, write_der};
+use crate::{Error, HttpClient, PDFSigningDocument, ValidationData};
+use chrono::{DateTime, Utc};
+use cryptographic_message_syntax::SignedData;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The DER encoded object identifiers used in certificates and OCSP requests.
+const OID_AUTHORITY_INFO_ACCESS: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x01, 0x01];
+const OID_OCSP: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01];
+const OID_OCSP_BASIC: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x01];
+const OID_SHA1: &[u8] = &[0x2b, 0x0e, 0x03, 0x02, 0x1a];
+
+/// The maximum time of a request to a responder.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// OCSP responses by issuer and serial number of the certificate. Share it (in an `Arc`)
+/// between the documents of a batch, so the responder is asked once for every certificate.
+/// Responses must be for the requested certificate and not be expired (`nextUpdate`), but
+/// their signature is not checked.
+pub struct OcspCache {
+    max_age: Duration,
+    responses: Mutex<HashMap<(Vec<u8>, Vec<u8>), CachedResponse>>,
+}
+
+struct CachedResponse {
+    this_update: DateTime<Utc>,
+    next_update: Option<DateTime<Utc>>,
+    data: Vec<u8>,
+}
+
+impl CachedResponse {
+    /// The response was produced at most `max_age` ago and is not expired.
+    fn is_fresh(&self, max_age: Duration, now: DateTime<Utc>) -> bool {
+        // A `thisUpdate` in the future has age 0.
+        let age = (now - self.this_update).to_std().unwrap_or_default();
+        age <= max_age
+            && self
+                .next_update
+                .map_or(true, |next_update| now < next_update)
+    }
+}
+
+impl OcspCache {
+    /// A cache that fetches a response again when its status is older than `max_age`
+    /// (measured from `thisUpdate` of the response) or when its `nextUpdate` has passed.
+    pub fn new(max_age: Duration) -> Self {
+        OcspCache {
+            max_age,
+            responses: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the number of cached responses.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove all the responses.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<(Vec<u8>, Vec<u8>), CachedResponse>> {
+        // The map stays valid when a thread panics while it is locked.
+        self.responses
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Get the OCSP response of the DER encoded `certificate`, from the cache or from the
+    /// responder in the certificate. `None` when the certificate has no responder.
+    fn get_response(
+        &self,
+        certificate: &[u8],
+        issuer: &[u8],
+        client: &HttpClient,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let invalid_certificate = || Error::from("OCSP: Invalid certificate.");
+        let certificate = read_tbs_certificate(certificate).ok_or_else(invalid_certificate)?;
+        let key = (
+            certificate.issuer.to_vec(),
+            certificate.serial_number.to_vec(),
+        );
+        if let Some(cached) = self.lock().get(&key) {
+            if cached.is_fresh(self.max_age, Utc::now()) {
+                return Ok(Some(cached.data.clone()));
+            }
+        }
+        let Some(url) = get_ocsp_url(&certificate) else {
+            return Ok(None);
+        };
+        let issuer = read_tbs_certificate(issuer).ok_or_else(invalid_certificate)?;
+
+        tracing::debug!("Fetch OCSP response from `{}`.", url);
+        let cert_id = create_cert_id(&certificate, &issuer);
+        let request = create_request(&cert_id);
+        let response = client.post(&url, "application/ocsp-request", &request, TIMEOUT)?;
+        // `OCSPResponse`: the status, 0 is successful, and the response.
+        let status = match read_der(&response) {
+            Some((0x30, content, _)) => read_der(content),
+            _ => None,
+        };
+        match status {
+            Some((0x0a, [0], _)) => {}
+            Some((0x0a, status, _)) => {
+                return Err(Error::Other(format!(
+                    "OCSP `{}`: The response has status {:?}.",
+                    url, status
+                )))
+            }
+            _ => return Err(Error::Other(format!("OCSP `{}`: Invalid response.", url))),
+        }
+        let Some((this_update, next_update)) = find_single_response(&response, &cert_id) else {
+            return Err(Error::Other(format!(
+                "OCSP `{}`: The response is not for the requested certificate.",
+                url
+            )));
+        };
+        if next_update.is_some_and(|next_update| next_update <= Utc::now()) {
+            return Err(Error::Other(format!(
+                "OCSP `{}`: The response is expired.",
+                url
+            )));
+        }
+        self.lock().insert(
+            key,
+            CachedResponse {
+                this_update,
+                next_update,
+                data: response.clone(),
+            },
+        );
+        Ok(Some(response))
+    }
+}
+
+impl fmt::Debug for OcspCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OcspCache")
+            .field("max_age", &self.max_age)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// Caches are equal when they are the same cache.
+impl PartialEq for OcspCache {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for OcspCache {}
+
+/// Get the URL of the OCSP responder in the authority information access extension.
+fn get_ocsp_url(certificate: &TbsCertificate) -> Option<String> {
+    let (_, value) = certificate
+        .extensions
+        .iter()
+        .find(|(oid, _)| *oid == OID_AUTHORITY_INFO_ACCESS)?;
+    let (0x30, mut descriptions, _) = read_der(value)? else {
+        return None;
+    };
+    while let Some((0x30, description, next)) = read_der(descriptions) {
+        descriptions = next;
+        if let Some((0x06, OID_OCSP, location)) = read_der(description) {
+            // `uniformResourceIdentifier`
+            if let Some((0x86, url, _)) = read_der(location) {
+                return String::from_utf8(url.to_vec()).ok();
+            }
+        }
+    }
+    None
+}
+
+/// Create the content of the `CertID` of a certificate, the certificate is identified with
+/// SHA-1 hashes, that every responder supports.
+fn create_cert_id(certificate: &TbsCertificate, issuer: &TbsCertificate) -> Vec<u8> {
+    let hash_algorithm = write_der(
+        0x30,
+        &[&write_der(0x06, OID_SHA1)[..], &[0x05, 0x00]].concat(),
+    );
+    [
+        hash_algorithm,
+        write_der(0x04, &Sha1::digest(certificate.issuer)),
+        write_der(0x04, &Sha1::digest(issuer.public_key)),
+        write_der(0x02, certificate.serial_number),
+    ]
+    .concat()
+}
+
+/// Create an `OCSPRequest` for 1 certificate.
+fn create_request(cert_id: &[u8]) -> Vec<u8> {
+    // `OCSPRequest`, `TBSRequest`, `requestList`, `Request` and `CertID`.
+    let mut request = write_der(0x30, cert_id);
+    for _ in 0..4 {
+        request = write_der(0x30, &request);
+    }
+    request
+}
+
+/// Find the `SingleResponse` for `cert_id` in an `OCSPResponse`.
+/// Returns its `thisUpdate` and `nextUpdate`.
+fn find_single_response(
+    response: &[u8],
+    cert_id: &[u8],
+) -> Option<(DateTime<Utc>, Option<DateTime<Utc>>)> {
+    // `OCSPResponse`: the status and the `responseBytes`.
+    let (0x30, response, _) = read_der(response)? else {
+        return None;
+    };
+    let (0x0a, _, rest) = read_der(response)? else {
+        return None;
+    };
+    let (0xa0, response_bytes, _) = read_der(rest)? else {
+        return None;
+    };
+    let (0x30, response_bytes, _) = read_der(response_bytes)? else {
+        return None;
+    };
+    let (0x06, OID_OCSP_BASIC, rest) = read_der(response_bytes)? else {
+        return None;
+    };
+    let (0x04, basic_response, _) = read_der(rest)? else {
+        return None;
+    };
+    // `BasicOCSPResponse` and `ResponseData`.
+    let (0x30, basic_response, _) = read_der(basic_response)? else {
+        return None;
+    };
+    let (0x30, mut response_data, _) = read_der(basic_response)? else {
+        return None;
+    };
+    // Skip the `version`, the `responderID` and `producedAt`.
+    let mut responses = None;
+    while let Some((tag, content, rest)) = read_der(response_data) {
+        response_data = rest;
+        if tag == 0x30 {
+            responses = Some(content);
+            break;
+        }
+    }
+    let mut responses = responses?;
+    let requested = cert_id_fields(cert_id)?;
+    while let Some((0x30, single_response, rest)) = read_der(responses) {
+        responses = rest;
+        let Some((0x30, response_cert_id, rest)) = read_der(single_response) else {
+            continue;
+        };
+        if cert_id_fields(response_cert_id) != Some(requested) {
+            continue;
+        }
+        // Skip the `certStatus`.
+        let (_, _, rest) = read_der(rest)?;
+        let (tag, this_update, rest) = read_der(rest)?;
+        let this_update = parse_time(tag, this_update)?;
+        let next_update = match read_der(rest) {
+            Some((0xa0, next_update, _)) => {
+                let (tag, next_update, _) = read_der(next_update)?;
+                Some(parse_time(tag, next_update)?)
+            }
+            _ => None,
+        };
+        return Some((this_update, next_update));
+    }
+    None
+}
+
+/// Get the object identifier of the hash algorithm and the fields after it (the hashes and
+/// the serial number) of the content of a `CertID`. Responders can leave out the `NULL`
+/// parameters of the algorithm.
+fn cert_id_fields(cert_id: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (0x30, hash_algorithm, fields) = read_der(cert_id)? else {
+        return None;
+    };
+    let (0x06, oid, _) = read_der(hash_algorithm)? else {
+        return None;
+    };
+    Some((oid, fields))
+}
+
+impl PDFSigningDocument {
+    /// Fetch the OCSP responses of the certificate chains of the signers of the signature
+    /// fields `field_names`, and add them with the certificates to the `DSS` in a new
+    /// revision. Returns the bytes of the document.
+    pub(crate) fn add_ocsp_responses(
+        &mut self,
+        field_names: &[&str],
+        cache: &OcspCache,
+        client: &HttpClient,
+    ) -> Result<Vec<u8>, Error> {
+        let mut validation_data = ValidationData::default();
+        let forms = self.acro_form.clone().unwrap_or_default();
+        for form in forms.iter() {
+            let FormComponent::SignedSignature { contents, .. } = form.get_form_component() else {
+                continue;
+            };
+            if !form
+                .get_fully_qualified_field_name()
+                .is_some_and(|field_name| field_names.contains(&field_name))
+            {
+                continue;
+            }
+            // The placeholder can have zeros after the signature.
+            let length = read_der(contents)
+                .map_or(contents.len(), |(_, _, rest)| contents.len() - rest.len());
+            let signed_data = SignedData::parse_ber(&contents[..length])
+                .map_err(|error| Error::Other(format!("OCSP: {}", error)))?;
+            let Some(signer) = signed_data.signers().next() else {
+                continue;
+            };
+            let Some(mut certificate) = signer
+                .certificate_from_signed_data(&signed_data)
+                .map_err(|error| Error::Other(format!("OCSP: {}", error)))?
+            else {
+                continue;
+            };
+            // Limit the length, so certificates that sign each other do not hang.
+            for _ in 0..16 {
+                let certificate_data = certificate.constructed_data().to_vec();
+                if !validation_data.certificates.contains(&certificate_data) {
+                    validation_data.certificates.push(certificate_data.clone());
+                }
+                if certificate.subject_name() == certificate.issuer_name() {
+                    break;
+                }
+                // Only ask for the status of certificates that were signed by the issuer.
+                let Some(issuer) = signed_data.certificates().find(|issuer| {
+                    issuer.subject_name() == certificate.issuer_name()
+                        && certificate.verify_signed_by_certificate(issuer).is_ok()
+                }) else {
+                    tracing::debug!("OCSP: The issuer of a certificate of the chain is missing.");
+                    break;
+                };
+                if let Some(response) =
+                    cache.get_response(&certificate_data, issuer.constructed_data(), client)?
+                {
+                    if !validation_data.ocsp_responses.contains(&response) {
+                        validation_data.ocsp_responses.push(response);
+                    }
+                }
+                certificate = issuer;
+            }
+        }
+
+        self.embed_validation_data(&validation_data)?;
+        let data = self.save_to_bytes()?;
+        self.copy_from(self.reload_from(data.clone(), self.file_name.clone())?);
+        self.load_all()?;
+        Ok(data)
+    }
+}
@@ -0,0 +1,127 @@
+//! Make the document smaller: remove unused objects and duplicates, which build up
+//! when a document is filled and signed several times.
+
+use crate::{Error, PDFSigningDocument};
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use std::collections::HashMap;
+
+/// Types of dictionaries that can be shared without changing the document.
+/// Other dictionaries (like pages and annotations) must stay separate objects.
+const SHAREABLE_TYPES: [&[u8]; 4] = [b"Font", b"FontDescriptor", b"ExtGState", b"Encoding"];
+
+impl PDFSigningDocument {
+    /// Rewrite the document (including the changes that are not saved yet) as 1 compressed
+    /// revision without unused objects, where identical streams (like images and font files)
+    /// and fonts are only stored once.
+    /// The objects of existing object streams are written as normal (compressed) objects.
+    ///
+    /// Existing signatures become invalid, so optimize before signing.
+    pub fn optimize(&mut self) -> Result<(), Error> {
+        let mut doc = self.get_latest_document();
+        let object_count = doc.objects.len();
+        let unused_count = doc.prune_objects().len();
+
+        let mut duplicate_count = 0;
+        loop {
+            // Replacing duplicates can make other objects identical (like the fonts that
+            // use identical font files), so repeat until nothing changes.
+            let duplicates = find_duplicates(&doc);
+            if duplicates.is_empty() {
+                break;
+            }
+            duplicate_count += duplicates.len();
+            doc.traverse_objects(|object| {
+                if let Object::Reference(id) = object {
+                    if let Some(original_id) = duplicates.get(id) {
+                        *id = *original_id;
+                    }
+                }
+            });
+            for duplicate_id in duplicates.keys() {
+                doc.objects.remove(duplicate_id);
+            }
+        }
+        doc.renumber_objects();
+
+        tracing::info!(
+            "Optimize: Removed {} unused and {} duplicate objects of {}.",
+            unused_count,
+            duplicate_count,
+            object_count
+        );
+        self.reload_document(doc)
+    }
+}
+
+/// Find the objects that are identical to an object with a lower id,
+/// return the duplicate and the original id.
+fn find_duplicates(doc: &Document) -> HashMap<ObjectId, ObjectId> {
+    let mut originals: HashMap<Vec<u8>, ObjectId> = HashMap::new();
+    let mut duplicates = HashMap::new();
+    // `objects` is sorted by id, so the object with the lowest id is kept.
+    for (object_id, object) in doc.objects.iter() {
+        let shareable = match object {
+            Object::Stream(stream) => {
+                !stream.dict.type_is(b"XRef") && !stream.dict.type_is(b"ObjStm")
+            }
+            Object::Dictionary(dict) => SHAREABLE_TYPES.iter().any(|kind| dict.type_is(kind)),
+            _ => false,
+        };
+        if !shareable {
+            continue;
+        }
+        let mut key = vec![];
+        write_object_key(object, &mut key);
+        match originals.get(&key) {
+            Some(original_id) => {
+                duplicates.insert(*object_id, *original_id);
+            }
+            None => {
+                originals.insert(key, *object_id);
+            }
+        }
+    }
+    duplicates
+}
+
+/// Write a byte sequence that is only equal for identical objects.
+/// The lengths are included, so different objects can not have the same key.
+fn write_object_key(object: &Object, key: &mut Vec<u8>) {
+    match object {
+        Object::Null => key.push(b'n'),
+        Object::Boolean(value) => write_key_bytes(key, b'b', &[*value as u8]),
+        Object::Integer(value) => write_key_bytes(key, b'i', &value.to_le_bytes()),
+        Object::Real(value) => write_key_bytes(key, b'f', &value.to_le_bytes()),
+        Object::Name(name) => write_key_bytes(key, b'/', name),
+        Object::String(text, _) => write_key_bytes(key, b'(', text),
+        Object::Array(array) => {
+            write_key_bytes(key, b'[', &(array.len() as u64).to_le_bytes());
+            for item in array {
+                write_object_key(item, key);
+            }
+        }
+        Object::Dictionary(dict) => write_dictionary_key(dict, key),
+        Object::Stream(stream) => {
+            write_dictionary_key(&stream.dict, key);
+            write_key_bytes(key, b's', &stream.content);
+        }
+        Object::Reference((id, generation)) => {
+            write_key_bytes(key, b'R', &id.to_le_bytes());
+            key.extend_from_slice(&generation.to_le_bytes());
+        }
+    }
+}
+
+fn write_dictionary_key(dict: &Dictionary, key: &mut Vec<u8>) {
+    write_key_bytes(key, b'<', &(dict.len() as u64).to_le_bytes());
+    for (name, value) in dict.iter() {
+        write_key_bytes(key, b'/', name);
+        write_object_key(value, key);
+    }
+}
+
+fn write_key_bytes(key: &mut Vec<u8>, tag: u8, bytes: &[u8]) {
+    key.push(tag);
+    key.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+    key.extend_from_slice(bytes);
+}
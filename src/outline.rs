@@ -0,0 +1,351 @@
+//! Read and write the document outline (bookmarks), like a "Signatures" bookmark
+//! to the signature summary page at the end of the document.
+
+use crate::lopdf_utils::{decode_text_string, text_string};
+use crate::{Error, InsertImageToPage, PDFSigningDocument};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId};
+use std::collections::HashSet;
+
+/// An item of the document outline.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Bookmark {
+    pub title: String,
+    /// The index (starting at 0) of the page the bookmark goes to, `None` when the
+    /// bookmark has no destination in this document (like a link to a website).
+    pub page_index: Option<usize>,
+    /// Show the children in the outline when the document is opened.
+    pub open: bool,
+    pub children: Vec<Bookmark>,
+}
+
+impl PDFSigningDocument {
+    /// Get the outline (bookmarks) of the document, including the changes not saved yet.
+    pub fn get_outline(&self) -> Vec<Bookmark> {
+        let page_ids = self.get_page_ids();
+        let first = self
+            .get_outline_root_id()
+            .and_then(|outlines_id| self.get_latest_object(outlines_id))
+            .and_then(|outlines| outlines.as_dict().ok())
+            .and_then(|outlines| outlines.get(b"First").ok());
+        self.get_outline_items(first, &page_ids, &mut HashSet::new())
+    }
+
+    /// Replace the outline of the document with `bookmarks`, with an incremental update.
+    /// Bookmarks go to the whole page (`Fit`). An empty list removes the outline.
+    pub fn set_outline(&mut self, bookmarks: &[Bookmark]) -> Result<(), Error> {
+        let catalog_id = self
+            .raw_document
+            .new_document
+            .trailer
+            .get(b"Root")?
+            .as_reference()?;
+        self.opt_clone_object_to_new_document(catalog_id)?;
+        if bookmarks.is_empty() {
+            self.raw_document
+                .new_document
+                .get_object_mut(catalog_id)?
+                .as_dict_mut()?
+                .remove(b"Outlines");
+            return Ok(());
+        }
+
+        let page_ids = self.get_page_ids();
+        let outlines_id = self.raw_document.new_document.new_object_id();
+        let (first_last, count) = self.add_outline_items(bookmarks, outlines_id, &page_ids)?;
+        let mut outlines = dictionary! {
+            "Type" => "Outlines",
+            "Count" => count,
+        };
+        if let Some((first_id, last_id)) = first_last {
+            outlines.set("First", Object::Reference(first_id));
+            outlines.set("Last", Object::Reference(last_id));
+        }
+        self.raw_document
+            .new_document
+            .set_object(outlines_id, outlines);
+        self.raw_document
+            .new_document
+            .get_object_mut(catalog_id)?
+            .as_dict_mut()?
+            .set("Outlines", Object::Reference(outlines_id));
+        Ok(())
+    }
+
+    /// Add `bookmark` (and its children) at the end of the outline, with an incremental update.
+    /// The existing bookmarks are not changed.
+    pub fn add_bookmark(&mut self, bookmark: &Bookmark) -> Result<(), Error> {
+        let outlines_id = match self.get_outline_root_id() {
+            Some(outlines_id) => outlines_id,
+            None => return self.set_outline(std::slice::from_ref(bookmark)),
+        };
+        let page_ids = self.get_page_ids();
+        let (first_last, count) =
+            self.add_outline_items(std::slice::from_ref(bookmark), outlines_id, &page_ids)?;
+        let item_id = match first_last {
+            Some((item_id, _)) => item_id,
+            None => return Ok(()),
+        };
+
+        self.opt_clone_object_to_new_document(outlines_id)?;
+        let outlines = self
+            .raw_document
+            .new_document
+            .get_object_mut(outlines_id)?
+            .as_dict_mut()?;
+        let last_id = outlines.get(b"Last").and_then(Object::as_reference).ok();
+        let total_count = outlines.get(b"Count").and_then(Object::as_i64).unwrap_or(0);
+        outlines.set("Count", total_count.max(0) + count);
+        outlines.set("Last", Object::Reference(item_id));
+        match last_id {
+            Some(last_id) => {
+                self.opt_clone_object_to_new_document(last_id)?;
+                self.raw_document
+                    .new_document
+                    .get_object_mut(last_id)?
+                    .as_dict_mut()?
+                    .set("Next", Object::Reference(item_id));
+                self.raw_document
+                    .new_document
+                    .get_object_mut(item_id)?
+                    .as_dict_mut()?
+                    .set("Prev", Object::Reference(last_id));
+            }
+            None => outlines.set("First", Object::Reference(item_id)),
+        }
+        Ok(())
+    }
+
+    fn get_outline_root_id(&self) -> Option<ObjectId> {
+        self.raw_document
+            .new_document
+            .trailer
+            .get(b"Root")
+            .ok()
+            .and_then(|root| self.resolve_latest_object(root))
+            .and_then(|catalog| catalog.as_dict().ok())
+            .and_then(|catalog| catalog.get(b"Outlines").ok())
+            .and_then(|outlines| outlines.as_reference().ok())
+    }
+
+    /// Get the bookmarks of the item `first` and its siblings (`Next`).
+    fn get_outline_items(
+        &self,
+        first: Option<&Object>,
+        page_ids: &[ObjectId],
+        visited: &mut HashSet<ObjectId>,
+    ) -> Vec<Bookmark> {
+        let mut bookmarks = vec![];
+        let mut item_id = first.and_then(|first| first.as_reference().ok());
+        while let Some(current_id) = item_id {
+            // An item that is already visited means there is a loop in the outline.
+            if !visited.insert(current_id) {
+                break;
+            }
+            let item = match self
+                .get_latest_object(current_id)
+                .and_then(|item| item.as_dict().ok())
+            {
+                Some(item) => item,
+                None => break,
+            };
+            bookmarks.push(Bookmark {
+                title: item
+                    .get(b"Title")
+                    .ok()
+                    .and_then(|title| self.resolve_latest_object(title))
+                    .and_then(|title| title.as_str().ok())
+                    .map(decode_text_string)
+                    .unwrap_or_default(),
+                page_index: self.get_destination_page_index(item, page_ids),
+                open: item.get(b"Count").and_then(Object::as_i64).unwrap_or(0) > 0,
+                children: self.get_outline_items(item.get(b"First").ok(), page_ids, visited),
+            });
+            item_id = item.get(b"Next").and_then(Object::as_reference).ok();
+        }
+        bookmarks
+    }
+
+    /// Get the page index of the destination (`Dest` or `GoTo` action) of an outline item.
+    fn get_destination_page_index(
+        &self,
+        item: &Dictionary,
+        page_ids: &[ObjectId],
+    ) -> Option<usize> {
+        let destination = match item.get(b"Dest") {
+            Ok(destination) => destination,
+            Err(_) => {
+                let action = self
+                    .resolve_latest_object(item.get(b"A").ok()?)?
+                    .as_dict()
+                    .ok()?;
+                if action.get(b"S").and_then(Object::as_name_str).ok() != Some("GoTo") {
+                    return None;
+                }
+                action.get(b"D").ok()?
+            }
+        };
+        let destination = match self.resolve_latest_object(destination)? {
+            Object::Name(name) => self.get_named_destination(name, false)?,
+            Object::String(name, _) => self.get_named_destination(name, true)?,
+            destination => destination.clone(),
+        };
+        // A named destination can be a dictionary with the destination in `D`.
+        let destination = match &destination {
+            Object::Dictionary(destination) => {
+                self.resolve_latest_object(destination.get(b"D").ok()?)?
+            }
+            destination => destination,
+        };
+        let page_id = destination.as_array().ok()?.first()?.as_reference().ok()?;
+        page_ids.iter().position(|id| *id == page_id)
+    }
+
+    /// Find a named destination in the catalog (`Dests`, for names) or in the name tree
+    /// (`Names -> Dests`, for strings).
+    fn get_named_destination(&self, name: &[u8], in_name_tree: bool) -> Option<Object> {
+        let catalog = self
+            .resolve_latest_object(self.raw_document.new_document.trailer.get(b"Root").ok()?)?
+            .as_dict()
+            .ok()?;
+        let destination = if in_name_tree {
+            let destinations = self
+                .resolve_latest_object(catalog.get(b"Names").ok()?)?
+                .as_dict()
+                .ok()?
+                .get(b"Dests")
+                .ok()?;
+            let mut entries = vec![];
+            self.collect_name_tree_entries(destinations, 0, &mut entries);
+            entries
+                .into_iter()
+                .find(|(entry_name, _)| entry_name == name)?
+                .1
+        } else {
+            self.resolve_latest_object(catalog.get(b"Dests").ok()?)?
+                .as_dict()
+                .ok()?
+                .get(name)
+                .ok()?
+                .clone()
+        };
+        self.resolve_latest_object(&destination).cloned()
+    }
+
+    /// Add the outline items for `bookmarks` (and their children) with parent `parent_id`.
+    /// Return the first and last item and the number of visible items.
+    fn add_outline_items(
+        &mut self,
+        bookmarks: &[Bookmark],
+        parent_id: ObjectId,
+        page_ids: &[ObjectId],
+    ) -> Result<(Option<(ObjectId, ObjectId)>, i64), Error> {
+        let item_ids: Vec<ObjectId> = bookmarks
+            .iter()
+            .map(|_| self.raw_document.new_document.new_object_id())
+            .collect();
+        let mut visible_count = bookmarks.len() as i64;
+        for (index, bookmark) in bookmarks.iter().enumerate() {
+            let item_id = item_ids[index];
+            let mut item = dictionary! {
+                "Title" => text_string(&bookmark.title),
+                "Parent" => Object::Reference(parent_id),
+            };
+            if let Some(prev_id) = index.checked_sub(1).map(|prev| item_ids[prev]) {
+                item.set("Prev", Object::Reference(prev_id));
+            }
+            if let Some(next_id) = item_ids.get(index + 1) {
+                item.set("Next", Object::Reference(*next_id));
+            }
+            if let Some(page_index) = bookmark.page_index {
+                let page_id = page_ids.get(page_index).ok_or_else(|| {
+                    Error::Other(format!("Page with index `{}` not found.", page_index))
+                })?;
+                item.set(
+                    "Dest",
+                    vec![Object::Reference(*page_id), Object::Name(b"Fit".to_vec())],
+                );
+            }
+            let (children, child_count) =
+                self.add_outline_items(&bookmark.children, item_id, page_ids)?;
+            if let Some((first_id, last_id)) = children {
+                item.set("First", Object::Reference(first_id));
+                item.set("Last", Object::Reference(last_id));
+                // A negative count means the item is closed.
+                match bookmark.open {
+                    true => {
+                        item.set("Count", child_count);
+                        visible_count += child_count;
+                    }
+                    false => item.set("Count", -child_count),
+                }
+            }
+            self.raw_document.new_document.set_object(item_id, item);
+        }
+        let first_last = item_ids.first().copied().zip(item_ids.last().copied());
+        Ok((first_last, visible_count))
+    }
+}
+
+/// Get the root of the outline (`Outlines`) of `doc`.
+pub(crate) fn get_outline_root_id(doc: &Document) -> Option<ObjectId> {
+    doc.catalog()
+        .ok()?
+        .get(b"Outlines")
+        .ok()?
+        .as_reference()
+        .ok()
+}
+
+/// Add the items of the outline `other_root_id` at the end of the outline of `doc`,
+/// used to keep the outlines of merged documents.
+pub(crate) fn append_outline(doc: &mut Document, other_root_id: ObjectId) -> Result<(), Error> {
+    let other_root = doc.get_dictionary(other_root_id)?;
+    let (other_first_id, other_last_id) = match (
+        other_root.get(b"First").and_then(Object::as_reference),
+        other_root.get(b"Last").and_then(Object::as_reference),
+    ) {
+        (Ok(other_first_id), Ok(other_last_id)) => (other_first_id, other_last_id),
+        _ => return Ok(()),
+    };
+    let other_count = other_root
+        .get(b"Count")
+        .and_then(Object::as_i64)
+        .unwrap_or(0)
+        .max(0);
+
+    let root_id = match get_outline_root_id(doc) {
+        Some(root_id) => root_id,
+        None => {
+            let root_id = doc.add_object(dictionary! { "Type" => "Outlines", "Count" => 0 });
+            doc.catalog_mut()?
+                .set("Outlines", Object::Reference(root_id));
+            root_id
+        }
+    };
+    let root = doc.get_object_mut(root_id)?.as_dict_mut()?;
+    let last_id = root.get(b"Last").and_then(Object::as_reference).ok();
+    let count = root.get(b"Count").and_then(Object::as_i64).unwrap_or(0);
+    root.set("Count", count.max(0) + other_count);
+    root.set("Last", Object::Reference(other_last_id));
+    match last_id {
+        Some(last_id) => {
+            doc.get_object_mut(last_id)?
+                .as_dict_mut()?
+                .set("Next", Object::Reference(other_first_id));
+            doc.get_object_mut(other_first_id)?
+                .as_dict_mut()?
+                .set("Prev", Object::Reference(last_id));
+        }
+        None => root.set("First", Object::Reference(other_first_id)),
+    }
+
+    // Link the top level items to the new root.
+    let mut item_id = Some(other_first_id);
+    let mut visited = HashSet::new();
+    while let Some(current_id) = item_id.filter(|current_id| visited.insert(*current_id)) {
+        let item = doc.get_object_mut(current_id)?.as_dict_mut()?;
+        item.set("Parent", Object::Reference(root_id));
+        item_id = item.get(b"Next").and_then(Object::as_reference).ok();
+    }
+    Ok(())
+}
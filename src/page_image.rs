@@ -0,0 +1,232 @@
+//! Add images anywhere in the document, not only in signature fields.
+
+use crate::image_options::ImageOptions;
+use crate::image_xobject::ImageXObject;
+use crate::journal::JournalChange;
+use crate::rectangle::Rectangle;
+use crate::svg::SvgImage;
+use crate::tagged_content::ContentTag;
+use crate::{Error, InsertImage, InsertImageToPage, PDFSigningDocument};
+use lopdf::{
+    content::{Content, Operation},
+    Object, ObjectId,
+};
+
+impl PDFSigningDocument {
+    /// Add an image (PNG, JPEG, TIFF or WebP, SVG with `ImageOptions::allow_svg`) to the
+    /// page with index `page_index`.
+    /// The first page has index 0. `rect` uses the coordinates of the page.
+    /// `options` define how the image is fitted into `rect` and how it is encoded.
+    /// Return the ObjectId of the image.
+    pub fn add_image(
+        &mut self,
+        page_index: usize,
+        image_bytes: &[u8],
+        rect: Rectangle,
+        options: &ImageOptions,
+    ) -> Result<ObjectId, Error> {
+        let _span =
+            tracing::info_span!("add_image", file_name = %self.file_name, page_index).entered();
+        let page_id = self.get_page_id(page_index)?;
+        let image_name = self.get_unused_xobject_name(page_id, "Image");
+        if !options.is_svg(image_bytes) {
+            let image_id = InsertImageToPage::add_image(
+                self,
+                image_bytes,
+                &image_name,
+                page_id,
+                rect.clone(),
+                options,
+            )?;
+            self.record_change(JournalChange::ImageInserted { page_index, rect });
+            return Ok(image_id);
+        }
+
+        let form_xobject_id =
+            self.add_image_as_form_xobject(image_bytes, &image_name, rect.clone(), options)?;
+        self.add_xobject(page_id, image_name.as_str(), form_xobject_id)?;
+        // The Form XObject starts at the origin, so move it to `rect`.
+        let operations = vec![
+            Operation::new("q", vec![]),
+            Operation::new(
+                "cm",
+                vec![
+                    1.into(),
+                    0.into(),
+                    0.into(),
+                    1.into(),
+                    rect.x1.into(),
+                    rect.y1.into(),
+                ],
+            ),
+            Operation::new("Do", vec![Object::Name(image_name.into_bytes())]),
+            Operation::new("Q", vec![]),
+        ];
+        let tag = ContentTag::image(options.alternate_text.as_deref(), &rect);
+        let operations = self.tag_page_content(page_id, operations, tag)?;
+        self.opt_clone_object_to_new_document(page_id)?;
+        self.add_to_page_content(page_id, Content { operations })?;
+        self.record_change(JournalChange::ImageInserted { page_index, rect });
+        Ok(form_xobject_id)
+    }
+
+    /// Add an image as Form XObject with the size of the image: the amount of pixels,
+    /// or the `viewBox` for SVG images (with `options.allow_svg`). The image will not be
+    /// visible.
+    /// Return the ObjectId and the size of the Form XObject.
+    pub(crate) fn add_image_as_unscaled_form_xobject(
+        &mut self,
+        image_bytes: &[u8],
+        image_name: &str,
+        options: &ImageOptions,
+    ) -> Result<(ObjectId, (f64, f64)), Error> {
+        let size = if options.is_svg(image_bytes) {
+            let svg = std::str::from_utf8(image_bytes)
+                .map_err(|_| Error::from("SVG: Image is not valid UTF-8."))?;
+            SvgImage::parse(svg)?.size()
+        } else {
+            let (image_xobject, _) = ImageXObject::from_reader(image_bytes)?;
+            (image_xobject.width as f64, image_xobject.height as f64)
+        };
+        let rect = Rectangle {
+            x1: 0.0,
+            y1: 0.0,
+            x2: size.0,
+            y2: size.1,
+        };
+        let xobject_id = self.add_image_as_form_xobject(image_bytes, image_name, rect, options)?;
+        Ok((xobject_id, size))
+    }
+
+    /// Get the ObjectId of the page with index `page_index`, the first page has index 0.
+    pub(crate) fn get_page_id(&self, page_index: usize) -> Result<ObjectId, Error> {
+        self.get_page_ids()
+            .get(page_index)
+            .copied()
+            .ok_or_else(|| Error::Other(format!("Page with index `{}` not found.", page_index)))
+    }
+
+    /// Get the index of the page with ObjectId `page_id`, `None` when it is not in the page tree.
+    pub(crate) fn get_page_index(&self, page_id: ObjectId) -> Option<usize> {
+        self.get_page_ids().iter().position(|id| *id == page_id)
+    }
+
+    /// Get the ObjectIds of all the pages in order, including the changes not saved yet.
+    pub(crate) fn get_page_ids(&self) -> Vec<ObjectId> {
+        let mut page_ids = vec![];
+        let pages_id = self
+            .raw_document
+            .new_document
+            .trailer
+            .get(b"Root")
+            .ok()
+            .and_then(|root| self.resolve_latest_object(root))
+            .and_then(|catalog| catalog.as_dict().ok())
+            .and_then(|catalog| catalog.get(b"Pages").ok())
+            .and_then(|pages| pages.as_reference().ok());
+        if let Some(pages_id) = pages_id {
+            self.collect_page_ids(pages_id, 0, &mut page_ids);
+        }
+        page_ids
+    }
+
+    fn collect_page_ids(&self, node_id: ObjectId, depth: usize, page_ids: &mut Vec<ObjectId>) {
+        // Limit the depth, so a loop in the page tree does not hang.
+        let node = match self.get_latest_object(node_id) {
+            Some(Object::Dictionary(node)) if depth < self.limits.max_depth => node,
+            _ => return,
+        };
+        let kids = node
+            .get(b"Kids")
+            .ok()
+            .and_then(|kids| self.resolve_latest_object(kids))
+            .and_then(|kids| kids.as_array().ok());
+        match kids {
+            Some(kids) if !node.type_is(b"Page") => {
+                for kid in kids {
+                    if let Ok(kid_id) = kid.as_reference() {
+                        self.collect_page_ids(kid_id, depth + 1, page_ids);
+                    }
+                }
+            }
+            _ => page_ids.push(node_id),
+        }
+    }
+
+    /// Get the visible area of the page (`CropBox`, or `MediaBox` when it is not set).
+    pub(crate) fn get_page_rect(&self, page_id: ObjectId) -> Result<Rectangle, Error> {
+        let page_box = self
+            .get_inherited_page_attribute(page_id, b"CropBox")
+            .or_else(|| self.get_inherited_page_attribute(page_id, b"MediaBox"))
+            .ok_or_else(|| Error::from("Page: MediaBox not found."))?;
+        Rectangle::from_array(page_box.as_array()?)
+    }
+
+    /// Get the rotation (`Rotate`) of the page in degrees: 0, 90, 180 or 270.
+    pub(crate) fn get_page_rotation(&self, page_id: ObjectId) -> i64 {
+        self.get_inherited_page_attribute(page_id, b"Rotate")
+            .and_then(|rotate| rotate.as_i64().ok())
+            .map(|rotate| rotate.rem_euclid(360) / 90 * 90)
+            .unwrap_or(0)
+    }
+
+    /// Get an attribute of the page, also when it is inherited from a `Pages` node.
+    pub(crate) fn get_inherited_page_attribute(
+        &self,
+        page_id: ObjectId,
+        key: &[u8],
+    ) -> Option<&Object> {
+        let mut node_id = page_id;
+        // Limit the depth, so a loop in the page tree does not hang.
+        for _ in 0..self.limits.max_depth {
+            let node = self.get_latest_object(node_id)?.as_dict().ok()?;
+            if let Ok(value) = node.get(key) {
+                return self.resolve_latest_object(value);
+            }
+            node_id = node.get(b"Parent").ok()?.as_reference().ok()?;
+        }
+        None
+    }
+
+    /// Get a XObject name that is not used yet on the page: `prefix` followed by a number.
+    pub(crate) fn get_unused_xobject_name(&self, page_id: ObjectId, prefix: &str) -> String {
+        let used_names = self.get_xobject_names(page_id);
+        (1..)
+            .map(|number| format!("{}{}", prefix, number))
+            .find(|name| !used_names.contains(&name.as_bytes().to_vec()))
+            .unwrap_or_else(|| prefix.to_owned())
+    }
+
+    /// Get the names in `Resources -> XObject` of the page, including the changes not saved yet.
+    pub(crate) fn get_xobject_names(&self, page_id: ObjectId) -> Vec<Vec<u8>> {
+        let resources = self
+            .get_latest_object(page_id)
+            .and_then(|page| page.as_dict().ok())
+            .and_then(|page| page.get(b"Resources").ok())
+            .and_then(|resources| self.resolve_latest_object(resources));
+        let xobjects = resources
+            .and_then(|resources| resources.as_dict().ok())
+            .and_then(|resources| resources.get(b"XObject").ok())
+            .and_then(|xobjects| self.resolve_latest_object(xobjects))
+            .and_then(|xobjects| xobjects.as_dict().ok());
+        xobjects
+            .map(|xobjects| xobjects.iter().map(|(name, _)| name.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Get an object from the new document, or from the previous documents when it is not changed.
+    pub(crate) fn get_latest_object(&self, object_id: ObjectId) -> Option<&Object> {
+        self.raw_document
+            .new_document
+            .get_object(object_id)
+            .or_else(|_| self.raw_document.get_prev_documents().get_object(object_id))
+            .ok()
+    }
+
+    pub(crate) fn resolve_latest_object<'a>(&'a self, object: &'a Object) -> Option<&'a Object> {
+        match object {
+            Object::Reference(object_id) => self.get_latest_object(*object_id),
+            _ => Some(object),
+        }
+    }
+}
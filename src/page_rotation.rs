@@ -0,0 +1,156 @@
+//! Rotate pages, for example scans that were uploaded sideways.
+
+use crate::journal::JournalChange;
+use crate::rectangle::Rectangle;
+use crate::{Error, InsertImageToPage, PDFSigningDocument};
+use lopdf::content::{Content, Operation};
+
+/// Page boxes that are rotated together with the content.
+const PAGE_BOXES: [&[u8]; 5] = [b"MediaBox", b"CropBox", b"BleedBox", b"TrimBox", b"ArtBox"];
+
+impl PDFSigningDocument {
+    /// Set the rotation (`Rotate`) of the pages with `page_indexes` to `rotation` degrees
+    /// clockwise, a multiple of 90. Only the way the page is shown changes, the coordinates
+    /// used to place images and fields stay the same. See `normalize_page_rotation`.
+    pub fn set_page_rotation(
+        &mut self,
+        page_indexes: &[usize],
+        rotation: i64,
+    ) -> Result<(), Error> {
+        if rotation % 90 != 0 {
+            return Err(Error::from(
+                "Rotate: The rotation should be a multiple of 90 degrees.",
+            ));
+        }
+        for page_index in page_indexes {
+            let page_id = self.get_page_id(*page_index)?;
+            self.opt_clone_object_to_new_document(page_id)?;
+            self.raw_document
+                .new_document
+                .get_object_mut(page_id)?
+                .as_dict_mut()?
+                .set("Rotate", rotation.rem_euclid(360));
+        }
+        self.record_change(JournalChange::PagesRotated {
+            page_indexes: page_indexes.to_vec(),
+            rotation: Some(rotation.rem_euclid(360)),
+        });
+        Ok(())
+    }
+
+    /// Remove the rotation (`Rotate`) of the pages with `page_indexes` by rotating the content
+    /// itself. The pages look the same, but images and fields that are placed afterwards
+    /// use upright coordinates.
+    ///
+    /// Pages with annotations (like form fields) are not supported,
+    /// because their appearances would have to be rotated too.
+    pub fn normalize_page_rotation(&mut self, page_indexes: &[usize]) -> Result<(), Error> {
+        let mut normalized_page_indexes = vec![];
+        for page_index in page_indexes {
+            let page_id = self.get_page_id(*page_index)?;
+            let rotation = self.get_page_rotation(page_id);
+            if rotation == 0 {
+                continue;
+            }
+            let has_annotations = self
+                .get_inherited_page_attribute(page_id, b"Annots")
+                .and_then(|annotations| annotations.as_array().ok())
+                .is_some_and(|annotations| !annotations.is_empty());
+            if has_annotations {
+                return Err(Error::Other(format!(
+                    "Rotate: Page with index `{}` has annotations, it can not be normalized.",
+                    page_index
+                )));
+            }
+
+            let media_box = self
+                .get_inherited_page_attribute(page_id, b"MediaBox")
+                .ok_or_else(|| Error::from("Page: MediaBox not found."))?;
+            let matrix =
+                get_normalize_matrix(rotation, &Rectangle::from_array(media_box.as_array()?)?);
+            let mut page_boxes = vec![];
+            for key in PAGE_BOXES {
+                if let Some(page_box) = self.get_inherited_page_attribute(page_id, key) {
+                    let page_box =
+                        transform_rect(&matrix, &Rectangle::from_array(page_box.as_array()?)?);
+                    page_boxes.push((key, page_box));
+                }
+            }
+
+            self.prepend_to_page_content(
+                page_id,
+                Content {
+                    operations: vec![
+                        Operation::new("q", vec![]),
+                        Operation::new("cm", matrix.iter().map(|value| (*value).into()).collect()),
+                    ],
+                },
+            )?;
+            self.add_to_page_content(
+                page_id,
+                Content {
+                    operations: vec![Operation::new("Q", vec![])],
+                },
+            )?;
+
+            let page = self
+                .raw_document
+                .new_document
+                .get_object_mut(page_id)?
+                .as_dict_mut()?;
+            for (key, page_box) in page_boxes {
+                page.set(
+                    key,
+                    vec![
+                        page_box.x1.into(),
+                        page_box.y1.into(),
+                        page_box.x2.into(),
+                        page_box.y2.into(),
+                    ],
+                );
+            }
+            page.set("Rotate", 0);
+            normalized_page_indexes.push(*page_index);
+        }
+        if !normalized_page_indexes.is_empty() {
+            self.record_change(JournalChange::PagesRotated {
+                page_indexes: normalized_page_indexes,
+                rotation: None,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Get the matrix (`cm`) that rotates the content by `rotation` degrees clockwise
+/// and moves the lower left corner of `media_box` to the origin.
+fn get_normalize_matrix(rotation: i64, media_box: &Rectangle) -> [f64; 6] {
+    let (left, right) = (
+        media_box.x1.min(media_box.x2),
+        media_box.x1.max(media_box.x2),
+    );
+    let (bottom, top) = (
+        media_box.y1.min(media_box.y2),
+        media_box.y1.max(media_box.y2),
+    );
+    match rotation {
+        90 => [0.0, -1.0, 1.0, 0.0, -bottom, right],
+        180 => [-1.0, 0.0, 0.0, -1.0, right, top],
+        270 => [0.0, 1.0, -1.0, 0.0, top, -left],
+        _ => [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+    }
+}
+
+/// Transform the corners of `rect` with `matrix`, the result is normalized (`x1 < x2`).
+fn transform_rect(matrix: &[f64; 6], rect: &Rectangle) -> Rectangle {
+    let [a, b, c, d, e, f] = *matrix;
+    let transform = |x: f64, y: f64| (a * x + c * y + e, b * x + d * y + f);
+    let (x1, y1) = transform(rect.x1, rect.y1);
+    let (x2, y2) = transform(rect.x2, rect.y2);
+    Rectangle {
+        x1: x1.min(x2),
+        y1: y1.min(y2),
+        x2: x1.max(x2),
+        y2: y1.max(y2),
+    }
+}
@@ -0,0 +1,270 @@
+//! Insert blank pages and delete pages, like a signature summary page at the end.
+//! The page tree is changed with an incremental update.
+
+use crate::journal::JournalChange;
+use crate::{Error, InsertImage, InsertImageToPage, PDFSigningDocument};
+use lopdf::{dictionary, Object, ObjectId};
+use std::collections::HashSet;
+
+impl PDFSigningDocument {
+    /// Insert a blank page, so it gets index `page_index`. Use the number of pages
+    /// as index to add the page at the end.
+    /// `size` is the width and height in points, `None` uses the size (`MediaBox`) of the
+    /// page before it, or of the first page when it is inserted at the start.
+    pub fn insert_blank_page(
+        &mut self,
+        page_index: usize,
+        size: Option<(f64, f64)>,
+    ) -> Result<(), Error> {
+        let page_ids = self.get_page_ids();
+        if page_index > page_ids.len() {
+            return Err(Error::Other(format!(
+                "Page with index `{}` not found.",
+                page_index
+            )));
+        }
+        let sibling_id = *page_ids
+            .get(page_index)
+            .or_else(|| page_ids.last())
+            .ok_or_else(|| Error::from("Page: Document has no pages."))?;
+        let size_page_id = page_ids[page_index.saturating_sub(1)];
+
+        let media_box = match size {
+            Some((width, height)) => vec![0.into(), 0.into(), width.into(), height.into()].into(),
+            None => self
+                .get_inherited_page_attribute(size_page_id, b"MediaBox")
+                .cloned()
+                .ok_or_else(|| Error::from("Page: MediaBox not found."))?,
+        };
+        let parent_id = self.get_page_parent_id(sibling_id)?;
+        let page_id = self.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => Object::Reference(parent_id),
+            "MediaBox" => media_box,
+            "Resources" => dictionary! {},
+        });
+
+        let kids = self.get_kids_mut(parent_id)?;
+        let position = kids
+            .iter()
+            .position(|kid| kid.as_reference().ok() == Some(sibling_id))
+            .unwrap_or(kids.len());
+        // After the last page, before any other page.
+        let position = if page_index == page_ids.len() {
+            position + 1
+        } else {
+            position
+        };
+        kids.insert(position.min(kids.len()), Object::Reference(page_id));
+        self.add_to_page_count(parent_id, 1)?;
+        self.record_change(JournalChange::PageInserted { page_index });
+        Ok(())
+    }
+
+    /// Delete the pages with `page_indexes`. The form fields with widgets on these pages
+    /// are removed from the form, fields with widgets on other pages keep those widgets.
+    /// The last page of a document can not be deleted.
+    pub fn delete_pages(&mut self, page_indexes: &[usize]) -> Result<(), Error> {
+        let page_ids = self.get_page_ids();
+        let mut delete_page_ids = vec![];
+        for page_index in page_indexes {
+            let page_id = *page_ids.get(*page_index).ok_or_else(|| {
+                Error::Other(format!("Page with index `{}` not found.", page_index))
+            })?;
+            if !delete_page_ids.contains(&page_id) {
+                delete_page_ids.push(page_id);
+            }
+        }
+        if delete_page_ids.len() >= page_ids.len() {
+            return Err(Error::from("Page: A document needs at least 1 page."));
+        }
+
+        let mut annotation_ids = HashSet::new();
+        for page_id in delete_page_ids {
+            let annotations = self
+                .get_latest_object(page_id)
+                .and_then(|page| page.as_dict().ok())
+                .and_then(|page| page.get(b"Annots").ok())
+                .and_then(|annotations| self.resolve_latest_object(annotations))
+                .and_then(|annotations| annotations.as_array().ok());
+            if let Some(annotations) = annotations {
+                annotation_ids.extend(
+                    annotations
+                        .iter()
+                        .filter_map(|annotation| annotation.as_reference().ok()),
+                );
+            }
+
+            let parent_id = self.get_page_parent_id(page_id)?;
+            self.get_kids_mut(parent_id)?
+                .retain(|kid| kid.as_reference().ok() != Some(page_id));
+            self.add_to_page_count(parent_id, -1)?;
+        }
+        self.remove_fields_with_widgets(&annotation_ids)?;
+        self.record_change(JournalChange::PagesDeleted {
+            page_indexes: page_indexes.to_vec(),
+        });
+        Ok(())
+    }
+
+    fn get_page_parent_id(&self, page_id: ObjectId) -> Result<ObjectId, Error> {
+        self.get_latest_object(page_id)
+            .and_then(|page| page.as_dict().ok())
+            .and_then(|page| page.get(b"Parent").ok())
+            .and_then(|parent| parent.as_reference().ok())
+            .ok_or_else(|| Error::from("Page: Parent not found."))
+    }
+
+    /// Get the `Kids` of a `Pages` node in the new document, so they can be changed.
+    fn get_kids_mut(&mut self, node_id: ObjectId) -> Result<&mut Vec<Object>, Error> {
+        self.opt_clone_object_to_new_document(node_id)?;
+        let kids = self
+            .raw_document
+            .new_document
+            .get_object(node_id)?
+            .as_dict()?
+            .get(b"Kids")?
+            .clone();
+        if let Object::Reference(kids_id) = kids {
+            self.opt_clone_object_to_new_document(kids_id)?;
+            return Ok(self
+                .raw_document
+                .new_document
+                .get_object_mut(kids_id)?
+                .as_array_mut()?);
+        }
+        Ok(self
+            .raw_document
+            .new_document
+            .get_object_mut(node_id)?
+            .as_dict_mut()?
+            .get_mut(b"Kids")?
+            .as_array_mut()?)
+    }
+
+    /// Add `difference` to the `Count` of the `Pages` node and all its ancestors.
+    fn add_to_page_count(&mut self, node_id: ObjectId, difference: i64) -> Result<(), Error> {
+        let mut node_id = Some(node_id);
+        // Limit the depth, so a loop in the page tree does not hang.
+        for _ in 0..self.limits.max_depth {
+            let current_id = match node_id {
+                Some(current_id) => current_id,
+                None => return Ok(()),
+            };
+            self.opt_clone_object_to_new_document(current_id)?;
+            let node = self
+                .raw_document
+                .new_document
+                .get_object_mut(current_id)?
+                .as_dict_mut()?;
+            let count = node.get(b"Count").and_then(Object::as_i64).unwrap_or(0);
+            node.set("Count", (count + difference).max(0));
+            node_id = node.get(b"Parent").and_then(Object::as_reference).ok();
+        }
+        Ok(())
+    }
+
+    /// Remove the widgets in `widget_ids` from the form, fields without widgets are removed too.
+    fn remove_fields_with_widgets(&mut self, widget_ids: &HashSet<ObjectId>) -> Result<(), Error> {
+        if widget_ids.is_empty() {
+            return Ok(());
+        }
+        let catalog_id = self
+            .raw_document
+            .new_document
+            .trailer
+            .get(b"Root")?
+            .as_reference()?;
+        let acro_form = self
+            .get_latest_object(catalog_id)
+            .and_then(|catalog| catalog.as_dict().ok())
+            .and_then(|catalog| catalog.get(b"AcroForm").ok())
+            .cloned();
+        let fields = acro_form
+            .as_ref()
+            .and_then(|acro_form| self.resolve_latest_object(acro_form))
+            .and_then(|acro_form| acro_form.as_dict().ok())
+            .and_then(|acro_form| acro_form.get(b"Fields").ok())
+            .and_then(|fields| self.resolve_latest_object(fields))
+            .and_then(|fields| fields.as_array().ok())
+            .cloned()
+            .unwrap_or_default();
+
+        let kept_fields = self.remove_widgets_from_fields(&fields, widget_ids, 0)?;
+        if kept_fields.len() == fields.len() {
+            return Ok(());
+        }
+        let acro_form = match acro_form {
+            Some(Object::Reference(acro_form_id)) => {
+                self.opt_clone_object_to_new_document(acro_form_id)?;
+                self.raw_document
+                    .new_document
+                    .get_object_mut(acro_form_id)?
+                    .as_dict_mut()?
+            }
+            _ => {
+                self.opt_clone_object_to_new_document(catalog_id)?;
+                self.raw_document
+                    .new_document
+                    .get_object_mut(catalog_id)?
+                    .as_dict_mut()?
+                    .get_mut(b"AcroForm")?
+                    .as_dict_mut()?
+            }
+        };
+        acro_form.set("Fields", kept_fields);
+        Ok(())
+    }
+
+    /// Get the `fields` that still have widgets after removing the widgets in `widget_ids`.
+    fn remove_widgets_from_fields(
+        &mut self,
+        fields: &[Object],
+        widget_ids: &HashSet<ObjectId>,
+        depth: usize,
+    ) -> Result<Vec<Object>, Error> {
+        let mut kept_fields = vec![];
+        for field in fields {
+            let field_id = match field.as_reference() {
+                Ok(field_id) => field_id,
+                Err(_) => {
+                    kept_fields.push(field.clone());
+                    continue;
+                }
+            };
+            // The field and the widget can be the same object.
+            if widget_ids.contains(&field_id) {
+                continue;
+            }
+            let kids = self
+                .get_latest_object(field_id)
+                .and_then(|field| field.as_dict().ok())
+                .and_then(|field| field.get(b"Kids").ok())
+                .and_then(|kids| self.resolve_latest_object(kids))
+                .and_then(|kids| kids.as_array().ok())
+                .cloned();
+            let kids = match kids {
+                // Limit the depth, so a loop in the field tree does not hang.
+                Some(kids) if depth < self.limits.max_depth => kids,
+                _ => {
+                    kept_fields.push(field.clone());
+                    continue;
+                }
+            };
+            let kept_kids = self.remove_widgets_from_fields(&kids, widget_ids, depth + 1)?;
+            if kept_kids.is_empty() {
+                continue;
+            }
+            if kept_kids.len() != kids.len() {
+                self.opt_clone_object_to_new_document(field_id)?;
+                self.raw_document
+                    .new_document
+                    .get_object_mut(field_id)?
+                    .as_dict_mut()?
+                    .set("Kids", kept_kids);
+            }
+            kept_fields.push(field.clone());
+        }
+        Ok(kept_fields)
+    }
+}
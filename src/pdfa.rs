@@ -0,0 +1,261 @@
+//! Opt-in PDF/A mode, so PDF/A-2b documents stay conformant when they are filled and signed.
+//!
+//! In this mode:
+//! - Text is written with the embedded font (`set_embedded_font`), standard fonts are not allowed.
+//! - Encryption is not allowed.
+//! - Changes of the document information are also written to the XMP metadata.
+//! - Metadata streams are not compressed and rewritten files start with a binary comment.
+//! - Signature widgets are printed and not hidden.
+
+use crate::font::TrueTypeFont;
+use crate::{Error, InsertImage, InsertImageToPage, PDFSigningDocument};
+use lopdf::{dictionary, Document, Object, ObjectId, Stream};
+
+/// Comment after the file header, PDF/A requires 4 bytes above 127 so the file is seen as binary.
+/// The characters are encoded as UTF-8, which only uses bytes above 127 for them.
+const BINARY_COMMENT: &str = "%âãÏÓ";
+/// XMP packet without properties, used when a document has no metadata yet.
+const EMPTY_XMP_PACKET: &str = "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\
+<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\
+<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\"></rdf:RDF>\
+</x:xmpmeta><?xpacket end=\"w\"?>";
+const NAMESPACE_DC: &str = "http://purl.org/dc/elements/1.1/";
+const NAMESPACE_PDF: &str = "http://ns.adobe.com/pdf/1.3/";
+const NAMESPACE_XMP: &str = "http://ns.adobe.com/xap/1.0/";
+
+/// Structure of the value of an XMP property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum XmpKind {
+    Text,
+    /// Text in different languages (`rdf:Alt`), only the default language is written.
+    LanguageAlternative,
+    /// Ordered list (`rdf:Seq`) with 1 item.
+    Sequence,
+}
+
+impl PDFSigningDocument {
+    /// Get the font for text written by this crate (rich text fields and text watermarks).
+    /// `None` means the standard fonts are used, which is not allowed in PDF/A mode.
+    pub(crate) fn get_text_font(&self) -> Result<Option<TrueTypeFont>, Error> {
+        match (&self.embedded_font, self.pdfa_mode) {
+            (Some(font), _) => Ok(Some(font.clone())),
+            (None, true) => Err(Error::from(
+                "PDF/A: Text needs an embedded font, set it with `set_embedded_font`.",
+            )),
+            (None, false) => Ok(None),
+        }
+    }
+
+    /// Prepare a document that is saved in full (not as incremental update) in PDF/A mode.
+    pub(crate) fn prepare_pdfa_document(&self, doc: &mut Document) {
+        if !self.pdfa_mode {
+            return;
+        }
+        keep_metadata_uncompressed(doc);
+        // The writer adds a new line after the version.
+        if !doc.version.contains(BINARY_COMMENT) {
+            doc.version = format!("{}\n{}", doc.version, BINARY_COMMENT);
+        }
+    }
+
+    /// Prepare the incremental update before saving in PDF/A mode.
+    pub(crate) fn prepare_pdfa_update(&self, new_document: &mut Document) {
+        if self.pdfa_mode {
+            keep_metadata_uncompressed(new_document);
+        }
+    }
+
+    /// Write the value of an entry of the `Info` dictionary to the XMP metadata of the document,
+    /// PDF/A requires they are the same. Dates are written in ISO 8601 format.
+    pub(crate) fn update_xmp_metadata(&mut self, info_key: &str, value: &str) -> Result<(), Error> {
+        let (name, namespace, kind) = match info_key {
+            "Title" => ("dc:title", NAMESPACE_DC, XmpKind::LanguageAlternative),
+            "Author" => ("dc:creator", NAMESPACE_DC, XmpKind::Sequence),
+            "Subject" => ("dc:description", NAMESPACE_DC, XmpKind::LanguageAlternative),
+            "Keywords" => ("pdf:Keywords", NAMESPACE_PDF, XmpKind::Text),
+            "CreationDate" => ("xmp:CreateDate", NAMESPACE_XMP, XmpKind::Text),
+            "ModDate" => ("xmp:ModifyDate", NAMESPACE_XMP, XmpKind::Text),
+            _ => return Ok(()),
+        };
+        if self.get_metadata_id().is_none() {
+            tracing::warn!("PDF/A: Document has no XMP metadata.");
+            return Ok(());
+        }
+        self.edit_xmp_metadata(|xmp| set_xmp_property(xmp, name, namespace, kind, value))
+    }
+
+    /// Change the XMP metadata of the document with `edit`, with an incremental update.
+    /// When the document has no metadata, an empty XMP packet is created first.
+    pub(crate) fn edit_xmp_metadata<F: FnOnce(&str) -> String>(
+        &mut self,
+        edit: F,
+    ) -> Result<(), Error> {
+        let metadata_id = match self.get_metadata_id() {
+            Some(metadata_id) => {
+                self.opt_clone_object_to_new_document(metadata_id)?;
+                metadata_id
+            }
+            None => {
+                let metadata_id = self.add_object(Stream::new(
+                    dictionary! {
+                        "Type" => "Metadata",
+                        "Subtype" => "XML",
+                    },
+                    EMPTY_XMP_PACKET.as_bytes().to_vec(),
+                ));
+                let catalog_id = self
+                    .raw_document
+                    .new_document
+                    .trailer
+                    .get(b"Root")?
+                    .as_reference()?;
+                self.opt_clone_object_to_new_document(catalog_id)?;
+                self.raw_document
+                    .new_document
+                    .get_object_mut(catalog_id)?
+                    .as_dict_mut()?
+                    .set("Metadata", Object::Reference(metadata_id));
+                metadata_id
+            }
+        };
+
+        let stream = self
+            .raw_document
+            .new_document
+            .get_object_mut(metadata_id)?
+            .as_stream_mut()?;
+        let xmp = stream
+            .decompressed_content()
+            .unwrap_or_else(|_| stream.content.clone());
+        let xmp = edit(&String::from_utf8_lossy(&xmp));
+        // PDF/A does not allow filters on metadata streams.
+        stream.dict.remove(b"Filter");
+        stream.dict.remove(b"DecodeParms");
+        stream.set_content(xmp.into_bytes());
+        stream.allows_compression = false;
+        Ok(())
+    }
+
+    /// Get the ObjectId of the XMP metadata stream of the catalog (`Metadata`).
+    fn get_metadata_id(&self) -> Option<ObjectId> {
+        let root = self.raw_document.new_document.trailer.get(b"Root").ok()?;
+        self.resolve_latest_object(root)?
+            .as_dict()
+            .ok()?
+            .get(b"Metadata")
+            .ok()?
+            .as_reference()
+            .ok()
+    }
+}
+
+/// Get the annotation flags (`F`) with `Print` set and `Invisible`, `Hidden` and `NoView` cleared.
+pub(crate) fn get_printable_annotation_flags(flags: i64) -> i64 {
+    (flags | 4) & !(1 | 2 | 32)
+}
+
+/// Metadata streams should be readable without decoding, PDF/A does not allow them to be compressed.
+fn keep_metadata_uncompressed(doc: &mut Document) {
+    for object in doc.objects.values_mut() {
+        if let Object::Stream(stream) = object {
+            if stream.dict.type_is(b"Metadata") {
+                stream.decompress();
+                stream.allows_compression = false;
+            }
+        }
+    }
+}
+
+/// Replace the XMP property `name` (like `dc:title`) in `xmp`, or add it when it is missing.
+pub(crate) fn set_xmp_property(
+    xmp: &str,
+    name: &str,
+    namespace: &str,
+    kind: XmpKind,
+    value: &str,
+) -> String {
+    let value = escape_xml(value);
+    let element = match kind {
+        XmpKind::Text => format!("<{0}>{1}</{0}>", name, value),
+        XmpKind::LanguageAlternative => format!(
+            "<{0}><rdf:Alt><rdf:li xml:lang=\"x-default\">{1}</rdf:li></rdf:Alt></{0}>",
+            name, value
+        ),
+        XmpKind::Sequence => format!(
+            "<{0}><rdf:Seq><rdf:li>{1}</rdf:li></rdf:Seq></{0}>",
+            name, value
+        ),
+    };
+
+    let mut xmp = xmp.to_owned();
+    // The property can be written as element or as attribute of `rdf:Description`.
+    if let Some((start, end)) = find_xmp_element(&xmp, name) {
+        xmp.replace_range(start..end, &element);
+        return xmp;
+    }
+    if let Some((start, end)) = find_xmp_attribute(&xmp, name) {
+        xmp.replace_range(start..end, "");
+    }
+    let prefix = name.split(':').next().unwrap_or_default();
+    let description = format!(
+        "<rdf:Description rdf:about=\"\" xmlns:{}=\"{}\">{}</rdf:Description>",
+        prefix, namespace, element
+    );
+    match xmp.find("</rdf:RDF>") {
+        Some(position) => xmp.insert_str(position, &description),
+        None => tracing::warn!("PDF/A: XMP metadata has no `rdf:RDF` element."),
+    }
+    xmp
+}
+
+/// Find the start and end of the element `name`, including the start and end tag.
+fn find_xmp_element(xmp: &str, name: &str) -> Option<(usize, usize)> {
+    let start_tag = format!("<{}", name);
+    let mut search_start = 0;
+    while let Some(position) = xmp[search_start..].find(&start_tag) {
+        let start = search_start + position;
+        let after_name = start + start_tag.len();
+        search_start = after_name;
+        // Skip elements that only start with the same name, like `dc:titles`.
+        match xmp[after_name..].chars().next() {
+            Some('>') | Some('/') => {}
+            Some(character) if character.is_whitespace() => {}
+            _ => continue,
+        }
+        let tag_end = after_name + xmp[after_name..].find('>')?;
+        if xmp[..tag_end].ends_with('/') {
+            return Some((start, tag_end + 1));
+        }
+        let end_tag = format!("</{}>", name);
+        let end = tag_end + xmp[tag_end..].find(&end_tag)? + end_tag.len();
+        return Some((start, end));
+    }
+    None
+}
+
+/// Find the start (including the whitespace before it) and end of the attribute `name`.
+fn find_xmp_attribute(xmp: &str, name: &str) -> Option<(usize, usize)> {
+    for quote in ['"', '\''] {
+        let pattern = format!("{}={}", name, quote);
+        let mut search_start = 0;
+        while let Some(position) = xmp[search_start..].find(&pattern) {
+            let start = search_start + position;
+            search_start = start + pattern.len();
+            if !xmp[..start].ends_with(char::is_whitespace) {
+                continue;
+            }
+            let value_start = start + pattern.len();
+            let end = value_start + xmp[value_start..].find(quote)? + 1;
+            let start = xmp[..start].trim_end().len();
+            return Some((start, end));
+        }
+    }
+    None
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
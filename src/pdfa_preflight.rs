@@ -0,0 +1,422 @@
+//! Pre-flight check of the PDF/A-2b rules, so users can be warned before an archive
+//! rejects the document.
+//!
+//! Only the rules that can be checked on the objects are checked, the content streams
+//! (like the use of device dependent colors) are not parsed.
+
+use crate::PDFSigningDocument;
+use lopdf::{Dictionary, Object, ObjectId};
+use std::collections::BTreeSet;
+
+/// Actions that are not allowed in PDF/A-2.
+const FORBIDDEN_ACTIONS: [&str; 11] = [
+    "Launch",
+    "Sound",
+    "Movie",
+    "ResetForm",
+    "ImportData",
+    "Hide",
+    "SetOCGState",
+    "Rendition",
+    "Trans",
+    "GoTo3DView",
+    "JavaScript",
+];
+/// Annotation types that are not allowed in PDF/A-2.
+const FORBIDDEN_ANNOTATIONS: [&str; 4] = ["3D", "Sound", "Screen", "Movie"];
+/// Font types that need an embedded font program. `Type0` fonts use a descendant font
+/// and `Type3` fonts are defined in the document itself.
+const EMBEDDED_FONT_TYPES: [&str; 5] = [
+    "Type1",
+    "MMType1",
+    "TrueType",
+    "CIDFontType0",
+    "CIDFontType2",
+];
+
+/// The PDF/A rule that a `PdfaIssue` violates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PdfaRule {
+    /// The document is encrypted.
+    Encryption,
+    /// The trailer has no file identifier (`ID`).
+    FileIdentifier,
+    /// The XMP metadata is missing, compressed or does not identify the document as PDF/A.
+    Metadata,
+    /// The catalog has no PDF/A output intent (`GTS_PDFA1`).
+    OutputIntent,
+    /// A font program is not embedded.
+    FontNotEmbedded,
+    /// JavaScript, another forbidden action or additional actions (`AA`).
+    Action,
+    /// An annotation type that is not allowed, like `Sound` or `Movie`.
+    AnnotationType,
+    /// An annotation that is not printed or is hidden.
+    AnnotationFlags,
+    /// An annotation without normal appearance.
+    AnnotationAppearance,
+    /// The form asks the viewer to create the appearances (`NeedAppearances`).
+    NeedAppearances,
+    /// A stream uses the LZW filter or its data is in an external file.
+    StreamFilter,
+    /// An embedded file, these are only allowed when they are PDF/A documents themselves.
+    EmbeddedFile,
+    /// Changes made by this crate would break the conformance, like text in a standard font.
+    PlannedChange,
+}
+
+/// A violation of a PDF/A rule found by `check_pdfa`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PdfaIssue {
+    pub rule: PdfaRule,
+    /// The object with the issue, `None` for issues of the document (like the trailer).
+    pub object_id: Option<ObjectId>,
+    pub message: String,
+}
+
+impl PdfaIssue {
+    fn new(rule: PdfaRule, object_id: Option<ObjectId>, message: String) -> Self {
+        PdfaIssue {
+            rule,
+            object_id,
+            message,
+        }
+    }
+}
+
+impl PDFSigningDocument {
+    /// Check the document (including the changes that are not saved yet) against the
+    /// PDF/A-2b rules and return the issues found. An empty list does not guarantee
+    /// the document is conformant, because the content streams are not checked.
+    ///
+    /// The settings of this document are checked too: text written without an embedded
+    /// font (`set_embedded_font`) or without PDF/A mode (`set_pdfa_mode`) is reported
+    /// as `PdfaRule::PlannedChange`.
+    pub fn check_pdfa(&self) -> Vec<PdfaIssue> {
+        let mut issues = vec![];
+        self.check_pdfa_planned_changes(&mut issues);
+        self.check_pdfa_trailer(&mut issues);
+
+        let prev_document = self.raw_document.get_prev_documents();
+        let object_ids: BTreeSet<ObjectId> = prev_document
+            .objects
+            .keys()
+            .chain(self.raw_document.new_document.objects.keys())
+            .copied()
+            .collect();
+        for object_id in object_ids {
+            if let Some(object) = self.get_latest_object(object_id) {
+                self.check_pdfa_object(object_id, object, &mut issues);
+            }
+        }
+        issues
+    }
+
+    fn check_pdfa_planned_changes(&self, issues: &mut Vec<PdfaIssue>) {
+        if !self.pdfa_mode {
+            issues.push(PdfaIssue::new(
+                PdfaRule::PlannedChange,
+                None,
+                "PDF/A mode is not enabled, changes can break the conformance.".to_owned(),
+            ));
+        }
+        if self.embedded_font.is_none() {
+            issues.push(PdfaIssue::new(
+                PdfaRule::PlannedChange,
+                None,
+                "No embedded font is set, text would be written in a standard font.".to_owned(),
+            ));
+        }
+    }
+
+    fn check_pdfa_trailer(&self, issues: &mut Vec<PdfaIssue>) {
+        let trailer = &self.raw_document.new_document.trailer;
+        if trailer.has(b"Encrypt") {
+            issues.push(PdfaIssue::new(
+                PdfaRule::Encryption,
+                None,
+                "The document is encrypted.".to_owned(),
+            ));
+        }
+        if !trailer.has(b"ID") {
+            issues.push(PdfaIssue::new(
+                PdfaRule::FileIdentifier,
+                None,
+                "The trailer has no file identifier (`ID`).".to_owned(),
+            ));
+        }
+
+        let catalog = trailer
+            .get(b"Root")
+            .ok()
+            .and_then(|root| root.as_reference().ok());
+        let catalog_dict = catalog
+            .and_then(|catalog| self.get_latest_object(catalog))
+            .and_then(|catalog| catalog.as_dict().ok());
+        let catalog_dict = match catalog_dict {
+            Some(catalog_dict) => catalog_dict,
+            None => return,
+        };
+        self.check_pdfa_metadata(catalog, catalog_dict, issues);
+
+        let has_output_intent = catalog_dict
+            .get(b"OutputIntents")
+            .ok()
+            .and_then(|intents| self.resolve_latest_object(intents))
+            .and_then(|intents| intents.as_array().ok())
+            .map(|intents| {
+                intents.iter().any(|intent| {
+                    self.resolve_latest_object(intent)
+                        .and_then(|intent| intent.as_dict().ok())
+                        .and_then(|intent| intent.get(b"S").ok())
+                        .and_then(|subtype| subtype.as_name_str().ok())
+                        == Some("GTS_PDFA1")
+                })
+            })
+            .unwrap_or(false);
+        if !has_output_intent {
+            issues.push(PdfaIssue::new(
+                PdfaRule::OutputIntent,
+                catalog,
+                "The catalog has no PDF/A output intent (`GTS_PDFA1`).".to_owned(),
+            ));
+        }
+
+        let acro_form = catalog_dict
+            .get(b"AcroForm")
+            .ok()
+            .and_then(|acro_form| self.resolve_latest_object(acro_form))
+            .and_then(|acro_form| acro_form.as_dict().ok());
+        let need_appearances = acro_form
+            .and_then(|acro_form| acro_form.get(b"NeedAppearances").ok())
+            .and_then(|value| value.as_bool().ok())
+            .unwrap_or(false);
+        if need_appearances {
+            issues.push(PdfaIssue::new(
+                PdfaRule::NeedAppearances,
+                catalog,
+                "The form has `NeedAppearances` set to `true`.".to_owned(),
+            ));
+        }
+
+        let names = catalog_dict
+            .get(b"Names")
+            .ok()
+            .and_then(|names| self.resolve_latest_object(names))
+            .and_then(|names| names.as_dict().ok());
+        if let Some(names) = names {
+            if names.has(b"JavaScript") {
+                issues.push(PdfaIssue::new(
+                    PdfaRule::Action,
+                    catalog,
+                    "The document contains JavaScript.".to_owned(),
+                ));
+            }
+            if names.has(b"EmbeddedFiles") {
+                issues.push(PdfaIssue::new(
+                    PdfaRule::EmbeddedFile,
+                    catalog,
+                    "The document has embedded files, these should be PDF/A documents.".to_owned(),
+                ));
+            }
+        }
+    }
+
+    fn check_pdfa_metadata(
+        &self,
+        catalog: Option<ObjectId>,
+        catalog_dict: &Dictionary,
+        issues: &mut Vec<PdfaIssue>,
+    ) {
+        let metadata_id = catalog_dict
+            .get(b"Metadata")
+            .ok()
+            .and_then(|metadata| metadata.as_reference().ok());
+        let metadata = metadata_id
+            .and_then(|metadata_id| self.get_latest_object(metadata_id))
+            .and_then(|metadata| metadata.as_stream().ok());
+        let metadata = match metadata {
+            Some(metadata) => metadata,
+            None => {
+                issues.push(PdfaIssue::new(
+                    PdfaRule::Metadata,
+                    catalog,
+                    "The catalog has no XMP metadata.".to_owned(),
+                ));
+                return;
+            }
+        };
+        if metadata.dict.has(b"Filter") {
+            issues.push(PdfaIssue::new(
+                PdfaRule::Metadata,
+                metadata_id,
+                "The XMP metadata is compressed.".to_owned(),
+            ));
+        }
+        let xmp = metadata
+            .decompressed_content()
+            .unwrap_or_else(|_| metadata.content.clone());
+        if !String::from_utf8_lossy(&xmp).contains("pdfaid:part") {
+            issues.push(PdfaIssue::new(
+                PdfaRule::Metadata,
+                metadata_id,
+                "The XMP metadata does not identify the document as PDF/A (`pdfaid:part`)."
+                    .to_owned(),
+            ));
+        }
+    }
+
+    fn check_pdfa_object(&self, object_id: ObjectId, object: &Object, issues: &mut Vec<PdfaIssue>) {
+        match object {
+            Object::Dictionary(dict) => self.check_pdfa_dictionary(object_id, dict, issues),
+            Object::Stream(stream) => {
+                let filters = stream.filters().unwrap_or_default();
+                if filters.iter().any(|filter| filter == "LZWDecode") {
+                    issues.push(PdfaIssue::new(
+                        PdfaRule::StreamFilter,
+                        Some(object_id),
+                        "The stream uses the `LZWDecode` filter.".to_owned(),
+                    ));
+                }
+                if stream.dict.has(b"F") {
+                    issues.push(PdfaIssue::new(
+                        PdfaRule::StreamFilter,
+                        Some(object_id),
+                        "The data of the stream is in an external file.".to_owned(),
+                    ));
+                }
+                self.check_pdfa_dictionary(object_id, &stream.dict, issues);
+            }
+            Object::Array(array) => {
+                for item in array {
+                    self.check_pdfa_object(object_id, item, issues);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Check a dictionary and the direct objects inside it.
+    fn check_pdfa_dictionary(
+        &self,
+        object_id: ObjectId,
+        dict: &Dictionary,
+        issues: &mut Vec<PdfaIssue>,
+    ) {
+        let name = |key: &[u8]| dict.get(key).and_then(Object::as_name_str).ok();
+
+        if dict.has(b"AA") {
+            issues.push(PdfaIssue::new(
+                PdfaRule::Action,
+                Some(object_id),
+                "The object has additional actions (`AA`).".to_owned(),
+            ));
+        }
+        if let Some(action) = name(b"S").filter(|action| FORBIDDEN_ACTIONS.contains(action)) {
+            issues.push(PdfaIssue::new(
+                PdfaRule::Action,
+                Some(object_id),
+                format!("The action `{}` is not allowed.", action),
+            ));
+        }
+
+        if dict.type_is(b"Font") || (dict.has(b"BaseFont") && dict.has(b"Subtype")) {
+            self.check_pdfa_font(object_id, dict, issues);
+        } else if dict.has(b"Rect") && dict.has(b"Subtype") {
+            self.check_pdfa_annotation(object_id, dict, issues);
+        } else if dict.type_is(b"Filespec") && dict.has(b"EF") {
+            issues.push(PdfaIssue::new(
+                PdfaRule::EmbeddedFile,
+                Some(object_id),
+                "The file specification has an embedded file, it should be a PDF/A document."
+                    .to_owned(),
+            ));
+        }
+
+        for (_, value) in dict.iter() {
+            match value {
+                Object::Dictionary(_) | Object::Array(_) => {
+                    self.check_pdfa_object(object_id, value, issues)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_pdfa_font(&self, object_id: ObjectId, dict: &Dictionary, issues: &mut Vec<PdfaIssue>) {
+        let subtype = dict.get(b"Subtype").and_then(Object::as_name_str).ok();
+        if !subtype.is_some_and(|subtype| EMBEDDED_FONT_TYPES.contains(&subtype)) {
+            return;
+        }
+        let is_embedded = dict
+            .get(b"FontDescriptor")
+            .ok()
+            .and_then(|descriptor| self.resolve_latest_object(descriptor))
+            .and_then(|descriptor| descriptor.as_dict().ok())
+            .map(|descriptor| {
+                descriptor.has(b"FontFile")
+                    || descriptor.has(b"FontFile2")
+                    || descriptor.has(b"FontFile3")
+            })
+            .unwrap_or(false);
+        if !is_embedded {
+            let base_font = dict
+                .get(b"BaseFont")
+                .and_then(Object::as_name_str)
+                .unwrap_or("unknown");
+            issues.push(PdfaIssue::new(
+                PdfaRule::FontNotEmbedded,
+                Some(object_id),
+                format!("The font `{}` is not embedded.", base_font),
+            ));
+        }
+    }
+
+    fn check_pdfa_annotation(
+        &self,
+        object_id: ObjectId,
+        dict: &Dictionary,
+        issues: &mut Vec<PdfaIssue>,
+    ) {
+        let subtype = dict
+            .get(b"Subtype")
+            .and_then(Object::as_name_str)
+            .unwrap_or_default();
+        if FORBIDDEN_ANNOTATIONS.contains(&subtype) {
+            issues.push(PdfaIssue::new(
+                PdfaRule::AnnotationType,
+                Some(object_id),
+                format!("The annotation type `{}` is not allowed.", subtype),
+            ));
+            return;
+        }
+        if subtype == "Popup" {
+            return;
+        }
+
+        let flags = dict.get(b"F").and_then(Object::as_i64).unwrap_or(0);
+        // `Print` should be set, `Invisible`, `Hidden`, `NoView` and `ToggleNoView` not.
+        if flags & 4 == 0 || flags & (1 | 2 | 32 | 256) != 0 {
+            issues.push(PdfaIssue::new(
+                PdfaRule::AnnotationFlags,
+                Some(object_id),
+                format!("The `{}` annotation is hidden or not printed.", subtype),
+            ));
+        }
+
+        let has_appearance = dict
+            .get(b"AP")
+            .ok()
+            .and_then(|appearance| self.resolve_latest_object(appearance))
+            .and_then(|appearance| appearance.as_dict().ok())
+            .map(|appearance| appearance.has(b"N"))
+            .unwrap_or(false);
+        if subtype != "Link" && !has_appearance {
+            issues.push(PdfaIssue::new(
+                PdfaRule::AnnotationAppearance,
+                Some(object_id),
+                format!("The `{}` annotation has no normal appearance.", subtype),
+            ));
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Signatures that are paused after the digest is calculated, so the user can authorize
+//! the digest before it is signed (like the Sole Control Assurance Level 2 of qualified
+//! remote signing), see `begin_signature`.
+
+use crate::external_signer::{create_signed_attributes, create_signed_data, KeyAlgorithm};
+use crate::signing_options::{DigestAlgorithm, SigningOptions};
+#[cfg(feature = "network")]
+use crate::LtvPolicy;
+use crate::{Error, PDFSigningDocument};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A signature of which the digest is calculated, but that is not signed yet, created with
+/// `begin_signature`. The digest of `get_digest` is signed outside this crate, like by a
+/// remote signing service after the user authorized it, and the signature is added with
+/// `complete_signature`. With the feature `serde` it can be stored (like in a file) between
+/// the two steps, the bytes are base64 encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct PendingSignature {
+    field_name: String,
+    signer_name: String,
+    /// The document with the signature placeholder.
+    #[cfg_attr(feature = "serde", serde(with = "base64_bytes"))]
+    document: Vec<u8>,
+    placeholder_size: usize,
+    digest_algorithm: DigestAlgorithm,
+    /// The DER encoded certificate of the signer, followed by the certificates of its
+    /// issuers.
+    #[cfg_attr(feature = "serde", serde(with = "base64_list"))]
+    certificates: Vec<Vec<u8>>,
+    /// The DER encoded signed attributes of the CMS signature, their digest is signed.
+    #[cfg_attr(feature = "serde", serde(with = "base64_bytes"))]
+    signed_attributes: Vec<u8>,
+}
+
+impl PendingSignature {
+    /// The fully qualified name of the signature field.
+    pub fn get_field_name(&self) -> &str {
+        &self.field_name
+    }
+
+    /// The digest that should be authorized and signed, the digest of the signed attributes
+    /// of the CMS signature.
+    pub fn get_digest(&self) -> Vec<u8> {
+        self.digest_algorithm.digest(&self.signed_attributes)
+    }
+
+    /// The digest algorithm of the `SigningOptions`, that the signer should use.
+    pub fn get_digest_algorithm(&self) -> DigestAlgorithm {
+        self.digest_algorithm
+    }
+}
+
+impl PDFSigningDocument {
+    /// Prepare the empty signature field `field_name` to be signed by the key of the
+    /// DER encoded `certificates` (the certificate of the signer, followed by the
+    /// certificates of its issuers), and calculate the digest to sign. See
+    /// `prepare_signature` for the other arguments.
+    /// This document is not changed, add the signature with `complete_signature`.
+    pub fn begin_signature(
+        &mut self,
+        field_name: &str,
+        signer_name: &str,
+        image: Option<&[u8]>,
+        options: &SigningOptions,
+        certificates: &[Vec<u8>],
+    ) -> Result<PendingSignature, Error> {
+        options.check_offline()?;
+        #[cfg(not(feature = "network"))]
+        if !options.time_stamp_urls.is_empty() {
+            return Err(Error::from(
+                "Signature: Time stamps of external signers need the feature `network`.",
+            ));
+        }
+        let certificate = certificates
+            .first()
+            .filter(|certificate| KeyAlgorithm::from_certificate(certificate).is_some())
+            .ok_or_else(|| {
+                Error::from(
+                    "Signature: The signer certificate is not valid, or its key is not RSA or ECDSA.",
+                )
+            })?;
+
+        let prepared_signature = self.prepare_signature(field_name, signer_name, image, options)?;
+        let digest_algorithm = options.digest_algorithm;
+        let signed_attributes = create_signed_attributes(
+            certificate,
+            &digest_algorithm.digest(&prepared_signature.get_signed_bytes()?),
+        );
+        Ok(PendingSignature {
+            field_name: field_name.to_owned(),
+            signer_name: signer_name.to_owned(),
+            document: prepared_signature.data,
+            placeholder_size: prepared_signature.placeholder_size,
+            digest_algorithm,
+            certificates: certificates.to_vec(),
+            signed_attributes,
+        })
+    }
+
+    /// Add the `signature` of the digest of `pending_signature` (PKCS #1 v1.5 for RSA keys,
+    /// a DER encoded `Ecdsa-Sig-Value` for ECDSA keys) and create the CMS signature.
+    /// Time stamps and OCSP responses are added as set in `options`, use the same options
+    /// as `begin_signature`.
+    /// Return the signed document, this document is reloaded with the signature.
+    pub fn complete_signature(
+        &mut self,
+        pending_signature: PendingSignature,
+        signature: &[u8],
+        options: &SigningOptions,
+    ) -> Result<Vec<u8>, Error> {
+        options.check_offline()?;
+        let PendingSignature {
+            field_name,
+            signer_name,
+            document,
+            placeholder_size,
+            digest_algorithm,
+            certificates,
+            signed_attributes,
+        } = pending_signature;
+        // Fail before the signature is created and time stamped.
+        PDFSigningDocument::check_placeholder_size(placeholder_size, 0, document.len())?;
+        let signed_data = create_signed_data(
+            &certificates,
+            digest_algorithm,
+            &signed_attributes,
+            signature,
+        )?;
+        #[cfg(feature = "network")]
+        let signed_data = match options.time_stamp_urls.is_empty() {
+            true => signed_data,
+            false => crate::time_stamp::add_time_stamp(
+                &signed_data,
+                &options.time_stamp_urls,
+                options.time_stamp_timeout,
+                &options.http_client,
+            )?,
+        };
+        let data = self.load_signed_document(
+            PDFSigningDocument::set_content(document, signed_data, placeholder_size)?,
+            &field_name,
+            &signer_name,
+        )?;
+
+        #[cfg(feature = "network")]
+        if let LtvPolicy::FetchOcsp(cache) = &options.ltv_policy {
+            return self.add_ocsp_responses(&[&field_name], cache, &options.http_client);
+        }
+        Ok(data)
+    }
+}
+
+/// Serialize bytes as a base64 string.
+#[cfg(feature = "serde")]
+mod base64_bytes {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        base64::decode(String::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+/// Serialize a list of bytes as a list of base64 strings.
+#[cfg(feature = "serde")]
+mod base64_list {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(list: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(list.iter().map(base64::encode))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Vec<u8>>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|bytes| base64::decode(bytes).map_err(D::Error::custom))
+            .collect()
+    }
+}
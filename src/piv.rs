@@ -0,0 +1,482 @@
+//! Sign with the key on a smartcard with the PIV applet, like government-issued cards,
+//! through PC/SC (feature `pcsc`), see `PivSigner::builder`.
+
+use crate::der::{read_der, write_der};
+use crate::external_signer::{ExternalSigner, KeyAlgorithm};
+use crate::yubikey::TouchCallback;
+use crate::{DigestAlgorithm, Error};
+use flate2::read::GzDecoder;
+use std::fmt;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+
+/// The application identifier of the PIV applet.
+const PIV_AID: &[u8] = &[0xa0, 0x00, 0x00, 0x03, 0x08, 0x00, 0x00, 0x10, 0x00];
+/// The class, instruction and parameters of the commands, `VERIFY` is for the PIN.
+const SELECT: [u8; 4] = [0x00, 0xa4, 0x04, 0x00];
+const GET_DATA: [u8; 4] = [0x00, 0xcb, 0x3f, 0xff];
+const VERIFY: [u8; 4] = [0x00, 0x20, 0x00, 0x80];
+const GET_RESPONSE: [u8; 4] = [0x00, 0xc0, 0x00, 0x00];
+
+/// A key slot of the PIV applet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PivSlot {
+    /// `9A`: PIV authentication, the PIN is verified once per session.
+    Authentication,
+    /// `9C`: digital signature, the PIN is verified before every signature.
+    #[default]
+    Signature,
+    /// `9D`: key management.
+    KeyManagement,
+    /// `9E`: card authentication, without PIN.
+    CardAuthentication,
+}
+
+impl PivSlot {
+    /// When the card needs the PIN for the key of the slot, by default.
+    fn get_pin_policy(self) -> PinPolicy {
+        match self {
+            PivSlot::Signature => PinPolicy::Always,
+            PivSlot::CardAuthentication => PinPolicy::Never,
+            _ => PinPolicy::Once,
+        }
+    }
+
+    /// The reference of the key in the `GENERAL AUTHENTICATE` command.
+    pub(crate) fn key_reference(self) -> u8 {
+        match self {
+            PivSlot::Authentication => 0x9a,
+            PivSlot::Signature => 0x9c,
+            PivSlot::KeyManagement => 0x9d,
+            PivSlot::CardAuthentication => 0x9e,
+        }
+    }
+
+    /// The tag of the data object with the certificate of the key.
+    fn certificate_tag(self) -> [u8; 3] {
+        match self {
+            PivSlot::Authentication => [0x5f, 0xc1, 0x05],
+            PivSlot::Signature => [0x5f, 0xc1, 0x0a],
+            PivSlot::KeyManagement => [0x5f, 0xc1, 0x0b],
+            PivSlot::CardAuthentication => [0x5f, 0xc1, 0x01],
+        }
+    }
+}
+
+/// When the card needs the PIN for a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PinPolicy {
+    Never,
+    /// Once per session.
+    Once,
+    /// Before every signature.
+    Always,
+}
+
+/// The question for the PIN of a smartcard, see `PinCallback`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinRequest {
+    /// The name of the reader with the card.
+    pub reader: String,
+    /// The number of wrong PINs that are allowed before the PIN is blocked, when the card
+    /// tells it.
+    pub tries_left: Option<u8>,
+}
+
+/// Asks the user for the PIN of a smartcard, like with a dialog. After a wrong PIN it is
+/// called again with the tries that are left. Return `None` to stop signing
+/// (`Error::PinCancelled`).
+pub type PinCallback = dyn Fn(&PinRequest) -> Option<String> + Send + Sync;
+
+/// Signs with the key in a slot of a PIV smartcard, through PC/SC. Use it with
+/// `sign_field_with_signer`, the PIN is asked with the `PinCallback` when the card needs it.
+pub struct PivSigner {
+    card: Mutex<pcsc::Card>,
+    reader: String,
+    slot: PivSlot,
+    /// The certificate of the slot, followed by the certificates of the issuers.
+    certificates: Vec<Vec<u8>>,
+    key_algorithm: KeyAlgorithm,
+    pin_callback: Option<Arc<PinCallback>>,
+    pub(crate) pin_policy: PinPolicy,
+    /// Called before the card waits for a touch, see `YubiKeySigner`.
+    pub(crate) touch_callback: Option<Arc<TouchCallback>>,
+}
+
+impl PivSigner {
+    pub fn builder() -> PivSignerBuilder {
+        PivSignerBuilder {
+            reader: None,
+            slot: PivSlot::default(),
+            issuer_certificates: vec![],
+            pin_callback: None,
+        }
+    }
+
+    /// Get the names of the smartcard readers.
+    pub fn list_readers() -> Result<Vec<String>, Error> {
+        let context = pcsc::Context::establish(pcsc::Scope::User).map_err(pcsc_error)?;
+        Ok(context
+            .list_readers_owned()
+            .map_err(pcsc_error)?
+            .iter()
+            .map(|reader| reader.to_string_lossy().into_owned())
+            .collect())
+    }
+
+    /// Get the name of the reader with the card.
+    pub fn get_reader(&self) -> &str {
+        &self.reader
+    }
+
+    /// Get the DER encoded certificate of the key.
+    pub fn get_certificate(&self) -> &[u8] {
+        &self.certificates[0]
+    }
+
+    /// Send a command to the card, see `transmit`.
+    pub(crate) fn transmit(
+        &self,
+        header: [u8; 4],
+        data: &[u8],
+        expect_response: bool,
+    ) -> Result<(Vec<u8>, u16), Error> {
+        let card = self.card.lock().unwrap_or_else(|error| error.into_inner());
+        transmit(&card, header, data, expect_response)
+    }
+
+    /// Verify the PIN, it is asked with the PIN callback. It is not asked when the PIN is
+    /// verified already, except when the key needs it for every signature.
+    fn verify_pin(&self, card: &pcsc::Card) -> Result<(), Error> {
+        if self.pin_policy == PinPolicy::Never {
+            return Ok(());
+        }
+        let (_, status) = transmit(card, VERIFY, &[], false)?;
+        let mut tries_left = match status {
+            0x9000 if self.pin_policy == PinPolicy::Once => return Ok(()),
+            0x6983 | 0x63c0 => return Err(Error::PinBlocked),
+            status if status & 0xfff0 == 0x63c0 => Some((status & 0x0f) as u8),
+            _ => None,
+        };
+        loop {
+            let pin_callback = self.pin_callback.as_ref().ok_or(Error::PinCancelled)?;
+            let pin = pin_callback(&PinRequest {
+                reader: self.reader.clone(),
+                tries_left,
+            })
+            .ok_or(Error::PinCancelled)?;
+            if pin.len() > 8 {
+                return Err(Error::from("PIV: The PIN is longer than 8 characters."));
+            }
+            // The PIN is padded with `0xFF` to 8 bytes.
+            let mut data = pin.into_bytes();
+            data.resize(8, 0xff);
+            let (_, status) = transmit(card, VERIFY, &data, false)?;
+            match status {
+                0x9000 => return Ok(()),
+                0x6983 | 0x63c0 => return Err(Error::PinBlocked),
+                status if status & 0xfff0 == 0x63c0 => tries_left = Some((status & 0x0f) as u8),
+                status => {
+                    return Err(Error::Other(format!(
+                        "PIV: Verifying the PIN failed with status {:04X}.",
+                        status
+                    )))
+                }
+            }
+        }
+    }
+}
+
+impl ExternalSigner for PivSigner {
+    fn get_certificates(&self) -> Result<Vec<Vec<u8>>, Error> {
+        Ok(self.certificates.clone())
+    }
+
+    fn sign_digest(
+        &self,
+        digest: &[u8],
+        digest_algorithm: DigestAlgorithm,
+    ) -> Result<Vec<u8>, Error> {
+        let unsupported_key = || Error::from("PIV: The key of the slot is not supported.");
+        // The card does the RSA operation only, the digest is padded here. For ECDSA the
+        // digest is cut to the length of the key, or padded with zeros.
+        let (algorithm, challenge) = match self.key_algorithm {
+            KeyAlgorithm::Rsa { modulus_length } => {
+                let algorithm = match modulus_length {
+                    128 => 0x06,
+                    256 => 0x07,
+                    384 => 0x05,
+                    512 => 0x16,
+                    _ => return Err(unsupported_key()),
+                };
+                let challenge = encode_pkcs1(digest, digest_algorithm, modulus_length)?;
+                (algorithm, challenge)
+            }
+            KeyAlgorithm::Ecdsa { key_length } => {
+                let algorithm = match key_length {
+                    32 => 0x11,
+                    48 => 0x14,
+                    _ => return Err(unsupported_key()),
+                };
+                let mut challenge = vec![0; key_length.saturating_sub(digest.len())];
+                challenge.extend_from_slice(&digest[..digest.len().min(key_length)]);
+                (algorithm, challenge)
+            }
+        };
+
+        // The card stays locked for other applications between the PIN and the signature.
+        let mut card = self.card.lock().unwrap_or_else(|error| error.into_inner());
+        let transaction = card.transaction().map_err(pcsc_error)?;
+        self.verify_pin(&transaction)?;
+        if let Some(touch_callback) = &self.touch_callback {
+            touch_callback();
+        }
+        // The dynamic authentication template: the response is asked for the challenge.
+        let template = write_der(
+            0x7c,
+            &[&[0x82, 0x00][..], &write_der(0x81, &challenge)].concat(),
+        );
+        let header = [0x00, 0x87, algorithm, self.slot.key_reference()];
+        let (response, status) = transmit(&transaction, header, &template, true)?;
+        match status {
+            0x9000 => {}
+            0x6982 => return Err(Error::from(
+                "PIV: The card did not sign, the PIN is not verified or the card was not touched.",
+            )),
+            status => {
+                return Err(Error::Other(format!(
+                    "PIV: Signing failed with status {:04X}.",
+                    status
+                )))
+            }
+        }
+        match read_der(&response).and_then(|(_, template, _)| read_der(template)) {
+            Some((0x82, signature, _)) => Ok(signature.to_vec()),
+            _ => Err(Error::from("PIV: Invalid signature of the card.")),
+        }
+    }
+}
+
+impl fmt::Debug for PivSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PivSigner")
+            .field("reader", &self.reader)
+            .field("slot", &self.slot)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Builder for `PivSigner`.
+#[derive(Clone)]
+pub struct PivSignerBuilder {
+    reader: Option<String>,
+    slot: PivSlot,
+    issuer_certificates: Vec<Vec<u8>>,
+    pin_callback: Option<Arc<PinCallback>>,
+}
+
+impl PivSignerBuilder {
+    /// Use the card in the reader that has `name` in its name, by default the card of the
+    /// first reader with a PIV card is used.
+    pub fn reader(mut self, name: &str) -> Self {
+        self.reader = Some(name.to_owned());
+        self
+    }
+
+    /// Sign with the key in `slot`, by default `PivSlot::Signature`.
+    pub fn slot(mut self, slot: PivSlot) -> Self {
+        self.slot = slot;
+        self
+    }
+
+    /// Add the DER encoded certificate of an issuer of the certificate of the key to the
+    /// signatures, the card only has the certificate of the key.
+    pub fn issuer_certificate(mut self, certificate: Vec<u8>) -> Self {
+        self.issuer_certificates.push(certificate);
+        self
+    }
+
+    /// Ask the PIN with `pin_callback`. Without it signing fails with
+    /// `Error::PinCancelled` when the card needs the PIN.
+    pub fn pin_callback(mut self, pin_callback: Arc<PinCallback>) -> Self {
+        self.pin_callback = Some(pin_callback);
+        self
+    }
+
+    /// Connect to the card and read the certificate of the slot.
+    pub fn build(self) -> Result<PivSigner, Error> {
+        let context = pcsc::Context::establish(pcsc::Scope::User).map_err(pcsc_error)?;
+        let mut errors = vec![];
+        for reader in context.list_readers_owned().map_err(pcsc_error)? {
+            let name = reader.to_string_lossy().into_owned();
+            if let Some(filter) = &self.reader {
+                if !name.contains(filter.as_str()) {
+                    continue;
+                }
+            }
+            let card = match context.connect(&reader, pcsc::ShareMode::Shared, pcsc::Protocols::ANY)
+            {
+                Ok(card) => card,
+                Err(error) => {
+                    errors.push(format!("`{}`: {}", name, error));
+                    continue;
+                }
+            };
+            let (_, status) = transmit(&card, SELECT, PIV_AID, true)?;
+            if status != 0x9000 {
+                errors.push(format!("`{}`: The card has no PIV applet.", name));
+                continue;
+            }
+            let certificate = read_certificate(&card, self.slot)?;
+            let key_algorithm = KeyAlgorithm::from_certificate(&certificate)
+                .ok_or_else(|| Error::from("PIV: The key of the slot is not supported."))?;
+            let mut certificates = vec![certificate];
+            certificates.extend(self.issuer_certificates);
+            return Ok(PivSigner {
+                card: Mutex::new(card),
+                reader: name,
+                slot: self.slot,
+                certificates,
+                key_algorithm,
+                pin_callback: self.pin_callback,
+                pin_policy: self.slot.get_pin_policy(),
+                touch_callback: None,
+            });
+        }
+        match errors.is_empty() {
+            true => Err(Error::from("PIV: No smartcard reader found.")),
+            false => Err(Error::Other(format!(
+                "PIV: No PIV card found: {}",
+                errors.join(", ")
+            ))),
+        }
+    }
+}
+
+impl fmt::Debug for PivSignerBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PivSignerBuilder")
+            .field("reader", &self.reader)
+            .field("slot", &self.slot)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Read the DER encoded certificate of the key in `slot`.
+fn read_certificate(card: &pcsc::Card, slot: PivSlot) -> Result<Vec<u8>, Error> {
+    let (response, status) = transmit(
+        card,
+        GET_DATA,
+        &write_der(0x5c, &slot.certificate_tag()),
+        true,
+    )?;
+    if status != 0x9000 {
+        return Err(Error::Other(format!(
+            "PIV: Slot {:02X} has no certificate.",
+            slot.key_reference()
+        )));
+    }
+    let invalid_certificate = || Error::from("PIV: Invalid certificate of the slot.");
+    let Some((0x53, mut object, _)) = read_der(&response) else {
+        return Err(invalid_certificate());
+    };
+    // The certificate and the certificate info, that tells if it is compressed.
+    let mut certificate = None;
+    let mut compressed = false;
+    while let Some((tag, content, next)) = read_der(object) {
+        match tag {
+            0x70 => certificate = Some(content),
+            0x71 => compressed = content.first() == Some(&1),
+            _ => {}
+        }
+        object = next;
+    }
+    let certificate = certificate.ok_or_else(invalid_certificate)?;
+    if !compressed {
+        return Ok(certificate.to_vec());
+    }
+    let mut data = vec![];
+    GzDecoder::new(certificate)
+        .read_to_end(&mut data)
+        .map_err(|_| invalid_certificate())?;
+    Ok(data)
+}
+
+/// Encode the `digest` for a PKCS #1 v1.5 signature with an RSA key of `length` bytes:
+/// the `DigestInfo`, padded with `0xFF`.
+fn encode_pkcs1(
+    digest: &[u8],
+    digest_algorithm: DigestAlgorithm,
+    length: usize,
+) -> Result<Vec<u8>, Error> {
+    let algorithm = write_der(
+        0x30,
+        &[&write_der(0x06, digest_algorithm.oid())[..], &[0x05, 0x00]].concat(),
+    );
+    let digest_info = write_der(0x30, &[algorithm, write_der(0x04, digest)].concat());
+    if digest_info.len() + 11 > length {
+        return Err(Error::from("PIV: The RSA key is too short for the digest."));
+    }
+    let mut encoded = vec![0x00, 0x01];
+    encoded.resize(length - digest_info.len() - 1, 0xff);
+    encoded.push(0x00);
+    encoded.extend(digest_info);
+    Ok(encoded)
+}
+
+/// Send the command `header` (class, instruction and parameters) with `data` to the card.
+/// Data longer than 255 bytes is sent in parts (command chaining), and the parts of
+/// long responses are collected. Returns the response and the status word.
+pub(crate) fn transmit(
+    card: &pcsc::Card,
+    header: [u8; 4],
+    data: &[u8],
+    expect_response: bool,
+) -> Result<(Vec<u8>, u16), Error> {
+    let mut parts: Vec<&[u8]> = data.chunks(255).collect();
+    if parts.is_empty() {
+        parts.push(&[]);
+    }
+    let mut response = vec![];
+    let mut status = 0;
+    for (index, part) in parts.iter().enumerate() {
+        let last = index + 1 == parts.len();
+        let mut command = header.to_vec();
+        if !last {
+            command[0] |= 0x10;
+        }
+        if !part.is_empty() {
+            command.push(part.len() as u8);
+            command.extend_from_slice(part);
+        }
+        if last && expect_response {
+            command.push(0x00);
+        }
+        status = send(card, &command, &mut response)?;
+        // `61XX`: more bytes of the response are available.
+        while status >> 8 == 0x61 {
+            let mut command = GET_RESPONSE.to_vec();
+            command.push(status as u8);
+            status = send(card, &command, &mut response)?;
+        }
+        if status != 0x9000 {
+            break;
+        }
+    }
+    Ok((response, status))
+}
+
+/// Send 1 command, add the data of the response to `response` and return the status word.
+fn send(card: &pcsc::Card, command: &[u8], response: &mut Vec<u8>) -> Result<u16, Error> {
+    let mut buffer = [0; pcsc::MAX_BUFFER_SIZE];
+    let data = card.transmit(command, &mut buffer).map_err(pcsc_error)?;
+    let [data @ .., status_1, status_2] = data else {
+        return Err(Error::from("PIV: Invalid response of the card."));
+    };
+    response.extend_from_slice(data);
+    Ok(u16::from_be_bytes([*status_1, *status_2]))
+}
+
+fn pcsc_error(error: pcsc::Error) -> Error {
+    Error::Other(format!("PC/SC: {}", error))
+}
@@ -0,0 +1,167 @@
+//! Signatures that are signed outside this crate, like with WebCrypto in the browser
+//! or with a hardware token, see `prepare_signature`.
+
+use crate::journal::JournalChange;
+use crate::rectangle::Rectangle;
+use crate::signing_options::{DigestAlgorithm, SigningOptions};
+use crate::{ByteRange, Error, InsertImage, PDFSigningDocument};
+use lopdf::{dictionary, ObjectId, Stream};
+use std::future::Future;
+
+/// A document with a signature placeholder, created with `prepare_signature`.
+/// Sign the bytes of `get_signed_bytes` (detached CMS signature) and add the
+/// signature with `finish`.
+#[derive(Debug, Clone)]
+pub struct PreparedSignature {
+    pub(crate) data: Vec<u8>,
+    byte_range: ByteRange,
+    pub(crate) placeholder_size: usize,
+    digest_algorithm: DigestAlgorithm,
+}
+
+impl PreparedSignature {
+    /// The document with the placeholder, the `Contents` of the signature are still empty.
+    pub fn get_document_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The `ByteRange` of the signature: the offset and length of the bytes before
+    /// and after the `Contents`.
+    pub fn get_byte_range(&self) -> &[usize] {
+        &self.byte_range.0
+    }
+
+    /// The bytes that should be signed, the document without the `Contents`.
+    pub fn get_signed_bytes(&self) -> Result<Vec<u8>, Error> {
+        PDFSigningDocument::get_signed_bytes(&self.data, &self.byte_range)
+    }
+
+    /// The digest algorithm of the `SigningOptions`, that the signer should use.
+    pub fn get_digest_algorithm(&self) -> DigestAlgorithm {
+        self.digest_algorithm
+    }
+
+    /// Add the DER encoded signature (CMS `SignedData`) and return the signed document.
+    /// Returns `Error::PlaceholderTooSmall` when the signature does not fit.
+    pub fn finish(self, signature: Vec<u8>) -> Result<Vec<u8>, Error> {
+        PDFSigningDocument::set_content(self.data, signature, self.placeholder_size)
+    }
+}
+
+impl PDFSigningDocument {
+    /// Prepare the empty signature field `field_name` to be signed outside this crate.
+    /// `signer_name` is the name in the signature and `image` (PNG, JPEG, TIFF or WebP, SVG
+    /// with `set_image_allow_svg`) its appearance, without an image the signature is not
+    /// visible.
+    /// This document is not changed, load the document of `PreparedSignature::finish`.
+    pub fn prepare_signature(
+        &mut self,
+        field_name: &str,
+        signer_name: &str,
+        image: Option<&[u8]>,
+        options: &SigningOptions,
+    ) -> Result<PreparedSignature, Error> {
+        let _span = tracing::info_span!(
+            "prepare_signature",
+            file_name = %self.file_name,
+            field_name,
+        )
+        .entered();
+        self.load_all()?;
+        let form_field = self.get_form_field(field_name)?;
+        if !form_field.is_empty_signature() {
+            return Err(Error::UnsupportedFieldType {
+                name: field_name.to_owned(),
+                expected: "signature field that is not signed yet".to_owned(),
+            });
+        }
+        let form_object_id = form_field
+            .get_object_id()
+            .ok_or_else(|| Error::from("AcroForm object is not a indirect reference."))?;
+
+        let mut document = self.clone();
+        document.set_signing_version(options)?;
+        let rect = document.get_rectangle_from_form(form_object_id)?;
+        let appearance_id = match image {
+            Some(image) => {
+                let image_options =
+                    (options.appearance.clone()).unwrap_or_else(|| self.image_options.clone());
+                document.add_image_as_form_xobject(image, "Signature", rect, &image_options)?
+            }
+            None => document.add_empty_appearance(&rect),
+        };
+        document.add_general_info_to_signature(
+            form_object_id,
+            appearance_id,
+            signer_name,
+            "",
+            options,
+        )?;
+
+        let mut data = Vec::new();
+        document.write_document(&mut data)?;
+        let (byte_range, data) = Self::set_next_byte_range(data, options.placeholder_size)?;
+        Ok(PreparedSignature {
+            data,
+            byte_range,
+            placeholder_size: options.placeholder_size,
+            digest_algorithm: options.digest_algorithm,
+        })
+    }
+
+    /// Sign the empty signature field `field_name` with an asynchronous `signer`, like
+    /// WebCrypto in the browser. The signer gets the bytes to sign and returns the
+    /// DER encoded signature, see `prepare_signature` for the other arguments.
+    /// Return the signed document, this document is reloaded with the signature.
+    pub async fn sign_field_with<F, Fut>(
+        &mut self,
+        field_name: &str,
+        signer_name: &str,
+        image: Option<&[u8]>,
+        options: &SigningOptions,
+        signer: F,
+    ) -> Result<Vec<u8>, Error>
+    where
+        F: FnOnce(Vec<u8>) -> Fut,
+        Fut: Future<Output = Result<Vec<u8>, Error>>,
+    {
+        let prepared_signature = self.prepare_signature(field_name, signer_name, image, options)?;
+        let signature = signer(prepared_signature.get_signed_bytes()?).await?;
+        self.load_signed_document(
+            prepared_signature.finish(signature)?,
+            field_name,
+            signer_name,
+        )
+    }
+
+    /// Reload this document from the `data` of a signed prepared signature, and add the
+    /// signature of `field_name` to the journal. Returns the `data`.
+    pub(crate) fn load_signed_document(
+        &mut self,
+        data: Vec<u8>,
+        field_name: &str,
+        signer_name: &str,
+    ) -> Result<Vec<u8>, Error> {
+        self.copy_from(self.reload_from(data.clone(), self.file_name.clone())?);
+        self.load_all()?;
+        self.record_change(JournalChange::SignatureAdded {
+            field_name: field_name.to_owned(),
+            user_id: String::new(),
+            user_name: signer_name.to_owned(),
+            user_email: String::new(),
+        });
+        Ok(data)
+    }
+
+    /// Add an empty appearance with the size of `rect`, for invisible signatures.
+    fn add_empty_appearance(&mut self, rect: &Rectangle) -> ObjectId {
+        self.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => vec![0.into(), 0.into(), rect.width().into(), rect.height().into()],
+            },
+            vec![],
+        ))
+    }
+}
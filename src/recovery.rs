@@ -0,0 +1,431 @@
+//! Recover documents with a damaged cross-reference table, by scanning the file for
+//! objects (`1 0 obj`) like most viewers do.
+
+use crate::limits::decode_bounded;
+use crate::{Error, ParsingLimits};
+use lopdf::xref::XrefEntry;
+use lopdf::Document;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Keys of the trailer that are copied to the rebuilt trailer.
+const TRAILER_KEYS: [&[u8]; 4] = [b"/Root", b"/Info", b"/Encrypt", b"/ID"];
+
+/// Check if objects of the cross-reference table could not be loaded or the catalog is missing.
+pub(crate) fn is_damaged(doc: &Document) -> bool {
+    if doc.catalog().is_err() {
+        return true;
+    }
+    // Object streams of encrypted documents can only be loaded after decrypting.
+    if doc.trailer.has(b"Encrypt") {
+        return false;
+    }
+    doc.reference_table
+        .entries
+        .iter()
+        .any(|(id, entry)| match entry {
+            XrefEntry::Normal { generation, .. } => !doc.objects.contains_key(&(*id, *generation)),
+            _ => false,
+        })
+}
+
+/// Rebuild the cross-reference table from the objects found in `data`.
+/// The new table and trailer are appended, so the existing bytes (and signatures) are kept.
+/// Fails when more objects are found than the `max_objects` of the `limits`.
+pub(crate) fn rebuild_xref(data: &[u8], limits: &ParsingLimits) -> Result<Vec<u8>, Error> {
+    let objects = find_objects(data);
+    if objects.is_empty() {
+        return Err(Error::from("Recovery: No objects found."));
+    }
+    // Objects in object streams are loaded by lopdf from the object stream,
+    // they only need to be known here to find the catalog and the size.
+    let compressed = find_compressed_objects(data, &objects, limits);
+    let ids: BTreeSet<u32> = objects.keys().chain(compressed.keys()).copied().collect();
+    if let Some(maximum) = limits.max_objects {
+        if ids.len() > maximum {
+            return Err(Error::TooManyObjects {
+                count: ids.len(),
+                maximum,
+            });
+        }
+    }
+
+    let mut trailer = find_trailer_entries(data, &objects);
+    let has_root = trailer
+        .iter()
+        .any(|(key, value)| key == b"/Root" && is_existing_reference(value, &ids));
+    if !has_root {
+        let catalog = objects
+            .iter()
+            .rev()
+            .filter(|(_, (offset, _))| !is_object_stream(get_dict(data, *offset)))
+            .find(|(_, (offset, _))| {
+                is_catalog(&data[*offset..find(data, b"endobj", *offset).unwrap_or(data.len())])
+            })
+            .map(|(id, (_, generation))| (*id, *generation))
+            .or_else(|| {
+                compressed
+                    .iter()
+                    .rev()
+                    .find(|(_, object)| is_catalog(object))
+                    .map(|(id, _)| (*id, 0))
+            });
+        let (catalog_id, catalog_generation) =
+            catalog.ok_or_else(|| Error::from("Recovery: No catalog found."))?;
+        trailer.retain(|(key, _)| key != b"/Root");
+        trailer.push((
+            b"/Root".to_vec(),
+            format!("{} {} R", catalog_id, catalog_generation).into_bytes(),
+        ));
+    }
+    tracing::warn!(
+        "Recovery: Rebuilt the cross-reference table with {} objects.",
+        ids.len()
+    );
+
+    let size = ids.iter().last().map(|id| id + 1).unwrap_or(1);
+    let mut repaired = data.to_vec();
+    repaired.push(b'\n');
+    let xref_start = repaired.len();
+    // One subsection for every run of consecutive objects, the other objects are free
+    // or in object streams.
+    repaired.extend_from_slice(b"xref\n0 1\n0000000000 65535 f \n");
+    let mut entries = objects.iter().peekable();
+    while let Some((first_id, _)) = entries.peek() {
+        let first_id = **first_id;
+        let mut subsection = String::new();
+        let mut count = 0;
+        while let Some((_, (offset, generation))) =
+            entries.next_if(|(id, _)| Some(**id) == first_id.checked_add(count))
+        {
+            subsection.push_str(&format!("{:010} {:05} n \n", offset, generation));
+            count += 1;
+        }
+        repaired.extend_from_slice(format!("{} {}\n{}", first_id, count, subsection).as_bytes());
+    }
+    repaired.extend_from_slice(format!("trailer\n<</Size {}", size).as_bytes());
+    for (key, value) in trailer {
+        repaired.extend_from_slice(&key);
+        repaired.push(b' ');
+        repaired.extend_from_slice(&value);
+    }
+    repaired.extend_from_slice(format!(">>\nstartxref\n{}\n%%EOF\n", xref_start).as_bytes());
+    Ok(repaired)
+}
+
+/// Find the offset and generation of all objects. When an object is found more than once,
+/// the last one is used, like in an incremental update.
+pub(crate) fn find_objects(data: &[u8]) -> BTreeMap<u32, (usize, u16)> {
+    let mut objects = BTreeMap::new();
+    let mut position = 0;
+    while let Some(keyword) = find(data, b"obj", position) {
+        position = keyword + 3;
+        let (id, generation, offset) = match parse_object_header(data, keyword) {
+            Some(header) => header,
+            None => continue,
+        };
+        // Every object takes at least a byte of the file, a larger id is garbage and
+        // would make the rebuilt table huge.
+        if id as usize > data.len() {
+            continue;
+        }
+        objects.insert(id, (offset, generation));
+
+        // Skip the stream data, it can contain anything (like an embedded PDF file).
+        let end = find(data, b"endobj", position).unwrap_or(data.len());
+        let stream = match find(&data[..end], b"stream", position) {
+            Some(stream) => stream,
+            None => continue,
+        };
+        let stream_start = stream + 6;
+        position = match get_direct_length(&data[position..stream]) {
+            Some(length) if stream_start + length <= data.len() => stream_start + length,
+            _ => find(data, b"endstream", stream_start).unwrap_or(data.len()),
+        };
+    }
+    objects
+}
+
+/// Parse `id generation obj` before the `obj` keyword at `keyword`,
+/// return the id, generation and the offset of the object.
+fn parse_object_header(data: &[u8], keyword: usize) -> Option<(u32, u16, usize)> {
+    let is_delimiter = |byte: &u8| byte.is_ascii_whitespace() || b"<>[]()/%".contains(byte);
+    if data.get(keyword + 3).map(|byte| !is_delimiter(byte)) == Some(true) {
+        return None;
+    }
+    let (generation, generation_start) = parse_number_before(data, keyword)?;
+    let (id, id_start) = parse_number_before(data, generation_start)?;
+    if id_start > 0 && !is_delimiter(&data[id_start - 1]) {
+        return None;
+    }
+    Some((id.try_into().ok()?, generation.try_into().ok()?, id_start))
+}
+
+/// Parse the number before `end`, separated by whitespace. Return the number and its start.
+fn parse_number_before(data: &[u8], end: usize) -> Option<(u64, usize)> {
+    let digits_end = end
+        - data[..end]
+            .iter()
+            .rev()
+            .take_while(|byte| byte.is_ascii_whitespace())
+            .count();
+    if digits_end == end {
+        return None;
+    }
+    let digits_start = digits_end
+        - data[..digits_end]
+            .iter()
+            .rev()
+            .take_while(|byte| byte.is_ascii_digit())
+            .count();
+    let number = std::str::from_utf8(&data[digits_start..digits_end])
+        .ok()?
+        .parse()
+        .ok()?;
+    Some((number, digits_start))
+}
+
+/// Find the objects in the object streams of `objects`, return the id and the data
+/// of every object. Object streams with `DecodeParms` are skipped.
+/// The decoded size is limited by the `max_stream_size` of the `limits`.
+fn find_compressed_objects(
+    data: &[u8],
+    objects: &BTreeMap<u32, (usize, u16)>,
+    limits: &ParsingLimits,
+) -> BTreeMap<u32, Vec<u8>> {
+    let mut compressed = BTreeMap::new();
+    for (id, (offset, _)) in objects {
+        let dict = get_dict(data, *offset);
+        if !is_object_stream(dict) {
+            continue;
+        }
+        let content = match get_stream_content(data, *offset, dict) {
+            Some(content) if find(dict, b"/DecodeParms", 0).is_none() => content,
+            _ => {
+                tracing::warn!("Recovery: Object stream {} can not be read.", id);
+                continue;
+            }
+        };
+        let limit = limits.max_stream_size.unwrap_or(usize::MAX);
+        let content = decode_bounded(content, &get_filters(dict), limit);
+        let (count, first) = match (
+            get_direct_number(dict, b"/N"),
+            get_direct_number(dict, b"/First"),
+        ) {
+            (Some(count), Some(first)) if first <= content.len() => (count, first),
+            _ => continue,
+        };
+        let header: Vec<usize> = content[..first]
+            .split(|byte| byte.is_ascii_whitespace())
+            .filter(|number| !number.is_empty())
+            .map_while(|number| std::str::from_utf8(number).ok()?.parse().ok())
+            .take(count.saturating_mul(2))
+            .collect();
+        for (index, pair) in header.chunks_exact(2).enumerate() {
+            let end = header
+                .get(index * 2 + 3)
+                .map(|next| first.saturating_add(*next))
+                .unwrap_or(content.len())
+                .min(content.len());
+            let start = first.saturating_add(pair[1]).min(end);
+            if let Ok(object_id) = u32::try_from(pair[0]) {
+                if pair[0] <= data.len() {
+                    compressed.insert(object_id, content[start..end].to_vec());
+                }
+            }
+        }
+    }
+    // Objects found in the file are used instead of the ones in object streams.
+    compressed.retain(|id, _| !objects.contains_key(id));
+    compressed
+}
+
+/// Get the (encoded) data of the stream at `offset` with the dictionary `dict`,
+/// `None` when the object is not a stream.
+pub(crate) fn get_stream_content<'a>(
+    data: &'a [u8],
+    offset: usize,
+    dict: &[u8],
+) -> Option<&'a [u8]> {
+    let stream = offset + dict.len();
+    if !data[stream..].starts_with(b"stream") {
+        return None;
+    }
+    let mut start = stream + 6;
+    if data.get(start) == Some(&b'\r') {
+        start += 1;
+    }
+    if data.get(start) == Some(&b'\n') {
+        start += 1;
+    }
+    let end = match get_direct_length(dict) {
+        Some(length) if start.saturating_add(length) <= data.len() => start + length,
+        _ => find(data, b"endstream", start).unwrap_or(data.len()),
+    };
+    data.get(start..end)
+}
+
+/// Get the names of the `Filter` of a stream dictionary, like `FlateDecode`.
+/// Empty when the stream has no filters or they are an indirect reference.
+pub(crate) fn get_filters(dict: &[u8]) -> Vec<&[u8]> {
+    let Some(key) = find(dict, b"/Filter", 0) else {
+        return vec![];
+    };
+    let value = &dict[key + 7..];
+    let value = &value[value
+        .iter()
+        .take_while(|byte| byte.is_ascii_whitespace())
+        .count()..];
+    let names = match value.first() {
+        Some(b'[') => {
+            &value[1..value
+                .iter()
+                .position(|byte| *byte == b']')
+                .unwrap_or(value.len())]
+        }
+        Some(b'/') => {
+            let end = value[1..]
+                .iter()
+                .position(|byte| byte.is_ascii_whitespace() || b"/<>[]".contains(byte))
+                .map(|end| end + 1)
+                .unwrap_or(value.len());
+            &value[..end]
+        }
+        _ => return vec![],
+    };
+    names
+        .split(|byte| byte.is_ascii_whitespace() || *byte == b'/')
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Get the dictionary of the object at `offset`, up to the `stream` or `endobj` keyword.
+pub(crate) fn get_dict(data: &[u8], offset: usize) -> &[u8] {
+    let end = [&b"stream"[..], b"endobj"]
+        .iter()
+        .filter_map(|keyword| find(data, keyword, offset))
+        .min()
+        .unwrap_or(data.len());
+    &data[offset..end]
+}
+
+pub(crate) fn is_object_stream(dict: &[u8]) -> bool {
+    find(dict, b"/Type/ObjStm", 0).is_some() || find(dict, b"/Type /ObjStm", 0).is_some()
+}
+
+fn is_catalog(object: &[u8]) -> bool {
+    find(object, b"/Type/Catalog", 0).is_some() || find(object, b"/Type /Catalog", 0).is_some()
+}
+
+/// Get the `Length` of a stream dictionary, when it is not an indirect reference.
+fn get_direct_length(dict: &[u8]) -> Option<usize> {
+    get_direct_number(dict, b"/Length")
+}
+
+/// Get the number of `key` in the dictionary, when it is not an indirect reference.
+pub(crate) fn get_direct_number(dict: &[u8], key: &[u8]) -> Option<usize> {
+    let mut position = 0;
+    while let Some(found) = find(dict, key, position) {
+        position = found + key.len();
+        // Skip `/Length1` (and others) of font files.
+        if dict.get(position).map(u8::is_ascii_alphanumeric) == Some(true) {
+            continue;
+        }
+        let mut values = dict[position..]
+            .split(|byte| byte.is_ascii_whitespace() || b"/>".contains(byte))
+            .filter(|value| !value.is_empty());
+        let length = std::str::from_utf8(values.next()?).ok()?.parse().ok()?;
+        // `/Length 12 0 R`
+        let is_reference = values.nth(1) == Some(&b"R"[..]);
+        return match is_reference {
+            true => None,
+            false => Some(length),
+        };
+    }
+    None
+}
+
+/// Find the entries of the trailer in the `trailer` dictionaries and the cross-reference
+/// streams. The last value of every key is used.
+fn find_trailer_entries(
+    data: &[u8],
+    objects: &BTreeMap<u32, (usize, u16)>,
+) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let mut trailers: Vec<usize> = vec![];
+    let mut position = 0;
+    while let Some(trailer) = find(data, b"trailer", position) {
+        trailers.push(trailer);
+        position = trailer + 7;
+    }
+    for (offset, _) in objects.values() {
+        let dict = get_dict(data, *offset);
+        if find(dict, b"/Type/XRef", 0).is_some() || find(dict, b"/Type /XRef", 0).is_some() {
+            trailers.push(*offset);
+        }
+    }
+    trailers.sort_unstable();
+
+    let mut entries: Vec<(Vec<u8>, Vec<u8>)> = vec![];
+    for trailer in trailers {
+        let end = [&b"startxref"[..], b"stream"]
+            .iter()
+            .filter_map(|keyword| find(data, keyword, trailer))
+            .min()
+            .unwrap_or(data.len());
+        let dict = &data[trailer..end];
+        for key in TRAILER_KEYS {
+            if let Some(value) = get_trailer_value(dict, key) {
+                entries.retain(|(existing_key, _)| existing_key != key);
+                entries.push((key.to_vec(), value));
+            }
+        }
+    }
+    entries
+}
+
+/// Get the value of a reference (`1 0 R`) or an array of strings (`ID`) in the dictionary.
+fn get_trailer_value(dict: &[u8], key: &[u8]) -> Option<Vec<u8>> {
+    let start = find(dict, key, 0)? + key.len();
+    let value = &dict[start..];
+    match value.iter().find(|byte| !byte.is_ascii_whitespace())? {
+        b'[' => {
+            let end = value.iter().position(|byte| *byte == b']')? + 1;
+            Some(value[..end].to_vec())
+        }
+        _ => {
+            let end = value.iter().position(|byte| *byte == b'R')? + 1;
+            let reference = std::str::from_utf8(&value[..end]).ok()?;
+            let parts: Vec<&str> = reference.split_whitespace().collect();
+            let is_reference = parts.len() == 3
+                && parts[..2]
+                    .iter()
+                    .all(|part| part.bytes().all(|byte| byte.is_ascii_digit()));
+            is_reference.then(|| reference.trim().as_bytes().to_vec())
+        }
+    }
+}
+
+/// A trailer or cross-reference stream of the file has an `Encrypt` entry.
+/// `Encrypt` dictionaries that are not a reference are not found.
+pub(crate) fn has_encrypt(data: &[u8], objects: &BTreeMap<u32, (usize, u16)>) -> bool {
+    find_trailer_entries(data, objects)
+        .iter()
+        .any(|(key, _)| key == b"/Encrypt")
+}
+
+fn is_existing_reference(value: &[u8], ids: &BTreeSet<u32>) -> bool {
+    let id = std::str::from_utf8(value)
+        .ok()
+        .and_then(|value| value.split_whitespace().next())
+        .and_then(|id| id.parse::<u32>().ok());
+    id.map(|id| ids.contains(&id)).unwrap_or(false)
+}
+
+pub(crate) fn find(data: &[u8], pattern: &[u8], start: usize) -> Option<usize> {
+    if start >= data.len() {
+        return None;
+    }
+    data[start..]
+        .windows(pattern.len())
+        .position(|window| window == pattern)
+        .map(|position| start + position)
+}
@@ -1,7 +1,41 @@
-#[derive(Debug, Clone, Default)]
+use crate::Error;
+use lopdf::Object;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct Rectangle {
     pub x1: f64,
     pub y1: f64,
     pub x2: f64,
     pub y2: f64,
 }
+
+impl Rectangle {
+    /// Create a rectangle from a `Rect` array. Values can be integers or reals.
+    pub(crate) fn from_array(rect: &[Object]) -> Result<Self, Error> {
+        if rect.len() < 4 {
+            return Err(Error::from("Rectangle should contain 4 values."));
+        }
+        let value = |object: &Object| {
+            object
+                .as_f64()
+                .or_else(|_| object.as_i64().map(|value| value as f64))
+        };
+        Ok(Rectangle {
+            x1: value(&rect[0])?,
+            y1: value(&rect[1])?,
+            x2: value(&rect[2])?,
+            y2: value(&rect[3])?,
+        })
+    }
+
+    pub fn width(&self) -> f64 {
+        (self.x2 - self.x1).abs()
+    }
+
+    pub fn height(&self) -> f64 {
+        (self.y2 - self.y1).abs()
+    }
+}
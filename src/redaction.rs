@@ -0,0 +1,381 @@
+//! Redact areas of pages: the text and images below the areas are removed from the
+//! content streams, not only covered, so the data can not be copied or extracted.
+
+use crate::image_replace::{multiply, transform_unit_square};
+use crate::journal::JournalChange;
+use crate::pdf_object::PdfObjectDeref;
+use crate::rectangle::Rectangle;
+use crate::text::{
+    get_numbers, load_fonts, ContentState, ShownGlyph, ShownText, IDENTITY, MAX_FORM_DEPTH,
+};
+use crate::{Error, PDFSigningDocument};
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use std::collections::{BTreeMap, HashSet};
+
+impl PDFSigningDocument {
+    /// Redact `areas`, given as page index (starting at 0) and rectangle in the coordinates
+    /// of the page. The text and images that overlap an area are removed from the content,
+    /// the areas are painted black and get a redaction annotation (`Redact`).
+    ///
+    /// Text is removed per character, images and form XObjects that can not be changed are
+    /// removed completely when they overlap an area. Vector graphics are only covered
+    /// and annotations (like form fields) are not changed.
+    /// The document is rewritten without its earlier versions (including the changes that
+    /// are not saved yet), so existing signatures become invalid: redact before signing.
+    pub fn redact(&mut self, areas: &[(usize, Rectangle)]) -> Result<(), Error> {
+        let mut doc = self.get_latest_document();
+        let page_ids: Vec<ObjectId> = doc.get_pages().into_values().collect();
+        let mut page_areas: BTreeMap<usize, Vec<Rectangle>> = BTreeMap::new();
+        for (page_index, rect) in areas {
+            page_areas.entry(*page_index).or_default().push(Rectangle {
+                x1: rect.x1.min(rect.x2),
+                y1: rect.y1.min(rect.y2),
+                x2: rect.x1.max(rect.x2),
+                y2: rect.y1.max(rect.y2),
+            });
+        }
+        for (page_index, areas) in page_areas {
+            let page_id = *page_ids.get(page_index).ok_or_else(|| {
+                Error::Other(format!("Page with index `{}` not found.", page_index))
+            })?;
+            redact_page(&mut doc, page_id, &areas)?;
+        }
+        // Remove the original content streams and the images that are not used anymore.
+        doc.prune_objects();
+        self.reload_document(doc)?;
+        for (page_index, rect) in areas {
+            self.record_change(JournalChange::Redacted {
+                page_index: *page_index,
+                rect: rect.clone(),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn redact_page(doc: &mut Document, page_id: ObjectId, areas: &[Rectangle]) -> Result<(), Error> {
+    let mut resources = get_inherited_dictionary(doc, page_id, b"Resources");
+    let mut content_data = vec![];
+    for content_id in doc.get_page_contents(page_id) {
+        if let Ok(stream) = doc.get_object(content_id).and_then(Object::as_stream) {
+            content_data.extend(
+                stream
+                    .decompressed_content()
+                    .unwrap_or_else(|_| stream.content.clone()),
+            );
+            // Operators can not continue in the next stream.
+            content_data.push(b'\n');
+        }
+    }
+    let operations = Content::decode(&content_data)?.operations;
+    let redacted = redact_operations(doc, operations, &mut resources, IDENTITY, areas, 0)?;
+
+    let mut operations = vec![Operation::new("q", vec![])];
+    operations.extend(redacted);
+    operations.push(Operation::new("Q", vec![]));
+    operations.push(Operation::new("q", vec![]));
+    operations.push(Operation::new("g", vec![0.into()]));
+    for area in areas {
+        operations.push(Operation::new(
+            "re",
+            vec![
+                area.x1.into(),
+                area.y1.into(),
+                area.width().into(),
+                area.height().into(),
+            ],
+        ));
+    }
+    operations.push(Operation::new("f", vec![]));
+    operations.push(Operation::new("Q", vec![]));
+    let mut content = Stream::new(dictionary! {}, Content { operations }.encode()?);
+    let _ = content.compress();
+    let content_id = doc.add_object(content);
+
+    let mut annotations = match doc.get_dictionary(page_id)?.get(b"Annots") {
+        Ok(annotations) => annotations.deref(doc)?.as_array()?.clone(),
+        Err(_) => vec![],
+    };
+    for area in areas {
+        annotations.push(Object::Reference(add_redact_annotation(doc, page_id, area)));
+    }
+
+    let page = doc.get_object_mut(page_id)?.as_dict_mut()?;
+    page.set("Contents", Object::Reference(content_id));
+    page.set("Resources", resources);
+    page.set("Annots", annotations);
+    Ok(())
+}
+
+/// Add a redaction annotation that shows `area` as black box.
+fn add_redact_annotation(doc: &mut Document, page_id: ObjectId, area: &Rectangle) -> ObjectId {
+    let appearance = Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => vec![0.into(), 0.into(), area.width().into(), area.height().into()],
+        },
+        format!("0 g 0 0 {} {} re f", area.width(), area.height()).into_bytes(),
+    );
+    let appearance_id = doc.add_object(appearance);
+    doc.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Redact",
+        "Rect" => vec![area.x1.into(), area.y1.into(), area.x2.into(), area.y2.into()],
+        "QuadPoints" => vec![
+            area.x1.into(), area.y2.into(),
+            area.x2.into(), area.y2.into(),
+            area.x1.into(), area.y1.into(),
+            area.x2.into(), area.y1.into(),
+        ],
+        "IC" => vec![0.into(), 0.into(), 0.into()],
+        "F" => 4,
+        "P" => Object::Reference(page_id),
+        "AP" => dictionary! { "N" => Object::Reference(appearance_id) },
+    })
+}
+
+/// Get a copy of the (inherited) dictionary `key` of the page, references are resolved.
+fn get_inherited_dictionary(doc: &Document, page_id: ObjectId, key: &[u8]) -> Dictionary {
+    let mut node_id = Some(page_id);
+    // Limit the depth, so a loop in the page tree does not hang.
+    for _ in 0..32 {
+        let node = match node_id.and_then(|node_id| doc.get_dictionary(node_id).ok()) {
+            Some(node) => node,
+            None => break,
+        };
+        if let Ok(value) = node.get(key) {
+            return value
+                .deref(doc)
+                .and_then(|value| Ok(value.as_dict()?))
+                .cloned()
+                .unwrap_or_default();
+        }
+        node_id = node.get(b"Parent").and_then(Object::as_reference).ok();
+    }
+    Dictionary::new()
+}
+
+/// Get a copy of the resource category (like `XObject`) in `resources`.
+fn get_resource_category(doc: &Document, resources: &Dictionary, category: &[u8]) -> Dictionary {
+    resources
+        .get(category)
+        .ok()
+        .and_then(|category| category.deref(doc).ok())
+        .and_then(|category| category.as_dict().ok())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Remove the text and images in `areas` from `operations`, with `ctm` as the initial
+/// transformation matrix. Redacted copies of form XObjects are added to `resources`
+/// and the XObjects that are not used anymore are removed from it.
+fn redact_operations(
+    doc: &mut Document,
+    operations: Vec<Operation>,
+    resources: &mut Dictionary,
+    ctm: [f64; 6],
+    areas: &[Rectangle],
+    depth: usize,
+) -> Result<Vec<Operation>, Error> {
+    let parent_resources = resources.clone();
+    let fonts = load_fonts(Some(&parent_resources), &|object| object.deref(doc).ok());
+    let mut xobjects = get_resource_category(doc, resources, b"XObject");
+    let mut content_state = ContentState::new(ctm, fonts);
+    let mut redacted = Vec::with_capacity(operations.len());
+
+    for operation in operations {
+        content_state.apply(&operation);
+        match operation.operator.as_str() {
+            "Tj" | "TJ" | "'" | "\"" => {
+                let scale = content_state.text_scale();
+                let shown_texts = content_state.show_text(&operation);
+                match redact_text(shown_texts, scale, areas) {
+                    Some(elements) => {
+                        if let ("\"", [word_spacing, char_spacing, ..]) =
+                            (operation.operator.as_str(), operation.operands.as_slice())
+                        {
+                            redacted.push(Operation::new("Tw", vec![word_spacing.clone()]));
+                            redacted.push(Operation::new("Tc", vec![char_spacing.clone()]));
+                        }
+                        if operation.operator == "'" || operation.operator == "\"" {
+                            redacted.push(Operation::new("T*", vec![]));
+                        }
+                        redacted.push(Operation::new("TJ", vec![elements.into()]));
+                    }
+                    None => redacted.push(operation),
+                }
+            }
+            "Do" => {
+                if let Some(operation) = redact_xobject(
+                    doc,
+                    operation,
+                    &parent_resources,
+                    &mut xobjects,
+                    content_state.ctm(),
+                    areas,
+                    depth,
+                )? {
+                    redacted.push(operation);
+                }
+            }
+            _ => redacted.push(operation),
+        }
+    }
+
+    // Remove the XObjects that are only drawn in the redacted areas.
+    let used_xobjects: HashSet<Vec<u8>> = redacted
+        .iter()
+        .filter(|operation| operation.operator == "Do")
+        .filter_map(|operation| operation.operands.first())
+        .filter_map(|name| name.as_name().ok())
+        .map(|name| name.to_vec())
+        .collect();
+    let names: Vec<Vec<u8>> = xobjects.iter().map(|(name, _)| name.clone()).collect();
+    for name in names {
+        if !used_xobjects.contains(&name) {
+            xobjects.remove(&name);
+        }
+    }
+    if resources.has(b"XObject") {
+        resources.set("XObject", xobjects);
+    }
+    Ok(redacted)
+}
+
+/// Get the `TJ` array without the characters that overlap an area, `None` when no
+/// characters overlap. The removed characters are replaced with a position adjustment,
+/// so the other characters stay at the same position.
+fn redact_text(
+    shown_texts: Vec<ShownText>,
+    scale: f64,
+    areas: &[Rectangle],
+) -> Option<Vec<Object>> {
+    let is_redacted =
+        |glyph: &ShownGlyph| scale != 0.0 && areas.iter().any(|area| overlaps(area, &glyph.rect));
+    let redacted = shown_texts.iter().any(|shown_text| match shown_text {
+        ShownText::Glyph(glyph) => is_redacted(glyph),
+        ShownText::Adjustment(_) => false,
+    });
+    if !redacted {
+        return None;
+    }
+
+    let mut elements = vec![];
+    let mut kept = vec![];
+    for shown_text in shown_texts {
+        match shown_text {
+            ShownText::Glyph(glyph) if is_redacted(&glyph) => {
+                if !kept.is_empty() {
+                    elements.push(Object::string_literal(std::mem::take(&mut kept)));
+                }
+                elements.push((-glyph.advance * 1000.0 / scale).into());
+            }
+            ShownText::Glyph(glyph) => kept.extend(glyph.code),
+            ShownText::Adjustment(adjustment) => {
+                if !kept.is_empty() {
+                    elements.push(Object::string_literal(std::mem::take(&mut kept)));
+                }
+                elements.push(adjustment);
+            }
+        }
+    }
+    if !kept.is_empty() {
+        elements.push(Object::string_literal(kept));
+    }
+    Some(elements)
+}
+
+/// Redact the XObject drawn by the `Do` operation: images that overlap an area are removed,
+/// form XObjects are replaced with a redacted copy. Return the operation to keep, if any.
+fn redact_xobject(
+    doc: &mut Document,
+    operation: Operation,
+    resources: &Dictionary,
+    xobjects: &mut Dictionary,
+    ctm: [f64; 6],
+    areas: &[Rectangle],
+    depth: usize,
+) -> Result<Option<Operation>, Error> {
+    let name = match operation
+        .operands
+        .first()
+        .and_then(|name| name.as_name().ok())
+    {
+        Some(name) => name.to_vec(),
+        None => return Ok(Some(operation)),
+    };
+    let xobject = match xobjects
+        .get(&name)
+        .ok()
+        .and_then(|xobject| xobject.deref(doc).ok())
+        .and_then(|xobject| xobject.as_stream().ok())
+    {
+        Some(xobject) => xobject.clone(),
+        None => return Ok(Some(operation)),
+    };
+    let overlaps_area = |rect: &Rectangle| areas.iter().any(|area| overlaps(area, rect));
+
+    match xobject.dict.get(b"Subtype").and_then(Object::as_name_str) {
+        Ok("Image") => match overlaps_area(&transform_unit_square(&ctm)) {
+            true => Ok(None),
+            false => Ok(Some(operation)),
+        },
+        Ok("Form") => {
+            let form_matrix = match get_numbers(&xobject.dict, b"Matrix")[..] {
+                [a, b, c, d, e, f] => [a, b, c, d, e, f],
+                _ => IDENTITY,
+            };
+            let form_ctm = multiply(&form_matrix, &ctm);
+            if let [x1, y1, x2, y2] = get_numbers(&xobject.dict, b"BBox")[..] {
+                let bbox_matrix = [x2 - x1, 0.0, 0.0, y2 - y1, x1, y1];
+                if !overlaps_area(&transform_unit_square(&multiply(&bbox_matrix, &form_ctm))) {
+                    return Ok(Some(operation));
+                }
+            }
+            if depth >= MAX_FORM_DEPTH {
+                return Ok(None);
+            }
+
+            // Forms without resources use the resources of the page.
+            let mut form_resources = match xobject.dict.get(b"Resources") {
+                Ok(form_resources) => form_resources.deref(doc)?.as_dict()?.clone(),
+                Err(_) => resources.clone(),
+            };
+            let content = xobject
+                .decompressed_content()
+                .unwrap_or_else(|_| xobject.content.clone());
+            let operations = Content::decode(&content)?.operations;
+            let operations = redact_operations(
+                doc,
+                operations,
+                &mut form_resources,
+                form_ctm,
+                areas,
+                depth + 1,
+            )?;
+
+            let mut form_dict = xobject.dict.clone();
+            for key in [&b"Filter"[..], b"DecodeParms", b"Length"] {
+                form_dict.remove(key);
+            }
+            form_dict.set("Resources", form_resources);
+            let mut form = Stream::new(form_dict, Content { operations }.encode()?);
+            let _ = form.compress();
+            let form_id = doc.add_object(form);
+            let form_name = format!("Redacted{}", form_id.0);
+            xobjects.set(form_name.clone(), Object::Reference(form_id));
+            Ok(Some(Operation::new(
+                "Do",
+                vec![Object::Name(form_name.into_bytes())],
+            )))
+        }
+        _ => Ok(Some(operation)),
+    }
+}
+
+/// Check if the rectangles overlap, rectangles that only touch do not overlap.
+fn overlaps(first: &Rectangle, second: &Rectangle) -> bool {
+    first.x1 < second.x2 && second.x1 < first.x2 && first.y1 < second.y2 && second.y1 < first.y2
+}
@@ -0,0 +1,246 @@
+//! Compare the incremental revisions of a document, like the revisions before and after
+//! a signature, to show what changed.
+
+use crate::acro_form::AcroForm;
+use crate::linearize::write_object;
+use crate::lopdf_utils::decode_text_string;
+use crate::recovery::find;
+use crate::{encryption, Error, PDFSigningDocument, ParsingLimits};
+use lopdf::{Dictionary, Document, IncrementalDocument, Object, ObjectId};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What happened to an object between 2 revisions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    Added,
+    Changed,
+    Removed,
+}
+
+/// An object that is added, changed or removed between 2 revisions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectChange {
+    pub kind: ChangeKind,
+    pub object_id: ObjectId,
+    /// The `Subtype` of annotations (like `Widget` or `Link`), the `Type` of other objects.
+    pub object_type: Option<String>,
+}
+
+/// A form field with a different value (`V`) between 2 revisions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldValueChange {
+    /// The fully qualified name of the field.
+    /// Signature fields are not compared, see the signature dictionaries in `other`.
+    pub field_name: String,
+    /// The value in the first revision, `None` when it is not set or the field is missing.
+    pub old_value: Option<String>,
+    /// The value in the second revision, `None` when it is not set or the field is removed.
+    pub new_value: Option<String>,
+}
+
+/// The differences between 2 revisions, grouped by the type of the objects.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RevisionDiff {
+    /// Annotations, including the widgets of form fields.
+    pub annotations: Vec<ObjectChange>,
+    /// Form fields with a different value.
+    pub field_values: Vec<FieldValueChange>,
+    /// Pages, a page is changed when for example an annotation or content is added.
+    pub pages: Vec<ObjectChange>,
+    /// All other objects, like content streams, fonts and the catalog.
+    pub other: Vec<ObjectChange>,
+}
+
+impl RevisionDiff {
+    /// Check if the revisions are the same.
+    pub fn is_empty(&self) -> bool {
+        self.annotations.is_empty()
+            && self.field_values.is_empty()
+            && self.pages.is_empty()
+            && self.other.is_empty()
+    }
+}
+
+impl PDFSigningDocument {
+    /// Get the number of revisions of the document: the original document and
+    /// the incremental updates that are saved. The changes that are not saved yet are not counted.
+    pub fn get_revision_count(&self) -> usize {
+        get_revision_ends(self.get_prev_documents_bytes()).len()
+    }
+
+    /// Compare the revisions with index `a` and `b` (0 is the original document, see
+    /// `get_revision_count`), the changes are from `a` to `b`.
+    /// Cross-reference streams and object streams are not compared.
+    pub fn diff_revisions(&self, a: usize, b: usize) -> Result<RevisionDiff, Error> {
+        let ends = get_revision_ends(self.get_prev_documents_bytes());
+        let load = |index: usize| match ends.get(index) {
+            Some(end) => self.load_revision(*end),
+            None => Err(Error::Other(format!(
+                "Revision with index `{}` not found.",
+                index
+            ))),
+        };
+        let (doc_a, doc_b) = (load(a)?, load(b)?);
+
+        let mut diff = RevisionDiff::default();
+        let object_ids: BTreeSet<ObjectId> = doc_a
+            .objects
+            .keys()
+            .chain(doc_b.objects.keys())
+            .copied()
+            .collect();
+        for object_id in object_ids {
+            let (object_a, object_b) =
+                (doc_a.objects.get(&object_id), doc_b.objects.get(&object_id));
+            let (kind, object) = match (object_a, object_b) {
+                (None, Some(object)) => (ChangeKind::Added, object),
+                (Some(object), None) => (ChangeKind::Removed, object),
+                (Some(object_a), Some(object_b)) if !is_equal(object_a, object_b) => {
+                    (ChangeKind::Changed, object_b)
+                }
+                _ => continue,
+            };
+            let dict = match object {
+                Object::Dictionary(dict) => Some(dict),
+                Object::Stream(stream) => Some(&stream.dict),
+                _ => None,
+            };
+            let name = |key: &[u8]| {
+                dict.and_then(|dict| dict.get(key).ok())
+                    .and_then(|name| name.as_name_str().ok())
+                    .map(str::to_owned)
+            };
+            let change = |object_type| ObjectChange {
+                kind,
+                object_id,
+                object_type,
+            };
+            match dict {
+                Some(dict) if dict.type_is(b"XRef") || dict.type_is(b"ObjStm") => {}
+                Some(dict) if is_annotation(dict) => {
+                    diff.annotations.push(change(name(b"Subtype")))
+                }
+                Some(dict) if dict.type_is(b"Page") => diff.pages.push(change(name(b"Type"))),
+                _ => diff.other.push(change(name(b"Type"))),
+            }
+        }
+
+        let (values_a, values_b) = (get_field_values(&doc_a), get_field_values(&doc_b));
+        let field_names: BTreeSet<&String> = values_a.keys().chain(values_b.keys()).collect();
+        for field_name in field_names {
+            let old_value = values_a.get(field_name).cloned().flatten();
+            let new_value = values_b.get(field_name).cloned().flatten();
+            if old_value != new_value {
+                diff.field_values.push(FieldValueChange {
+                    field_name: field_name.clone(),
+                    old_value,
+                    new_value,
+                });
+            }
+        }
+        Ok(diff)
+    }
+
+    /// Load the revision that ends at `end`, decrypted with the key of the document.
+    pub(crate) fn load_revision(&self, end: usize) -> Result<Document, Error> {
+        let data = &self.get_prev_documents_bytes()[..end];
+        let raw_document = match &self.security_handler {
+            Some(security_handler) => {
+                encryption::reload_encrypted_document(data.to_vec(), security_handler)?
+            }
+            None => IncrementalDocument::load_from(data)?,
+        };
+        Ok(raw_document.get_prev_documents().clone())
+    }
+}
+
+/// Find the end of every revision: the `%%EOF` after a `startxref` that points into the file.
+/// The first part of a linearized file (`startxref 0`) is not a revision.
+pub(crate) fn get_revision_ends(data: &[u8]) -> Vec<usize> {
+    let mut ends = vec![];
+    let mut position = 0;
+    while let Some(found) = find(data, b"%%EOF", position) {
+        position = found + 5;
+        let start = found.saturating_sub(32);
+        let startxref = match find(&data[start..found], b"startxref", 0) {
+            Some(startxref) => start + startxref + 9,
+            None => continue,
+        };
+        let offset = std::str::from_utf8(&data[startxref..found])
+            .ok()
+            .and_then(|offset| offset.trim().parse::<usize>().ok());
+        if !matches!(offset, Some(offset) if offset > 0 && offset < found) {
+            continue;
+        }
+        // Include the end of line after `%%EOF`.
+        let mut end = position;
+        while end < data.len() && (data[end] == b'\r' || data[end] == b'\n') {
+            end += 1;
+        }
+        ends.push(end);
+    }
+    ends
+}
+
+/// Compare 2 objects by writing them, lopdf objects can not be compared directly.
+fn is_equal(object_a: &Object, object_b: &Object) -> bool {
+    let (mut data_a, mut data_b) = (vec![], vec![]);
+    // Objects that can not be written (a real that is not finite) are different.
+    write_object(&mut data_a, object_a).is_ok()
+        && write_object(&mut data_b, object_b).is_ok()
+        && data_a == data_b
+}
+
+/// Annotations do not always have a `Type`, but they always have a `Subtype` and `Rect`.
+fn is_annotation(dict: &Dictionary) -> bool {
+    dict.type_is(b"Annot") || (dict.has(b"Subtype") && dict.has(b"Rect") && !dict.has(b"Type"))
+}
+
+/// Get the value of every form field as text, by the fully qualified name of the field.
+fn get_field_values(doc: &Document) -> BTreeMap<String, Option<String>> {
+    let forms =
+        AcroForm::load_all_forms(doc, ParsingLimits::default().max_depth).unwrap_or_default();
+    forms
+        .iter()
+        .filter_map(|form| {
+            let field_name = form.get_fully_qualified_field_name()?.to_owned();
+            let value = form
+                .get_object_id()
+                .and_then(|object_id| doc.get_dictionary(object_id).ok())
+                .and_then(|field| field.get(b"V").ok())
+                .and_then(|value| get_value_text(doc, value, 0));
+            Some((field_name, value))
+        })
+        .collect()
+}
+
+/// Get the value of a form field as text. The selected options of a list are separated by `, `.
+fn get_value_text(doc: &Document, value: &Object, depth: usize) -> Option<String> {
+    match value {
+        Object::String(text, _) => Some(decode_text_string(text)),
+        Object::Name(name) => Some(String::from_utf8_lossy(name).into_owned()),
+        Object::Integer(number) => Some(number.to_string()),
+        Object::Real(number) => Some(number.to_string()),
+        Object::Boolean(value) => Some(value.to_string()),
+        Object::Array(items) => Some(
+            items
+                .iter()
+                .filter_map(|item| get_value_text(doc, item, depth + 1))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        // Text can be a stream (for long text).
+        Object::Stream(stream) => {
+            let content = match stream.dict.has(b"Filter") {
+                true => stream.decompressed_content().ok()?,
+                false => stream.content.clone(),
+            };
+            Some(decode_text_string(&content))
+        }
+        // Limit the depth, so a loop of references does not hang.
+        Object::Reference(object_id) if depth < 8 => {
+            get_value_text(doc, doc.get_object(*object_id).ok()?, depth + 1)
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,509 @@
+//! Support for rich text form fields (`RV`).
+//!
+//! Only a limited subset of XHTML is supported:
+//! - `<b>`, `<strong>`: Bold text
+//! - `<i>`, `<em>`: Italic text
+//! - `<span style="...">`: `color`, `font-weight` and `font-style`
+//! - `<p>`, `<br/>`: Line breaks
+//!
+//! Unknown tags are ignored, but their text is kept.
+
+use crate::field_validation::validate_field_value;
+use crate::font::{encode_win_ansi, TrueTypeFont};
+use crate::journal::JournalChange;
+use crate::rectangle::Rectangle;
+use crate::utils::parse_font;
+use crate::watermark::get_text_width;
+use crate::{lopdf_utils, Error, PDFSigningDocument};
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Dictionary, Object, ObjectId, Stream,
+};
+use std::str::from_utf8;
+
+/// Field flag (`Ff`) bit position 26: `RichText`.
+const FIELD_FLAG_RICH_TEXT: i64 = 1 << 25;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct RichTextStyle {
+    pub bold: bool,
+    pub italic: bool,
+    /// Color in RGB, values between `0.0` and `1.0`.
+    pub color: Option<(f64, f64, f64)>,
+}
+
+impl RichTextStyle {
+    /// The name of the font in the resource dictionary of the appearance stream.
+    fn font_name(&self) -> &'static str {
+        match (self.bold, self.italic) {
+            (false, false) => "Helv",
+            (true, false) => "HeBo",
+            (false, true) => "HeOb",
+            (true, true) => "HeBO",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RichTextRun {
+    pub text: String,
+    pub style: RichTextStyle,
+}
+
+/// Parsed rich text value. Every item is 1 line of text.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RichText {
+    pub lines: Vec<Vec<RichTextRun>>,
+}
+
+impl RichText {
+    /// Parse the supported XHTML subset.
+    pub(crate) fn parse(xhtml: &str) -> Result<Self, Error> {
+        let mut lines: Vec<Vec<RichTextRun>> = vec![vec![]];
+        let mut style_stack = vec![RichTextStyle::default()];
+        let mut rest = xhtml;
+
+        while !rest.is_empty() {
+            if let Some(tag_rest) = rest.strip_prefix('<') {
+                let tag_end = tag_rest
+                    .find('>')
+                    .ok_or_else(|| Error::from("Rich text: Tag is not closed."))?;
+                let tag = tag_rest[..tag_end].trim();
+                rest = &tag_rest[tag_end + 1..];
+
+                // Skip comments, processing instructions and doctype
+                if tag.starts_with('!') || tag.starts_with('?') {
+                    continue;
+                }
+
+                let current_style = style_stack.last().cloned().unwrap_or_default();
+                if let Some(closing_tag) = tag.strip_prefix('/') {
+                    let name = closing_tag.trim().to_lowercase();
+                    if name == "p" || name == "div" {
+                        Self::end_line(&mut lines);
+                    }
+                    if Self::is_style_tag(&name) && style_stack.len() > 1 {
+                        style_stack.pop();
+                    }
+                    continue;
+                }
+
+                let self_closing = tag.ends_with('/');
+                let tag = tag.trim_end_matches('/');
+                let (name, attributes) = match tag.find(char::is_whitespace) {
+                    Some(index) => (tag[..index].to_lowercase(), &tag[index..]),
+                    None => (tag.to_lowercase(), ""),
+                };
+
+                match name.as_str() {
+                    "br" => Self::end_line(&mut lines),
+                    // Start a new line, unless we are already at the start of one.
+                    "p" | "div" if !lines.last().map(Vec::is_empty).unwrap_or(true) => {
+                        Self::end_line(&mut lines);
+                    }
+                    _ => {}
+                }
+
+                if Self::is_style_tag(&name) && !self_closing {
+                    let mut style = current_style;
+                    match name.as_str() {
+                        "b" | "strong" => style.bold = true,
+                        "i" | "em" => style.italic = true,
+                        _ => {}
+                    }
+                    if let Some(css) = Self::get_attribute(attributes, "style") {
+                        Self::apply_css(&mut style, &css);
+                    }
+                    style_stack.push(style);
+                }
+            } else {
+                let text_end = rest.find('<').unwrap_or(rest.len());
+                let text = Self::decode_entities(&rest[..text_end]);
+                rest = &rest[text_end..];
+
+                // Collapse whitespace like a browser would do. This is done across the
+                // runs of a line, so spaces at a run boundary are kept once.
+                let line = lines.last_mut().expect("At least 1 line expected.");
+                let mut previous_space = line.last().map_or(true, |run| run.text.ends_with(' '));
+                let mut collapsed = String::with_capacity(text.len());
+                for character in text.chars() {
+                    if character.is_whitespace() {
+                        if !previous_space {
+                            collapsed.push(' ');
+                        }
+                        previous_space = true;
+                    } else {
+                        collapsed.push(character);
+                        previous_space = false;
+                    }
+                }
+                let text = collapsed;
+                if text.is_empty() {
+                    continue;
+                }
+                let style = style_stack.last().cloned().unwrap_or_default();
+                match line.last_mut() {
+                    Some(run) if run.style == style => run.text.push_str(&text),
+                    _ => line.push(RichTextRun { text, style }),
+                }
+            }
+        }
+
+        Self::trim_line(lines.last_mut().expect("At least 1 line expected."));
+        // Remove the trailing empty line
+        if lines.len() > 1 && lines.last().map(Vec::is_empty).unwrap_or(false) {
+            lines.pop();
+        }
+        Ok(RichText { lines })
+    }
+
+    /// The text without any styling, used for the `V` value.
+    pub(crate) fn to_plain_text(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| line.iter().map(|run| run.text.as_str()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\r")
+    }
+
+    /// Finish the current line and start a new one.
+    fn end_line(lines: &mut Vec<Vec<RichTextRun>>) {
+        if let Some(line) = lines.last_mut() {
+            Self::trim_line(line);
+        }
+        lines.push(vec![]);
+    }
+
+    /// Remove the collapsed space at the end of a line.
+    fn trim_line(line: &mut Vec<RichTextRun>) {
+        if let Some(run) = line.last_mut() {
+            if run.text.ends_with(' ') {
+                run.text.pop();
+            }
+            if run.text.is_empty() {
+                line.pop();
+            }
+        }
+    }
+
+    fn is_style_tag(name: &str) -> bool {
+        matches!(
+            name,
+            "b" | "strong" | "i" | "em" | "span" | "p" | "div" | "body" | "font"
+        )
+    }
+
+    fn get_attribute(attributes: &str, key: &str) -> Option<String> {
+        let start = attributes.find(&format!("{}=", key))? + key.len() + 1;
+        let value = &attributes[start..];
+        let quote = value.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            return None;
+        }
+        let end = value[1..].find(quote)?;
+        Some(value[1..=end].to_owned())
+    }
+
+    fn apply_css(style: &mut RichTextStyle, css: &str) {
+        for declaration in css.split(';') {
+            let mut parts = declaration.splitn(2, ':');
+            let property = parts.next().unwrap_or("").trim().to_lowercase();
+            let value = parts.next().unwrap_or("").trim().to_lowercase();
+            match property.as_str() {
+                "color" => style.color = Self::parse_color(&value),
+                "font-weight" => style.bold = value == "bold" || value == "bolder",
+                "font-style" => style.italic = value == "italic" || value == "oblique",
+                "" => {}
+                _ => tracing::debug!("Rich text: CSS property `{}` is not supported.", property),
+            }
+        }
+    }
+
+    /// Parse `#rgb` and `#rrggbb` colors.
+    fn parse_color(value: &str) -> Option<(f64, f64, f64)> {
+        let hex = value.strip_prefix('#')?;
+        let expanded = match hex.len() {
+            3 => hex.chars().flat_map(|c| [c, c]).collect::<String>(),
+            6 => hex.to_owned(),
+            _ => return None,
+        };
+        let channel = |index: usize| {
+            u8::from_str_radix(&expanded[index..index + 2], 16)
+                .ok()
+                .map(|value| value as f64 / 255.0)
+        };
+        Some((channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    fn decode_entities(text: &str) -> String {
+        text.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&nbsp;", " ")
+            .replace("&amp;", "&")
+    }
+
+    /// Plain text without styling, every line of `text` is 1 line.
+    pub(crate) fn from_plain_text(text: &str) -> Self {
+        let lines = text
+            .lines()
+            .map(|line| match line.is_empty() {
+                true => vec![],
+                false => vec![RichTextRun {
+                    text: line.to_owned(),
+                    style: RichTextStyle::default(),
+                }],
+            })
+            .collect();
+        RichText { lines }
+    }
+
+    /// Wrap the XHTML in a `body` element, as required for the rich value (`RV` and `RC`).
+    pub(crate) fn to_rich_value(xhtml: &str) -> String {
+        if xhtml.trim_start().starts_with("<?xml") || xhtml.trim_start().starts_with("<body") {
+            xhtml.to_owned()
+        } else {
+            format!(
+                "<?xml version=\"1.0\"?><body xmlns=\"http://www.w3.org/1999/xhtml\" \
+                xmlns:xfa=\"http://www.xfa.org/schema/xfa-data/1.0/\" \
+                xfa:APIVersion=\"Acrobat:11.0.0\" xfa:spec=\"2.0.2\">{}</body>",
+                xhtml
+            )
+        }
+    }
+
+    /// Wrap the lines at spaces, so they fit in `width` (in points). A word that is wider
+    /// than `width` is not split. Without an embedded `font` the widths of Helvetica Bold
+    /// are used for all the styles, so the text fits in every style.
+    fn wrap(&self, width: f64, font_size: f64, font: Option<&TrueTypeFont>) -> Self {
+        let text_width = |text: &str| {
+            let width = match font {
+                Some(font) => font.get_text_width(&font.encode(text)),
+                None => get_text_width(text),
+            };
+            width * font_size / 1000.0
+        };
+        let mut lines = vec![];
+        for line in &self.lines {
+            let mut wrapped_line: Vec<RichTextRun> = vec![];
+            let mut line_width = 0.0;
+            // A run can start in the middle of a word (`<b>Hel</b>lo`), the line is only
+            // broken after a space.
+            let mut after_space = true;
+            for run in line {
+                for word in run.text.split_inclusive(' ') {
+                    if after_space
+                        && !wrapped_line.is_empty()
+                        && line_width + text_width(word.trim_end()) > width
+                    {
+                        Self::trim_line(&mut wrapped_line);
+                        lines.push(std::mem::take(&mut wrapped_line));
+                        line_width = 0.0;
+                    }
+                    line_width += text_width(word);
+                    after_space = word.ends_with(' ');
+                    match wrapped_line.last_mut() {
+                        Some(last) if last.style == run.style => last.text.push_str(word),
+                        _ => wrapped_line.push(RichTextRun {
+                            text: word.to_owned(),
+                            style: run.style.clone(),
+                        }),
+                    }
+                }
+            }
+            lines.push(wrapped_line);
+        }
+        RichText { lines }
+    }
+
+    /// Create the appearance stream content for the given font size and field height.
+    /// With an embedded `font` all the styles use that font, so bold and italic are not visible.
+    fn to_content(
+        &self,
+        font_size: i32,
+        height: f64,
+        font: Option<&TrueTypeFont>,
+    ) -> Content<Vec<Operation>> {
+        let mut operations = vec![
+            Operation::new("BMC", vec!["Tx".into()]),
+            Operation::new("q", vec![]),
+        ];
+        operations.append(&mut self.to_text_operations(
+            font_size.max(1) as f64,
+            (2.0, height - 2.0),
+            (0.0, 0.0, 0.0),
+            font,
+        ));
+        operations.append(&mut vec![
+            Operation::new("Q", vec![]),
+            Operation::new("EMC", vec![]),
+        ]);
+        Content { operations }
+    }
+
+    /// Create the text object (`BT` ... `ET`) that starts in the top left corner `start`.
+    /// Text without a color uses `default_color`.
+    pub(crate) fn to_text_operations(
+        &self,
+        font_size: f64,
+        start: (f64, f64),
+        default_color: (f64, f64, f64),
+        font: Option<&TrueTypeFont>,
+    ) -> Vec<Operation> {
+        let leading = font_size * 1.2;
+        let mut operations = vec![
+            Operation::new("BT", vec![]),
+            Operation::new("TL", vec![leading.into()]),
+            Operation::new("Td", vec![start.0.into(), (start.1 - font_size).into()]),
+        ];
+
+        for (index, line) in self.lines.iter().enumerate() {
+            if index > 0 {
+                operations.push(Operation::new("T*", vec![]));
+            }
+            for run in line {
+                let (r, g, b) = run.style.color.unwrap_or(default_color);
+                operations.push(Operation::new(
+                    "Tf",
+                    vec![
+                        Object::Name(run.style.font_name().as_bytes().to_vec()),
+                        font_size.into(),
+                    ],
+                ));
+                operations.push(Operation::new("rg", vec![r.into(), g.into(), b.into()]));
+                operations.push(Operation::new(
+                    "Tj",
+                    vec![Object::string_literal(match font {
+                        Some(font) => font.encode(&run.text),
+                        None => encode_win_ansi(&run.text),
+                    })],
+                ));
+            }
+        }
+        operations.push(Operation::new("ET", vec![]));
+        operations
+    }
+}
+
+/// Create the font resources for all the styles of rich text.
+/// With an embedded `font` all the styles use that font, added with `add_object`.
+pub(crate) fn rich_text_font_resources<F: FnMut(Object) -> ObjectId>(
+    font: Option<&TrueTypeFont>,
+    mut add_object: F,
+) -> Dictionary {
+    match font {
+        Some(font) => {
+            let font_dict = font.to_font_dictionary(&mut add_object);
+            let font_id = add_object(font_dict.into());
+            dictionary! {
+                "Helv" => Object::Reference(font_id),
+                "HeBo" => Object::Reference(font_id),
+                "HeOb" => Object::Reference(font_id),
+                "HeBO" => Object::Reference(font_id),
+            }
+        }
+        None => dictionary! {
+            "Helv" => PDFSigningDocument::standard_font("Helvetica"),
+            "HeBo" => PDFSigningDocument::standard_font("Helvetica-Bold"),
+            "HeOb" => PDFSigningDocument::standard_font("Helvetica-Oblique"),
+            "HeBO" => PDFSigningDocument::standard_font("Helvetica-BoldOblique"),
+        },
+    }
+}
+
+impl PDFSigningDocument {
+    /// Fill a rich text field with a limited subset of XHTML.
+    ///
+    /// This sets the `RV` (rich value), the `V` (plain text value) and
+    /// generates an appearance with bold, italic and colored text, wrapped to the width
+    /// of the field.
+    pub fn fill_rich_text_field(&mut self, field_name: &str, xhtml: &str) -> Result<(), Error> {
+        self.load_acro_form()?;
+        let form_field = self.get_form_field(field_name)?;
+        let field_id = form_field
+            .get_object_id()
+            .ok_or_else(|| Error::from("AcroForm object is not a indirect reference."))?;
+
+        let rich_text = RichText::parse(xhtml)?;
+        let rich_value = RichText::to_rich_value(xhtml);
+
+        let mut doc = self.raw_document.get_prev_documents().clone();
+        let widget_ids = form_field.get_widget_ids(&doc)?;
+        validate_field_value(
+            &doc,
+            doc.get_object(field_id)?.as_dict()?,
+            field_name,
+            &rich_text.to_plain_text(),
+            self.validate_field_format,
+            self.limits.max_depth,
+        )?;
+
+        let field = doc.get_object_mut(field_id)?.as_dict_mut()?;
+        let flags = field.get(b"Ff").and_then(Object::as_i64).unwrap_or(0);
+        field.set("Ff", Object::Integer(flags | FIELD_FLAG_RICH_TEXT));
+        field.set("RV", lopdf_utils::text_string(&rich_value));
+        field.set("V", lopdf_utils::text_string(&rich_text.to_plain_text()));
+        let da = field.get(b"DA").ok().cloned();
+
+        let font = parse_font(match da {
+            Some(Object::String(ref bytes, _)) => Some(from_utf8(bytes)?),
+            _ => None,
+        });
+        let font_size = if (font.0).1 > 0 { (font.0).1 } else { 12 };
+
+        let text_font = self.get_text_font()?;
+        let font_resources =
+            rich_text_font_resources(text_font.as_ref(), |object| doc.add_object(object));
+
+        for widget_id in widget_ids {
+            let widget = doc.get_object(widget_id)?.as_dict()?;
+            let rect = Rectangle::from_array(widget.get(b"Rect")?.as_array()?)?;
+            let (width, height) = (rect.width(), rect.height());
+
+            // 2 points of padding on both sides, like `to_content` uses.
+            let content = rich_text
+                .wrap(width - 4.0, font_size as f64, text_font.as_ref())
+                .to_content(font_size, height, text_font.as_ref());
+            let resources = dictionary! {
+                "Font" => font_resources.clone(),
+            };
+            let mut stream = Stream::new(
+                dictionary! {
+                    "Type" => "XObject",
+                    "Subtype" => "Form",
+                    "BBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+                    "Resources" => resources,
+                },
+                content.encode()?,
+            );
+            let _ = stream.compress();
+            let appearance_id = doc.add_object(stream);
+
+            let widget = doc.get_object_mut(widget_id)?.as_dict_mut()?;
+            widget.set(
+                "AP",
+                dictionary! {
+                    "N" => Object::Reference(appearance_id),
+                },
+            );
+        }
+
+        self.reload_document(doc)?;
+        self.record_change(JournalChange::FieldFilled {
+            field_name: field_name.to_owned(),
+            value: xhtml.to_owned(),
+        });
+        Ok(())
+    }
+
+    /// Create a font dictionary for one of the standard 14 fonts.
+    pub(crate) fn standard_font(base_font: &str) -> lopdf::Dictionary {
+        dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => Object::Name(base_font.as_bytes().to_vec()),
+            "Encoding" => "WinAnsiEncoding",
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! Remove active and external content (JavaScript, actions that leave the document,
+//! embedded files and external references) before an uploaded document is processed.
+
+use crate::pdf_object::PdfObjectDeref;
+use crate::{Error, PDFSigningDocument};
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+/// Keys of a dictionary that contain 1 action.
+const ACTION_KEYS: [&[u8]; 3] = [b"A", b"OpenAction", b"PA"];
+
+/// What kind of content `sanitize` removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SanitizeKind {
+    /// JavaScript and `Rendition` actions, document level JavaScript, XFA forms and
+    /// rich media annotations.
+    JavaScript,
+    /// Actions that run programs, open links or send data (`Launch`, `URI`, `SubmitForm`).
+    Action,
+    /// Embedded files, associated files (`AF`) and file attachment annotations.
+    EmbeddedFile,
+    /// References to other files: remote go-to actions, imported data, external stream data
+    /// and reference XObjects.
+    ExternalReference,
+}
+
+/// Content removed by `sanitize`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SanitizedItem {
+    pub kind: SanitizeKind,
+    /// The object the content was removed from.
+    pub object_id: ObjectId,
+    pub message: String,
+}
+
+impl SanitizedItem {
+    fn new(kind: SanitizeKind, object_id: ObjectId, message: String) -> Self {
+        SanitizedItem {
+            kind,
+            object_id,
+            message,
+        }
+    }
+}
+
+impl PDFSigningDocument {
+    /// Remove JavaScript, `Rendition`, `Launch`, `URI` and `SubmitForm` actions, rich media
+    /// annotations, embedded files and references to external files from the document, and
+    /// return what was removed.
+    ///
+    /// When something is removed the document is rewritten (including the changes that
+    /// are not saved yet), so existing signatures become invalid: sanitize before signing.
+    /// The document is not changed when nothing is removed.
+    pub fn sanitize(&mut self) -> Result<Vec<SanitizedItem>, Error> {
+        let mut doc = self.get_latest_document();
+        let mut removed = vec![];
+        let object_ids: Vec<ObjectId> = doc.objects.keys().copied().collect();
+        for object_id in object_ids {
+            let mut object = doc.get_object(object_id)?.clone();
+            let count = removed.len();
+            sanitize_object(&doc, object_id, &mut object, &mut removed);
+            if removed.len() > count {
+                doc.objects.insert(object_id, object);
+            }
+        }
+        if removed.is_empty() {
+            return Ok(removed);
+        }
+        for item in &removed {
+            tracing::info!("Sanitize: {} ({:?})", item.message, item.object_id);
+        }
+        doc.prune_objects();
+        self.reload_document(doc)?;
+        Ok(removed)
+    }
+}
+
+fn sanitize_object(
+    doc: &Document,
+    object_id: ObjectId,
+    object: &mut Object,
+    removed: &mut Vec<SanitizedItem>,
+) {
+    match object {
+        Object::Dictionary(dict) => sanitize_dictionary(doc, object_id, dict, removed),
+        Object::Stream(stream) => {
+            if stream.dict.has(b"F") {
+                for key in [&b"F"[..], b"FFilter", b"FDecodeParms"] {
+                    stream.dict.remove(key);
+                }
+                removed.push(SanitizedItem::new(
+                    SanitizeKind::ExternalReference,
+                    object_id,
+                    "Removed the external file of a stream.".to_owned(),
+                ));
+            }
+            sanitize_dictionary(doc, object_id, &mut stream.dict, removed);
+        }
+        Object::Array(array) => {
+            for item in array.iter_mut() {
+                sanitize_object(doc, object_id, item, removed);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Sanitize a dictionary and the direct objects inside it.
+fn sanitize_dictionary(
+    doc: &Document,
+    object_id: ObjectId,
+    dict: &mut Dictionary,
+    removed: &mut Vec<SanitizedItem>,
+) {
+    for key in ACTION_KEYS {
+        let action = dict
+            .get(key)
+            .ok()
+            .and_then(|action| find_action(doc, action, 0));
+        if let Some((kind, action)) = action {
+            dict.remove(key);
+            removed.push(SanitizedItem::new(
+                kind,
+                object_id,
+                format!("Removed a `{}` action.", action),
+            ));
+        }
+    }
+    sanitize_additional_actions(doc, object_id, dict, removed);
+
+    // Entries of the catalog (`Names`, `AcroForm`) and of file specifications.
+    let mut remove_entry = |dict: &mut Dictionary, key: &[u8], kind, message: &str| {
+        if dict.remove(key).is_some() {
+            removed.push(SanitizedItem::new(kind, object_id, message.to_owned()));
+        }
+    };
+    if !dict.has(b"S") {
+        remove_entry(
+            dict,
+            b"JavaScript",
+            SanitizeKind::JavaScript,
+            "Removed the document level JavaScript.",
+        );
+    }
+    remove_entry(
+        dict,
+        b"XFA",
+        SanitizeKind::JavaScript,
+        "Removed the XFA form, which can contain scripts.",
+    );
+    remove_entry(
+        dict,
+        b"EmbeddedFiles",
+        SanitizeKind::EmbeddedFile,
+        "Removed the embedded files.",
+    );
+    remove_entry(
+        dict,
+        b"AF",
+        SanitizeKind::EmbeddedFile,
+        "Removed the associated files (`AF`).",
+    );
+    if dict.type_is(b"Filespec") {
+        remove_entry(
+            dict,
+            b"EF",
+            SanitizeKind::EmbeddedFile,
+            "Removed the embedded file of a file specification.",
+        );
+        dict.remove(b"RF");
+    }
+    if dict.type_is(b"XObject") {
+        remove_entry(
+            dict,
+            b"Ref",
+            SanitizeKind::ExternalReference,
+            "Removed the reference to a page of another document.",
+        );
+    }
+    sanitize_annotations(doc, object_id, dict, removed);
+
+    for (_, value) in dict.iter_mut() {
+        if matches!(value, Object::Dictionary(_) | Object::Array(_)) {
+            sanitize_object(doc, object_id, value, removed);
+        }
+    }
+}
+
+/// Remove the triggers of the additional actions (`AA`) that run an unsafe action.
+fn sanitize_additional_actions(
+    doc: &Document,
+    object_id: ObjectId,
+    dict: &mut Dictionary,
+    removed: &mut Vec<SanitizedItem>,
+) {
+    let mut additional_actions = match dict.get(b"AA").ok().and_then(|value| value.deref(doc).ok())
+    {
+        Some(Object::Dictionary(additional_actions)) => additional_actions.clone(),
+        _ => return,
+    };
+    let count = removed.len();
+    let triggers: Vec<Vec<u8>> = additional_actions
+        .iter()
+        .map(|(trigger, _)| trigger.clone())
+        .collect();
+    for trigger in triggers {
+        let action = additional_actions
+            .get(&trigger)
+            .ok()
+            .and_then(|action| find_action(doc, action, 0));
+        if let Some((kind, action)) = action {
+            additional_actions.remove(&trigger);
+            removed.push(SanitizedItem::new(
+                kind,
+                object_id,
+                format!(
+                    "Removed a `{}` action of the `{}` trigger.",
+                    action,
+                    String::from_utf8_lossy(&trigger)
+                ),
+            ));
+        }
+    }
+    if additional_actions.is_empty() {
+        dict.remove(b"AA");
+    } else if removed.len() > count {
+        dict.set("AA", additional_actions);
+    }
+}
+
+/// Remove the file attachment and rich media annotations from the `Annots` of a page.
+fn sanitize_annotations(
+    doc: &Document,
+    object_id: ObjectId,
+    dict: &mut Dictionary,
+    removed: &mut Vec<SanitizedItem>,
+) {
+    let annotations = match dict
+        .get(b"Annots")
+        .ok()
+        .and_then(|value| value.deref(doc).ok())
+    {
+        Some(Object::Array(annotations)) => annotations.clone(),
+        _ => return,
+    };
+    let count = removed.len();
+    let kept_annotations: Vec<Object> = annotations
+        .iter()
+        .filter(|annotation| {
+            let subtype = annotation
+                .deref(doc)
+                .ok()
+                .and_then(|annotation| annotation.as_dict().ok())
+                .and_then(|annotation| annotation.get(b"Subtype").ok())
+                .and_then(|subtype| subtype.as_name_str().ok());
+            let (kind, message) = match subtype {
+                Some("FileAttachment") => (
+                    SanitizeKind::EmbeddedFile,
+                    "Removed a file attachment annotation.",
+                ),
+                // The content of rich media annotations (Flash, 3D, video) runs scripts.
+                Some("RichMedia") => (
+                    SanitizeKind::JavaScript,
+                    "Removed a rich media annotation, which can run scripts.",
+                ),
+                _ => return true,
+            };
+            removed.push(SanitizedItem::new(kind, object_id, message.to_owned()));
+            false
+        })
+        .cloned()
+        .collect();
+    if removed.len() > count {
+        dict.set("Annots", kept_annotations);
+    }
+}
+
+/// Find an unsafe action in `action` or the actions after it (`Next`).
+/// Returns the kind and the action type (`S`).
+fn find_action(doc: &Document, action: &Object, depth: usize) -> Option<(SanitizeKind, String)> {
+    // Limit the depth, so a loop of `Next` actions does not hang.
+    if depth > 32 {
+        return None;
+    }
+    match action.deref(doc).ok()? {
+        Object::Array(actions) => actions
+            .iter()
+            .find_map(|action| find_action(doc, action, depth + 1)),
+        Object::Dictionary(action) => {
+            let action_type = action
+                .get(b"S")
+                .and_then(Object::as_name_str)
+                .unwrap_or_default();
+            let kind = match action_type {
+                // Rendition actions can have a script (`JS`) and are used by `Screen`
+                // annotations to play media.
+                "JavaScript" | "Rendition" => Some(SanitizeKind::JavaScript),
+                "Launch" | "URI" | "SubmitForm" => Some(SanitizeKind::Action),
+                "GoToR" | "GoToE" | "ImportData" => Some(SanitizeKind::ExternalReference),
+                _ => None,
+            };
+            match kind {
+                Some(kind) => Some((kind, action_type.to_owned())),
+                None => action
+                    .get(b"Next")
+                    .ok()
+                    .and_then(|next| find_action(doc, next, depth + 1)),
+            }
+        }
+        _ => None,
+    }
+}
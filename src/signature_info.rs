@@ -1,6 +1,9 @@
+use crate::error::Error;
+use crate::pdf_object::PdfObjectDeref;
+use crate::pdfa::get_printable_annotation_flags;
+use crate::rectangle::Rectangle;
+use crate::signing_options::SigningOptions;
 use crate::PDFSigningDocument;
-use crate::{error::Error, UserSignatureInfo};
-use chrono::Utc;
 use lopdf::ObjectId;
 
 impl PDFSigningDocument {
@@ -9,8 +12,9 @@ impl PDFSigningDocument {
         &mut self,
         signature_obj_id: ObjectId,
         new_appearance_id: ObjectId,
-        user_signature_info: &UserSignatureInfo,
+        user_name: &str,
         _signature_name: &str,
+        options: &SigningOptions,
     ) -> Result<(), Error> {
         use lopdf::{Object::*, StringFormat};
         let _root_obj_id = self
@@ -55,7 +59,7 @@ impl PDFSigningDocument {
 
         // TODO if kids is `None` we have to create it.
         if kids.is_none() {
-            log::error!("Unimplemented state: `Kids` entry is missing in Signature.");
+            tracing::error!("Unimplemented state: `Kids` entry is missing in Signature.");
         }
         let kids = kids.unwrap_or_default();
 
@@ -88,12 +92,20 @@ impl PDFSigningDocument {
                 );
                 // TODO Set the `F` value to 132: For docs see page 385.
                 // This will `Locked` and `Print`
+                if self.pdfa_mode {
+                    // PDF/A requires visible annotations to be printed.
+                    let flags = child_dict_mut
+                        .get(b"F")
+                        .and_then(|flags| flags.as_i64())
+                        .unwrap_or(0);
+                    child_dict_mut.set("F", Integer(get_printable_annotation_flags(flags)));
+                }
                 found_and_replace_appearance = true;
             }
         }
 
         if !found_and_replace_appearance {
-            log::error!("None of the `Kids` are of type `Annot`.");
+            tracing::error!("None of the `Kids` are of type `Annot`.");
         }
 
         // Update `V` tag in `FT = Sig`
@@ -110,13 +122,13 @@ impl PDFSigningDocument {
         //     ])),
         // );
 
-        // Get system time in UTC
-        let now = Utc::now();
-
-        let v_dictionary = Dictionary(lopdf::Dictionary::from_iter(vec![
+        let mut v_dictionary = Dictionary(lopdf::Dictionary::from_iter(vec![
             ("Type", Name("Sig".as_bytes().to_vec())),
             ("Filter", Name("Adobe.PPKLite".as_bytes().to_vec())),
-            ("SubFilter", Name("adbe.pkcs7.detached".as_bytes().to_vec())),
+            (
+                "SubFilter",
+                Name(options.sub_filter.name().as_bytes().to_vec()),
+            ),
             // The order of `ByteRange` and `Contents` is important.
             // They should not be moved or switched in ordering.
             (
@@ -130,24 +142,14 @@ impl PDFSigningDocument {
             ),
             (
                 "Contents", // Will be filled in later
-                String(vec![0u8; 9000], StringFormat::Hexadecimal),
-            ),
-            (
-                "M",
                 String(
-                    now.format("D:%Y%m%d%H%M%S+00'00'")
-                        .to_string()
-                        .as_bytes()
-                        .to_vec(),
-                    StringFormat::Literal,
+                    vec![0u8; options.placeholder_size],
+                    StringFormat::Hexadecimal,
                 ),
             ),
             (
                 "Name",
-                String(
-                    user_signature_info.user_name.as_bytes().to_vec(),
-                    StringFormat::Literal,
-                ),
+                String(user_name.as_bytes().to_vec(), StringFormat::Literal),
             ),
             (
                 "Prob_Build",
@@ -230,6 +232,30 @@ impl PDFSigningDocument {
             //     ]))]),
             // ),
         ]));
+        let v_dict = v_dictionary.as_dict_mut()?;
+        if let Some(now) = self.get_current_time() {
+            v_dict.set(
+                "M",
+                String(
+                    now.format("D:%Y%m%d%H%M%S+00'00'")
+                        .to_string()
+                        .as_bytes()
+                        .to_vec(),
+                    StringFormat::Literal,
+                ),
+            );
+        }
+        if self.deterministic {
+            // The platform would make the document differ between machines.
+            if let Ok(app) = v_dict
+                .get_mut(b"Prop_Build")
+                .and_then(|prop_build| prop_build.as_dict_mut())
+                .and_then(|prop_build| prop_build.get_mut(b"App"))
+                .and_then(|app| app.as_dict_mut())
+            {
+                app.remove(b"OS");
+            }
+        }
 
         // Add `V` as new object
         let v_ref = self.raw_document.new_document.add_object(v_dictionary);
@@ -242,6 +268,63 @@ impl PDFSigningDocument {
 
         sign_dict.set("V", Reference(v_ref));
 
-        Ok(())
+        self.add_doc_mdp(v_ref, options)?;
+        self.add_validation_data(options)
+    }
+
+    /// For an AcroForm find the rectangle on the page.
+    pub(crate) fn get_rectangle_from_form(
+        &mut self,
+        form_id: ObjectId,
+    ) -> Result<Rectangle, Error> {
+        let mut rect = None;
+        // Get kids
+        let form_dict = self
+            .raw_document
+            .get_prev_documents()
+            .get_object(form_id)?
+            .as_dict()?;
+
+        let kids = if form_dict.has(b"Kids") {
+            Some(form_dict.get(b"Kids")?.as_array()?)
+        } else {
+            None
+        };
+
+        if let Some(kids) = kids {
+            for child in kids {
+                let child_dict = child
+                    .deref(self.raw_document.get_prev_documents())?
+                    .as_dict()?;
+                // Child should be of `Type` `Annot` for Annotation.
+                if child_dict.has(b"Rect") {
+                    let child_rect = child_dict.get(b"Rect")?.as_array()?;
+                    if child_rect.len() >= 4 {
+                        // Found a reference, set as return value
+                        rect = Some(Rectangle {
+                            x1: child_rect[0].as_f64()?,
+                            y1: child_rect[1].as_f64()?,
+                            x2: child_rect[2].as_f64()?,
+                            y2: child_rect[3].as_f64()?,
+                        });
+                    }
+                }
+            }
+        } else {
+            if form_dict.has(b"Rect") {
+                let child_rect = form_dict.get(b"Rect")?.as_array()?;
+                if child_rect.len() >= 4 {
+                    // Found a reference, set as return value
+                    rect = Some(Rectangle {
+                        x1: child_rect[0].as_f64()?,
+                        y1: child_rect[1].as_f64()?,
+                        x2: child_rect[2].as_f64()?,
+                        y2: child_rect[3].as_f64()?,
+                    });
+                }
+            }
+        }
+
+        rect.ok_or_else(|| Error::Other("AcroForm: Rectangle not found.".to_owned()))
     }
 }
@@ -0,0 +1,591 @@
+//! Options used when documents are digitally signed, see `SigningOptions::builder`.
+
+#[cfg(feature = "signing")]
+use crate::field_metadata::{FieldMetadataParser, MetadataParser};
+use crate::image_options::ImageOptions;
+use crate::{Error, FormField, InsertImage, InsertImageToPage, PDFSigningDocument};
+#[cfg(feature = "network")]
+use crate::{HttpClient, OcspCache};
+#[cfg(feature = "signing")]
+use cryptographic_message_syntax::SignerBuilder;
+use lopdf::{dictionary, Dictionary, Object, ObjectId, Stream};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The algorithm used to calculate the digest of the signed bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DigestAlgorithm {
+    #[default]
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl DigestAlgorithm {
+    #[cfg(feature = "signing")]
+    fn to_x509(self) -> x509_certificate::DigestAlgorithm {
+        match self {
+            DigestAlgorithm::Sha256 => x509_certificate::DigestAlgorithm::Sha256,
+            DigestAlgorithm::Sha384 => x509_certificate::DigestAlgorithm::Sha384,
+            DigestAlgorithm::Sha512 => x509_certificate::DigestAlgorithm::Sha512,
+        }
+    }
+
+    /// Calculate the digest of `data`.
+    pub(crate) fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            DigestAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+            DigestAlgorithm::Sha384 => Sha384::digest(data).to_vec(),
+            DigestAlgorithm::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+
+    /// The DER encoded object identifier of the algorithm.
+    pub(crate) fn oid(self) -> &'static [u8] {
+        match self {
+            DigestAlgorithm::Sha256 => &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01],
+            DigestAlgorithm::Sha384 => &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02],
+            DigestAlgorithm::Sha512 => &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03],
+        }
+    }
+}
+
+/// The format of the signature (`SubFilter`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SubFilter {
+    /// CMS signature (`adbe.pkcs7.detached`), supported by all viewers.
+    #[default]
+    Pkcs7Detached,
+    /// PAdES signature (`ETSI.CAdES.detached`) (PDF 2.0). The signer must include the
+    /// `signing-certificate-v2` attribute, see ETSI EN 319 142-1.
+    CadesDetached,
+}
+
+impl SubFilter {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            SubFilter::Pkcs7Detached => "adbe.pkcs7.detached",
+            SubFilter::CadesDetached => "ETSI.CAdES.detached",
+        }
+    }
+}
+
+/// The changes that are allowed after a certification signature (`DocMDP`),
+/// without invalidating it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocMdpPermissions {
+    /// No changes are allowed, also no other signatures.
+    NoChanges,
+    /// Filling in forms and signing.
+    FillForms,
+    /// Filling in forms, signing and adding or changing annotations.
+    FillFormsAndAnnotate,
+}
+
+impl DocMdpPermissions {
+    /// Get the value of `P` in the transform parameters.
+    fn value(self) -> i64 {
+        match self {
+            DocMdpPermissions::NoChanges => 1,
+            DocMdpPermissions::FillForms => 2,
+            DocMdpPermissions::FillFormsAndAnnotate => 3,
+        }
+    }
+}
+
+/// Validation data (DER encoded) that is needed to validate the signature later,
+/// also when certificates are expired or revoked.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ValidationData {
+    /// The certificates of the chain, including the root certificate.
+    pub certificates: Vec<Vec<u8>>,
+    /// OCSP responses for the certificates.
+    pub ocsp_responses: Vec<Vec<u8>>,
+    /// Certificate revocation lists for the certificates.
+    pub crls: Vec<Vec<u8>>,
+}
+
+/// How the document is prepared for long term validation (LTV).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum LtvPolicy {
+    /// No validation data is added.
+    #[default]
+    None,
+    /// Add the validation data to the Document Security Store (`DSS`) of the document.
+    /// The data is not fetched by this crate, it should be collected by the caller.
+    Embed(ValidationData),
+    /// Fetch the OCSP responses of the certificate chains of the signers after signing,
+    /// and add them with the certificates to the `DSS` in a new revision (feature
+    /// `network`). The responses are cached, use the same cache for a batch of documents.
+    #[cfg(feature = "network")]
+    FetchOcsp(Arc<OcspCache>),
+}
+
+/// Maps a signature field to the `user_id` of the user that signs it,
+/// `None` when the field is not signed.
+pub type SignerMatchingFn = dyn Fn(&FormField) -> Option<String> + Send + Sync;
+
+/// How the signature fields are matched to the users that sign them.
+/// A user signs all the fields that match.
+#[derive(Clone, Default)]
+pub enum MatchBy {
+    /// The `user_id` in the info of the user in the field is matched. By default the name
+    /// of the field is the encoded info, see `SigningOptionsBuilder::field_metadata_parser`.
+    #[default]
+    UserId,
+    /// The `box_id` in the info of the user in the field is matched, see `UserId`.
+    EncodedBoxId,
+    /// The name of the field is the `box_id` of the user. Every user must have a field.
+    BoxId,
+    /// The name of the field is the `user_email` of the user, the case is ignored.
+    Email,
+    /// The name of the field is the pattern with `{user_id}`, `{box_id}` and `{email}`
+    /// replaced by the info of the user, like `signature_{email}`.
+    FieldNamePattern(String),
+    /// A custom function that maps a signature field to the `user_id` of a user.
+    Custom(Arc<SignerMatchingFn>),
+}
+
+impl fmt::Debug for MatchBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchBy::UserId => write!(f, "UserId"),
+            MatchBy::EncodedBoxId => write!(f, "EncodedBoxId"),
+            MatchBy::BoxId => write!(f, "BoxId"),
+            MatchBy::Email => write!(f, "Email"),
+            MatchBy::FieldNamePattern(pattern) => write!(f, "FieldNamePattern({:?})", pattern),
+            MatchBy::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+impl PartialEq for MatchBy {
+    /// Custom functions are only equal when they are the same `Arc`.
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (MatchBy::FieldNamePattern(pattern), MatchBy::FieldNamePattern(other_pattern)) => {
+                pattern == other_pattern
+            }
+            (MatchBy::Custom(matching_fn), MatchBy::Custom(other_matching_fn)) => {
+                Arc::ptr_eq(matching_fn, other_matching_fn)
+            }
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+/// Options used by `sign`, create them with `SigningOptions::builder`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SigningOptions {
+    pub(crate) digest_algorithm: DigestAlgorithm,
+    pub(crate) sub_filter: SubFilter,
+    pub(crate) time_stamp_urls: Vec<String>,
+    #[cfg_attr(not(feature = "network"), allow(dead_code))]
+    pub(crate) time_stamp_timeout: Duration,
+    #[cfg(feature = "network")]
+    pub(crate) http_client: HttpClient,
+    pub(crate) offline: bool,
+    pub(crate) placeholder_size: usize,
+    pub(crate) appearance: Option<ImageOptions>,
+    pub(crate) doc_mdp: Option<DocMdpPermissions>,
+    pub(crate) ltv_policy: LtvPolicy,
+    pub(crate) pdf_version: Option<String>,
+    pub(crate) max_iterations: usize,
+    pub(crate) match_by: MatchBy,
+    #[cfg(feature = "signing")]
+    pub(crate) metadata_parser: MetadataParser,
+}
+
+impl Default for SigningOptions {
+    fn default() -> Self {
+        SigningOptions {
+            digest_algorithm: DigestAlgorithm::default(),
+            sub_filter: SubFilter::default(),
+            time_stamp_urls: vec![],
+            time_stamp_timeout: Duration::from_secs(10),
+            #[cfg(feature = "network")]
+            http_client: HttpClient::default(),
+            offline: false,
+            placeholder_size: 9000,
+            appearance: None,
+            doc_mdp: None,
+            ltv_policy: LtvPolicy::default(),
+            pdf_version: None,
+            max_iterations: 10000,
+            match_by: MatchBy::default(),
+            #[cfg(feature = "signing")]
+            metadata_parser: MetadataParser::default(),
+        }
+    }
+}
+
+impl SigningOptions {
+    pub fn builder() -> SigningOptionsBuilder {
+        SigningOptionsBuilder {
+            options: SigningOptions::default(),
+        }
+    }
+
+    /// Return `Error::NetworkDisabled` when the options are offline and something needs
+    /// the network.
+    pub(crate) fn check_offline(&self) -> Result<(), Error> {
+        if !self.offline {
+            return Ok(());
+        }
+        if !self.time_stamp_urls.is_empty() {
+            return Err(Error::NetworkDisabled {
+                operation: "time stamp".to_owned(),
+            });
+        }
+        #[cfg(feature = "network")]
+        if let LtvPolicy::FetchOcsp(_) = self.ltv_policy {
+            return Err(Error::NetworkDisabled {
+                operation: "OCSP response".to_owned(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Apply the digest algorithm and the time stamp server at `time_stamp_url` to the
+    /// signer of a user.
+    #[cfg(feature = "signing")]
+    pub(crate) fn configure_signer<'a>(
+        &self,
+        signer: SignerBuilder<'a>,
+        time_stamp_url: Option<&str>,
+    ) -> Result<SignerBuilder<'a>, Error> {
+        let signer = signer.message_digest_algorithm(self.digest_algorithm.to_x509());
+        match time_stamp_url {
+            Some(url) => signer
+                .time_stamp_url(url)
+                .map_err(|error| Error::Other(format!("Time stamp: {}", error))),
+            None => Ok(signer),
+        }
+    }
+}
+
+/// Builder for `SigningOptions`, the options that are not set keep their default.
+#[derive(Debug, Clone)]
+pub struct SigningOptionsBuilder {
+    options: SigningOptions,
+}
+
+impl SigningOptionsBuilder {
+    /// The algorithm used for the digest of the document, default SHA-256.
+    pub fn digest_algorithm(mut self, digest_algorithm: DigestAlgorithm) -> Self {
+        self.options.digest_algorithm = digest_algorithm;
+        self
+    }
+
+    /// The format of the signature, default `adbe.pkcs7.detached`.
+    pub fn sub_filter(mut self, sub_filter: SubFilter) -> Self {
+        self.options.sub_filter = sub_filter;
+        self
+    }
+
+    /// Add a time stamp of the time stamp authority (TSA) at `url` to every signature.
+    /// Call it for every TSA, they are tried in order until one returns a time stamp, so
+    /// an outage of the first TSA does not stop the signing.
+    /// With the feature `network` the token is checked: the signature, message imprint
+    /// and nonce must be valid, otherwise the next TSA is tried.
+    pub fn time_stamp_url(mut self, url: &str) -> Self {
+        self.options.time_stamp_urls.push(url.to_owned());
+        self
+    }
+
+    /// The maximum time of a request to a TSA before the next one is tried, default 10
+    /// seconds. Only used with the feature `network`, the CMS crate has its own timeout.
+    pub fn time_stamp_timeout(mut self, timeout: Duration) -> Self {
+        self.options.time_stamp_timeout = timeout;
+        self
+    }
+
+    /// The client of the requests to the TSAs and OCSP responders (feature `network`),
+    /// to set a proxy or trust extra root certificates, see `HttpClient::builder`.
+    #[cfg(feature = "network")]
+    pub fn http_client(mut self, http_client: HttpClient) -> Self {
+        self.options.http_client = http_client;
+        self
+    }
+
+    /// Make sure signing does not use the network, default `false`. When the other options
+    /// need it (a time stamp URL or `LtvPolicy::FetchOcsp`), `sign` returns
+    /// `Error::NetworkDisabled` before anything is signed, and so does
+    /// `sign_field_with_signer` with an `ExternalSigner` that needs the network (like a
+    /// `CscSigner`). Every request of the `http_client` fails as well.
+    /// Checking signatures never uses the network.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.options.offline = offline;
+        self
+    }
+
+    /// The number of bytes reserved in the document for every signature (`Contents`),
+    /// default 9000, at least 1024. Signatures with a time stamp or a long certificate chain
+    /// need more.
+    pub fn placeholder_size(mut self, placeholder_size: usize) -> Self {
+        self.options.placeholder_size = placeholder_size.max(1024);
+        self
+    }
+
+    /// How the signature image is placed in the field and encoded.
+    /// Without it the image options of the document are used (see `set_image_scaling`).
+    pub fn appearance(mut self, image_options: ImageOptions) -> Self {
+        self.options.appearance = Some(image_options);
+        self
+    }
+
+    /// Make the first signature a certification signature that allows the `permissions`.
+    /// Documents that are already signed can not be certified, this is ignored for them.
+    pub fn doc_mdp(mut self, permissions: DocMdpPermissions) -> Self {
+        self.options.doc_mdp = Some(permissions);
+        self
+    }
+
+    /// How the document is prepared for long term validation, default `LtvPolicy::None`.
+    pub fn ltv_policy(mut self, ltv_policy: LtvPolicy) -> Self {
+        self.options.ltv_policy = ltv_policy;
+        self
+    }
+
+    /// The PDF version written in the signed revisions. By default the version of the
+    /// document is kept, but at least 1.5. A higher version is set in the `Version` of
+    /// the catalog, incremental updates do not change the header.
+    pub fn pdf_version(mut self, pdf_version: &str) -> Self {
+        self.options.pdf_version = Some(pdf_version.to_owned());
+        self
+    }
+
+    /// How the signature fields are matched to the users, default `MatchBy::UserId`.
+    pub fn match_by(mut self, match_by: MatchBy) -> Self {
+        self.options.match_by = match_by;
+        self
+    }
+
+    /// How the info of the user is read from a signature field for `MatchBy::UserId` and
+    /// `MatchBy::EncodedBoxId`, default `EncodedFieldName`.
+    #[cfg(feature = "signing")]
+    pub fn field_metadata_parser(mut self, parser: impl FieldMetadataParser + 'static) -> Self {
+        self.options.metadata_parser = MetadataParser(Arc::new(parser));
+        self
+    }
+
+    /// The maximum number of form fields `sign` visits, default 10000.
+    /// After every signature the fields are visited again from the first field, so
+    /// documents with many fields need more. When the maximum is reached
+    /// `Error::TooManyIterations` is returned.
+    pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+        self.options.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn build(self) -> SigningOptions {
+        #[cfg(feature = "network")]
+        if self.options.offline {
+            return SigningOptions {
+                http_client: self.options.http_client.to_offline(),
+                ..self.options
+            };
+        }
+        self.options
+    }
+}
+
+impl PDFSigningDocument {
+    /// Set the version of the revision that is signed, see `SigningOptionsBuilder::pdf_version`.
+    /// The header of an incremental update is not written, so a higher version is set in
+    /// the `Version` of the catalog as well.
+    pub(crate) fn set_signing_version(&mut self, options: &SigningOptions) -> Result<(), Error> {
+        let header_version = &self.raw_document.get_prev_documents().version;
+        let header_version = header_version.lines().next().unwrap_or_default().to_owned();
+        let version = match &options.pdf_version {
+            Some(version) => version.clone(),
+            None => match parse_version(&header_version) {
+                Some(number) if number >= 1.5 => header_version.clone(),
+                _ => "1.5".to_owned(),
+            },
+        };
+        self.raw_document.new_document.version = version.clone();
+
+        let catalog_id = self
+            .raw_document
+            .new_document
+            .trailer
+            .get(b"Root")?
+            .as_reference()?;
+        let catalog_version = self
+            .get_latest_object(catalog_id)
+            .and_then(|catalog| catalog.as_dict().ok())
+            .and_then(|catalog| catalog.get(b"Version").ok())
+            .and_then(|version| version.as_name_str().ok())
+            .and_then(parse_version);
+        let new_version = parse_version(&version);
+        if new_version > parse_version(&header_version) && new_version > catalog_version {
+            self.opt_clone_object_to_new_document(catalog_id)?;
+            self.raw_document
+                .new_document
+                .get_object_mut(catalog_id)?
+                .as_dict_mut()?
+                .set("Version", Object::Name(version.into_bytes()));
+        }
+        Ok(())
+    }
+
+    /// Certify the document with the signature `signature_id` when this is enabled
+    /// and the document is not signed yet, see `SigningOptionsBuilder::doc_mdp`.
+    pub(crate) fn add_doc_mdp(
+        &mut self,
+        signature_id: ObjectId,
+        options: &SigningOptions,
+    ) -> Result<(), Error> {
+        let permissions = match options.doc_mdp {
+            Some(permissions) => permissions,
+            None => return Ok(()),
+        };
+        if self.get_last_signed_byte_range().is_some() {
+            tracing::warn!("DocMDP: The document is already signed, it is not certified.");
+            return Ok(());
+        }
+        self.opt_clone_object_to_new_document(signature_id)?;
+        self.raw_document
+            .new_document
+            .get_object_mut(signature_id)?
+            .as_dict_mut()?
+            .set(
+                "Reference",
+                vec![dictionary! {
+                    "Type" => "SigRef",
+                    "TransformMethod" => "DocMDP",
+                    "TransformParams" => dictionary! {
+                        "Type" => "TransformParams",
+                        "P" => permissions.value(),
+                        "V" => "1.2",
+                    },
+                }
+                .into()],
+            );
+        self.add_digital_signature_data(signature_id)
+    }
+
+    /// Add the validation data to the Document Security Store (`DSS`) when this is enabled,
+    /// see `SigningOptionsBuilder::ltv_policy`. Data that is already in the store is skipped.
+    pub(crate) fn add_validation_data(&mut self, options: &SigningOptions) -> Result<(), Error> {
+        match &options.ltv_policy {
+            LtvPolicy::Embed(validation_data) => self.embed_validation_data(validation_data),
+            LtvPolicy::None => Ok(()),
+            // Added after signing, see `sign`.
+            #[cfg(feature = "network")]
+            LtvPolicy::FetchOcsp(_) => Ok(()),
+        }
+    }
+
+    /// Add the validation data to the `DSS`, data that is already in it is skipped.
+    pub(crate) fn embed_validation_data(
+        &mut self,
+        validation_data: &ValidationData,
+    ) -> Result<(), Error> {
+        let mut dss = self.get_latest_dss()?;
+        self.add_dss_validation_data(&mut dss, validation_data);
+        self.set_dss(dss)
+    }
+
+    /// Get the `DSS` of the document with the changes not saved yet, or a new one.
+    pub(crate) fn get_latest_dss(&self) -> Result<Dictionary, Error> {
+        let catalog_id = self
+            .raw_document
+            .new_document
+            .trailer
+            .get(b"Root")?
+            .as_reference()?;
+        Ok(self
+            .get_latest_object(catalog_id)
+            .and_then(|catalog| catalog.as_dict().ok())
+            .and_then(|catalog| catalog.get(b"DSS").ok())
+            .and_then(|dss| self.resolve_latest_object(dss))
+            .and_then(|dss| dss.as_dict().ok())
+            .cloned()
+            .unwrap_or_else(|| dictionary! { "Type" => "DSS" }))
+    }
+
+    /// Add the `dss` as a new object and set it in the catalog.
+    pub(crate) fn set_dss(&mut self, dss: Dictionary) -> Result<(), Error> {
+        let catalog_id = self
+            .raw_document
+            .new_document
+            .trailer
+            .get(b"Root")?
+            .as_reference()?;
+        let dss_id = self.add_object(dss);
+        self.opt_clone_object_to_new_document(catalog_id)?;
+        self.raw_document
+            .new_document
+            .get_object_mut(catalog_id)?
+            .as_dict_mut()?
+            .set("DSS", Object::Reference(dss_id));
+        Ok(())
+    }
+
+    /// Add the validation data to the arrays of the `dss`. Returns the references of the
+    /// certificates, OCSP responses and CRLs, the streams that are already in it are reused.
+    pub(crate) fn add_dss_validation_data(
+        &mut self,
+        dss: &mut Dictionary,
+        validation_data: &ValidationData,
+    ) -> [Vec<ObjectId>; 3] {
+        [
+            self.add_dss_streams(dss, "Certs", &validation_data.certificates),
+            self.add_dss_streams(dss, "OCSPs", &validation_data.ocsp_responses),
+            self.add_dss_streams(dss, "CRLs", &validation_data.crls),
+        ]
+    }
+
+    /// Add a stream for every item that is not in the array `key` of the `dss` yet.
+    /// Returns the references of the streams of the items.
+    fn add_dss_streams(
+        &mut self,
+        dss: &mut Dictionary,
+        key: &str,
+        items: &[Vec<u8>],
+    ) -> Vec<ObjectId> {
+        let mut array = dss
+            .get(key.as_bytes())
+            .ok()
+            .and_then(|array| self.resolve_latest_object(array))
+            .and_then(|array| array.as_array().ok())
+            .cloned()
+            .unwrap_or_default();
+        let existing: Vec<(Vec<u8>, ObjectId)> = array
+            .iter()
+            .filter_map(|item| {
+                let stream = self.resolve_latest_object(item)?.as_stream().ok()?;
+                let content = stream
+                    .decompressed_content()
+                    .unwrap_or_else(|_| stream.content.clone());
+                Some((content, item.as_reference().ok()?))
+            })
+            .collect();
+        let mut references = vec![];
+        for item in items {
+            if let Some((_, stream_id)) = existing.iter().find(|(content, _)| content == item) {
+                references.push(*stream_id);
+                continue;
+            }
+            let mut stream = Stream::new(dictionary! {}, item.clone());
+            let _ = stream.compress();
+            let stream_id = self.add_object(stream);
+            array.push(Object::Reference(stream_id));
+            references.push(stream_id);
+        }
+        dss.set(key, array);
+        references
+    }
+}
+
+/// Parse a PDF version like `1.7`, `None` when it is not a version.
+pub(crate) fn parse_version(version: &str) -> Option<f64> {
+    version.trim().parse().ok()
+}
@@ -0,0 +1,198 @@
+//! Which users sign which signature fields: before signing with the dry-run `plan_signing`
+//! and after signing in the `SigningResult` of `sign`.
+//! With the `serde` feature the plan can be serialized, for example to show it for
+//! confirmation before the document is signed.
+
+use crate::acro_form::{AcroForm, FormComponent};
+use crate::{Error, MatchBy, PDFSigningDocument, SigningOptions, UserSignatureInfo};
+use lopdf::Dictionary;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// What `sign` would do with the signature fields, see `plan_signing`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct SigningPlan {
+    /// Every signature field of the document, in the order they are signed.
+    pub fields: Vec<SigningField>,
+    /// The users that do not sign any field.
+    pub unused_user_ids: Vec<String>,
+}
+
+impl SigningPlan {
+    /// Get the fields that are signed.
+    pub fn signed_fields(&self) -> impl Iterator<Item = &SigningField> {
+        self.fields
+            .iter()
+            .filter(|field| matches!(field.action, SigningAction::Sign { .. }))
+    }
+}
+
+/// A signature field in the `SigningPlan` or `SigningResult`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct SigningField {
+    /// The fully qualified name of the field.
+    pub field_name: Option<String>,
+    /// The index of the page of the field, the first page has index 0.
+    pub page_index: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub action: SigningAction,
+}
+
+/// What happens (or happened) with a signature field: it is signed by a user or
+/// skipped (and why).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "action", rename_all = "camelCase"))]
+pub enum SigningAction {
+    /// The field is signed by the user.
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    Sign { user_id: String, user_name: String },
+    /// The field is already signed.
+    AlreadySigned,
+    /// The field has no name, so it has no user.
+    NoFieldName,
+    /// The field does not have the info of a user (see `FieldMetadataParser`),
+    /// the field is probably not created for signing with this crate.
+    NotEncoded,
+    /// The field is for a user that is not in the list of users.
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    MissingUser { user_id: String },
+    /// No user in the list of users matches the name of the field
+    /// (see `SigningOptionsBuilder::match_by`).
+    NoMatchingUser,
+}
+
+/// The signed document and what happened with the signature fields, see `sign`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigningResult {
+    /// The bytes of the document, the document as it was read when nothing is signed.
+    pub document: Vec<u8>,
+    /// Every signature field of the document, `SigningAction::AlreadySigned` is used for
+    /// the fields that were signed before.
+    pub fields: Vec<SigningField>,
+}
+
+impl SigningResult {
+    /// Get the fields that are signed.
+    pub fn signed_fields(&self) -> impl Iterator<Item = &SigningField> {
+        self.fields
+            .iter()
+            .filter(|field| matches!(field.action, SigningAction::Sign { .. }))
+    }
+
+    /// Check if at least 1 field is signed.
+    pub fn is_signed(&self) -> bool {
+        self.signed_fields().next().is_some()
+    }
+}
+
+impl PDFSigningDocument {
+    /// Match the users to the signature fields like `sign`, without changing the document.
+    /// The fields are matched with `SigningOptionsBuilder::match_by`.
+    pub fn plan_signing(
+        &mut self,
+        users_signature_info: &[UserSignatureInfo],
+        options: &SigningOptions,
+    ) -> Result<SigningPlan, Error> {
+        let form_fields = self.get_form_fields()?;
+        let forms = self.acro_form.clone().unwrap_or_default();
+
+        let mut fields = vec![];
+        for (form, form_field) in forms.iter().zip(form_fields) {
+            if !matches!(
+                form.get_form_component(),
+                FormComponent::EmptySignature | FormComponent::SignedSignature { .. }
+            ) {
+                continue;
+            }
+            let action = match self.match_signature_field(form, users_signature_info, options) {
+                Ok(info) => SigningAction::Sign {
+                    user_id: info.user_id.clone(),
+                    user_name: info.user_name.clone(),
+                },
+                Err(action) => action,
+            };
+            fields.push(SigningField {
+                field_name: form_field.fully_qualified_name().map(str::to_owned),
+                page_index: form_field.page_index(),
+                action,
+            });
+        }
+
+        let signing_user_ids: Vec<&str> = fields
+            .iter()
+            .filter_map(|field| match &field.action {
+                SigningAction::Sign { user_id, .. } => Some(user_id.as_str()),
+                _ => None,
+            })
+            .collect();
+        let unused_user_ids = users_signature_info
+            .iter()
+            .filter(|info| !signing_user_ids.contains(&info.user_id.as_str()))
+            .map(|info| info.user_id.clone())
+            .collect();
+        Ok(SigningPlan {
+            fields,
+            unused_user_ids,
+        })
+    }
+
+    /// Find the user that signs the signature field, or the reason the field is skipped.
+    /// When multiple users match, the last one is used.
+    pub(crate) fn match_signature_field<'a>(
+        &self,
+        form: &AcroForm,
+        users_signature_info: &'a [UserSignatureInfo],
+        options: &SigningOptions,
+    ) -> Result<&'a UserSignatureInfo, SigningAction> {
+        if !form.is_empty_signature() {
+            return Err(SigningAction::AlreadySigned);
+        }
+        let field_name = form
+            .get_partial_field_name()
+            .ok_or(SigningAction::NoFieldName)?;
+        let read_field_info = || {
+            let field_dict = form
+                .get_object_id()
+                .and_then(|object_id| self.get_latest_object(object_id))
+                .and_then(|field| field.as_dict().ok());
+            let field_info = self.get_form_field_info(form);
+            options
+                .metadata_parser
+                .0
+                .parse(&field_info, field_dict.unwrap_or(&Dictionary::new()))
+                .ok_or(SigningAction::NotEncoded)
+        };
+        let mut users = users_signature_info.iter();
+        let user = match &options.match_by {
+            MatchBy::UserId => {
+                let user_id = read_field_info()?.user_id;
+                return users
+                    .rfind(|info| info.user_id == user_id)
+                    .ok_or(SigningAction::MissingUser { user_id });
+            }
+            MatchBy::EncodedBoxId => {
+                let box_id = read_field_info()?.box_id;
+                users.rfind(|info| info.box_id == box_id)
+            }
+            MatchBy::BoxId => users.rfind(|info| info.box_id == field_name),
+            MatchBy::Email => users.rfind(|info| info.user_email.eq_ignore_ascii_case(field_name)),
+            MatchBy::FieldNamePattern(pattern) => users.rfind(|info| {
+                pattern
+                    .replace("{user_id}", &info.user_id)
+                    .replace("{box_id}", &info.box_id)
+                    .replace("{email}", &info.user_email)
+                    == field_name
+            }),
+            MatchBy::Custom(matching_fn) => {
+                let user_id = matching_fn(&self.get_form_field_info(form));
+                users.rfind(|info| Some(&info.user_id) == user_id.as_ref())
+            }
+        };
+        user.ok_or(SigningAction::NoMatchingUser)
+    }
+}
@@ -0,0 +1,128 @@
+//! Extract page ranges into new documents, for example to split a signed packet
+//! back into the individual agreements.
+
+use crate::merge::{flatten_pages, get_acro_form, get_fields};
+use crate::{Error, PDFSigningDocument};
+use lopdf::{Document, Object, ObjectId};
+use std::collections::HashSet;
+use std::ops::RangeBounds;
+
+impl PDFSigningDocument {
+    /// Create a new document with the pages with `page_indexes` (starting at 0), like `0..3`.
+    ///
+    /// The resources and annotations of these pages are kept, just like the form fields
+    /// with a widget on these pages. Fields that are only on other pages are removed.
+    /// The new document is not encrypted. Signatures are not valid in the new document,
+    /// because they sign the bytes of the original document.
+    pub fn extract_pages<R: RangeBounds<usize>>(&self, page_indexes: R) -> Result<Self, Error> {
+        let mut doc = self.get_latest_document();
+        doc.trailer.remove(b"Encrypt");
+        let pages_id = doc.catalog()?.get(b"Pages")?.as_reference()?;
+        let page_ids: Vec<ObjectId> = flatten_pages(&mut doc)?
+            .into_iter()
+            .enumerate()
+            .filter(|(page_index, _)| page_indexes.contains(page_index))
+            .map(|(_, page_id)| page_id)
+            .collect();
+        if page_ids.is_empty() {
+            return Err(Error::from("Split: No pages in the page range."));
+        }
+
+        let mut annotation_ids = HashSet::new();
+        for page_id in &page_ids {
+            let page = doc.get_object_mut(*page_id)?.as_dict_mut()?;
+            page.set("Parent", Object::Reference(pages_id));
+            let annotations = match page.get(b"Annots").ok().cloned() {
+                Some(Object::Reference(annotations_id)) => {
+                    doc.get_object(annotations_id)?.as_array()?.clone()
+                }
+                Some(annotations) => annotations.as_array()?.clone(),
+                None => continue,
+            };
+            annotation_ids.extend(
+                annotations
+                    .iter()
+                    .filter_map(|annotation| annotation.as_reference().ok()),
+            );
+        }
+        let pages = doc.get_object_mut(pages_id)?.as_dict_mut()?;
+        pages.set("Count", page_ids.len() as i64);
+        pages.set(
+            "Kids",
+            page_ids
+                .into_iter()
+                .map(Object::Reference)
+                .collect::<Vec<_>>(),
+        );
+
+        if let Some(mut acro_form) = get_acro_form(&doc) {
+            let mut fields = vec![];
+            for field in get_fields(&doc, &acro_form).unwrap_or_default() {
+                let keep = match field {
+                    Object::Reference(field_id) => keep_field(&mut doc, field_id, &annotation_ids)?,
+                    _ => true,
+                };
+                if keep {
+                    fields.push(field);
+                }
+            }
+            acro_form.set("Fields", fields);
+            let acro_form_id = doc.add_object(acro_form);
+            doc.catalog_mut()?
+                .set("AcroForm", Object::Reference(acro_form_id));
+        }
+
+        doc.prune_objects();
+        doc.compress();
+        let mut new_binary_pdf = Vec::new();
+        doc.save_to(&mut new_binary_pdf)?;
+        Self::read_from(new_binary_pdf.as_slice(), self.file_name.clone())
+    }
+
+    /// Split the document into new documents of `page_count` pages,
+    /// the last document has the remaining pages. See `extract_pages`.
+    pub fn split_every(&self, page_count: usize) -> Result<Vec<Self>, Error> {
+        if page_count == 0 {
+            return Err(Error::from("Split: The page count should be at least 1."));
+        }
+        let total_page_count = self.get_latest_document().get_pages().len();
+        (0..total_page_count)
+            .step_by(page_count)
+            .map(|start| self.extract_pages(start..(start + page_count).min(total_page_count)))
+            .collect()
+    }
+}
+
+/// Check if the field has a widget in `annotation_ids`, the `Kids` without widgets
+/// in `annotation_ids` are removed.
+fn keep_field(
+    doc: &mut Document,
+    field_id: ObjectId,
+    annotation_ids: &HashSet<ObjectId>,
+) -> Result<bool, Error> {
+    let kids = doc
+        .get_dictionary(field_id)?
+        .get(b"Kids")
+        .and_then(Object::as_array)
+        .ok()
+        .cloned();
+    let kids = match kids {
+        Some(kids) => kids,
+        // The field and the widget are the same object.
+        None => return Ok(annotation_ids.contains(&field_id)),
+    };
+
+    let mut kept_kids = vec![];
+    for kid in kids {
+        if let Object::Reference(kid_id) = kid {
+            if keep_field(doc, kid_id, annotation_ids)? {
+                kept_kids.push(kid);
+            }
+        }
+    }
+    let keep = !kept_kids.is_empty();
+    doc.get_object_mut(field_id)?
+        .as_dict_mut()?
+        .set("Kids", kept_kids);
+    Ok(keep)
+}
@@ -0,0 +1,107 @@
+//! Metadata of the document in 1 struct, see `summary`.
+//! With the `serde` feature it can be serialized, for example to return it as JSON.
+
+use crate::acro_form::FormComponent;
+use crate::{AttachmentRelationship, Error, FormField, PDFSigningDocument, Permissions};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// The metadata of a document, see `PDFSigningDocument::summary`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct DocumentSummary {
+    pub file_name: String,
+    pub page_count: usize,
+    /// The title of the document information dictionary (`Title`).
+    pub title: Option<String>,
+    pub fields: Vec<FormField>,
+    pub signatures: Vec<SignatureSummary>,
+    pub attachments: Vec<AttachmentSummary>,
+    pub encrypted: bool,
+    /// The permissions of an encrypted document, `None` when it is not encrypted.
+    pub permissions: Option<Permissions>,
+}
+
+/// A signed signature field, the signature is not checked (see `verify_signatures`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct SignatureSummary {
+    /// The fully qualified name of the signature field.
+    pub field_name: String,
+    /// The name in the signature (`Name`).
+    pub signer_name: Option<String>,
+    pub sub_filter: Option<String>,
+    /// The index of the page of the signature field, the first page has index 0.
+    pub page_index: Option<usize>,
+    /// The signature covers the whole document, there are no changes after it.
+    pub covers_whole_document: bool,
+}
+
+/// An embedded file, without its data (see `get_attachments`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct AttachmentSummary {
+    pub file_name: String,
+    /// The size of the data in bytes.
+    pub size: usize,
+    pub mime_type: Option<String>,
+    pub description: Option<String>,
+    pub relationship: AttachmentRelationship,
+}
+
+impl PDFSigningDocument {
+    /// Get the page count, form fields, signatures, attachments and encryption of the
+    /// document. Like `get_form_fields` the fields are the fields of the document as it
+    /// was read, with the changes not saved yet.
+    pub fn summary(&mut self) -> Result<DocumentSummary, Error> {
+        let fields = self.get_form_fields()?;
+        let file_length = self.get_prev_documents_bytes().len() as u64;
+        let forms = self.acro_form.clone().unwrap_or_default();
+        let signatures = forms
+            .iter()
+            .zip(&fields)
+            .filter_map(|(form, field)| match form.get_form_component() {
+                FormComponent::SignedSignature {
+                    sub_filter,
+                    byte_range,
+                    name,
+                    ..
+                } => Some(SignatureSummary {
+                    field_name: field.fully_qualified_name().unwrap_or_default().to_owned(),
+                    signer_name: name.clone(),
+                    sub_filter: sub_filter.clone(),
+                    page_index: field.page_index(),
+                    covers_whole_document: byte_range
+                        .last()
+                        .is_some_and(|(start, length)| start + length == file_length),
+                }),
+                _ => None,
+            })
+            .collect();
+        let attachments = self
+            .get_attachments()
+            .into_iter()
+            .map(|attachment| AttachmentSummary {
+                size: attachment.data.len(),
+                file_name: attachment.file_name,
+                mime_type: attachment.mime_type,
+                description: attachment.description,
+                relationship: attachment.relationship,
+            })
+            .collect();
+
+        Ok(DocumentSummary {
+            file_name: self.file_name.clone(),
+            page_count: self.get_page_ids().len(),
+            title: self.get_title(),
+            fields,
+            signatures,
+            attachments,
+            encrypted: self.security_handler.is_some(),
+            permissions: self.get_permissions(),
+        })
+    }
+}
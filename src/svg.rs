@@ -0,0 +1,414 @@
+//! Convert simple SVG images (like drawn signatures) to PDF vector content.
+//!
+//! Supported are `path`, `line`, `polyline`, `polygon`, `rect`, `circle` and `ellipse`
+//! elements (also inside `g` elements) with the `fill`, `stroke`, `stroke-width`, `fill-rule`,
+//! `stroke-linecap`, `stroke-linejoin` and `transform` properties. Other elements are ignored.
+
+use crate::image_placement::ImagePlacement;
+use crate::rectangle::Rectangle;
+use crate::Error;
+use lopdf::content::{Content, Operation};
+use std::str::FromStr;
+use svgtypes::{Color, Length, PointsParser, SimplePathSegment, SimplifyingPathParser};
+use svgtypes::{Transform, ViewBox};
+
+/// Distance of the Bézier control points to draw a quarter of a circle.
+const KAPPA: f64 = 0.552_284_8;
+
+#[derive(Debug, Clone)]
+struct SvgStyle {
+    fill: Option<(f64, f64, f64)>,
+    stroke: Option<(f64, f64, f64)>,
+    stroke_width: f64,
+    /// `fill-rule: evenodd`
+    even_odd: bool,
+    /// PDF line cap style (`J`)
+    line_cap: i64,
+    /// PDF line join style (`j`)
+    line_join: i64,
+}
+
+impl Default for SvgStyle {
+    fn default() -> Self {
+        SvgStyle {
+            fill: Some((0.0, 0.0, 0.0)),
+            stroke: None,
+            stroke_width: 1.0,
+            even_odd: false,
+            line_cap: 0,
+            line_join: 0,
+        }
+    }
+}
+
+impl SvgStyle {
+    fn apply_property(&mut self, name: &str, value: &str) {
+        let value = value.trim();
+        match name.trim() {
+            "fill" => self.fill = Self::parse_paint(value, self.fill),
+            "stroke" => self.stroke = Self::parse_paint(value, self.stroke),
+            "stroke-width" => {
+                if let Ok(length) = Length::from_str(value) {
+                    self.stroke_width = length.number;
+                }
+            }
+            "fill-rule" => self.even_odd = value == "evenodd",
+            "stroke-linecap" => {
+                self.line_cap = match value {
+                    "round" => 1,
+                    "square" => 2,
+                    _ => 0,
+                }
+            }
+            "stroke-linejoin" => {
+                self.line_join = match value {
+                    "round" => 1,
+                    "bevel" => 2,
+                    _ => 0,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Parse a color, unknown values (like gradients) keep the `current` color.
+    fn parse_paint(value: &str, current: Option<(f64, f64, f64)>) -> Option<(f64, f64, f64)> {
+        if value == "none" {
+            return None;
+        }
+        match Color::from_str(value) {
+            Ok(color) => Some((
+                color.red as f64 / 255.0,
+                color.green as f64 / 255.0,
+                color.blue as f64 / 255.0,
+            )),
+            Err(_) => {
+                tracing::debug!("SVG: Paint `{}` is not supported.", value);
+                current
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum PathSegment {
+    MoveTo(f64, f64),
+    LineTo(f64, f64),
+    CurveTo(f64, f64, f64, f64, f64, f64),
+    ClosePath,
+}
+
+#[derive(Debug, Clone)]
+struct SvgShape {
+    segments: Vec<PathSegment>,
+    style: SvgStyle,
+    transform: Transform,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct SvgImage {
+    view_box: ViewBox,
+    shapes: Vec<SvgShape>,
+}
+
+impl SvgImage {
+    /// Check if the data looks like an SVG image.
+    pub(crate) fn is_svg(data: &[u8]) -> bool {
+        let start = String::from_utf8_lossy(&data[..data.len().min(4096)]);
+        let start = start.trim_start_matches('\u{feff}').trim_start();
+        start.starts_with('<') && start.contains("<svg")
+    }
+
+    pub(crate) fn parse(svg: &str) -> Result<Self, Error> {
+        let document =
+            roxmltree::Document::parse(svg).map_err(|err| Error::Other(format!("SVG: {}", err)))?;
+        let root = document.root_element();
+        if root.tag_name().name() != "svg" {
+            return Err(Error::from("SVG: Root element should be `svg`."));
+        }
+
+        let view_box = match root.attribute("viewBox") {
+            Some(view_box) => ViewBox::from_str(view_box)
+                .map_err(|err| Error::Other(format!("SVG: Invalid `viewBox`: {}", err)))?,
+            None => ViewBox::new(
+                0.0,
+                0.0,
+                Self::get_number(root, "width"),
+                Self::get_number(root, "height"),
+            ),
+        };
+        if view_box.w <= 0.0 || view_box.h <= 0.0 {
+            return Err(Error::from("SVG: Image has no size, set `viewBox`."));
+        }
+
+        let mut shapes = vec![];
+        let style = Self::parse_style(root, &SvgStyle::default());
+        Self::parse_children(root, &style, Transform::default(), &mut shapes)?;
+        Ok(SvgImage { view_box, shapes })
+    }
+
+    fn parse_children(
+        node: roxmltree::Node,
+        parent_style: &SvgStyle,
+        parent_transform: Transform,
+        shapes: &mut Vec<SvgShape>,
+    ) -> Result<(), Error> {
+        for child in node.children().filter(|child| child.is_element()) {
+            let style = Self::parse_style(child, parent_style);
+            let transform = match child.attribute("transform") {
+                Some(transform) => Self::multiply(
+                    &parent_transform,
+                    &Transform::from_str(transform).map_err(|err| {
+                        Error::Other(format!("SVG: Invalid `transform`: {}", err))
+                    })?,
+                ),
+                None => parent_transform,
+            };
+
+            let number = |name| Self::get_number(child, name);
+            let segments = match child.tag_name().name() {
+                "g" => {
+                    Self::parse_children(child, &style, transform, shapes)?;
+                    continue;
+                }
+                "path" => Self::parse_path(child.attribute("d").unwrap_or_default()),
+                "line" => vec![
+                    PathSegment::MoveTo(number("x1"), number("y1")),
+                    PathSegment::LineTo(number("x2"), number("y2")),
+                ],
+                name @ ("polyline" | "polygon") => {
+                    let mut segments: Vec<PathSegment> =
+                        PointsParser::from(child.attribute("points").unwrap_or_default())
+                            .enumerate()
+                            .map(|(index, (x, y))| match index {
+                                0 => PathSegment::MoveTo(x, y),
+                                _ => PathSegment::LineTo(x, y),
+                            })
+                            .collect();
+                    if name == "polygon" {
+                        segments.push(PathSegment::ClosePath);
+                    }
+                    segments
+                }
+                "rect" => {
+                    let (x, y) = (number("x"), number("y"));
+                    let (width, height) = (number("width"), number("height"));
+                    vec![
+                        PathSegment::MoveTo(x, y),
+                        PathSegment::LineTo(x + width, y),
+                        PathSegment::LineTo(x + width, y + height),
+                        PathSegment::LineTo(x, y + height),
+                        PathSegment::ClosePath,
+                    ]
+                }
+                "circle" => Self::ellipse(number("cx"), number("cy"), number("r"), number("r")),
+                "ellipse" => Self::ellipse(number("cx"), number("cy"), number("rx"), number("ry")),
+                name => {
+                    tracing::debug!("SVG: Element `{}` is not supported.", name);
+                    continue;
+                }
+            };
+            shapes.push(SvgShape {
+                segments,
+                style,
+                transform,
+            });
+        }
+        Ok(())
+    }
+
+    /// Get the style of `node`, properties that are not set are inherited from the parent.
+    fn parse_style(node: roxmltree::Node, parent_style: &SvgStyle) -> SvgStyle {
+        let mut style = parent_style.clone();
+        for attribute in node.attributes() {
+            style.apply_property(attribute.name(), attribute.value());
+        }
+        // The `style` attribute overrides the presentation attributes.
+        if let Some(css) = node.attribute("style") {
+            for declaration in css.split(';') {
+                if let Some((name, value)) = declaration.split_once(':') {
+                    style.apply_property(name, value);
+                }
+            }
+        }
+        style
+    }
+
+    /// Get a number or length attribute, units are ignored.
+    fn get_number(node: roxmltree::Node, name: &str) -> f64 {
+        node.attribute(name)
+            .and_then(|value| Length::from_str(value).ok())
+            .map(|length| length.number)
+            .unwrap_or(0.0)
+    }
+
+    fn parse_path(data: &str) -> Vec<PathSegment> {
+        let mut segments = vec![];
+        let mut current = (0.0, 0.0);
+        let mut subpath_start = (0.0, 0.0);
+        for segment in SimplifyingPathParser::from(data) {
+            let segment = match segment {
+                Ok(segment) => segment,
+                Err(err) => {
+                    // Like browsers, render the path up to the error.
+                    tracing::warn!("SVG: Invalid path data: {}", err);
+                    break;
+                }
+            };
+            match segment {
+                SimplePathSegment::MoveTo { x, y } => {
+                    segments.push(PathSegment::MoveTo(x, y));
+                    current = (x, y);
+                    subpath_start = (x, y);
+                }
+                SimplePathSegment::LineTo { x, y } => {
+                    segments.push(PathSegment::LineTo(x, y));
+                    current = (x, y);
+                }
+                SimplePathSegment::CurveTo {
+                    x1,
+                    y1,
+                    x2,
+                    y2,
+                    x,
+                    y,
+                } => {
+                    segments.push(PathSegment::CurveTo(x1, y1, x2, y2, x, y));
+                    current = (x, y);
+                }
+                // PDF has no quadratic curves, convert to a cubic curve.
+                SimplePathSegment::Quadratic { x1, y1, x, y } => {
+                    segments.push(PathSegment::CurveTo(
+                        current.0 + 2.0 / 3.0 * (x1 - current.0),
+                        current.1 + 2.0 / 3.0 * (y1 - current.1),
+                        x + 2.0 / 3.0 * (x1 - x),
+                        y + 2.0 / 3.0 * (y1 - y),
+                        x,
+                        y,
+                    ));
+                    current = (x, y);
+                }
+                SimplePathSegment::ClosePath => {
+                    segments.push(PathSegment::ClosePath);
+                    current = subpath_start;
+                }
+            }
+        }
+        segments
+    }
+
+    fn ellipse(cx: f64, cy: f64, rx: f64, ry: f64) -> Vec<PathSegment> {
+        let (kx, ky) = (rx * KAPPA, ry * KAPPA);
+        vec![
+            PathSegment::MoveTo(cx + rx, cy),
+            PathSegment::CurveTo(cx + rx, cy + ky, cx + kx, cy + ry, cx, cy + ry),
+            PathSegment::CurveTo(cx - kx, cy + ry, cx - rx, cy + ky, cx - rx, cy),
+            PathSegment::CurveTo(cx - rx, cy - ky, cx - kx, cy - ry, cx, cy - ry),
+            PathSegment::CurveTo(cx + kx, cy - ry, cx + rx, cy - ky, cx + rx, cy),
+            PathSegment::ClosePath,
+        ]
+    }
+
+    /// Apply `child` inside the coordinate system of `parent`.
+    fn multiply(parent: &Transform, child: &Transform) -> Transform {
+        Transform::new(
+            parent.a * child.a + parent.c * child.b,
+            parent.b * child.a + parent.d * child.b,
+            parent.a * child.c + parent.c * child.d,
+            parent.b * child.c + parent.d * child.d,
+            parent.a * child.e + parent.c * child.f + parent.e,
+            parent.b * child.e + parent.d * child.f + parent.f,
+        )
+    }
+
+    /// Get the size of the image (the `viewBox`).
+    pub(crate) fn size(&self) -> (f64, f64) {
+        (self.view_box.w, self.view_box.h)
+    }
+
+    /// Create the content that draws the image inside `rect`.
+    pub(crate) fn to_content(
+        &self,
+        rect: &Rectangle,
+        placement: ImagePlacement,
+    ) -> Content<Vec<Operation>> {
+        let view_box = &self.view_box;
+        let image_rect = placement.get_image_rect(view_box.w, view_box.h, rect);
+        let scale_x = image_rect.width() / view_box.w;
+        let scale_y = image_rect.height() / view_box.h;
+        // SVG coordinates start at the top, PDF coordinates at the bottom.
+        let x_offset = image_rect.x1 - view_box.x * scale_x;
+        let y_offset = image_rect.y2 + view_box.y * scale_y;
+
+        let mut operations = vec![Operation::new("q", vec![])];
+        operations.extend(placement.clip_operations(rect));
+        operations.extend(placement.rotation_operations(&image_rect));
+        operations.push(Operation::new(
+            "cm",
+            vec![
+                scale_x.into(),
+                0.into(),
+                0.into(),
+                (-scale_y).into(),
+                x_offset.into(),
+                y_offset.into(),
+            ],
+        ));
+        for shape in &self.shapes {
+            let style = &shape.style;
+            let transform = &shape.transform;
+            operations.push(Operation::new("q", vec![]));
+            operations.push(Operation::new(
+                "cm",
+                vec![
+                    transform.a.into(),
+                    transform.b.into(),
+                    transform.c.into(),
+                    transform.d.into(),
+                    transform.e.into(),
+                    transform.f.into(),
+                ],
+            ));
+            if let Some((r, g, b)) = style.fill {
+                operations.push(Operation::new("rg", vec![r.into(), g.into(), b.into()]));
+            }
+            if let Some((r, g, b)) = style.stroke {
+                operations.push(Operation::new("RG", vec![r.into(), g.into(), b.into()]));
+                operations.push(Operation::new("w", vec![style.stroke_width.into()]));
+                operations.push(Operation::new("J", vec![style.line_cap.into()]));
+                operations.push(Operation::new("j", vec![style.line_join.into()]));
+            }
+            for segment in &shape.segments {
+                operations.push(match *segment {
+                    PathSegment::MoveTo(x, y) => Operation::new("m", vec![x.into(), y.into()]),
+                    PathSegment::LineTo(x, y) => Operation::new("l", vec![x.into(), y.into()]),
+                    PathSegment::CurveTo(x1, y1, x2, y2, x, y) => Operation::new(
+                        "c",
+                        vec![
+                            x1.into(),
+                            y1.into(),
+                            x2.into(),
+                            y2.into(),
+                            x.into(),
+                            y.into(),
+                        ],
+                    ),
+                    PathSegment::ClosePath => Operation::new("h", vec![]),
+                });
+            }
+            let paint_operator = match (style.fill.is_some(), style.stroke.is_some()) {
+                (true, true) if style.even_odd => "B*",
+                (true, true) => "B",
+                (true, false) if style.even_odd => "f*",
+                (true, false) => "f",
+                (false, true) => "S",
+                // End the path without painting.
+                (false, false) => "n",
+            };
+            operations.push(Operation::new(paint_operator, vec![]));
+            operations.push(Operation::new("Q", vec![]));
+        }
+        operations.push(Operation::new("Q", vec![]));
+        Content { operations }
+    }
+}
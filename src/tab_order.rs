@@ -0,0 +1,134 @@
+use crate::signing_options::parse_version;
+use crate::{Error, PDFSigningDocument};
+use lopdf::{Document, Object, ObjectId};
+use std::collections::HashMap;
+
+/// The tab order (`Tabs`) used for the annotations of a page.
+/// `AnnotationsArray` and `Widget` are defined in PDF 2.0, the document version is raised
+/// to 2.0 when they are used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabOrder {
+    /// Row order (`R`)
+    Row,
+    /// Column order (`C`)
+    Column,
+    /// Structure order (`S`)
+    Structure,
+    /// Order of the `Annots` array (`A`) (PDF 2.0)
+    AnnotationsArray,
+    /// Widget order (`W`) (PDF 2.0)
+    Widget,
+}
+
+impl TabOrder {
+    fn as_name(&self) -> &'static str {
+        match self {
+            TabOrder::Row => "R",
+            TabOrder::Column => "C",
+            TabOrder::Structure => "S",
+            TabOrder::AnnotationsArray => "A",
+            TabOrder::Widget => "W",
+        }
+    }
+
+    fn is_pdf_2(&self) -> bool {
+        matches!(self, TabOrder::AnnotationsArray | TabOrder::Widget)
+    }
+}
+
+/// Set the `Version` of the catalog to 2.0, unless the header or the catalog already has
+/// that version (or a higher one).
+fn require_pdf_2(doc: &mut Document) -> Result<(), Error> {
+    let catalog_id = doc.trailer.get(b"Root")?.as_reference()?;
+    let catalog_version = doc
+        .get_object(catalog_id)?
+        .as_dict()?
+        .get(b"Version")
+        .ok()
+        .and_then(|version| version.as_name_str().ok())
+        .and_then(parse_version);
+    let header_version = parse_version(doc.version.lines().next().unwrap_or_default());
+    if header_version < Some(2.0) && catalog_version < Some(2.0) {
+        let catalog = doc.get_object_mut(catalog_id)?.as_dict_mut()?;
+        catalog.set("Version", Object::Name(b"2.0".to_vec()));
+    }
+    Ok(())
+}
+
+impl PDFSigningDocument {
+    /// Set the tab order (`Tabs`) of every page.
+    /// For the PDF 2.0 orders the `Version` of the catalog is set to 2.0.
+    pub fn set_tab_order(&mut self, tab_order: TabOrder) -> Result<(), Error> {
+        let mut doc = self.raw_document.get_prev_documents().clone();
+        if tab_order.is_pdf_2() {
+            require_pdf_2(&mut doc)?;
+        }
+        for page_id in doc.get_pages().into_values() {
+            let page = doc.get_object_mut(page_id)?.as_dict_mut()?;
+            page.set(
+                "Tabs",
+                Object::Name(tab_order.as_name().as_bytes().to_vec()),
+            );
+        }
+        self.reload_document(doc)
+    }
+
+    /// Reorder the `Annots` of every page so the widgets of `field_names` come first,
+    /// in the given order. The other annotations keep their order and are placed after them.
+    ///
+    /// The `Tabs` entry of the pages is set to `A` (`TabOrder::AnnotationsArray`), so viewers
+    /// use the order of `Annots`. Without `Tabs` viewers can use the structure order of
+    /// tagged documents or a row order instead. `A` is defined in PDF 2.0, so the `Version`
+    /// of the catalog is set to 2.0.
+    pub fn set_field_tab_order(&mut self, field_names: &[&str]) -> Result<(), Error> {
+        self.load_acro_form()?;
+        let mut doc = self.raw_document.get_prev_documents().clone();
+        require_pdf_2(&mut doc)?;
+
+        // Link every widget to its position in the tab order.
+        let mut widget_order: HashMap<ObjectId, usize> = HashMap::new();
+        for (index, field_name) in field_names.iter().enumerate() {
+            for widget_id in self.get_form_field(field_name)?.get_widget_ids(&doc)? {
+                widget_order.insert(widget_id, index);
+            }
+        }
+
+        for page_id in doc.get_pages().into_values() {
+            // `Annots` can be an array or a reference to an array.
+            let annots_id = match doc.get_object(page_id)?.as_dict()?.get(b"Annots") {
+                Ok(Object::Reference(annots_id)) => Some(*annots_id),
+                Ok(_) => None,
+                Err(_) => continue,
+            };
+            let annots = match annots_id {
+                Some(annots_id) => doc.get_object_mut(annots_id)?.as_array_mut()?,
+                None => doc
+                    .get_object_mut(page_id)?
+                    .as_dict_mut()?
+                    .get_mut(b"Annots")?
+                    .as_array_mut()?,
+            };
+            Self::sort_annotations(annots, &widget_order);
+
+            let page = doc.get_object_mut(page_id)?.as_dict_mut()?;
+            page.set(
+                "Tabs",
+                Object::Name(TabOrder::AnnotationsArray.as_name().as_bytes().to_vec()),
+            );
+        }
+
+        self.reload_document(doc)
+    }
+
+    fn sort_annotations(annots: &mut [Object], widget_order: &HashMap<ObjectId, usize>) {
+        // `sort_by_key` is stable, so unknown annotations keep their relative order.
+        annots.sort_by_key(|annot| {
+            annot
+                .as_reference()
+                .ok()
+                .and_then(|annot_id| widget_order.get(&annot_id))
+                .copied()
+                .unwrap_or(usize::MAX)
+        });
+    }
+}
@@ -0,0 +1,401 @@
+//! Keep tagged (accessible) documents valid when content is added, see `set_tag_content`.
+//! Added content gets a structure element in the structure tree (`StructTreeRoot`),
+//! or is marked as `Artifact` when it is not part of the real content, like a watermark.
+
+use crate::lopdf_utils::text_string;
+use crate::rectangle::Rectangle;
+use crate::{Error, InsertImage, InsertImageToPage, PDFSigningDocument};
+use lopdf::content::Operation;
+use lopdf::{dictionary, Dictionary, Object, ObjectId};
+
+/// How content that is added to a page is tagged.
+#[derive(Debug, Clone)]
+pub(crate) enum ContentTag<'a> {
+    /// Real content, with a structure element of type `structure_type` (like `Figure`).
+    Element {
+        structure_type: &'static str,
+        alternate_text: &'a str,
+        bbox: Rectangle,
+    },
+    /// Content that is not part of the real content, with the properties of the `Artifact`
+    /// (like `Type` and `Subtype`).
+    Artifact(Dictionary),
+}
+
+impl<'a> ContentTag<'a> {
+    /// A `Figure` for images with an alternate description, the others are decorative.
+    pub(crate) fn image(alternate_text: Option<&'a str>, bbox: &Rectangle) -> Self {
+        match alternate_text {
+            Some(alternate_text) => ContentTag::Element {
+                structure_type: "Figure",
+                alternate_text,
+                bbox: bbox.clone(),
+            },
+            None => ContentTag::Artifact(Dictionary::new()),
+        }
+    }
+}
+
+impl PDFSigningDocument {
+    /// Wrap the `operations` that are added to the page in a marked-content sequence with
+    /// `tag`, and add the structure element. The operations are returned unchanged when
+    /// tagging is disabled or the document is not tagged.
+    pub(crate) fn tag_page_content(
+        &mut self,
+        page_id: ObjectId,
+        operations: Vec<Operation>,
+        tag: ContentTag,
+    ) -> Result<Vec<Operation>, Error> {
+        let struct_tree_root_id = match self.get_struct_tree_root_id() {
+            Some(struct_tree_root_id) => struct_tree_root_id,
+            None => return Ok(operations),
+        };
+        let (tag_name, properties) = match tag {
+            ContentTag::Artifact(properties) => ("Artifact", properties),
+            ContentTag::Element {
+                structure_type,
+                alternate_text,
+                bbox,
+            } => {
+                let element_id = self.raw_document.new_document.new_object_id();
+                let mcid = self.add_page_parent_tree_entry(
+                    struct_tree_root_id,
+                    page_id,
+                    Object::Reference(element_id),
+                )?;
+                let parent_id = self.get_structure_parent_id(struct_tree_root_id)?;
+                let element = dictionary! {
+                    "Type" => "StructElem",
+                    "S" => structure_type,
+                    "P" => Object::Reference(parent_id),
+                    "Pg" => Object::Reference(page_id),
+                    "K" => mcid,
+                    "Alt" => text_string(alternate_text),
+                    "A" => dictionary! {
+                        "O" => "Layout",
+                        "BBox" => vec![bbox.x1.into(), bbox.y1.into(), bbox.x2.into(), bbox.y2.into()],
+                    },
+                };
+                self.raw_document
+                    .new_document
+                    .set_object(element_id, element);
+                self.add_structure_kid(parent_id, element_id)?;
+                (structure_type, dictionary! { "MCID" => mcid })
+            }
+        };
+
+        let tag_name = Object::Name(tag_name.as_bytes().to_vec());
+        let mut tagged_operations = vec![match properties.is_empty() {
+            true => Operation::new("BMC", vec![tag_name]),
+            false => Operation::new("BDC", vec![tag_name, Object::Dictionary(properties)]),
+        }];
+        tagged_operations.extend(operations);
+        tagged_operations.push(Operation::new("EMC", vec![]));
+        Ok(tagged_operations)
+    }
+
+    /// Add a structure element (`Link` or `Annot`) for an annotation that is added to the page.
+    /// Nothing is changed when tagging is disabled or the document is not tagged.
+    pub(crate) fn tag_annotation(
+        &mut self,
+        page_id: ObjectId,
+        annotation_id: ObjectId,
+    ) -> Result<(), Error> {
+        let struct_tree_root_id = match self.get_struct_tree_root_id() {
+            Some(struct_tree_root_id) => struct_tree_root_id,
+            None => return Ok(()),
+        };
+        let annotation = self
+            .get_latest_object(annotation_id)
+            .ok_or_else(|| Error::from("Annotation not found."))?
+            .as_dict()?;
+        let structure_type = match annotation.get(b"Subtype").and_then(Object::as_name) {
+            Ok(b"Link") => "Link",
+            _ => "Annot",
+        };
+        let contents = annotation.get(b"Contents").ok().cloned();
+
+        let parent_id = self.get_structure_parent_id(struct_tree_root_id)?;
+        let mut element = dictionary! {
+            "Type" => "StructElem",
+            "S" => structure_type,
+            "P" => Object::Reference(parent_id),
+            "Pg" => Object::Reference(page_id),
+            "K" => dictionary! {
+                "Type" => "OBJR",
+                "Obj" => Object::Reference(annotation_id),
+                "Pg" => Object::Reference(page_id),
+            },
+        };
+        if let Some(contents) = contents {
+            element.set("Alt", contents);
+        }
+        let element_id = self.add_object(element);
+        self.add_structure_kid(parent_id, element_id)?;
+
+        let key = self.add_parent_tree_entry(struct_tree_root_id, Object::Reference(element_id))?;
+        self.opt_clone_object_to_new_document(annotation_id)?;
+        self.raw_document
+            .new_document
+            .get_object_mut(annotation_id)?
+            .as_dict_mut()?
+            .set("StructParent", key);
+
+        // The tab order of pages with annotations must follow the structure.
+        self.opt_clone_object_to_new_document(page_id)?;
+        let page = self
+            .raw_document
+            .new_document
+            .get_object_mut(page_id)?
+            .as_dict_mut()?;
+        if !page.has(b"Tabs") {
+            page.set("Tabs", "S");
+        }
+        Ok(())
+    }
+
+    /// Get the structure tree root of the document, `None` when tagging is disabled
+    /// or the document is not tagged.
+    fn get_struct_tree_root_id(&self) -> Option<ObjectId> {
+        if !self.tag_content {
+            return None;
+        }
+        let catalog = self
+            .raw_document
+            .new_document
+            .trailer
+            .get(b"Root")
+            .ok()
+            .and_then(|root| self.resolve_latest_object(root))
+            .and_then(|catalog| catalog.as_dict().ok())?;
+        match catalog.get(b"StructTreeRoot") {
+            Ok(Object::Reference(struct_tree_root_id)) => Some(*struct_tree_root_id),
+            Ok(_) => {
+                tracing::warn!("Tagged content: StructTreeRoot is not an indirect object.");
+                None
+            }
+            Err(_) => None,
+        }
+    }
+
+    /// Get the element that new structure elements are added to: the only element of the
+    /// structure tree (like `Document`), or else the structure tree root.
+    fn get_structure_parent_id(&self, struct_tree_root_id: ObjectId) -> Result<ObjectId, Error> {
+        let struct_tree_root = self
+            .get_latest_object(struct_tree_root_id)
+            .ok_or_else(|| Error::from("Tagged content: StructTreeRoot not found."))?
+            .as_dict()?;
+        let kids = match struct_tree_root.get(b"K") {
+            Ok(Object::Array(kids)) => kids.as_slice(),
+            Ok(kid) => std::slice::from_ref(kid),
+            Err(_) => &[],
+        };
+        let element_id = match kids {
+            [Object::Reference(element_id)] => *element_id,
+            _ => return Ok(struct_tree_root_id),
+        };
+        let is_element = self
+            .get_latest_object(element_id)
+            .and_then(|element| element.as_dict().ok())
+            .map(|element| element.has(b"S"))
+            .unwrap_or(false);
+        match is_element {
+            true => Ok(element_id),
+            false => Ok(struct_tree_root_id),
+        }
+    }
+
+    /// Add `kid_id` as last kid (`K`) of the structure element or structure tree root.
+    fn add_structure_kid(&mut self, parent_id: ObjectId, kid_id: ObjectId) -> Result<(), Error> {
+        self.opt_clone_object_to_new_document(parent_id)?;
+        let parent = self
+            .raw_document
+            .new_document
+            .get_object_mut(parent_id)?
+            .as_dict_mut()?;
+        let kid = Object::Reference(kid_id);
+        match parent.get_mut(b"K") {
+            Ok(Object::Array(kids)) => kids.push(kid),
+            Ok(existing_kid) => *existing_kid = Object::Array(vec![existing_kid.clone(), kid]),
+            Err(_) => parent.set("K", kid),
+        }
+        Ok(())
+    }
+
+    /// Add the structure element `element` for the next marked-content identifier (`MCID`) of
+    /// the page to the parent tree, return the identifier.
+    fn add_page_parent_tree_entry(
+        &mut self,
+        struct_tree_root_id: ObjectId,
+        page_id: ObjectId,
+        element: Object,
+    ) -> Result<i64, Error> {
+        let mut entries = self.get_parent_tree_entries(struct_tree_root_id);
+        let key = self
+            .get_latest_object(page_id)
+            .and_then(|page| page.as_dict().ok())
+            .and_then(|page| page.get(b"StructParents").ok())
+            .and_then(|key| key.as_i64().ok());
+        let index = key.and_then(|key| entries.iter().position(|(entry_key, _)| *entry_key == key));
+        match (key, index) {
+            (Some(_), Some(index)) => match &mut entries[index].1 {
+                // The array of the page is an indirect object, it can be changed without
+                // writing the parent tree again.
+                Object::Reference(elements_id) => {
+                    let elements_id = *elements_id;
+                    self.opt_clone_object_to_new_document(elements_id)?;
+                    let elements = self
+                        .raw_document
+                        .new_document
+                        .get_object_mut(elements_id)?
+                        .as_array_mut()?;
+                    elements.push(element);
+                    Ok(elements.len() as i64 - 1)
+                }
+                Object::Array(elements) => {
+                    elements.push(element);
+                    let mcid = elements.len() as i64 - 1;
+                    self.set_parent_tree_entries(struct_tree_root_id, entries)?;
+                    Ok(mcid)
+                }
+                _ => Err(Error::from(
+                    "Tagged content: Invalid parent tree entry of the page.",
+                )),
+            },
+            (Some(key), None) => {
+                entries.push((key, Object::Array(vec![element])));
+                self.set_parent_tree_entries(struct_tree_root_id, entries)?;
+                Ok(0)
+            }
+            (None, _) => {
+                let key = self.get_next_parent_tree_key(struct_tree_root_id, &entries);
+                entries.push((key, Object::Array(vec![element])));
+                self.set_parent_tree_entries(struct_tree_root_id, entries)?;
+                self.opt_clone_object_to_new_document(page_id)?;
+                self.raw_document
+                    .new_document
+                    .get_object_mut(page_id)?
+                    .as_dict_mut()?
+                    .set("StructParents", key);
+                Ok(0)
+            }
+        }
+    }
+
+    /// Add `value` to the parent tree with a new key, return the key.
+    fn add_parent_tree_entry(
+        &mut self,
+        struct_tree_root_id: ObjectId,
+        value: Object,
+    ) -> Result<i64, Error> {
+        let mut entries = self.get_parent_tree_entries(struct_tree_root_id);
+        let key = self.get_next_parent_tree_key(struct_tree_root_id, &entries);
+        entries.push((key, value));
+        self.set_parent_tree_entries(struct_tree_root_id, entries)?;
+        Ok(key)
+    }
+
+    /// Get a key that is not used in the parent tree, at least `ParentTreeNextKey`.
+    fn get_next_parent_tree_key(
+        &self,
+        struct_tree_root_id: ObjectId,
+        entries: &[(i64, Object)],
+    ) -> i64 {
+        let next_key = self
+            .get_latest_object(struct_tree_root_id)
+            .and_then(|root| root.as_dict().ok())
+            .and_then(|root| root.get(b"ParentTreeNextKey").ok())
+            .and_then(|next_key| next_key.as_i64().ok())
+            .unwrap_or(0);
+        entries
+            .iter()
+            .map(|(key, _)| key + 1)
+            .fold(next_key, i64::max)
+    }
+
+    /// Get the entries (key and value) of the parent tree, including the changes not saved yet.
+    fn get_parent_tree_entries(&self, struct_tree_root_id: ObjectId) -> Vec<(i64, Object)> {
+        let parent_tree = self
+            .get_latest_object(struct_tree_root_id)
+            .and_then(|root| root.as_dict().ok())
+            .and_then(|root| root.get(b"ParentTree").ok())
+            .cloned();
+        let mut entries = vec![];
+        if let Some(parent_tree) = parent_tree {
+            self.collect_number_tree_entries(&parent_tree, 0, &mut entries);
+        }
+        entries
+    }
+
+    /// Collect the entries (key and value) of the number tree `node` and its kids.
+    fn collect_number_tree_entries(
+        &self,
+        node: &Object,
+        depth: usize,
+        entries: &mut Vec<(i64, Object)>,
+    ) {
+        // Limit the depth, so a loop in the number tree does not hang.
+        let node = match self.resolve_latest_object(node) {
+            Some(Object::Dictionary(node)) if depth < self.limits.max_depth => node,
+            _ => return,
+        };
+        let array = |key: &[u8]| {
+            node.get(key)
+                .ok()
+                .and_then(|array| self.resolve_latest_object(array))
+                .and_then(|array| array.as_array().ok())
+        };
+        if let Some(nums) = array(b"Nums") {
+            for pair in nums.chunks_exact(2) {
+                if let Ok(key) = pair[0].as_i64() {
+                    entries.push((key, pair[1].clone()));
+                }
+            }
+        }
+        for kid in array(b"Kids").into_iter().flatten() {
+            self.collect_number_tree_entries(kid, depth + 1, entries);
+        }
+    }
+
+    /// Replace the parent tree with a tree of 1 node with `entries`, and update the next key.
+    fn set_parent_tree_entries(
+        &mut self,
+        struct_tree_root_id: ObjectId,
+        mut entries: Vec<(i64, Object)>,
+    ) -> Result<(), Error> {
+        entries.sort_by_key(|(key, _)| *key);
+        let next_key = self.get_next_parent_tree_key(struct_tree_root_id, &entries);
+        let nums: Vec<Object> = entries
+            .into_iter()
+            .flat_map(|(key, value)| [Object::Integer(key), value])
+            .collect();
+
+        self.opt_clone_object_to_new_document(struct_tree_root_id)?;
+        let parent_tree_id = self
+            .raw_document
+            .new_document
+            .get_object(struct_tree_root_id)?
+            .as_dict()?
+            .get(b"ParentTree")
+            .and_then(Object::as_reference)
+            .ok();
+        let parent_tree_id = match parent_tree_id {
+            // Replace the root node, the other nodes are not used anymore.
+            Some(parent_tree_id) => {
+                self.raw_document
+                    .new_document
+                    .set_object(parent_tree_id, dictionary! { "Nums" => nums });
+                parent_tree_id
+            }
+            None => self.add_object(dictionary! { "Nums" => nums }),
+        };
+        let struct_tree_root = self
+            .raw_document
+            .new_document
+            .get_object_mut(struct_tree_root_id)?
+            .as_dict_mut()?;
+        struct_tree_root.set("ParentTree", Object::Reference(parent_tree_id));
+        struct_tree_root.set("ParentTreeNextKey", next_key);
+        Ok(())
+    }
+}
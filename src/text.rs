@@ -0,0 +1,826 @@
+//! Extract the text of pages with the position of each character, for example to place
+//! fields at an anchor text or to search the document.
+//!
+//! Characters are decoded with the `ToUnicode` CMap of the font, or with the encoding
+//! (`WinAnsiEncoding`, `MacRomanEncoding`, `StandardEncoding` and `Differences`).
+
+use crate::font::decode_win_ansi;
+use crate::image_replace::{as_number, multiply, transform_unit_square};
+use crate::rectangle::Rectangle;
+use crate::{Error, PDFSigningDocument};
+use lopdf::content::{Content, Operation};
+use lopdf::{Dictionary, Object};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub(crate) const IDENTITY: [f64; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+/// Limit the depth of nested form XObjects.
+pub(crate) const MAX_FORM_DEPTH: usize = 8;
+
+/// Characters `0x80` to `0xFF` of `MacRomanEncoding`.
+const MAC_ROMAN_80_TO_FF: &str = "ÄÅÇÉÑÖÜáàâäãåçéèêëíìîïñóòôöõúùûü†°¢£§•¶ß®©™´¨≠ÆØ∞±≤≥¥µ∂∑∏π∫ªºΩæø\
+¿¡¬√ƒ≈∆«»…\u{A0}ÀÃÕŒœ–—“”‘’÷◊ÿŸ⁄€‹›ﬁﬂ‡·‚„‰ÂÊÁËÈÍÎÏÌÓÔ\u{F8FF}ÒÚÛÙıˆ˜¯˘˙˚¸˝˛ˇ";
+/// Glyph names used in `Differences` that are not 1 character or `uniXXXX`.
+const GLYPH_NAMES: [(&str, char); 95] = [
+    ("space", ' '),
+    ("exclam", '!'),
+    ("quotedbl", '"'),
+    ("numbersign", '#'),
+    ("dollar", '$'),
+    ("percent", '%'),
+    ("ampersand", '&'),
+    ("quotesingle", '\''),
+    ("parenleft", '('),
+    ("parenright", ')'),
+    ("asterisk", '*'),
+    ("plus", '+'),
+    ("comma", ','),
+    ("hyphen", '-'),
+    ("period", '.'),
+    ("slash", '/'),
+    ("zero", '0'),
+    ("one", '1'),
+    ("two", '2'),
+    ("three", '3'),
+    ("four", '4'),
+    ("five", '5'),
+    ("six", '6'),
+    ("seven", '7'),
+    ("eight", '8'),
+    ("nine", '9'),
+    ("colon", ':'),
+    ("semicolon", ';'),
+    ("less", '<'),
+    ("equal", '='),
+    ("greater", '>'),
+    ("question", '?'),
+    ("at", '@'),
+    ("bracketleft", '['),
+    ("backslash", '\\'),
+    ("bracketright", ']'),
+    ("asciicircum", '^'),
+    ("underscore", '_'),
+    ("grave", '`'),
+    ("braceleft", '{'),
+    ("bar", '|'),
+    ("braceright", '}'),
+    ("asciitilde", '~'),
+    ("quoteleft", '‘'),
+    ("quoteright", '’'),
+    ("quotedblleft", '“'),
+    ("quotedblright", '”'),
+    ("quotesinglbase", '‚'),
+    ("quotedblbase", '„'),
+    ("endash", '–'),
+    ("emdash", '—'),
+    ("bullet", '•'),
+    ("ellipsis", '…'),
+    ("dagger", '†'),
+    ("daggerdbl", '‡'),
+    ("perthousand", '‰'),
+    ("guilsinglleft", '‹'),
+    ("guilsinglright", '›'),
+    ("guillemotleft", '«'),
+    ("guillemotright", '»'),
+    ("Euro", '€'),
+    ("trademark", '™'),
+    ("copyright", '©'),
+    ("registered", '®'),
+    ("degree", '°'),
+    ("section", '§'),
+    ("paragraph", '¶'),
+    ("sterling", '£'),
+    ("yen", '¥'),
+    ("cent", '¢'),
+    ("currency", '¤'),
+    ("fi", 'ﬁ'),
+    ("fl", 'ﬂ'),
+    ("germandbls", 'ß'),
+    ("AE", 'Æ'),
+    ("ae", 'æ'),
+    ("OE", 'Œ'),
+    ("oe", 'œ'),
+    ("Oslash", 'Ø'),
+    ("oslash", 'ø'),
+    ("Eth", 'Ð'),
+    ("eth", 'ð'),
+    ("Thorn", 'Þ'),
+    ("thorn", 'þ'),
+    ("dotlessi", 'ı'),
+    ("multiply", '×'),
+    ("divide", '÷'),
+    ("plusminus", '±'),
+    ("minus", '−'),
+    ("nbspace", '\u{A0}'),
+    ("exclamdown", '¡'),
+    ("questiondown", '¿'),
+    ("ordfeminine", 'ª'),
+    ("ordmasculine", 'º'),
+    ("mu", 'µ'),
+];
+/// Accents of glyph names like `eacute`, with the base letters and the composed characters.
+const ACCENTED_GLYPHS: [(&str, &str, &str); 7] = [
+    ("grave", "AEIOUaeiou", "ÀÈÌÒÙàèìòù"),
+    ("acute", "AEIOUYaeiouy", "ÁÉÍÓÚÝáéíóúý"),
+    ("circumflex", "AEIOUaeiou", "ÂÊÎÔÛâêîôû"),
+    ("dieresis", "AEIOUaeiouy", "ÄËÏÖÜäëïöüÿ"),
+    ("tilde", "ANOano", "ÃÑÕãñõ"),
+    ("ring", "Aa", "Åå"),
+    ("cedilla", "Cc", "Çç"),
+];
+
+/// A character on a page and the area it covers, in the coordinates of the page.
+#[derive(Debug, Clone)]
+pub struct TextGlyph {
+    /// The Unicode text of the character, this can be more than 1 character (like a ligature).
+    pub text: String,
+    pub rect: Rectangle,
+}
+
+impl PDFSigningDocument {
+    /// Extract the characters of the page with index `page_index` (starting at 0),
+    /// in the order of the content. Text in form XObjects is included, text in
+    /// annotations (like form fields) is not.
+    pub fn extract_text_glyphs(&self, page_index: usize) -> Result<Vec<TextGlyph>, Error> {
+        let page_id = self.get_page_id(page_index)?;
+        let resources = self
+            .get_inherited_page_attribute(page_id, b"Resources")
+            .and_then(|resources| resources.as_dict().ok());
+        let operations = self.get_page_operations(page_id)?;
+        let mut glyphs = vec![];
+        self.collect_text_glyphs(operations, resources, IDENTITY, 0, &mut glyphs)?;
+        Ok(glyphs)
+    }
+
+    /// Extract the text of the page with index `page_index` (starting at 0). Lines are
+    /// separated by a newline and spaces are added between words that are drawn apart.
+    pub fn extract_text(&self, page_index: usize) -> Result<String, Error> {
+        Ok(glyphs_to_text(&self.extract_text_glyphs(page_index)?))
+    }
+
+    fn collect_text_glyphs<'a>(
+        &'a self,
+        operations: Vec<Operation>,
+        resources: Option<&'a Dictionary>,
+        ctm: [f64; 6],
+        depth: usize,
+        glyphs: &mut Vec<TextGlyph>,
+    ) -> Result<(), Error> {
+        let resolve = |object: &'a Object| self.resolve_latest_object(object);
+        let mut content_state = ContentState::new(ctm, load_fonts(resources, &resolve));
+        for operation in operations {
+            content_state.apply(&operation);
+            match operation.operator.as_str() {
+                "Tj" | "TJ" | "'" | "\"" => {
+                    glyphs.extend(content_state.show_text(&operation).into_iter().filter_map(
+                        |shown_text| match shown_text {
+                            ShownText::Glyph(glyph) => Some(TextGlyph {
+                                text: glyph.text,
+                                rect: glyph.rect,
+                            }),
+                            ShownText::Adjustment(_) => None,
+                        },
+                    ));
+                }
+                "Do" if depth < MAX_FORM_DEPTH => {
+                    let form = operation
+                        .operands
+                        .first()
+                        .and_then(|name| name.as_name().ok())
+                        .and_then(|name| {
+                            let xobjects = resources?.get(b"XObject").ok()?;
+                            resolve(xobjects)?.as_dict().ok()?.get(name).ok()
+                        })
+                        .and_then(resolve)
+                        .and_then(|form| form.as_stream().ok())
+                        .filter(|form| {
+                            form.dict.get(b"Subtype").and_then(Object::as_name_str).ok()
+                                == Some("Form")
+                        });
+                    let form = match form {
+                        Some(form) => form,
+                        None => continue,
+                    };
+                    let form_matrix = match get_numbers(&form.dict, b"Matrix")[..] {
+                        [a, b, c, d, e, f] => [a, b, c, d, e, f],
+                        _ => IDENTITY,
+                    };
+                    // Forms without resources use the resources of the page.
+                    let form_resources = match form.dict.get(b"Resources") {
+                        Ok(form_resources) => resolve(form_resources)
+                            .and_then(|form_resources| form_resources.as_dict().ok()),
+                        Err(_) => resources,
+                    };
+                    let content = form
+                        .decompressed_content()
+                        .unwrap_or_else(|_| form.content.clone());
+                    self.collect_text_glyphs(
+                        Content::decode(&content)?.operations,
+                        form_resources,
+                        multiply(&form_matrix, &content_state.ctm()),
+                        depth + 1,
+                        glyphs,
+                    )?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Join the characters to text. A newline is added when the next character is on
+/// another line, a space when there is a gap between the characters.
+fn glyphs_to_text(glyphs: &[TextGlyph]) -> String {
+    let mut text = String::new();
+    let mut previous: Option<&TextGlyph> = None;
+    for glyph in glyphs {
+        if let Some(previous) = previous {
+            let height = previous.rect.height().max(glyph.rect.height());
+            let same_line = (glyph.rect.y1 - previous.rect.y1).abs() < height / 2.0;
+            if !same_line {
+                text.push('\n');
+            } else if glyph.rect.x1 - previous.rect.x2 > height * 0.15
+                && !previous.text.ends_with(' ')
+                && !glyph.text.starts_with(' ')
+            {
+                text.push(' ');
+            }
+        }
+        text.push_str(&glyph.text);
+        previous = Some(glyph);
+    }
+    text
+}
+
+/// Get the numbers in the array `key` of `dict`, like a `Matrix` or `BBox`.
+pub(crate) fn get_numbers(dict: &Dictionary, key: &[u8]) -> Vec<f64> {
+    dict.get(key)
+        .and_then(Object::as_array)
+        .map(|values| values.iter().filter_map(as_number).collect())
+        .unwrap_or_default()
+}
+
+/// Load the fonts in the resources, so they can be selected by name with `Tf`.
+pub(crate) fn load_fonts<'a>(
+    resources: Option<&'a Dictionary>,
+    resolve: &dyn Fn(&'a Object) -> Option<&'a Object>,
+) -> HashMap<Vec<u8>, Rc<TextFont>> {
+    let fonts = resources
+        .and_then(|resources| resources.get(b"Font").ok())
+        .and_then(resolve)
+        .and_then(|fonts| fonts.as_dict().ok());
+    let mut loaded_fonts = HashMap::new();
+    for (name, font) in fonts.into_iter().flat_map(Dictionary::iter) {
+        if let Some(font) = resolve(font).and_then(|font| font.as_dict().ok()) {
+            loaded_fonts.insert(name.clone(), Rc::new(TextFont::from_font(font, resolve)));
+        }
+    }
+    loaded_fonts
+}
+
+/// The base encoding of a simple font.
+#[derive(Debug, Clone, Copy)]
+enum BaseEncoding {
+    Standard,
+    WinAnsi,
+    MacRoman,
+}
+
+/// The metrics of a font needed to find the area of each character,
+/// and how character codes are decoded to Unicode.
+#[derive(Debug, Clone)]
+pub(crate) struct TextFont {
+    /// Composite fonts (`Type0`) use 2 bytes per character.
+    two_bytes: bool,
+    /// Widths of the characters, relative to the font size.
+    widths: HashMap<u32, f64>,
+    default_width: f64,
+    ascent: f64,
+    descent: f64,
+    to_unicode: HashMap<u32, String>,
+    base_encoding: BaseEncoding,
+    differences: HashMap<u32, char>,
+}
+
+impl TextFont {
+    fn from_font<'a>(
+        font: &'a Dictionary,
+        resolve: &dyn Fn(&'a Object) -> Option<&'a Object>,
+    ) -> Self {
+        let get = |dict: &'a Dictionary, key: &[u8]| dict.get(key).ok().and_then(resolve);
+        let number = |dict: &'a Dictionary, key: &[u8]| get(dict, key).and_then(as_number);
+        let array = |dict: &'a Dictionary, key: &[u8]| -> &'a [Object] {
+            get(dict, key)
+                .and_then(|value| value.as_array().ok())
+                .map(Vec::as_slice)
+                .unwrap_or_default()
+        };
+
+        let subtype = font.get(b"Subtype").and_then(Object::as_name_str).ok();
+        let two_bytes = subtype == Some("Type0");
+        let metrics_font = match two_bytes {
+            true => array(font, b"DescendantFonts")
+                .first()
+                .and_then(resolve)
+                .and_then(|descendant| descendant.as_dict().ok()),
+            false => Some(font),
+        };
+        let descriptor = metrics_font
+            .and_then(|metrics_font| get(metrics_font, b"FontDescriptor"))
+            .and_then(|descriptor| descriptor.as_dict().ok());
+        let descriptor_number =
+            |key: &[u8]| descriptor.and_then(|descriptor| number(descriptor, key));
+
+        let mut widths = HashMap::new();
+        let default_width;
+        if let (true, Some(metrics_font)) = (two_bytes, metrics_font) {
+            default_width = number(metrics_font, b"DW").unwrap_or(1000.0) / 1000.0;
+            // `W` contains `first [widths]` and `first last width`.
+            let w = array(metrics_font, b"W");
+            let mut index = 0;
+            while index + 1 < w.len() {
+                let first = as_number(&w[index]).unwrap_or(0.0) as u32;
+                match resolve(&w[index + 1]).and_then(|value| value.as_array().ok()) {
+                    Some(range_widths) => {
+                        for (offset, width) in range_widths.iter().enumerate() {
+                            let width = as_number(width).unwrap_or(0.0) / 1000.0;
+                            widths.insert(first.saturating_add(offset as u32), width);
+                        }
+                        index += 2;
+                    }
+                    None if index + 2 < w.len() => {
+                        let last = as_number(&w[index + 1]).unwrap_or(0.0) as u32;
+                        let width = as_number(&w[index + 2]).unwrap_or(0.0) / 1000.0;
+                        // Limit the range, so an invalid range does not use all memory.
+                        for code in first..=last.min(first.saturating_add(0xFFFF)) {
+                            widths.insert(code, width);
+                        }
+                        index += 3;
+                    }
+                    None => break,
+                }
+            }
+        } else {
+            // `Type3` fonts have their own glyph space.
+            let scale = match subtype {
+                Some("Type3") => array(font, b"FontMatrix")
+                    .first()
+                    .and_then(as_number)
+                    .unwrap_or(0.001),
+                _ => 0.001,
+            };
+            default_width = descriptor_number(b"MissingWidth").unwrap_or(500.0) * scale;
+            let first_char = number(font, b"FirstChar").unwrap_or(0.0) as u32;
+            for (offset, width) in array(font, b"Widths").iter().enumerate() {
+                let width = resolve(width).and_then(as_number).unwrap_or(0.0);
+                widths.insert(first_char.saturating_add(offset as u32), width * scale);
+            }
+        }
+
+        let to_unicode = get(font, b"ToUnicode")
+            .and_then(|to_unicode| to_unicode.as_stream().ok())
+            .map(|to_unicode| {
+                parse_to_unicode(
+                    &to_unicode
+                        .decompressed_content()
+                        .unwrap_or_else(|_| to_unicode.content.clone()),
+                )
+            })
+            .unwrap_or_default();
+        let encoding = get(font, b"Encoding");
+        let encoding_name = encoding
+            .and_then(|encoding| match encoding {
+                Object::Dictionary(encoding) => get(encoding, b"BaseEncoding"),
+                _ => Some(encoding),
+            })
+            .and_then(|name| name.as_name_str().ok());
+        let base_encoding = match encoding_name {
+            Some("WinAnsiEncoding") => BaseEncoding::WinAnsi,
+            Some("MacRomanEncoding") => BaseEncoding::MacRoman,
+            _ => BaseEncoding::Standard,
+        };
+        let mut differences = HashMap::new();
+        if let Some(Object::Dictionary(encoding)) = encoding {
+            let mut code = 0;
+            for difference in array(encoding, b"Differences") {
+                match difference {
+                    Object::Integer(first_code) => code = *first_code as u32,
+                    Object::Name(glyph_name) => {
+                        if let Some(character) = glyph_name_to_char(glyph_name) {
+                            differences.insert(code, character);
+                        }
+                        code += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        TextFont {
+            two_bytes,
+            widths,
+            default_width,
+            ascent: descriptor_number(b"Ascent")
+                .filter(|ascent| *ascent > 0.0)
+                .unwrap_or(800.0)
+                / 1000.0,
+            descent: descriptor_number(b"Descent")
+                .filter(|descent| *descent < 0.0)
+                .unwrap_or(-200.0)
+                / 1000.0,
+            to_unicode,
+            base_encoding,
+            differences,
+        }
+    }
+
+    fn get_width(&self, code: u32) -> f64 {
+        self.widths
+            .get(&code)
+            .copied()
+            .unwrap_or(self.default_width)
+    }
+
+    /// Decode the character code to Unicode, unknown characters become `U+FFFD`.
+    fn decode(&self, code: u32) -> String {
+        if let Some(text) = self.to_unicode.get(&code) {
+            return text.clone();
+        }
+        let character = match (self.two_bytes, u8::try_from(code)) {
+            (false, Ok(code)) => self.differences.get(&u32::from(code)).copied().or_else(|| {
+                match self.base_encoding {
+                    BaseEncoding::WinAnsi => decode_win_ansi(code),
+                    BaseEncoding::MacRoman if code >= 0x80 => {
+                        MAC_ROMAN_80_TO_FF.chars().nth(usize::from(code - 0x80))
+                    }
+                    _ => match code {
+                        0x27 => Some('’'),
+                        0x60 => Some('‘'),
+                        0x20..=0x7E => Some(char::from(code)),
+                        _ => None,
+                    },
+                }
+            }),
+            _ => None,
+        };
+        character.unwrap_or('\u{FFFD}').to_string()
+    }
+}
+
+/// Get the character of a glyph name, like `A`, `eacute` or `uni20AC`.
+fn glyph_name_to_char(glyph_name: &[u8]) -> Option<char> {
+    let glyph_name = std::str::from_utf8(glyph_name).ok()?;
+    let mut characters = glyph_name.chars();
+    if let (Some(character), None) = (characters.next(), characters.next()) {
+        return Some(character);
+    }
+    if let Some(&(_, character)) = GLYPH_NAMES.iter().find(|(name, _)| *name == glyph_name) {
+        return Some(character);
+    }
+    for (accent, letters, accented_letters) in ACCENTED_GLYPHS {
+        if let Some(letter) = glyph_name.strip_suffix(accent) {
+            if let Some(index) = letters.chars().position(|base| letter == base.to_string()) {
+                return accented_letters.chars().nth(index);
+            }
+        }
+    }
+    let hex = glyph_name
+        .strip_prefix("uni")
+        .or_else(|| glyph_name.strip_prefix('u'))?;
+    char::from_u32(u32::from_str_radix(hex.get(..hex.len().min(6))?, 16).ok()?)
+}
+
+/// A token of a CMap: a hex string, an array bracket or another word.
+#[derive(Debug, PartialEq)]
+enum CMapToken {
+    Hex(Vec<u8>),
+    ArrayStart,
+    ArrayEnd,
+    Word(Vec<u8>),
+}
+
+/// Parse the `bfchar` and `bfrange` mappings of a `ToUnicode` CMap.
+fn parse_to_unicode(data: &[u8]) -> HashMap<u32, String> {
+    let tokens = tokenize_cmap(data);
+    let code = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .take(4)
+            .fold(0, |code, byte| (code << 8) | u32::from(*byte))
+    };
+    let text = |bytes: &[u8]| {
+        let units: Vec<u16> = bytes
+            .chunks(2)
+            .map(|unit| u16::from_be_bytes([unit[0], *unit.get(1).unwrap_or(&0)]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+
+    let mut mappings = HashMap::new();
+    let mut index = 0;
+    let mut section = None;
+    while index < tokens.len() {
+        match (&tokens[index], section) {
+            (CMapToken::Word(word), _) => {
+                section = match word.as_slice() {
+                    b"beginbfchar" => Some(false),
+                    b"beginbfrange" => Some(true),
+                    _ => None,
+                };
+                index += 1;
+            }
+            (CMapToken::Hex(source), Some(false)) => {
+                if let Some(CMapToken::Hex(destination)) = tokens.get(index + 1) {
+                    mappings.insert(code(source), text(destination));
+                }
+                index += 2;
+            }
+            (CMapToken::Hex(first), Some(true)) => {
+                let (first, last) = match tokens.get(index + 1) {
+                    Some(CMapToken::Hex(last)) => (code(first), code(last)),
+                    _ => {
+                        index += 1;
+                        continue;
+                    }
+                };
+                // Limit the range, so an invalid range does not use all memory.
+                let last = last.min(first.saturating_add(0xFFFF));
+                match tokens.get(index + 2) {
+                    Some(CMapToken::Hex(destination)) if destination.len() >= 2 => {
+                        let mut destination = destination.clone();
+                        let length = destination.len();
+                        let base =
+                            u16::from_be_bytes([destination[length - 2], destination[length - 1]]);
+                        for (offset, source) in (first..=last).enumerate() {
+                            let unit = base.wrapping_add(offset as u16).to_be_bytes();
+                            destination[length - 2..].copy_from_slice(&unit);
+                            mappings.insert(source, text(&destination));
+                        }
+                        index += 3;
+                    }
+                    Some(CMapToken::ArrayStart) => {
+                        index += 3;
+                        let mut source = first;
+                        while let Some(CMapToken::Hex(destination)) = tokens.get(index) {
+                            if source <= last {
+                                mappings.insert(source, text(destination));
+                            }
+                            source += 1;
+                            index += 1;
+                        }
+                        index += 1;
+                    }
+                    _ => index += 3,
+                }
+            }
+            _ => index += 1,
+        }
+    }
+    mappings
+}
+
+fn tokenize_cmap(data: &[u8]) -> Vec<CMapToken> {
+    let is_delimiter = |byte: u8| b"<>[]()/%{}".contains(&byte) || byte.is_ascii_whitespace();
+    let mut tokens = vec![];
+    let mut index = 0;
+    while index < data.len() {
+        match data[index] {
+            b'<' if data.get(index + 1) == Some(&b'<') => index += 2,
+            b'>' if data.get(index + 1) == Some(&b'>') => index += 2,
+            b'<' => {
+                let end = data[index..]
+                    .iter()
+                    .position(|byte| *byte == b'>')
+                    .map_or(data.len(), |end| index + end);
+                let digits: Vec<u8> = data[index + 1..end]
+                    .iter()
+                    .filter_map(|byte| (*byte as char).to_digit(16))
+                    .map(|digit| digit as u8)
+                    .collect();
+                // An odd number of digits is completed with `0`.
+                let bytes = digits
+                    .chunks(2)
+                    .map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0))
+                    .collect();
+                tokens.push(CMapToken::Hex(bytes));
+                index = end + 1;
+            }
+            b'[' => {
+                tokens.push(CMapToken::ArrayStart);
+                index += 1;
+            }
+            b']' => {
+                tokens.push(CMapToken::ArrayEnd);
+                index += 1;
+            }
+            b'%' => {
+                while index < data.len() && data[index] != b'\n' && data[index] != b'\r' {
+                    index += 1;
+                }
+            }
+            byte if is_delimiter(byte) => index += 1,
+            _ => {
+                let start = index;
+                while index < data.len() && !is_delimiter(data[index]) {
+                    index += 1;
+                }
+                tokens.push(CMapToken::Word(data[start..index].to_vec()));
+            }
+        }
+    }
+    tokens
+}
+
+#[derive(Debug, Clone)]
+struct GraphicsState {
+    ctm: [f64; 6],
+    font: Option<Rc<TextFont>>,
+    font_size: f64,
+    char_spacing: f64,
+    word_spacing: f64,
+    horizontal_scaling: f64,
+    leading: f64,
+    rise: f64,
+}
+
+/// A character drawn by a text showing operation.
+#[derive(Debug, Clone)]
+pub(crate) struct ShownGlyph {
+    /// The bytes of the character code in the string.
+    pub(crate) code: Vec<u8>,
+    /// How far the text position moves after the character, in text space.
+    pub(crate) advance: f64,
+    pub(crate) rect: Rectangle,
+    pub(crate) text: String,
+}
+
+/// An element of a text showing operation.
+#[derive(Debug, Clone)]
+pub(crate) enum ShownText {
+    Glyph(ShownGlyph),
+    /// A position adjustment of a `TJ` array.
+    Adjustment(Object),
+}
+
+/// Follow the graphics and text state of a content stream, to find where characters are drawn.
+pub(crate) struct ContentState {
+    state: GraphicsState,
+    saved_states: Vec<GraphicsState>,
+    text_matrix: [f64; 6],
+    line_matrix: [f64; 6],
+    fonts: HashMap<Vec<u8>, Rc<TextFont>>,
+}
+
+impl ContentState {
+    pub(crate) fn new(ctm: [f64; 6], fonts: HashMap<Vec<u8>, Rc<TextFont>>) -> Self {
+        ContentState {
+            state: GraphicsState {
+                ctm,
+                font: None,
+                font_size: 0.0,
+                char_spacing: 0.0,
+                word_spacing: 0.0,
+                horizontal_scaling: 1.0,
+                leading: 0.0,
+                rise: 0.0,
+            },
+            saved_states: vec![],
+            text_matrix: IDENTITY,
+            line_matrix: IDENTITY,
+            fonts,
+        }
+    }
+
+    /// The current transformation matrix.
+    pub(crate) fn ctm(&self) -> [f64; 6] {
+        self.state.ctm
+    }
+
+    /// The scale of position adjustments in `TJ` arrays, the font size and horizontal scaling.
+    pub(crate) fn text_scale(&self) -> f64 {
+        self.state.font_size * self.state.horizontal_scaling
+    }
+
+    /// Update the state with `operation`. For `'` and `"` this only moves to the next line
+    /// (and sets the spacing), the text is shown with `show_text`.
+    pub(crate) fn apply(&mut self, operation: &Operation) {
+        let numbers: Vec<f64> = operation.operands.iter().filter_map(as_number).collect();
+        let state = &mut self.state;
+        match (operation.operator.as_str(), numbers.as_slice()) {
+            ("q", _) => self.saved_states.push(state.clone()),
+            ("Q", _) => *state = self.saved_states.pop().unwrap_or_else(|| state.clone()),
+            ("cm", [a, b, c, d, e, f]) => {
+                state.ctm = multiply(&[*a, *b, *c, *d, *e, *f], &state.ctm)
+            }
+            ("BT", _) => {
+                self.text_matrix = IDENTITY;
+                self.line_matrix = IDENTITY;
+            }
+            ("Tf", [font_size]) => {
+                state.font_size = *font_size;
+                state.font = operation
+                    .operands
+                    .first()
+                    .and_then(|name| name.as_name().ok())
+                    .and_then(|name| self.fonts.get(name))
+                    .cloned();
+            }
+            ("Tc", [char_spacing]) => state.char_spacing = *char_spacing,
+            ("Tw", [word_spacing]) => state.word_spacing = *word_spacing,
+            ("Tz", [horizontal_scaling]) => state.horizontal_scaling = horizontal_scaling / 100.0,
+            ("TL", [leading]) => state.leading = *leading,
+            ("Ts", [rise]) => state.rise = *rise,
+            ("Td", [x, y]) | ("TD", [x, y]) => {
+                if operation.operator == "TD" {
+                    state.leading = -y;
+                }
+                self.line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, *x, *y], &self.line_matrix);
+                self.text_matrix = self.line_matrix;
+            }
+            ("Tm", [a, b, c, d, e, f]) => {
+                self.line_matrix = [*a, *b, *c, *d, *e, *f];
+                self.text_matrix = self.line_matrix;
+            }
+            ("T*", _) | ("'", _) | ("\"", _) => {
+                if let ("\"", [word_spacing, char_spacing, ..]) =
+                    (operation.operator.as_str(), numbers.as_slice())
+                {
+                    state.word_spacing = *word_spacing;
+                    state.char_spacing = *char_spacing;
+                }
+                let leading = state.leading;
+                self.line_matrix =
+                    multiply(&[1.0, 0.0, 0.0, 1.0, 0.0, -leading], &self.line_matrix);
+                self.text_matrix = self.line_matrix;
+            }
+            _ => {}
+        }
+    }
+
+    /// Get the characters and adjustments of a text showing operation (`Tj`, `TJ`, `'`
+    /// or `"`) and move the text matrix to the end of the text.
+    pub(crate) fn show_text(&mut self, operation: &Operation) -> Vec<ShownText> {
+        let elements = match operation.operands.last() {
+            Some(Object::Array(elements)) => elements.as_slice(),
+            Some(text) => std::slice::from_ref(text),
+            None => &[],
+        };
+        let state = &self.state;
+        let scale = self.text_scale();
+        let (two_bytes, ascent, descent) = match &state.font {
+            Some(font) => (font.two_bytes, font.ascent, font.descent),
+            None => (false, 0.8, -0.2),
+        };
+
+        let mut shown_texts = vec![];
+        for element in elements {
+            let text = match element {
+                Object::String(text, _) => text,
+                _ => {
+                    if let Some(adjustment) = as_number(element) {
+                        let x = -adjustment / 1000.0 * scale;
+                        self.text_matrix =
+                            multiply(&[1.0, 0.0, 0.0, 1.0, x, 0.0], &self.text_matrix);
+                        shown_texts.push(ShownText::Adjustment(element.clone()));
+                    }
+                    continue;
+                }
+            };
+            for code_bytes in text.chunks(if two_bytes { 2 } else { 1 }) {
+                let code = code_bytes
+                    .iter()
+                    .fold(0, |code, byte| (code << 8) | u32::from(*byte));
+                let width = state.font.as_ref().map_or(0.5, |font| font.get_width(code));
+                let mut advance = width * state.font_size + state.char_spacing;
+                if !two_bytes && code == 32 {
+                    advance += state.word_spacing;
+                }
+                advance *= state.horizontal_scaling;
+
+                let glyph_matrix = multiply(
+                    &[
+                        advance,
+                        0.0,
+                        0.0,
+                        (ascent - descent) * state.font_size,
+                        0.0,
+                        state.rise + descent * state.font_size,
+                    ],
+                    &multiply(&self.text_matrix, &state.ctm),
+                );
+                shown_texts.push(ShownText::Glyph(ShownGlyph {
+                    code: code_bytes.to_vec(),
+                    advance,
+                    rect: transform_unit_square(&glyph_matrix),
+                    text: state
+                        .font
+                        .as_ref()
+                        .map_or_else(|| "\u{FFFD}".to_owned(), |font| font.decode(code)),
+                }));
+                self.text_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, advance, 0.0], &self.text_matrix);
+            }
+        }
+        shown_texts
+    }
+}
@@ -0,0 +1,216 @@
+//! Request RFC 3161 time stamp tokens for signatures (feature `network`), with failover
+//! between time stamp authorities, see `SigningOptionsBuilder::time_stamp_url`.
+
+use crate::der::{read_der, write_der};
+use crate::time_stamp_verification::{parse_tst_info, verify_time_stamp_token};
+use crate::trust_store::TrustAnchors;
+use crate::{Error, HttpClient};
+use cryptographic_message_syntax::SignedData;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+
+/// The DER encoded object identifiers used in time stamp requests and signatures.
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const OID_TIME_STAMP_TOKEN: &[u8] = &[
+    0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x10, 0x02, 0x0e,
+];
+
+/// Add a time stamp token of the signature value to the unsigned attributes of the first
+/// signer of the DER encoded CMS `signed_data`. The time stamp authorities at `urls` are
+/// tried in order, until one returns a valid token.
+pub(crate) fn add_time_stamp(
+    signed_data: &[u8],
+    urls: &[String],
+    timeout: Duration,
+    client: &HttpClient,
+) -> Result<Vec<u8>, Error> {
+    let invalid_signature = || Error::from("Time stamp: Invalid signature.");
+    // `ContentInfo`: the content type and the explicitly tagged `SignedData`.
+    let Some((0x30, content_info, _)) = read_der(signed_data) else {
+        return Err(invalid_signature());
+    };
+    let Some((0x06, _, rest)) = read_der(content_info) else {
+        return Err(invalid_signature());
+    };
+    let content_type = &content_info[..content_info.len() - rest.len()];
+    let Some((0xa0, content, _)) = read_der(rest) else {
+        return Err(invalid_signature());
+    };
+    let Some((0x30, signed_data, _)) = read_der(content) else {
+        return Err(invalid_signature());
+    };
+    // The signer infos are the last field of `SignedData`.
+    let mut fields = split_der(signed_data).ok_or_else(invalid_signature)?;
+    let Some((0x31, signer_infos, _)) = fields.pop() else {
+        return Err(invalid_signature());
+    };
+    let mut signer_infos = split_der(signer_infos).ok_or_else(invalid_signature)?;
+    if signer_infos.is_empty() {
+        return Err(invalid_signature());
+    }
+    let (0x30, signer_info, _) = signer_infos.remove(0) else {
+        return Err(invalid_signature());
+    };
+    let mut signer_info = split_der(signer_info).ok_or_else(invalid_signature)?;
+    // The signature value is the only octet string of the signer info.
+    let signature = signer_info
+        .iter()
+        .find_map(|(tag, content, _)| (*tag == 0x04).then_some(*content))
+        .ok_or_else(invalid_signature)?;
+
+    let token = request_time_stamp_token(urls, timeout, client, signature)?;
+    let attribute = write_der(
+        0x30,
+        &[
+            write_der(0x06, OID_TIME_STAMP_TOKEN),
+            write_der(0x31, &token),
+        ]
+        .concat(),
+    );
+    let mut unsigned_attributes = vec![];
+    if let Some((0xa1, attributes, _)) = signer_info.last() {
+        unsigned_attributes.extend_from_slice(attributes);
+        signer_info.pop();
+    }
+    unsigned_attributes.extend_from_slice(&attribute);
+
+    let mut signer_info: Vec<u8> = signer_info
+        .iter()
+        .flat_map(|(_, _, value)| value.iter().copied())
+        .collect();
+    signer_info.extend(write_der(0xa1, &unsigned_attributes));
+    let mut signer_infos_data = write_der(0x30, &signer_info);
+    for (_, _, value) in signer_infos {
+        signer_infos_data.extend_from_slice(value);
+    }
+    let mut signed_data: Vec<u8> = fields
+        .iter()
+        .flat_map(|(_, _, value)| value.iter().copied())
+        .collect();
+    signed_data.extend(write_der(0x31, &signer_infos_data));
+    let content = write_der(0xa0, &write_der(0x30, &signed_data));
+    Ok(write_der(0x30, &[content_type, &content].concat()))
+}
+
+/// Request a time stamp token of `data` (a signature or the signed bytes of a document)
+/// from the first time stamp authority of `urls` that returns a valid token within
+/// `timeout`. Returns the DER encoded token.
+pub(crate) fn request_time_stamp_token(
+    urls: &[String],
+    timeout: Duration,
+    client: &HttpClient,
+    data: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let mut errors = vec![];
+    for url in urls {
+        match request_from(url, timeout, client, data) {
+            Ok(token) => return Ok(token),
+            Err(error) => {
+                tracing::warn!("Time stamp `{}`: {}", url, error);
+                errors.push(error.to_string());
+            }
+        }
+    }
+    Err(Error::Other(format!(
+        "Time stamp: No time stamp authority returned a valid token: {}",
+        errors.join(", ")
+    )))
+}
+
+/// Request a token from the time stamp authority at `url`, and check its signature,
+/// message imprint and nonce.
+fn request_from(
+    url: &str,
+    timeout: Duration,
+    client: &HttpClient,
+    data: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let nonce = random_nonce();
+    let hash_algorithm = write_der(
+        0x30,
+        &[&write_der(0x06, OID_SHA256)[..], &[0x05, 0x00]].concat(),
+    );
+    let message_imprint = write_der(
+        0x30,
+        &[hash_algorithm, write_der(0x04, &Sha256::digest(data))].concat(),
+    );
+    // `TimeStampReq`: the version, message imprint, nonce and `certReq`, so the
+    // certificate of the authority is in the token.
+    let request = write_der(
+        0x30,
+        &[
+            &[0x02, 0x01, 0x01][..],
+            &message_imprint,
+            &write_der(0x02, &nonce),
+            &[0x01, 0x01, 0xff],
+        ]
+        .concat(),
+    );
+    let response = client.post(url, "application/timestamp-query", &request, timeout)?;
+
+    let invalid_response = || Error::from("Invalid response.");
+    // `TimeStampResp`: the status, 0 is granted and 1 is granted with modifications,
+    // and the token.
+    let Some((0x30, response, _)) = read_der(&response) else {
+        return Err(invalid_response());
+    };
+    let Some((0x30, status_info, token)) = read_der(response) else {
+        return Err(invalid_response());
+    };
+    match read_der(status_info) {
+        Some((0x02, [0] | [1], _)) => {}
+        Some((0x02, status, _)) => {
+            return Err(Error::Other(format!(
+                "The response has status {:?}.",
+                status
+            )))
+        }
+        _ => return Err(invalid_response()),
+    }
+    let Some((0x30, _, rest)) = read_der(token) else {
+        return Err(invalid_response());
+    };
+    let token = &token[..token.len() - rest.len()];
+
+    let signed_data = SignedData::parse_ber(token)
+        .map_err(|error| Error::Other(format!("Invalid token: {}", error)))?;
+    // The certificates of the TSA are not checked against trust stores here.
+    let verification = verify_time_stamp_token(url, &signed_data, data, &TrustAnchors::default());
+    if !verification.signature_valid || !verification.imprint_valid {
+        return Err(Error::from("The token is not valid for the data."));
+    }
+    let token_nonce = signed_data
+        .signed_content()
+        .and_then(parse_tst_info)
+        .and_then(|tst_info| tst_info.nonce);
+    if token_nonce.as_deref() != Some(&nonce[..]) {
+        return Err(Error::from(
+            "The nonce of the token does not match the request.",
+        ));
+    }
+    Ok(token.to_vec())
+}
+
+/// A random positive 64 bit number, encoded as the content of a DER integer.
+fn random_nonce() -> Vec<u8> {
+    let mut nonce: Vec<u8> = rand::random::<u64>()
+        .to_be_bytes()
+        .into_iter()
+        .skip_while(|byte| *byte == 0)
+        .collect();
+    if nonce.first().is_none_or(|byte| byte & 0x80 != 0) {
+        nonce.insert(0, 0);
+    }
+    nonce
+}
+
+/// Split DER encoded values, returns the tag, the content and the encoded value of each.
+fn split_der(mut data: &[u8]) -> Option<Vec<(u8, &[u8], &[u8])>> {
+    let mut values = vec![];
+    while !data.is_empty() {
+        let (tag, content, rest) = read_der(data)?;
+        values.push((tag, content, &data[..data.len() - rest.len()]));
+        data = rest;
+    }
+    Some(values)
+}
@@ -0,0 +1,299 @@
+//! Check the RFC 3161 time stamp tokens of signatures and document time stamps,
+//! see `SignatureVerification::time_stamp`.
+
+#[cfg(feature = "signing")]
+use crate::certificate_details::read_tbs_certificate;
+#[cfg(feature = "signing")]
+use crate::der::read_der;
+#[cfg(feature = "signing")]
+use crate::trust_store::TrustAnchors;
+use crate::verification::VerificationStatus;
+use chrono::{DateTime, Utc};
+#[cfg(feature = "signing")]
+use chrono::{NaiveDateTime, TimeZone};
+#[cfg(feature = "signing")]
+use cryptographic_message_syntax::SignedData;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "signing")]
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+/// The DER encoded object identifiers of the hash algorithms of a message imprint.
+#[cfg(feature = "signing")]
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+#[cfg(feature = "signing")]
+const OID_SHA384: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+#[cfg(feature = "signing")]
+const OID_SHA512: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03];
+/// The object identifier (DER encoded content) of the extended key usage extension.
+#[cfg(feature = "signing")]
+const OID_EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25];
+/// The object identifier (DER encoded content) of the `id-kp-timeStamping` key usage.
+#[cfg(feature = "signing")]
+const OID_TIME_STAMPING: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x08];
+
+/// The result of the check of a time stamp token.
+/// The certificates of the time stamp authority (TSA) are checked up to a certificate of
+/// the trust stores of the options, see `VerificationOptionsBuilder::trust_store`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct TimeStampVerification {
+    /// The time of the time stamp (`genTime`).
+    pub time: Option<DateTime<Utc>>,
+    /// The token (CMS) is signed correctly by the TSA.
+    pub signature_valid: bool,
+    /// The message imprint of the token matches the hash of the signature, or of the
+    /// signed bytes for a document time stamp.
+    pub imprint_valid: bool,
+    /// The certificate of the TSA is for time stamps (extended key usage `timeStamping`),
+    /// and every certificate is signed by the next one and valid at `time`, up to a trusted
+    /// certificate. `None` when the chain does not end at a trusted certificate, like
+    /// without trust stores.
+    pub chain_valid: Option<bool>,
+    /// The outcome of the checks above.
+    pub status: VerificationStatus,
+}
+
+/// Check the time stamp token in `contents` of a document time stamp (`ETSI.RFC3161`).
+#[cfg(feature = "signing")]
+pub(crate) fn verify_document_time_stamp(
+    field_name: &str,
+    contents: &[u8],
+    signed_bytes: &[u8],
+    trust_anchors: &TrustAnchors,
+) -> Option<TimeStampVerification> {
+    match SignedData::parse_ber(contents) {
+        Ok(token) => Some(verify_time_stamp_token(
+            field_name,
+            &token,
+            signed_bytes,
+            trust_anchors,
+        )),
+        Err(error) => {
+            tracing::warn!("Time stamp `{}`: {}", field_name, error);
+            None
+        }
+    }
+}
+
+/// Check the time stamp token in the unsigned attributes of the signer of a signature.
+#[cfg(feature = "signing")]
+pub(crate) fn verify_signature_time_stamp(
+    field_name: &str,
+    signed_data: &SignedData,
+    trust_anchors: &TrustAnchors,
+) -> Option<TimeStampVerification> {
+    let signer = signed_data.signers().next()?;
+    match signer.time_stamp_token_signed_data() {
+        Ok(token) => Some(verify_time_stamp_token(
+            field_name,
+            &token?,
+            signer.signature(),
+            trust_anchors,
+        )),
+        Err(error) => {
+            tracing::warn!("Time stamp `{}`: {}", field_name, error);
+            None
+        }
+    }
+}
+
+/// Check the signature, message imprint (of `stamped_data`) and certificates of a token.
+#[cfg(feature = "signing")]
+pub(crate) fn verify_time_stamp_token(
+    field_name: &str,
+    token: &SignedData,
+    stamped_data: &[u8],
+    trust_anchors: &TrustAnchors,
+) -> TimeStampVerification {
+    let mut signature_valid = false;
+    for signer in token.signers() {
+        let result = signer
+            .verify_signature_with_signed_data(token)
+            .and_then(|_| signer.verify_message_digest_with_signed_data(token));
+        signature_valid = match result {
+            Ok(()) => true,
+            Err(error) => {
+                tracing::warn!("Time stamp `{}`: {}", field_name, error);
+                false
+            }
+        };
+        if !signature_valid {
+            break;
+        }
+    }
+
+    let tst_info = token.signed_content().and_then(parse_tst_info);
+    if tst_info.is_none() {
+        tracing::warn!("Time stamp `{}`: Invalid `TSTInfo`.", field_name);
+    }
+    let time = tst_info.as_ref().map(|tst_info| tst_info.time);
+    let imprint_valid = tst_info.as_ref().is_some_and(|tst_info| {
+        let hash = match tst_info.hash_algorithm.as_slice() {
+            OID_SHA256 => Sha256::digest(stamped_data).to_vec(),
+            OID_SHA384 => Sha384::digest(stamped_data).to_vec(),
+            OID_SHA512 => Sha512::digest(stamped_data).to_vec(),
+            _ => {
+                tracing::warn!("Time stamp `{}`: Unsupported hash algorithm.", field_name);
+                return false;
+            }
+        };
+        hash == tst_info.hashed_message
+    });
+    let chain_valid = verify_certificate_chain(field_name, token, time, trust_anchors);
+
+    let status = match (signature_valid && imprint_valid, chain_valid) {
+        (false, _) | (true, Some(false)) => VerificationStatus::Invalid,
+        (true, Some(true)) => VerificationStatus::Valid,
+        (true, None) => VerificationStatus::Indeterminate,
+    };
+    TimeStampVerification {
+        time,
+        signature_valid,
+        imprint_valid,
+        chain_valid,
+        status,
+    }
+}
+
+/// Check the certificate of the signer of the token is for time stamps, and the
+/// certificates up to a trusted certificate. `None` when the chain ends before a trusted
+/// certificate, like at a self-signed certificate that is not trusted.
+#[cfg(feature = "signing")]
+fn verify_certificate_chain(
+    field_name: &str,
+    token: &SignedData,
+    time: Option<DateTime<Utc>>,
+    trust_anchors: &TrustAnchors,
+) -> Option<bool> {
+    let signer = token.signers().next()?;
+    let mut certificate = signer.certificate_from_signed_data(token).ok()??;
+    if !is_time_stamping_certificate(certificate.constructed_data()) {
+        tracing::warn!(
+            "Time stamp `{}`: The certificate is not for time stamps.",
+            field_name
+        );
+        return Some(false);
+    }
+    // Limit the length, so certificates that sign each other do not hang.
+    for _ in 0..16 {
+        if let Some(time) = time {
+            if time < certificate.validity_not_before() || time > certificate.validity_not_after() {
+                tracing::warn!("Time stamp `{}`: Certificate is not valid.", field_name);
+                return Some(false);
+            }
+        }
+        if trust_anchors.is_trusted(certificate)? {
+            return Some(true);
+        }
+        if certificate.subject_name() == certificate.issuer_name() {
+            tracing::warn!(
+                "Time stamp `{}`: The certificate chain does not end at a trusted certificate.",
+                field_name
+            );
+            return None;
+        }
+        let issuer = token
+            .certificates()
+            .find(|issuer| issuer.subject_name() == certificate.issuer_name())?;
+        if let Err(error) = certificate.verify_signed_by_certificate(issuer) {
+            tracing::warn!("Time stamp `{}`: {}", field_name, error);
+            return Some(false);
+        }
+        certificate = issuer;
+    }
+    None
+}
+
+/// The certificate has the `timeStamping` usage in the extended key usage extension.
+#[cfg(feature = "signing")]
+fn is_time_stamping_certificate(certificate: &[u8]) -> bool {
+    let Some(tbs_certificate) = read_tbs_certificate(certificate) else {
+        return false;
+    };
+    tbs_certificate
+        .extensions
+        .iter()
+        .filter(|(oid, _)| *oid == OID_EXTENDED_KEY_USAGE)
+        .any(|(_, value)| {
+            let Some((0x30, mut usages, _)) = read_der(value) else {
+                return false;
+            };
+            while let Some((tag, usage, next)) = read_der(usages) {
+                if tag == 0x06 && usage == OID_TIME_STAMPING {
+                    return true;
+                }
+                usages = next;
+            }
+            false
+        })
+}
+
+/// The fields of `TSTInfo` (RFC 3161) that are checked.
+#[cfg(feature = "signing")]
+pub(crate) struct TstInfo {
+    /// The DER encoded object identifier of the hash algorithm of the message imprint.
+    hash_algorithm: Vec<u8>,
+    hashed_message: Vec<u8>,
+    time: DateTime<Utc>,
+    /// The content of the nonce integer of the request, checked when a token is requested.
+    #[cfg_attr(not(feature = "network"), allow(dead_code))]
+    pub(crate) nonce: Option<Vec<u8>>,
+}
+
+/// Parse the DER encoded `TSTInfo`: a sequence of the version, policy, message imprint,
+/// serial number and time (`genTime`), followed by optional fields.
+#[cfg(feature = "signing")]
+pub(crate) fn parse_tst_info(data: &[u8]) -> Option<TstInfo> {
+    let (0x30, tst_info, _) = read_der(data)? else {
+        return None;
+    };
+    let (0x02, _version, rest) = read_der(tst_info)? else {
+        return None;
+    };
+    let (0x06, _policy, rest) = read_der(rest)? else {
+        return None;
+    };
+    let (0x30, message_imprint, rest) = read_der(rest)? else {
+        return None;
+    };
+    let (0x02, _serial_number, rest) = read_der(rest)? else {
+        return None;
+    };
+    let (0x18, gen_time, mut rest) = read_der(rest)? else {
+        return None;
+    };
+    // The optional `accuracy` and `ordering` come before the nonce.
+    let mut nonce = None;
+    while let Some((tag, content, next)) = read_der(rest) {
+        match tag {
+            0x30 | 0x01 => rest = next,
+            0x02 => {
+                nonce = Some(content.to_vec());
+                break;
+            }
+            _ => break,
+        }
+    }
+
+    let (0x30, algorithm_identifier, rest) = read_der(message_imprint)? else {
+        return None;
+    };
+    let (0x06, hash_algorithm, _) = read_der(algorithm_identifier)? else {
+        return None;
+    };
+    let (0x04, hashed_message, _) = read_der(rest)? else {
+        return None;
+    };
+
+    // `YYYYMMDDHHMMSS[.fff]Z`, the fraction of the second is ignored.
+    let gen_time = std::str::from_utf8(gen_time.get(..14)?).ok()?;
+    let time = NaiveDateTime::parse_from_str(gen_time, "%Y%m%d%H%M%S").ok()?;
+    Some(TstInfo {
+        hash_algorithm: hash_algorithm.to_vec(),
+        hashed_message: hashed_message.to_vec(),
+        time: Utc.from_utc_datetime(&time),
+        nonce,
+    })
+}
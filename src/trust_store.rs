@@ -0,0 +1,132 @@
+//! The trusted root certificates (trust anchors) of the signer certificate chains,
+//! see `VerificationOptionsBuilder::trust_store`.
+
+use crate::Error;
+#[cfg(feature = "signing")]
+use cryptographic_message_syntax::SignedData;
+#[cfg(feature = "fs")]
+use std::path::PathBuf;
+#[cfg(feature = "signing")]
+use x509_certificate::CapturedX509Certificate;
+
+/// Where the trusted certificates come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrustStore {
+    /// A file with 1 or more PEM encoded certificates, like a CA bundle.
+    #[cfg(feature = "fs")]
+    PemFile(PathBuf),
+    /// DER encoded certificates.
+    Der(Vec<Vec<u8>>),
+    /// The certificate store of the operating system (feature `native-certs`).
+    #[cfg(feature = "native-certs")]
+    System,
+}
+
+/// The certificates of the trust stores of the options, read once for every
+/// `verify_signatures`.
+#[derive(Default)]
+pub(crate) struct TrustAnchors {
+    /// `None` when there are no trust stores, the chains are not checked then.
+    #[cfg(feature = "signing")]
+    certificates: Option<Vec<CapturedX509Certificate>>,
+}
+
+impl TrustAnchors {
+    /// Read the certificates of the trust stores.
+    /// Without the `signing` feature nothing is read, the chains are not checked.
+    #[cfg(feature = "signing")]
+    pub(crate) fn load(trust_stores: &[TrustStore]) -> Result<Self, Error> {
+        if trust_stores.is_empty() {
+            return Ok(TrustAnchors { certificates: None });
+        }
+        let parse_error = |error| Error::Other(format!("Trust store: {}", error));
+        let mut certificates = vec![];
+        for trust_store in trust_stores {
+            match trust_store {
+                #[cfg(feature = "fs")]
+                TrustStore::PemFile(path) => certificates.extend(
+                    CapturedX509Certificate::from_pem_multiple(std::fs::read(path)?)
+                        .map_err(parse_error)?,
+                ),
+                TrustStore::Der(certificates_der) => {
+                    for certificate in certificates_der {
+                        certificates.push(
+                            CapturedX509Certificate::from_der(certificate.clone())
+                                .map_err(parse_error)?,
+                        );
+                    }
+                }
+                #[cfg(feature = "native-certs")]
+                TrustStore::System => {
+                    // Certificates of the system that can not be parsed are skipped,
+                    // one bad certificate should not stop the verification.
+                    for certificate in rustls_native_certs::load_native_certs()? {
+                        match CapturedX509Certificate::from_der(certificate.0) {
+                            Ok(certificate) => certificates.push(certificate),
+                            Err(error) => tracing::warn!("Trust store: {}", error),
+                        }
+                    }
+                }
+            }
+        }
+        Ok(TrustAnchors {
+            certificates: Some(certificates),
+        })
+    }
+
+    #[cfg(not(feature = "signing"))]
+    pub(crate) fn load(_trust_stores: &[TrustStore]) -> Result<Self, Error> {
+        Ok(TrustAnchors {})
+    }
+
+    /// Check if the `certificate` is a trusted certificate or is signed by one.
+    /// `None` without trust stores.
+    #[cfg(feature = "signing")]
+    pub(crate) fn is_trusted(&self, certificate: &CapturedX509Certificate) -> Option<bool> {
+        Some(is_trusted(self.certificates.as_ref()?, certificate))
+    }
+
+    /// Check that the certificates of the signer are signed by the next one, up to a
+    /// trusted certificate. The validity periods and revocation are not checked.
+    /// `None` without trust stores or without a signer certificate.
+    #[cfg(feature = "signing")]
+    pub(crate) fn verify_chain(&self, field_name: &str, signed_data: &SignedData) -> Option<bool> {
+        let anchors = self.certificates.as_ref()?;
+        let signer = signed_data.signers().next()?;
+        let mut certificate = signer.certificate_from_signed_data(signed_data).ok()??;
+        // Limit the length, so certificates that sign each other do not hang.
+        for _ in 0..16 {
+            if is_trusted(anchors, certificate) {
+                return Some(true);
+            }
+            if certificate.subject_name() == certificate.issuer_name() {
+                break;
+            }
+            let Some(issuer) = signed_data
+                .certificates()
+                .find(|issuer| issuer.subject_name() == certificate.issuer_name())
+            else {
+                break;
+            };
+            if let Err(error) = certificate.verify_signed_by_certificate(issuer) {
+                tracing::warn!("Signature `{}`: {}", field_name, error);
+                return Some(false);
+            }
+            certificate = issuer;
+        }
+        tracing::warn!(
+            "Signature `{}`: The certificate chain does not end at a trusted certificate.",
+            field_name
+        );
+        Some(false)
+    }
+}
+
+#[cfg(feature = "signing")]
+fn is_trusted(anchors: &[CapturedX509Certificate], certificate: &CapturedX509Certificate) -> bool {
+    anchors.iter().any(|anchor| {
+        anchor.constructed_data() == certificate.constructed_data()
+            || (anchor.subject_name() == certificate.issuer_name()
+                && certificate.verify_signed_by_certificate(anchor).is_ok())
+    })
+}
@@ -0,0 +1,312 @@
+//! Trusted lists of the EU (ETSI TS 119 612) as a trust source for `verify_signatures`,
+//! see `VerificationOptionsBuilder::trusted_list`.
+
+#[cfg(feature = "signing")]
+use crate::certificate_details::read_tbs_certificate;
+#[cfg(feature = "signing")]
+use crate::der::read_der;
+use crate::Error;
+#[cfg(feature = "signing")]
+use cryptographic_message_syntax::SignedData;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "signing")]
+use x509_certificate::CapturedX509Certificate;
+
+/// The type of the services that issue qualified certificates.
+const SERVICE_TYPE_CA_QC: &str = "http://uri.etsi.org/TrstSvc/Svctype/CA/QC";
+/// The status of services that are currently approved by the supervisory body.
+const SERVICE_STATUS_GRANTED: &str = "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/granted";
+/// The object identifier (DER encoded content) of the `QCStatements` certificate extension.
+#[cfg(feature = "signing")]
+const OID_QC_STATEMENTS: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x01, 0x03];
+/// The object identifier (DER encoded content) of the `QcCompliance` statement
+/// (ETSI EN 319 412-5).
+#[cfg(feature = "signing")]
+const OID_QC_COMPLIANCE: &[u8] = &[0x04, 0x00, 0x8e, 0x46, 0x01, 0x01];
+
+/// A trusted list, or the list of trusted lists (LOTL) of the EU, read from XML.
+/// The lists are not downloaded: read them from a cache that is updated elsewhere.
+/// The XML signature of the list is not checked, and only the current status of the
+/// services is used (not the history).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrustedList {
+    /// The country of the list (`SchemeTerritory`), `EU` for the LOTL.
+    pub territory: Option<String>,
+    /// The trust services of the list, the LOTL has none.
+    pub services: Vec<TrustService>,
+    /// The locations of the XML of other trusted lists (`TSLLocation`),
+    /// for the LOTL the lists of the member states.
+    pub pointers: Vec<String>,
+}
+
+/// A trust service (`TSPService`) of a trusted list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustService {
+    /// The name of the trust service provider (`TSPName`).
+    pub provider_name: String,
+    pub service_name: String,
+    /// The type of the service (`ServiceTypeIdentifier`), like
+    /// `http://uri.etsi.org/TrstSvc/Svctype/CA/QC`.
+    pub service_type: String,
+    /// The current status of the service (`ServiceStatus`).
+    pub status: String,
+    /// The DER encoded certificates that identify the service.
+    pub certificates: Vec<Vec<u8>>,
+}
+
+impl TrustService {
+    /// The status of the service is granted.
+    pub fn is_granted(&self) -> bool {
+        self.status == SERVICE_STATUS_GRANTED
+    }
+
+    /// The service issues qualified certificates and is granted.
+    pub fn is_qualified_ca(&self) -> bool {
+        self.service_type == SERVICE_TYPE_CA_QC && self.is_granted()
+    }
+}
+
+/// The result of the check of the signer certificate against the trusted lists,
+/// see `SignatureVerification::trusted_list`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct TrustedListStatus {
+    /// The country of the trusted list of the service that issued the certificate,
+    /// `None` when no service issued it.
+    pub territory: Option<String>,
+    pub provider_name: Option<String>,
+    pub service_name: Option<String>,
+    /// The service that issued the certificate is granted.
+    pub granted: bool,
+    /// The certificate is a qualified certificate: it is issued by a granted `CA/QC`
+    /// service and has the `QcCompliance` statement. Used for qualified electronic
+    /// signatures, the signature itself is checked by `signature_valid`.
+    pub qualified: bool,
+}
+
+impl TrustedList {
+    /// Read a trusted list or the LOTL from its XML.
+    pub fn from_xml(xml: &str) -> Result<Self, Error> {
+        let document = roxmltree::Document::parse(xml)
+            .map_err(|err| Error::Other(format!("Trusted list: {}", err)))?;
+        let root = document.root_element();
+        if root.tag_name().name() != "TrustServiceStatusList" {
+            return Err(Error::from(
+                "Trusted list: Root element should be `TrustServiceStatusList`.",
+            ));
+        }
+
+        let scheme_information = get_child(root, "SchemeInformation");
+        let territory = scheme_information
+            .and_then(|node| get_child(node, "SchemeTerritory"))
+            .and_then(get_text);
+        let pointers = scheme_information
+            .and_then(|node| get_child(node, "PointersToOtherTSL"))
+            .into_iter()
+            .flat_map(|node| get_children(node, "OtherTSLPointer"))
+            .filter(|pointer| {
+                // The LOTL points to the XML and the PDF version of every list.
+                pointer
+                    .descendants()
+                    .find(|node| node.tag_name().name() == "MimeType")
+                    .and_then(get_text)
+                    .is_none_or(|mime_type| mime_type.ends_with("xml"))
+            })
+            .filter_map(|pointer| get_child(pointer, "TSLLocation").and_then(get_text))
+            .collect();
+
+        let mut services = vec![];
+        let providers = get_child(root, "TrustServiceProviderList")
+            .into_iter()
+            .flat_map(|node| get_children(node, "TrustServiceProvider"));
+        for provider in providers {
+            let provider_name = get_child(provider, "TSPInformation")
+                .and_then(|node| get_child(node, "TSPName"))
+                .and_then(get_name)
+                .unwrap_or_default();
+            let provider_services = get_child(provider, "TSPServices")
+                .into_iter()
+                .flat_map(|node| get_children(node, "TSPService"))
+                .filter_map(|service| get_child(service, "ServiceInformation"));
+            for information in provider_services {
+                let certificates = get_child(information, "ServiceDigitalIdentity")
+                    .into_iter()
+                    .flat_map(|node| node.descendants())
+                    .filter(|node| node.tag_name().name() == "X509Certificate")
+                    .filter_map(get_text)
+                    .filter_map(|certificate| {
+                        let certificate: String = certificate
+                            .chars()
+                            .filter(|c| !c.is_ascii_whitespace())
+                            .collect();
+                        base64::decode(certificate).ok()
+                    })
+                    .collect();
+                services.push(TrustService {
+                    provider_name: provider_name.clone(),
+                    service_name: get_child(information, "ServiceName")
+                        .and_then(get_name)
+                        .unwrap_or_default(),
+                    service_type: get_child(information, "ServiceTypeIdentifier")
+                        .and_then(get_text)
+                        .unwrap_or_default(),
+                    status: get_child(information, "ServiceStatus")
+                        .and_then(get_text)
+                        .unwrap_or_default(),
+                    certificates,
+                });
+            }
+        }
+
+        Ok(TrustedList {
+            territory,
+            services,
+            pointers,
+        })
+    }
+}
+
+fn get_child<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &str,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children()
+        .find(|child| child.tag_name().name() == name)
+}
+
+fn get_children<'a, 'input: 'a>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = roxmltree::Node<'a, 'input>> + 'a {
+    node.children()
+        .filter(move |child| child.tag_name().name() == name)
+}
+
+fn get_text(node: roxmltree::Node) -> Option<String> {
+    node.text()
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .map(str::to_owned)
+}
+
+/// Get the English name of a list of names in multiple languages, or the first name.
+fn get_name(node: roxmltree::Node) -> Option<String> {
+    let names: Vec<_> = get_children(node, "Name").collect();
+    names
+        .iter()
+        .find(|name| name.attribute((roxmltree::NS_XML_URI, "lang")) == Some("en"))
+        .or(names.first())
+        .and_then(|name| get_text(*name))
+}
+
+/// Find the service that issued the signer certificate (or a certificate of its chain)
+/// in the trusted lists.
+#[cfg(feature = "signing")]
+pub(crate) fn evaluate_trusted_lists(
+    field_name: &str,
+    signed_data: &SignedData,
+    trusted_lists: &[std::sync::Arc<TrustedList>],
+) -> Option<TrustedListStatus> {
+    if trusted_lists.is_empty() {
+        return None;
+    }
+    let signer = signed_data.signers().next()?;
+    let signer_certificate = match signer.certificate_from_signed_data(signed_data) {
+        Ok(certificate) => certificate?,
+        Err(error) => {
+            tracing::warn!("Signature `{}`: {}", field_name, error);
+            return None;
+        }
+    };
+
+    let mut certificate = signer_certificate;
+    // Limit the length, so certificates that sign each other do not hang.
+    for _ in 0..16 {
+        for list in trusted_lists {
+            for service in &list.services {
+                if service
+                    .certificates
+                    .iter()
+                    .any(|service_certificate| is_issued_by(certificate, service_certificate))
+                {
+                    return Some(TrustedListStatus {
+                        territory: list.territory.clone(),
+                        provider_name: Some(service.provider_name.clone()),
+                        service_name: Some(service.service_name.clone()),
+                        granted: service.is_granted(),
+                        qualified: service.is_qualified_ca()
+                            && is_qualified_certificate(signer_certificate),
+                    });
+                }
+            }
+        }
+        if certificate.subject_name() == certificate.issuer_name() {
+            break;
+        }
+        let Some(issuer) = signed_data
+            .certificates()
+            .find(|issuer| issuer.subject_name() == certificate.issuer_name())
+        else {
+            break;
+        };
+        // A certificate with the name of a listed issuer is only in the chain when the
+        // issuer signed it.
+        if let Err(error) = certificate.verify_signed_by_certificate(issuer) {
+            tracing::warn!("Signature `{}`: {}", field_name, error);
+            break;
+        }
+        certificate = issuer;
+    }
+    Some(TrustedListStatus {
+        territory: None,
+        provider_name: None,
+        service_name: None,
+        granted: false,
+        qualified: false,
+    })
+}
+
+/// The certificate is the certificate of the service, or is signed by it.
+#[cfg(feature = "signing")]
+fn is_issued_by(certificate: &CapturedX509Certificate, service_certificate: &[u8]) -> bool {
+    if certificate.constructed_data() == service_certificate {
+        return true;
+    }
+    let Ok(service_certificate) = CapturedX509Certificate::from_der(service_certificate) else {
+        return false;
+    };
+    service_certificate.subject_name() == certificate.issuer_name()
+        && certificate
+            .verify_signed_by_certificate(&service_certificate)
+            .is_ok()
+}
+
+/// The certificate has the `QcCompliance` statement in the `QCStatements` extension.
+#[cfg(feature = "signing")]
+fn is_qualified_certificate(certificate: &CapturedX509Certificate) -> bool {
+    let Some(tbs_certificate) = read_tbs_certificate(certificate.constructed_data()) else {
+        return false;
+    };
+    tbs_certificate
+        .extensions
+        .iter()
+        .filter(|(oid, _)| *oid == OID_QC_STATEMENTS)
+        .any(|(_, value)| has_qc_compliance(value))
+}
+
+/// The `QCStatements` (a sequence of `QCStatement`) contain the `QcCompliance` statement.
+#[cfg(feature = "signing")]
+fn has_qc_compliance(qc_statements: &[u8]) -> bool {
+    let Some((0x30, mut statements, _)) = read_der(qc_statements) else {
+        return false;
+    };
+    while let Some((0x30, statement, next)) = read_der(statements) {
+        statements = next;
+        if let Some((0x06, OID_QC_COMPLIANCE, _)) = read_der(statement) {
+            return true;
+        }
+    }
+    false
+}
@@ -1,20 +1,34 @@
-use cryptographic_message_syntax::SignerBuilder;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "signing")]
+use std::sync::Arc;
+#[cfg(feature = "signing")]
+use x509_certificate::{CapturedX509Certificate, KeyInfoSigner};
 
 /// The info provided to PDF service when a document needs to be signed.
+/// The signing key is shared and must be `Send` and `Sync`, so the info can be held
+/// across `.await` in a server.
+#[cfg(feature = "signing")]
 #[derive(Clone)]
-pub struct UserSignatureInfo<'a> {
+pub struct UserSignatureInfo {
     pub box_id: String,
     pub user_id: String,
     pub user_name: String,
     pub user_email: String,
-    pub user_signature: Vec<u8>,
-    pub user_signing_keys: SignerBuilder<'a>,
+    /// The signature image, shared so the same image can be used by multiple signers
+    /// without copying it (`Vec<u8>` converts with `.into()`).
+    pub user_signature: Arc<[u8]>,
+    /// The signing key (like `Arc::new(InMemorySigningKeyPair)`), shared so the same key
+    /// can be used by multiple signers.
+    pub user_signing_keys: Arc<dyn KeyInfoSigner + Send + Sync>,
+    /// The certificate of the signing key.
+    pub user_certificate: CapturedX509Certificate,
 }
 
 /// The info inside the PDF form signature.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
 pub struct UserFormSignatureInfo {
     pub user_id: String,
     pub box_id: String,
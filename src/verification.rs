@@ -0,0 +1,494 @@
+//! Check the signatures of the document, see `verify_signatures`.
+//! With the `serde` feature the results can be serialized and deserialized, for example
+//! to store them or return them as JSON. The field names are camel case.
+
+use crate::acro_form::FormComponent;
+use crate::ades_level::get_ades_level;
+#[cfg(feature = "signing")]
+use crate::der::read_der;
+use crate::revision_diff::get_revision_ends;
+#[cfg(feature = "signing")]
+use crate::time_stamp_verification::{verify_document_time_stamp, verify_signature_time_stamp};
+use crate::trust_store::TrustAnchors;
+#[cfg(feature = "signing")]
+use crate::trusted_list::evaluate_trusted_lists;
+use crate::{
+    AdesLevel, CertificateDetails, Error, PDFSigningDocument, TimeStampVerification,
+    TrustedListStatus, VerificationOptions,
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The outcome of the check of a signature or a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub enum VerificationStatus {
+    /// The byte range is correct, the signature matches the signed bytes and the
+    /// certificate chain ends at a trusted certificate.
+    Valid,
+    /// The byte range is not correct or the signature does not match the signed bytes.
+    Invalid,
+    /// The signature is not checked (see `SignatureVerification::signature_valid`),
+    /// the certificate chain is not confirmed by trust stores or trusted lists,
+    /// or the document has no signatures.
+    Indeterminate,
+}
+
+/// Why the `ByteRange` of a signature is not correct.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub enum ByteRangeIssue {
+    /// The `ByteRange` does not have 2 ranges, does not start at the begin of the file or
+    /// ends after the end of the file.
+    Malformed,
+    /// The gap between the 2 ranges is not exactly the hexadecimal `Contents` of the
+    /// signature, so bytes that are not signed can be shown.
+    GapNotContents,
+    /// The signed bytes do not end at the end of a revision (`%%EOF`), so the revision of
+    /// the signature has bytes that are not signed (partial coverage, like shadow attacks).
+    PartialCoverage,
+}
+
+/// The result of the check of 1 signature, see `PDFSigningDocument::verify_signatures`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct SignatureVerification {
+    /// The fully qualified name of the signature field.
+    pub field_name: String,
+    /// The name in the signature (`Name`), this is not checked with the certificate.
+    pub signer_name: Option<String>,
+    pub sub_filter: Option<String>,
+    /// The offset and length of the signed parts of the file.
+    pub byte_range: Vec<(u64, u64)>,
+    /// The `ByteRange` starts at the begin of the file, only leaves out the `Contents` and
+    /// ends at the end of a revision.
+    pub byte_range_valid: bool,
+    /// Why the `ByteRange` is not valid, `None` when it is valid.
+    pub byte_range_issue: Option<ByteRangeIssue>,
+    /// The `ByteRange` ends at the end of the file: the signature covers the whole document,
+    /// there are no changes after it.
+    pub covers_whole_document: bool,
+    /// The index of the revision the signature covers (0 is the original document, see
+    /// `get_revision_count`). `None` when the `ByteRange` does not end at a revision.
+    pub revision: Option<usize>,
+    /// The number of revisions (incremental updates) after the signed part of the file.
+    pub later_revisions: usize,
+    /// The number of bytes of the file up to the end of the `ByteRange`.
+    pub covered_length: u64,
+    /// The length of the file, see `coverage`.
+    pub file_length: u64,
+    /// The signature (CMS) is correct and matches the digest of the signed bytes, for
+    /// document time stamps the token is signed correctly and its message imprint matches.
+    /// `None` when it is not checked, without the `signing` feature. The certificate chain
+    /// is checked by `chain_trusted`, revocation is not checked.
+    pub signature_valid: Option<bool>,
+    /// The certificate chain of the signer ends at a certificate of the trust stores of the
+    /// options. `None` without trust stores, for document time stamps or without the
+    /// `signing` feature.
+    pub chain_trusted: Option<bool>,
+    /// The outcome of the checks above: `Valid` needs a correct signature and a chain that
+    /// ends at a trust store or a granted service of a trusted list. The time stamp is not
+    /// part of it, except for document time stamps which have the status of the time stamp.
+    pub status: VerificationStatus,
+    /// The check of the time stamp token of the signature, or of the document time stamp.
+    /// `None` when there is no time stamp or without the `signing` feature.
+    pub time_stamp: Option<TimeStampVerification>,
+    /// The check of the signer certificate against the trusted lists of the options.
+    /// `None` without trusted lists, without a signer certificate in the signature, for
+    /// document time stamps or without the `signing` feature.
+    pub trusted_list: Option<TrustedListStatus>,
+    /// The certificate of the signer. `None` when the signature has no signer certificate,
+    /// for document time stamps or without the `signing` feature.
+    pub signer_certificate: Option<CertificateDetails>,
+    /// The PAdES baseline level of the signature. `None` for signatures that are not
+    /// PAdES signatures (`ETSI.CAdES.detached`) and for document time stamps.
+    pub ades_level: Option<AdesLevel>,
+}
+
+impl SignatureVerification {
+    /// Check that the byte range is correct and the signature is not invalid.
+    pub fn is_valid(&self) -> bool {
+        self.byte_range_valid && self.signature_valid != Some(false)
+    }
+
+    /// Get the part of the file up to the end of the `ByteRange`, from 0 to 1.
+    /// It is less than 1 when the document is changed after it was signed.
+    pub fn coverage(&self) -> f64 {
+        if self.file_length == 0 {
+            return 0.0;
+        }
+        self.covered_length as f64 / self.file_length as f64
+    }
+}
+
+/// The result of the check of all the signatures of a document, see `verification_report`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct VerificationReport {
+    pub file_name: String,
+    /// `Invalid` when a signature is invalid, `Indeterminate` when a signature is not
+    /// checked or there are no signatures, otherwise `Valid`.
+    pub status: VerificationStatus,
+    pub signatures: Vec<SignatureVerification>,
+}
+
+impl PDFSigningDocument {
+    /// Check the signed signature fields of the document (as it was read, without the
+    /// changes not saved yet), in the order of the fields.
+    pub fn verify_signatures(
+        &mut self,
+        options: &VerificationOptions,
+    ) -> Result<Vec<SignatureVerification>, Error> {
+        self.load_all()?;
+        let trust_anchors = TrustAnchors::load(&options.trust_stores)?;
+        let file_data = self.get_prev_documents_bytes();
+        let revision_ends = get_revision_ends(file_data);
+        let forms = self.acro_form.clone().unwrap_or_default();
+        let mut verifications = vec![];
+        for form in forms.iter() {
+            let (sub_filter, contents, byte_range, name) = match form.get_form_component() {
+                FormComponent::SignedSignature {
+                    sub_filter,
+                    contents,
+                    byte_range,
+                    name,
+                    ..
+                } => (sub_filter, contents, byte_range, name),
+                _ => continue,
+            };
+            let field_name = form
+                .get_fully_qualified_field_name()
+                .unwrap_or_default()
+                .to_owned();
+            let signed_bytes = get_signed_bytes(file_data, byte_range, contents, &revision_ends);
+            if let Err(issue) = &signed_bytes {
+                tracing::warn!(
+                    "Signature `{}`: Invalid `ByteRange` ({:?}).",
+                    field_name,
+                    issue
+                );
+            }
+            let byte_range_issue = signed_bytes.as_ref().err().copied();
+            let signed_bytes = signed_bytes.ok();
+            let is_time_stamp = sub_filter.as_deref() == Some("ETSI.RFC3161");
+            let checks = match &signed_bytes {
+                Some(signed_bytes) => verify_signature(
+                    &field_name,
+                    contents,
+                    signed_bytes,
+                    is_time_stamp,
+                    options,
+                    &trust_anchors,
+                ),
+                None => SignatureChecks::default(),
+            };
+            let signature_valid = checks.signature_valid;
+            // Only a trust anchor (a trust store or a granted service of a trusted list)
+            // makes a signature valid, anyone can create a self-signed certificate.
+            let is_chain_trusted = checks.chain_trusted == Some(true)
+                || checks
+                    .trusted_list
+                    .as_ref()
+                    .is_some_and(|trusted_list| trusted_list.granted);
+            let status = match (signed_bytes.is_some(), signature_valid, &checks.time_stamp) {
+                (false, _, _) | (true, Some(false), _) => VerificationStatus::Invalid,
+                (true, Some(true), Some(time_stamp)) if is_time_stamp => time_stamp.status,
+                (true, Some(true), _) if !is_time_stamp && is_chain_trusted => {
+                    VerificationStatus::Valid
+                }
+                (true, _, _) => VerificationStatus::Indeterminate,
+            };
+            let covered_length = byte_range
+                .last()
+                .map_or(0, |(start, length)| start.saturating_add(*length));
+            let revision = usize::try_from(covered_length)
+                .ok()
+                .and_then(|end| find_revision(file_data, end, &revision_ends));
+            let later_revisions = match revision {
+                Some(revision) => revision_ends.len() - 1 - revision,
+                None => revision_ends
+                    .iter()
+                    .filter(|end| **end as u64 > covered_length)
+                    .count(),
+            };
+            verifications.push(SignatureVerification {
+                byte_range_valid: signed_bytes.is_some(),
+                byte_range_issue,
+                covers_whole_document: covered_length == file_data.len() as u64,
+                revision,
+                later_revisions,
+                covered_length,
+                file_length: file_data.len() as u64,
+                signature_valid,
+                chain_trusted: checks.chain_trusted,
+                field_name,
+                signer_name: name.clone(),
+                sub_filter: sub_filter.clone(),
+                byte_range: byte_range.clone(),
+                status,
+                time_stamp: checks.time_stamp,
+                trusted_list: checks.trusted_list,
+                signer_certificate: checks.signer_certificate,
+                ades_level: None,
+            });
+        }
+        self.set_ades_levels(&mut verifications);
+        Ok(verifications)
+    }
+
+    /// Set the PAdES level of the PAdES signatures.
+    fn set_ades_levels(&self, verifications: &mut [SignatureVerification]) {
+        let is_pades = |verification: &SignatureVerification| {
+            verification.sub_filter.as_deref() == Some("ETSI.CAdES.detached")
+        };
+        if !verifications.iter().any(is_pades) {
+            return;
+        }
+        let get_end = |verification: &SignatureVerification| {
+            verification
+                .byte_range
+                .last()
+                .map_or(0, |(start, length)| start + length)
+        };
+        let document_time_stamp_ends: Vec<u64> = verifications
+            .iter()
+            .filter(|verification| verification.sub_filter.as_deref() == Some("ETSI.RFC3161"))
+            .map(get_end)
+            .collect();
+        let validation_data_ends = self.get_validation_data_ends();
+        for verification in verifications.iter_mut() {
+            if is_pades(verification) {
+                verification.ades_level = Some(get_ades_level(
+                    get_end(verification),
+                    verification.time_stamp.is_some(),
+                    &document_time_stamp_ends,
+                    &validation_data_ends,
+                ));
+            }
+        }
+    }
+
+    /// Check the signatures like `verify_signatures`, with the status of the document.
+    pub fn verification_report(
+        &mut self,
+        options: &VerificationOptions,
+    ) -> Result<VerificationReport, Error> {
+        let signatures = self.verify_signatures(options)?;
+        let status = if signatures.is_empty() {
+            VerificationStatus::Indeterminate
+        } else if signatures
+            .iter()
+            .any(|signature| signature.status == VerificationStatus::Invalid)
+        {
+            VerificationStatus::Invalid
+        } else if signatures
+            .iter()
+            .any(|signature| signature.status == VerificationStatus::Indeterminate)
+        {
+            VerificationStatus::Indeterminate
+        } else {
+            VerificationStatus::Valid
+        };
+        Ok(VerificationReport {
+            file_name: self.file_name.clone(),
+            status,
+            signatures,
+        })
+    }
+}
+
+/// Get the signed bytes of the file. The gap between the 2 ranges must be the hexadecimal
+/// `contents` and the second range must end at one of the `revision_ends`.
+fn get_signed_bytes(
+    file_data: &[u8],
+    byte_range: &[(u64, u64)],
+    contents: &[u8],
+    revision_ends: &[usize],
+) -> Result<Vec<u8>, ByteRangeIssue> {
+    let [(first_start, first_length), (second_start, second_length)] = *byte_range else {
+        return Err(ByteRangeIssue::Malformed);
+    };
+    let to_usize = |value: u64| usize::try_from(value).map_err(|_| ByteRangeIssue::Malformed);
+    let first_end = to_usize(first_start)?
+        .checked_add(to_usize(first_length)?)
+        .ok_or(ByteRangeIssue::Malformed)?;
+    let second_start = to_usize(second_start)?;
+    let second_end = second_start
+        .checked_add(to_usize(second_length)?)
+        .ok_or(ByteRangeIssue::Malformed)?;
+    if first_start != 0 || second_start <= first_end + 1 || second_end > file_data.len() {
+        return Err(ByteRangeIssue::Malformed);
+    }
+
+    let gap = &file_data[first_end..second_start];
+    let hex = &gap[1..gap.len() - 1];
+    let is_contents = gap.first() == Some(&b'<')
+        && gap.last() == Some(&b'>')
+        && hex.len() == contents.len() * 2
+        && hex.chunks(2).zip(contents).all(|(pair, byte)| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                == Some(*byte)
+        });
+    if !is_contents {
+        return Err(ByteRangeIssue::GapNotContents);
+    }
+
+    if find_revision(file_data, second_end, revision_ends).is_none() {
+        return Err(ByteRangeIssue::PartialCoverage);
+    }
+
+    let mut signed_bytes = Vec::with_capacity(first_end + second_end - second_start);
+    signed_bytes.extend_from_slice(&file_data[..first_end]);
+    signed_bytes.extend_from_slice(&file_data[second_start..second_end]);
+    Ok(signed_bytes)
+}
+
+/// Find the index of the revision that ends at `end`.
+/// The end of a revision includes the end of line after `%%EOF`, `end` may be before it.
+fn find_revision(file_data: &[u8], end: usize, revision_ends: &[usize]) -> Option<usize> {
+    let part = file_data.get(..end)?;
+    let trimmed_length = part.len()
+        - part
+            .iter()
+            .rev()
+            .take_while(|byte| matches!(byte, b'\r' | b'\n'))
+            .count();
+    if !part[..trimmed_length].ends_with(b"%%EOF") {
+        return None;
+    }
+    revision_ends.iter().position(|revision_end| {
+        *revision_end >= end
+            && file_data[end..*revision_end]
+                .iter()
+                .all(|byte| matches!(byte, b'\r' | b'\n'))
+    })
+}
+
+/// The results of the checks of the CMS signature.
+#[derive(Default)]
+struct SignatureChecks {
+    signature_valid: Option<bool>,
+    chain_trusted: Option<bool>,
+    time_stamp: Option<TimeStampVerification>,
+    trusted_list: Option<TrustedListStatus>,
+    signer_certificate: Option<CertificateDetails>,
+}
+
+/// Check the CMS signature in `contents`, the placeholder can have zeros after it.
+/// The signature is not checked for document time stamps, only the time stamp is.
+#[cfg(feature = "signing")]
+fn verify_signature(
+    field_name: &str,
+    contents: &[u8],
+    signed_bytes: &[u8],
+    is_time_stamp: bool,
+    options: &VerificationOptions,
+    trust_anchors: &TrustAnchors,
+) -> SignatureChecks {
+    use cryptographic_message_syntax::SignedData;
+
+    let contents = match get_signature_der(field_name, contents) {
+        Ok(contents) => contents,
+        Err(error) => {
+            tracing::warn!("{}", error);
+            return SignatureChecks {
+                signature_valid: Some(false),
+                ..SignatureChecks::default()
+            };
+        }
+    };
+    if is_time_stamp {
+        let time_stamp =
+            verify_document_time_stamp(field_name, contents, signed_bytes, trust_anchors);
+        return SignatureChecks {
+            signature_valid: Some(
+                time_stamp.as_ref().is_some_and(|time_stamp| {
+                    time_stamp.signature_valid && time_stamp.imprint_valid
+                }),
+            ),
+            time_stamp,
+            ..SignatureChecks::default()
+        };
+    }
+    let signed_data = match SignedData::parse_ber(contents) {
+        Ok(signed_data) => signed_data,
+        Err(error) => {
+            tracing::warn!("Signature `{}`: {}", field_name, error);
+            return SignatureChecks {
+                signature_valid: Some(false),
+                ..SignatureChecks::default()
+            };
+        }
+    };
+    let mut checks = SignatureChecks {
+        signature_valid: Some(false),
+        chain_trusted: trust_anchors.verify_chain(field_name, &signed_data),
+        time_stamp: verify_signature_time_stamp(field_name, &signed_data, trust_anchors),
+        trusted_list: evaluate_trusted_lists(field_name, &signed_data, &options.trusted_lists),
+        signer_certificate: get_signer_certificate(field_name, &signed_data),
+    };
+    let mut has_signer = false;
+    for signer in signed_data.signers() {
+        has_signer = true;
+        let result = signer
+            .verify_signature_with_signed_data(&signed_data)
+            .and_then(|_| signer.verify_message_digest_with_content(signed_bytes));
+        if let Err(error) = result {
+            tracing::warn!("Signature `{}`: {}", field_name, error);
+            return checks;
+        }
+    }
+    checks.signature_valid = Some(has_signer);
+    checks
+}
+
+/// Get the details of the certificate of the (first) signer.
+#[cfg(feature = "signing")]
+fn get_signer_certificate(
+    field_name: &str,
+    signed_data: &cryptographic_message_syntax::SignedData,
+) -> Option<CertificateDetails> {
+    let signer = signed_data.signers().next()?;
+    let certificate = signer.certificate_from_signed_data(signed_data).ok()??;
+    match CertificateDetails::from_der(certificate.constructed_data()) {
+        Ok(details) => Some(details),
+        Err(error) => {
+            tracing::warn!("Signature `{}`: {}", field_name, error);
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "signing"))]
+fn verify_signature(
+    _field_name: &str,
+    _contents: &[u8],
+    _signed_bytes: &[u8],
+    _is_time_stamp: bool,
+    _options: &VerificationOptions,
+    _trust_anchors: &TrustAnchors,
+) -> SignatureChecks {
+    SignatureChecks::default()
+}
+
+/// Get the DER encoded signature at the start of `contents`, without the zeros of the
+/// placeholder after it. Signatures with an indefinite length (BER) are returned as they
+/// are. Returns `Error::InvalidSignature` when the length is longer than the `Contents`.
+#[cfg(feature = "signing")]
+fn get_signature_der<'a>(field_name: &str, contents: &'a [u8]) -> Result<&'a [u8], Error> {
+    if contents.get(1) == Some(&0x80) {
+        return Ok(contents);
+    }
+    match read_der(contents) {
+        Some((_, _, rest)) => Ok(&contents[..contents.len() - rest.len()]),
+        None => Err(Error::InvalidSignature {
+            name: field_name.to_owned(),
+        }),
+    }
+}
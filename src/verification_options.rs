@@ -0,0 +1,49 @@
+//! Options used when signatures are checked, see `VerificationOptions::builder`.
+
+use crate::{TrustStore, TrustedList};
+use std::sync::Arc;
+
+/// Options used by `verify_signatures` and `verification_report`, create them with
+/// `VerificationOptions::builder` or use the default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerificationOptions {
+    #[cfg_attr(not(feature = "signing"), allow(dead_code))]
+    pub(crate) trusted_lists: Vec<Arc<TrustedList>>,
+    pub(crate) trust_stores: Vec<TrustStore>,
+}
+
+impl VerificationOptions {
+    pub fn builder() -> VerificationOptionsBuilder {
+        VerificationOptionsBuilder {
+            options: VerificationOptions::default(),
+        }
+    }
+}
+
+/// Builder for `VerificationOptions`, the options that are not set keep their default.
+#[derive(Debug, Clone)]
+pub struct VerificationOptionsBuilder {
+    options: VerificationOptions,
+}
+
+impl VerificationOptionsBuilder {
+    /// Check the signer certificates against a trusted list (see
+    /// `SignatureVerification::trusted_list`), call it for every list. The lists are
+    /// shared, so they can be read once and used for many documents.
+    pub fn trusted_list(mut self, trusted_list: Arc<TrustedList>) -> Self {
+        self.options.trusted_lists.push(trusted_list);
+        self
+    }
+
+    /// Check the certificate chain of the signers up to the certificates of a trust store
+    /// (see `SignatureVerification::chain_trusted`), call it for every store.
+    /// Without trust stores the chains are not checked.
+    pub fn trust_store(mut self, trust_store: TrustStore) -> Self {
+        self.options.trust_stores.push(trust_store);
+        self
+    }
+
+    pub fn build(self) -> VerificationOptions {
+        self.options
+    }
+}
@@ -0,0 +1,265 @@
+//! Stamp a (translucent) text or image watermark on every page.
+
+use crate::font::encode_win_ansi;
+use crate::image_options::ImageOptions;
+use crate::journal::JournalChange;
+use crate::rectangle::Rectangle;
+use crate::tagged_content::ContentTag;
+use crate::{Error, InsertImage, InsertImageToPage, PDFSigningDocument};
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Object, ObjectId, Stream, StringFormat,
+};
+
+/// Font size used inside the watermark XObject, it is scaled to the page later.
+const TEXT_FONT_SIZE: f64 = 100.0;
+/// Ascender and descender of Helvetica Bold (in 1/1000 of the font size).
+const HELVETICA_BOLD_ASCENT: f64 = 718.0;
+const HELVETICA_BOLD_DESCENT: f64 = 207.0;
+/// Widths of the ASCII characters 32 (space) to 126 (`~`) of Helvetica Bold.
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611, 975, 722, 722, 722, 722, 667,
+    611, 778, 722, 278, 556, 722, 611, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 333, 278, 333, 584, 556, 333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556,
+    278, 889, 611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+/// Width used for characters that are not in `HELVETICA_BOLD_WIDTHS`.
+const HELVETICA_BOLD_DEFAULT_WIDTH: u16 = 556;
+
+/// The content of a watermark.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Watermark {
+    /// Text drawn in Helvetica Bold (or the embedded font), for example "DRAFT" or the email of the recipient.
+    Text(String),
+    /// Image data (PNG, JPEG, TIFF or WebP, SVG with `WatermarkOptions::allow_svg`).
+    Image(Vec<u8>),
+}
+
+/// How a watermark is drawn on the pages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatermarkOptions {
+    /// Opacity from 0.0 (invisible) to 1.0 (opaque).
+    pub opacity: f64,
+    /// Rotation in degrees (counterclockwise).
+    /// `None` follows the diagonal of the page from the bottom left to the top right.
+    pub rotation: Option<f64>,
+    /// Part of the page (0.0 - 1.0) the rotated watermark fits in.
+    pub scale: f64,
+    /// Color (RGB, 0.0 - 1.0) of text watermarks.
+    pub color: (f64, f64, f64),
+    /// Read image watermarks that are SVG images, see `ImageOptions::allow_svg`.
+    pub allow_svg: bool,
+}
+
+impl Default for WatermarkOptions {
+    fn default() -> Self {
+        WatermarkOptions {
+            opacity: 0.3,
+            rotation: None,
+            scale: 0.8,
+            color: (0.5, 0.5, 0.5),
+            allow_svg: false,
+        }
+    }
+}
+
+impl PDFSigningDocument {
+    /// Stamp `watermark` in the center of every page, on top of the existing content.
+    /// The watermark is added once as a Form XObject and reused by all pages.
+    pub fn add_watermark(
+        &mut self,
+        watermark: &Watermark,
+        options: &WatermarkOptions,
+    ) -> Result<(), Error> {
+        let (xobject_id, size) = self.add_watermark_xobject(watermark, options)?;
+
+        // The pages of the latest revision, so pages added after loading are stamped too.
+        let page_ids = self.get_page_ids();
+        let page_count = page_ids.len();
+        for page_id in page_ids {
+            let page_rect = self.get_page_rect(page_id)?;
+            let page_rotation = self.get_page_rotation(page_id);
+            let xobject_name = self.get_unused_xobject_name(page_id, "Watermark");
+            self.add_xobject(page_id, xobject_name.as_str(), xobject_id)?;
+
+            let matrix = Self::get_watermark_matrix(size, &page_rect, page_rotation, options);
+            let operations = vec![
+                Operation::new("q", vec![]),
+                Operation::new("cm", matrix.iter().map(|&value| value.into()).collect()),
+                Operation::new("Do", vec![Object::Name(xobject_name.into_bytes())]),
+                Operation::new("Q", vec![]),
+            ];
+            let tag = ContentTag::Artifact(dictionary! {
+                "Type" => "Pagination",
+                "Subtype" => "Watermark",
+            });
+            let operations = self.tag_page_content(page_id, operations, tag)?;
+            self.opt_clone_object_to_new_document(page_id)?;
+            self.add_to_page_content(page_id, Content { operations })?;
+        }
+        self.record_change(JournalChange::WatermarkAdded {
+            page_indexes: (0..page_count).collect(),
+        });
+        Ok(())
+    }
+
+    /// Add the watermark as a Form XObject with its lower left corner at the origin.
+    /// Return the ObjectId and the size of the Form XObject.
+    fn add_watermark_xobject(
+        &mut self,
+        watermark: &Watermark,
+        options: &WatermarkOptions,
+    ) -> Result<(ObjectId, (f64, f64)), Error> {
+        let graphics_state = dictionary! {
+            "Type" => "ExtGState",
+            "CA" => options.opacity.clamp(0.0, 1.0),
+            "ca" => options.opacity.clamp(0.0, 1.0),
+        };
+        let (resources, operations, bbox) = match watermark {
+            Watermark::Text(text) => {
+                let (red, green, blue) = options.color;
+                let (font, encoded_text, width, ascent, descent) = match self.get_text_font()? {
+                    Some(font) => {
+                        let encoded_text = font.encode(text);
+                        let width = font.get_text_width(&encoded_text);
+                        let font_dict = font.to_font_dictionary(|object| self.add_object(object));
+                        (
+                            Object::Reference(self.add_object(font_dict)),
+                            encoded_text,
+                            width,
+                            font.ascent(),
+                            -font.descent(),
+                        )
+                    }
+                    None => (
+                        Object::Dictionary(Self::standard_font("Helvetica-Bold")),
+                        encode_win_ansi(text),
+                        get_text_width(text),
+                        HELVETICA_BOLD_ASCENT,
+                        HELVETICA_BOLD_DESCENT,
+                    ),
+                };
+                let width = width / 1000.0 * TEXT_FONT_SIZE;
+                let descent = descent / 1000.0 * TEXT_FONT_SIZE;
+                let ascent = ascent / 1000.0 * TEXT_FONT_SIZE;
+                let resources = dictionary! {
+                    "Font" => dictionary! {
+                        "WmFont" => font,
+                    },
+                    "ExtGState" => dictionary! {
+                        "WmGS" => graphics_state,
+                    },
+                };
+                // The descender is below the origin, so move the text up.
+                let operations = vec![
+                    Operation::new("gs", vec![Object::Name(b"WmGS".to_vec())]),
+                    Operation::new("rg", vec![red.into(), green.into(), blue.into()]),
+                    Operation::new("BT", vec![]),
+                    Operation::new(
+                        "Tf",
+                        vec![Object::Name(b"WmFont".to_vec()), TEXT_FONT_SIZE.into()],
+                    ),
+                    Operation::new("Td", vec![0.into(), descent.into()]),
+                    Operation::new(
+                        "Tj",
+                        vec![Object::String(encoded_text, StringFormat::Literal)],
+                    ),
+                    Operation::new("ET", vec![]),
+                ];
+                (resources, operations, (width, ascent + descent))
+            }
+            Watermark::Image(image_data) => {
+                let (image_id, size) = self.add_image_as_unscaled_form_xobject(
+                    image_data,
+                    "WmImage",
+                    &ImageOptions {
+                        allow_svg: options.allow_svg,
+                        ..ImageOptions::default()
+                    },
+                )?;
+                let resources = dictionary! {
+                    "XObject" => dictionary! {
+                        "WmImage" => Object::Reference(image_id),
+                    },
+                    "ExtGState" => dictionary! {
+                        "WmGS" => graphics_state,
+                    },
+                };
+                let operations = vec![
+                    Operation::new("gs", vec![Object::Name(b"WmGS".to_vec())]),
+                    Operation::new("Do", vec![Object::Name(b"WmImage".to_vec())]),
+                ];
+                (resources, operations, size)
+            }
+        };
+
+        let mut stream = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => vec![0.into(), 0.into(), bbox.0.into(), bbox.1.into()],
+                "Resources" => resources,
+            },
+            Content { operations }.encode()?,
+        );
+        let _ = stream.compress();
+        Ok((self.add_object(stream), bbox))
+    }
+
+    /// Get the matrix (`cm`) that rotates the watermark and places it in the center of the page.
+    fn get_watermark_matrix(
+        size: (f64, f64),
+        page_rect: &Rectangle,
+        page_rotation: i64,
+        options: &WatermarkOptions,
+    ) -> [f64; 6] {
+        // The rotation of the page is undone, so the watermark looks the same on all pages.
+        let (visible_width, visible_height) = if page_rotation % 180 == 0 {
+            (page_rect.width(), page_rect.height())
+        } else {
+            (page_rect.height(), page_rect.width())
+        };
+        let rotation = options
+            .rotation
+            .unwrap_or_else(|| visible_height.atan2(visible_width).to_degrees());
+        let angle = (rotation + page_rotation as f64).to_radians();
+        let (sin, cos) = angle.sin_cos();
+
+        // Fit the bounding box of the rotated watermark inside the page.
+        let bounding_width = size.0 * cos.abs() + size.1 * sin.abs();
+        let bounding_height = size.0 * sin.abs() + size.1 * cos.abs();
+        let scale = if bounding_width > 0.0 && bounding_height > 0.0 {
+            (page_rect.width() * options.scale / bounding_width)
+                .min(page_rect.height() * options.scale / bounding_height)
+        } else {
+            1.0
+        };
+
+        let (a, b, c, d) = (scale * cos, scale * sin, -scale * sin, scale * cos);
+        // Move the center of the watermark to the center of the page.
+        let (center_x, center_y) = (size.0 / 2.0, size.1 / 2.0);
+        let page_center_x = (page_rect.x1 + page_rect.x2) / 2.0;
+        let page_center_y = (page_rect.y1 + page_rect.y2) / 2.0;
+        [
+            a,
+            b,
+            c,
+            d,
+            page_center_x - (a * center_x + c * center_y),
+            page_center_y - (b * center_x + d * center_y),
+        ]
+    }
+}
+
+/// Get the width of `text` in Helvetica Bold, in 1/1000 of the font size.
+pub(crate) fn get_text_width(text: &str) -> f64 {
+    text.chars()
+        .map(|character| {
+            let index = (character as usize).wrapping_sub(32);
+            *HELVETICA_BOLD_WIDTHS
+                .get(index)
+                .unwrap_or(&HELVETICA_BOLD_DEFAULT_WIDTH) as f64
+        })
+        .sum()
+}
@@ -0,0 +1,26 @@
+//! Choose how the cross-reference section of a new revision is written.
+
+use lopdf::xref::XrefType;
+
+/// The format of the cross-reference section written for new revisions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum XrefFormat {
+    /// Use the same format as the last revision of the document.
+    #[default]
+    Auto,
+    /// Classic cross-reference table (`xref`), supported by all versions.
+    Table,
+    /// Compressed cross-reference stream (`/Type /XRef`) (PDF 1.5)
+    Stream,
+}
+
+impl XrefFormat {
+    /// Get the type to write, `previous` is the type of the last revision.
+    pub(crate) fn to_xref_type(self, previous: XrefType) -> XrefType {
+        match self {
+            XrefFormat::Auto => previous,
+            XrefFormat::Table => XrefType::CrossReferenceTable,
+            XrefFormat::Stream => XrefType::CrossReferenceStream,
+        }
+    }
+}
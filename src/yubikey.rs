@@ -0,0 +1,241 @@
+//! Sign with the PIV keys of a YubiKey (feature `pcsc`), with the PIN and touch policies
+//! of the key, see `YubiKeySigner::builder`.
+
+use crate::der::read_der;
+use crate::piv::{PinCallback, PinPolicy, PivSigner, PivSlot};
+use crate::{DigestAlgorithm, Error, ExternalSigner};
+use std::fmt;
+use std::sync::Arc;
+
+/// The commands of YubiKeys (firmware 5) that are not in the PIV standard.
+const GET_SERIAL: [u8; 4] = [0x00, 0xf8, 0x00, 0x00];
+const GET_METADATA: u8 = 0xf7;
+
+/// The slot of a YubiKey that signs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum YubiKeySlot {
+    /// `9A`: PIV authentication.
+    Authentication,
+    /// `9C`: digital signature.
+    #[default]
+    Signature,
+}
+
+/// When a YubiKey must be touched to sign, it is set when the key is generated or imported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TouchPolicy {
+    Never,
+    /// For every signature.
+    Always,
+    /// A touch is valid for the signatures of the next 15 seconds.
+    Cached,
+}
+
+/// Called right before a YubiKey waits for a touch, like to show "Touch your YubiKey".
+/// The YubiKey stops waiting after 15 seconds.
+pub type TouchCallback = dyn Fn() + Send + Sync;
+
+/// Signs with the key in slot `9A` or `9C` of a YubiKey. Use it with
+/// `sign_field_with_signer`. The PIN is only asked when the PIN policy of the key needs it,
+/// and the `TouchCallback` is called when the touch policy needs a touch.
+pub struct YubiKeySigner {
+    signer: PivSigner,
+    serial_number: Option<u32>,
+    touch_policy: Option<TouchPolicy>,
+}
+
+impl YubiKeySigner {
+    pub fn builder() -> YubiKeySignerBuilder {
+        YubiKeySignerBuilder {
+            slot: YubiKeySlot::default(),
+            serial_number: None,
+            issuer_certificates: vec![],
+            pin_callback: None,
+            touch_callback: None,
+        }
+    }
+
+    /// Get the serial number, `None` for YubiKeys before firmware 5.
+    pub fn get_serial_number(&self) -> Option<u32> {
+        self.serial_number
+    }
+
+    /// Get the touch policy of the key, `None` when the YubiKey does not tell it (before
+    /// firmware 5.3). Then the `TouchCallback` is called for every signature.
+    pub fn get_touch_policy(&self) -> Option<TouchPolicy> {
+        self.touch_policy
+    }
+
+    /// Get the DER encoded certificate of the key.
+    pub fn get_certificate(&self) -> &[u8] {
+        self.signer.get_certificate()
+    }
+}
+
+impl ExternalSigner for YubiKeySigner {
+    fn get_certificates(&self) -> Result<Vec<Vec<u8>>, Error> {
+        self.signer.get_certificates()
+    }
+
+    fn sign_digest(
+        &self,
+        digest: &[u8],
+        digest_algorithm: DigestAlgorithm,
+    ) -> Result<Vec<u8>, Error> {
+        self.signer.sign_digest(digest, digest_algorithm)
+    }
+}
+
+impl fmt::Debug for YubiKeySigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("YubiKeySigner")
+            .field("signer", &self.signer)
+            .field("serial_number", &self.serial_number)
+            .field("touch_policy", &self.touch_policy)
+            .finish()
+    }
+}
+
+/// Builder for `YubiKeySigner`.
+#[derive(Clone)]
+pub struct YubiKeySignerBuilder {
+    slot: YubiKeySlot,
+    serial_number: Option<u32>,
+    issuer_certificates: Vec<Vec<u8>>,
+    pin_callback: Option<Arc<PinCallback>>,
+    touch_callback: Option<Arc<TouchCallback>>,
+}
+
+impl YubiKeySignerBuilder {
+    /// Sign with the key in `slot`, by default `YubiKeySlot::Signature`.
+    pub fn slot(mut self, slot: YubiKeySlot) -> Self {
+        self.slot = slot;
+        self
+    }
+
+    /// Use the YubiKey with `serial_number` when more YubiKeys are connected, by default
+    /// the first YubiKey is used.
+    pub fn serial_number(mut self, serial_number: u32) -> Self {
+        self.serial_number = Some(serial_number);
+        self
+    }
+
+    /// Add the DER encoded certificate of an issuer of the certificate of the key to the
+    /// signatures, the YubiKey only has the certificate of the key.
+    pub fn issuer_certificate(mut self, certificate: Vec<u8>) -> Self {
+        self.issuer_certificates.push(certificate);
+        self
+    }
+
+    /// Ask the PIN with `pin_callback`, see `PivSignerBuilder::pin_callback`.
+    pub fn pin_callback(mut self, pin_callback: Arc<PinCallback>) -> Self {
+        self.pin_callback = Some(pin_callback);
+        self
+    }
+
+    /// Call `touch_callback` before the YubiKey waits for a touch.
+    pub fn touch_callback(mut self, touch_callback: Arc<TouchCallback>) -> Self {
+        self.touch_callback = Some(touch_callback);
+        self
+    }
+
+    /// Connect to the YubiKey, and read the certificate and the policies of the key.
+    pub fn build(self) -> Result<YubiKeySigner, Error> {
+        let slot = match self.slot {
+            YubiKeySlot::Authentication => PivSlot::Authentication,
+            YubiKeySlot::Signature => PivSlot::Signature,
+        };
+        let mut errors = vec![];
+        // The readers of YubiKeys are named like `Yubico YubiKey OTP+FIDO+CCID 00`.
+        for reader in PivSigner::list_readers()? {
+            if !reader.contains("Yubico") {
+                continue;
+            }
+            let mut builder = PivSigner::builder().reader(&reader).slot(slot);
+            for certificate in &self.issuer_certificates {
+                builder = builder.issuer_certificate(certificate.clone());
+            }
+            if let Some(pin_callback) = &self.pin_callback {
+                builder = builder.pin_callback(pin_callback.clone());
+            }
+            let mut signer = match builder.build() {
+                Ok(signer) => signer,
+                Err(error) => {
+                    errors.push(error.to_string());
+                    continue;
+                }
+            };
+
+            let serial_number = match signer.transmit(GET_SERIAL, &[], true)? {
+                (serial_number, 0x9000) => <[u8; 4]>::try_from(serial_number)
+                    .ok()
+                    .map(u32::from_be_bytes),
+                _ => None,
+            };
+            if self.serial_number.is_some() && serial_number != self.serial_number {
+                continue;
+            }
+
+            // The metadata has the PIN policy and the touch policy, the default (0) of the
+            // touch policy is never.
+            let header = [0x00, GET_METADATA, 0x00, slot.key_reference()];
+            let policies = match signer.transmit(header, &[], true)? {
+                (metadata, 0x9000) => read_policies(&metadata),
+                _ => None,
+            };
+            if let Some((pin_policy, _)) = policies {
+                signer.pin_policy = match pin_policy {
+                    1 => PinPolicy::Never,
+                    2 => PinPolicy::Once,
+                    3 => PinPolicy::Always,
+                    _ => signer.pin_policy,
+                };
+            }
+            let touch_policy = match policies {
+                Some((_, 2)) => Some(TouchPolicy::Always),
+                Some((_, 3)) => Some(TouchPolicy::Cached),
+                Some(_) => Some(TouchPolicy::Never),
+                None => None,
+            };
+            if touch_policy != Some(TouchPolicy::Never) {
+                signer.touch_callback = self.touch_callback.clone();
+            }
+            return Ok(YubiKeySigner {
+                signer,
+                serial_number,
+                touch_policy,
+            });
+        }
+        match (self.serial_number, errors.is_empty()) {
+            (Some(serial_number), true) => Err(Error::Other(format!(
+                "YubiKey: No YubiKey with serial number {} found.",
+                serial_number
+            ))),
+            (None, true) => Err(Error::from("YubiKey: No YubiKey found.")),
+            (_, false) => Err(Error::Other(format!(
+                "YubiKey: No YubiKey found: {}",
+                errors.join(", ")
+            ))),
+        }
+    }
+}
+
+impl fmt::Debug for YubiKeySignerBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("YubiKeySignerBuilder")
+            .field("slot", &self.slot)
+            .field("serial_number", &self.serial_number)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Read the PIN policy and the touch policy of the metadata of a slot.
+fn read_policies(mut metadata: &[u8]) -> Option<(u8, u8)> {
+    while let Some((tag, content, next)) = read_der(metadata) {
+        if let (0x02, [pin_policy, touch_policy]) = (tag, content) {
+            return Some((*pin_policy, *touch_policy));
+        }
+        metadata = next;
+    }
+    None
+}
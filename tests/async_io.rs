@@ -0,0 +1,31 @@
+//! The async variants must work in both tokio runtimes.
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use common::build_form_pdf;
+use pdf_rs::PDFSigningDocument;
+#[cfg(feature = "signing")]
+use pdf_rs::SigningOptions;
+
+async fn read_sign_and_write() {
+    let data = build_form_pdf();
+    let mut document = PDFSigningDocument::read_from_async(&*data, "form.pdf".to_owned())
+        .await
+        .unwrap();
+    #[cfg(feature = "signing")]
+    let _ = document.sign_async(&[], &SigningOptions::default()).await;
+    let mut written = vec![];
+    document.write_document_async(&mut written).await.unwrap();
+    assert!(written.starts_with(b"%PDF-"));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn current_thread_runtime() {
+    read_sign_and_write().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn multi_thread_runtime() {
+    read_sign_and_write().await;
+}
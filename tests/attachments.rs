@@ -0,0 +1,71 @@
+//! Attachments and the signatures that cover them.
+
+mod common;
+
+use common::{build_pdf, find, stream};
+use pdf_rs::{Attachment, PDFSigningDocument};
+
+fn attachment(file_name: &str, data: &[u8]) -> Attachment {
+    Attachment {
+        file_name: file_name.to_owned(),
+        data: data.to_vec(),
+        mime_type: Some("application/json".to_owned()),
+        ..Attachment::default()
+    }
+}
+
+/// Build a PDF file with the attachment `data.json` and a signature `Signature1`, that
+/// covers the whole file except its `Contents`.
+fn build_signed_pdf_with_attachment() -> Vec<u8> {
+    let embedded_file = stream("/Type /EmbeddedFile", "{\"id\":1}");
+    let build = |byte_range: [usize; 3]| {
+        let signature = format!(
+            "<< /Type /Sig /Filter /Adobe.PPKLite /SubFilter /adbe.pkcs7.detached \
+             /ByteRange [0 {:010} {:010} {:010}] /Contents <3000> >>",
+            byte_range[0], byte_range[1], byte_range[2]
+        );
+        build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [6 0 R] >> \
+             /Names << /EmbeddedFiles << /Names [(data.json) 4 0 R] >> >> >>",
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Annots [6 0 R] >>",
+            "<< /Type /Filespec /F (data.json) /UF (data.json) /EF << /F 5 0 R >> >>",
+            &embedded_file,
+            "<< /FT /Sig /T (Signature1) /Type /Annot /Subtype /Widget /Rect [0 0 0 0] \
+             /P 3 0 R /V 7 0 R >>",
+            &signature,
+        ])
+    };
+    // The numbers of the `ByteRange` have a fixed width, so the objects do not move.
+    let data = build([0; 3]);
+    let contents_start = find(&data, b"<3000>");
+    let contents_end = contents_start + b"<3000>".len();
+    build([contents_start, contents_end, data.len() - contents_end])
+}
+
+#[test]
+fn attachment_added_before_signing_is_covered() {
+    let data = build_signed_pdf_with_attachment();
+    let document = PDFSigningDocument::read_from(&*data, "signed.pdf".to_owned()).unwrap();
+    assert!(document.get_unsigned_attachments().is_empty());
+    let attachment = document.get_attachment("data.json").unwrap();
+    assert_eq!(attachment.data, b"{\"id\":1}");
+}
+
+#[test]
+fn attachment_added_after_signing_is_reported() {
+    let data = build_signed_pdf_with_attachment();
+    let mut document = PDFSigningDocument::read_from(&*data, "signed.pdf".to_owned()).unwrap();
+    document
+        .add_attachment(&attachment("late.json", b"{\"id\":2}"))
+        .unwrap();
+    // Not saved yet.
+    assert_eq!(document.get_unsigned_attachments(), vec!["late.json"]);
+
+    let mut saved = vec![];
+    document.write_document(&mut saved).unwrap();
+    assert!(saved.starts_with(&data));
+    let document = PDFSigningDocument::read_from(&*saved, "saved.pdf".to_owned()).unwrap();
+    assert_eq!(document.get_unsigned_attachments(), vec!["late.json"]);
+    assert_eq!(document.get_attachments().len(), 2);
+}
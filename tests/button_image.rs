@@ -0,0 +1,38 @@
+//! Images in pushbuttons with `fill_button_image`, PNG images need the feature `images`.
+#![cfg(feature = "images")]
+
+mod common;
+
+use common::{build_form_pdf, build_pdf, contains};
+use pdf_rs::{Error, PDFSigningDocument};
+
+/// A red PNG image of 2 by 2 pixels.
+const IMAGE: &[u8] = include_bytes!("assets/red.png");
+
+#[test]
+fn pushbutton_with_inherited_flags_gets_image() {
+    // The pushbutton flag (bit 17) is set on the parent of the field.
+    let data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [4 0 R] >> >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Annots [5 0 R] >>",
+        "<< /T (buttons) /FT /Btn /Ff 65536 /Kids [5 0 R] >>",
+        "<< /T (Photo) /Parent 4 0 R /Type /Annot /Subtype /Widget /Rect [10 10 60 60] \
+         /P 3 0 R >>",
+    ]);
+    let mut document = PDFSigningDocument::read_from(&*data, "form.pdf".to_owned()).unwrap();
+    document.fill_button_image("Photo", IMAGE).unwrap();
+    let saved = document.save_to_bytes().unwrap();
+    assert!(contains(&saved, b"/ButtonImagePhoto"));
+}
+
+#[test]
+fn check_box_is_rejected() {
+    let data = build_form_pdf();
+    let mut document = PDFSigningDocument::read_from(&*data, "form.pdf".to_owned()).unwrap();
+    let result = document.fill_button_image("Check1", IMAGE);
+    assert!(matches!(
+        result,
+        Err(Error::UnsupportedFieldType { ref name, .. }) if name == "Check1"
+    ));
+}
@@ -0,0 +1,113 @@
+//! Build small PDF files for the tests, like malformed files that other tools do not write.
+
+#![allow(dead_code)]
+
+/// Build a PDF file with the `objects` (numbered from 1) and a cross-reference table, the
+/// first object is the catalog.
+pub fn build_pdf(objects: &[&str]) -> Vec<u8> {
+    let mut data = b"%PDF-1.7\n".to_vec();
+    let mut offsets = vec![];
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(data.len());
+        data.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", index + 1, object).as_bytes());
+    }
+    let xref = data.len();
+    data.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+    );
+    for offset in offsets {
+        data.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    data.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .as_bytes(),
+    );
+    data
+}
+
+/// Build a PDF file with one page and a signature field `Signature1` that is signed with the
+/// hex encoded `contents`. The `ByteRange` covers the whole file except the `Contents`.
+pub fn build_signed_pdf(contents: &str) -> Vec<u8> {
+    build_signature_pdf("/Type /Sig /SubFilter /adbe.pkcs7.detached", contents)
+}
+
+/// Build a PDF file like `build_signed_pdf`, with the `entries` (like the `Type` and
+/// `SubFilter`) in the signature dictionary.
+pub fn build_signature_pdf(entries: &str, contents: &str) -> Vec<u8> {
+    let signature = format!(
+        "<< {} /Filter /Adobe.PPKLite \
+         /ByteRange [0 AAAAAAAAAA BBBBBBBBBB CCCCCCCCCC] /Contents <{}> >>",
+        entries, contents
+    );
+    let mut data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [4 0 R] >> >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Annots [4 0 R] >>",
+        "<< /FT /Sig /T (Signature1) /Type /Annot /Subtype /Widget /Rect [0 0 0 0] /P 3 0 R /V 5 0 R >>",
+        &signature,
+    ]);
+    let contents_start = find(&data, format!("<{}>", contents).as_bytes());
+    let contents_end = contents_start + contents.len() + 2;
+    let length = data.len();
+    for (placeholder, value) in [
+        (&b"AAAAAAAAAA"[..], contents_start),
+        (b"BBBBBBBBBB", contents_end),
+        (b"CCCCCCCCCC", length - contents_end),
+    ] {
+        let position = find(&data, placeholder);
+        data[position..position + 10].copy_from_slice(format!("{:010}", value).as_bytes());
+    }
+    data
+}
+
+/// Find the position of `needle` in `data`.
+pub fn find(data: &[u8], needle: &[u8]) -> usize {
+    data.windows(needle.len())
+        .position(|window| window == needle)
+        .expect("not found")
+}
+
+/// `data` contains `needle`.
+pub fn contains(data: &[u8], needle: &[u8]) -> bool {
+    data.windows(needle.len()).any(|window| window == needle)
+}
+
+/// A stream object with `content`, the `dictionary` entries are added to the `Length`.
+pub fn stream(dictionary: &str, content: &str) -> String {
+    format!(
+        "<< /Length {} {} >>\nstream\n{}\nendstream",
+        content.len(),
+        dictionary,
+        content
+    )
+}
+
+/// Build a PDF file with one page with text, and a text field `Text1`, a check box `Check1`
+/// and an empty signature field `Signature1` on it.
+pub fn build_form_pdf() -> Vec<u8> {
+    let content = stream("", "BT /F1 12 Tf 20 150 Td (Hello world) Tj ET");
+    let appearance = stream(
+        "/Type /XObject /Subtype /Form /BBox [0 0 100 20]",
+        "/Tx BMC EMC",
+    );
+    build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [5 0 R 6 0 R 7 0 R] \
+         /DA (/Helv 0 Tf 0 g) /DR << /Font << /Helv 9 0 R >> >> >> >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Contents 4 0 R \
+         /Resources << /Font << /F1 9 0 R >> >> /Annots [5 0 R 6 0 R 7 0 R] >>",
+        &content,
+        "<< /FT /Tx /T (Text1) /Type /Annot /Subtype /Widget /Rect [10 10 110 30] /P 3 0 R \
+         /DA (/Helv 12 Tf 0 g) /V (old) /AP << /N 8 0 R >> >>",
+        "<< /FT /Btn /T (Check1) /Type /Annot /Subtype /Widget /Rect [10 40 30 60] /P 3 0 R \
+         /V /Off /AS /Off >>",
+        "<< /FT /Sig /T (Signature1) /Type /Annot /Subtype /Widget /Rect [10.0 70.0 110.0 90.0] \
+         /P 3 0 R >>",
+        &appearance,
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>",
+    ])
+}
@@ -0,0 +1,100 @@
+//! Documents protected with a password.
+
+mod common;
+
+use common::{build_form_pdf, contains};
+use pdf_rs::{Bookmark, EncryptionOptions, Error, PDFSigningDocument, Permissions};
+
+/// Encrypt the form document with `options` and return the saved document.
+fn encrypt(options: &EncryptionOptions) -> Vec<u8> {
+    let mut document =
+        PDFSigningDocument::read_from(&*build_form_pdf(), "form.pdf".to_owned()).unwrap();
+    document.encrypt_document(options).unwrap();
+    document.save_to_bytes().unwrap()
+}
+
+fn read(data: &[u8], password: &str) -> Result<PDFSigningDocument, Error> {
+    PDFSigningDocument::read_from_with_password(data, "encrypted.pdf".to_owned(), password)
+}
+
+fn options() -> EncryptionOptions {
+    EncryptionOptions {
+        user_password: "user".to_owned(),
+        owner_password: "owner".to_owned(),
+        permissions: Permissions::default(),
+    }
+}
+
+#[test]
+fn document_is_opened_with_user_or_owner_password() {
+    let data = encrypt(&options());
+    for password in ["user", "owner"] {
+        let mut document = read(&data, password).unwrap();
+        assert_eq!(document.get_form_fields().unwrap().len(), 3);
+    }
+}
+
+#[test]
+fn wrong_password_is_rejected() {
+    let data = encrypt(&options());
+    for password in ["", "wrong"] {
+        assert!(matches!(
+            read(&data, password),
+            Err(Error::EncryptionRequired)
+        ));
+    }
+}
+
+#[test]
+fn changes_are_saved_with_the_same_encryption() {
+    let data = encrypt(&options());
+    let mut document = read(&data, "user").unwrap();
+    let bookmark = Bookmark {
+        title: "Secret chapter".to_owned(),
+        page_index: Some(0),
+        open: false,
+        children: vec![],
+    };
+    document
+        .set_outline(std::slice::from_ref(&bookmark))
+        .unwrap();
+    let saved = document.save_to_bytes().unwrap();
+    assert!(saved.starts_with(&data));
+    assert!(!contains(&saved, b"Secret chapter"));
+
+    let document = read(&saved, "owner").unwrap();
+    assert_eq!(document.get_outline(), vec![bookmark]);
+}
+
+#[test]
+fn strings_and_streams_are_encrypted_with_aes_256() {
+    let data = encrypt(&options());
+    assert!(contains(&data, b"/AESV3"));
+    assert!(!contains(&data, b"Hello world"));
+    assert!(!contains(&data, b"(old)"));
+}
+
+#[test]
+fn permissions_are_written() {
+    let permissions = Permissions {
+        print: false,
+        copy: false,
+        ..Permissions::default()
+    };
+    let data = encrypt(&EncryptionOptions {
+        permissions,
+        ..options()
+    });
+    let document = read(&data, "user").unwrap();
+    assert_eq!(document.get_permissions(), Some(permissions));
+}
+
+#[test]
+fn empty_user_password_opens_without_password() {
+    let data = encrypt(&EncryptionOptions {
+        user_password: String::new(),
+        ..options()
+    });
+    let document = PDFSigningDocument::read_from(&*data, "encrypted.pdf".to_owned()).unwrap();
+    assert_eq!(document.get_permissions(), Some(Permissions::default()));
+}
@@ -0,0 +1,111 @@
+//! Filling text fields with `fill_form`, that needs the feature `serde`.
+#![cfg(feature = "serde")]
+
+mod common;
+
+use common::{build_pdf, contains};
+use pdf_rs::{Error, FieldMatching, PDFSigningDocument};
+use serde_json::{json, Map};
+
+/// Build a PDF file with the text fields `buyer.name` and `seller.name`, the type of the
+/// fields is set on their parents.
+fn build_nested_form_pdf() -> Vec<u8> {
+    build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [4 0 R 5 0 R] \
+         /DA (/Helv 0 Tf 0 g) /DR << /Font << /Helv 8 0 R >> >> >> >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Annots [6 0 R 7 0 R] >>",
+        "<< /T (buyer) /FT /Tx /Kids [6 0 R] >>",
+        "<< /T (seller) /FT /Tx /Kids [7 0 R] >>",
+        "<< /T (name) /Parent 4 0 R /Type /Annot /Subtype /Widget /Rect [10 10 110 30] \
+         /P 3 0 R /DA (/Helv 12 Tf 0 g) >>",
+        "<< /T (name) /Parent 5 0 R /Type /Annot /Subtype /Widget /Rect [10 40 110 60] \
+         /P 3 0 R /DA (/Helv 12 Tf 0 g) >>",
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>",
+    ])
+}
+
+#[test]
+fn child_fields_have_fully_qualified_names() {
+    let data = build_nested_form_pdf();
+    let mut document = PDFSigningDocument::read_from(&*data, "form.pdf".to_owned()).unwrap();
+    let names: Vec<_> = document
+        .get_form_fields()
+        .unwrap()
+        .iter()
+        .map(|field| field.fully_qualified_name().map(str::to_owned))
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            Some("buyer.name".to_owned()),
+            Some("seller.name".to_owned())
+        ]
+    );
+}
+
+#[test]
+fn fully_qualified_name_fills_one_field() {
+    let data = build_nested_form_pdf();
+    let mut document = PDFSigningDocument::read_from(&*data, "form.pdf".to_owned()).unwrap();
+    document.set_field_matching(FieldMatching::FullyQualified);
+    let mut values = Map::new();
+    values.insert("seller.name".to_owned(), json!("Bob"));
+    // The partial name does not match with `FullyQualified`.
+    values.insert("name".to_owned(), json!("Ann"));
+    document.fill_form(values).unwrap();
+    let saved = document.save_to_bytes().unwrap();
+    assert!(contains(&saved, b"(Bob)"));
+    assert!(!contains(&saved, b"(Ann)"));
+}
+
+#[test]
+fn value_that_is_not_a_string_is_an_error() {
+    let data = build_nested_form_pdf();
+    let mut document = PDFSigningDocument::read_from(&*data, "form.pdf".to_owned()).unwrap();
+    document.set_field_matching(FieldMatching::FullyQualified);
+    let mut values = Map::new();
+    values.insert("buyer.name".to_owned(), json!(5));
+    assert!(document.fill_form(values).is_err());
+}
+
+/// Build a PDF file with the text field `code`, that has a `MaxLen` of 3 set on its parent,
+/// and the text field `date` with the format `mm/dd/yyyy`.
+fn build_validated_form_pdf() -> Vec<u8> {
+    build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [4 0 R 6 0 R] \
+         /DA (/Helv 0 Tf 0 g) /DR << /Font << /Helv 7 0 R >> >> >> >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Annots [5 0 R 6 0 R] >>",
+        "<< /T (order) /FT /Tx /MaxLen 3 /Kids [5 0 R] >>",
+        "<< /T (code) /Parent 4 0 R /Type /Annot /Subtype /Widget /Rect [10 10 110 30] \
+         /P 3 0 R /DA (/Helv 12 Tf 0 g) >>",
+        "<< /T (date) /FT /Tx /Type /Annot /Subtype /Widget /Rect [10 40 110 60] \
+         /P 3 0 R /DA (/Helv 12 Tf 0 g) \
+         /AA << /F << /S /JavaScript /JS (AFDate_FormatEx(\"mm/dd/yyyy\");) >> >> >>",
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>",
+    ])
+}
+
+/// Fill `field_name` of the validated form with `value`, with the format checked.
+fn fill_validated_form(field_name: &str, value: &str) -> Result<(), Error> {
+    let data = build_validated_form_pdf();
+    let mut document = PDFSigningDocument::read_from(&*data, "form.pdf".to_owned()).unwrap();
+    document.set_validate_field_format(true);
+    let mut values = Map::new();
+    values.insert(field_name.to_owned(), json!(value));
+    document.fill_form(values)
+}
+
+#[test]
+fn max_len_of_parent_is_inherited() {
+    assert!(fill_validated_form("code", "ABC").is_ok());
+    assert!(fill_validated_form("code", "ABCD").is_err());
+}
+
+#[test]
+fn date_must_exist() {
+    assert!(fill_validated_form("date", "02/29/2024").is_ok());
+    assert!(fill_validated_form("date", "02/31/2024").is_err());
+    assert!(fill_validated_form("date", "2024-02-01").is_err());
+}
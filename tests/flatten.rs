@@ -0,0 +1,37 @@
+//! Form fields drawn in the content of the pages with `flatten_form`.
+
+mod common;
+
+use common::{build_form_pdf, build_pdf, contains};
+use pdf_rs::PDFSigningDocument;
+
+#[test]
+fn widgets_are_drawn_and_form_is_removed() {
+    let data = build_form_pdf();
+    let mut document = PDFSigningDocument::read_from(&*data, "form.pdf".to_owned()).unwrap();
+    assert_eq!(document.flatten_form().unwrap(), 3);
+    let saved = document.save_to_bytes().unwrap();
+    assert!(!contains(&saved, b"/AcroForm"));
+    assert!(!contains(&saved, b"/Widget"));
+    // Only `Text1` has an appearance, it is drawn as XObject.
+    assert!(contains(&saved, b"/Flattened1"));
+    assert!(!contains(&saved, b"/Flattened2"));
+
+    let mut document = PDFSigningDocument::read_from(&*saved, "flat.pdf".to_owned()).unwrap();
+    assert!(document.get_form_fields().unwrap().is_empty());
+    assert!(document.extract_text(0).unwrap().contains("Hello world"));
+}
+
+#[test]
+fn document_without_form_is_not_changed() {
+    let data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] >>",
+    ]);
+    let mut document = PDFSigningDocument::read_from(&*data, "plain.pdf".to_owned()).unwrap();
+    assert_eq!(document.flatten_form().unwrap(), 0);
+    let saved = document.save_to_bytes().unwrap();
+    assert!(saved.starts_with(&data));
+    assert!(!contains(&saved, b"/Flattened"));
+}
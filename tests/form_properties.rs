@@ -0,0 +1,48 @@
+//! Properties of form fields, like the options of choice fields.
+
+mod common;
+
+use common::{build_pdf, contains};
+use pdf_rs::{ChoiceOption, PDFSigningDocument};
+
+/// Build a PDF file with the combo box `Color`, with `red` selected.
+fn build_choice_pdf() -> Vec<u8> {
+    build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [4 0 R] >> >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Annots [4 0 R] >>",
+        "<< /T (Color) /FT /Ch /Ff 131072 /Opt [(red) (blue)] /V (red) /I [0] \
+         /Type /Annot /Subtype /Widget /Rect [10 10 110 30] /P 3 0 R \
+         /DA (/Helv 12 Tf 0 g) /AP << /N 5 0 R >> >>",
+        "<< /Length 0 >>\nstream\n\nendstream",
+    ])
+}
+
+/// Set the options of `Color` and return the incremental update of the saved document.
+fn set_options(options: &[&str]) -> Vec<u8> {
+    let data = build_choice_pdf();
+    let mut document = PDFSigningDocument::read_from(&*data, "form.pdf".to_owned()).unwrap();
+    let options: Vec<_> = options
+        .iter()
+        .map(|option| ChoiceOption::new(option.to_string(), option.to_string()))
+        .collect();
+    document.set_field_options("Color", &options).unwrap();
+    document.save_to_bytes().unwrap()[data.len()..].to_vec()
+}
+
+#[test]
+fn value_that_is_still_an_option_is_kept() {
+    let revision = set_options(&["green", "red"]);
+    assert!(contains(&revision, b"/V(red)"));
+    assert!(!contains(&revision, b"/I["));
+    // The appearance is created again.
+    assert!(contains(&revision, b"/AP<</N "));
+    assert!(!contains(&revision, b"/AP<</N 5 0 R>>"));
+}
+
+#[test]
+fn value_that_is_no_option_is_removed() {
+    let revision = set_options(&["green", "blue"]);
+    assert!(contains(&revision, b"/Opt[(green)(blue)]"));
+    assert!(!contains(&revision, b"/V("));
+}
@@ -0,0 +1,101 @@
+//! Images in the formats that can be embedded.
+
+mod common;
+
+use common::build_form_pdf;
+#[cfg(feature = "images")]
+use pdf_rs::lopdf::{Document, Object};
+use pdf_rs::{Error, ImageOptions, PDFSigningDocument, Rectangle};
+
+/// Add `image` to the first page of a document, returns the saved document.
+fn add_image(image: &[u8]) -> Result<Vec<u8>, Error> {
+    let data = build_form_pdf();
+    let mut document = PDFSigningDocument::read_from(&*data, "form.pdf".to_owned()).unwrap();
+    let rect = Rectangle {
+        x1: 10.0,
+        y1: 10.0,
+        x2: 20.0,
+        y2: 20.0,
+    };
+    document.add_image(0, image, rect, &ImageOptions::default())?;
+    document.save_to_bytes()
+}
+
+/// A JPEG file of 1 gray pixel with the start of frame `marker` and `precision`, without
+/// the scan data (it is not decoded).
+fn jpeg(marker: u8, precision: u8) -> Vec<u8> {
+    let mut data = vec![0xFF, 0xD8, 0xFF, marker, 0x00, 0x0B, precision];
+    data.extend_from_slice(&[0x00, 0x01, 0x00, 0x01, 0x01, 0x01, 0x11, 0x00]);
+    data.extend_from_slice(&[0xFF, 0xD9]);
+    data
+}
+
+#[test]
+fn baseline_jpeg_is_embedded() {
+    assert!(add_image(&jpeg(0xC0, 8)).is_ok());
+}
+
+#[test]
+fn jpeg_with_12_bit_precision_is_rejected() {
+    assert!(matches!(add_image(&jpeg(0xC1, 12)), Err(Error::Other(_))));
+}
+
+#[test]
+fn jpeg_with_arithmetic_coding_is_rejected() {
+    assert!(matches!(add_image(&jpeg(0xC9, 8)), Err(Error::Other(_))));
+}
+
+/// A little endian TIFF file of 2 x 1 gray pixels with 8 bits, `photometric` 0 is
+/// `WhiteIsZero` and 1 `BlackIsZero`.
+#[cfg(feature = "images")]
+fn gray_tiff(photometric: u16, pixels: [u8; 2]) -> Vec<u8> {
+    // Tag, type (3 is `SHORT`, 4 is `LONG`) and value of the IFD entries.
+    let entries: [(u16, u16, u32); 9] = [
+        (256, 3, 2),
+        (257, 3, 1),
+        (258, 3, 8),
+        (259, 3, 1),
+        (262, 3, photometric.into()),
+        // The pixels follow the IFD: 8 + 2 + 9 * 12 + 4.
+        (273, 4, 122),
+        (277, 3, 1),
+        (278, 3, 1),
+        (279, 4, 2),
+    ];
+    let mut data = b"II*\0\x08\0\0\0".to_vec();
+    data.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    for (tag, value_type, value) in entries {
+        data.extend_from_slice(&tag.to_le_bytes());
+        data.extend_from_slice(&value_type.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&pixels);
+    data
+}
+
+/// Get the samples of the image XObject in `data`.
+#[cfg(feature = "images")]
+fn image_samples(data: &[u8]) -> Vec<u8> {
+    let doc = Document::load_mem(data).unwrap();
+    let stream = doc
+        .objects
+        .values()
+        .filter_map(|object| object.as_stream().ok())
+        .find(|stream| stream.dict.get(b"Subtype").ok() == Some(&Object::Name(b"Image".to_vec())))
+        .unwrap();
+    assert!(stream.dict.get(b"Decode").is_err());
+    stream
+        .decompressed_content()
+        .unwrap_or_else(|_| stream.content.clone())
+}
+
+#[cfg(feature = "images")]
+#[test]
+fn tiff_with_white_is_zero_is_inverted() {
+    let white_is_zero = add_image(&gray_tiff(0, [0x00, 0x40])).unwrap();
+    assert_eq!(image_samples(&white_is_zero), vec![0xFF, 0xBF]);
+    let black_is_zero = add_image(&gray_tiff(1, [0x00, 0x40])).unwrap();
+    assert_eq!(image_samples(&black_is_zero), vec![0x00, 0x40]);
+}
@@ -0,0 +1,41 @@
+//! Linearized documents (fast web view), written with `set_linearize`.
+
+mod common;
+
+use common::{build_form_pdf, build_signed_pdf, contains};
+use pdf_rs::{Bookmark, PDFSigningDocument};
+
+#[test]
+fn linearized_document_is_read_again() {
+    let mut document =
+        PDFSigningDocument::read_from(&*build_form_pdf(), "form.pdf".to_owned()).unwrap();
+    let bookmark = Bookmark {
+        title: "Start".to_owned(),
+        page_index: Some(0),
+        open: false,
+        children: vec![],
+    };
+    document
+        .set_outline(std::slice::from_ref(&bookmark))
+        .unwrap();
+    document.set_linearize(true);
+    let saved = document.save_to_bytes().unwrap();
+    // The linearization dictionary is the first object of the file.
+    assert!(saved.starts_with(b"%PDF-"));
+    assert!(contains(&saved[..1024.min(saved.len())], b"/Linearized"));
+
+    let mut document = PDFSigningDocument::read_from(&*saved, "saved.pdf".to_owned()).unwrap();
+    assert_eq!(document.get_form_fields().unwrap().len(), 3);
+    assert_eq!(document.get_outline(), vec![bookmark]);
+    assert!(document.extract_text(0).unwrap().contains("Hello world"));
+}
+
+#[test]
+fn signed_document_is_saved_as_incremental_update() {
+    let data = build_signed_pdf("3000");
+    let mut document = PDFSigningDocument::read_from(&*data, "signed.pdf".to_owned()).unwrap();
+    document.set_linearize(true);
+    let saved = document.save_to_bytes().unwrap();
+    assert!(saved.starts_with(&data));
+    assert!(!contains(&saved, b"/Linearized"));
+}
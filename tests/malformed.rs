@@ -0,0 +1,337 @@
+//! Malformed documents, like uploads of an attacker, must return errors and not panic.
+
+mod common;
+
+use common::{build_form_pdf, build_pdf, build_signed_pdf, find, stream};
+use pdf_rs::{
+    Error, FormFieldType, PDFSigningDocument, ParsingLimits, SigningOptions, VerificationOptions,
+};
+use serde_json::{json, Map, Value};
+
+/// Read `data` and use the document, every step may fail but must not panic.
+fn exercise(data: &[u8]) {
+    let Ok(mut document) = PDFSigningDocument::read_from(data, "malformed.pdf".to_owned()) else {
+        return;
+    };
+    let _ = document.get_form_fields();
+    let _ = document.summary();
+    let _ = document.verify_signatures(&VerificationOptions::default());
+    for page_index in 0..2 {
+        let _ = document.extract_text(page_index);
+    }
+    let mut values = Map::new();
+    values.insert("Text1".to_owned(), json!("new value"));
+    values.insert("Check1".to_owned(), Value::Bool(true));
+    let _ = document.fill_form(values);
+    let _ = document.flatten_form();
+    let _ = document.save_to_bytes();
+}
+
+#[test]
+fn valid_form_document() {
+    let data = build_form_pdf();
+    let mut document = PDFSigningDocument::read_from(&*data, "form.pdf".to_owned()).unwrap();
+    assert_eq!(document.get_form_fields().unwrap().len(), 3);
+    assert!(document.extract_text(0).unwrap().contains("Hello world"));
+    let mut values = Map::new();
+    values.insert("Text1".to_owned(), json!("new value"));
+    document.fill_form(values).unwrap();
+    document.save_to_bytes().unwrap();
+}
+
+#[test]
+fn truncated_documents() {
+    let data = build_form_pdf();
+    let step = (data.len() / 97).max(1);
+    for length in (0..data.len()).step_by(step) {
+        exercise(&data[..length]);
+    }
+}
+
+#[test]
+fn corrupted_bytes() {
+    let data = build_form_pdf();
+    // A fixed xorshift generator, so failures can be reproduced.
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    for _ in 0..300 {
+        let mut corrupted = data.clone();
+        for _ in 0..1 + next() % 4 {
+            let index = (next() % corrupted.len() as u64) as usize;
+            corrupted[index] = match next() % 4 {
+                0 => b'0',
+                1 => b' ',
+                2 => b'(',
+                _ => next() as u8,
+            };
+        }
+        exercise(&corrupted);
+    }
+}
+
+#[test]
+fn malformed_structures() {
+    let page = "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Annots [4 0 R] >>";
+    let pages = "<< /Type /Pages /Kids [3 0 R] /Count 1 >>";
+    let catalog = "<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [4 0 R] >> >>";
+    let font_widths = stream("", "BT /F1 12 Tf (AB) Tj ET");
+    let documents = [
+        // `Fields` is not an array.
+        build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields 5 >> >>",
+            pages,
+            page,
+        ]),
+        // `AcroForm` is not a dictionary.
+        build_pdf(&["<< /Type /Catalog /Pages 2 0 R /AcroForm 7 >>", pages, page]),
+        // A loop in the `Parent` of the fields.
+        build_pdf(&[
+            catalog,
+            pages,
+            page,
+            "<< /FT /Tx /T (Text1) /Parent 5 0 R /Rect [0 0 10 10] >>",
+            "<< /T (Parent) /Parent 4 0 R >>",
+        ]),
+        // A loop in the `Kids` of a field.
+        build_pdf(&[
+            catalog,
+            pages,
+            page,
+            "<< /FT /Tx /T (Text1) /Kids [4 0 R] >>",
+        ]),
+        // The field type and name have the wrong types.
+        build_pdf(&[
+            catalog,
+            pages,
+            page,
+            "<< /FT (Tx) /T 5 /Rect [0 0 10 10] >>",
+        ]),
+        // A `Rect` with too few numbers and a `DA` that is not a string.
+        build_pdf(&[
+            catalog,
+            pages,
+            page,
+            "<< /FT /Tx /T (Text1) /Rect [0 0] /DA 5 >>",
+        ]),
+        // A field that references a missing object.
+        build_pdf(&[
+            catalog,
+            pages,
+            page,
+            "<< /FT /Tx /T (Text1) /AP << /N 99 0 R >> >>",
+        ]),
+        // The signature value is not a dictionary.
+        build_pdf(&[catalog, pages, page, "<< /FT /Sig /T (Signature1) /V 5 >>"]),
+        // A `ByteRange` with negative numbers and an uneven length.
+        build_pdf(&[
+            catalog,
+            pages,
+            page,
+            "<< /FT /Sig /T (Signature1) /V << /Filter /Adobe.PPKLite /Contents <00> \
+             /ByteRange [-1 5 7] >> >>",
+        ]),
+        // A `ByteRange` outside the document.
+        build_pdf(&[
+            catalog,
+            pages,
+            page,
+            "<< /FT /Sig /T (Signature1) /V << /Filter /Adobe.PPKLite /Contents <3000> \
+             /ByteRange [0 4294967295 4294967295 4294967295] >> >>",
+        ]),
+        // A loop in the page tree.
+        build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>",
+            "<< /Type /Pages /Kids [2 0 R 3 0 R] /Count 2 >>",
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] >>",
+        ]),
+        // Font widths with a range at the end of the character codes.
+        build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>",
+            pages,
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Contents 4 0 R \
+             /Resources << /Font << /F1 5 0 R >> >> >>",
+            &font_widths,
+            "<< /Type /Font /Subtype /Type0 /BaseFont /Test /Encoding /Identity-H \
+             /DescendantFonts [6 0 R] >>",
+            "<< /Type /Font /Subtype /CIDFontType2 /BaseFont /Test \
+             /W [4294967295 4294967295 500 4294967290 [500 500 500 500 500 500 500]] >>",
+        ]),
+        // Simple font widths that start at the end of the character codes.
+        build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>",
+            pages,
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Contents 4 0 R \
+             /Resources << /Font << /F1 5 0 R >> >> >>",
+            &font_widths,
+            "<< /Type /Font /Subtype /TrueType /BaseFont /Test /FirstChar 4294967295 \
+             /LastChar 4294967295 /Widths [500 500 500] >>",
+        ]),
+        // A truncated signature.
+        build_signed_pdf("3005"),
+        // A signature with an indefinite length.
+        build_signed_pdf("30800000"),
+    ];
+    for data in &documents {
+        exercise(data);
+    }
+}
+
+#[test]
+fn limits_on_every_load_path() {
+    let data = build_form_pdf();
+    // Without the cross-reference table the document is recovered.
+    let damaged = data[..find(&data, b"xref")].to_vec();
+    let limits = ParsingLimits {
+        max_objects: Some(3),
+        ..ParsingLimits::default()
+    };
+    for (data, recovery) in [(&data, false), (&data, true), (&damaged, true)] {
+        let result = PDFSigningDocument::builder()
+            .recovery(recovery)
+            .limits(limits)
+            .read_from(&**data, "limits.pdf".to_owned());
+        assert!(matches!(
+            result,
+            Err(Error::TooManyObjects { maximum: 3, .. })
+        ));
+    }
+    let recovered = PDFSigningDocument::builder()
+        .recovery(true)
+        .read_from(&*damaged, "limits.pdf".to_owned())
+        .unwrap();
+    assert!(recovered.is_recovered());
+}
+
+#[test]
+fn hex_encoded_flate_stream_is_decoded_for_the_limit() {
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+    std::io::Write::write_all(&mut encoder, &[0; 1 << 20]).unwrap();
+    let hex: String = encoder
+        .finish()
+        .unwrap()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Contents 4 0 R >>",
+        &stream(
+            "/Filter [/ASCIIHexDecode /FlateDecode]",
+            &format!("{}>", hex),
+        ),
+    ]);
+    let result = PDFSigningDocument::builder()
+        .limits(ParsingLimits {
+            max_stream_size: Some(1 << 16),
+            ..ParsingLimits::default()
+        })
+        .read_from(&*data, "bomb.pdf".to_owned());
+    assert!(matches!(
+        result,
+        Err(Error::StreamTooLarge {
+            object_id: (4, 0),
+            ..
+        })
+    ));
+}
+
+#[test]
+fn objects_in_object_streams_are_counted_before_parsing() {
+    let object_stream = stream(
+        "/Type /ObjStm /N 3 /First 13",
+        "5 0 6 5 7 10 null null null",
+    );
+    let data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] >>",
+        &object_stream,
+    ]);
+    let result = PDFSigningDocument::builder()
+        .max_objects(5)
+        .read_from(&*data, "object_stream.pdf".to_owned());
+    assert!(matches!(
+        result,
+        Err(Error::TooManyObjects {
+            count: 7,
+            maximum: 5
+        })
+    ));
+}
+
+/// Build a document with a chain of `depth` fields, every field is the kid of the one
+/// before it. Only the first field has the type, the last field is a text field.
+fn build_nested_fields_pdf(depth: usize) -> Vec<u8> {
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [4 0 R] >> >>".to_owned(),
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_owned(),
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] >>".to_owned(),
+    ];
+    for index in 0..depth {
+        let id = index + 4;
+        let mut field = format!("<< /T (f{}) ", index);
+        if index == 0 {
+            field.push_str("/FT /Tx ");
+        } else {
+            field.push_str(&format!("/Parent {} 0 R ", id - 1));
+        }
+        if index + 1 < depth {
+            field.push_str(&format!("/Kids [{} 0 R] ", id + 1));
+        } else {
+            field.push_str("/Type /Annot /Subtype /Widget /Rect [0 0 10 10] ");
+        }
+        field.push_str(">>");
+        objects.push(field);
+    }
+    let objects: Vec<&str> = objects.iter().map(String::as_str).collect();
+    build_pdf(&objects)
+}
+
+#[test]
+fn nested_fields_are_loaded_with_inherited_type() {
+    let data = build_nested_fields_pdf(4);
+    let mut document = PDFSigningDocument::read_from(&*data, "nested.pdf".to_owned()).unwrap();
+    let fields = document.get_form_fields().unwrap();
+    assert_eq!(fields.len(), 1);
+    assert_eq!(fields[0].name(), Some("f3"));
+    assert_eq!(fields[0].fully_qualified_name(), Some("f0.f1.f2.f3"));
+    assert_eq!(fields[0].field_type(), FormFieldType::Text);
+}
+
+#[test]
+fn field_hierarchy_deeper_than_max_depth_is_rejected() {
+    let data = build_nested_fields_pdf(6);
+    let mut document = PDFSigningDocument::builder()
+        .limits(ParsingLimits {
+            max_depth: 3,
+            ..ParsingLimits::default()
+        })
+        .read_from(&*data, "nested.pdf".to_owned())
+        .unwrap();
+    assert!(matches!(
+        document.get_form_fields(),
+        Err(Error::NestingTooDeep { maximum: 3 })
+    ));
+}
+
+#[test]
+fn limits_are_kept_when_document_is_reloaded() {
+    let data = build_form_pdf();
+    // The document has 9 objects, the signature adds more.
+    let mut document = PDFSigningDocument::builder()
+        .max_objects(10)
+        .read_from(&*data, "limits.pdf".to_owned())
+        .unwrap();
+    let result =
+        document.prepare_signature("Signature1", "Signer", None, &SigningOptions::default());
+    assert!(matches!(
+        result,
+        Err(Error::TooManyObjects { maximum: 10, .. })
+    ));
+}
@@ -0,0 +1,64 @@
+//! Documents merged into 1 document.
+
+mod common;
+
+use common::build_form_pdf;
+use pdf_rs::{PDFSigningDocument, SigningOptions};
+
+fn read_form() -> PDFSigningDocument {
+    PDFSigningDocument::read_from(&*build_form_pdf(), "form.pdf".to_owned()).unwrap()
+}
+
+fn field_names(document: &mut PDFSigningDocument) -> Vec<String> {
+    document
+        .get_form_fields()
+        .unwrap()
+        .iter()
+        .filter_map(|field| field.name().map(str::to_owned))
+        .collect()
+}
+
+#[test]
+fn pages_are_kept_and_colliding_fields_are_renamed() {
+    let mut merged =
+        PDFSigningDocument::merge(&[read_form(), read_form()], "merged.pdf".to_owned()).unwrap();
+    assert_eq!(merged.summary().unwrap().page_count, 2);
+    let mut names = field_names(&mut merged);
+    names.sort();
+    assert_eq!(
+        names,
+        vec![
+            "Check1",
+            "Check1_2",
+            "Signature1",
+            "Signature1_2",
+            "Text1",
+            "Text1_2"
+        ]
+    );
+}
+
+#[test]
+fn merged_document_is_read_again() {
+    let merged =
+        PDFSigningDocument::merge(&[read_form(), read_form()], "merged.pdf".to_owned()).unwrap();
+    let saved = merged.save_to_bytes().unwrap();
+    let mut document = PDFSigningDocument::read_from(&*saved, "saved.pdf".to_owned()).unwrap();
+    assert_eq!(document.summary().unwrap().page_count, 2);
+    assert_eq!(field_names(&mut document).len(), 6);
+}
+
+#[test]
+fn renamed_signature_field_can_be_signed() {
+    let mut merged =
+        PDFSigningDocument::merge(&[read_form(), read_form()], "merged.pdf".to_owned()).unwrap();
+    let prepared = merged
+        .prepare_signature("Signature1_2", "Signer", None, &SigningOptions::default())
+        .unwrap();
+    assert_eq!(prepared.get_byte_range().len(), 4);
+}
+
+#[test]
+fn no_documents_is_an_error() {
+    assert!(PDFSigningDocument::merge(&[], "merged.pdf".to_owned()).is_err());
+}
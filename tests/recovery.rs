@@ -0,0 +1,53 @@
+//! Documents with a damaged cross-reference table, that are recovered by scanning the file.
+
+mod common;
+
+use common::{build_form_pdf, find, stream};
+use pdf_rs::PDFSigningDocument;
+
+/// Read `data` with recovery, it must be damaged.
+fn recover(data: &[u8]) -> PDFSigningDocument {
+    let document = PDFSigningDocument::builder()
+        .recovery(true)
+        .read_from(data, "damaged.pdf".to_owned())
+        .unwrap();
+    assert!(document.is_recovered());
+    document
+}
+
+#[test]
+fn implausible_object_id_is_skipped() {
+    let data = build_form_pdf();
+    let mut damaged = data[..find(&data, b"xref")].to_vec();
+    damaged.extend_from_slice(b"4000000000 0 obj\n<< /Type /Catalog >>\nendobj\n");
+    let mut document = recover(&damaged);
+    assert_eq!(document.summary().unwrap().page_count, 1);
+    // Only the objects that exist are in the rebuilt table.
+    assert!(document.save_to_bytes().unwrap().len() < damaged.len() + 1000);
+}
+
+#[test]
+fn objects_in_object_streams_are_recovered() {
+    let objects = [
+        "<< /Type /Catalog /Pages 3 0 R >>",
+        "<< /Type /Pages /Kids [4 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 3 0 R /MediaBox [0 0 200 200] >>",
+    ];
+    let mut header = String::new();
+    let mut body = String::new();
+    for (index, object) in objects.iter().enumerate() {
+        header.push_str(&format!("{} {} ", index + 2, body.len()));
+        body.push_str(object);
+        body.push('\n');
+    }
+    let object_stream = stream(
+        &format!("/Type /ObjStm /N 3 /First {}", header.len()),
+        &format!("{}{}", header, body),
+    );
+    let damaged = format!(
+        "%PDF-1.7\n1 0 obj\n{}\nendobj\ntrailer\n<< /Size 5 /Root 2 0 R >>\nstartxref\n0\n%%EOF\n",
+        object_stream
+    );
+    let mut document = recover(damaged.as_bytes());
+    assert_eq!(document.summary().unwrap().page_count, 1);
+}
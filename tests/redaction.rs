@@ -0,0 +1,57 @@
+//! Redacted areas of pages, the text below them is removed from the content.
+
+mod common;
+
+use common::{build_pdf, contains, stream};
+use pdf_rs::{PDFSigningDocument, Rectangle};
+
+/// A page with the text `Secret` at the top and `Public` at the bottom.
+fn build_text_pdf() -> Vec<u8> {
+    let content = stream(
+        "",
+        "BT /F1 12 Tf 20 150 Td (Secret) Tj ET BT /F1 12 Tf 20 50 Td (Public) Tj ET",
+    );
+    build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Contents 4 0 R \
+         /Resources << /Font << /F1 5 0 R >> >> >>",
+        &content,
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>",
+    ])
+}
+
+fn top_area() -> Rectangle {
+    Rectangle {
+        x1: 10.0,
+        y1: 140.0,
+        x2: 120.0,
+        y2: 170.0,
+    }
+}
+
+#[test]
+fn text_in_area_is_removed() {
+    let data = build_text_pdf();
+    let mut document = PDFSigningDocument::read_from(&*data, "text.pdf".to_owned()).unwrap();
+    assert!(document.extract_text(0).unwrap().contains("Secret"));
+
+    document.redact(&[(0, top_area())]).unwrap();
+    let text = document.extract_text(0).unwrap();
+    assert!(!text.contains("Secret"));
+    assert!(text.contains("Public"));
+
+    // The original content stream is not kept in an earlier revision.
+    let saved = document.save_to_bytes().unwrap();
+    assert!(!contains(&saved, b"(Secret)"));
+    assert!(contains(&saved, b"/Redact"));
+    let document = PDFSigningDocument::read_from(&*saved, "saved.pdf".to_owned()).unwrap();
+    assert!(!document.extract_text(0).unwrap().contains("Secret"));
+}
+
+#[test]
+fn area_on_missing_page_is_an_error() {
+    let mut document =
+        PDFSigningDocument::read_from(&*build_text_pdf(), "text.pdf".to_owned()).unwrap();
+    assert!(document.redact(&[(1, top_area())]).is_err());
+}
@@ -0,0 +1,64 @@
+//! Rich text fields filled with XHTML.
+
+mod common;
+
+use common::{build_form_pdf, contains};
+use pdf_rs::lopdf::Document;
+use pdf_rs::PDFSigningDocument;
+
+/// Fill `Text1` with `xhtml` and return the saved document.
+fn fill(xhtml: &str) -> Vec<u8> {
+    let mut document =
+        PDFSigningDocument::read_from(&*build_form_pdf(), "form.pdf".to_owned()).unwrap();
+    document.fill_rich_text_field("Text1", xhtml).unwrap();
+    document.save_to_bytes().unwrap()
+}
+
+#[test]
+fn spaces_at_run_boundaries_are_kept() {
+    let saved = fill("<body><b>Hello</b> world</body>");
+    assert!(contains(&saved, b"/V(Hello world)"));
+    assert!(contains(&saved, b"/RV"));
+}
+
+#[test]
+fn runs_without_spaces_are_joined() {
+    let saved = fill("<body><b>Hel</b>lo <i> big </i>  world</body>");
+    assert!(contains(&saved, b"/V(Hello big world)"));
+}
+
+#[test]
+fn whitespace_is_collapsed_across_lines() {
+    let saved = fill("<body><p> First  line </p><p>Second<br/> line</p></body>");
+    assert!(contains(&saved, b"/V(First line"));
+    assert!(!contains(&saved, b"/V(First line "));
+    assert!(!contains(&saved, b"Second "));
+}
+
+#[test]
+fn text_that_is_not_ascii_is_utf16() {
+    let saved = fill("<body>Caf\u{e9}</body>");
+    // `FEFF` is the byte order mark, `00E9` the `é`.
+    assert!(contains(
+        &saved.to_ascii_uppercase(),
+        b"/V<FEFF00430061006600E9>"
+    ));
+}
+
+#[test]
+fn text_is_wrapped_to_width_of_field() {
+    let saved = fill("<body>one two three four five six seven eight nine ten</body>");
+    let doc = Document::load_mem(&saved).unwrap();
+    let (_, appearance) = doc
+        .objects
+        .iter()
+        .filter_map(|(id, object)| Some((id, object.as_stream().ok()?)))
+        .find(|(_, stream)| stream.dict.has(b"BBox") && stream.dict.has(b"Resources"))
+        .unwrap();
+    let content = appearance
+        .decompressed_content()
+        .unwrap_or_else(|_| appearance.content.clone());
+    // `Text1` is 100 points wide, the text at 12 points needs at least 3 lines.
+    let breaks = content.windows(2).filter(|window| window == b"T*").count();
+    assert!(breaks >= 2);
+}
@@ -0,0 +1,76 @@
+//! Active and external content removed by `sanitize`.
+
+mod common;
+
+use common::{build_form_pdf, build_pdf, contains};
+use pdf_rs::{PDFSigningDocument, SanitizeKind};
+
+/// Sanitize `data` and return the kinds of content that were removed and the saved document.
+fn sanitize(data: &[u8]) -> (Vec<SanitizeKind>, Vec<u8>) {
+    let mut document = PDFSigningDocument::read_from(data, "active.pdf".to_owned()).unwrap();
+    let removed = document.sanitize().unwrap();
+    let saved = document.save_to_bytes().unwrap();
+    (removed.iter().map(|item| item.kind).collect(), saved)
+}
+
+#[test]
+fn document_without_active_content_is_not_changed() {
+    let data = build_form_pdf();
+    let (removed, saved) = sanitize(&data);
+    assert!(removed.is_empty());
+    assert!(saved.starts_with(&data));
+}
+
+#[test]
+fn javascript_and_uri_actions_are_removed() {
+    let data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R /OpenAction << /S /JavaScript /JS (app.alert(1)) >> \
+         /Names << /JavaScript 5 0 R >> >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Annots [4 0 R] >>",
+        "<< /Type /Annot /Subtype /Link /Rect [0 0 10 10] \
+         /A << /S /GoTo /D [3 0 R /Fit] /Next << /S /URI /URI (https://example.com) >> >> >>",
+        "<< /Names [(init) << /S /JavaScript /JS (app.alert(2)) >>] >>",
+    ]);
+    let (removed, saved) = sanitize(&data);
+    assert!(removed.contains(&SanitizeKind::JavaScript));
+    assert!(removed.contains(&SanitizeKind::Action));
+    assert!(!contains(&saved, b"app.alert"));
+    assert!(!contains(&saved, b"example.com"));
+}
+
+#[test]
+fn rendition_actions_are_removed() {
+    let data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Annots [4 0 R] >>",
+        "<< /Type /Annot /Subtype /Screen /Rect [0 0 10 10] \
+         /AA << /PO << /S /Rendition /OP 0 /JS (app.alert(3)) >> >> >>",
+    ]);
+    let (removed, saved) = sanitize(&data);
+    assert_eq!(removed, vec![SanitizeKind::JavaScript]);
+    assert!(!contains(&saved, b"app.alert"));
+}
+
+#[test]
+fn rich_media_and_file_attachment_annotations_are_removed() {
+    let data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Annots [4 0 R 5 0 R 6 0 R] >>",
+        "<< /Type /Annot /Subtype /RichMedia /Rect [0 0 10 10] \
+         /RichMediaContent << /Assets << /Names [] >> >> >>",
+        "<< /Type /Annot /Subtype /FileAttachment /Rect [10 0 20 10] \
+         /FS << /Type /Filespec /F (data.txt) >> >>",
+        "<< /Type /Annot /Subtype /Text /Rect [20 0 30 10] /Contents (Note) >>",
+    ]);
+    let (removed, saved) = sanitize(&data);
+    assert_eq!(
+        removed,
+        vec![SanitizeKind::JavaScript, SanitizeKind::EmbeddedFile]
+    );
+    assert!(!contains(&saved, b"/RichMedia"));
+    assert!(!contains(&saved, b"/FileAttachment"));
+    assert!(contains(&saved, b"/Text"));
+}
@@ -0,0 +1,133 @@
+//! Documents saved with the options of the cross-reference section and object streams.
+
+mod common;
+
+use common::{build_form_pdf, contains};
+use pdf_rs::{
+    Bookmark, FreeTextOptions, ObjectStreamOptions, PDFSigningDocument, Rectangle, XrefFormat,
+};
+use serde_json::{json, Map};
+
+/// Read `data` with object streams and a cross-reference stream.
+fn read_packed(data: &[u8]) -> PDFSigningDocument {
+    let mut document = PDFSigningDocument::read_from(data, "form.pdf".to_owned()).unwrap();
+    document.set_xref_format(XrefFormat::Stream);
+    document.set_object_streams(Some(ObjectStreamOptions::default()));
+    document
+}
+
+#[test]
+fn object_streams_in_incremental_update() {
+    let data = build_form_pdf();
+    let mut document = read_packed(&data);
+    let bookmark = Bookmark {
+        title: "Start".to_owned(),
+        page_index: Some(0),
+        open: false,
+        children: vec![],
+    };
+    document
+        .set_outline(std::slice::from_ref(&bookmark))
+        .unwrap();
+    let saved = document.save_to_bytes().unwrap();
+    assert!(contains(&saved[data.len()..], b"/ObjStm"));
+
+    let mut document = PDFSigningDocument::read_from(&*saved, "saved.pdf".to_owned()).unwrap();
+    assert_eq!(document.get_outline(), vec![bookmark]);
+    assert_eq!(document.get_form_fields().unwrap().len(), 3);
+}
+
+#[test]
+fn object_streams_in_rewritten_document() {
+    let mut document = read_packed(&build_form_pdf());
+    let mut values = Map::new();
+    values.insert("Text1".to_owned(), json!("new value"));
+    document.fill_form(values).unwrap();
+    let saved = document.save_to_bytes().unwrap();
+    assert!(contains(&saved, b"/ObjStm"));
+    // The offsets of the small document fit in 2 bytes.
+    assert!(contains(&saved, b"/W[1 2 2]"));
+
+    let mut document = PDFSigningDocument::read_from(&*saved, "saved.pdf".to_owned()).unwrap();
+    assert_eq!(document.get_form_fields().unwrap().len(), 3);
+}
+
+#[test]
+fn object_streams_are_split_at_max_objects() {
+    let data = build_form_pdf();
+    let mut document = read_packed(&data);
+    document.set_object_streams(Some(ObjectStreamOptions {
+        max_objects: 1,
+        ..ObjectStreamOptions::default()
+    }));
+    let bookmarks = ["First", "Second"].map(|title| Bookmark {
+        title: title.to_owned(),
+        page_index: Some(0),
+        open: false,
+        children: vec![],
+    });
+    document.set_outline(&bookmarks).unwrap();
+    let saved = document.save_to_bytes().unwrap();
+    let object_streams = saved[data.len()..]
+        .windows(b"/ObjStm".len())
+        .filter(|window| *window == b"/ObjStm")
+        .count();
+    assert!(object_streams >= 3);
+
+    let document = PDFSigningDocument::read_from(&*saved, "saved.pdf".to_owned()).unwrap();
+    assert_eq!(document.get_outline(), bookmarks.to_vec());
+}
+
+#[test]
+fn object_streams_are_not_used_with_cross_reference_table() {
+    let data = build_form_pdf();
+    let mut document = read_packed(&data);
+    document.set_xref_format(XrefFormat::Table);
+    let mut values = Map::new();
+    values.insert("Text1".to_owned(), json!("new value"));
+    document.fill_form(values).unwrap();
+    let saved = document.save_to_bytes().unwrap();
+    assert!(!contains(&saved[data.len()..], b"/ObjStm"));
+    assert!(contains(&saved[data.len()..], b"\nxref\n"));
+}
+
+#[test]
+fn incremental_update_is_read_again() {
+    let data = build_form_pdf();
+    let mut document = PDFSigningDocument::read_from(&*data, "form.pdf".to_owned()).unwrap();
+    let rect = Rectangle {
+        x1: 10.5,
+        y1: 100.0,
+        x2: 110.25,
+        y2: 130.0,
+    };
+    document
+        .add_free_text_annotation(0, rect, "Note", &FreeTextOptions::default())
+        .unwrap();
+    let saved = document.save_to_bytes().unwrap();
+    // The previous revision is written as it is.
+    assert!(saved.starts_with(&data));
+    assert!(contains(&saved[data.len()..], b"/FreeText"));
+
+    let mut document = PDFSigningDocument::read_from(&*saved, "saved.pdf".to_owned()).unwrap();
+    assert_eq!(document.get_form_fields().unwrap().len(), 3);
+    let resaved = document.save_to_bytes().unwrap();
+    assert!(resaved.starts_with(&saved));
+}
+
+#[test]
+fn real_that_is_not_finite_is_rejected() {
+    let mut document =
+        PDFSigningDocument::read_from(&*build_form_pdf(), "form.pdf".to_owned()).unwrap();
+    let rect = Rectangle {
+        x1: f64::NAN,
+        y1: 100.0,
+        x2: f64::INFINITY,
+        y2: 130.0,
+    };
+    // A `NaN` has no syntax in PDF, it is not written as an invalid file.
+    let result = document
+        .add_free_text_annotation(0, rect, "Note", &FreeTextOptions::default())
+        .and_then(|_| document.save_to_bytes());
+    assert!(result.is_err());
+}
@@ -0,0 +1,90 @@
+//! Signatures that are prepared in this crate and signed outside of it.
+
+mod common;
+
+use common::{build_form_pdf, contains};
+use pdf_rs::Error;
+#[cfg(feature = "serde")]
+use pdf_rs::PendingSignature;
+use pdf_rs::{DigestAlgorithm, ExternalSigner, PDFSigningDocument, SigningOptions};
+
+/// Prepare the signature of `Signature1` of `data`, returns the new revision.
+fn prepare(data: &[u8], options: &SigningOptions) -> Vec<u8> {
+    let mut document = PDFSigningDocument::read_from(data, "form.pdf".to_owned()).unwrap();
+    let prepared = document
+        .prepare_signature("Signature1", "Signer", None, options)
+        .unwrap();
+    prepared.get_document_bytes()[data.len()..].to_vec()
+}
+
+#[test]
+fn old_version_is_raised_in_catalog() {
+    let mut data = build_form_pdf();
+    data[5..8].copy_from_slice(b"1.4");
+    let revision = prepare(&data, &SigningOptions::default());
+    assert!(contains(&revision, b"/Version/1.5"));
+}
+
+#[test]
+fn pdf_version_is_set_in_catalog() {
+    let data = build_form_pdf();
+    let options = SigningOptions::builder().pdf_version("2.0").build();
+    let revision = prepare(&data, &options);
+    assert!(contains(&revision, b"/Version/2.0"));
+}
+
+#[test]
+fn current_version_is_kept() {
+    let data = build_form_pdf();
+    let revision = prepare(&data, &SigningOptions::default());
+    assert!(!contains(&revision, b"/Version"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn pending_signature_with_small_placeholder_is_rejected() {
+    let data = build_form_pdf();
+    let mut document = PDFSigningDocument::read_from(&*data, "form.pdf".to_owned()).unwrap();
+    for placeholder_size in [0, 3, 25] {
+        let pending_signature: PendingSignature = serde_json::from_value(serde_json::json!({
+            "fieldName": "Signature1",
+            "signerName": "Signer",
+            "document": "JVBERi0=",
+            "placeholderSize": placeholder_size,
+            "digestAlgorithm": "Sha256",
+            "certificates": [],
+            "signedAttributes": "",
+        }))
+        .unwrap();
+        let result =
+            document.complete_signature(pending_signature, &[0; 64], &SigningOptions::default());
+        assert!(matches!(result, Err(Error::PlaceholderTooSmall { .. })));
+    }
+}
+
+/// A signer like a remote signing service, it must not be called offline.
+struct NetworkSigner;
+
+impl ExternalSigner for NetworkSigner {
+    fn get_certificates(&self) -> Result<Vec<Vec<u8>>, Error> {
+        panic!("The signer is used offline.");
+    }
+
+    fn sign_digest(&self, _: &[u8], _: DigestAlgorithm) -> Result<Vec<u8>, Error> {
+        panic!("The signer is used offline.");
+    }
+
+    fn needs_network(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn offline_rejects_network_signer() {
+    let data = build_form_pdf();
+    let mut document = PDFSigningDocument::read_from(&*data, "form.pdf".to_owned()).unwrap();
+    let options = SigningOptions::builder().offline(true).build();
+    let result =
+        document.sign_field_with_signer("Signature1", "Signer", None, &options, &NetworkSigner);
+    assert!(matches!(result, Err(Error::NetworkDisabled { .. })));
+}
@@ -0,0 +1,26 @@
+//! The tab order of the pages.
+
+mod common;
+
+use common::{build_form_pdf, contains};
+use pdf_rs::{PDFSigningDocument, TabOrder};
+
+#[test]
+fn field_tab_order_needs_pdf_2() {
+    let data = build_form_pdf();
+    let mut document = PDFSigningDocument::read_from(&*data, "form.pdf".to_owned()).unwrap();
+    document.set_field_tab_order(&["Check1", "Text1"]).unwrap();
+    let saved = document.save_to_bytes().unwrap();
+    assert!(contains(&saved, b"/Tabs/A"));
+    assert!(contains(&saved, b"/Version/2.0"));
+}
+
+#[test]
+fn row_order_keeps_version() {
+    let data = build_form_pdf();
+    let mut document = PDFSigningDocument::read_from(&*data, "form.pdf".to_owned()).unwrap();
+    document.set_tab_order(TabOrder::Row).unwrap();
+    let saved = document.save_to_bytes().unwrap();
+    assert!(contains(&saved, b"/Tabs/R"));
+    assert!(!contains(&saved, b"/Version"));
+}
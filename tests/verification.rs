@@ -0,0 +1,435 @@
+//! Signatures are only checked with the feature `signing`.
+#![cfg(feature = "signing")]
+
+mod common;
+
+use common::{build_form_pdf, build_signature_pdf, build_signed_pdf, find};
+use pdf_rs::{
+    AdesLevel, Bookmark, ByteRangeIssue, CertificateDetails, KeyUsage, MatchBy, PDFSigningDocument,
+    SignatureVerification, SigningOptions, SubFilter, TrustStore, TrustedList, TrustedListStatus,
+    UserSignatureInfo, VerificationOptions, VerificationStatus,
+};
+use std::sync::Arc;
+use x509_certificate::{CapturedX509Certificate, InMemorySigningKeyPair};
+
+/// The certificates of `tests/assets`: the signer certificate is issued by the root.
+const ROOT_CERTIFICATE: &str = include_str!("assets/root.pem");
+/// A root certificate that did not issue the signer certificate.
+const OTHER_ROOT_CERTIFICATE: &str = include_str!("assets/other_root.pem");
+const SIGNER_CERTIFICATE: &str = include_str!("assets/signer.pem");
+const SIGNER_KEY: &str = include_str!("assets/signer_key.pem");
+const SIGNATURE_IMAGE: &[u8] = b"<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"100\" \
+    height=\"20\"><rect width=\"100\" height=\"20\"/></svg>";
+
+/// Sign `Signature1` of `data` with the signer certificate, returns the signed document.
+fn sign(data: &[u8], sub_filter: SubFilter) -> Vec<u8> {
+    let key = InMemorySigningKeyPair::from_pkcs8_pem(SIGNER_KEY).unwrap();
+    let certificate = CapturedX509Certificate::from_pem(SIGNER_CERTIFICATE).unwrap();
+    let users = [UserSignatureInfo {
+        box_id: "Signature1".to_owned(),
+        user_id: "1".to_owned(),
+        user_name: "Test Signer".to_owned(),
+        user_email: "signer@example.com".to_owned(),
+        user_signature: SIGNATURE_IMAGE.into(),
+        user_signing_keys: Arc::new(key),
+        user_certificate: certificate,
+    }];
+    let options = SigningOptions::builder()
+        .match_by(MatchBy::BoxId)
+        .sub_filter(sub_filter)
+        .build();
+    let mut document = PDFSigningDocument::read_from(data, "form.pdf".to_owned()).unwrap();
+    document.set_image_allow_svg(true);
+    document.sign(&users, &options).unwrap().document
+}
+
+/// A trust store with the PEM encoded `certificate`.
+fn trust_store(certificate: &str) -> TrustStore {
+    let certificate = CapturedX509Certificate::from_pem(certificate).unwrap();
+    TrustStore::Der(vec![certificate.constructed_data().to_vec()])
+}
+
+/// A trusted list of Belgium with the root certificate as `CA/QC` service with `status`.
+fn trusted_list(status: &str) -> Arc<TrustedList> {
+    let certificate: String = ROOT_CERTIFICATE
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+    let xml = format!(
+        r#"<TrustServiceStatusList xmlns="http://uri.etsi.org/02231/v2#">
+            <SchemeInformation><SchemeTerritory>BE</SchemeTerritory></SchemeInformation>
+            <TrustServiceProviderList><TrustServiceProvider>
+                <TSPInformation><TSPName><Name xml:lang="en">Test Provider</Name></TSPName></TSPInformation>
+                <TSPServices><TSPService><ServiceInformation>
+                    <ServiceTypeIdentifier>http://uri.etsi.org/TrstSvc/Svctype/CA/QC</ServiceTypeIdentifier>
+                    <ServiceName><Name xml:lang="en">Test CA</Name></ServiceName>
+                    <ServiceDigitalIdentity><DigitalId>
+                        <X509Certificate>{}</X509Certificate>
+                    </DigitalId></ServiceDigitalIdentity>
+                    <ServiceStatus>http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/{}</ServiceStatus>
+                </ServiceInformation></TSPService></TSPServices>
+            </TrustServiceProvider></TrustServiceProviderList>
+        </TrustServiceStatusList>"#,
+        certificate, status
+    );
+    Arc::new(TrustedList::from_xml(&xml).unwrap())
+}
+
+/// Get the last 3 numbers of the `ByteRange` of `build_signed_pdf`.
+fn get_byte_range(data: &[u8]) -> [usize; 3] {
+    let start = find(data, b"/ByteRange [0 ") + 14;
+    [0, 11, 22].map(|offset| {
+        std::str::from_utf8(&data[start + offset..start + offset + 10])
+            .unwrap()
+            .parse()
+            .unwrap()
+    })
+}
+
+/// Replace the last 3 numbers of the `ByteRange` of `build_signed_pdf`.
+fn set_byte_range(data: &mut [u8], byte_range: [usize; 3]) {
+    let start = find(data, b"/ByteRange [0 ") + 14;
+    for (offset, value) in [0, 11, 22].into_iter().zip(byte_range) {
+        data[start + offset..start + offset + 10]
+            .copy_from_slice(format!("{:010}", value).as_bytes());
+    }
+}
+
+fn verify(data: &[u8], options: &VerificationOptions) -> Vec<SignatureVerification> {
+    let mut document = PDFSigningDocument::read_from(data, "signed.pdf".to_owned()).unwrap();
+    document.verify_signatures(options).unwrap()
+}
+
+#[test]
+fn signature_that_is_not_cms_is_invalid() {
+    // An empty SEQUENCE instead of the CMS `SignedData`.
+    let data = build_signed_pdf("3000");
+    let mut document = PDFSigningDocument::read_from(&*data, "invalid.pdf".to_owned()).unwrap();
+    let verifications = document
+        .verify_signatures(&VerificationOptions::default())
+        .unwrap();
+    assert_eq!(verifications.len(), 1);
+    assert_eq!(verifications[0].field_name, "Signature1");
+    assert!(verifications[0].byte_range_valid);
+    assert!(verifications[0].covers_whole_document);
+    assert_eq!(verifications[0].signature_valid, Some(false));
+    assert!(!verifications[0].is_valid());
+}
+
+#[test]
+fn signature_with_trusted_certificate_is_valid() {
+    let data = sign(&build_form_pdf(), SubFilter::Pkcs7Detached);
+    let options = VerificationOptions::builder()
+        .trust_store(trust_store(ROOT_CERTIFICATE))
+        .build();
+    let verifications = verify(&data, &options);
+    assert_eq!(verifications.len(), 1);
+    assert_eq!(verifications[0].field_name, "Signature1");
+    assert_eq!(verifications[0].signature_valid, Some(true));
+    assert_eq!(verifications[0].chain_trusted, Some(true));
+    assert_eq!(verifications[0].status, VerificationStatus::Valid);
+    assert!(verifications[0].is_valid());
+}
+
+#[test]
+fn signature_without_trust_store_is_indeterminate() {
+    let data = sign(&build_form_pdf(), SubFilter::Pkcs7Detached);
+    let verifications = verify(&data, &VerificationOptions::default());
+    assert_eq!(verifications[0].signature_valid, Some(true));
+    assert_eq!(verifications[0].chain_trusted, None);
+    assert_eq!(verifications[0].status, VerificationStatus::Indeterminate);
+}
+
+#[test]
+fn changed_signed_bytes_are_invalid() {
+    let mut data = sign(&build_form_pdf(), SubFilter::Pkcs7Detached);
+    let position = find(&data, b"Hello world");
+    data[position] = b'J';
+    let options = VerificationOptions::builder()
+        .trust_store(trust_store(ROOT_CERTIFICATE))
+        .build();
+    let verifications = verify(&data, &options);
+    assert!(verifications[0].byte_range_valid);
+    assert_eq!(verifications[0].signature_valid, Some(false));
+    assert_eq!(verifications[0].status, VerificationStatus::Invalid);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn verification_report_is_serialized() {
+    let data = sign(&build_form_pdf(), SubFilter::Pkcs7Detached);
+    let mut document = PDFSigningDocument::read_from(&*data, "signed.pdf".to_owned()).unwrap();
+    let report = document
+        .verification_report(&VerificationOptions::default())
+        .unwrap();
+    assert_eq!(report.status, VerificationStatus::Indeterminate);
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["fileName"], "signed.pdf");
+    assert_eq!(json["status"], "indeterminate");
+    assert_eq!(json["signatures"][0]["fieldName"], "Signature1");
+    assert_eq!(json["signatures"][0]["signatureValid"], true);
+}
+
+#[test]
+fn signer_certificate_details_are_read() {
+    let data = sign(&build_form_pdf(), SubFilter::Pkcs7Detached);
+    let verifications = verify(&data, &VerificationOptions::default());
+    let certificate = verifications[0].signer_certificate.as_ref().unwrap();
+    assert_eq!(certificate.subject, "CN=Test Signer, O=pdf-rs tests");
+    assert_eq!(certificate.common_name.as_deref(), Some("Test Signer"));
+    assert_eq!(certificate.issuer, "CN=Test Root CA, O=pdf-rs tests");
+    assert_eq!(certificate.serial_number, "1234");
+    assert!(certificate.not_before < certificate.not_after);
+    assert_eq!(
+        certificate.key_usage,
+        vec![KeyUsage::DigitalSignature, KeyUsage::NonRepudiation]
+    );
+    assert_eq!(certificate.emails, vec!["signer@example.com".to_owned()]);
+}
+
+#[test]
+fn certificate_with_length_that_is_not_minimal_is_rejected() {
+    let certificate = CapturedX509Certificate::from_pem(SIGNER_CERTIFICATE).unwrap();
+    let data = certificate.constructed_data();
+    assert!(CertificateDetails::from_der(data).is_ok());
+    // The length of the outer `SEQUENCE` with one byte more (`0x83 0x00 ..`).
+    assert_eq!(data[1], 0x82);
+    let mut long_length = vec![data[0], 0x83, 0x00];
+    long_length.extend_from_slice(&data[2..]);
+    assert!(CertificateDetails::from_der(&long_length).is_err());
+}
+
+#[test]
+fn certificate_with_indefinite_length_is_rejected() {
+    let certificate = CapturedX509Certificate::from_pem(SIGNER_CERTIFICATE).unwrap();
+    let data = certificate.constructed_data();
+    let mut indefinite_length = vec![data[0], 0x80];
+    indefinite_length.extend_from_slice(&data[4..]);
+    indefinite_length.extend_from_slice(&[0x00, 0x00]);
+    assert!(CertificateDetails::from_der(&indefinite_length).is_err());
+}
+
+#[test]
+fn signature_with_certificate_of_other_root_is_indeterminate() {
+    let data = sign(&build_form_pdf(), SubFilter::Pkcs7Detached);
+    let options = VerificationOptions::builder()
+        .trust_store(trust_store(OTHER_ROOT_CERTIFICATE))
+        .build();
+    let verifications = verify(&data, &options);
+    assert_eq!(verifications[0].signature_valid, Some(true));
+    assert_eq!(verifications[0].chain_trusted, Some(false));
+    assert_eq!(verifications[0].status, VerificationStatus::Indeterminate);
+}
+
+#[test]
+fn signer_certificate_in_trust_store_is_trusted() {
+    let data = sign(&build_form_pdf(), SubFilter::Pkcs7Detached);
+    let options = VerificationOptions::builder()
+        .trust_store(trust_store(OTHER_ROOT_CERTIFICATE))
+        .trust_store(trust_store(SIGNER_CERTIFICATE))
+        .build();
+    let verifications = verify(&data, &options);
+    assert_eq!(verifications[0].chain_trusted, Some(true));
+    assert_eq!(verifications[0].status, VerificationStatus::Valid);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+fn certificates_are_read_from_pem_file() {
+    let data = sign(&build_form_pdf(), SubFilter::Pkcs7Detached);
+    let path = std::env::temp_dir().join(format!("pdf-rs-roots-{}.pem", std::process::id()));
+    std::fs::write(
+        &path,
+        format!("{}{}", OTHER_ROOT_CERTIFICATE, ROOT_CERTIFICATE),
+    )
+    .unwrap();
+    let options = VerificationOptions::builder()
+        .trust_store(TrustStore::PemFile(path.clone()))
+        .build();
+    let verifications = verify(&data, &options);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(verifications[0].chain_trusted, Some(true));
+    assert_eq!(verifications[0].status, VerificationStatus::Valid);
+}
+
+#[test]
+fn invalid_trust_store_is_an_error() {
+    let data = sign(&build_form_pdf(), SubFilter::Pkcs7Detached);
+    let options = VerificationOptions::builder()
+        .trust_store(TrustStore::Der(vec![b"not a certificate".to_vec()]))
+        .build();
+    let mut document = PDFSigningDocument::read_from(&*data, "signed.pdf".to_owned()).unwrap();
+    assert!(document.verify_signatures(&options).is_err());
+}
+
+#[test]
+fn signature_issued_by_granted_service_is_valid() {
+    let data = sign(&build_form_pdf(), SubFilter::Pkcs7Detached);
+    let options = VerificationOptions::builder()
+        .trusted_list(trusted_list("granted"))
+        .build();
+    let verifications = verify(&data, &options);
+    assert_eq!(
+        verifications[0].trusted_list,
+        Some(TrustedListStatus {
+            territory: Some("BE".to_owned()),
+            provider_name: Some("Test Provider".to_owned()),
+            service_name: Some("Test CA".to_owned()),
+            granted: true,
+            // The signer certificate has no `QcCompliance` statement.
+            qualified: false,
+        })
+    );
+    assert_eq!(verifications[0].chain_trusted, None);
+    assert_eq!(verifications[0].status, VerificationStatus::Valid);
+}
+
+#[test]
+fn signature_issued_by_withdrawn_service_is_indeterminate() {
+    let data = sign(&build_form_pdf(), SubFilter::Pkcs7Detached);
+    let options = VerificationOptions::builder()
+        .trusted_list(trusted_list("withdrawn"))
+        .build();
+    let verifications = verify(&data, &options);
+    let trusted_list = verifications[0].trusted_list.as_ref().unwrap();
+    assert_eq!(trusted_list.service_name.as_deref(), Some("Test CA"));
+    assert!(!trusted_list.granted);
+    assert_eq!(verifications[0].status, VerificationStatus::Indeterminate);
+}
+
+#[test]
+fn pades_signature_without_time_stamp_is_baseline_b() {
+    let data = sign(&build_form_pdf(), SubFilter::CadesDetached);
+    let verifications = verify(&data, &VerificationOptions::default());
+    assert_eq!(
+        verifications[0].sub_filter.as_deref(),
+        Some("ETSI.CAdES.detached")
+    );
+    assert_eq!(verifications[0].signature_valid, Some(true));
+    assert_eq!(verifications[0].ades_level, Some(AdesLevel::BaselineB));
+}
+
+#[test]
+fn cms_signature_has_no_ades_level() {
+    let data = sign(&build_form_pdf(), SubFilter::Pkcs7Detached);
+    let verifications = verify(&data, &VerificationOptions::default());
+    assert_eq!(verifications[0].ades_level, None);
+}
+
+#[test]
+fn signature_without_time_stamp_has_no_time_stamp() {
+    let data = sign(&build_form_pdf(), SubFilter::Pkcs7Detached);
+    let verifications = verify(&data, &VerificationOptions::default());
+    assert_eq!(verifications[0].time_stamp, None);
+}
+
+#[test]
+fn document_time_stamp_with_invalid_token_is_invalid() {
+    // A `SEQUENCE` with an `INTEGER`, not a time stamp token.
+    let data = build_signature_pdf("/Type /DocTimeStamp /SubFilter /ETSI.RFC3161", "3003020100");
+    let verifications = verify(&data, &VerificationOptions::default());
+    assert_eq!(verifications.len(), 1);
+    assert_eq!(verifications[0].sub_filter.as_deref(), Some("ETSI.RFC3161"));
+    assert!(verifications[0].byte_range_valid);
+    assert_eq!(verifications[0].signature_valid, Some(false));
+    assert_eq!(verifications[0].time_stamp, None);
+    assert_eq!(verifications[0].status, VerificationStatus::Invalid);
+}
+
+#[test]
+fn byte_range_of_whole_file_is_valid() {
+    let data = build_signed_pdf("3000");
+    let verifications = verify(&data, &VerificationOptions::default());
+    assert!(verifications[0].byte_range_valid);
+    assert_eq!(verifications[0].byte_range_issue, None);
+    assert!(verifications[0].covers_whole_document);
+    assert_eq!(verifications[0].later_revisions, 0);
+}
+
+#[test]
+fn revisions_after_signature_are_counted() {
+    let data = build_signed_pdf("3000");
+    let mut document = PDFSigningDocument::read_from(&*data, "signed.pdf".to_owned()).unwrap();
+    let bookmark = Bookmark {
+        title: "Start".to_owned(),
+        page_index: Some(0),
+        open: false,
+        children: vec![],
+    };
+    document.set_outline(&[bookmark]).unwrap();
+    let saved = document.save_to_bytes().unwrap();
+    assert!(saved.starts_with(&data));
+
+    let verifications = verify(&saved, &VerificationOptions::default());
+    assert!(verifications[0].byte_range_valid);
+    assert_eq!(verifications[0].revision, Some(0));
+    assert_eq!(verifications[0].later_revisions, 1);
+    assert!(!verifications[0].covers_whole_document);
+    assert_eq!(verifications[0].covered_length, data.len() as u64);
+    assert_eq!(verifications[0].file_length, saved.len() as u64);
+}
+
+#[test]
+fn byte_range_that_ends_in_revision_is_partial_coverage() {
+    let mut data = build_signed_pdf("3000");
+    let [first_length, second_start, second_length] = get_byte_range(&data);
+    set_byte_range(&mut data, [first_length, second_start, second_length - 10]);
+    let verifications = verify(&data, &VerificationOptions::default());
+    assert!(!verifications[0].byte_range_valid);
+    assert_eq!(
+        verifications[0].byte_range_issue,
+        Some(ByteRangeIssue::PartialCoverage)
+    );
+    assert_eq!(verifications[0].status, VerificationStatus::Invalid);
+}
+
+#[test]
+fn byte_range_with_gap_around_other_bytes_is_rejected() {
+    let mut data = build_signed_pdf("3000");
+    let [first_length, second_start, second_length] = get_byte_range(&data);
+    // The gap starts before the `<` of the `Contents`.
+    set_byte_range(&mut data, [first_length - 1, second_start, second_length]);
+    let verifications = verify(&data, &VerificationOptions::default());
+    assert_eq!(
+        verifications[0].byte_range_issue,
+        Some(ByteRangeIssue::GapNotContents)
+    );
+    assert_eq!(verifications[0].status, VerificationStatus::Invalid);
+}
+
+#[test]
+fn byte_range_past_end_of_file_is_malformed() {
+    let mut data = build_signed_pdf("3000");
+    let [first_length, second_start, second_length] = get_byte_range(&data);
+    set_byte_range(&mut data, [first_length, second_start, second_length + 1]);
+    let verifications = verify(&data, &VerificationOptions::default());
+    assert_eq!(
+        verifications[0].byte_range_issue,
+        Some(ByteRangeIssue::Malformed)
+    );
+    assert_eq!(verifications[0].status, VerificationStatus::Invalid);
+}
+
+#[test]
+fn truncated_contents_is_invalid() {
+    // The DER length (5) is longer than the `Contents` (2 bytes).
+    let data = build_signed_pdf("3005");
+    let mut document = PDFSigningDocument::read_from(&*data, "truncated.pdf".to_owned()).unwrap();
+    let verifications = document
+        .verify_signatures(&VerificationOptions::default())
+        .unwrap();
+    assert_eq!(verifications.len(), 1);
+    assert!(verifications[0].byte_range_valid);
+    assert_eq!(verifications[0].signature_valid, Some(false));
+    assert_eq!(verifications[0].status, VerificationStatus::Invalid);
+}
+
+#[test]
+fn long_form_length_past_contents_is_invalid() {
+    let data = build_signed_pdf("3082ffff0000");
+    let mut document = PDFSigningDocument::read_from(&*data, "truncated.pdf".to_owned()).unwrap();
+    let verifications = document
+        .verify_signatures(&VerificationOptions::default())
+        .unwrap();
+    assert_eq!(verifications[0].status, VerificationStatus::Invalid);
+}
@@ -0,0 +1,45 @@
+//! Watermarks stamped on every page.
+
+mod common;
+
+use common::build_form_pdf;
+use pdf_rs::{JournalChange, PDFSigningDocument, Watermark, WatermarkOptions};
+
+#[test]
+fn inserted_page_gets_watermark() {
+    let data = build_form_pdf();
+    let mut document = PDFSigningDocument::read_from(&*data, "form.pdf".to_owned()).unwrap();
+    document.insert_blank_page(1, None).unwrap();
+    document
+        .add_watermark(
+            &Watermark::Text("DRAFT".to_owned()),
+            &WatermarkOptions::default(),
+        )
+        .unwrap();
+    assert_eq!(
+        document.get_journal().last().unwrap().change,
+        JournalChange::WatermarkAdded {
+            page_indexes: vec![0, 1]
+        }
+    );
+    assert!(document.save_to_bytes().is_ok());
+}
+
+#[test]
+fn svg_watermark_needs_allow_svg() {
+    let data = build_form_pdf();
+    let mut document = PDFSigningDocument::read_from(&*data, "form.pdf".to_owned()).unwrap();
+    let watermark = Watermark::Image(
+        b"<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"10\" height=\"10\">\
+          <rect width=\"10\" height=\"10\"/></svg>"
+            .to_vec(),
+    );
+    assert!(document
+        .add_watermark(&watermark, &WatermarkOptions::default())
+        .is_err());
+    let options = WatermarkOptions {
+        allow_svg: true,
+        ..WatermarkOptions::default()
+    };
+    assert!(document.add_watermark(&watermark, &options).is_ok());
+}